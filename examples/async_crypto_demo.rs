@@ -64,10 +64,11 @@ fn demo_async_encryption(test_dir: &str) -> Result<(), Box<dyn std::error::Error
         encrypt_filename: false,
         delete_source: false,
         file_extension: "async_enc".to_string(),
+        ..Default::default()
     };
     
     // 加载文件
-    let files = FileManager::load_files_from_directory(test_dir);
+    let files = FileManager::load_files_from_directory(test_dir, &settings);
     let mut selected_files = files.clone();
     
     // 选择所有文件进行加密
@@ -136,10 +137,11 @@ fn demo_progress_reporting(test_dir: &str) -> Result<(), Box<dyn std::error::Err
         encrypt_filename: false,
         delete_source: false,
         file_extension: "progress_enc".to_string(),
+        ..Default::default()
     };
     
     // 加载文件
-    let files = FileManager::load_files_from_directory(test_dir);
+    let files = FileManager::load_files_from_directory(test_dir, &settings);
     let mut selected_files = files.clone();
     
     // 选择所有文件进行加密
@@ -192,10 +194,11 @@ fn demo_operation_cancellation(test_dir: &str) -> Result<(), Box<dyn std::error:
         encrypt_filename: false,
         delete_source: false,
         file_extension: "cancel_enc".to_string(),
+        ..Default::default()
     };
     
     // 加载文件
-    let files = FileManager::load_files_from_directory(test_dir);
+    let files = FileManager::load_files_from_directory(test_dir, &settings);
     let mut selected_files = files.clone();
     
     // 选择所有文件进行加密
@@ -1,6 +1,5 @@
 use krypton::core::FileManager;
-use krypton::crypto::{CryptoEngine, create_crypto_provider, encrypt_stream, decrypt_stream};
-use krypton::crypto::traits::CryptoProvider;
+use krypton::crypto::{CryptoEngine, StreamControl, create_crypto_provider, encrypt_stream, decrypt_stream};
 use krypton::models::{Settings, OperationMode, EncryptionAlgorithm};
 use std::fs;
 use std::io::Cursor;
@@ -34,6 +33,7 @@ fn demo_strategy_pattern() -> Result<(), Box<dyn std::error::Error>> {
     let algorithms = vec![
         EncryptionAlgorithm::AES256,
         EncryptionAlgorithm::ChaCha20,
+        EncryptionAlgorithm::XChaCha20,
     ];
     
     for algorithm in algorithms {
@@ -48,7 +48,7 @@ fn demo_strategy_pattern() -> Result<(), Box<dyn std::error::Error>> {
         let mut input = Cursor::new(test_data.as_bytes());
         let mut encrypted = Vec::new();
         
-        provider.encrypt_stream(password, &mut input, &mut encrypted)?;
+        provider.encrypt_stream(password, &mut input, &mut encrypted, &StreamControl::default())?;
         println!("  - 原始数据: {} 字节", test_data.len());
         println!("  - 加密数据: {} 字节", encrypted.len());
         
@@ -56,7 +56,7 @@ fn demo_strategy_pattern() -> Result<(), Box<dyn std::error::Error>> {
         let mut encrypted_input = Cursor::new(&encrypted);
         let mut decrypted = Vec::new();
         
-        provider.decrypt_stream(password, &mut encrypted_input, &mut decrypted)?;
+        provider.decrypt_stream(password, &mut encrypted_input, &mut decrypted, &StreamControl::default())?;
         let decrypted_text = String::from_utf8(decrypted)?;
         
         println!("  - 解密成功: {}", decrypted_text == test_data);
@@ -118,7 +118,8 @@ fn demo_full_encryption_workflow() -> Result<(), Box<dyn std::error::Error>> {
     
     // 测试不同的加密算法
     let algorithms = [EncryptionAlgorithm::AES256,
-        EncryptionAlgorithm::ChaCha20];
+        EncryptionAlgorithm::ChaCha20,
+        EncryptionAlgorithm::XChaCha20];
     
     for (i, algorithm) in algorithms.iter().enumerate() {
         println!("\n🔐 测试算法 {}: {:?}", i + 1, algorithm);
@@ -138,7 +139,7 @@ fn demo_full_encryption_workflow() -> Result<(), Box<dyn std::error::Error>> {
         println!("  📊 {}", info);
         
         // 加载文件
-        let files = FileManager::load_files_from_directory(test_dir);
+        let files = FileManager::load_files_from_directory(test_dir, &settings);
         let mut selected_files = files.clone();
         
         // 选择所有文件进行加密
@@ -182,7 +183,7 @@ fn demo_full_encryption_workflow() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // 显示最终文件统计
-    let final_files = FileManager::load_files_from_directory(test_dir);
+    let final_files = FileManager::load_files_from_directory(test_dir, &Settings::default());
     println!("\n📋 最终文件统计:");
     println!("  - 总文件数: {}", final_files.len());
     
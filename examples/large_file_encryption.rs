@@ -38,7 +38,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // 加载文件
-    let files = FileManager::load_files_from_directory(test_dir);
+    let files = FileManager::load_files_from_directory(test_dir, &settings);
     println!("找到 {} 个文件:", files.len());
     for file in &files {
         println!("- {}", file.name);
@@ -92,7 +92,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("✅ 解密完成！耗时: {:?}", decrypt_duration);
                     
                     // 验证解密后的文件
-                    let final_files = FileManager::load_files_from_directory(test_dir);
+                    let final_files = FileManager::load_files_from_directory(test_dir, &settings);
                     println!("最终文件列表:");
                     for file in &final_files {
                         println!("- {}", file.name);
@@ -60,10 +60,11 @@ fn test_with_thread_count(test_dir: &str, thread_count: u32) -> Result<(), Box<d
         encrypt_filename: false,
         delete_source: false,
         file_extension: "enc".to_string(),
+        ..Default::default()
     };
     
     // 加载文件
-    let mut files = FileManager::load_files_from_directory(test_dir);
+    let mut files = FileManager::load_files_from_directory(test_dir, &settings);
     for file in &mut files {
         file.selected = true;
     }
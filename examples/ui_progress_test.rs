@@ -60,10 +60,11 @@ fn test_async_progress_updates(test_dir: &str) -> Result<(), Box<dyn std::error:
         encrypt_filename: false,
         delete_source: false,
         file_extension: "uitest".to_string(),
+        ..Default::default()
     };
     
     // 加载文件
-    let files = FileManager::load_files_from_directory(test_dir);
+    let files = FileManager::load_files_from_directory(test_dir, &settings);
     let mut selected_files = files.clone();
     
     // 选择所有文件进行加密
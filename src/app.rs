@@ -1,173 +1,2131 @@
 use eframe::egui;
-use crate::models::{OperationMode, FileItem, AppState, Settings, FileManagerState, ProgressState, DialogState, OperationHandle, ProgressInfo, ProgressCallback};
+use crate::models::{OperationMode, ConflictPolicy, FileItem, FileResult, AppState, ActiveTab, Settings, FileManagerState, ProgressState, OperationView, DialogState, ToolsState, NotesState, AuditState, SchedulerState, WatchState, PreviewState, VaultState, BackupState, BackupMessage, MigrationState, MigrationMessage, OperationEvent, OperationStatus, ShareLogin, ConfigLockState, ApiState};
 use crate::core::FileManager;
-use crate::crypto::CryptoEngine;
-use crate::ui::{SettingsPanel, FilePanel, ProgressPanel, ControlPanel, ErrorDialog, CompleteDialog, PanelEvent, DialogEvent};
+use crate::core::api::{ApiConfig, ApiRequest, ApiResponse, ApiServer, JobReport, JobSubmission};
+use crate::core::app_data;
+use crate::core::backup::{self, BackupBackend, BackupManifest, LocalBackend};
+use crate::core::dir_settings::DirectorySettings;
+use crate::core::notes::{Note, NoteStore};
+use crate::core::job::{JobDefinition, JOB_FILE_EXTENSION};
+use crate::core::journal::Journal;
+use crate::core::migrate;
+use crate::core::name_index::FilenameIndex;
+use crate::core::network;
+use crate::core::encrypted_view::EncryptedFolderView;
+use crate::core::preflight;
+use crate::core::preview::FilePreview;
+use crate::core::quarantine::Quarantine;
+use crate::core::scheduler::{Schedule, ScheduledJob};
+use crate::core::shred;
+use crate::core::watch::FolderWatcher;
+use crate::core::tpm::{self, SealedKeyStore};
+use crate::crypto::{CryptoEngine, StreamControl, create_crypto_provider};
+use crate::crypto::traits::Argon2KeyDerivation;
+use crate::crypto::armor;
+use crate::crypto::audit;
+use crate::crypto::header::FileHeader;
+use crate::crypto::keys::Identity;
+use crate::crypto::vault::{Vault, VAULT_FILE_EXTENSION};
+use crate::progress::ProgressFormatter;
+use crate::ui::{SettingsPanel, FilePanel, ProgressPanel, MiniProgressWindow, OperationTabs, CommandPalette, CommandPaletteState, PaletteAction, ControlPanel, ToolsPanel, NotesPanel, ErrorDialog, CompleteDialog, PreflightDialog, PreflightEvent, DryRunDialog, DryRunEvent, ResumeDialog, ResumeEvent, ConflictPromptDialog, ConflictPromptEvent, InfoDialog, AuditDialog, PasswordPromptDialog, ShareLoginDialog, ShareLoginEvent, ConfigUnlockDialog, ConfigUnlockEvent, SchedulePanel, PreviewPanel, PanelEvent, DialogEvent, PasswordPromptEvent, ScheduleEvent, WatchPanel, WatchEvent, BackupPanel, BackupEvent, VaultPanel, VaultEvent, ToolsEvent, NotesEvent};
+use crate::ui::theme;
 use rfd::FileDialog;
-use std::sync::Arc;
+use std::time::Duration;
+
+/// 监视文件夹的轮询间隔
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 pub struct KryptonApp {
     // 应用设置
     settings: Settings,
 
-    // 文件管理状态
-    file_manager: FileManagerState,
+    // 文件管理状态
+    file_manager: FileManagerState,
+
+    // 批处理标签页，每个有自己的操作句柄和进度，可以同时运行
+    operations: Vec<OperationView>,
+    active_operation: usize,
+
+    // 对话框状态
+    dialog: DialogState,
+
+    // 当前页面
+    active_tab: ActiveTab,
+
+    // 工具页状态
+    tools: ToolsState,
+    migration: MigrationState,
+
+    // 配置文件加密状态
+    config_lock: ConfigLockState,
+
+    // 本机控制接口
+    api: ApiState,
+
+    // 加密笔记页状态
+    notes: NotesState,
+
+    // 目录审计状态
+    audit: AuditState,
+
+    // 计划任务状态
+    scheduler: SchedulerState,
+
+    // 监视文件夹状态
+    watch: WatchState,
+
+    // 文件预览窗格状态
+    preview: PreviewState,
+
+    // 每个目录上次使用的设置
+    directory_settings: DirectorySettings,
+
+    // 加密容器页状态
+    backup: BackupState,
+    vault: VaultState,
+
+    // 是否显示置顶的迷你进度窗口
+    mini_progress: bool,
+
+    // Ctrl+P命令面板
+    palette: CommandPaletteState,
+}
+
+impl Default for KryptonApp {
+    fn default() -> Self {
+        Self {
+            settings: Settings::default(),
+            file_manager: FileManagerState::default(),
+            operations: vec![OperationView::default()],
+            active_operation: 0,
+            dialog: DialogState::default(),
+            active_tab: ActiveTab::Files,
+            tools: ToolsState::default(),
+            migration: MigrationState::default(),
+            config_lock: ConfigLockState::default(),
+            api: ApiState::default(),
+            notes: NotesState::default(),
+            audit: AuditState::default(),
+            scheduler: SchedulerState::default(),
+            watch: WatchState::default(),
+            preview: PreviewState::default(),
+            directory_settings: DirectorySettings::default(),
+            backup: BackupState::default(),
+            vault: VaultState::default(),
+            mini_progress: false,
+            palette: CommandPaletteState::default(),
+        }
+    }
+}
+
+impl KryptonApp {
+    pub fn new() -> Self {
+        let mut app = Self::default();
+        app.config_lock.encrypted = app_data::config_encrypted();
+        if app_data::unlock_config_from_env() {
+            app.load_app_data();
+        } else {
+            // 配置已加密，先询问主密码再加载
+            app.config_lock.locked = true;
+            app.config_lock.show_unlock = true;
+        }
+        app.vault.tpm_available = tpm::tpm_available();
+        app
+    }
+
+    /// 加载保存在应用数据目录中的计划任务和目录设置
+    fn load_app_data(&mut self) {
+        match Schedule::load() {
+            Ok(schedule) => self.scheduler.schedule = schedule,
+            Err(e) => self.scheduler.set_status(format!("Failed to load schedule: {}", e), true),
+        }
+        match DirectorySettings::load() {
+            Ok(directory_settings) => self.directory_settings = directory_settings,
+            Err(e) => eprintln!("Failed to load directory settings: {}", e),
+        }
+        match ApiConfig::load() {
+            Ok(config) => {
+                self.api.pending_start = config.enabled;
+                self.api.config = config;
+            }
+            Err(e) => self.api.set_status(format!("Failed to load the API settings: {}", e), true),
+        }
+        match Journal::unfinished() {
+            Ok(journals) => {
+                self.dialog.show_resume_dialog = !journals.is_empty();
+                self.dialog.interrupted = journals;
+            }
+            Err(e) => eprintln!("Failed to look for interrupted batches: {}", e),
+        }
+    }
+
+    /// 用输入的主密码解锁配置，成功后加载配置
+    fn handle_config_unlock(&mut self, event: ConfigUnlockEvent) {
+        if event == ConfigUnlockEvent::Skip {
+            self.config_lock.show_unlock = false;
+            self.config_lock.unlock_password.clear();
+            self.config_lock.unlock_error = None;
+            return;
+        }
+        let result = app_data::unlock_config(&self.config_lock.unlock_password);
+        self.config_lock.unlock_password.clear();
+        if let Err(e) = result {
+            self.config_lock.unlock_error = Some(e.to_string());
+            return;
+        }
+        self.config_lock.locked = false;
+        self.config_lock.show_unlock = false;
+        self.config_lock.unlock_error = None;
+        self.load_app_data();
+    }
+
+    /// 用新的主密码加密配置文件（已加密时更换密码）
+    fn encrypt_config(&mut self) {
+        let config_lock = &mut self.config_lock;
+        if config_lock.new_password != config_lock.confirm_password {
+            config_lock.set_status("The passwords do not match", true);
+            return;
+        }
+        let result = app_data::encrypt_config(&config_lock.new_password);
+        config_lock.new_password.clear();
+        config_lock.confirm_password.clear();
+        match result {
+            Ok(()) => {
+                let message = if config_lock.encrypted { "Master password changed" } else { "Configuration encrypted" };
+                config_lock.encrypted = true;
+                config_lock.set_status(message, false);
+            }
+            Err(e) => config_lock.set_status(format!("Failed to encrypt the configuration: {}", e), true),
+        }
+    }
+
+    fn decrypt_config(&mut self) {
+        match app_data::decrypt_config() {
+            Ok(()) => {
+                self.config_lock.encrypted = false;
+                self.config_lock.set_status("Configuration encryption removed", false);
+            }
+            Err(e) => self.config_lock.set_status(format!("Failed to decrypt the configuration: {}", e), true),
+        }
+    }
+    
+    fn load_left_files(&mut self) {
+        if self.file_manager.loaded_left_directory != self.file_manager.left_directory {
+            self.file_manager.loaded_left_directory = self.file_manager.left_directory.clone();
+            self.restore_directory_settings(&self.file_manager.left_directory.clone());
+        }
+        let result = FileManager::try_load_files_from_directory(&self.file_manager.left_directory, &self.settings);
+        self.file_manager.left_shred_limitations = shred::limitations(std::path::Path::new(&self.file_manager.left_directory));
+        (self.file_manager.left_files, self.file_manager.left_error) = match result {
+            Ok(files) => (files, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        self.file_manager.details.clear();
+        self.close_preview();
+    }
+
+    /// 打开目录时恢复该目录上次使用的扩展名、算法和文件名加密选项
+    fn restore_directory_settings(&mut self, directory: &str) {
+        if self.active_operation().state != AppState::Idle || directory.is_empty() {
+            return;
+        }
+        if let Some(preset) = self.directory_settings.get(std::path::Path::new(directory)) {
+            preset.apply(&mut self.settings);
+        }
+    }
+
+    /// 记录本次操作的目录所使用的设置
+    fn remember_directory_settings(&mut self) {
+        let directory = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => &self.file_manager.left_directory,
+            OperationMode::Decrypt | OperationMode::Verify => &self.file_manager.right_directory,
+        };
+        if directory.is_empty() {
+            return;
+        }
+        self.directory_settings.remember(std::path::Path::new(directory), &self.settings);
+        if let Err(e) = self.directory_settings.save() {
+            eprintln!("Failed to save directory settings: {}", e);
+        }
+    }
+
+    /// 在预览窗格中显示左侧列表中的文件
+    fn preview_file(&mut self, index: usize) {
+        let Some(file) = self.file_manager.left_files.get(index) else {
+            return;
+        };
+        self.preview = match FilePreview::load(&file.path) {
+            Ok(preview) => PreviewState { preview: Some(preview), ..Default::default() },
+            Err(e) => PreviewState { error: Some(format!("Cannot preview '{}': {}", file.name, e)), ..Default::default() },
+        };
+        self.file_manager.preview_index = Some(index);
+        self.file_manager.encrypted_preview_index = None;
+    }
+
+    /// 在内存中解密右侧列表中的文件并预览，不写出明文文件
+    fn preview_encrypted_file(&mut self, index: usize) {
+        let Some(file) = self.file_manager.right_files.get(index).cloned() else {
+            return;
+        };
+        if self.settings.password.is_empty() {
+            self.dialog.show_error("Password cannot be empty");
+            return;
+        }
+
+        let directory = std::path::PathBuf::from(&self.file_manager.right_directory);
+        let view = EncryptedFolderView::open(&directory, &self.settings);
+        let result = match view.find(&file.path) {
+            Some(entry) => view.open_entry(entry)
+                .map_err(|e| e.to_string())
+                .and_then(|reader| {
+                    FilePreview::from_reader(std::path::Path::new(&entry.name), entry.size, reader)
+                        .map_err(|e| e.to_string())
+                }),
+            None => Err("file is not part of the encrypted folder".to_string()),
+        };
+
+        self.close_preview();
+        self.preview = match result {
+            Ok(preview) => PreviewState { preview: Some(preview), decrypted: true, ..Default::default() },
+            Err(e) => PreviewState { error: Some(format!("Cannot preview '{}': {}", file.name, e)), ..Default::default() },
+        };
+        self.file_manager.encrypted_preview_index = Some(index);
+    }
+
+    fn close_preview(&mut self) {
+        self.preview = PreviewState::default();
+        self.file_manager.preview_index = None;
+        self.file_manager.encrypted_preview_index = None;
+    }
+    
+    fn load_right_files(&mut self) {
+        // 先恢复设置，扩展名会影响加密文件的筛选
+        if self.file_manager.loaded_right_directory != self.file_manager.right_directory {
+            self.file_manager.loaded_right_directory = self.file_manager.right_directory.clone();
+            self.restore_directory_settings(&self.file_manager.right_directory.clone());
+        }
+        let result = FileManager::try_load_encrypted_files_from_directory(&self.file_manager.right_directory, &self.settings);
+        self.file_manager.right_shred_limitations = shred::limitations(std::path::Path::new(&self.file_manager.right_directory));
+        (self.file_manager.right_files, self.file_manager.right_error) = match result {
+            Ok(files) => (files, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        self.file_manager.details.clear();
+        if self.file_manager.encrypted_preview_index.is_some() {
+            self.close_preview();
+        }
+        self.start_integrity_check();
+    }
+
+    /// 连接需要登录的网络共享，成功后重新加载文件列表
+    fn handle_share_login(&mut self, event: ShareLoginEvent) {
+        let Some(login) = self.dialog.share_login.as_mut() else {
+            return;
+        };
+        if event == ShareLoginEvent::Cancel {
+            self.dialog.share_login = None;
+            return;
+        }
+        let result = network::connect_share(&login.share, &login.user, &login.password);
+        login.password.clear();
+        if let Err(e) = result {
+            login.error = Some(e);
+            return;
+        }
+        self.dialog.share_login = None;
+        if self.file_manager.left_error.is_some() {
+            self.load_left_files();
+        }
+        if self.file_manager.right_error.is_some() {
+            self.load_right_files();
+        }
+    }
+
+    /// 在后台快速检查右侧的每个文件，结果逐个显示为文件旁的标记
+    fn start_integrity_check(&mut self) {
+        let files = self.file_manager.right_files.clone();
+        let algorithm = self.settings.encryption_algorithm.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            for file in files {
+                let badge = audit::quick_check(&file, file.algorithm.as_ref().unwrap_or(&algorithm));
+                // 接收端已被新的检查替换时停止
+                if sender.send((file.path, badge)).is_err() {
+                    break;
+                }
+            }
+        });
+        self.file_manager.integrity.clear();
+        self.file_manager.integrity_receiver = Some(receiver);
+    }
+
+    /// 接收后台完整性检查的结果
+    fn check_integrity_status(&mut self) {
+        let Some(receiver) = &self.file_manager.integrity_receiver else {
+            return;
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok((path, badge)) => {
+                    self.file_manager.integrity.insert(path, badge);
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.file_manager.integrity_receiver = None;
+                    break;
+                }
+            }
+        }
+    }
+    
+    /// 当前无法开始操作的原因（显示在Start按钮旁）
+    fn start_blockers(&self) -> Vec<String> {
+        let mut blockers = Vec::new();
+        if let Some(error) = self.settings.key_error() {
+            blockers.push(error);
+        }
+        let files = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => &self.file_manager.left_files,
+            OperationMode::Decrypt | OperationMode::Verify => &self.file_manager.right_files,
+        };
+        if !files.iter().any(|file| file.selected) {
+            blockers.push("No files selected".to_string());
+        }
+        if let Some(error) = self.settings.extension_error() {
+            blockers.push(error);
+        }
+        if let Some(error) = self.settings.without_inactive_options().conflict_error() {
+            blockers.push(error.to_string());
+        }
+        blockers
+    }
+
+    fn start_operation(&mut self) {
+        // Get selected files based on operation mode
+        let selected_files: Vec<FileItem> = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => self.file_manager.left_files.iter()
+                .filter(|f| f.selected)
+                .cloned()
+                .collect(),
+            OperationMode::Decrypt | OperationMode::Verify => self.file_manager.right_files.iter()
+                .filter(|f| f.selected)
+                .cloned()
+                .collect(),
+        };
+
+        self.remember_directory_settings();
+        let index = self.active_operation;
+        // 手动启动的批处理在完成对话框中提供撤销删除
+        let settings = Settings { undo_delete: true, ..self.settings.clone() };
+        if let Err(error) = self.start_batch(index, settings, selected_files) {
+            self.dialog.show_error(error);
+        }
+    }
+
+    /// 检查能否开始，输出与已有文件冲突时先显示输出预览（`review` 为false时直接开始）
+    fn request_start(&mut self, review: bool) {
+        let blockers = self.start_blockers();
+        if !blockers.is_empty() {
+            self.dialog.show_error(blockers.join("\n"));
+            return;
+        }
+        if self.settings.conflict_policy == ConflictPolicy::Ask {
+            // 逐个询问还没有决定如何处理的已有输出，全部回答后再开始
+            let files = self.operation_files();
+            let prompts: Vec<_> = preflight::plan_outputs(&self.settings, files).into_iter()
+                .filter(|planned| planned.exists && files[planned.index].conflict.is_none())
+                .collect();
+            if !prompts.is_empty() {
+                self.dialog.conflict_prompts = prompts;
+                self.dialog.conflict_apply_all = false;
+                return;
+            }
+        } else if review {
+            let plan = preflight::plan_outputs(&self.settings, self.operation_files());
+            if plan.iter().any(|planned| planned.exists) {
+                self.dialog.preflight = plan;
+                self.dialog.show_preflight_dialog = true;
+                return;
+            }
+        }
+        // 当前标签页的批处理还在运行时，在新标签页中开始
+        if self.active_operation().is_running() {
+            self.new_operation_tab();
+        }
+        self.start_operation();
+    }
+
+    /// 当前模式下处理的文件列表
+    fn operation_files(&self) -> &[FileItem] {
+        match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => &self.file_manager.left_files,
+            OperationMode::Decrypt | OperationMode::Verify => &self.file_manager.right_files,
+        }
+    }
+
+    fn operation_files_mut(&mut self) -> &mut [FileItem] {
+        match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => &mut self.file_manager.left_files,
+            OperationMode::Decrypt | OperationMode::Verify => &mut self.file_manager.right_files,
+        }
+    }
+
+    /// 继续或放弃中断的批处理
+    fn handle_resume(&mut self, event: ResumeEvent) {
+        match event {
+            ResumeEvent::Later => {
+                self.dialog.show_resume_dialog = false;
+                self.dialog.resume_password.clear();
+                self.dialog.resume_error = None;
+            }
+            ResumeEvent::Discard(index) => {
+                let journal = self.dialog.interrupted.remove(index);
+                if let Err(e) = journal.finish() {
+                    self.dialog.resume_error = Some(format!("Failed to remove the journal: {}", e));
+                }
+                self.dialog.show_resume_dialog = !self.dialog.interrupted.is_empty();
+            }
+            ResumeEvent::Resume(index) => {
+                let journal = self.dialog.interrupted[index].clone();
+                let result = journal.resume()
+                    .map_err(|e| format!("Failed to clean up the interrupted batch: {}", e))
+                    .and_then(|(mut settings, files)| {
+                        settings.password = self.dialog.resume_password.clone();
+                        settings.journal = Some(journal);
+                        let operation = self.idle_operation();
+                        self.start_batch(operation, settings, files)
+                    });
+                match result {
+                    Ok(()) => {
+                        self.dialog.interrupted.remove(index);
+                        self.dialog.resume_password.clear();
+                        self.dialog.resume_error = None;
+                        self.dialog.show_resume_dialog = !self.dialog.interrupted.is_empty();
+                    }
+                    Err(e) => self.dialog.resume_error = Some(e),
+                }
+            }
+        }
+    }
+
+    /// 记录对已有输出的回答，所有已有输出都有了处理方式后开始
+    fn handle_conflict_prompt(&mut self, event: ConflictPromptEvent) {
+        match event {
+            ConflictPromptEvent::Cancel => self.dialog.conflict_prompts.clear(),
+            ConflictPromptEvent::Resolve(policy) => {
+                let count = if self.dialog.conflict_apply_all { self.dialog.conflict_prompts.len() } else { 1 };
+                let resolved: Vec<usize> = self.dialog.conflict_prompts.drain(..count).map(|planned| planned.index).collect();
+                let files = self.operation_files_mut();
+                for index in resolved {
+                    if let Some(file) = files.get_mut(index) {
+                        file.conflict = Some(policy);
+                    }
+                }
+                if self.dialog.conflict_prompts.is_empty() {
+                    self.request_start(false);
+                }
+            }
+        }
+    }
+
+    fn active_operation(&self) -> &OperationView {
+        &self.operations[self.active_operation]
+    }
+
+    fn active_operation_mut(&mut self) -> &mut OperationView {
+        &mut self.operations[self.active_operation]
+    }
+
+    /// 是否有批处理正在运行
+    fn any_operation_running(&self) -> bool {
+        self.operations.iter().any(OperationView::is_running)
+    }
+
+    /// 找一个空闲的标签页给后台启动的批处理，优先使用当前标签页，都在运行时新建一个
+    fn idle_operation(&mut self) -> usize {
+        if !self.active_operation().is_running() {
+            return self.active_operation;
+        }
+        match self.operations.iter().position(|operation| !operation.is_running()) {
+            Some(index) => index,
+            None => {
+                self.operations.push(OperationView::default());
+                self.operations.len() - 1
+            }
+        }
+    }
+
+    /// 在第 `index` 个标签页中使用给定的设置和文件启动异步批处理
+    fn start_batch(&mut self, index: usize, settings: Settings, files: Vec<FileItem>) -> Result<(), String> {
+        // 界面中被禁用的选项保留旧值，引擎会把它们当作冲突拒绝
+        let settings = settings.without_inactive_options();
+        let mode = match settings.operation_mode {
+            OperationMode::Encrypt => "Encrypt",
+            OperationMode::Decrypt => "Decrypt",
+            OperationMode::Verify => "Verify",
+            OperationMode::Archive => "Archive",
+            OperationMode::Container => "Container",
+        };
+        let title = format!("{} · {} files", mode, files.len());
+        let settings_mode = settings.operation_mode.clone();
+
+        // Start async crypto operation
+        // 进度和状态通过操作事件通道在update()中处理，无需回调
+        let handle = CryptoEngine::start_operation_async_static(settings, files, None)?;
+        self.operations[index] = OperationView {
+            title,
+            handle: Some(handle),
+            progress: ProgressState {
+                current_file_name: "Starting processing...".to_string(),
+                ..Default::default()
+            },
+            state: AppState::Running,
+            scheduled_job: None,
+            api_job: None,
+            verify: settings_mode == OperationMode::Verify,
+        };
+        Ok(())
+    }
+    
+    fn stop_operation(&mut self) {
+        let operation = self.active_operation_mut();
+        if let Some(handle) = &operation.handle {
+            handle.stop();
+        }
+        operation.handle = None;
+        operation.state = AppState::Idle;
+        operation.progress.current_progress = 0.0;
+        operation.progress.total_progress = 0.0;
+        operation.progress.current_file_name = String::new();
+    }
+
+    fn resume_operation(&mut self) {
+        self.active_operation_mut().state = AppState::Running;
+    }
+
+    fn skip_current_task(&mut self) {
+        let operation = self.active_operation_mut();
+        if let Some(handle) = &operation.handle {
+            handle.skip_current();
+        }
+        operation.progress.current_progress = 0.0;
+    }
+
+    /// 添加一个空的批处理标签页并切换过去
+    fn new_operation_tab(&mut self) {
+        self.operations.push(OperationView::default());
+        self.active_operation = self.operations.len() - 1;
+    }
+
+    /// 关闭没有在运行的批处理标签页，至少保留一个
+    fn close_operation_tab(&mut self, index: usize) {
+        if self.operations.len() <= 1 || self.operations.get(index).is_none_or(OperationView::is_running) {
+            return;
+        }
+        self.operations.remove(index);
+        if self.active_operation >= index && self.active_operation > 0 {
+            self.active_operation -= 1;
+        }
+    }
+
+    /// 检查所有异步操作的状态并更新UI
+    fn check_operation_status(&mut self) {
+        for index in 0..self.operations.len() {
+            if let Some(status) = Self::drain_operation_events(&mut self.operations[index]) {
+                self.finish_operation(index, status);
+            }
+        }
+    }
+
+    /// 处理工作线程发来的所有事件，操作结束时返回最终状态
+    fn drain_operation_events(operation: &mut OperationView) -> Option<OperationStatus> {
+        let handle = operation.handle.as_mut()?;
+        while let Some(event) = handle.try_recv_event() {
+            operation.progress.results.extend(FileResult::from_event(&event));
+            match event {
+                OperationEvent::Progress(progress_info) => operation.progress.apply(progress_info),
+                OperationEvent::FileCompleted { .. } => operation.progress.completed_files += 1,
+                OperationEvent::FileSkipped { .. } => operation.progress.skipped_files += 1,
+                OperationEvent::FileFailed { name, error, .. } => operation.progress.failed_files.push((name, error)),
+                OperationEvent::ReportWritten(path) => operation.progress.report_path = Some(path),
+                OperationEvent::SourcesQuarantined(quarantine) => operation.progress.quarantine = Some(quarantine),
+                OperationEvent::Finished(status) => {
+                    operation.state = AppState::Idle;
+                    operation.handle = None;
+                    return Some(status);
+                }
+            }
+        }
+        None
+    }
+
+    /// 显示结束的批处理的结果
+    fn finish_operation(&mut self, index: usize, status: OperationStatus) {
+        let scheduled_job = self.operations[index].scheduled_job.take();
+        let quarantine = self.operations[index].progress.quarantine.take();
+        let api_job = self.operations[index].api_job.take();
+        let progress = &self.operations[index].progress;
+
+        // 通过控制接口提交的任务由调用方查询结果，不弹出对话框
+        if let Some(id) = api_job {
+            if let Some(job) = self.api.jobs.get_mut(&id) {
+                job.update(progress);
+                job.status = status;
+            }
+            if let Some(quarantine) = quarantine {
+                self.settle_quarantine(quarantine, false);
+            }
+            return;
+        }
+
+        // 计划任务的结果显示在计划页，不弹出对话框
+        if let Some(index) = scheduled_job {
+            self.scheduler.running_job = None;
+            let name = self.scheduler.schedule.jobs.get(index)
+                .map(|job| job.display_name())
+                .unwrap_or_default();
+            match status {
+                OperationStatus::Completed => self.scheduler.set_status(
+                    format!("Scheduled job '{}' completed ({} files)", name, progress.completed_files),
+                    false,
+                ),
+                OperationStatus::Failed(error) => self.scheduler.set_status(
+                    format!("Scheduled job '{}' failed: {}", name, error),
+                    true,
+                ),
+                OperationStatus::Cancelled | OperationStatus::Running => self.scheduler.set_status(
+                    format!("Scheduled job '{}' was cancelled", name),
+                    true,
+                ),
+            }
+            return;
+        }
+
+        // 校验批处理显示通过和失败的文件数，并列出每个失败文件的错误
+        if self.operations[index].verify {
+            let mut summary = format!("{} files passed, {} failed.", progress.completed_files, progress.failed_files.len());
+            for (_, error) in &progress.failed_files {
+                summary.push_str(&format!("\n{}", error));
+            }
+            match status {
+                OperationStatus::Completed => self.dialog.show_info("Verification Complete", summary),
+                OperationStatus::Failed(error) if progress.failed_files.is_empty() => self.dialog.show_error(error),
+                OperationStatus::Failed(_) => self.dialog.show_error(summary),
+                OperationStatus::Cancelled | OperationStatus::Running => {}
+            }
+            return;
+        }
+
+        match status {
+            OperationStatus::Completed => {
+                self.dialog.complete_report_path = progress.report_path.clone();
+                // 上一个完成对话框还没关闭时，先删除它隔离的文件
+                if let Some(previous) = std::mem::replace(&mut self.dialog.complete_quarantine, quarantine) {
+                    self.settle_quarantine(previous, false);
+                }
+                self.dialog.show_complete_dialog = true;
+            }
+            OperationStatus::Failed(error) => {
+                // 多个文件失败时列出所有失败的文件
+                let message = if progress.failed_files.len() > 1 {
+                    progress.failed_files.iter()
+                        .map(|(_, error)| error.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                } else {
+                    error
+                };
+                self.dialog.show_error(message);
+            }
+            OperationStatus::Cancelled | OperationStatus::Running => {}
+        }
+    }
+
+    /// 撤销删除时把隔离的源文件移回原处，否则删除它们
+    fn settle_quarantine(&mut self, quarantine: Quarantine, undo: bool) {
+        if undo {
+            match quarantine.restore() {
+                Ok(restored) => {
+                    self.load_left_files();
+                    self.load_right_files();
+                    self.dialog.show_info("Undo Delete", format!("Restored {} source files.", restored));
+                }
+                Err(e) => self.dialog.show_error(format!("Failed to restore source files: {}", e)),
+            }
+        } else if let Err(e) = quarantine.purge() {
+            self.dialog.show_error(format!("Failed to delete source files: {}", e));
+        }
+    }
+
+    /// 按设置启动或停止控制接口
+    fn start_api(&mut self, ctx: &egui::Context) {
+        // 先释放旧的服务，停止监听
+        self.api.server = None;
+        self.api.set_status("", false);
+        if !self.api.config.enabled {
+            return;
+        }
+        let ctx = ctx.clone();
+        match ApiServer::start(&self.api.config, move || ctx.request_repaint()) {
+            Ok(server) => self.api.server = Some(server),
+            Err(e) => self.api.set_status(format!("Failed to start the control API: {}", e), true),
+        }
+    }
+
+    fn save_api_config(&mut self) {
+        if let Err(e) = self.api.config.save() {
+            self.api.set_status(format!("Failed to save the API settings: {}", e), true);
+        }
+    }
+
+    /// 处理控制接口收到的请求
+    fn check_api(&mut self, ctx: &egui::Context) {
+        if std::mem::take(&mut self.api.pending_start) {
+            self.start_api(ctx);
+        }
+        while let Some(call) = self.api.server.as_ref().and_then(ApiServer::try_recv) {
+            let response = self.handle_api_request(call.request.clone());
+            call.reply(response);
+        }
+    }
+
+    fn handle_api_request(&mut self, request: ApiRequest) -> ApiResponse {
+        // 先用各标签页的最新进度更新运行中的任务
+        for operation in &self.operations {
+            if let Some(job) = operation.api_job.and_then(|id| self.api.jobs.get_mut(&id)) {
+                job.update(&operation.progress);
+            }
+        }
+        match request {
+            ApiRequest::ListJobs => ApiResponse::ok(200, self.api.jobs.values().collect::<Vec<_>>()),
+            ApiRequest::JobStatus(id) => match self.api.jobs.get(&id) {
+                Some(job) => ApiResponse::ok(200, job),
+                None => ApiResponse::error(404, format!("Unknown job {}", id)),
+            },
+            ApiRequest::CancelJob(id) => {
+                let operation = self.operations.iter().find(|operation| operation.api_job == Some(id));
+                match (operation.and_then(|operation| operation.handle.as_ref()), self.api.jobs.get(&id)) {
+                    // 批处理停止后发出结束事件，任务状态随之变为Cancelled
+                    (Some(handle), Some(job)) => {
+                        handle.stop();
+                        ApiResponse::ok(202, job)
+                    }
+                    (None, Some(_)) => ApiResponse::error(409, format!("Job {} has already finished", id)),
+                    (_, None) => ApiResponse::error(404, format!("Unknown job {}", id)),
+                }
+            }
+            ApiRequest::SubmitJob(submission) => self.submit_api_job(*submission),
+        }
+    }
+
+    /// 在空闲的标签页中启动通过接口提交的任务
+    fn submit_api_job(&mut self, submission: JobSubmission) -> ApiResponse {
+        let (job, password) = match submission.into_job() {
+            Ok(job) => job,
+            Err(e) => return ApiResponse::error(400, e),
+        };
+        let files = job.resolve_files();
+        if files.is_empty() {
+            return ApiResponse::error(422, format!("No files matched in '{}'", job.source_directory.display()));
+        }
+        let name = if job.name.is_empty() { "API job".to_string() } else { job.name.clone() };
+        let total_files = files.len();
+        let operation = self.idle_operation();
+        if let Err(e) = self.start_batch(operation, job.settings_with_password(&password), files) {
+            return ApiResponse::error(422, e);
+        }
+        self.api.next_job_id += 1;
+        let id = self.api.next_job_id;
+        self.operations[operation].title = format!("API: {}", name);
+        self.operations[operation].api_job = Some(id);
+        let report = JobReport::new(id, name, total_files);
+        self.api.jobs.insert(id, report.clone());
+        ApiResponse::ok(201, report)
+    }
+
+    /// 检查是否有到期的计划任务（只在没有其他操作运行时启动）
+    fn check_schedule(&mut self) {
+        if self.any_operation_running() || self.scheduler.prompt_job.is_some() {
+            return;
+        }
+        if let Some(index) = self.scheduler.schedule.next_due(chrono::Local::now()) {
+            self.trigger_scheduled_job(index);
+        }
+    }
+
+    /// 运行计划任务：已记住密码时直接运行，否则弹出密码输入框
+    fn trigger_scheduled_job(&mut self, index: usize) {
+        let Some(job) = self.scheduler.schedule.jobs.get(index) else {
+            return;
+        };
+        match self.scheduler.session_passwords.get(&job.job_path).cloned() {
+            Some(password) => self.run_scheduled_job(index, &password),
+            None => {
+                // 解密任务显示第一个带提示的文件中的密码提示
+                let hint = JobDefinition::load(&job.job_path).ok()
+                    .filter(|job| job.settings.operation_mode.reads_encrypted())
+                    .and_then(|job| job.resolve_files().into_iter().find_map(|file| file.hint));
+                self.scheduler.prompt_job = Some(index);
+                self.scheduler.prompt_hint = hint;
+                self.scheduler.prompt_password.clear();
+            }
+        }
+    }
+
+    /// 记录本次运行时间并启动计划任务
+    fn run_scheduled_job(&mut self, index: usize, password: &str) {
+        if self.scheduler.running_job.is_some() {
+            self.scheduler.set_status("Another scheduled job is running", true);
+            return;
+        }
+        let Some(scheduled) = self.scheduler.schedule.jobs.get_mut(index) else {
+            return;
+        };
+        scheduled.last_run = Some(chrono::Local::now().timestamp());
+        let job_path = scheduled.job_path.clone();
+        let name = scheduled.display_name();
+        self.save_schedule();
+
+        let job = match JobDefinition::load(&job_path) {
+            Ok(job) => job,
+            Err(e) => {
+                self.scheduler.set_status(format!("Failed to load job '{}': {}", name, e), true);
+                return;
+            }
+        };
+        let files = job.resolve_files();
+        if files.is_empty() {
+            self.scheduler.set_status(format!("Scheduled job '{}' found no files to process", name), false);
+            return;
+        }
+
+        let operation = self.idle_operation();
+        match self.start_batch(operation, job.settings_with_password(password), files) {
+            Ok(()) => {
+                self.operations[operation].title = format!("Job: {}", name);
+                self.operations[operation].scheduled_job = Some(index);
+                self.scheduler.running_job = Some(index);
+                self.scheduler.set_status(format!("Running scheduled job '{}'...", name), false);
+            }
+            Err(e) => self.scheduler.set_status(format!("Scheduled job '{}' did not start: {}", name, e), e.contains("up to date")),
+        }
+    }
+
+    /// 处理计划任务密码输入框
+    fn handle_password_prompt(&mut self, event: PasswordPromptEvent) {
+        let Some(index) = self.scheduler.prompt_job.take() else {
+            return;
+        };
+        let password = std::mem::take(&mut self.scheduler.prompt_password);
+        match event {
+            PasswordPromptEvent::Run => {
+                if self.scheduler.prompt_remember {
+                    if let Some(job) = self.scheduler.schedule.jobs.get(index) {
+                        self.scheduler.session_passwords.insert(job.job_path.clone(), password.clone());
+                    }
+                }
+                self.run_scheduled_job(index, &password);
+            }
+            PasswordPromptEvent::SkipRun => {
+                // 跳过本次运行，等待下一个周期
+                if let Some(job) = self.scheduler.schedule.jobs.get_mut(index) {
+                    job.last_run = Some(chrono::Local::now().timestamp());
+                }
+                self.save_schedule();
+            }
+        }
+    }
+
+    /// 处理加密容器页事件
+    fn handle_vault_event(&mut self, event: VaultEvent) {
+        match event {
+            VaultEvent::New => {
+                let Some(path) = FileDialog::new()
+                    .set_title("Create Vault")
+                    .add_filter("Krypton Vault", &[VAULT_FILE_EXTENSION])
+                    .set_file_name(format!("vault.{}", VAULT_FILE_EXTENSION))
+                    .save_file()
+                else {
+                    return;
+                };
+                match Vault::create(&path, &self.vault.password) {
+                    Ok(vault) => {
+                        self.vault.set_status(format!("Created vault {}", path.display()), false);
+                        self.set_open_vault(vault);
+                    }
+                    Err(e) => self.vault.set_status(format!("Failed to create vault: {}", e), true),
+                }
+            }
+            VaultEvent::Open => {
+                let Some(path) = FileDialog::new()
+                    .set_title("Open Vault")
+                    .add_filter("Krypton Vault", &[VAULT_FILE_EXTENSION])
+                    .pick_file()
+                else {
+                    return;
+                };
+                let sealed_keys = if self.vault.tpm_available { SealedKeyStore::load_default().ok() } else { None };
+                let remembered = sealed_keys.as_ref().is_some_and(|keys| keys.contains(&path));
+                if self.vault.password.is_empty() {
+                    // 未输入密码时使用密封在本机TPM中的密码
+                    let unsealed = match &sealed_keys {
+                        Some(keys) => keys.unseal(&path),
+                        None => Ok(None),
+                    };
+                    match unsealed {
+                        Ok(Some(password)) => self.vault.password = password,
+                        Ok(None) => {
+                            self.vault.set_status("No password is remembered on this device for this vault", true);
+                            return;
+                        }
+                        Err(e) => {
+                            self.vault.set_status(format!("Failed to unseal the vault password: {}", e), true);
+                            return;
+                        }
+                    }
+                }
+                match Vault::open(&path, &self.vault.password) {
+                    Ok(vault) => {
+                        self.vault.set_status(format!("Opened vault with {} entries", vault.entries().len()), false);
+                        self.vault.remembered = remembered;
+                        self.set_open_vault(vault);
+                    }
+                    Err(e) => self.vault.set_status(format!("Failed to open vault: {}", e), true),
+                }
+            }
+            VaultEvent::Close => {
+                self.vault = VaultState {
+                    tpm_available: self.vault.tpm_available,
+                    ..VaultState::default()
+                };
+            }
+            VaultEvent::RememberOnDevice => {
+                let Some(vault) = &self.vault.vault else {
+                    return;
+                };
+                let result = SealedKeyStore::load_default()
+                    .and_then(|mut keys| keys.seal(vault.path(), &self.vault.password));
+                match result {
+                    Ok(()) => {
+                        self.vault.remembered = true;
+                        self.vault.set_status("Vault password sealed to this device's TPM", false);
+                    }
+                    Err(e) => self.vault.set_status(format!("Failed to seal the vault password: {}", e), true),
+                }
+            }
+            VaultEvent::ForgetOnDevice => {
+                let Some(vault) = &self.vault.vault else {
+                    return;
+                };
+                match SealedKeyStore::load_default().and_then(|mut keys| keys.forget(vault.path())) {
+                    Ok(()) => {
+                        self.vault.remembered = false;
+                        self.vault.set_status("Removed the remembered vault password from this device", false);
+                    }
+                    Err(e) => self.vault.set_status(format!("Failed to remove the remembered password: {}", e), true),
+                }
+            }
+            VaultEvent::AddFiles => {
+                let Some(paths) = FileDialog::new().set_title("Add Files to Vault").pick_files() else {
+                    return;
+                };
+                let Some(vault) = self.vault.vault.as_mut() else {
+                    return;
+                };
+                let mut added = 0;
+                let mut errors = Vec::new();
+                for path in &paths {
+                    match vault.add_file(path) {
+                        Ok(()) => added += 1,
+                        Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+                    }
+                }
+                self.refresh_vault_status(format!("Added {} file(s)", added), errors);
+            }
+            VaultEvent::ExtractSelected => {
+                let Some(directory) = FileDialog::new().set_title("Extract To").pick_folder() else {
+                    return;
+                };
+                let Some(vault) = self.vault.vault.as_mut() else {
+                    return;
+                };
+                let mut extracted = 0;
+                let mut errors = Vec::new();
+                for name in &self.vault.selected {
+                    match vault.extract_to(name, &directory) {
+                        Ok(_) => extracted += 1,
+                        Err(e) => errors.push(format!("{}: {}", name, e)),
+                    }
+                }
+                self.refresh_vault_status(format!("Extracted {} file(s) to {}", extracted, directory.display()), errors);
+            }
+            VaultEvent::RemoveSelected => {
+                let Some(vault) = self.vault.vault.as_mut() else {
+                    return;
+                };
+                let mut removed = 0;
+                let mut errors = Vec::new();
+                for name in std::mem::take(&mut self.vault.selected) {
+                    match vault.remove(&name) {
+                        Ok(()) => removed += 1,
+                        Err(e) => errors.push(format!("{}: {}", name, e)),
+                    }
+                }
+                self.refresh_vault_status(format!("Removed {} entries", removed), errors);
+            }
+            VaultEvent::Compact => {
+                let Some(vault) = self.vault.vault.as_mut() else {
+                    return;
+                };
+                let reclaimed = self.vault.wasted_bytes;
+                match vault.compact() {
+                    Ok(()) => self.refresh_vault_status(
+                        format!("Compacted vault, reclaimed {}", ProgressFormatter::format_bytes(reclaimed)),
+                        Vec::new(),
+                    ),
+                    Err(e) => self.vault.set_status(format!("Failed to compact vault: {}", e), true),
+                }
+            }
+        }
+    }
+
+    fn set_open_vault(&mut self, vault: Vault) {
+        self.vault.wasted_bytes = vault.wasted_bytes().unwrap_or(0);
+        self.vault.selected.clear();
+        self.vault.vault = Some(vault);
+    }
+
+    /// 操作完成后更新可回收空间、清理失效的选择并显示结果
+    fn refresh_vault_status(&mut self, message: String, errors: Vec<String>) {
+        if let Some(vault) = &self.vault.vault {
+            self.vault.wasted_bytes = vault.wasted_bytes().unwrap_or(0);
+            let names: std::collections::BTreeSet<String> = vault.entries().iter().map(|entry| entry.name.clone()).collect();
+            self.vault.selected.retain(|name| names.contains(name));
+        }
+        if errors.is_empty() {
+            self.vault.set_status(message, false);
+        } else {
+            self.vault.set_status(format!("{}; {} failed: {}", message, errors.len(), errors.join("; ")), true);
+        }
+    }
+
+    /// 处理监视文件夹页事件
+    fn handle_watch_event(&mut self, event: WatchEvent) {
+        match event {
+            WatchEvent::BrowseSource => {
+                if let Some(path) = FileDialog::new().set_title("Select Drop Folder").pick_folder() {
+                    self.watch.source_directory = path.to_string_lossy().to_string();
+                }
+            }
+            WatchEvent::BrowseDestination => {
+                if let Some(path) = FileDialog::new().set_title("Select Destination Folder").pick_folder() {
+                    self.watch.destination_directory = path.to_string_lossy().to_string();
+                }
+            }
+            WatchEvent::Start => self.start_watching(),
+            WatchEvent::Stop => {
+                self.watch.watcher = None;
+                self.watch.queue.clear();
+                if let Some(handle) = &self.watch.handle {
+                    handle.stop();
+                }
+                self.watch.log("Stopped watching", false);
+            }
+            WatchEvent::ClearLog => self.watch.log.clear(),
+        }
+    }
+
+    /// 开始监视投放目录
+    fn start_watching(&mut self) {
+        if self.settings.password.is_empty() {
+            self.watch.log("Enter a password before starting to watch", true);
+            return;
+        }
+        let source = std::path::PathBuf::from(&self.watch.source_directory);
+        let destination = std::path::PathBuf::from(&self.watch.destination_directory);
+        if !source.is_dir() {
+            self.watch.log(format!("Drop folder '{}' does not exist", source.display()), true);
+            return;
+        }
+        if self.watch.destination_directory.is_empty() {
+            self.watch.log("Select a destination folder", true);
+            return;
+        }
+        // 输出到投放目录会导致加密结果被再次加密
+        if source.canonicalize().ok() == destination.canonicalize().ok() {
+            self.watch.log("The destination must be different from the drop folder", true);
+            return;
+        }
+
+        match FolderWatcher::new(&source) {
+            Ok(watcher) => {
+                self.watch.log(format!("Watching '{}' for new files", source.display()), false);
+                self.watch.watcher = Some(watcher);
+                self.watch.last_poll = None;
+            }
+            Err(e) => self.watch.log(format!("Failed to watch '{}': {}", source.display(), e), true),
+        }
+    }
+
+    /// 轮询投放目录、处理加密批次的事件，并在空闲时加密排队的新文件
+    fn check_watch(&mut self) {
+        if let Some(handle) = self.watch.handle.as_mut() {
+            let mut events = Vec::new();
+            while let Some(event) = handle.try_recv_event() {
+                events.push(event);
+            }
+            for event in events {
+                match event {
+                    OperationEvent::Progress(_) => {}
+                    OperationEvent::FileCompleted { name, output, .. } => {
+                        let output = output.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                        self.watch.log(format!("Encrypted {} -> {}", name, output), false);
+                    }
+                    OperationEvent::FileSkipped { name, .. } => self.watch.log(format!("Skipped {}", name), false),
+                    OperationEvent::FileFailed { name, error, .. } => self.watch.log(format!("Failed {}: {}", name, error), true),
+                    OperationEvent::ReportWritten(path) => self.watch.log(format!("Report written to {}", path.display()), false),
+                    OperationEvent::SourcesQuarantined(_) => {}
+                    OperationEvent::Finished(status) => {
+                        if let OperationStatus::Failed(error) = status {
+                            self.watch.log(error, true);
+                        }
+                        self.watch.handle = None;
+                    }
+                }
+            }
+        }
+
+        let Some(watcher) = self.watch.watcher.as_mut() else {
+            return;
+        };
+        if self.watch.last_poll.is_some_and(|last| last.elapsed() < WATCH_POLL_INTERVAL) {
+            return;
+        }
+        self.watch.last_poll = Some(std::time::Instant::now());
+        match watcher.poll() {
+            Ok(files) => {
+                for path in files {
+                    if !self.watch.queue.contains(&path) {
+                        self.watch.queue.push(path);
+                    }
+                }
+            }
+            Err(e) => {
+                let message = format!("Stopped watching '{}': {}", watcher.directory().display(), e);
+                self.watch.watcher = None;
+                self.watch.queue.clear();
+                self.watch.log(message, true);
+                return;
+            }
+        }
+
+        if self.watch.handle.is_some() || self.watch.queue.is_empty() {
+            return;
+        }
+        let files: Vec<FileItem> = self.watch.queue.drain(..)
+            .map(|path| {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                let mut file = FileItem::new(path, name);
+                file.selected = true;
+                file
+            })
+            .collect();
+        let settings = Settings {
+            operation_mode: OperationMode::Encrypt,
+            delete_source: self.watch.delete_originals,
+            shred_source: self.watch.delete_originals && self.watch.shred_originals,
+            output_directory: Some(std::path::PathBuf::from(&self.watch.destination_directory)),
+            incremental: false,
+            ..self.settings.clone()
+        };
+        self.watch.log(format!("Encrypting {} new file(s)", files.len()), false);
+        match CryptoEngine::start_operation_async_static(settings, files, None) {
+            Ok(handle) => self.watch.handle = Some(handle),
+            Err(e) => self.watch.log(e, true),
+        }
+    }
+
+    /// 处理备份页事件
+    fn handle_backup_event(&mut self, event: BackupEvent) {
+        match event {
+            BackupEvent::BrowseSource => {
+                if let Some(path) = FileDialog::new().set_title("Select Folder to Back Up").pick_folder() {
+                    self.backup.source_directory = path.to_string_lossy().to_string();
+                }
+            }
+            BackupEvent::BrowseDestination => {
+                if let Some(path) = FileDialog::new().set_title("Select Backup Destination").pick_folder() {
+                    self.backup.destination_directory = path.to_string_lossy().to_string();
+                }
+            }
+            BackupEvent::BrowseRestoreTarget => {
+                if let Some(path) = FileDialog::new().set_title("Select Restore Folder").pick_folder() {
+                    self.backup.restore_directory = path.to_string_lossy().to_string();
+                }
+            }
+            BackupEvent::Backup => self.start_backup(),
+            BackupEvent::Verify => self.start_backup_check(false, None),
+            BackupEvent::RestoreAll => self.start_backup_check(true, None),
+            BackupEvent::RestoreSelected => {
+                let selection = self.backup.selected.clone();
+                self.start_backup_check(true, Some(selection));
+            }
+            BackupEvent::LoadManifest => self.load_backup_manifest(),
+            BackupEvent::SelectMatching => {
+                if let Some(manifest) = &self.backup.manifest {
+                    let filter = self.backup.filter.to_lowercase();
+                    let matching = manifest.entries.iter()
+                        .filter(|entry| entry.path.to_lowercase().contains(&filter))
+                        .map(|entry| entry.path.clone());
+                    self.backup.selected.extend(matching);
+                }
+            }
+            BackupEvent::ClearSelection => self.backup.selected.clear(),
+            BackupEvent::Cancel => self.backup.cancel.store(true, std::sync::atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// 在后台把源目录备份到目标
+    fn start_backup(&mut self) {
+        if self.settings.password.is_empty() {
+            self.backup.set_status("Enter a password before backing up", true);
+            return;
+        }
+        let source = std::path::PathBuf::from(&self.backup.source_directory);
+        let destination = std::path::PathBuf::from(&self.backup.destination_directory);
+        if !source.is_dir() {
+            self.backup.set_status(format!("Source folder '{}' does not exist", source.display()), true);
+            return;
+        }
+        if self.backup.destination_directory.is_empty() {
+            self.backup.set_status("Select a backup destination", true);
+            return;
+        }
+        // 目标位于源目录内时备份会包含自身
+        let source = source.canonicalize().unwrap_or(source);
+        if destination.canonicalize().unwrap_or(destination.clone()).starts_with(&source) {
+            self.backup.set_status("The destination must not be inside the source folder", true);
+            return;
+        }
+
+        let settings = self.settings.clone();
+        self.spawn_backup_task(move |cancel, progress| {
+            let mut backend = LocalBackend::new(destination).with_direct_io(settings.direct_io);
+            let summary = backup::run_backup(&source, &mut backend, &settings, cancel, progress)?;
+            Ok((
+                format!(
+                    "Backed up {} files ({}) to '{}': {} added, {} changed, {} unchanged, {} deleted",
+                    summary.manifest.live_entries().count(),
+                    ProgressFormatter::format_bytes(summary.manifest.total_size()),
+                    backend.location(),
+                    summary.added,
+                    summary.changed,
+                    summary.unchanged,
+                    summary.deleted
+                ),
+                Vec::new(),
+            ))
+        });
+    }
+
+    /// 读取并校验备份清单，列出可恢复的文件
+    fn load_backup_manifest(&mut self) {
+        if self.settings.password.is_empty() {
+            self.backup.set_status("Enter the backup password first", true);
+            return;
+        }
+        let backend = LocalBackend::new(&self.backup.destination_directory);
+        match BackupManifest::load(&backend, &self.settings.password) {
+            Ok(manifest) => {
+                let paths: std::collections::BTreeSet<&String> = manifest.entries.iter().map(|entry| &entry.path).collect();
+                self.backup.selected.retain(|path| paths.contains(path));
+                self.backup.set_status(format!("Loaded manifest with {} files", manifest.live_entries().count()), false);
+                self.backup.manifest = Some(manifest);
+            }
+            Err(e) => {
+                self.backup.manifest = None;
+                self.backup.selected.clear();
+                self.backup.set_status(e, true);
+            }
+        }
+    }
+
+    /// 在后台校验备份，或把备份（`selection` 为Some时只恢复其中的文件）恢复到指定目录
+    fn start_backup_check(&mut self, restore: bool, selection: Option<std::collections::BTreeSet<String>>) {
+        if self.settings.password.is_empty() {
+            self.backup.set_status("Enter the backup password first", true);
+            return;
+        }
+        if self.backup.destination_directory.is_empty() {
+            self.backup.set_status("Select the backup location", true);
+            return;
+        }
+        if restore && self.backup.restore_directory.is_empty() {
+            self.backup.set_status("Select a folder to restore into", true);
+            return;
+        }
+
+        let backend = LocalBackend::new(&self.backup.destination_directory);
+        let target = std::path::PathBuf::from(&self.backup.restore_directory);
+        let password = self.settings.password.clone();
+        self.spawn_backup_task(move |cancel, progress| {
+            if restore {
+                let report = backup::restore_backup(&backend, &password, &target, selection.as_ref(), cancel, progress)?;
+                Ok((format!("Restored {} files to '{}'", report.ok, target.display()), report.problems))
+            } else {
+                let report = backup::verify_backup(&backend, &password, cancel, progress)?;
+                Ok((format!("Verified {} files, manifest signature is valid", report.ok), report.problems))
+            }
+        });
+    }
+
+    /// 在后台线程运行备份任务，通过通道汇报进度和结果
+    fn spawn_backup_task<F>(&mut self, task: F)
+    where
+        F: FnOnce(&std::sync::atomic::AtomicBool, backup::ProgressCallback) -> Result<(String, Vec<String>), String> + Send + 'static,
+    {
+        if self.backup.is_running() {
+            return;
+        }
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
+
+        std::thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let mut progress = move |done: usize, total: usize, current: &str| {
+                let _ = progress_sender.send(BackupMessage::Progress { done, total, current: current.to_string() });
+            };
+            let message = match task(&thread_cancel, &mut progress) {
+                Ok((message, problems)) => BackupMessage::Finished { is_error: !problems.is_empty(), message, problems },
+                Err(message) => BackupMessage::Finished { message, problems: Vec::new(), is_error: true },
+            };
+            let _ = sender.send(message);
+        });
+
+        self.backup.receiver = Some(receiver);
+        self.backup.cancel = cancel;
+        self.backup.done = 0;
+        self.backup.total = 0;
+        self.backup.current_file.clear();
+        self.backup.problems.clear();
+        self.backup.set_status("", false);
+    }
+
+    /// 接收备份线程的进度和结果
+    fn check_backup(&mut self) {
+        let Some(receiver) = &self.backup.receiver else {
+            return;
+        };
+        let mut messages = Vec::new();
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.backup.receiver = None;
+                    break;
+                }
+            }
+        }
+
+        for message in messages {
+            match message {
+                BackupMessage::Progress { done, total, current } => {
+                    self.backup.done = done;
+                    self.backup.total = total;
+                    self.backup.current_file = current;
+                }
+                BackupMessage::Finished { message, problems, is_error } => {
+                    self.backup.receiver = None;
+                    self.backup.problems = problems;
+                    self.backup.set_status(message, is_error);
+                }
+            }
+        }
+    }
+
+    /// 处理计划页事件
+    fn handle_schedule_event(&mut self, event: ScheduleEvent) {
+        match event {
+            ScheduleEvent::BrowseJob => {
+                if let Some(path) = FileDialog::new()
+                    .set_title("Select Job")
+                    .add_filter("Krypton Job", &[JOB_FILE_EXTENSION])
+                    .pick_file()
+                {
+                    self.scheduler.new_job_path = path.to_string_lossy().to_string();
+                }
+            }
+            ScheduleEvent::AddJob => {
+                let path = std::path::PathBuf::from(&self.scheduler.new_job_path);
+                if let Err(e) = JobDefinition::load(&path) {
+                    self.scheduler.set_status(format!("Invalid job file: {}", e), true);
+                    return;
+                }
+                let recurrence = self.scheduler.new_recurrence.clone();
+                self.scheduler.schedule.jobs.push(ScheduledJob::new(path, recurrence));
+                self.scheduler.new_job_path.clear();
+                self.scheduler.set_status("Job scheduled", false);
+                self.save_schedule();
+            }
+            ScheduleEvent::RemoveJob(index) => {
+                if index < self.scheduler.schedule.jobs.len() {
+                    let job = self.scheduler.schedule.jobs.remove(index);
+                    self.scheduler.session_passwords.remove(&job.job_path);
+                    self.save_schedule();
+                }
+            }
+            ScheduleEvent::RunNow(index) => self.trigger_scheduled_job(index),
+            ScheduleEvent::Changed => self.save_schedule(),
+        }
+    }
+
+    fn save_schedule(&mut self) {
+        if let Err(e) = self.scheduler.schedule.save() {
+            self.scheduler.set_status(format!("Failed to save schedule: {}", e), true);
+        }
+    }
+
+    /// 处理工具页事件
+    /// 处理主界面各面板和命令面板发出的事件
+    fn handle_panel_event(&mut self, event: PanelEvent) {
+        match event {
+            PanelEvent::LoadLeftFiles => self.load_left_files(),
+            PanelEvent::LoadRightFiles => self.load_right_files(),
+            PanelEvent::ReloadFiles => {
+                self.load_left_files();
+                self.load_right_files();
+            }
+            PanelEvent::StartOperation => self.request_start(true),
+            PanelEvent::DryRun => {
+                self.dialog.dry_run = Some(preflight::dry_run(&self.settings, self.operation_files()));
+            }
+            PanelEvent::StopOperation => self.stop_operation(),
+            PanelEvent::ResumeOperation => self.resume_operation(),
+            PanelEvent::ResumePrevious => {
+                if self.dialog.interrupted.is_empty() {
+                    self.dialog.show_info("Resume Previous Operation", "There are no interrupted batches to resume.");
+                } else {
+                    self.dialog.show_resume_dialog = true;
+                }
+            }
+            PanelEvent::SelectLeftDirectory => self.select_left_directory(),
+            PanelEvent::SelectRightDirectory => self.select_right_directory(),
+            PanelEvent::RestoreNames => self.restore_names(),
+            PanelEvent::AuditFiles => self.start_audit(),
+            PanelEvent::SaveJob => self.save_job(),
+            PanelEvent::LoadJob => self.load_job(),
+            PanelEvent::PreviewFile(index) => self.preview_file(index),
+            PanelEvent::PreviewEncryptedFile(index) => self.preview_encrypted_file(index),
+            PanelEvent::ClosePreview => self.close_preview(),
+            PanelEvent::CalibrateKdf => self.calibrate_kdf(),
+            PanelEvent::GenerateX25519Key => self.generate_x25519_key(),
+            PanelEvent::SelectX25519Identity => {
+                if let Some(path) = FileDialog::new().set_title("Select X25519 Secret Key").pick_file() {
+                    self.settings.kms.x25519_identity = path.to_string_lossy().to_string();
+                }
+            }
+            PanelEvent::CopyX25519PublicKey => self.copy_x25519_public_key(),
+            PanelEvent::SelectKeyfile => {
+                if let Some(path) = FileDialog::new().set_title("Select Keyfile").pick_file() {
+                    self.settings.keyfile = Some(path);
+                }
+            }
+            PanelEvent::SelectOutputDirectory => {
+                if let Some(path) = FileDialog::new().set_title("Select Output Directory").pick_folder() {
+                    self.settings.output_directory = Some(path);
+                }
+            }
+            PanelEvent::ResizeThreadPool => CryptoEngine::resize_shared_pool(self.settings.max_threads as usize),
+            PanelEvent::NewOperationTab => self.new_operation_tab(),
+            PanelEvent::CloseOperationTab(index) => self.close_operation_tab(index),
+            PanelEvent::ConnectShare(share) => {
+                self.dialog.share_login = Some(ShareLogin { share, ..Default::default() });
+            }
+        }
+    }
+
+    /// 执行命令面板中选中的命令
+    fn run_palette_action(&mut self, ctx: &egui::Context, action: PaletteAction) {
+        let locked = self.active_operation().state != AppState::Idle;
+        match action {
+            PaletteAction::Panel(event) => self.handle_panel_event(event),
+            PaletteAction::Tool(event) => {
+                self.active_tab = ActiveTab::Tools;
+                self.handle_tools_event(ctx, event);
+            }
+            PaletteAction::ShowTab(tab) => self.active_tab = tab,
+            PaletteAction::SetMode(_) | PaletteAction::SetAlgorithm(_) if locked => {
+                self.dialog.show_error("Settings are locked while this batch is running");
+            }
+            PaletteAction::SetMode(mode) => self.settings.operation_mode = mode,
+            PaletteAction::SetAlgorithm(algorithm) => self.settings.encryption_algorithm = algorithm,
+        }
+    }
+
+    fn handle_tools_event(&mut self, ctx: &egui::Context, event: ToolsEvent) {
+        match event {
+            ToolsEvent::EncryptText => self.encrypt_text(),
+            ToolsEvent::EncryptTextToFile => self.encrypt_text_to_file(),
+            ToolsEvent::EncryptTextToParts => self.encrypt_text_to_parts(),
+            ToolsEvent::SaveParts => self.save_text_parts(),
+            ToolsEvent::LoadParts => self.load_text_parts(),
+            ToolsEvent::DecryptText => self.decrypt_text(),
+            ToolsEvent::PasteFromClipboard => {
+                match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+                    Ok(text) => {
+                        self.tools.input_text = text;
+                        self.tools.set_status("Pasted from clipboard", false);
+                    }
+                    Err(e) => self.tools.set_status(format!("Failed to read clipboard: {}", e), true),
+                }
+            }
+            ToolsEvent::CopyOutput => {
+                ctx.copy_text(self.tools.output_text.clone());
+                self.tools.set_status("Output copied to clipboard", false);
+            }
+            ToolsEvent::UseOutputAsInput => {
+                self.tools.input_text = std::mem::take(&mut self.tools.output_text);
+                self.tools.output_parts.clear();
+                self.tools.set_status("", false);
+            }
+            ToolsEvent::Clear => {
+                self.tools = ToolsState {
+                    part_size_kb: self.tools.part_size_kb,
+                    ..ToolsState::default()
+                };
+            }
+            ToolsEvent::BrowseMigrationDirectory => {
+                if let Some(path) = FileDialog::new().set_title("Select Folder to Migrate").pick_folder() {
+                    self.migration.directory = path.to_string_lossy().to_string();
+                    self.migration.files.clear();
+                }
+            }
+            ToolsEvent::ScanForMigration => self.scan_for_migration(),
+            ToolsEvent::StartMigration => self.start_migration(),
+            ToolsEvent::CancelMigration => self.migration.cancel.store(true, std::sync::atomic::Ordering::Relaxed),
+            ToolsEvent::RollBackMigration => {
+                let total = self.migration.migrated.len();
+                let problems = migrate::rollback(&mut self.migration.migrated);
+                self.finish_migration_cleanup("Rolled back", total, problems);
+            }
+            ToolsEvent::ToggleApi => {
+                if self.api.config.enabled && self.api.config.token.is_empty() {
+                    self.api.config.token = ApiConfig::generate_token();
+                }
+                self.save_api_config();
+                self.start_api(ctx);
+            }
+            ToolsEvent::CopyApiToken => {
+                ctx.copy_text(self.api.config.token.clone());
+                self.api.set_status("Token copied to clipboard", false);
+            }
+            ToolsEvent::RegenerateApiToken => {
+                self.api.config.token = ApiConfig::generate_token();
+                if let Some(server) = &self.api.server {
+                    server.set_token(&self.api.config.token);
+                }
+                self.save_api_config();
+            }
+            ToolsEvent::EncryptConfig => self.encrypt_config(),
+            ToolsEvent::DecryptConfig => self.decrypt_config(),
+            ToolsEvent::UnlockConfig => self.config_lock.show_unlock = true,
+            ToolsEvent::DiscardMigrationBackups => {
+                let total = self.migration.migrated.len();
+                let problems = migrate::discard_backups(&mut self.migration.migrated);
+                self.finish_migration_cleanup("Deleted the backups of", total, problems);
+            }
+        }
+    }
 
-    // 进度状态
-    progress: ProgressState,
+    /// 查找迁移目录中的旧格式文件
+    fn scan_for_migration(&mut self) {
+        let directory = std::path::PathBuf::from(&self.migration.directory);
+        if !directory.is_dir() {
+            self.migration.set_status(format!("Folder '{}' does not exist", directory.display()), true);
+            return;
+        }
+        match migrate::find_legacy_files(&directory, &self.settings.file_extension) {
+            Ok(files) => {
+                let message = if files.is_empty() {
+                    "No files in an older format were found".to_string()
+                } else {
+                    format!("Found {} files in an older format", files.len())
+                };
+                self.migration.files = files;
+                self.migration.problems.clear();
+                self.migration.set_status(message, false);
+            }
+            Err(e) => self.migration.set_status(format!("Failed to scan '{}': {}", directory.display(), e), true),
+        }
+    }
 
-    // 应用状态
-    app_state: AppState,
+    /// 在后台线程迁移扫描到的文件
+    fn start_migration(&mut self) {
+        if self.migration.is_running() || self.migration.files.is_empty() {
+            return;
+        }
+        if self.settings.password.is_empty() {
+            self.migration.set_status("Enter the password of the files first", true);
+            return;
+        }
+        if !self.migration.migrated.is_empty() {
+            self.migration.set_status("Roll back or discard the backups of the previous migration first", true);
+            return;
+        }
 
-    // 对话框状态
-    dialog: DialogState,
+        let files = std::mem::take(&mut self.migration.files);
+        let algorithm = self.settings.encryption_algorithm.clone();
+        let password = self.settings.password.clone();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let thread_cancel = cancel.clone();
 
-    // 异步操作句柄
-    operation_handle: Option<OperationHandle>,
-}
+        std::thread::spawn(move || {
+            let progress_sender = sender.clone();
+            let mut progress = move |done: usize, total: usize, current: &str| {
+                let _ = progress_sender.send(MigrationMessage::Progress { done, total, current: current.to_string() });
+            };
+            let report = migrate::migrate_files(&files, &algorithm, &password, &thread_cancel, &mut progress);
+            let _ = sender.send(MigrationMessage::Finished(report));
+        });
 
-impl Default for KryptonApp {
-    fn default() -> Self {
-        Self {
-            settings: Settings::default(),
-            file_manager: FileManagerState::default(),
-            progress: ProgressState::default(),
-            app_state: AppState::Idle,
-            dialog: DialogState::default(),
-            operation_handle: None,
+        self.migration.receiver = Some(receiver);
+        self.migration.cancel = cancel;
+        self.migration.done = 0;
+        self.migration.total = 0;
+        self.migration.current_file.clear();
+        self.migration.problems.clear();
+        self.migration.set_status("", false);
+    }
+
+    /// 接收迁移线程的进度和结果
+    fn check_migration(&mut self) {
+        let Some(receiver) = &self.migration.receiver else {
+            return;
+        };
+        let mut messages = Vec::new();
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.migration.receiver = None;
+                    self.migration.set_status("Migration failed unexpectedly", true);
+                    break;
+                }
+            }
+        }
+
+        for message in messages {
+            match message {
+                MigrationMessage::Progress { done, total, current } => {
+                    self.migration.done = done;
+                    self.migration.total = total;
+                    self.migration.current_file = current;
+                }
+                MigrationMessage::Finished(report) => {
+                    self.migration.receiver = None;
+                    let mut message = format!("Migrated {} files", report.migrated.len());
+                    if !report.problems.is_empty() {
+                        message.push_str(&format!(", {} failed", report.problems.len()));
+                    }
+                    if report.cancelled {
+                        message.push_str(" before the migration was cancelled");
+                    }
+                    self.migration.set_status(message, !report.problems.is_empty());
+                    self.migration.migrated = report.migrated;
+                    self.migration.problems = report.problems;
+                    self.load_right_files();
+                }
+            }
         }
     }
-}
 
-impl KryptonApp {
-    pub fn new() -> Self {
-        Self::default()
+    /// 回滚或删除备份之后更新状态，出错的文件留在列表中以便重试
+    fn finish_migration_cleanup(&mut self, action: &str, total: usize, problems: Vec<String>) {
+        if problems.is_empty() {
+            self.migration.set_status(format!("{} {} files", action, total), false);
+        } else {
+            self.migration.set_status(format!("{} {} of {} files", action, total - problems.len(), total), true);
+        }
+        self.migration.problems = problems;
+        self.load_right_files();
     }
-    
-    fn load_left_files(&mut self) {
-        self.file_manager.left_files = FileManager::load_files_from_directory(&self.file_manager.left_directory);
+
+    fn encrypt_text(&mut self) {
+        if self.settings.password.is_empty() {
+            self.tools.set_status("Password cannot be empty", true);
+            return;
+        }
+
+        match armor::encrypt_text(&self.settings.encryption_algorithm, &self.settings.password, &self.tools.input_text) {
+            Ok(armored) => {
+                self.tools.output_text = armored;
+                self.tools.output_parts.clear();
+                self.tools.set_status("Text encrypted", false);
+            }
+            Err(e) => self.tools.set_status(format!("Failed to encrypt text: {}", e), true),
+        }
     }
-    
-    fn load_right_files(&mut self) {
-        self.file_manager.right_files = FileManager::load_encrypted_files_from_directory(&self.file_manager.right_directory, &self.settings);
+
+    /// 加密文本并拆分为多段封装
+    fn encrypt_text_to_parts(&mut self) {
+        if self.settings.password.is_empty() {
+            self.tools.set_status("Password cannot be empty", true);
+            return;
+        }
+
+        let max_part_len = self.tools.part_size_kb as usize * 1024;
+        match armor::encrypt_text_parts(&self.settings.encryption_algorithm, &self.settings.password, &self.tools.input_text, max_part_len) {
+            Ok(parts) => {
+                self.tools.output_text = parts.join("\n");
+                self.tools.set_status(format!("Text encrypted into {} part(s)", parts.len()), false);
+                self.tools.output_parts = parts;
+            }
+            Err(e) => self.tools.set_status(format!("Failed to encrypt text: {}", e), true),
+        }
     }
-    
-    fn start_operation(&mut self) {
-        self.app_state = AppState::Running;
-        self.progress.current_progress = 0.0;
-        self.progress.total_progress = 0.0;
-        self.progress.current_file_name = "Starting processing...".to_string();
 
-        // Get selected files based on operation mode
-        let selected_files: Vec<FileItem> = match self.settings.operation_mode {
-            OperationMode::Encrypt => self.file_manager.left_files.iter()
-                .filter(|f| f.selected)
-                .cloned()
-                .collect(),
-            OperationMode::Decrypt => self.file_manager.right_files.iter()
-                .filter(|f| f.selected)
-                .cloned()
-                .collect(),
+    /// 把每一段分别保存为文本文件
+    fn save_text_parts(&mut self) {
+        let Some(directory) = FileDialog::new().set_title("Save Parts To").pick_folder() else {
+            return;
         };
 
-        // 创建进度回调
-        let progress_callback: ProgressCallback = {
-            // 注意：这里我们不能直接捕获self，因为会导致借用检查器问题
-            // 在实际应用中，可能需要使用消息传递或其他机制来更新UI
-            Arc::new(move |progress_info: ProgressInfo| {
-                // 这里可以发送消息到主线程更新UI
-                println!("Progress: {}/{} files, current: {} ({:.1}%)",
-                    progress_info.current_file_index + 1,
-                    progress_info.total_files,
-                    progress_info.current_file,
-                    progress_info.overall_progress * 100.0
-                );
-            })
+        let total = self.tools.output_parts.len();
+        let result = self.tools.output_parts.iter().enumerate().try_for_each(|(index, part)| {
+            let path = directory.join(format!("message-part-{:03}-of-{:03}.txt", index + 1, total));
+            std::fs::write(&path, part)
+        });
+        match result {
+            Ok(()) => self.tools.set_status(format!("Saved {} part(s) to {}", total, directory.display()), false),
+            Err(e) => self.tools.set_status(format!("Failed to save parts: {}", e), true),
+        }
+    }
+
+    /// 读取保存的分段文件作为输入
+    fn load_text_parts(&mut self) {
+        let Some(paths) = FileDialog::new()
+            .set_title("Load Message Parts")
+            .add_filter("Text", &["txt", "asc"])
+            .pick_files()
+        else {
+            return;
         };
 
-        // Start async crypto operation
-        match CryptoEngine::start_operation_async_static(
-            self.settings.clone(),
-            selected_files,
-            Some(progress_callback),
-        ) {
-            Ok(handle) => {
-                self.operation_handle = Some(handle);
-                // 操作已启动，状态保持为Running
+        let mut texts = Vec::new();
+        for path in &paths {
+            match std::fs::read_to_string(path) {
+                Ok(text) => texts.push(text),
+                Err(e) => {
+                    self.tools.set_status(format!("Failed to read {}: {}", path.display(), e), true);
+                    return;
+                }
             }
-            Err(error) => {
-                self.dialog.error_message = error;
-                self.dialog.show_error_dialog = true;
-                self.app_state = AppState::Idle;
+        }
+        self.tools.input_text = texts.join("\n");
+        self.tools.set_status(format!("Loaded {} file(s); use Decrypt Text to reassemble them", paths.len()), false);
+    }
+
+    fn encrypt_text_to_file(&mut self) {
+        if self.settings.password.is_empty() {
+            self.tools.set_status("Password cannot be empty", true);
+            return;
+        }
+
+        let Some(path) = FileDialog::new()
+            .set_title("Save Encrypted Text")
+            .set_file_name(format!("note.txt.{}", self.settings.file_extension))
+            .save_file()
+        else {
+            return;
+        };
+
+        let result = std::fs::File::create(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| {
+                let mut writer = std::io::BufWriter::new(file);
+                let mut reader = std::io::Cursor::new(self.tools.input_text.as_bytes());
+                let header = FileHeader {
+                    algorithm: Some(self.settings.encryption_algorithm.clone()),
+                    ..FileHeader::with_hint(&self.settings.password_hint)
+                };
+                header.write_to(&mut writer).map_err(|e| e.to_string())?;
+                create_crypto_provider(&self.settings.encryption_algorithm)
+                    .encrypt_stream(&self.settings.password, &mut reader, &mut writer, &StreamControl::default())
+                    .map_err(|e| e.to_string())
+            });
+
+        match result {
+            Ok(()) => self.tools.set_status(format!("Encrypted text saved to {}", path.display()), false),
+            Err(e) => self.tools.set_status(format!("Failed to save encrypted text: {}", e), true),
+        }
+    }
+
+    fn decrypt_text(&mut self) {
+        if self.settings.password.is_empty() {
+            self.tools.set_status("Password cannot be empty", true);
+            return;
+        }
+
+        match armor::decrypt_text(&self.settings.password, &self.tools.input_text) {
+            Ok(text) => {
+                self.tools.output_text = text;
+                self.tools.set_status("Text decrypted", false);
             }
+            Err(e) => self.tools.set_status(format!("Failed to decrypt text: {}", e), true),
         }
     }
-    
-    fn stop_operation(&mut self) {
-        if let Some(handle) = &self.operation_handle {
-            handle.stop();
+
+    /// 处理加密笔记页事件
+    fn handle_notes_event(&mut self, event: NotesEvent) {
+        match event {
+            NotesEvent::Unlock => self.unlock_notes(),
+            NotesEvent::Lock => {
+                self.notes = NotesState::default();
+            }
+            NotesEvent::NewNote => {
+                self.notes.notes.push(Note::new("New note"));
+                self.notes.selected = Some(self.notes.notes.len() - 1);
+                self.notes.dirty = true;
+            }
+            NotesEvent::DeleteNote(index) => {
+                if index < self.notes.notes.len() {
+                    self.notes.notes.remove(index);
+                    self.notes.selected = None;
+                    self.notes.dirty = true;
+                }
+            }
+            NotesEvent::Save => self.save_notes(),
         }
-        self.operation_handle = None;
-        self.app_state = AppState::Idle;
-        self.progress.current_progress = 0.0;
-        self.progress.total_progress = 0.0;
-        self.progress.current_file_name = String::new();
     }
 
-    fn resume_operation(&mut self) {
-        self.app_state = AppState::Running;
+    fn unlock_notes(&mut self) {
+        let result = NoteStore::open_default(self.settings.encryption_algorithm.clone())
+            .and_then(|store| store.load(&self.notes.master_password));
+
+        match result {
+            Ok(notes) => {
+                self.notes.notes = notes;
+                self.notes.unlocked = true;
+                self.notes.selected = None;
+                self.notes.dirty = false;
+                self.notes.set_status(format!("Unlocked {} notes", self.notes.notes.len()), false);
+            }
+            Err(e) => self.notes.set_status(format!("Failed to unlock notes: {}", e), true),
+        }
     }
 
-    fn skip_current_task(&mut self) {
-        if let Some(handle) = &self.operation_handle {
-            handle.skip_current();
+    fn save_notes(&mut self) {
+        let result = NoteStore::open_default(self.settings.encryption_algorithm.clone())
+            .and_then(|store| store.save(&self.notes.master_password, &self.notes.notes));
+
+        match result {
+            Ok(()) => {
+                self.notes.dirty = false;
+                self.notes.set_status("Notes saved", false);
+            }
+            Err(e) => self.notes.set_status(format!("Failed to save notes: {}", e), true),
         }
-        self.progress.current_progress = 0.0;
     }
 
-    /// 检查异步操作状态并更新UI
-    fn check_operation_status(&mut self) {
-        if let Some(handle) = &mut self.operation_handle {
-            // 尝试接收进度更新
-            while let Some(progress_info) = handle.try_recv_progress() {
-                self.progress.current_file_name = progress_info.current_file;
-                self.progress.current_progress = progress_info.current_file_progress;
-                self.progress.total_progress = progress_info.overall_progress;
-                self.progress.current_file_index = progress_info.current_file_index;
-                self.progress.total_files = progress_info.total_files;
-                self.progress.current_file_size = progress_info.current_file_size;
-                self.progress.processed_bytes = progress_info.processed_bytes;
-                self.progress.total_bytes = progress_info.total_bytes;
-                self.progress.speed_mbps = progress_info.speed_mbps;
-                self.progress.elapsed_time = progress_info.elapsed_time;
-                self.progress.estimated_remaining = progress_info.estimated_remaining;
-            }
-
-            // 检查操作是否完成
-            if handle.is_finished() {
-                let status = handle.status();
-                match status {
-                    crate::models::OperationStatus::Completed => {
-                        self.dialog.show_complete_dialog = true;
-                        self.app_state = AppState::Idle;
-                    }
-                    crate::models::OperationStatus::Failed(error) => {
-                        self.dialog.error_message = error;
-                        self.dialog.show_error_dialog = true;
-                        self.app_state = AppState::Idle;
-                    }
-                    crate::models::OperationStatus::Cancelled => {
-                        self.app_state = AppState::Idle;
-                    }
-                    _ => {}
+    /// 根据加密文件名索引恢复解密目录中的原始文件名
+    fn restore_names(&mut self) {
+        if self.settings.password.is_empty() {
+            self.dialog.show_error("Password cannot be empty");
+            return;
+        }
+
+        let directory = std::path::PathBuf::from(&self.file_manager.right_directory);
+        match FilenameIndex::restore_names(&directory, &self.settings.password) {
+            Ok(summary) => {
+                let mut message = format!(
+                    "Restored {} file names ({} entries not decrypted yet).",
+                    summary.restored, summary.missing
+                );
+                if !summary.conflicts.is_empty() {
+                    message.push_str(&format!("\nSkipped, target already exists: {}", summary.conflicts.join(", ")));
+                }
+                if !summary.errors.is_empty() {
+                    message.push_str(&format!("\nErrors: {}", summary.errors.join("; ")));
                 }
+                self.dialog.show_info("Restore Names", message);
+                self.load_right_files();
+            }
+            Err(e) => self.dialog.show_error(format!("Failed to read filename index: {}", e)),
+        }
+    }
+
+    /// 生成X25519密钥对：保存私钥文件，并把公钥加入接收者列表
+    fn generate_x25519_key(&mut self) {
+        let Some(path) = FileDialog::new()
+            .set_title("Save X25519 Secret Key")
+            .set_file_name("krypton-x25519.key")
+            .save_file()
+        else {
+            return;
+        };
+        let identity = Identity::generate();
+        if let Err(e) = identity.save(&path) {
+            self.dialog.show_error(e);
+            return;
+        }
+        let recipient = identity.recipient().to_string();
+        let recipients = &mut self.settings.kms.x25519_recipients;
+        if !recipients.is_empty() && !recipients.ends_with('\n') {
+            recipients.push('\n');
+        }
+        recipients.push_str(&recipient);
+        self.settings.kms.x25519_identity = path.to_string_lossy().to_string();
+        self.dialog.show_info(
+            "X25519 Key Pair Generated",
+            format!("Secret key saved to {}. Keep it private; it is needed to decrypt.\n\nPublic key (share it with people who encrypt files for you):\n{}", path.display(), recipient),
+        );
+    }
+
+    /// 把私钥文件对应的X25519公钥复制到剪贴板
+    fn copy_x25519_public_key(&mut self) {
+        let recipient = match Identity::load(std::path::Path::new(self.settings.kms.x25519_identity.trim())) {
+            Ok(identity) => identity.recipient().to_string(),
+            Err(e) => return self.dialog.show_error(e),
+        };
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(recipient.clone())) {
+            Ok(()) => self.dialog.show_info("Public Key Copied", recipient),
+            Err(e) => self.dialog.show_error(format!("Failed to copy the public key: {}", e)),
+        }
+    }
 
-                // 清理操作句柄
-                self.operation_handle = None;
+    /// 按目标解锁时间在本机校准Argon2参数
+    fn calibrate_kdf(&mut self) {
+        let target = std::time::Duration::from_millis(self.settings.kdf_target_ms as u64);
+        match Argon2KeyDerivation::calibrate(target) {
+            Ok(params) => {
+                self.settings.kdf = params;
+                self.dialog.show_info(
+                    "Key Derivation Calibrated",
+                    format!("New files will use Argon2id with {}, about {} ms per unlock on this machine.", params, self.settings.kdf_target_ms),
+                );
             }
+            Err(e) => self.dialog.show_error(format!("Failed to calibrate key derivation: {}", e)),
         }
     }
-    
+
+    /// 在后台并行审计解密面板中的所有加密文件
+    fn start_audit(&mut self) {
+        if self.audit.running {
+            return;
+        }
+
+        let files = self.file_manager.right_files.clone();
+        let algorithm = self.settings.encryption_algorithm.clone();
+        let password = Some(self.settings.password.clone()).filter(|p| !p.is_empty());
+        let engine = CryptoEngine::from_settings(&self.settings);
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || {
+            let reports = engine.audit_files(&files, &algorithm, password.as_deref());
+            let _ = sender.send(reports);
+        });
+
+        self.audit.running = true;
+        self.audit.receiver = Some(receiver);
+    }
+
+    /// 检查后台审计是否完成
+    fn check_audit_status(&mut self) {
+        let Some(receiver) = &self.audit.receiver else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Ok(reports) => {
+                self.audit.reports = reports;
+                self.audit.show_dialog = true;
+                self.audit.running = false;
+                self.audit.receiver = None;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => {}
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.audit.running = false;
+                self.audit.receiver = None;
+                self.dialog.show_error("Audit failed unexpectedly");
+            }
+        }
+    }
+
+    /// 将当前的源目录、已选文件和设置保存为任务定义文件
+    fn save_job(&mut self) {
+        let (directory, files) = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => (&self.file_manager.left_directory, &self.file_manager.left_files),
+            OperationMode::Decrypt | OperationMode::Verify => (&self.file_manager.right_directory, &self.file_manager.right_files),
+        };
+        if directory.is_empty() || !files.iter().any(|file| file.selected) {
+            self.dialog.show_error("Select a directory and at least one file before saving a job");
+            return;
+        }
+
+        let Some(path) = FileDialog::new()
+            .set_title("Save Job")
+            .add_filter("Krypton Job", &[JOB_FILE_EXTENSION])
+            .set_file_name(format!("backup.{}", JOB_FILE_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+
+        let name = path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let job = JobDefinition::from_selection(name, directory.as_str(), files, &self.settings);
+        match job.save(&path) {
+            Ok(()) => self.dialog.show_info("Job Saved", format!("Job saved to {}\nThe password is not stored in the job file.", path.display())),
+            Err(e) => self.dialog.show_error(format!("Failed to save job: {}", e)),
+        }
+    }
+
+    /// 加载任务定义文件：应用设置（保留当前密码）并选中任务中的文件
+    fn load_job(&mut self) {
+        let Some(path) = FileDialog::new()
+            .set_title("Load Job")
+            .add_filter("Krypton Job", &[JOB_FILE_EXTENSION])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let job = match JobDefinition::load(&path) {
+            Ok(job) => job,
+            Err(e) => {
+                self.dialog.show_error(format!("Failed to load job: {}", e));
+                return;
+            }
+        };
+
+        self.settings = job.settings_with_password(&self.settings.password);
+        let directory = job.source_directory.to_string_lossy().to_string();
+        let files = match job.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => {
+                self.file_manager.left_directory = directory;
+                self.load_left_files();
+                &mut self.file_manager.left_files
+            }
+            OperationMode::Decrypt | OperationMode::Verify => {
+                self.file_manager.right_directory = directory;
+                self.load_right_files();
+                &mut self.file_manager.right_files
+            }
+        };
+        let selected: Vec<String> = job.resolve_files().into_iter().map(|file| file.name).collect();
+        for file in files.iter_mut() {
+            file.selected = selected.contains(&file.name);
+        }
+
+        self.active_tab = ActiveTab::Files;
+        self.dialog.show_info("Job Loaded", format!("Loaded job '{}' with {} file(s). Enter the password and press Start to run it.", job.name, selected.len()));
+    }
+
     fn select_left_directory(&mut self) {
         if let Some(path) = FileDialog::new()
             .set_title("Select Directory for Encryption")
@@ -193,59 +2151,195 @@ impl eframe::App for KryptonApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // 检查异步操作状态
         self.check_operation_status();
+        self.check_audit_status();
+        self.check_integrity_status();
+        self.check_schedule();
+        self.check_watch();
+        self.check_backup();
+        self.check_migration();
+        self.check_api(ctx);
+        theme::set_palette(ctx, self.settings.color_palette);
+        theme::set_layout(ctx, self.settings.layout_mode);
+
+        // 监视文件夹时定期唤醒以轮询目录
+        if self.watch.is_active() || self.watch.handle.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+
+        // 有启用的计划任务时定期唤醒，即使没有用户输入也能按时运行
+        if self.scheduler.schedule.has_enabled_jobs() {
+            ctx.request_repaint_after(Duration::from_secs(15));
+        }
 
         // 如果有正在进行的操作，请求持续重绘以更新进度
-        if self.operation_handle.is_some() && self.app_state == AppState::Running {
+        if self.any_operation_running() || self.audit.running || self.backup.is_running() || self.migration.is_running()
+            || self.file_manager.integrity_receiver.is_some()
+        {
             ctx.request_repaint();
         }
 
+        // Ctrl+P命令面板，先于其他控件处理快捷键
+        let palette_action = CommandPalette::render(ctx, &mut self.palette);
+
+        let mut tools_event = None;
+        let mut notes_event = None;
+        let mut schedule_event = None;
+        let mut watch_event = None;
+        let mut backup_event = None;
+        let mut vault_event = None;
+
+        // 文件预览窗格
+        let mut preview_event = None;
+        let previewing = self.file_manager.preview_index.is_some() || self.file_manager.encrypted_preview_index.is_some();
+        if self.active_tab == ActiveTab::Files && previewing {
+            egui::SidePanel::right("preview_pane")
+                .resizable(true)
+                .default_width(360.0)
+                .show(ctx, |ui| {
+                    preview_event = PreviewPanel::render(ui, &mut self.preview);
+                });
+        }
+        if let Some(event) = preview_event {
+            self.handle_panel_event(event);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            // Tab bar
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Files, "Files");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Tools, "Tools");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Notes, "Notes");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Schedule, "Schedule");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Watch, "Watch");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Backup, "Backup");
+                ui.selectable_value(&mut self.active_tab, ActiveTab::Vault, "Vault");
+            });
+            ui.separator();
+
+            if self.active_tab == ActiveTab::Schedule {
+                schedule_event = SchedulePanel::render(ui, &mut self.scheduler);
+                return;
+            }
+
+            if self.active_tab == ActiveTab::Vault {
+                vault_event = VaultPanel::render(ui, &mut self.vault);
+                return;
+            }
+
+            if self.active_tab == ActiveTab::Notes {
+                notes_event = NotesPanel::render(ui, &mut self.notes);
+                return;
+            }
+
             // Settings panel
-            SettingsPanel::render(
+            let locked = self.active_operation().state != AppState::Idle;
+            if let Some(event) = SettingsPanel::render(
                 ui,
                 &mut self.settings,
-            );
+                locked,
+            ) {
+                self.handle_panel_event(event);
+            }
 
             // ui.separator();
 
-            
+            if self.active_tab == ActiveTab::Tools {
+                ui.separator();
+                tools_event = ToolsPanel::render(ui, &mut self.tools, &mut self.migration, &mut self.config_lock, &mut self.api, &self.settings);
+                return;
+            }
+
+            if self.active_tab == ActiveTab::Watch {
+                ui.separator();
+                watch_event = WatchPanel::render(ui, &mut self.watch);
+                return;
+            }
+
+            if self.active_tab == ActiveTab::Backup {
+                ui.separator();
+                backup_event = BackupPanel::render(ui, &mut self.backup);
+                return;
+            }
+
             // File panel
             if let Some(event) = FilePanel::render(
                 ui,
                 &mut self.file_manager,
                 &self.settings,
             ) {
-                match event {
-                    PanelEvent::LoadLeftFiles => self.load_left_files(),
-                    PanelEvent::LoadRightFiles => self.load_right_files(),
-                    PanelEvent::SelectLeftDirectory => self.select_left_directory(),
-                    PanelEvent::SelectRightDirectory => self.select_right_directory(),
-                    _ => {}
-                }
+                self.handle_panel_event(event);
             }
             
+            // 批处理标签页
+            if let Some(event) = OperationTabs::render(ui, &self.operations, &mut self.active_operation) {
+                self.handle_panel_event(event);
+            }
+
             // Progress panel
             ProgressPanel::render(
                 ui,
-                &self.progress,
+                &self.operations[self.active_operation].progress,
+                &mut self.mini_progress,
             );
             
             ui.separator();
             
             // Control panel
+            let start_blockers = self.start_blockers();
             if let Some(event) = ControlPanel::render(
                 ui,
-                &self.app_state,
+                &self.operations[self.active_operation].state,
+                &start_blockers,
             ) {
-                match event {
-                    PanelEvent::StartOperation => self.start_operation(),
-                    PanelEvent::StopOperation => self.stop_operation(),
-                    PanelEvent::ResumeOperation => self.resume_operation(),
-                    _ => {}
-                }
+                self.handle_panel_event(event);
             }
         });
         
+        if self.mini_progress {
+            if let Some(event) = MiniProgressWindow::render(ctx, &self.operations[self.active_operation].progress, &self.operations[self.active_operation].state, &mut self.mini_progress) {
+                self.handle_panel_event(event);
+            }
+        }
+
+        if let Some(action) = palette_action {
+            self.run_palette_action(ctx, action);
+        }
+
+        if let Some(event) = tools_event {
+            self.handle_tools_event(ctx, event);
+        }
+        if let Some(event) = notes_event {
+            self.handle_notes_event(event);
+        }
+        if let Some(event) = schedule_event {
+            self.handle_schedule_event(event);
+        }
+        if let Some(event) = watch_event {
+            self.handle_watch_event(event);
+        }
+        if let Some(event) = backup_event {
+            self.handle_backup_event(event);
+        }
+        if let Some(event) = vault_event {
+            self.handle_vault_event(event);
+        }
+
+        // 计划任务的密码输入框
+        if let Some(index) = self.scheduler.prompt_job {
+            let job_name = self.scheduler.schedule.jobs.get(index)
+                .map(|job| job.display_name())
+                .unwrap_or_default();
+            if let Some(event) = PasswordPromptDialog::render(
+                ctx,
+                &job_name,
+                self.scheduler.prompt_hint.as_deref(),
+                &mut self.scheduler.prompt_password,
+                &mut self.scheduler.prompt_remember,
+            ) {
+                self.handle_password_prompt(event);
+            }
+        }
+
         // Render dialogs
         if let Some(event) = ErrorDialog::render(
             ctx,
@@ -255,12 +2349,93 @@ impl eframe::App for KryptonApp {
             match event {
                 DialogEvent::SkipCurrentTask => self.skip_current_task(),
                 DialogEvent::StopAllOperations => self.stop_operation(),
+                DialogEvent::UndoDelete | DialogEvent::PurgeQuarantine => {}
             }
         }
         
-        CompleteDialog::render(
+        let preflight_files = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => &mut self.file_manager.left_files,
+            OperationMode::Decrypt | OperationMode::Verify => &mut self.file_manager.right_files,
+        };
+        if let Some(event) = PreflightDialog::render(
+            ctx,
+            &mut self.dialog.show_preflight_dialog,
+            &self.dialog.preflight,
+            preflight_files,
+            self.settings.conflict_policy,
+            &mut self.dialog.preflight_conflicts_only,
+        ) {
+            self.dialog.preflight.clear();
+            if event == PreflightEvent::Start {
+                self.request_start(false);
+            }
+        }
+
+        if let Some(report) = &self.dialog.dry_run {
+            if let Some(event) = DryRunDialog::render(ctx, report) {
+                self.dialog.dry_run = None;
+                if event == DryRunEvent::Start {
+                    self.request_start(true);
+                }
+            }
+        }
+
+        if self.dialog.show_resume_dialog {
+            if let Some(event) = ResumeDialog::render(
+                ctx,
+                &self.dialog.interrupted,
+                &mut self.dialog.resume_password,
+                self.dialog.resume_error.as_deref(),
+            ) {
+                self.handle_resume(event);
+            }
+        }
+
+        if let Some(planned) = self.dialog.conflict_prompts.first() {
+            if let Some(event) = ConflictPromptDialog::render(
+                ctx,
+                planned,
+                self.dialog.conflict_prompts.len(),
+                &mut self.dialog.conflict_apply_all,
+            ) {
+                self.handle_conflict_prompt(event);
+            }
+        }
+
+        if let Some(event) = CompleteDialog::render(
             ctx,
             &mut self.dialog.show_complete_dialog,
+            self.dialog.complete_report_path.as_deref(),
+            self.dialog.complete_quarantine.as_ref().map_or(0, Quarantine::len),
+        ) {
+            if let Some(quarantine) = self.dialog.complete_quarantine.take() {
+                self.settle_quarantine(quarantine, event == DialogEvent::UndoDelete);
+            }
+        }
+
+        if let Some(login) = &mut self.dialog.share_login {
+            if let Some(event) = ShareLoginDialog::render(ctx, login) {
+                self.handle_share_login(event);
+            }
+        }
+
+        if self.config_lock.show_unlock {
+            if let Some(event) = ConfigUnlockDialog::render(ctx, &mut self.config_lock) {
+                self.handle_config_unlock(event);
+            }
+        }
+
+        AuditDialog::render(
+            ctx,
+            &mut self.audit.show_dialog,
+            &self.audit.reports,
+        );
+
+        InfoDialog::render(
+            ctx,
+            &mut self.dialog.show_info_dialog,
+            &self.dialog.info_title,
+            &self.dialog.info_message,
         );
     }
 } 
\ No newline at end of file
@@ -0,0 +1,252 @@
+use crate::core::app_data;
+use crate::core::container::Container;
+use crate::core::job::JobDefinition;
+use crate::core::preflight;
+use crate::crypto::CryptoEngine;
+use crate::models::{OperationEvent, OperationStatus};
+use crate::progress::ProgressFormatter;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 从环境变量读取密码（用于无人值守运行）
+pub const PASSWORD_ENV: &str = "KRYPTON_PASSWORD";
+
+/// 从环境变量读取密钥文件路径（用于打开使用密钥文件的容器）
+pub const KEYFILE_ENV: &str = "KRYPTON_KEYFILE";
+
+/// 让 `run-job` 以JSON Lines格式输出操作事件的参数
+pub const JSON_FLAG: &str = "--json";
+
+/// 让 `run-job` 只报告计划的输出和需要的磁盘空间、不写出任何文件的参数
+pub const DRY_RUN_FLAG: &str = "--dry-run";
+
+/// 处理命令行参数，返回None表示没有命令行任务，应启动图形界面
+pub fn run(args: &[String]) -> Option<i32> {
+    match args {
+        [command, flags @ .., job_path] if command == "run-job" && flags.iter().all(|flag| flag == JSON_FLAG || flag == DRY_RUN_FLAG) => {
+            let json = flags.iter().any(|flag| flag == JSON_FLAG);
+            match flags.iter().any(|flag| flag == DRY_RUN_FLAG) {
+                true => Some(exit_code(dry_run_job(Path::new(job_path), json))),
+                false => Some(exit_code(run_job(Path::new(job_path), json))),
+            }
+        }
+        [command, ..] if command == "run-job" => {
+            eprintln!("Usage: krypton run-job [{}] [{}] <file.kjob>", JSON_FLAG, DRY_RUN_FLAG);
+            Some(2)
+        }
+        [command, container] if command == "list-container" => Some(exit_code(list_container(Path::new(container)))),
+        [command, container, destination, entries @ ..] if command == "extract-container" => {
+            Some(exit_code(extract_container(Path::new(container), Path::new(destination), entries)))
+        }
+        [command, ..] if command == "list-container" || command == "extract-container" => {
+            eprintln!("Usage: krypton list-container <file.krypton>");
+            eprintln!("       krypton extract-container <file.krypton> <destination> [entry...]");
+            Some(2)
+        }
+        #[cfg(feature = "mount")]
+        [command, directory, mountpoint] if command == "mount" => Some(exit_code(mount_folder(Path::new(directory), Path::new(mountpoint)))),
+        #[cfg(feature = "mount")]
+        [command, ..] if command == "mount" => {
+            eprintln!("Usage: krypton mount <encrypted directory> <mountpoint>");
+            Some(2)
+        }
+        _ => None,
+    }
+}
+
+fn exit_code(result: Result<(), String>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+/// 在命令行中运行保存的任务
+///
+/// `json` 为true时每个操作事件以一行JSON输出到标准输出，其余提示信息输出到标准错误
+fn run_job(path: &Path, json: bool) -> Result<(), String> {
+    // 增量任务需要读写应用数据目录中的状态
+    if !app_data::unlock_config_from_env() {
+        return Err(format!("The configuration is encrypted; set {} to the master password", app_data::CONFIG_PASSWORD_ENV));
+    }
+    let job = JobDefinition::load(path)
+        .map_err(|e| format!("Failed to load job '{}': {}", path.display(), e))?;
+    let files = job.resolve_files();
+    if files.is_empty() {
+        return Err(format!("No files matched in '{}'", job.source_directory.display()));
+    }
+
+    if let Some(hint) = files.iter().find_map(|file| file.hint.as_deref()) {
+        message(json, &format!("Password hint: {}", hint));
+    }
+    let password = read_password(json)?;
+    let settings = job.settings_with_password(&password);
+    message(json, &format!("Running job '{}' ({} files, {})", job.name, files.len(), settings.encryption_algorithm));
+
+    let mut handle = CryptoEngine::start_operation_async_static(settings, files, None)?;
+    loop {
+        let Some(event) = handle.try_recv_event() else {
+            std::thread::sleep(Duration::from_millis(50));
+            continue;
+        };
+
+        if json {
+            let line = serde_json::to_string(&event).map_err(|e| format!("Failed to serialize event: {}", e))?;
+            println!("{}", line);
+        }
+        match event {
+            OperationEvent::Finished(status) => {
+                return match status {
+                    OperationStatus::Completed => {
+                        message(json, "Job completed");
+                        Ok(())
+                    }
+                    OperationStatus::Failed(e) => Err(e),
+                    OperationStatus::Cancelled | OperationStatus::Running => Err("Job cancelled".to_string()),
+                };
+            }
+            // JSON模式下事件已经输出
+            _ if json => {}
+            OperationEvent::Progress(_) => {}
+            OperationEvent::FileCompleted { name, .. } => println!("  done     {}", name),
+            OperationEvent::FileSkipped { name, .. } => println!("  skipped  {}", name),
+            OperationEvent::FileFailed { name, error, .. } => eprintln!("  failed   {}: {}", name, error),
+            OperationEvent::ReportWritten(path) => println!("  report   {}", path.display()),
+            OperationEvent::SourcesQuarantined(_) => {}
+        }
+    }
+}
+
+/// 试运行保存的任务：列出每个文件的输出、冲突和估计大小，以及各文件系统需要的空间，不需要密码也不写出文件。
+/// 空间不足时返回错误
+///
+/// `json` 为true时把整个报告以一行JSON输出到标准输出
+fn dry_run_job(path: &Path, json: bool) -> Result<(), String> {
+    let job = JobDefinition::load(path)
+        .map_err(|e| format!("Failed to load job '{}': {}", path.display(), e))?;
+    let files = job.resolve_files();
+    if files.is_empty() {
+        return Err(format!("No files matched in '{}'", job.source_directory.display()));
+    }
+    let report = preflight::dry_run(&job.settings_with_password(""), &files);
+
+    if json {
+        let line = serde_json::to_string(&report).map_err(|e| format!("Failed to serialize the report: {}", e))?;
+        println!("{}", line);
+    } else {
+        println!("Dry run of job '{}' ({} files), nothing is written", job.name, report.entries.len());
+        if let Some(bundle) = &report.bundle {
+            match bundle {
+                Ok(output) => println!("  into     {}", output.display()),
+                Err(e) => eprintln!("  error    {}", e),
+            }
+        }
+        for entry in &report.entries {
+            let output = entry.planned.output.as_ref().map_or("(random name)".to_string(), |output| output.display().to_string());
+            println!(
+                "  {} {:<16} {} -> {} ({})",
+                entry.action.symbol(), entry.action.to_string(), entry.planned.name, output,
+                ProgressFormatter::format_bytes(entry.estimated_size),
+            );
+        }
+        for requirement in &report.space {
+            let available = requirement.available.map_or("unknown".to_string(), ProgressFormatter::format_bytes);
+            println!(
+                "  space    {}: needs {}, {} available",
+                requirement.directory.display(), ProgressFormatter::format_bytes(requirement.required), available,
+            );
+        }
+    }
+    match report.has_enough_space() {
+        true => Ok(()),
+        false => Err("Not enough disk space for the outputs".to_string()),
+    }
+}
+
+/// 列出容器中的条目（只解密索引）
+fn list_container(path: &Path) -> Result<(), String> {
+    let container = Container::open(path, &read_password(false)?, keyfile_from_env().as_deref())?;
+    for entry in container.entries() {
+        println!("{:>14}  {}", entry.size, entry.path);
+    }
+    Ok(())
+}
+
+/// 把容器中的条目提取到目标目录，未指定条目时提取全部
+fn extract_container(path: &Path, destination: &Path, entries: &[String]) -> Result<(), String> {
+    let container = Container::open(path, &read_password(false)?, keyfile_from_env().as_deref())?;
+    if entries.is_empty() {
+        for output in container.extract_all(destination)? {
+            println!("  done     {}", output.display());
+        }
+        return Ok(());
+    }
+    for name in entries {
+        let entry = container.entry(name).ok_or_else(|| format!("'{}' is not in the container", name))?;
+        let output = container.extract_into(entry, destination)?;
+        println!("  done     {}", output.display());
+    }
+    Ok(())
+}
+
+/// 把加密文件夹只读挂载到 `mountpoint`，按回车后卸载
+#[cfg(feature = "mount")]
+fn mount_folder(directory: &Path, mountpoint: &Path) -> Result<(), String> {
+    use crate::core::encrypted_view::EncryptedFolderView;
+    use crate::core::mount::EncryptedMount;
+    use crate::models::Settings;
+
+    let settings = Settings { password: read_password(false)?, ..Settings::default() };
+    let view = EncryptedFolderView::open(directory, &settings);
+    if view.entries().is_empty() {
+        return Err(format!("No encrypted files in '{}'", directory.display()));
+    }
+    let mount = EncryptedMount::mount(view, mountpoint)
+        .map_err(|e| format!("Failed to mount '{}' on '{}': {}", directory.display(), mountpoint.display(), e))?;
+    println!("Mounted '{}' read-only on '{}', press Enter to unmount", directory.display(), mountpoint.display());
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+    mount.unmount().map_err(|e| format!("Failed to unmount '{}': {}", mountpoint.display(), e))
+}
+
+/// 容器使用密钥文件加密时，从环境变量读取密钥文件路径
+fn keyfile_from_env() -> Option<PathBuf> {
+    std::env::var_os(KEYFILE_ENV).filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+/// 输出提示信息：JSON模式下写到标准错误，以免混入事件流
+fn message(json: bool, text: &str) {
+    if json {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// 读取密码：优先使用环境变量，否则从标准输入读取一行（JSON模式下提示写到标准错误）
+fn read_password(json: bool) -> Result<String, String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        if !password.is_empty() {
+            return Ok(password);
+        }
+    }
+
+    if json {
+        eprint!("Password: ");
+        io::stderr().flush().map_err(|e| e.to_string())?;
+    } else {
+        print!("Password: ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+    }
+    let mut password = String::new();
+    io::stdin().lock().read_line(&mut password).map_err(|e| e.to_string())?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("Password cannot be empty".to_string());
+    }
+    Ok(password)
+}
@@ -0,0 +1,338 @@
+//! 本机REST控制接口
+//!
+//! 启用后只在 127.0.0.1 上监听HTTP请求，脚本和其他程序可以通过它向正在运行的Krypton提交任务、
+//! 查询进度和取消任务。每个请求都要带 `Authorization: Bearer <令牌>`。
+//! 请求在后台线程中解析后交给界面线程处理，任务和界面中启动的批处理一样显示在标签页中
+//!
+//! - `GET /api/v1/jobs`：列出本次会话中通过接口提交的任务
+//! - `POST /api/v1/jobs`：提交任务，请求体为 `{"job": <任务定义>, "password": "..."}`
+//!   或 `{"job_path": "<.kjob文件>", "password": "..."}`
+//! - `GET /api/v1/jobs/<id>`：查询任务的进度和结果
+//! - `POST /api/v1/jobs/<id>/cancel`：取消任务
+
+use super::app_data;
+use super::job::JobDefinition;
+use crate::models::{OperationStatus, ProgressState};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// 接口设置文件名
+const API_CONFIG_FILE_NAME: &str = "api.json";
+/// 默认监听端口
+pub const DEFAULT_API_PORT: u16 = 7878;
+/// 接口路径前缀
+pub const API_PREFIX: &str = "/api/v1";
+/// 等待界面线程回复的最长时间
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+/// 请求体的最大长度
+const MAX_BODY_LEN: u64 = 1024 * 1024;
+
+/// 接口设置，保存在应用数据目录中
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    pub port: u16,
+    /// 访问令牌，第一次启用时生成
+    pub token: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: DEFAULT_API_PORT, token: String::new() }
+    }
+}
+
+impl ApiConfig {
+    pub fn load() -> io::Result<Self> {
+        app_data::load_json(&app_data::app_data_file(API_CONFIG_FILE_NAME)?)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        app_data::save_config(&app_data::app_data_file(API_CONFIG_FILE_NAME)?, self)
+    }
+
+    /// 生成新的随机访问令牌
+    pub fn generate_token() -> String {
+        hex::encode(rand::random::<[u8; 32]>())
+    }
+}
+
+/// 提交任务的请求体
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct JobSubmission {
+    /// 完整的任务定义
+    pub job: Option<JobDefinition>,
+    /// 或者磁盘上的任务定义文件
+    pub job_path: Option<PathBuf>,
+    pub password: String,
+}
+
+impl JobSubmission {
+    /// 取出任务定义，二者都没有或都有时返回错误
+    pub fn into_job(self) -> Result<(JobDefinition, String), String> {
+        let job = match (self.job, self.job_path) {
+            (Some(job), None) => job,
+            (None, Some(path)) => JobDefinition::load(&path)
+                .map_err(|e| format!("Failed to load job '{}': {}", path.display(), e))?,
+            _ => return Err("Provide either \"job\" or \"job_path\"".to_string()),
+        };
+        Ok((job, self.password))
+    }
+}
+
+/// 解析后的接口请求
+#[derive(Debug, Clone)]
+pub enum ApiRequest {
+    ListJobs,
+    SubmitJob(Box<JobSubmission>),
+    JobStatus(u64),
+    CancelJob(u64),
+}
+
+/// 接口回复：HTTP状态码和JSON内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    pub fn ok(status: u16, body: impl Serialize) -> Self {
+        let body = serde_json::to_value(body).unwrap_or(Value::Null);
+        Self { status, body }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self { status, body: json!({ "error": message.into() }) }
+    }
+}
+
+/// 等待界面线程处理的请求
+pub struct ApiCall {
+    pub request: ApiRequest,
+    reply: mpsc::Sender<ApiResponse>,
+}
+
+impl ApiCall {
+    pub fn reply(self, response: ApiResponse) {
+        // 请求线程等待超时后不再接收回复
+        let _ = self.reply.send(response);
+    }
+}
+
+/// 失败的文件及错误信息
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FailedFile {
+    pub name: String,
+    pub error: String,
+}
+
+/// 通过接口提交的任务的进度和结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobReport {
+    pub id: u64,
+    pub name: String,
+    pub status: OperationStatus,
+    /// 总进度（0.0 - 1.0）
+    pub progress: f32,
+    pub current_file: String,
+    pub total_files: usize,
+    pub completed_files: usize,
+    pub skipped_files: usize,
+    pub failed_files: Vec<FailedFile>,
+    pub report_path: Option<PathBuf>,
+}
+
+impl JobReport {
+    pub fn new(id: u64, name: impl Into<String>, total_files: usize) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            status: OperationStatus::Running,
+            progress: 0.0,
+            current_file: String::new(),
+            total_files,
+            completed_files: 0,
+            skipped_files: 0,
+            failed_files: Vec::new(),
+            report_path: None,
+        }
+    }
+
+    /// 从批处理标签页的进度更新
+    pub fn update(&mut self, progress: &ProgressState) {
+        self.progress = progress.total_progress;
+        self.current_file = progress.current_file_name.clone();
+        self.completed_files = progress.completed_files;
+        self.skipped_files = progress.skipped_files;
+        self.failed_files = progress.failed_files.iter()
+            .map(|(name, error)| FailedFile { name: name.clone(), error: error.clone() })
+            .collect();
+        self.report_path = progress.report_path.clone();
+    }
+}
+
+/// 运行中的接口服务，释放时停止监听
+pub struct ApiServer {
+    server: Arc<Server>,
+    receiver: mpsc::Receiver<ApiCall>,
+    token: Arc<Mutex<String>>,
+    port: u16,
+}
+
+impl ApiServer {
+    /// 在本机端口上开始监听，收到请求后调用 `wake` 唤醒界面线程
+    pub fn start(config: &ApiConfig, wake: impl Fn() + Send + 'static) -> Result<Self, String> {
+        if config.token.is_empty() {
+            return Err("The API token is empty".to_string());
+        }
+        let server = Server::http(("127.0.0.1", config.port))
+            .map_err(|e| format!("Cannot listen on port {}: {}", config.port, e))?;
+        let server = Arc::new(server);
+        let (sender, receiver) = mpsc::channel();
+        let token = Arc::new(Mutex::new(config.token.clone()));
+        let listener = Arc::clone(&server);
+        let current_token = Arc::clone(&token);
+        std::thread::spawn(move || {
+            for mut request in listener.incoming_requests() {
+                let token = current_token.lock().unwrap().clone();
+                let response = handle(&mut request, &token, &sender, &wake);
+                let body = response.body.to_string();
+                let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("valid header");
+                let _ = request.respond(Response::from_string(body).with_status_code(response.status).with_header(content_type));
+            }
+        });
+        Ok(Self { server, receiver, token, port: config.port })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// 更换访问令牌，之后的请求必须使用新令牌
+    pub fn set_token(&self, token: &str) {
+        *self.token.lock().unwrap() = token.to_string();
+    }
+
+    /// 取出一个等待处理的请求
+    pub fn try_recv(&self) -> Option<ApiCall> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+/// 检查令牌并解析请求，交给界面线程处理后返回它的回复
+fn handle(request: &mut Request, token: &str, sender: &mpsc::Sender<ApiCall>, wake: &dyn Fn()) -> ApiResponse {
+    let authorization = request.headers().iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.as_str().to_string());
+    if !authorized(authorization.as_deref(), token) {
+        return ApiResponse::error(401, "Missing or wrong API token");
+    }
+    let body = match read_body(request.as_reader()) {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+    let api_request = match route(request.method(), request.url(), &body) {
+        Ok(api_request) => api_request,
+        Err(response) => return response,
+    };
+
+    let (reply, replies) = mpsc::channel();
+    if sender.send(ApiCall { request: api_request, reply }).is_err() {
+        return ApiResponse::error(503, "Krypton is shutting down");
+    }
+    wake();
+    replies.recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| ApiResponse::error(503, "Krypton did not respond in time"))
+}
+
+/// 比较 `Bearer` 令牌（比较哈希值，耗时与内容无关）
+fn authorized(authorization: Option<&str>, token: &str) -> bool {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| blake3::hash(given.trim().as_bytes()) == blake3::hash(token.as_bytes()))
+}
+
+/// 读取请求体，超过 [`MAX_BODY_LEN`] 时拒绝而不是截断
+fn read_body(reader: &mut dyn Read) -> Result<Vec<u8>, ApiResponse> {
+    let mut body = Vec::new();
+    if reader.take(MAX_BODY_LEN + 1).read_to_end(&mut body).is_err() {
+        return Err(ApiResponse::error(400, "Cannot read the request body"));
+    }
+    if body.len() as u64 > MAX_BODY_LEN {
+        return Err(ApiResponse::error(413, format!("The request body is larger than {} bytes", MAX_BODY_LEN)));
+    }
+    Ok(body)
+}
+
+/// 按方法和路径解析请求
+fn route(method: &Method, url: &str, body: &[u8]) -> Result<ApiRequest, ApiResponse> {
+    let path = url.split('?').next().unwrap_or_default();
+    // 前缀之后必须是完整的路径段（不接受 "/api/v1jobs"）
+    let Some(path) = path.strip_prefix(API_PREFIX).filter(|path| path.is_empty() || path.starts_with('/')) else {
+        return Err(ApiResponse::error(404, "Not found"));
+    };
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let job_id = |segment: &str| segment.parse::<u64>()
+        .map_err(|_| ApiResponse::error(404, format!("Unknown job '{}'", segment)));
+    match (method, segments.as_slice()) {
+        (Method::Get, ["jobs"]) => Ok(ApiRequest::ListJobs),
+        (Method::Post, ["jobs"]) => serde_json::from_slice(body)
+            .map(|submission| ApiRequest::SubmitJob(Box::new(submission)))
+            .map_err(|e| ApiResponse::error(400, format!("Invalid job submission: {}", e))),
+        (Method::Get, ["jobs", id]) => Ok(ApiRequest::JobStatus(job_id(id)?)),
+        (Method::Post, ["jobs", id, "cancel"]) => Ok(ApiRequest::CancelJob(job_id(id)?)),
+        (_, ["jobs", ..]) => Err(ApiResponse::error(405, "Method not allowed")),
+        _ => Err(ApiResponse::error(404, "Not found")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_and_authorization() {
+        assert!(authorized(Some("Bearer secret"), "secret"));
+        assert!(!authorized(Some("Bearer wrong"), "secret"));
+        assert!(!authorized(Some("secret"), "secret"));
+        assert!(!authorized(None, "secret"));
+
+        assert!(matches!(route(&Method::Get, "/api/v1/jobs", b""), Ok(ApiRequest::ListJobs)));
+        assert!(matches!(route(&Method::Get, "/api/v1/jobs/7?verbose=1", b""), Ok(ApiRequest::JobStatus(7))));
+        assert!(matches!(route(&Method::Post, "/api/v1/jobs/7/cancel", b""), Ok(ApiRequest::CancelJob(7))));
+        let submit = route(&Method::Post, "/api/v1/jobs", br#"{"job_path": "backup.kjob", "password": "pw"}"#);
+        let Ok(ApiRequest::SubmitJob(submission)) = submit else {
+            panic!("expected a job submission");
+        };
+        assert_eq!(submission.job_path, Some(PathBuf::from("backup.kjob")));
+        assert_eq!(submission.password, "pw");
+
+        assert_eq!(route(&Method::Get, "/other", b"").unwrap_err().status, 404);
+        assert_eq!(route(&Method::Get, "/api/v1jobs", b"").unwrap_err().status, 404);
+        assert_eq!(route(&Method::Post, "/api/v10/jobs", b"").unwrap_err().status, 404);
+        assert_eq!(route(&Method::Get, "/api/v1/jobs/abc", b"").unwrap_err().status, 404);
+        assert_eq!(route(&Method::Delete, "/api/v1/jobs/7", b"").unwrap_err().status, 405);
+        assert_eq!(route(&Method::Post, "/api/v1/jobs", b"not json").unwrap_err().status, 400);
+        assert!(JobSubmission::default().into_job().is_err());
+
+        // 过长的请求体被拒绝，而不是截断后再解析
+        let body = vec![b' '; MAX_BODY_LEN as usize];
+        assert_eq!(read_body(&mut body.as_slice()).unwrap().len(), body.len());
+        let oversized = vec![b' '; MAX_BODY_LEN as usize + 1];
+        assert_eq!(read_body(&mut oversized.as_slice()).unwrap_err().status, 413);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::crypto::armor;
+use crate::models::EncryptionAlgorithm;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 应用数据目录名称
+const APP_DIR_NAME: &str = "krypton";
+
+/// 覆盖数据目录的环境变量（便于便携模式和测试）
+pub const DATA_DIR_ENV: &str = "KRYPTON_DATA_DIR";
+
+/// 配置加密后保存主密码校验数据的文件名（存在即表示配置已加密）
+const CONFIG_LOCK_FILE_NAME: &str = "config.lock";
+/// 校验数据中加密的固定文本
+const CONFIG_LOCK_TEXT: &str = "krypton configuration";
+/// 未在界面中输入主密码时读取的环境变量（命令行和无人值守运行时使用）
+pub const CONFIG_PASSWORD_ENV: &str = "KRYPTON_CONFIG_PASSWORD";
+
+/// 解锁后的配置主密码，配置未加密或尚未解锁时为None
+static CONFIG_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+/// 获取应用数据目录（不存在时自动创建）
+pub fn app_data_dir() -> io::Result<PathBuf> {
+    let dir = match std::env::var_os(DATA_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::data_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No application data directory available"))?
+            .join(APP_DIR_NAME),
+    };
+
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// 获取应用数据目录下的文件路径
+pub fn app_data_file(name: &str) -> io::Result<PathBuf> {
+    Ok(app_data_dir()?.join(name))
+}
+
+/// 原子地写入文件（先写临时文件再重命名）
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp_name = path.file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_default();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
+
+/// 从JSON文件加载数据，文件不存在时返回默认值。用主密码加密的配置文件先解密
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> io::Result<T> {
+    match fs::read_to_string(path) {
+        Ok(data) => serde_json::from_str(&open_config(path, data)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// 将数据保存为JSON文件
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let data = serde_json::to_vec_pretty(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write_atomic(path, &data)
+}
+
+/// 保存应用数据目录中的配置文件（设置记录、计划任务、增量状态等），配置已加密时用主密码加密
+pub fn save_config<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    match config_password() {
+        Some(password) => write_atomic(path, seal_config(&password, &json)?.as_bytes()),
+        // 已加密但未解锁时不能用明文覆盖
+        None if config_encrypted() => Err(config_locked_error()),
+        None => write_atomic(path, json.as_bytes()),
+    }
+}
+
+fn config_password() -> Option<String> {
+    CONFIG_PASSWORD.lock().unwrap().clone()
+}
+
+/// 配置文件是否已用主密码加密
+pub fn config_encrypted() -> bool {
+    app_data_file(CONFIG_LOCK_FILE_NAME).is_ok_and(|path| path.exists())
+}
+
+/// 配置已加密但尚未解锁
+pub fn config_locked() -> bool {
+    config_encrypted() && config_password().is_none()
+}
+
+/// 用主密码解锁配置
+pub fn unlock_config(password: &str) -> io::Result<()> {
+    let check = fs::read_to_string(app_data_file(CONFIG_LOCK_FILE_NAME)?)?;
+    match armor::decrypt_text(password, &check) {
+        Ok(text) if text == CONFIG_LOCK_TEXT => {
+            *CONFIG_PASSWORD.lock().unwrap() = Some(password.to_string());
+            Ok(())
+        }
+        _ => Err(io::Error::new(io::ErrorKind::PermissionDenied, "Wrong master password")),
+    }
+}
+
+/// 配置已锁定且设置了环境变量时用它解锁，返回配置是否可以读写
+pub fn unlock_config_from_env() -> bool {
+    if !config_locked() {
+        return true;
+    }
+    std::env::var(CONFIG_PASSWORD_ENV).ok()
+        .filter(|password| !password.is_empty())
+        .is_some_and(|password| unlock_config(&password).is_ok())
+}
+
+/// 用主密码加密数据目录中的所有配置文件（已加密时换用新密码）
+pub fn encrypt_config(password: &str) -> io::Result<()> {
+    let files = read_config_files()?;
+    // 先写校验数据，中途失败时已加密的文件仍能用新密码解锁
+    write_atomic(&app_data_file(CONFIG_LOCK_FILE_NAME)?, seal_config(password, CONFIG_LOCK_TEXT)?.as_bytes())?;
+    *CONFIG_PASSWORD.lock().unwrap() = Some(password.to_string());
+    for (path, json) in files {
+        write_atomic(&path, seal_config(password, &json)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// 把所有配置文件解密为明文并去掉主密码
+pub fn decrypt_config() -> io::Result<()> {
+    for (path, json) in read_config_files()? {
+        write_atomic(&path, json.as_bytes())?;
+    }
+    fs::remove_file(app_data_file(CONFIG_LOCK_FILE_NAME)?)?;
+    *CONFIG_PASSWORD.lock().unwrap() = None;
+    Ok(())
+}
+
+/// 读出数据目录中所有配置文件（`*.json`）的明文
+fn read_config_files() -> io::Result<Vec<(PathBuf, String)>> {
+    if config_locked() {
+        return Err(config_locked_error());
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(app_data_dir()?)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|extension| extension == "json") {
+            let json = open_config(&path, fs::read_to_string(&path)?)?;
+            files.push((path, json));
+        }
+    }
+    Ok(files)
+}
+
+fn seal_config(password: &str, text: &str) -> io::Result<String> {
+    armor::encrypt_text(&EncryptionAlgorithm::AES256, password, text)
+        .map_err(|e| io::Error::other(e.to_string()))
+}
+
+/// 文件内容是加密的文本封装时用主密码解密，否则原样返回。
+/// 配置已加密（同一目录中有校验数据）时不接受明文文件，防止替换为未加密的配置
+fn open_config(path: &Path, contents: String) -> io::Result<String> {
+    if !contents.starts_with(armor::ARMOR_BEGIN) {
+        if path.with_file_name(CONFIG_LOCK_FILE_NAME).exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("'{}' is not encrypted although the configuration is", path.display()),
+            ));
+        }
+        return Ok(contents);
+    }
+    let password = config_password().ok_or_else(config_locked_error)?;
+    armor::decrypt_text(&password, &contents)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Cannot decrypt the configuration with the master password"))
+}
+
+fn config_locked_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "The configuration is encrypted and locked")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_plaintext_config_rejected_when_encrypted() {
+        let dir = TestDir::new("app_data_lock");
+        let path = dir.join("schedule.json");
+        fs::write(&path, r#"{"jobs": "none"}"#).unwrap();
+        assert_eq!(load_json::<HashMap<String, String>>(&path).unwrap()["jobs"], "none");
+
+        // 加密的配置旁被放入明文文件
+        fs::write(dir.join(CONFIG_LOCK_FILE_NAME), seal_config("master", CONFIG_LOCK_TEXT).unwrap()).unwrap();
+        let error = load_json::<HashMap<String, String>>(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
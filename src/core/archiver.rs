@@ -0,0 +1,191 @@
+//! 7-Zip归档输出：把选中的文件打包为一个AES-256加密的 .7z 文件，而不是为每个文件生成 .enc
+//!
+//! 内容用LZMA2压缩后以AES-256（7-Zip的SHA-256密钥派生）加密，文件名列表也一起加密，
+//! 接收方用7-Zip、p7zip或其他支持7z的归档工具输入密码即可解压
+
+use super::output::unique_path;
+use crate::models::{ConflictPolicy, FileItem, OperationEvent, Settings};
+use crate::progress::ProgressTracker;
+use sevenz_rust::{AesEncoderOptions, SevenZArchiveEntry, SevenZMethod, SevenZMethodConfiguration, SevenZWriter};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 归档文件的扩展名
+pub const ARCHIVE_EXTENSION: &str = "7z";
+/// 源文件所在目录没有名称（例如根目录）时使用的归档名
+const DEFAULT_ARCHIVE_NAME: &str = "archive";
+/// 每读取这么多字节报告一次文件内的进度
+const PROGRESS_INTERVAL: u64 = 1024 * 1024;
+
+/// 归档的输出路径：输出目录（未设置时为列出文件时所选的目录）下以源目录命名、扩展名为 `extension` 的文件，
+/// 已存在时按冲突策略处理
+pub fn archive_path(settings: &Settings, files: &[FileItem], extension: &str) -> Result<PathBuf, String> {
+    let source_directory = files.first().and_then(FileItem::listed_directory).unwrap_or(Path::new(""));
+    let name = source_directory.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| DEFAULT_ARCHIVE_NAME.to_string());
+    let directory = settings.output_directory.as_deref().unwrap_or(source_directory);
+    let path = directory.join(format!("{}.{}", name, extension));
+    match settings.conflict_policy {
+        ConflictPolicy::Overwrite => Ok(path),
+        ConflictPolicy::KeepBoth => Ok(unique_path(&path)),
+        ConflictPolicy::Skip | ConflictPolicy::Ask if path.exists() => Err(format!("Archive '{}' already exists", path.display())),
+        ConflictPolicy::Skip | ConflictPolicy::Ask => Ok(path),
+    }
+}
+
+/// 把 `files` 打包为用 `password` 加密的7z归档 `output`，通过 `progress_tracker` 报告每个文件的进度。
+/// 先写入临时文件，完成后再改名；失败或 `should_stop` 被设置时删除不完整的归档
+pub fn create_archive(
+    files: &[FileItem],
+    output: &Path,
+    password: &str,
+    should_stop: &AtomicBool,
+    progress_tracker: &ProgressTracker,
+) -> Result<(), String> {
+    let mut temp_path = output.as_os_str().to_owned();
+    temp_path.push(".partial");
+    let temp_path = PathBuf::from(temp_path);
+
+    let result = write_archive(files, &temp_path, password, should_stop, progress_tracker)
+        .and_then(|_| fs::rename(&temp_path, output)
+            .map_err(|e| format!("Failed to write archive '{}': {}", output.display(), e)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    } else {
+        for (index, file) in files.iter().enumerate() {
+            progress_tracker.send_event(OperationEvent::FileCompleted { index, name: file.name.clone(), output: output.to_path_buf() });
+        }
+    }
+    result
+}
+
+fn write_archive(
+    files: &[FileItem],
+    path: &Path,
+    password: &str,
+    should_stop: &AtomicBool,
+    progress_tracker: &ProgressTracker,
+) -> Result<(), String> {
+    // 空密码的AES编码器会写出实际上没有保护的归档（例如密钥来自密钥管理服务时设置中没有密码）
+    if password.is_empty() {
+        return Err("7z archives need a password".to_string());
+    }
+    let write_error = |e: sevenz_rust::Error| format!("Failed to write archive '{}': {}", path.display(), e);
+    let mut writer = SevenZWriter::create(path).map_err(write_error)?;
+    writer.set_content_methods(vec![
+        AesEncoderOptions::new(password.into()).into(),
+        SevenZMethodConfiguration::new(SevenZMethod::LZMA2),
+    ]);
+    writer.set_encrypt_header(true);
+
+    for (index, file) in files.iter().enumerate() {
+        if should_stop.load(Ordering::Relaxed) {
+            return Err("Operation cancelled".to_string());
+        }
+        progress_tracker.start_file(index, file);
+        let fail = |error: String| {
+            progress_tracker.send_event(OperationEvent::FileFailed { index, name: file.name.clone(), error: error.clone() });
+            progress_tracker.fail_file();
+            error
+        };
+        let input = File::open(&file.path)
+            .map_err(|e| fail(format!("Failed to open file '{}': {}", file.name, e)))?;
+        let reader = ProgressReader::new(input, file.size_on_disk(), should_stop, progress_tracker);
+        writer.push_archive_entry(SevenZArchiveEntry::from_path(&file.path, file.relative_name()), Some(reader))
+            .map_err(|e| match should_stop.load(Ordering::Relaxed) {
+                true => "Operation cancelled".to_string(),
+                false => fail(format!("Failed to add file '{}' to the archive: {}", file.name, e)),
+            })?;
+        progress_tracker.complete_file(file.size_on_disk(), None);
+    }
+    writer.finish().map_err(|e| format!("Failed to write archive '{}': {}", path.display(), e))?;
+    Ok(())
+}
+
+/// 读取源文件时报告文件内的进度，取消时中断读取
+pub(crate) struct ProgressReader<'a> {
+    inner: File,
+    read: u64,
+    reported: u64,
+    size: u64,
+    should_stop: &'a AtomicBool,
+    progress_tracker: &'a ProgressTracker,
+}
+
+impl<'a> ProgressReader<'a> {
+    pub(crate) fn new(inner: File, size: u64, should_stop: &'a AtomicBool, progress_tracker: &'a ProgressTracker) -> Self {
+        Self { inner, read: 0, reported: 0, size, should_stop, progress_tracker }
+    }
+}
+
+impl Read for ProgressReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.should_stop.load(Ordering::Relaxed) {
+            return Err(io::Error::other("Operation cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        if self.read - self.reported >= PROGRESS_INTERVAL && self.size > 0 {
+            self.reported = self.read;
+            self.progress_tracker.update_file_progress(self.read as f32 / self.size as f32);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OperationMode;
+    use crate::test_support::TestDir;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let dir = TestDir::new("archiver");
+        let source = dir.join("documents");
+        fs::create_dir_all(&source).unwrap();
+        let mut files = Vec::new();
+        for (name, contents) in [("a.txt", b"first file".repeat(1000)), ("b.bin", vec![7u8; 3 * PROGRESS_INTERVAL as usize])] {
+            fs::write(source.join(name), &contents).unwrap();
+            files.push(FileItem::new(source.join(name), name.to_string()));
+        }
+
+        let settings = Settings { output_directory: Some(dir.to_path_buf()), ..Settings::default() };
+        let output = archive_path(&settings, &files, ARCHIVE_EXTENSION).unwrap();
+        assert_eq!(output, dir.join("documents.7z"));
+
+        let (sender, events) = mpsc::channel();
+        let total_size = files.iter().map(FileItem::size_on_disk).sum();
+        let tracker = ProgressTracker::new(files.len(), total_size, OperationMode::Archive, sender, None, None);
+        create_archive(&files, &output, "pw", &AtomicBool::new(false), &tracker).unwrap();
+        let completed = events.try_iter().filter(|event| matches!(event, OperationEvent::FileCompleted { .. })).count();
+        assert_eq!(completed, files.len());
+        assert!(!dir.join("documents.7z.partial").exists());
+
+        let extracted = dir.join("extracted");
+        sevenz_rust::decompress_file_with_password(&output, &extracted, "pw".into()).unwrap();
+        for file in &files {
+            assert_eq!(fs::read(extracted.join(&file.name)).unwrap(), fs::read(&file.path).unwrap());
+        }
+        assert!(sevenz_rust::decompress_file_with_password(&output, dir.join("wrong"), "other".into()).is_err());
+
+        // 已存在的归档按冲突策略处理，取消时不留下任何文件
+        let skip = Settings { conflict_policy: ConflictPolicy::Skip, ..settings.clone() };
+        assert!(archive_path(&skip, &files, ARCHIVE_EXTENSION).is_err());
+        let keep_both = Settings { conflict_policy: ConflictPolicy::KeepBoth, ..settings };
+        let second = archive_path(&keep_both, &files, ARCHIVE_EXTENSION).unwrap();
+        assert_eq!(second, dir.join("documents (1).7z"));
+        assert!(create_archive(&files, &second, "pw", &AtomicBool::new(true), &tracker).is_err());
+        assert!(!second.exists());
+        assert!(!dir.join("documents (1).7z.partial").exists());
+
+        // 没有密码时不写出归档
+        assert_eq!(create_archive(&files, &second, "", &AtomicBool::new(false), &tracker).unwrap_err(), "7z archives need a password");
+        assert!(!second.exists());
+        assert!(!dir.join("documents (1).7z.partial").exists());
+    }
+}
@@ -0,0 +1,648 @@
+use super::direct_io::{DirectReader, DirectWriter};
+use super::scan;
+use crate::crypto::header::FileHeader;
+use crate::crypto::traits::{Argon2KeyDerivation, KeyDerivation, StreamControl};
+use crate::crypto::create_crypto_provider;
+use crate::models::{EncryptionAlgorithm, Settings};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// 备份清单在目标中的名称
+pub const MANIFEST_NAME: &str = "krypton-backup.json";
+/// 加密文件在目标中所在的目录
+const DATA_PREFIX: &str = "data";
+const MANIFEST_VERSION: u32 = 1;
+/// 扫描源目录时每发现这么多文件报告一次进度
+const SCAN_PROGRESS_INTERVAL: usize = 1000;
+
+/// 备份目标存储，本地目录之外的存储（例如网络存储）实现该接口即可作为备份目标
+pub trait BackupBackend: Send {
+    /// 显示用的位置描述
+    fn location(&self) -> String;
+
+    /// 写入一个对象，`write` 负责生成内容；写入失败时不能留下不完整的对象
+    fn put(&mut self, name: &str, write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>) -> io::Result<()>;
+
+    /// 读取一个对象
+    fn get(&self, name: &str) -> io::Result<Box<dyn Read>>;
+
+    /// 删除一个不再被清单引用的对象
+    fn remove(&mut self, name: &str) -> io::Result<()>;
+}
+
+/// 本地目录（也可以是挂载的网络共享）
+pub struct LocalBackend {
+    root: PathBuf,
+    direct_io: bool,
+}
+
+impl LocalBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into(), direct_io: false }
+    }
+
+    /// 写入对象时绕过操作系统页缓存
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    fn object_path(&self, name: &str) -> PathBuf {
+        name.split('/').fold(self.root.clone(), |path, part| path.join(part))
+    }
+}
+
+impl BackupBackend for LocalBackend {
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn put(&mut self, name: &str, write: &mut dyn FnMut(&mut dyn Write) -> io::Result<()>) -> io::Result<()> {
+        let path = self.object_path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // 先写入临时文件，完成后再替换，避免中断时留下不完整的对象
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".partial");
+        let temp_path = PathBuf::from(temp_path);
+        let result = (|| {
+            let file = if self.direct_io {
+                let mut writer = DirectWriter::create(&temp_path)?;
+                write(&mut writer)?;
+                writer.finish()?
+            } else {
+                let mut writer = BufWriter::new(File::create(&temp_path)?);
+                write(&mut writer)?;
+                writer.into_inner().map_err(|e| e.into_error())?
+            };
+            file.sync_all()?;
+            fs::rename(&temp_path, &path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    fn get(&self, name: &str) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(BufReader::new(File::open(self.object_path(name))?)))
+    }
+
+    fn remove(&mut self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.object_path(name))
+    }
+}
+
+/// 清单中的一个文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// 相对源目录的路径（以/分隔）
+    pub path: String,
+    pub size: u64,
+    /// 修改时间（Unix秒）
+    pub modified: u64,
+    /// 明文的SHA-256
+    pub hash: String,
+    /// 加密对象在目标中的名称
+    pub object: String,
+    /// 加密对象的SHA-256，校验备份时无需解密
+    pub object_hash: String,
+    /// 源文件是否只读
+    #[serde(default)]
+    pub readonly: bool,
+    /// 源文件被删除时记录的时间（RFC 3339），加密副本仍保留以便恢复
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<String>,
+}
+
+impl ManifestEntry {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted.is_some()
+    }
+}
+
+/// 备份清单
+///
+/// 清单以明文JSON保存（路径、大小、哈希和时间戳可见），并用由密码派生的密钥做HMAC签名
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    /// 第几次备份，用于命名本次写入的加密对象
+    #[serde(default)]
+    pub generation: u32,
+    /// 备份时间（RFC 3339）
+    pub created: String,
+    pub source: PathBuf,
+    pub algorithm: EncryptionAlgorithm,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// 带签名的清单文件
+#[derive(Serialize, Deserialize)]
+struct SignedManifest {
+    manifest: BackupManifest,
+    /// 派生签名密钥的盐值（十六进制）
+    salt: String,
+    /// HMAC-SHA256签名（十六进制）
+    signature: String,
+}
+
+impl BackupManifest {
+    /// 签名并写入备份目标
+    fn save(&self, backend: &mut dyn BackupBackend, password: &str) -> Result<(), String> {
+        let salt = Argon2KeyDerivation::default().generate_salt();
+        let signature = sign(self, password, &salt)?;
+        let signed = SignedManifest {
+            manifest: self.clone(),
+            salt: hex::encode(salt),
+            signature: hex::encode(signature),
+        };
+        let json = serde_json::to_vec_pretty(&signed)
+            .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+        backend.put(MANIFEST_NAME, &mut |writer| writer.write_all(&json))
+            .map_err(|e| format!("Failed to write manifest: {}", e))
+    }
+
+    /// 从备份目标读取清单并校验签名
+    pub fn load(backend: &dyn BackupBackend, password: &str) -> Result<Self, String> {
+        let mut json = Vec::new();
+        backend.get(MANIFEST_NAME)
+            .and_then(|mut reader| reader.read_to_end(&mut json))
+            .map_err(|e| format!("Failed to read manifest in '{}': {}", backend.location(), e))?;
+        let signed: SignedManifest = serde_json::from_slice(&json)
+            .map_err(|e| format!("Manifest is corrupted: {}", e))?;
+        if signed.manifest.version != MANIFEST_VERSION {
+            return Err(format!("Unsupported manifest version: {}", signed.manifest.version));
+        }
+
+        let salt = hex::decode(&signed.salt).map_err(|_| "Manifest salt is corrupted".to_string())?;
+        let signature = hex::decode(&signed.signature).map_err(|_| "Manifest signature is corrupted".to_string())?;
+        let mut mac = manifest_mac(password, &salt)?;
+        mac.update(&manifest_bytes(&signed.manifest)?);
+        mac.verify_slice(&signature)
+            .map_err(|_| "Manifest signature does not match (wrong password or the manifest was modified)".to_string())?;
+        Ok(signed.manifest)
+    }
+
+    /// 源目录中仍然存在的文件
+    pub fn live_entries(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.entries.iter().filter(|entry| !entry.is_deleted())
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.live_entries().map(|entry| entry.size).sum()
+    }
+}
+
+/// 一次备份的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSummary {
+    pub manifest: BackupManifest,
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    /// 本次发现被删除的文件数
+    pub deleted: usize,
+}
+
+/// 校验或恢复的结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupReport {
+    /// 通过校验（或已恢复）的文件数
+    pub ok: usize,
+    /// 每个出问题的文件一条说明
+    pub problems: Vec<String>,
+}
+
+/// 进度回调：已完成数、总数、当前文件
+pub type ProgressCallback<'a> = &'a mut dyn FnMut(usize, usize, &str);
+
+/// 把源目录树加密备份到目标，完成后写入签名清单
+///
+/// 目标中已有清单时进行差异备份：只加密新增或内容已改变的文件，未改变的文件沿用上次的加密副本，
+/// 源目录中已删除的文件在清单中标记为已删除。已有清单必须能用当前密码校验且算法一致。
+/// 使用设置中的密码、算法、扩展名和密码提示；单个文件失败会中止整个备份，
+/// 此时不会写入新清单，目标中上一次的清单仍然有效
+pub fn run_backup(
+    source: &Path,
+    backend: &mut dyn BackupBackend,
+    settings: &Settings,
+    cancel: &AtomicBool,
+    progress: ProgressCallback,
+) -> Result<BackupSummary, String> {
+    if settings.password.is_empty() {
+        return Err("Password cannot be empty".to_string());
+    }
+    if let Some(error) = settings.extension_error() {
+        return Err(error);
+    }
+
+    let previous = load_previous(backend, settings)?;
+    let previous_entries: BTreeMap<&str, &ManifestEntry> = previous.iter()
+        .flat_map(|manifest| &manifest.entries)
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let generation = previous.as_ref().map_or(0, |manifest| manifest.generation) + 1;
+
+    let files = collect_files(source, cancel, progress)?;
+
+    let provider = create_crypto_provider(&settings.encryption_algorithm);
+    let header = FileHeader {
+        algorithm: Some(settings.encryption_algorithm.clone()),
+        ..FileHeader::with_hint(&settings.password_hint)
+    };
+    let now = chrono::Local::now().to_rfc3339();
+    let mut entries = Vec::with_capacity(files.len());
+    let (mut added, mut changed, mut unchanged) = (0, 0, 0);
+
+    for (index, (path, relative)) in files.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Backup cancelled".to_string());
+        }
+        progress(index, files.len(), relative);
+
+        let metadata = fs::metadata(path).map_err(|e| format!("Failed to read '{}': {}", relative, e))?;
+        let modified = metadata.modified().ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs());
+        let readonly = metadata.permissions().readonly();
+
+        // 大小和修改时间都未变时直接沿用；只有修改时间变化时比较内容哈希
+        if let Some(&old) = previous_entries.get(relative.as_str()) {
+            let same_content = old.size == metadata.len()
+                && (old.modified == modified || hash_file(path).is_ok_and(|hash| hash == old.hash));
+            if same_content {
+                entries.push(ManifestEntry { modified, readonly, deleted: None, ..old.clone() });
+                unchanged += 1;
+                continue;
+            }
+        }
+
+        let object = format!("{}/{}.{}.{}", DATA_PREFIX, relative, generation, settings.file_extension);
+        let source: Box<dyn Read> = if settings.direct_io {
+            Box::new(DirectReader::open(path).map_err(|e| format!("Failed to open '{}': {}", relative, e))?)
+        } else {
+            Box::new(BufReader::new(File::open(path).map_err(|e| format!("Failed to open '{}': {}", relative, e))?))
+        };
+        let mut reader = HashingReader::new(source);
+        let mut object_hash = None;
+        backend.put(&object, &mut |writer| {
+            let mut writer = HashingWriter::new(writer);
+            header.write_to(&mut writer)?;
+            provider.encrypt_stream(&settings.password, &mut reader, &mut writer, &StreamControl::default())
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            object_hash = Some(writer.finish());
+            Ok(())
+        }).map_err(|e| format!("Failed to back up '{}': {}", relative, e))?;
+
+        if previous_entries.contains_key(relative.as_str()) {
+            changed += 1;
+        } else {
+            added += 1;
+        }
+        entries.push(ManifestEntry {
+            path: relative.clone(),
+            size: reader.bytes,
+            modified,
+            hash: reader.finish(),
+            object,
+            object_hash: object_hash.unwrap_or_default(),
+            readonly,
+            deleted: None,
+        });
+    }
+    progress(files.len(), files.len(), "");
+
+    // 源目录中已不存在的文件保留加密副本，并标记删除时间
+    let current: BTreeSet<&str> = files.iter().map(|(_, relative)| relative.as_str()).collect();
+    let mut deleted = 0;
+    for old in previous_entries.values().filter(|old| !current.contains(old.path.as_str())) {
+        if !old.is_deleted() {
+            deleted += 1;
+        }
+        entries.push(ManifestEntry { deleted: old.deleted.clone().or_else(|| Some(now.clone())), ..(*old).clone() });
+    }
+
+    let manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        generation,
+        created: now,
+        source: source.to_path_buf(),
+        algorithm: settings.encryption_algorithm.clone(),
+        entries,
+    };
+    manifest.save(backend, &settings.password)?;
+
+    // 新清单保存后，删除被新版本替换的旧加密副本
+    let referenced: BTreeSet<&str> = manifest.entries.iter().map(|entry| entry.object.as_str()).collect();
+    for old in previous_entries.values().filter(|old| !referenced.contains(old.object.as_str())) {
+        let _ = backend.remove(&old.object);
+    }
+
+    Ok(BackupSummary { manifest, added, changed, unchanged, deleted })
+}
+
+/// 读取目标中上一次的清单，没有清单时返回None
+///
+/// 清单存在但无法用当前密码校验、或算法不同时报错，避免用新密码覆盖已有备份
+fn load_previous(backend: &dyn BackupBackend, settings: &Settings) -> Result<Option<BackupManifest>, String> {
+    match backend.get(MANIFEST_NAME) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        _ => {}
+    }
+    let manifest = BackupManifest::load(backend, &settings.password)
+        .map_err(|e| format!("Cannot update the existing backup: {}", e))?;
+    if manifest.algorithm != settings.encryption_algorithm {
+        return Err(format!(
+            "The existing backup uses {}; select the same algorithm or a new destination",
+            manifest.algorithm
+        ));
+    }
+    Ok(Some(manifest))
+}
+
+/// 校验清单签名，并检查每个加密对象都存在且未被修改（不解密）
+pub fn verify_backup(
+    backend: &dyn BackupBackend,
+    password: &str,
+    cancel: &AtomicBool,
+    progress: ProgressCallback,
+) -> Result<BackupReport, String> {
+    let manifest = BackupManifest::load(backend, password)?;
+    let mut report = BackupReport::default();
+
+    for (index, entry) in manifest.entries.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        progress(index, manifest.entries.len(), &entry.path);
+        let result = backend.get(&entry.object).and_then(|mut reader| {
+            let mut hasher = Sha256::new();
+            io::copy(&mut reader, &mut hasher)?;
+            Ok(hex::encode(hasher.finalize()))
+        });
+        match result {
+            Ok(hash) if hash == entry.object_hash => report.ok += 1,
+            Ok(_) => report.problems.push(format!("{}: encrypted copy was modified", entry.path)),
+            Err(e) => report.problems.push(format!("{}: {}", entry.path, e)),
+        }
+    }
+    progress(manifest.entries.len(), manifest.entries.len(), "");
+    Ok(report)
+}
+
+/// 把备份恢复到目标目录，重建目录结构，恢复修改时间和只读属性，并在解密时逐个校验哈希
+///
+/// `selection` 为Some时只恢复其中列出的路径（可以包含已删除的文件），否则恢复源目录中仍存在的文件；
+/// 目标目录中已存在的同名文件会被跳过
+pub fn restore_backup(
+    backend: &dyn BackupBackend,
+    password: &str,
+    target: &Path,
+    selection: Option<&BTreeSet<String>>,
+    cancel: &AtomicBool,
+    progress: ProgressCallback,
+) -> Result<BackupReport, String> {
+    let manifest = BackupManifest::load(backend, password)?;
+    let provider = create_crypto_provider(&manifest.algorithm);
+    let entries: Vec<&ManifestEntry> = manifest.entries.iter()
+        .filter(|entry| match selection {
+            Some(selection) => selection.contains(&entry.path),
+            None => !entry.is_deleted(),
+        })
+        .collect();
+    let mut report = BackupReport::default();
+
+    for (index, entry) in entries.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Cancelled".to_string());
+        }
+        progress(index, entries.len(), &entry.path);
+        let Some(output_path) = safe_join(target, &entry.path) else {
+            report.problems.push(format!("{}: unsafe path in manifest", entry.path));
+            continue;
+        };
+        if output_path.exists() {
+            report.problems.push(format!("{}: already exists in the target, skipped", entry.path));
+            continue;
+        }
+
+        let result = (|| -> Result<(), String> {
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let mut object = backend.get(&entry.object).map_err(|e| e.to_string())?;
+            let (_, mut reader) = FileHeader::read_from(&mut object).map_err(|e| e.to_string())?;
+            let file = File::create(&output_path).map_err(|e| e.to_string())?;
+            let mut writer = HashingWriter::new(BufWriter::new(file));
+            provider.decrypt_stream(password, &mut reader, &mut writer, &StreamControl::default()).map_err(|e| e.to_string())?;
+            let hash = writer.finish();
+            let file = writer.inner.into_inner().map_err(|e| e.into_error().to_string())?;
+            if hash != entry.hash {
+                return Err("decrypted content does not match the manifest".to_string());
+            }
+            let _ = file.set_modified(UNIX_EPOCH + Duration::from_secs(entry.modified));
+            if entry.readonly {
+                let mut permissions = file.metadata().map_err(|e| e.to_string())?.permissions();
+                permissions.set_readonly(true);
+                fs::set_permissions(&output_path, permissions).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => report.ok += 1,
+            Err(e) => {
+                let _ = fs::remove_file(&output_path);
+                report.problems.push(format!("{}: {}", entry.path, e));
+            }
+        }
+    }
+    progress(entries.len(), entries.len(), "");
+    Ok(report)
+}
+
+/// 并行扫描源目录中的普通文件（跳过符号链接），扫描期间报告已发现的文件数，
+/// 返回按路径排序的路径和以/分隔的相对路径
+fn collect_files(source: &Path, cancel: &AtomicBool, progress: ProgressCallback) -> Result<Vec<(PathBuf, String)>, String> {
+    let mut files = Vec::new();
+    scan::scan_tree_into(source, cancel, |file| {
+        files.push((file.path, file.relative));
+        if files.len() % SCAN_PROGRESS_INTERVAL == 0 {
+            progress(0, 0, &format!("scanning, {} files found", files.len()));
+        }
+    }).map_err(|e| format!("Failed to read '{}': {}", source.display(), e))?;
+    if cancel.load(Ordering::Relaxed) {
+        return Err("Backup cancelled".to_string());
+    }
+
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(files)
+}
+
+/// 计算文件内容的SHA-256
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// 把清单中的相对路径拼接到目标目录，拒绝绝对路径和 `..`
+pub(crate) fn safe_join(target: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    relative.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| target.join(relative))
+}
+
+/// 签名覆盖的清单字节
+fn manifest_bytes(manifest: &BackupManifest) -> Result<Vec<u8>, String> {
+    serde_json::to_vec(manifest).map_err(|e| format!("Failed to serialize manifest: {}", e))
+}
+
+fn manifest_mac(password: &str, salt: &[u8]) -> Result<Hmac<Sha256>, String> {
+    let key = Argon2KeyDerivation::default().derive_key(password, salt).map_err(|e| e.to_string())?;
+    Hmac::<Sha256>::new_from_slice(&key).map_err(|e| e.to_string())
+}
+
+fn sign(manifest: &BackupManifest, password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
+    let mut mac = manifest_mac(password, salt)?;
+    mac.update(&manifest_bytes(manifest)?);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+/// 读取时计算SHA-256和字节数
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, hasher: Sha256::new(), bytes: 0 }
+    }
+
+    fn finish(&mut self) -> String {
+        hex::encode(std::mem::take(&mut self.hasher).finalize())
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.hasher.update(&buf[..count]);
+        self.bytes += count as u64;
+        Ok(count)
+    }
+}
+
+/// 写入时计算SHA-256
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(&mut self) -> String {
+        hex::encode(std::mem::take(&mut self.hasher).finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.hasher.update(&buf[..count]);
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_backup_verify_and_restore() {
+        let dir = TestDir::new("backup");
+        let source = dir.join("source");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), b"alpha").unwrap();
+        fs::write(source.join("nested").join("b.bin"), vec![7u8; 3000]).unwrap();
+
+        let settings = Settings { password: "secret".to_string(), ..Default::default() };
+        let mut backend = LocalBackend::new(dir.join("backup"));
+        let manifest = run_backup(&source, &mut backend, &settings, &AtomicBool::new(false), &mut |_, _, _| {}).unwrap().manifest;
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(manifest.entries[1].path, "nested/b.bin");
+
+        let report = verify_backup(&backend, "secret", &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!((report.ok, report.problems.len()), (2, 0));
+        assert!(verify_backup(&backend, "wrong", &AtomicBool::new(false), &mut |_, _, _| {}).is_err());
+
+        // 只恢复选中的文件
+        let restored = dir.join("restored");
+        let selection = BTreeSet::from(["nested/b.bin".to_string()]);
+        let report = restore_backup(&backend, "secret", &restored, Some(&selection), &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!(report.ok, 1);
+        assert_eq!(fs::read(restored.join("nested").join("b.bin")).unwrap(), vec![7u8; 3000]);
+        assert!(!restored.join("a.txt").exists());
+
+        // 篡改加密对象后校验失败
+        fs::write(backend.object_path(&manifest.entries[0].object), b"x").unwrap();
+        let report = verify_backup(&backend, "secret", &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!(report.problems.len(), 1);
+    }
+
+    #[test]
+    fn test_differential_backup() {
+        let dir = TestDir::new("backup_diff");
+        let source = dir.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("keep.txt"), b"same").unwrap();
+        fs::write(source.join("edit.txt"), b"before").unwrap();
+        fs::write(source.join("gone.txt"), b"bye").unwrap();
+
+        let settings = Settings { password: "secret".to_string(), ..Default::default() };
+        let mut backend = LocalBackend::new(dir.join("backup"));
+        let first = run_backup(&source, &mut backend, &settings, &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!(first.added, 3);
+
+        fs::write(source.join("edit.txt"), b"after, longer").unwrap();
+        fs::remove_file(source.join("gone.txt")).unwrap();
+        fs::write(source.join("new.txt"), b"hello").unwrap();
+        let second = run_backup(&source, &mut backend, &settings, &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!((second.added, second.changed, second.unchanged, second.deleted), (1, 1, 1, 1));
+        assert_eq!(second.manifest.live_entries().count(), 3);
+
+        // 被替换的旧副本已删除，已删除文件的副本仍可恢复
+        let old_edit = first.manifest.entries.iter().find(|entry| entry.path == "edit.txt").unwrap();
+        assert!(!backend.object_path(&old_edit.object).exists());
+        let restored = dir.join("restored");
+        let selection = BTreeSet::from(["gone.txt".to_string()]);
+        restore_backup(&backend, "secret", &restored, Some(&selection), &AtomicBool::new(false), &mut |_, _, _| {}).unwrap();
+        assert_eq!(fs::read(restored.join("gone.txt")).unwrap(), b"bye");
+
+        // 不能用其他密码更新已有备份
+        let other = Settings { password: "other".to_string(), ..Default::default() };
+        assert!(run_backup(&source, &mut backend, &other, &AtomicBool::new(false), &mut |_, _, _| {}).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! 校验和附属文件
+//!
+//! 加密时可以为每个输出文件写出 `<文件名>.sha256` 或 `<文件名>.b3`，内容与 sha256sum / b3sum 的输出格式相同，
+//! 传输后可以直接用这些工具检查（例如 `sha256sum -c name.enc.sha256`）。解密前如果输入文件旁有附属文件则先核对
+
+use crate::models::ChecksumSidecar;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// 附属文件的路径：在文件名后追加扩展名
+pub fn sidecar_path(path: &Path, kind: ChecksumSidecar) -> Option<PathBuf> {
+    let extension = kind.extension()?;
+    let mut name = path.file_name()?.to_os_string();
+    name.push(".");
+    name.push(extension);
+    Some(path.with_file_name(name))
+}
+
+/// 文件旁已有的附属文件
+pub fn existing_sidecars(path: &Path) -> Vec<(ChecksumSidecar, PathBuf)> {
+    ChecksumSidecar::ALL.into_iter()
+        .filter_map(|kind| Some((kind, sidecar_path(path, kind)?)))
+        .filter(|(_, sidecar)| sidecar.is_file())
+        .collect()
+}
+
+/// 计算文件的校验和（十六进制）
+pub fn file_checksum(path: &Path, kind: ChecksumSidecar) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    match kind {
+        ChecksumSidecar::None => Ok(String::new()),
+        ChecksumSidecar::Sha256 => {
+            let mut hasher = Sha256::new();
+            read_all(&mut file, &mut buffer, |data| hasher.update(data))?;
+            Ok(hex::encode(hasher.finalize()))
+        }
+        ChecksumSidecar::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            read_all(&mut file, &mut buffer, |data| {
+                hasher.update(data);
+            })?;
+            Ok(hasher.finalize().to_hex().to_string())
+        }
+    }
+}
+
+fn read_all(reader: &mut impl Read, buffer: &mut [u8], mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    loop {
+        match reader.read(buffer)? {
+            0 => return Ok(()),
+            n => update(&buffer[..n]),
+        }
+    }
+}
+
+/// 为文件写出附属文件，返回附属文件的路径（未启用时返回None）
+pub fn write_sidecar(path: &Path, kind: ChecksumSidecar) -> io::Result<Option<PathBuf>> {
+    let (Some(sidecar), Some(name)) = (sidecar_path(path, kind), path.file_name()) else {
+        return Ok(None);
+    };
+    let checksum = file_checksum(path, kind)?;
+    // 与 sha256sum / b3sum 相同：校验和、两个空格、文件名
+    fs::write(&sidecar, format!("{}  {}\n", checksum, name.to_string_lossy()))?;
+    Ok(Some(sidecar))
+}
+
+/// 核对文件旁所有的附属文件，不一致时返回错误信息
+pub fn verify_sidecars(path: &Path) -> Result<(), String> {
+    for (kind, sidecar) in existing_sidecars(path) {
+        let content = fs::read_to_string(&sidecar)
+            .map_err(|e| format!("Cannot read checksum file '{}': {}", sidecar.display(), e))?;
+        let expected = parse_checksum(&content)
+            .ok_or_else(|| format!("Checksum file '{}' is not in {} format", sidecar.display(), kind))?;
+        let actual = file_checksum(path, kind)
+            .map_err(|e| format!("Cannot compute the {} checksum: {}", kind, e))?;
+        if !actual.eq_ignore_ascii_case(&expected) {
+            return Err(format!("{} checksum does not match '{}', the file was damaged in transfer", kind, sidecar.display()));
+        }
+    }
+    Ok(())
+}
+
+/// 读取附属文件第一行中的校验和（`<hex>  <name>` 或 `<hex> *<name>`）
+fn parse_checksum(content: &str) -> Option<String> {
+    let checksum = content.lines().next()?.split_whitespace().next()?;
+    (checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit())).then(|| checksum.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_sidecar_round_trip() {
+        let dir = TestDir::new("checksum");
+        let path = dir.join("data.enc");
+        fs::write(&path, b"abc").unwrap();
+
+        let sidecar = write_sidecar(&path, ChecksumSidecar::Sha256).unwrap().unwrap();
+        assert_eq!(sidecar, dir.join("data.enc.sha256"));
+        assert_eq!(
+            fs::read_to_string(&sidecar).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  data.enc\n"
+        );
+        write_sidecar(&path, ChecksumSidecar::Blake3).unwrap();
+        assert_eq!(existing_sidecars(&path).len(), 2);
+        assert_eq!(write_sidecar(&path, ChecksumSidecar::None).unwrap(), None);
+        assert!(verify_sidecars(&path).is_ok());
+
+        fs::write(&path, b"abd").unwrap();
+        assert!(verify_sidecars(&path).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Krypton容器：把一组文件连同相对路径和修改时间加密打包为一个 `.krypton` 文件
+//!
+//! 结构：容器标记 | 文件头（与单个加密文件相同：密钥派生参数、批处理盐值、算法和密钥文件校验）|
+//! 各条目的加密流 | 加密的索引（JSON）| 尾部（索引偏移、索引长度、容器标记）。
+//! 整个容器只运行一次密钥派生，条目和索引各自用主密钥和自己的盐值派生密钥，
+//! 所以列出条目只需解密末尾的索引，提取单个条目只需解密该条目的数据
+
+use super::archiver::ProgressReader;
+use super::backup::safe_join;
+use crate::crypto::format::Counted;
+use crate::crypto::header::FileHeader;
+use crate::crypto::traits::{BatchKey, StreamControl};
+use crate::crypto::{create_crypto_provider_with_batch_key, create_decryption_provider, keyfile, CryptoProvider};
+use crate::models::{FileItem, OperationEvent, Settings};
+use crate::progress::ProgressTracker;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, UNIX_EPOCH};
+
+/// 容器文件的扩展名
+pub const CONTAINER_EXTENSION: &str = "krypton";
+/// 容器开头和结尾的标记
+pub const CONTAINER_MAGIC: &[u8; 8] = b"KRYPTONC";
+/// 尾部长度：索引偏移、索引长度和容器标记
+const FOOTER_LEN: u64 = 8 + 8 + CONTAINER_MAGIC.len() as u64;
+/// 索引的最大长度，避免损坏的尾部导致分配过多内存
+const MAX_INDEX_LEN: u64 = 64 * 1024 * 1024;
+
+/// 容器中的一个文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerEntry {
+    /// 相对路径，以 `/` 分隔
+    pub path: String,
+    /// 明文长度
+    pub size: u64,
+    /// 修改时间（Unix秒），无法读取时为None
+    pub modified: Option<u64>,
+    /// 加密数据在容器中的偏移和长度
+    offset: u64,
+    length: u64,
+}
+
+/// 文件是否以容器标记开头
+pub fn is_container(path: &Path) -> bool {
+    let mut magic = [0u8; CONTAINER_MAGIC.len()];
+    File::open(path).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == CONTAINER_MAGIC
+}
+
+/// 把 `files` 加密打包为容器 `output`，条目路径相对于所有文件的公共上级目录。
+/// 先写入临时文件，完成后再改名；失败或 `should_stop` 被设置时删除不完整的容器
+pub fn create_container(
+    settings: &Settings,
+    files: &[FileItem],
+    output: &Path,
+    should_stop: &AtomicBool,
+    progress_tracker: &ProgressTracker,
+) -> Result<(), String> {
+    let mut temp_path = output.as_os_str().to_owned();
+    temp_path.push(".partial");
+    let temp_path = PathBuf::from(temp_path);
+
+    let result = write_container(settings, files, &temp_path, should_stop, progress_tracker)
+        .and_then(|_| fs::rename(&temp_path, output)
+            .map_err(|e| format!("Failed to write container '{}': {}", output.display(), e)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    } else {
+        for (index, file) in files.iter().enumerate() {
+            progress_tracker.send_event(OperationEvent::FileCompleted { index, name: file.name.clone(), output: output.to_path_buf() });
+        }
+    }
+    result
+}
+
+fn write_container(
+    settings: &Settings,
+    files: &[FileItem],
+    path: &Path,
+    should_stop: &AtomicBool,
+    progress_tracker: &ProgressTracker,
+) -> Result<(), String> {
+    let write_error = |e: io::Error| format!("Failed to write container '{}': {}", path.display(), e);
+    let (password, keyfile_check) = keyfile::bind_password(settings.keyfile.as_deref(), &settings.password)?;
+    let key = BatchKey::generate(&password, settings.kdf)
+        .map_err(|e| format!("Failed to derive the container key: {}", e))?;
+    let provider = create_crypto_provider_with_batch_key(&settings.encryption_algorithm, &key)
+        .ok_or_else(|| format!("{} cannot be used for containers", settings.encryption_algorithm))?;
+    let header = FileHeader {
+        keyfile_check,
+        kdf_params: (!settings.kdf.is_default()).then_some(settings.kdf),
+        batch_salt: Some(key.salt),
+        algorithm: Some(settings.encryption_algorithm.clone()),
+        ..FileHeader::with_hint(&settings.password_hint)
+    };
+
+    let mut writer = Counted::new(BufWriter::new(File::create(path).map_err(write_error)?));
+    writer.write_all(CONTAINER_MAGIC).map_err(write_error)?;
+    header.write_to(&mut writer).map_err(write_error)?;
+
+    let base = common_base(files);
+    let mut entries = Vec::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        if should_stop.load(Ordering::Relaxed) {
+            return Err("Operation cancelled".to_string());
+        }
+        progress_tracker.start_file(index, file);
+        let fail = |error: String| {
+            progress_tracker.send_event(OperationEvent::FileFailed { index, name: file.name.clone(), error: error.clone() });
+            progress_tracker.fail_file();
+            error
+        };
+        let input = File::open(&file.path)
+            .map_err(|e| fail(format!("Failed to open file '{}': {}", file.name, e)))?;
+        let modified = input.metadata().and_then(|metadata| metadata.modified()).ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+        let offset = writer.count();
+        let mut reader = Counted::new(ProgressReader::new(input, file.size_on_disk(), should_stop, progress_tracker));
+        provider.encrypt_stream(&password, &mut reader, &mut writer, &StreamControl::default())
+            .map_err(|e| match should_stop.load(Ordering::Relaxed) {
+                true => "Operation cancelled".to_string(),
+                false => fail(format!("Failed to add file '{}' to the container: {}", file.name, e)),
+            })?;
+        entries.push(ContainerEntry {
+            path: entry_path(&base, file),
+            size: reader.count(),
+            modified,
+            offset,
+            length: writer.count() - offset,
+        });
+        progress_tracker.complete_file(file.size_on_disk(), None);
+    }
+
+    // 索引同样加密，不解密就看不到文件名和大小
+    let index = serde_json::to_vec(&entries).map_err(|e| format!("Failed to serialize the container index: {}", e))?;
+    let index_offset = writer.count();
+    provider.encrypt_stream(&password, &mut index.as_slice(), &mut writer, &StreamControl::default())
+        .map_err(|e| format!("Failed to encrypt the container index: {}", e))?;
+    let index_len = writer.count() - index_offset;
+    writer.write_all(&index_offset.to_le_bytes()).map_err(write_error)?;
+    writer.write_all(&index_len.to_le_bytes()).map_err(write_error)?;
+    writer.write_all(CONTAINER_MAGIC).map_err(write_error)?;
+    let file = writer.into_inner().into_inner().map_err(|e| write_error(e.into_error()))?;
+    file.sync_all().map_err(write_error)
+}
+
+/// 所有文件的公共上级目录
+fn common_base(files: &[FileItem]) -> PathBuf {
+    let mut parents = files.iter().filter_map(|file| file.path.parent());
+    let Some(first) = parents.next() else {
+        return PathBuf::new();
+    };
+    parents.fold(first.to_path_buf(), |base, parent| {
+        base.ancestors().find(|ancestor| parent.starts_with(ancestor)).unwrap_or(Path::new("")).to_path_buf()
+    })
+}
+
+/// 条目在容器中的相对路径，不在公共上级目录下时只保留文件名
+fn entry_path(base: &Path, file: &FileItem) -> String {
+    match file.path.strip_prefix(base) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join("/"),
+        _ => file.name.clone(),
+    }
+}
+
+/// 打开的容器：已解密索引，可以列出和提取条目
+pub struct Container {
+    path: PathBuf,
+    password: String,
+    provider: Box<dyn CryptoProvider>,
+    entries: Vec<ContainerEntry>,
+}
+
+impl Container {
+    /// 打开容器并解密索引，需要加密时使用的密钥文件（如果有）
+    pub fn open(path: &Path, password: &str, keyfile: Option<&Path>) -> Result<Self, String> {
+        let open_error = |e: String| format!("Failed to open container '{}': {}", path.display(), e);
+        let mut file = File::open(path).map_err(|e| open_error(e.to_string()))?;
+        let mut magic = [0u8; CONTAINER_MAGIC.len()];
+        file.read_exact(&mut magic).map_err(|e| open_error(e.to_string()))?;
+        if &magic != CONTAINER_MAGIC {
+            return Err(open_error("not a Krypton container".to_string()));
+        }
+        let header = match FileHeader::read_from(&mut file).map_err(|e| open_error(e.to_string()))? {
+            (Some(header), _) => header,
+            (None, _) => return Err(open_error("the container header is missing".to_string())),
+        };
+        let algorithm = header.algorithm.clone()
+            .ok_or_else(|| open_error("the container header does not name an algorithm".to_string()))?;
+        let password = keyfile::unlock_password(keyfile, password, header.keyfile_check.as_ref())?;
+        let provider = create_decryption_provider(&algorithm, Some(&header), &password)
+            .map_err(|e| open_error(e.to_string()))?;
+
+        let (index_offset, index_len) = read_footer(&mut file).map_err(|e| open_error(e.to_string()))?;
+        if index_len > MAX_INDEX_LEN {
+            return Err(open_error("the container index is too large".to_string()));
+        }
+        file.seek(SeekFrom::Start(index_offset)).map_err(|e| open_error(e.to_string()))?;
+        let mut index = Vec::new();
+        provider.decrypt_stream(&password, &mut BufReader::new(file).take(index_len), &mut index, &StreamControl::default())
+            .map_err(|e| open_error(e.to_string()))?;
+        let entries = serde_json::from_slice(&index)
+            .map_err(|e| open_error(format!("the container index is invalid: {}", e)))?;
+        Ok(Self { path: path.to_path_buf(), password, provider, entries })
+    }
+
+    /// 容器中的所有条目
+    pub fn entries(&self) -> &[ContainerEntry] {
+        &self.entries
+    }
+
+    /// 按相对路径查找条目
+    pub fn entry(&self, path: &str) -> Option<&ContainerEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// 把一个条目的明文写到 `writer`，只读取和解密该条目的数据
+    pub fn extract(&self, entry: &ContainerEntry, writer: &mut dyn Write) -> Result<(), String> {
+        let extract_error = |e: String| format!("Failed to extract '{}': {}", entry.path, e);
+        let mut file = File::open(&self.path).map_err(|e| extract_error(e.to_string()))?;
+        file.seek(SeekFrom::Start(entry.offset)).map_err(|e| extract_error(e.to_string()))?;
+        let mut reader = Counted::new(BufReader::new(file).take(entry.length));
+        let mut writer = Counted::new(writer);
+        self.provider.decrypt_stream(&self.password, &mut reader, &mut writer, &StreamControl::default())
+            .map_err(|e| extract_error(e.to_string()))?;
+        if writer.count() != entry.size {
+            return Err(extract_error(format!("{} bytes expected, {} decrypted", entry.size, writer.count())));
+        }
+        Ok(())
+    }
+
+    /// 把一个条目提取为文件 `output` 并恢复修改时间，失败时不留下不完整的文件
+    pub fn extract_to(&self, entry: &ContainerEntry, output: &Path) -> Result<(), String> {
+        let write_error = |e: io::Error| format!("Failed to write '{}': {}", output.display(), e);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+        let mut writer = BufWriter::new(File::create(output).map_err(write_error)?);
+        let result = self.extract(entry, &mut writer)
+            .and_then(|_| writer.into_inner().map_err(|e| write_error(e.into_error())))
+            .and_then(|file| match entry.modified {
+                Some(secs) => file.set_modified(UNIX_EPOCH + Duration::from_secs(secs)).map_err(write_error),
+                None => Ok(()),
+            });
+        if result.is_err() {
+            let _ = fs::remove_file(output);
+        }
+        result
+    }
+
+    /// 把一个条目按相对路径提取到 `destination` 下，返回写出的文件；拒绝指向目录之外的路径
+    pub fn extract_into(&self, entry: &ContainerEntry, destination: &Path) -> Result<PathBuf, String> {
+        let output = safe_join(destination, &entry.path)
+            .ok_or_else(|| format!("Refusing to extract '{}' outside the destination", entry.path))?;
+        self.extract_to(entry, &output).map(|_| output)
+    }
+
+    /// 把所有条目按相对路径提取到 `destination` 下，返回写出的文件
+    pub fn extract_all(&self, destination: &Path) -> Result<Vec<PathBuf>, String> {
+        self.entries.iter().map(|entry| self.extract_into(entry, destination)).collect()
+    }
+}
+
+/// 读取尾部的索引偏移和长度
+fn read_footer(file: &mut File) -> io::Result<(u64, u64)> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let len = file.seek(SeekFrom::End(0))?;
+    if len < FOOTER_LEN {
+        return Err(invalid("the container is truncated"));
+    }
+    file.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+    let mut footer = [0u8; FOOTER_LEN as usize];
+    file.read_exact(&mut footer)?;
+    if &footer[16..] != CONTAINER_MAGIC {
+        return Err(invalid("the container is truncated"));
+    }
+    let index_offset = u64::from_le_bytes(footer[..8].try_into().expect("8 bytes"));
+    let index_len = u64::from_le_bytes(footer[8..16].try_into().expect("8 bytes"));
+    if index_offset.checked_add(index_len).is_none_or(|end| end > len - FOOTER_LEN) {
+        return Err(invalid("the container index is out of range"));
+    }
+    Ok((index_offset, index_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{KdfAlgorithm, KdfParams, OperationMode};
+    use crate::test_support::TestDir;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_container_round_trip() {
+        let dir = TestDir::new("container");
+        let source = dir.join("project");
+        fs::create_dir_all(source.join("docs")).unwrap();
+        let mut files = Vec::new();
+        for (name, contents) in [("readme.txt", b"top level".repeat(100)), ("docs/guide.md", b"nested file".repeat(5000)), ("empty", Vec::new())] {
+            fs::write(source.join(name), &contents).unwrap();
+            files.push(FileItem::new(source.join(name), name.rsplit('/').next().unwrap().to_string()));
+        }
+        let modified = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::options().write(true).open(source.join("readme.txt")).unwrap().set_modified(modified).unwrap();
+
+        let kdf = KdfParams { memory_kib: 1024, ..KdfParams::for_algorithm(KdfAlgorithm::Scrypt) };
+        let settings = Settings { password: "pw".to_string(), kdf, ..Settings::default() };
+        let output = dir.join("project.krypton");
+        let (sender, _events) = mpsc::channel();
+        let total_size = files.iter().map(FileItem::size_on_disk).sum();
+        let tracker = ProgressTracker::new(files.len(), total_size, OperationMode::Container, sender, None, None);
+        create_container(&settings, &files, &output, &AtomicBool::new(false), &tracker).unwrap();
+        assert!(is_container(&output));
+        assert!(!is_container(&source.join("readme.txt")));
+
+        let container = Container::open(&output, "pw", None).unwrap();
+        let paths: Vec<&str> = container.entries().iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["readme.txt", "docs/guide.md", "empty"]);
+        let guide = container.entry("docs/guide.md").unwrap();
+        assert_eq!(guide.size, 11 * 5000);
+        let mut contents = Vec::new();
+        container.extract(guide, &mut contents).unwrap();
+        assert_eq!(contents, fs::read(source.join("docs/guide.md")).unwrap());
+
+        let extracted = dir.join("extracted");
+        assert_eq!(container.extract_all(&extracted).unwrap().len(), files.len());
+        for name in ["readme.txt", "docs/guide.md", "empty"] {
+            assert_eq!(fs::read(extracted.join(name)).unwrap(), fs::read(source.join(name)).unwrap());
+        }
+        assert_eq!(fs::metadata(extracted.join("readme.txt")).unwrap().modified().unwrap(), modified);
+
+        assert!(Container::open(&output, "wrong", None).is_err());
+        let data = fs::read(&output).unwrap();
+        fs::write(&output, &data[..data.len() - 1]).unwrap();
+        assert!(Container::open(&output, "pw", None).is_err());
+    }
+}
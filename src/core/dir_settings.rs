@@ -0,0 +1,93 @@
+use super::app_data;
+use crate::models::{EncryptionAlgorithm, Settings};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 目录设置记录的存储文件名
+const DIR_SETTINGS_FILE_NAME: &str = "directory_settings.json";
+
+/// 某个目录上次使用的设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryPreset {
+    pub file_extension: String,
+    pub encryption_algorithm: EncryptionAlgorithm,
+    pub encrypt_filename: bool,
+}
+
+impl DirectoryPreset {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            file_extension: settings.file_extension.clone(),
+            encryption_algorithm: settings.encryption_algorithm.clone(),
+            encrypt_filename: settings.encrypt_filename,
+        }
+    }
+
+    /// 把记录的设置应用到当前设置
+    pub fn apply(&self, settings: &mut Settings) {
+        settings.file_extension = self.file_extension.clone();
+        settings.encryption_algorithm = self.encryption_algorithm.clone();
+        settings.encrypt_filename = self.encrypt_filename;
+    }
+}
+
+/// 按目录记录上次使用的扩展名、算法和文件名加密选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirectorySettings {
+    entries: BTreeMap<PathBuf, DirectoryPreset>,
+}
+
+impl DirectorySettings {
+    /// 加载记录，不存在时返回空记录
+    pub fn load() -> io::Result<Self> {
+        app_data::load_json(&app_data::app_data_file(DIR_SETTINGS_FILE_NAME)?)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        app_data::save_config(&app_data::app_data_file(DIR_SETTINGS_FILE_NAME)?, self)
+    }
+
+    /// 查询目录上次使用的设置
+    pub fn get(&self, directory: &Path) -> Option<&DirectoryPreset> {
+        self.entries.get(&directory_key(directory))
+    }
+
+    /// 记录目录本次使用的设置
+    pub fn remember(&mut self, directory: &Path, settings: &Settings) {
+        self.entries.insert(directory_key(directory), DirectoryPreset::from_settings(settings));
+    }
+}
+
+/// 使用规范化路径作为键，使不同写法的同一目录对应同一条记录
+fn directory_key(directory: &Path) -> PathBuf {
+    fs::canonicalize(directory).unwrap_or_else(|_| directory.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_remember_and_restore() {
+        let dir = TestDir::new("dir_settings");
+
+        let used = Settings {
+            file_extension: "kry".to_string(),
+            encryption_algorithm: EncryptionAlgorithm::ChaCha20,
+            encrypt_filename: false,
+            ..Default::default()
+        };
+        let mut memory = DirectorySettings::default();
+        memory.remember(&dir, &used);
+
+        // 同一目录的不同写法也能找到记录
+        let mut restored = Settings::default();
+        memory.get(&dir.join(".")).unwrap().apply(&mut restored);
+        assert_eq!(DirectoryPreset::from_settings(&restored), DirectoryPreset::from_settings(&used));
+        assert!(memory.get(&std::env::temp_dir()).is_none());
+    }
+}
@@ -0,0 +1,221 @@
+//! 绕过操作系统页缓存的文件读写（Linux的O_DIRECT、Windows的FILE_FLAG_NO_BUFFERING、macOS的F_NOCACHE）
+//!
+//! 直接I/O要求缓冲区地址、读写长度和文件偏移都按扇区对齐。读写器内部使用对齐的缓冲区，
+//! 总是以整块读写；写入结束时最后一块补零写出，再把文件截断到实际长度。
+//! 文件系统不支持直接I/O时（例如tmpfs）自动退回普通读写
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// 对齐要求（覆盖4K扇区的磁盘）
+const ALIGNMENT: usize = 4096;
+/// 每次读写的字节数，须为 [`ALIGNMENT`] 的整数倍
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// 按 [`ALIGNMENT`] 对齐的缓冲区
+struct AlignedBuffer {
+    storage: Vec<u8>,
+    offset: usize,
+}
+
+impl AlignedBuffer {
+    fn new() -> Self {
+        let storage = vec![0u8; BLOCK_SIZE + ALIGNMENT];
+        let offset = storage.as_ptr().align_offset(ALIGNMENT);
+        Self { storage, offset }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.storage[self.offset..self.offset + BLOCK_SIZE]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.storage[self.offset..self.offset + BLOCK_SIZE]
+    }
+}
+
+/// 以直接I/O方式打开文件，返回文件和是否真正启用了直接I/O
+fn open_direct(path: &Path, options: &mut OpenOptions) -> io::Result<(File, bool)> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut direct = options.clone();
+        match direct.custom_flags(libc::O_DIRECT).open(path) {
+            Ok(file) => return Ok((file, true)),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+        let mut direct = options.clone();
+        if let Ok(file) = direct.custom_flags(FILE_FLAG_NO_BUFFERING).open(path) {
+            return Ok((file, true));
+        }
+    }
+
+    let file = options.open(path)?;
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::unix::io::AsRawFd;
+        // macOS没有O_DIRECT，F_NOCACHE不要求对齐，但整块读写依然更快
+        unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) };
+    }
+    Ok((file, false))
+}
+
+/// 直接I/O读取器
+pub struct DirectReader {
+    file: File,
+    buffer: AlignedBuffer,
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl DirectReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let (file, _) = open_direct(path, OpenOptions::new().read(true))?;
+        Ok(Self {
+            file,
+            buffer: AlignedBuffer::new(),
+            start: 0,
+            end: 0,
+            eof: false,
+        })
+    }
+
+    /// 读取下一整块；只有文件末尾的读取会返回不足一块
+    fn fill(&mut self) -> io::Result<()> {
+        let mut filled = 0;
+        while filled < BLOCK_SIZE {
+            match self.file.read(&mut self.buffer.as_mut_slice()[filled..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            // 直接I/O下不足一块说明已到文件末尾，偏移不再对齐，不能继续读取
+            if filled % ALIGNMENT != 0 {
+                self.eof = true;
+                break;
+            }
+        }
+        self.start = 0;
+        self.end = filled;
+        Ok(())
+    }
+}
+
+impl Read for DirectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.end {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.end - self.start);
+        buf[..n].copy_from_slice(&self.buffer.as_slice()[self.start..self.start + n]);
+        self.start += n;
+        Ok(n)
+    }
+}
+
+/// 直接I/O写入器，必须调用 [`DirectWriter::finish`] 写出最后一块
+pub struct DirectWriter {
+    file: File,
+    buffer: AlignedBuffer,
+    len: usize,
+    written: u64,
+    direct: bool,
+}
+
+impl DirectWriter {
+    /// 创建（或截断）文件
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let (file, direct) = open_direct(path, OpenOptions::new().write(true).create(true).truncate(true))?;
+        Ok(Self {
+            file,
+            buffer: AlignedBuffer::new(),
+            len: 0,
+            written: 0,
+            direct,
+        })
+    }
+
+    pub(crate) fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// 已写入的逻辑长度
+    pub fn position(&self) -> u64 {
+        self.written + self.len as u64
+    }
+
+    fn flush_block(&mut self, len: usize) -> io::Result<()> {
+        self.file.write_all(&self.buffer.as_slice()[..len])?;
+        self.len = 0;
+        Ok(())
+    }
+
+    /// 写出剩余数据（直接I/O时补零到对齐长度），截断到实际长度后返回文件
+    pub fn finish(mut self) -> io::Result<File> {
+        let total = self.position();
+        if self.len > 0 {
+            let padded = if self.direct { self.len.next_multiple_of(ALIGNMENT) } else { self.len };
+            self.buffer.as_mut_slice()[self.len..padded].fill(0);
+            self.flush_block(padded)?;
+        }
+        self.file.set_len(total)?;
+        Ok(self.file)
+    }
+}
+
+impl Write for DirectWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK_SIZE - self.len);
+        self.buffer.as_mut_slice()[self.len..self.len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        if self.len == BLOCK_SIZE {
+            self.flush_block(BLOCK_SIZE)?;
+            self.written += BLOCK_SIZE as u64;
+        }
+        Ok(n)
+    }
+
+    /// 直接I/O只能写整块，不足一块的数据留到 [`DirectWriter::finish`] 时写出
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_direct_round_trip_with_unaligned_length() {
+        let dir = TestDir::new("direct");
+        let path = dir.join("data.bin");
+
+        let data: Vec<u8> = (0..BLOCK_SIZE * 2 + 12345).map(|i| (i % 251) as u8).collect();
+        let mut writer = DirectWriter::create(&path).unwrap();
+        for piece in data.chunks(70_001) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), data.len() as u64);
+
+        let mut read_back = Vec::new();
+        DirectReader::open(&path).unwrap().read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, data);
+    }
+}
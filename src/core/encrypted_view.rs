@@ -0,0 +1,91 @@
+use super::name_index::FilenameIndex;
+use super::FileManager;
+use crate::crypto::format::max_plaintext_size;
+use crate::crypto::header::FileHeader;
+use crate::crypto::random_access::EncryptedFileReader;
+use crate::crypto::{create_crypto_provider, CryptoResult};
+use crate::models::{EncryptionAlgorithm, Settings};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// 只读视图中的一个加密文件
+#[derive(Debug, Clone)]
+pub struct EncryptedEntry {
+    pub path: PathBuf,
+    /// 原始文件名（来自文件名索引，或去掉加密扩展名）
+    pub name: String,
+    /// 解密后的大小
+    pub size: u64,
+    pub algorithm: EncryptionAlgorithm,
+}
+
+/// 加密文件夹的只读视图：以原始文件名列出文件，读取时在内存中按块解密
+///
+/// 多分卷文件不包含在视图中
+pub struct EncryptedFolderView {
+    entries: Vec<EncryptedEntry>,
+    password: String,
+}
+
+impl EncryptedFolderView {
+    /// 扫描目录中的加密文件，文件名索引无法读取时使用加密后的文件名
+    pub fn open(directory: &Path, settings: &Settings) -> Self {
+        let names = FilenameIndex::load(directory, &settings.password).unwrap_or_default();
+        let suffix = format!(".{}", settings.file_extension);
+
+        let entries = FileManager::load_encrypted_files_from_directory(&directory.to_string_lossy(), settings)
+            .into_iter()
+            .filter(|file| !file.is_multi_volume())
+            .map(|file| {
+                let algorithm = file.algorithm.clone().unwrap_or_else(|| settings.encryption_algorithm.clone());
+                let stem = file.name.strip_suffix(&suffix).unwrap_or(&file.name);
+                EncryptedEntry {
+                    name: names.original_name(stem).unwrap_or(stem).to_string(),
+                    size: plaintext_size(&file.path, &algorithm).unwrap_or(0),
+                    path: file.path,
+                    algorithm,
+                }
+            })
+            .collect();
+
+        Self {
+            entries,
+            password: settings.password.clone(),
+        }
+    }
+
+    pub fn entries(&self) -> &[EncryptedEntry] {
+        &self.entries
+    }
+
+    /// 按加密文件路径查找条目
+    pub fn find(&self, path: &Path) -> Option<&EncryptedEntry> {
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+
+    /// 打开条目的解密读取器
+    pub fn open_entry(&self, entry: &EncryptedEntry) -> CryptoResult<EncryptedFileReader<File>> {
+        EncryptedFileReader::open(&entry.path, &entry.algorithm, &self.password)
+    }
+}
+
+/// 不派生密钥，只根据文件大小和文件头计算明文大小
+fn plaintext_size(path: &Path, algorithm: &EncryptionAlgorithm) -> CryptoResult<u64> {
+    let mut file = File::open(path)?;
+    let total = file.metadata()?.len();
+    let (header, _) = FileHeader::read_from(&mut file)?;
+    let header_len = header.as_ref().map_or(0, |header| header.encoded_len());
+    if let Some(compression) = header.as_ref().and_then(|header| header.compression) {
+        return Ok(compression.plaintext_len);
+    }
+    // 级联加密时外层的明文是内层的密文
+    let outer = create_crypto_provider(header.as_ref().map_or(algorithm, |header| header.disk_algorithm(algorithm)));
+    let size = max_plaintext_size(total.saturating_sub(header_len), outer.chunk_size(), outer.nonce_len());
+    match header.and_then(|header| header.cascade) {
+        Some(cascade) => {
+            let inner = create_crypto_provider(&cascade.inner);
+            Ok(max_plaintext_size(size, inner.chunk_size(), inner.nonce_len()))
+        }
+        None => Ok(size),
+    }
+}
@@ -0,0 +1,101 @@
+//! 文件列表中悬停时显示的文件详情
+//!
+//! 长文件名在面板中会被截断，同名文件也无从区分，因此悬停时显示完整路径、大小、修改时间，
+//! 加密文件还显示文件头中的信息。详情在第一次悬停时读取并缓存，重新加载文件列表时清除
+
+use crate::crypto::header::FileHeader;
+use crate::models::FileItem;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// 加密文件头的读取结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeaderDetails {
+    /// 待加密的明文文件，不读取文件头
+    NotEncrypted,
+    /// 没有文件头的旧格式文件
+    Legacy,
+    Header(Box<FileHeader>),
+    Unreadable(String),
+}
+
+/// 悬停时显示的文件详情
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDetails {
+    pub path: PathBuf,
+    /// 文件大小，多分卷文件为所有分卷的合计
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub volumes: usize,
+    pub header: HeaderDetails,
+}
+
+impl FileDetails {
+    /// 读取文件的大小和修改时间，`encrypted` 为true时同时读取文件头（多分卷文件在第一个分卷中）
+    pub fn load(file: &FileItem, encrypted: bool) -> Self {
+        let paths = file.input_paths();
+        let metadata: Vec<_> = paths.iter().filter_map(|path| fs::metadata(path).ok()).collect();
+        let size = (metadata.len() == paths.len()).then(|| metadata.iter().map(|metadata| metadata.len()).sum());
+        let modified = metadata.iter().filter_map(|metadata| metadata.modified().ok()).max();
+        let header = if encrypted { read_header(&paths[0]) } else { HeaderDetails::NotEncrypted };
+        Self { path: file.path.clone(), size, modified, volumes: file.volumes.len(), header }
+    }
+}
+
+fn read_header(path: &std::path::Path) -> HeaderDetails {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return HeaderDetails::Unreadable(e.to_string()),
+    };
+    let header = FileHeader::read_from(&mut file).map(|(header, _)| header);
+    match header {
+        Ok(Some(header)) => HeaderDetails::Header(Box::new(header)),
+        Ok(None) => HeaderDetails::Legacy,
+        Err(e) => HeaderDetails::Unreadable(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::header::StreamTotals;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_load_details() {
+        let dir = TestDir::new("details");
+        let mut header = FileHeader::with_hint("blue");
+        header.totals = Some(StreamTotals { plaintext_len: 3, chunks: 1 });
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        data.extend_from_slice(b"ciphertext");
+        let path = dir.join("a.enc");
+        fs::write(&path, &data).unwrap();
+        let file = FileItem {
+            path: path.clone(),
+            selected: false,
+            name: "a.enc".to_string(),
+            subdirectory: None,
+            volumes: Vec::new(),
+            algorithm: None,
+            hint: None,
+            conflict: None,
+        };
+
+        let details = FileDetails::load(&file, true);
+        assert_eq!(details.size, Some(data.len() as u64));
+        assert!(details.modified.is_some());
+        assert_eq!(details.header, HeaderDetails::Header(Box::new(header)));
+        assert_eq!(FileDetails::load(&file, false).header, HeaderDetails::NotEncrypted);
+
+        fs::write(&path, [0u8; 32]).unwrap();
+        assert_eq!(FileDetails::load(&file, true).header, HeaderDetails::Legacy);
+        fs::write(&path, b"no header here").unwrap();
+        assert!(matches!(FileDetails::load(&file, true).header, HeaderDetails::Unreadable(_)));
+        fs::remove_file(&path).unwrap();
+        let missing = FileDetails::load(&file, true);
+        assert_eq!(missing.size, None);
+        assert!(matches!(missing.header, HeaderDetails::Unreadable(_)));
+    }
+}
@@ -0,0 +1,158 @@
+use super::app_data;
+use crate::models::FileItem;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// 增量加密状态数据库文件名
+const STATE_FILE_NAME: &str = "incremental.json";
+
+/// 源文件在上次加密时的指纹
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceRecord {
+    pub size: u64,
+    /// 修改时间（自Unix纪元起的纳秒数）
+    pub modified: u128,
+    /// 文件内容的SHA-256（十六进制）
+    pub hash: String,
+    /// 上次生成的加密文件路径
+    pub output: PathBuf,
+}
+
+/// 增量加密状态 - 记录每个源文件的大小、修改时间和内容哈希，
+/// 再次加密同一目录时只处理新增或已修改的文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IncrementalState {
+    #[serde(skip)]
+    path: PathBuf,
+    entries: BTreeMap<PathBuf, SourceRecord>,
+}
+
+impl IncrementalState {
+    /// 从指定路径加载状态数据库，不存在时返回空状态
+    pub fn load(path: PathBuf) -> io::Result<Self> {
+        let mut state: Self = app_data::load_json(&path)?;
+        state.path = path;
+        Ok(state)
+    }
+
+    /// 从应用数据目录中的默认位置加载
+    pub fn load_default() -> io::Result<Self> {
+        Self::load(app_data::app_data_file(STATE_FILE_NAME)?)
+    }
+
+    /// 保存状态数据库
+    pub fn save(&self) -> io::Result<()> {
+        app_data::save_config(&self.path, self)
+    }
+
+    /// 查询源文件的上次记录
+    pub fn record(&self, source: &Path) -> Option<&SourceRecord> {
+        self.entries.get(&state_key(source))
+    }
+
+    /// 判断文件是否需要重新加密（新文件、内容已修改或上次的输出已不存在）
+    pub fn needs_processing(&self, file: &FileItem) -> bool {
+        let Some(record) = self.record(&file.path) else {
+            return true;
+        };
+        if !record.output.is_file() {
+            return true;
+        }
+        let Ok((size, modified)) = file_stamp(&file.path) else {
+            return true;
+        };
+        if size != record.size {
+            return true;
+        }
+        if modified == record.modified {
+            return false;
+        }
+
+        // 修改时间变化但大小相同：比较内容哈希，避免仅被touch的文件被重复加密
+        hash_file(&file.path).map_or(true, |hash| hash != record.hash)
+    }
+
+    /// 记录源文件加密成功后的指纹，并删除该文件上一次生成的过期输出
+    pub fn update(&mut self, source: &Path, output: &Path) -> io::Result<()> {
+        let (size, modified) = file_stamp(source)?;
+        let hash = hash_file(source)?;
+        let record = SourceRecord {
+            size,
+            modified,
+            hash,
+            output: output.to_path_buf(),
+        };
+
+        if let Some(previous) = self.entries.insert(state_key(source), record) {
+            if previous.output != output && previous.output.is_file() {
+                fs::remove_file(&previous.output)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// 状态数据库的键（尽量使用规范化的绝对路径）
+fn state_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// 获取文件大小和修改时间
+fn file_stamp(path: &Path) -> io::Result<(u64, u128)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((metadata.len(), modified))
+}
+
+/// 计算文件内容的SHA-256
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_only_changed_files_need_processing() {
+        let dir = TestDir::new("incremental");
+        let source = dir.join("a.txt");
+        let output = dir.join("a.txt.enc");
+        fs::write(&source, b"first").unwrap();
+        fs::write(&output, b"ciphertext").unwrap();
+
+        let mut state = IncrementalState::load(dir.join(STATE_FILE_NAME)).unwrap();
+        let file = FileItem::new(source.clone(), "a.txt".to_string());
+        assert!(state.needs_processing(&file));
+
+        state.update(&source, &output).unwrap();
+        assert!(!state.needs_processing(&file));
+
+        fs::write(&source, b"second!").unwrap();
+        assert!(state.needs_processing(&file));
+
+        state.update(&source, &output).unwrap();
+        fs::remove_file(&output).unwrap();
+        assert!(state.needs_processing(&file));
+    }
+}
@@ -0,0 +1,144 @@
+use super::{app_data, FileManager};
+use crate::models::{FileItem, OperationMode, Settings};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 任务定义文件的扩展名
+pub const JOB_FILE_EXTENSION: &str = "kjob";
+
+/// 保存的批处理任务定义（.kjob 文件）
+///
+/// 记录源目录、文件筛选条件和设置，不包含密码，运行时再提供
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JobDefinition {
+    pub name: String,
+    /// 源目录（加密时为明文目录，解密时为密文目录）
+    pub source_directory: PathBuf,
+    /// 只处理这些文件（子目录中的文件为以/分隔的相对路径），为空时处理目录中的所有文件
+    pub files: Vec<String>,
+    /// 文件名通配符（支持 `*` 和 `?`），为空时不筛选
+    pub pattern: String,
+    pub settings: Settings,
+}
+
+impl JobDefinition {
+    /// 从当前的设置和文件列表创建任务定义（只记录已选中的文件，不记录界面中被禁用的选项）
+    pub fn from_selection(name: impl Into<String>, source_directory: impl Into<PathBuf>, files: &[FileItem], settings: &Settings) -> Self {
+        let selected: Vec<String> = files.iter()
+            .filter(|file| file.selected)
+            .map(|file| file.name.clone())
+            .collect();
+        // 全部选中时不记录文件名，以便之后新增的文件也会被处理
+        let files = if selected.len() == files.len() { Vec::new() } else { selected };
+
+        Self {
+            name: name.into(),
+            source_directory: source_directory.into(),
+            files,
+            pattern: String::new(),
+            settings: settings.without_inactive_options(),
+        }
+    }
+
+    /// 读取任务定义文件
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// 保存任务定义文件
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        app_data::save_json(path, self)
+    }
+
+    /// 列出源目录中符合条件的文件，并全部标记为选中
+    pub fn resolve_files(&self) -> Vec<FileItem> {
+        let directory = self.source_directory.to_string_lossy();
+        let files = match self.settings.operation_mode {
+            OperationMode::Encrypt | OperationMode::Archive | OperationMode::Container => FileManager::load_files_from_directory(&directory, &self.settings),
+            OperationMode::Decrypt | OperationMode::Verify => FileManager::load_encrypted_files_from_directory(&directory, &self.settings),
+        };
+
+        files.into_iter()
+            .filter(|file| self.files.is_empty() || self.files.contains(&file.relative_name()))
+            .filter(|file| self.pattern.is_empty() || wildcard_match(&self.pattern, &file.name))
+            .map(|mut file| {
+                file.selected = true;
+                file
+            })
+            .collect()
+    }
+
+    /// 使用运行时提供的密码生成完整设置
+    pub fn settings_with_password(&self, password: &str) -> Settings {
+        Settings {
+            password: password.to_string(),
+            ..self.settings.clone()
+        }
+    }
+}
+
+/// 简单的通配符匹配（`*` 匹配任意字符串，`?` 匹配单个字符，不区分大小写）
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.pdf", "Report.PDF"));
+        assert!(wildcard_match("data_??.csv", "data_01.csv"));
+        assert!(!wildcard_match("data_??.csv", "data_1.csv"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("*.txt", "notes.md"));
+    }
+
+    #[test]
+    fn test_job_round_trip_omits_password() {
+        let settings = Settings {
+            password: "secret".to_string(),
+            ..Default::default()
+        };
+        let job = JobDefinition {
+            name: "backup".to_string(),
+            source_directory: PathBuf::from("/tmp/src"),
+            files: vec!["a.txt".to_string()],
+            pattern: "*.txt".to_string(),
+            settings,
+        };
+
+        let json = serde_json::to_string(&job).unwrap();
+        assert!(!json.contains("secret"));
+        let loaded: JobDefinition = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.files, job.files);
+        assert!(loaded.settings.password.is_empty());
+        assert_eq!(loaded.settings_with_password("pw").password, "pw");
+    }
+}
@@ -0,0 +1,257 @@
+//! 可恢复批处理的日志
+//!
+//! 设置了可恢复时，加密或解密批处理开始时在应用数据目录中写出 `journal-<批次>.json`，记录设置（不含密码）和文件列表，
+//! 之后随处理进度记录已完成的文件及其输出、正在处理的文件和正在写入的输出，以及隔离区中的源文件。
+//! 批处理正常结束（包括失败和取消）时删除日志；程序崩溃或系统重启时日志留下，下次启动时可以从中断处继续。
+//!
+//! 加密格式的输出不能从中间续写：继续时中断前正在处理的文件删除不完整的输出后从头处理。
+//! 日志最多每 [`CHECKPOINT_INTERVAL`] 写一次（配置加密时每次都要派生密钥），崩溃前最后一段时间内完成的文件会重新处理；
+//! 这段时间内开始写入的输出没有记录，继续时按输出路径删除临时文件和批处理开始后写入的文件（随机文件名的输出无法找到）
+
+use super::app_data;
+use super::output::OutputFile;
+use crate::crypto::CryptoEngine;
+use crate::models::{FileItem, Settings};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// 日志文件名的前缀，之后是批次编号和 `.json`
+const JOURNAL_PREFIX: &str = "journal-";
+/// 两次写出日志的最短间隔
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 日志中记录的批处理状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub batch_id: String,
+    /// 开始时间（RFC 3339）
+    pub started: String,
+    /// 写日志的进程，用于排除本进程中仍在运行的批处理
+    pub process_id: u32,
+    pub settings: Settings,
+    pub files: Vec<FileItem>,
+    /// 已完成的文件（源文件路径 → 输出路径，跳过的文件没有输出）
+    pub completed: BTreeMap<PathBuf, Option<PathBuf>>,
+    /// 正在处理的文件（源文件路径 → 已处理的字节数）
+    pub in_progress: BTreeMap<PathBuf, u64>,
+    /// 正在写入、尚未完成的输出（源文件路径 → 输出路径）
+    pub partial_outputs: BTreeMap<PathBuf, PathBuf>,
+    /// 隔离区中的源文件（原路径，隔离后的路径）
+    pub quarantined: Vec<(PathBuf, PathBuf)>,
+}
+
+/// 一个批处理的日志，克隆的实例共享同一份记录
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+    record: Arc<Mutex<JournalRecord>>,
+    last_saved: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Journal {
+    /// 在应用数据目录中为批处理创建日志
+    pub fn begin(settings: &Settings, files: &[FileItem]) -> io::Result<Self> {
+        Self::begin_in(&app_data::app_data_dir()?, settings, files)
+    }
+
+    /// 在指定目录中为批处理创建日志并立即写出
+    pub fn begin_in(directory: &Path, settings: &Settings, files: &[FileItem]) -> io::Result<Self> {
+        let mut suffix = [0u8; 4];
+        rand::thread_rng().fill_bytes(&mut suffix);
+        let batch_id = format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), hex::encode(suffix));
+        let record = JournalRecord {
+            batch_id: batch_id.clone(),
+            started: chrono::Local::now().to_rfc3339(),
+            process_id: std::process::id(),
+            settings: settings.clone(),
+            files: files.to_vec(),
+            ..JournalRecord::default()
+        };
+        let journal = Self::from_record(directory.join(format!("{}{}.json", JOURNAL_PREFIX, batch_id)), record);
+        journal.save()?;
+        Ok(journal)
+    }
+
+    fn from_record(path: PathBuf, record: JournalRecord) -> Self {
+        Self { path, record: Arc::new(Mutex::new(record)), last_saved: Arc::new(Mutex::new(None)) }
+    }
+
+    /// 应用数据目录中中断的批处理（不包括本进程中正在运行的），按开始时间排序
+    pub fn unfinished() -> io::Result<Vec<Self>> {
+        Self::unfinished_in(&app_data::app_data_dir()?)
+    }
+
+    /// 指定目录中中断的批处理的日志，无法读取的日志被忽略
+    pub fn unfinished_in(directory: &Path) -> io::Result<Vec<Self>> {
+        let mut journals = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let is_journal = path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(JOURNAL_PREFIX) && name.ends_with(".json"));
+            if !is_journal {
+                continue;
+            }
+            let Ok(record) = app_data::load_json::<JournalRecord>(&path) else {
+                continue;
+            };
+            if record.process_id != std::process::id() {
+                journals.push(Self::from_record(path, record));
+            }
+        }
+        journals.sort_by_key(|journal| journal.record().started);
+        Ok(journals)
+    }
+
+    /// 当前记录的副本
+    pub fn record(&self) -> JournalRecord {
+        self.record.lock().unwrap().clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 继续批处理的准备：由本进程接管日志，删除中断时不完整的输出，返回设置和文件列表
+    pub fn resume(&self) -> io::Result<(Settings, Vec<FileItem>)> {
+        let mut record = self.record.lock().unwrap();
+        record.process_id = std::process::id();
+        let started = chrono::DateTime::parse_from_rfc3339(&record.started).ok();
+        // 日志还没来得及记录的输出：中断后留下的临时文件，以及批处理开始后写入的目标文件
+        let unrecorded = record.files.iter()
+            .filter(|file| !record.completed.contains_key(&file.path))
+            .filter_map(|file| CryptoEngine::planned_output_path(&record.settings, file))
+            .flat_map(|output| {
+                let modified = fs::metadata(&output).and_then(|metadata| metadata.modified()).ok();
+                let written = matches!((modified, started), (Some(modified), Some(started)) if chrono::DateTime::<chrono::Local>::from(modified) >= started);
+                let partial = OutputFile::partial_path(&output);
+                std::iter::once(partial).chain(written.then_some(output))
+            })
+            .collect::<Vec<_>>();
+        for output in std::mem::take(&mut record.partial_outputs).into_values().chain(unrecorded) {
+            match fs::remove_file(&output) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        record.in_progress.clear();
+        let resumed = (record.settings.clone(), record.files.clone());
+        drop(record);
+        self.save()?;
+        Ok(resumed)
+    }
+
+    /// 中断前已完成的文件的输出，未完成时为None
+    pub fn completed_output(&self, source: &Path) -> Option<Option<PathBuf>> {
+        self.record.lock().unwrap().completed.get(source).cloned()
+    }
+
+    pub fn file_started(&self, source: &Path) {
+        self.update(|record| {
+            record.in_progress.insert(source.to_path_buf(), 0);
+        });
+    }
+
+    /// 记录正在处理的文件已处理的字节数
+    pub fn file_progress(&self, source: &Path, bytes: u64) {
+        self.update(|record| {
+            record.in_progress.insert(source.to_path_buf(), bytes);
+        });
+    }
+
+    /// 记录开始写入的输出，中断后继续时删除
+    pub fn output_created(&self, source: &Path, output: &Path) {
+        self.update(|record| {
+            record.partial_outputs.insert(source.to_path_buf(), output.to_path_buf());
+        });
+    }
+
+    /// 记录完成（`output` 为None时为跳过）的文件和当前隔离的源文件
+    pub fn file_completed(&self, source: &Path, output: Option<&Path>, quarantined: Vec<(PathBuf, PathBuf)>) {
+        self.update(|record| {
+            record.in_progress.remove(source);
+            record.partial_outputs.remove(source);
+            record.completed.insert(source.to_path_buf(), output.map(Path::to_path_buf));
+            record.quarantined = quarantined;
+        });
+    }
+
+    /// 失败的文件在继续时重新处理（不完整的输出已在失败时删除）
+    pub fn file_failed(&self, source: &Path) {
+        self.update(|record| {
+            record.in_progress.remove(source);
+            record.partial_outputs.remove(source);
+        });
+    }
+
+    /// 批处理结束，删除日志
+    pub fn finish(&self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// 修改记录，距上次写出超过 [`CHECKPOINT_INTERVAL`] 时写出日志。
+    /// 日志写出失败不影响批处理本身，只是中断后无法从这里继续
+    fn update(&self, change: impl FnOnce(&mut JournalRecord)) {
+        change(&mut self.record.lock().unwrap());
+        let mut last_saved = self.last_saved.lock().unwrap();
+        if last_saved.is_none_or(|saved| saved.elapsed() >= CHECKPOINT_INTERVAL) {
+            *last_saved = Some(Instant::now());
+            drop(last_saved);
+            let _ = self.save();
+        }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let record = self.record.lock().unwrap().clone();
+        app_data::save_config(&self.path, &record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_journal_resume_and_finish() {
+        let dir = TestDir::new("journal");
+        let files: Vec<FileItem> = ["a.txt", "b.txt"].iter()
+            .map(|name| FileItem::new(dir.join(name), name.to_string()))
+            .collect();
+        let journal = Journal::begin_in(&dir, &Settings::default(), &files).unwrap();
+        assert!(journal.path().exists());
+
+        journal.file_completed(&files[0].path, Some(&dir.join("a.txt.enc")), Vec::new());
+        journal.file_started(&files[1].path);
+        let partial = dir.join("b.txt.enc");
+        fs::write(&partial, b"half").unwrap();
+        journal.output_created(&files[1].path, &partial);
+
+        // 本进程中的日志不算中断的批处理；模拟另一个进程留下的日志
+        assert!(Journal::unfinished_in(&dir).unwrap().is_empty());
+        let mut record = journal.record();
+        record.process_id = 0;
+        app_data::save_json(journal.path(), &record).unwrap();
+        let unfinished = Journal::unfinished_in(&dir).unwrap();
+        assert_eq!(unfinished.len(), 1);
+
+        let resumed = &unfinished[0];
+        let (_, resumed_files) = resumed.resume().unwrap();
+        assert_eq!(resumed_files.len(), 2);
+        assert!(!partial.exists());
+        assert_eq!(resumed.completed_output(&files[0].path), Some(Some(dir.join("a.txt.enc"))));
+        assert_eq!(resumed.completed_output(&files[1].path), None);
+
+        resumed.finish().unwrap();
+        assert!(!journal.path().exists());
+    }
+}
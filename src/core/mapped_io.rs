@@ -0,0 +1,149 @@
+//! 内存映射的文件读写，处理很大的文件时代替 `BufReader`/`BufWriter`
+//!
+//! 读取时把整个文件映射到内存，按顺序访问，不再为每块调用一次read；写入时按预计长度预分配并映射输出，
+//! 超出时扩大映射，结束时截断到实际长度。数据仍经过页缓存，与直接I/O同时设置时直接I/O优先。
+//!
+//! 映射的文件在读写过程中被其他程序截断，或者映射的区域无法分配磁盘空间时，访问映射会使进程收到
+//! SIGBUS（Windows上为访问冲突）而不是返回错误，因此只作为高级设置中的可选项
+
+use super::output::preallocate;
+use memmap2::{Mmap, MmapMut};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// 小于这个大小的文件不映射：映射和解除映射的开销超过省下的系统调用
+pub const MIN_MAPPED_SIZE: u64 = 64 * 1024 * 1024;
+/// 写入超出映射时至少扩大的字节数
+const GROW_STEP: u64 = 64 * 1024 * 1024;
+
+/// 映射整个文件的读取器
+pub struct MappedReader {
+    /// 空文件不能映射，为None
+    map: Option<Mmap>,
+    position: usize,
+}
+
+impl MappedReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(Self { map: None, position: 0 });
+        }
+        // 安全性：映射期间文件被其他程序截断时访问会触发SIGBUS，见模块文档
+        let map = unsafe { Mmap::map(&file)? };
+        #[cfg(unix)]
+        map.advise(memmap2::Advice::Sequential)?;
+        Ok(Self { map: Some(map), position: 0 })
+    }
+}
+
+impl Read for MappedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(map) = &self.map else {
+            return Ok(0);
+        };
+        let n = buf.len().min(map.len() - self.position);
+        buf[..n].copy_from_slice(&map[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// 写入映射的输出文件的写入器，必须调用 [`MappedWriter::finish`] 截断到实际长度
+pub struct MappedWriter {
+    file: File,
+    map: Option<MmapMut>,
+    /// 已映射（和预分配）的长度
+    mapped: u64,
+    written: u64,
+}
+
+impl MappedWriter {
+    /// 创建（或截断）文件，第一次写入或 [`MappedWriter::reserve`] 时才映射
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        Ok(Self { file, map: None, mapped: 0, written: 0 })
+    }
+
+    /// 预分配并映射至少 `len` 字节
+    pub fn reserve(&mut self, len: u64) -> io::Result<()> {
+        if len <= self.mapped {
+            return Ok(());
+        }
+        // 先解除旧的映射（Windows上不能改变已映射文件的长度），已写入的数据留在页缓存中
+        self.map = None;
+        preallocate(&self.file, len)?;
+        self.file.set_len(len)?;
+        // 安全性：文件由本写入器独占创建，映射的区域已经预分配
+        self.map = Some(unsafe { MmapMut::map_mut(&self.file)? });
+        self.mapped = len;
+        Ok(())
+    }
+
+    /// 已写入的长度
+    pub fn position(&self) -> u64 {
+        self.written
+    }
+
+    /// 解除映射，把文件截断到实际写入的长度后返回文件
+    pub fn finish(mut self) -> io::Result<File> {
+        self.map = None;
+        self.file.set_len(self.written)?;
+        Ok(self.file)
+    }
+}
+
+impl Write for MappedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = self.written + buf.len() as u64;
+        if end > self.mapped {
+            self.reserve(end.max(self.mapped + GROW_STEP))?;
+        }
+        let Some(map) = &mut self.map else {
+            return Ok(0);
+        };
+        let start = self.written as usize;
+        map[start..start + buf.len()].copy_from_slice(buf);
+        self.written = end;
+        Ok(buf.len())
+    }
+
+    /// 写入映射的数据已在页缓存中，由操作系统写回磁盘
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_mapped_round_trip_beyond_reserved_length() {
+        let dir = TestDir::new("mapped");
+        let path = dir.join("data.bin");
+
+        // 预留的长度不够时写入扩大映射，结束时截断到实际长度
+        let data: Vec<u8> = (0..300_007).map(|i| (i % 251) as u8).collect();
+        let mut writer = MappedWriter::create(&path).unwrap();
+        writer.reserve(100_000).unwrap();
+        for piece in data.chunks(70_001) {
+            writer.write_all(piece).unwrap();
+        }
+        assert_eq!(writer.position(), data.len() as u64);
+        writer.finish().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), data.len() as u64);
+
+        let mut read_back = Vec::new();
+        MappedReader::open(&path).unwrap().read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, data);
+
+        // 空文件不映射
+        MappedWriter::create(&path).unwrap().finish().unwrap();
+        let mut read_back = Vec::new();
+        MappedReader::open(&path).unwrap().read_to_end(&mut read_back).unwrap();
+        assert!(read_back.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! 把旧格式的加密文件迁移到当前格式
+//!
+//! 旧格式是没有文件头的文件、不记录明文总长度的版本1文件头（无法发现在块边界处被截断的文件），
+//! 以及数据块使用随机nonce的文件（无法发现被重排或删除的数据块，见 [`crate::crypto::stream`]）。
+//! 迁移时用同一个密码解密并重新加密，明文通过内存中的管道直接交给加密端，不会写到磁盘；
+//! 密码提示和Argon2参数沿用原文件头。原文件改名为 `<名称>.pre-migration` 保留，
+//! 确认没有问题后删除，或者回滚为原文件
+
+use super::output::OutputFile;
+use super::quarantine::QUARANTINE_DIR_NAME;
+use super::scan::scan_tree;
+use crate::crypto::format::{self, Counted};
+use crate::crypto::header::{algorithm_code, FileHeader, StreamTotals};
+use crate::crypto::stream::ChunkNonces;
+use crate::crypto::traits::BatchKey;
+use crate::crypto::{create_crypto_provider, create_crypto_provider_with_kdf, create_decryption_provider, CryptoResult, StreamControl};
+use crate::models::EncryptionAlgorithm;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// 迁移前的原文件的后缀
+pub const BACKUP_SUFFIX: &str = "pre-migration";
+
+/// 迁移中的新文件的后缀，完成后改名为原文件名
+const TEMP_SUFFIX: &str = "migrating";
+
+/// 加密文件的格式版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// 没有文件头
+    Headerless,
+    /// 版本1文件头，不记录明文总长度
+    HeaderV1,
+    /// 记录了总长度，但数据块使用随机nonce
+    RandomNonces,
+    /// 当前格式
+    Current,
+}
+
+/// 读取文件头判断加密文件的格式
+pub fn detect_format(path: &Path) -> CryptoResult<FileFormat> {
+    let mut file = File::open(path)?;
+    let (header, mut reader) = FileHeader::read_from(&mut file)?;
+    let header = match header {
+        None => return Ok(FileFormat::Headerless),
+        // 压缩的文件不记录总长度，不是旧格式
+        Some(header) if header.totals.is_none() && header.compression.is_none() => return Ok(FileFormat::HeaderV1),
+        Some(header) => header,
+    };
+    // 按第一块的nonce判断，没有记录算法的文件由使用12字节nonce的旧版本写出
+    let nonce_len = create_crypto_provider(header.disk_algorithm(&EncryptionAlgorithm::AES256)).nonce_len();
+    let mut prefix = vec![0u8; format::SALT_LEN + nonce_len];
+    if reader.read_exact(&mut prefix).is_ok() && !ChunkNonces::detect(&prefix[format::SALT_LEN..]).is_sequenced() {
+        return Ok(FileFormat::RandomNonces);
+    }
+    Ok(FileFormat::Current)
+}
+
+/// 递归查找目录中扩展名为 `extension` 的旧格式文件，按路径排序
+pub fn find_legacy_files(directory: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let found = Mutex::new(Vec::new());
+    scan_tree(directory, &AtomicBool::new(false), &|file| {
+        let in_quarantine = file.path.components().any(|component| component.as_os_str() == QUARANTINE_DIR_NAME);
+        if in_quarantine || file.path.extension().is_none_or(|ext| ext != extension) {
+            return;
+        }
+        if matches!(detect_format(&file.path), Ok(FileFormat::Headerless | FileFormat::HeaderV1 | FileFormat::RandomNonces)) {
+            found.lock().unwrap().push(file.path);
+        }
+    })?;
+    let mut files = found.into_inner().unwrap();
+    files.sort();
+    Ok(files)
+}
+
+/// 已迁移的文件及其原文件的备份
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigratedFile {
+    pub path: PathBuf,
+    pub backup: PathBuf,
+}
+
+/// 一次迁移的结果
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub migrated: Vec<MigratedFile>,
+    /// 每个失败的文件一条说明，失败的文件保持原样
+    pub problems: Vec<String>,
+    pub cancelled: bool,
+}
+
+/// 逐个迁移文件，单个文件失败不影响其他文件；`cancel` 被置位时在下一个文件前停止
+pub fn migrate_files(
+    files: &[PathBuf],
+    algorithm: &EncryptionAlgorithm,
+    password: &str,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(usize, usize, &str),
+) -> MigrationReport {
+    let _batch_key_scope = BatchKey::cache_scope();
+    let mut report = MigrationReport::default();
+    for (index, path) in files.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            report.cancelled = true;
+            break;
+        }
+        progress(index, files.len(), &path.display().to_string());
+        match migrate_file(path, algorithm, password) {
+            Ok(migrated) => report.migrated.push(migrated),
+            Err(e) => report.problems.push(e),
+        }
+    }
+    progress(files.len(), files.len(), "");
+    report
+}
+
+/// 把一个文件重新加密为当前格式，原文件改名为备份
+pub fn migrate_file(path: &Path, algorithm: &EncryptionAlgorithm, password: &str) -> Result<MigratedFile, String> {
+    let backup = with_suffix(path, BACKUP_SUFFIX);
+    if backup.exists() {
+        return Err(format!("'{}' already has a backup from an earlier migration", path.display()));
+    }
+    let temp = with_suffix(path, TEMP_SUFFIX);
+    reencrypt(path, &temp, algorithm, password)
+        .map_err(|e| format!("Failed to migrate '{}': {}", path.display(), e))?;
+
+    let replace = fs::rename(path, &backup).and_then(|_| {
+        fs::rename(&temp, path).inspect_err(|_| {
+            let _ = fs::rename(&backup, path);
+        })
+    });
+    if let Err(e) = replace {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to replace '{}': {}", path.display(), e));
+    }
+    Ok(MigratedFile { path: path.to_path_buf(), backup })
+}
+
+/// 用备份替换迁移后的文件，成功的文件从列表中移除，返回出错的文件的说明
+pub fn rollback(files: &mut Vec<MigratedFile>) -> Vec<String> {
+    retain_failed(files, |file| {
+        // 先确认备份还在，再删除迁移后的文件（Windows上改名不能覆盖已有文件）
+        fs::metadata(&file.backup)
+            .and_then(|_| fs::remove_file(&file.path))
+            .and_then(|_| fs::rename(&file.backup, &file.path))
+            .map_err(|e| format!("Failed to roll back '{}': {}", file.path.display(), e))
+    })
+}
+
+/// 删除迁移前的备份，成功的文件从列表中移除，返回出错的文件的说明
+pub fn discard_backups(files: &mut Vec<MigratedFile>) -> Vec<String> {
+    retain_failed(files, |file| {
+        fs::remove_file(&file.backup)
+            .map_err(|e| format!("Failed to delete '{}': {}", file.backup.display(), e))
+    })
+}
+
+fn retain_failed(files: &mut Vec<MigratedFile>, action: impl Fn(&MigratedFile) -> Result<(), String>) -> Vec<String> {
+    let mut problems = Vec::new();
+    files.retain(|file| match action(file) {
+        Ok(()) => false,
+        Err(e) => {
+            problems.push(e);
+            true
+        }
+    });
+    problems
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// 解密 `input` 并用相同的密码重新加密到 `output`
+///
+/// 先完整解密一遍得到明文长度（同时验证密码和数据完整性），再边解密边加密写出，
+/// 文件头中需要在开头写入明文总长度
+fn reencrypt(input: &Path, output: &Path, algorithm: &EncryptionAlgorithm, password: &str) -> Result<(), String> {
+    let mut file = BufReader::new(File::open(input).map_err(|e| e.to_string())?);
+    let (header, mut reader) = FileHeader::read_from(&mut file).map_err(|e| e.to_string())?;
+    let header = header.unwrap_or_default();
+    if header.wrapped_key.is_some() || header.token_challenge.is_some() || header.keyfile_check.is_some() || header.cascade.is_some()
+        || header.compression.is_some()
+    {
+        return Err("cascaded or compressed files and files protected by a key service, keyfile or hardware token must be decrypted and encrypted again".to_string());
+    }
+    let algorithm = header.data_algorithm(algorithm);
+    let decryptor = create_decryption_provider(algorithm, Some(&header), password).map_err(|e| e.to_string())?;
+    let old_control = StreamControl::default().with_binding(header.digest_binding());
+    let mut plaintext = Counted::new(io::sink());
+    decryptor.decrypt_stream(password, &mut reader, &mut plaintext, &old_control).map_err(|e| e.to_string())?;
+    let plaintext_len = plaintext.count();
+    drop(reader);
+
+    // 批处理主密钥的文件迁移后改为每个文件单独派生密钥
+    let encryptor = create_crypto_provider_with_kdf(algorithm, &header.kdf_params.unwrap_or_default());
+    let new_header = FileHeader {
+        kdf_params: header.kdf_params,
+        algorithm: Some(algorithm.clone()),
+        totals: Some(StreamTotals::for_plaintext(plaintext_len, encryptor.chunk_size())),
+        bound_digest: algorithm_code(algorithm).is_some(),
+        ..FileHeader::with_hint(&header.hint)
+    };
+    let new_control = StreamControl::default().with_binding(new_header.digest_binding());
+    let expected_len = new_header.encoded_len() + format::encrypted_size(plaintext_len, encryptor.chunk_size(), encryptor.nonce_len());
+    let mut writer = OutputFile::create(output, expected_len).map_err(|e| e.to_string())?;
+    new_header.write_to(&mut writer).map_err(|e| e.to_string())?;
+
+    let mut file = BufReader::new(File::open(input).map_err(|e| e.to_string())?);
+    let (_, mut reader) = FileHeader::read_from(&mut file).map_err(|e| e.to_string())?;
+    let (pipe_writer, pipe_reader) = format::pipe();
+    let (decrypted, encrypted) = std::thread::scope(|scope| {
+        let encrypting = scope.spawn(move || {
+            let mut pipe_reader = Counted::new(pipe_reader);
+            encryptor.encrypt_stream(password, &mut pipe_reader, &mut writer, &new_control)
+                .map(|_| (pipe_reader.count(), writer))
+        });
+        let mut pipe_writer = pipe_writer;
+        let decrypted = decryptor.decrypt_stream(password, &mut reader, &mut pipe_writer, &old_control);
+        // 关闭管道，加密端读到结尾
+        drop(pipe_writer);
+        (decrypted, encrypting.join().expect("encryption thread panicked"))
+    });
+    decrypted.map_err(|e| e.to_string())?;
+    let (written, writer) = encrypted.map_err(|e| e.to_string())?;
+    if written != plaintext_len {
+        return Err(format!("the file changed during migration ({} bytes expected, {} read)", plaintext_len, written));
+    }
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::KdfParams;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_migrate_and_roll_back_headerless_file() {
+        let dir = TestDir::new("migrate");
+        let path = dir.join("old.enc");
+        let plaintext: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let kdf = KdfParams { memory_kib: 8 * 1024, iterations: 1, parallelism: 1, ..KdfParams::default() };
+        let algorithm = EncryptionAlgorithm::ChaCha20;
+
+        // 旧格式：带非默认参数的版本1文件头，不记录总长度
+        let mut old = Vec::new();
+        FileHeader { kdf_params: Some(kdf), ..FileHeader::with_hint("hint") }.write_to(&mut old).unwrap();
+        create_crypto_provider_with_kdf(&algorithm, &kdf).encrypt_stream("pw", &mut plaintext.as_slice(), &mut old, &StreamControl::default()).unwrap();
+        fs::write(&path, &old).unwrap();
+        assert_eq!(find_legacy_files(&dir, "enc").unwrap(), vec![path.clone()]);
+
+        assert!(migrate_file(&path, &algorithm, "wrong").is_err());
+        assert_eq!(fs::read(&path).unwrap(), old);
+
+        let migrated = migrate_file(&path, &algorithm, "pw").unwrap();
+        assert_eq!(detect_format(&path).unwrap(), FileFormat::Current);
+        assert!(find_legacy_files(&dir, "enc").unwrap().is_empty());
+        let mut file = File::open(&path).unwrap();
+        let (header, mut reader) = FileHeader::read_from(&mut file).unwrap();
+        let header = header.unwrap();
+        assert_eq!((header.hint.as_str(), header.kdf_params, header.bound_digest), ("hint", Some(kdf), true));
+        let mut decrypted = Vec::new();
+        let control = StreamControl::default().with_binding(header.digest_binding());
+        create_decryption_provider(&algorithm, Some(&header), "pw").unwrap()
+            .decrypt_stream("pw", &mut reader, &mut decrypted, &control).unwrap();
+        assert_eq!(decrypted, plaintext);
+
+        let mut migrated = vec![migrated];
+        assert!(rollback(&mut migrated).is_empty());
+        assert!(migrated.is_empty());
+        assert_eq!(fs::read(&path).unwrap(), old);
+        assert!(!with_suffix(&path, BACKUP_SUFFIX).exists());
+    }
+}
@@ -0,0 +1,167 @@
+pub mod api;
+pub mod app_data;
+pub mod archiver;
+pub mod backup;
+pub mod checksum;
+pub mod container;
+pub mod dir_settings;
+pub mod direct_io;
+pub mod encrypted_view;
+pub mod file_details;
+pub mod incremental;
+pub mod journal;
+pub mod job;
+pub mod mapped_io;
+#[cfg(feature = "mount")]
+pub mod mount;
+pub mod migrate;
+pub mod notes;
+pub mod scheduler;
+pub mod name_index;
+pub mod network;
+pub mod output;
+pub mod preflight;
+pub mod preview;
+pub mod quarantine;
+pub mod recovery;
+pub mod report;
+pub mod scan;
+pub mod shred;
+pub mod tpm;
+pub mod volumes;
+pub mod watch;
+
+use crate::crypto::header::read_hint;
+use crate::models::{FileItem, Settings};
+use network::DirectoryError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub struct FileManager;
+
+impl FileManager {
+    pub fn load_files_from_directory(directory: &str, settings: &Settings) -> Vec<FileItem> {
+        Self::try_load_files_from_directory(directory, settings).unwrap_or_else(|e| {
+            eprintln!("无法打开目录 '{}': {}", directory, e);
+            Vec::new()
+        })
+    }
+
+    pub fn load_encrypted_files_from_directory(directory: &str, settings: &Settings) -> Vec<FileItem> {
+        Self::try_load_encrypted_files_from_directory(directory, settings).unwrap_or_else(|e| {
+            eprintln!("无法打开目录 '{}': {}", directory, e);
+            Vec::new()
+        })
+    }
+
+    /// 列出目录中的文件（设置了包含子目录时也列出子目录中的文件），无法打开目录时返回原因
+    pub fn try_load_files_from_directory(directory: &str, settings: &Settings) -> Result<Vec<FileItem>, DirectoryError> {
+        // 检查目录路径是否为空
+        if directory.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 网络共享离线或需要登录时给出具体原因
+        network::check_directory(Path::new(directory))?;
+
+        let mut files: Vec<FileItem> = Self::list_directory(Path::new(directory), settings)
+            .map_err(|e| DirectoryError::Unreadable(e.to_string()))?
+            .into_iter()
+            // 跳过加密文件名索引
+            .filter(|file| file.name != name_index::NAME_INDEX_FILE_NAME)
+            .collect();
+
+        // 按相对路径排序
+        files.sort_by_cached_key(FileItem::relative_name);
+        Ok(files)
+    }
+
+    /// 列出目录中的加密文件（设置了包含子目录时也列出子目录中的文件），无法打开目录时返回原因
+    pub fn try_load_encrypted_files_from_directory(directory: &str, settings: &Settings) -> Result<Vec<FileItem>, DirectoryError> {
+        // 检查目录路径是否为空
+        if directory.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 网络共享离线或需要登录时给出具体原因
+        network::check_directory(Path::new(directory))?;
+
+        let mut files = Vec::new();
+        let mut volume_sets: BTreeMap<_, Vec<(u32, PathBuf)>> = BTreeMap::new();
+        let extension_with_dot = format!(".{}", settings.file_extension);
+        let listed = Self::list_directory(Path::new(directory), settings)
+            .map_err(|e| DirectoryError::Unreadable(e.to_string()))?;
+
+        for file in listed {
+            // 筛选加密文件（以指定后缀结尾）
+            if file.name.ends_with(&extension_with_dot) {
+                files.push(file);
+            } else if let Some((base_name, number)) = volumes::parse_volume_name(&file.name) {
+                // 多分卷加密文件（例如 name.enc.001）
+                if base_name.ends_with(&extension_with_dot) {
+                    volume_sets.entry((file.subdirectory.clone(), base_name.to_string()))
+                        .or_default()
+                        .push((number, file.path));
+                }
+            }
+        }
+
+        // 将分卷合并为一个逻辑文件
+        for ((subdirectory, base_name), mut parts) in volume_sets {
+            parts.sort_by_key(|(number, _)| *number);
+            let volumes = parts.into_iter().map(|(_, path)| path).collect();
+            files.push(FileItem { subdirectory, ..FileItem::from_volumes(base_name, volumes) });
+        }
+
+        // 读取文件头中的密码提示（多分卷时位于第一个分卷）
+        for file in &mut files {
+            let first = file.volumes.first().unwrap_or(&file.path);
+            file.hint = read_hint(first).ok().flatten();
+        }
+
+        // 按相对路径排序
+        files.sort_by_cached_key(FileItem::relative_name);
+        Ok(files)
+    }
+
+    /// 列出目录中的普通文件。设置了包含子目录时按最大层数进入子目录（不跟随符号链接），
+    /// 子目录中的文件记录相对于 `root` 的子目录；无法读取的子目录跳过，只有 `root` 本身无法读取时返回错误
+    fn list_directory(root: &Path, settings: &Settings) -> io::Result<Vec<FileItem>> {
+        let mut files = Vec::new();
+        let mut pending: Vec<(PathBuf, Option<PathBuf>, u32)> = vec![(root.to_path_buf(), None, 0)];
+        while let Some((directory, subdirectory, depth)) = pending.pop() {
+            let entries = match fs::read_dir(&directory) {
+                Ok(entries) => entries,
+                Err(e) if subdirectory.is_none() => return Err(e),
+                Err(e) => {
+                    eprintln!("无法读取子目录 '{}': {}", directory.display(), e);
+                    continue;
+                }
+            };
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        eprintln!("读取目录项时出错: {}", e);
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()).map(str::to_string) else {
+                    continue;
+                };
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    if settings.recursive && (settings.max_depth == 0 || depth < settings.max_depth) {
+                        let child = subdirectory.as_ref().map_or_else(|| PathBuf::from(&name), |parent| parent.join(&name));
+                        pending.push((path, Some(child), depth + 1));
+                    }
+                } else if path.is_file() {
+                    files.push(FileItem { subdirectory: subdirectory.clone(), ..FileItem::new(path, name) });
+                }
+            }
+        }
+        Ok(files)
+    }
+} 
\ No newline at end of file
@@ -0,0 +1,524 @@
+//! 把加密文件夹挂载为只读文件系统（`mount` 功能，目前只支持Linux的FUSE）
+//!
+//! 挂载点下以原始文件名列出 [`EncryptedFolderView`] 中的文件，读取时用 [`EncryptedFileReader`] 在内存中按块解密，
+//! 不写出明文文件。这里直接实现FUSE内核协议的只读子集：有权限时直接调用mount(2)，否则通过setuid的
+//! fusermount3/fusermount挂载。子目录中的文件平铺在挂载点下，同名的文件只列出第一个；
+//! 级联加密和压缩的文件无法随机访问，打开时返回EIO。
+//!
+//! Windows（WinFsp）和macOS尚不支持，[`EncryptedMount::mount`] 在这些平台上返回 `Unsupported`
+
+use super::encrypted_view::EncryptedFolderView;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 挂载中的加密文件夹，丢弃时卸载
+pub struct EncryptedMount {
+    mountpoint: PathBuf,
+    #[cfg(target_os = "linux")]
+    session: Option<linux::Session>,
+}
+
+impl EncryptedMount {
+    /// 把视图中的文件只读挂载到已存在的空目录 `mountpoint`
+    #[cfg(target_os = "linux")]
+    pub fn mount(view: EncryptedFolderView, mountpoint: &Path) -> io::Result<Self> {
+        let session = linux::Session::start(view, mountpoint)?;
+        Ok(Self { mountpoint: mountpoint.to_path_buf(), session: Some(session) })
+    }
+
+    /// 把视图中的文件只读挂载到已存在的空目录 `mountpoint`
+    #[cfg(not(target_os = "linux"))]
+    pub fn mount(_view: EncryptedFolderView, _mountpoint: &Path) -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "mounting encrypted folders is only supported on Linux"))
+    }
+
+    pub fn mountpoint(&self) -> &Path {
+        &self.mountpoint
+    }
+
+    /// 卸载并等待处理请求的线程结束
+    pub fn unmount(mut self) -> io::Result<()> {
+        self.unmount_now()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn unmount_now(&mut self) -> io::Result<()> {
+        match self.session.take() {
+            Some(session) => session.stop(&self.mountpoint),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn unmount_now(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for EncryptedMount {
+    fn drop(&mut self) {
+        let _ = self.unmount_now();
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::EncryptedFolderView;
+    use crate::core::encrypted_view::EncryptedEntry;
+    use crate::crypto::random_access::EncryptedFileReader;
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::CString;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::thread::{self, JoinHandle};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const FUSE_ROOT_ID: u64 = 1;
+    /// 实现的协议版本
+    const KERNEL_VERSION: u32 = 7;
+    const KERNEL_MINOR_VERSION: u32 = 31;
+    /// 请求中最大的写入长度（只读文件系统不会收到写入，但内核按它检查读取缓冲区的大小）
+    const MAX_WRITE: u32 = 64 * 1024;
+    const IN_HEADER_LEN: usize = 40;
+    const OUT_HEADER_LEN: usize = 16;
+    /// 内核缓存名称和属性的秒数
+    const ATTR_TTL: u64 = 1;
+    const BLOCK_SIZE: u32 = 4096;
+    /// 打开的文件保留内核的页缓存
+    const FOPEN_KEEP_CACHE: u32 = 1 << 1;
+
+    const FUSE_LOOKUP: u32 = 1;
+    const FUSE_FORGET: u32 = 2;
+    const FUSE_GETATTR: u32 = 3;
+    const FUSE_OPEN: u32 = 14;
+    const FUSE_READ: u32 = 15;
+    const FUSE_STATFS: u32 = 17;
+    const FUSE_RELEASE: u32 = 18;
+    const FUSE_FLUSH: u32 = 25;
+    const FUSE_INIT: u32 = 26;
+    const FUSE_OPENDIR: u32 = 27;
+    const FUSE_READDIR: u32 = 28;
+    const FUSE_RELEASEDIR: u32 = 29;
+    const FUSE_ACCESS: u32 = 34;
+    const FUSE_INTERRUPT: u32 = 36;
+    const FUSE_DESTROY: u32 = 38;
+    const FUSE_BATCH_FORGET: u32 = 42;
+
+    /// 一次挂载：FUSE设备上的请求由后台线程处理
+    pub(super) struct Session {
+        /// 通过fusermount挂载时用同一个程序卸载
+        fusermount: Option<PathBuf>,
+        thread: JoinHandle<()>,
+    }
+
+    impl Session {
+        pub(super) fn start(view: EncryptedFolderView, mountpoint: &Path) -> io::Result<Self> {
+            let (device, fusermount) = mount_device(mountpoint)?;
+            let thread = thread::spawn(move || Filesystem::new(view).serve(device));
+            Ok(Self { fusermount, thread })
+        }
+
+        /// 卸载后内核断开连接，处理线程读到ENODEV后结束
+        pub(super) fn stop(self, mountpoint: &Path) -> io::Result<()> {
+            let result = match &self.fusermount {
+                Some(fusermount) => {
+                    let status = Command::new(fusermount).arg("-u").arg("-z").arg("--").arg(mountpoint).status()?;
+                    match status.success() {
+                        true => Ok(()),
+                        false => Err(io::Error::other(format!("{} failed to unmount '{}'", fusermount.display(), mountpoint.display()))),
+                    }
+                }
+                None => {
+                    let path = c_path(mountpoint)?;
+                    match unsafe { libc::umount2(path.as_ptr(), libc::MNT_DETACH) } {
+                        0 => Ok(()),
+                        _ => Err(io::Error::last_os_error()),
+                    }
+                }
+            };
+            if result.is_ok() {
+                let _ = self.thread.join();
+            }
+            result
+        }
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    /// 打开 `/dev/fuse` 并挂载到 `mountpoint`，没有权限时改用fusermount
+    fn mount_device(mountpoint: &Path) -> io::Result<(File, Option<PathBuf>)> {
+        let device = OpenOptions::new().read(true).write(true).open("/dev/fuse")?;
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let options = CString::new(format!(
+            "fd={},rootmode=40000,user_id={},group_id={},default_permissions",
+            device.as_raw_fd(), uid, gid,
+        ))?;
+        let target = c_path(mountpoint)?;
+        let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_RDONLY;
+        let result = unsafe {
+            libc::mount(c"krypton".as_ptr(), target.as_ptr(), c"fuse.krypton".as_ptr(), flags, options.as_ptr().cast())
+        };
+        if result == 0 {
+            return Ok((device, None));
+        }
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::EPERM) {
+            return Err(error);
+        }
+        drop(device);
+
+        let fusermount = ["fusermount3", "fusermount"].iter()
+            .find_map(|name| find_in_path(name))
+            .ok_or(error)?;
+        let device = fusermount_mount(&fusermount, mountpoint)?;
+        Ok((device, Some(fusermount)))
+    }
+
+    fn find_in_path(name: &str) -> Option<PathBuf> {
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|directory| directory.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// 让fusermount挂载，它通过 `_FUSE_COMMFD` 指定的套接字把打开的FUSE设备传回来
+    fn fusermount_mount(fusermount: &Path, mountpoint: &Path) -> io::Result<File> {
+        let mut sockets = [0 as RawFd; 2];
+        if unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, sockets.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // 本进程一端不传给子进程，另一端没有CLOEXEC，由fusermount继承
+        let (local, remote) = unsafe { (File::from_raw_fd(sockets[0]), File::from_raw_fd(sockets[1])) };
+        unsafe { libc::fcntl(local.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+
+        let status = Command::new(fusermount)
+            .env("_FUSE_COMMFD", remote.as_raw_fd().to_string())
+            .args(["-o", "ro,nosuid,nodev,fsname=krypton,subtype=krypton", "--"])
+            .arg(mountpoint)
+            .status()?;
+        drop(remote);
+        if !status.success() {
+            return Err(io::Error::other(format!("{} failed to mount '{}'", fusermount.display(), mountpoint.display())));
+        }
+        receive_fd(local.as_raw_fd()).map(|fd| unsafe { File::from_raw_fd(fd) })
+    }
+
+    /// 接收套接字上以SCM_RIGHTS传来的文件描述符
+    fn receive_fd(socket: RawFd) -> io::Result<RawFd> {
+        let mut byte = [0u8; 1];
+        let mut iov = libc::iovec { iov_base: byte.as_mut_ptr().cast(), iov_len: byte.len() };
+        // u64保证控制消息缓冲区对齐
+        let mut control = [0u64; 8];
+        let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr().cast();
+        message.msg_controllen = std::mem::size_of_val(&control) as _;
+        if unsafe { libc::recvmsg(socket, &mut message, 0) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let header = unsafe { libc::CMSG_FIRSTHDR(&message) };
+        if header.is_null() || unsafe { (*header).cmsg_type } != libc::SCM_RIGHTS {
+            return Err(io::Error::other("fusermount did not pass the FUSE device"));
+        }
+        Ok(unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(header) as *const RawFd) })
+    }
+
+    /// 请求头中用到的字段
+    struct Request {
+        opcode: u32,
+        unique: u64,
+        nodeid: u64,
+    }
+
+    /// 按本机字节序编码回复的结构体
+    #[derive(Default)]
+    struct Reply(Vec<u8>);
+
+    impl Reply {
+        fn u32(mut self, value: u32) -> Self {
+            self.0.extend_from_slice(&value.to_ne_bytes());
+            self
+        }
+
+        fn u64(mut self, value: u64) -> Self {
+            self.0.extend_from_slice(&value.to_ne_bytes());
+            self
+        }
+
+        fn u16(mut self, value: u16) -> Self {
+            self.0.extend_from_slice(&value.to_ne_bytes());
+            self
+        }
+
+        fn zeros(mut self, len: usize) -> Self {
+            self.0.resize(self.0.len() + len, 0);
+            self
+        }
+    }
+
+    fn read_u32(body: &[u8], offset: usize) -> Option<u32> {
+        body.get(offset..offset + 4).map(|bytes| u32::from_ne_bytes(bytes.try_into().expect("4 bytes")))
+    }
+
+    fn read_u64(body: &[u8], offset: usize) -> Option<u64> {
+        body.get(offset..offset + 8).map(|bytes| u64::from_ne_bytes(bytes.try_into().expect("8 bytes")))
+    }
+
+    /// 挂载的只读文件系统：根目录的inode为1，第i个文件为i+2
+    struct Filesystem {
+        view: EncryptedFolderView,
+        /// 挂载点下列出的文件（视图中条目的序号）和显示的文件名
+        files: Vec<(usize, String)>,
+        open: HashMap<u64, EncryptedFileReader<File>>,
+        next_handle: u64,
+        uid: u32,
+        gid: u32,
+        mounted_at: u64,
+    }
+
+    impl Filesystem {
+        fn new(view: EncryptedFolderView) -> Self {
+            let mut seen = HashSet::new();
+            let files = view.entries().iter().enumerate()
+                .filter_map(|(index, entry)| {
+                    let name = Path::new(&entry.name).file_name()?.to_string_lossy().to_string();
+                    seen.insert(name.clone()).then_some((index, name))
+                })
+                .collect();
+            let mounted_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Self { view, files, open: HashMap::new(), next_handle: 1, uid, gid, mounted_at }
+        }
+
+        /// 处理请求直到卸载（读取设备返回ENODEV）或收到DESTROY
+        fn serve(mut self, mut device: File) {
+            let mut buffer = vec![0u8; MAX_WRITE as usize + 4096];
+            loop {
+                let len = match device.read(&mut buffer) {
+                    Ok(len) => len,
+                    // 请求在读取前被中断
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted || e.raw_os_error() == Some(libc::ENOENT) => continue,
+                    Err(_) => return,
+                };
+                let (Some(opcode), Some(unique), Some(nodeid)) = (read_u32(&buffer, 4), read_u64(&buffer, 8), read_u64(&buffer, 16)) else {
+                    continue;
+                };
+                let request = Request { opcode, unique, nodeid };
+                let body = buffer.get(IN_HEADER_LEN..len).unwrap_or_default();
+                let result = match self.handle(&request, body) {
+                    Ok(Some(reply)) => Ok(reply),
+                    Ok(None) => continue,
+                    Err(errno) => Err(errno),
+                };
+                let (error, data) = match result {
+                    Ok(reply) => (0, reply.0),
+                    Err(errno) => (-errno, Vec::new()),
+                };
+                let message = Reply::default()
+                    .u32((OUT_HEADER_LEN + data.len()) as u32)
+                    .u32(error as u32)
+                    .u64(request.unique);
+                let mut message = message.0;
+                message.extend_from_slice(&data);
+                // 请求已被中断时内核拒绝回复，忽略即可
+                let _ = device.write_all(&message);
+                if request.opcode == FUSE_DESTROY {
+                    return;
+                }
+            }
+        }
+
+        /// 处理一个请求，返回回复的内容；不需要回复的请求返回None，失败时返回errno
+        fn handle(&mut self, request: &Request, body: &[u8]) -> Result<Option<Reply>, i32> {
+            match request.opcode {
+                FUSE_INIT => {
+                    let major = read_u32(body, 0).ok_or(libc::EINVAL)?;
+                    let max_readahead = read_u32(body, 8).ok_or(libc::EINVAL)?;
+                    if major < KERNEL_VERSION {
+                        return Err(libc::EPROTO);
+                    }
+                    Ok(Some(Reply::default()
+                        .u32(KERNEL_VERSION)
+                        .u32(KERNEL_MINOR_VERSION)
+                        .u32(max_readahead)
+                        // 不请求任何可选功能
+                        .u32(0)
+                        .u16(16)
+                        .u16(12)
+                        .u32(MAX_WRITE)
+                        .u32(1)
+                        .zeros(36)))
+                }
+                FUSE_LOOKUP if request.nodeid == FUSE_ROOT_ID => {
+                    let name = body.split(|&byte| byte == 0).next().unwrap_or_default();
+                    let position = self.files.iter()
+                        .position(|(_, file_name)| file_name.as_bytes() == name)
+                        .ok_or(libc::ENOENT)?;
+                    let inode = position as u64 + 2;
+                    let reply = Reply::default().u64(inode).u64(0).u64(ATTR_TTL).u64(ATTR_TTL).u32(0).u32(0);
+                    Ok(Some(self.attr(reply, inode)?))
+                }
+                FUSE_LOOKUP => Err(libc::ENOENT),
+                FUSE_GETATTR => {
+                    let reply = Reply::default().u64(ATTR_TTL).u32(0).u32(0);
+                    Ok(Some(self.attr(reply, request.nodeid)?))
+                }
+                FUSE_OPENDIR if request.nodeid == FUSE_ROOT_ID => Ok(Some(Reply::default().u64(0).u32(0).u32(0))),
+                FUSE_OPENDIR => Err(libc::ENOTDIR),
+                FUSE_READDIR => {
+                    let offset = read_u64(body, 8).ok_or(libc::EINVAL)?;
+                    let size = read_u32(body, 16).ok_or(libc::EINVAL)? as usize;
+                    Ok(Some(self.read_directory(offset, size)))
+                }
+                FUSE_OPEN => {
+                    let flags = read_u32(body, 0).ok_or(libc::EINVAL)? as i32;
+                    if flags & libc::O_ACCMODE != libc::O_RDONLY {
+                        return Err(libc::EROFS);
+                    }
+                    let entry = self.entry(request.nodeid).ok_or(libc::ENOENT)?;
+                    let reader = self.view.open_entry(entry).map_err(|_| libc::EIO)?;
+                    let handle = self.next_handle;
+                    self.next_handle += 1;
+                    self.open.insert(handle, reader);
+                    Ok(Some(Reply::default().u64(handle).u32(FOPEN_KEEP_CACHE).u32(0)))
+                }
+                FUSE_READ => {
+                    let handle = read_u64(body, 0).ok_or(libc::EINVAL)?;
+                    let offset = read_u64(body, 8).ok_or(libc::EINVAL)?;
+                    let size = read_u32(body, 16).ok_or(libc::EINVAL)? as usize;
+                    let reader = self.open.get_mut(&handle).ok_or(libc::EBADF)?;
+                    Ok(Some(Reply(read_at(reader, offset, size).map_err(|_| libc::EIO)?)))
+                }
+                FUSE_RELEASE => {
+                    let handle = read_u64(body, 0).ok_or(libc::EINVAL)?;
+                    self.open.remove(&handle);
+                    Ok(Some(Reply::default()))
+                }
+                FUSE_RELEASEDIR | FUSE_FLUSH | FUSE_DESTROY => Ok(Some(Reply::default())),
+                FUSE_ACCESS => {
+                    let mask = read_u32(body, 0).ok_or(libc::EINVAL)? as i32;
+                    match mask & libc::W_OK {
+                        0 => Ok(Some(Reply::default())),
+                        _ => Err(libc::EROFS),
+                    }
+                }
+                FUSE_STATFS => {
+                    let files = self.files.len() as u64;
+                    Ok(Some(Reply::default()
+                        .u64(0).u64(0).u64(0).u64(files).u64(0)
+                        .u32(BLOCK_SIZE).u32(255).u32(BLOCK_SIZE).u32(0)
+                        .zeros(24)))
+                }
+                FUSE_FORGET | FUSE_BATCH_FORGET | FUSE_INTERRUPT => Ok(None),
+                _ => Err(libc::ENOSYS),
+            }
+        }
+
+        fn entry(&self, inode: u64) -> Option<&EncryptedEntry> {
+            let (index, _) = self.files.get(inode.checked_sub(2)? as usize)?;
+            self.view.entries().get(*index)
+        }
+
+        /// 在回复后追加inode的属性
+        fn attr(&self, reply: Reply, inode: u64) -> Result<Reply, i32> {
+            let (size, time, mode, nlink) = match inode {
+                FUSE_ROOT_ID => (0, self.mounted_at, libc::S_IFDIR | 0o555, 2),
+                _ => {
+                    let entry = self.entry(inode).ok_or(libc::ENOENT)?;
+                    let modified = fs::metadata(&entry.path).and_then(|metadata| metadata.modified()).ok()
+                        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                        .map_or(self.mounted_at, |elapsed| elapsed.as_secs());
+                    (entry.size, modified, libc::S_IFREG | 0o444, 1)
+                }
+            };
+            Ok(reply
+                .u64(inode)
+                .u64(size)
+                .u64(size.div_ceil(512))
+                .u64(time).u64(time).u64(time)
+                .u32(0).u32(0).u32(0)
+                .u32(mode)
+                .u32(nlink)
+                .u32(self.uid)
+                .u32(self.gid)
+                .u32(0)
+                .u32(BLOCK_SIZE)
+                .u32(0))
+        }
+
+        /// 从第 `offset` 项开始列出根目录，不超过 `size` 字节
+        fn read_directory(&self, offset: u64, size: usize) -> Reply {
+            let entries = [(FUSE_ROOT_ID, ".", libc::DT_DIR), (FUSE_ROOT_ID, "..", libc::DT_DIR)].into_iter()
+                .chain(self.files.iter().enumerate().map(|(position, (_, name))| (position as u64 + 2, name.as_str(), libc::DT_REG)));
+            let mut reply = Reply::default();
+            for (index, (inode, name, kind)) in entries.enumerate().skip(offset as usize) {
+                let record_len = (24 + name.len()).next_multiple_of(8);
+                if reply.0.len() + record_len > size {
+                    break;
+                }
+                let mut record = Reply::default().u64(inode).u64(index as u64 + 1).u32(name.len() as u32).u32(kind as u32);
+                record.0.extend_from_slice(name.as_bytes());
+                record.0.resize(record_len, 0);
+                reply.0.extend_from_slice(&record.0);
+            }
+            reply
+        }
+    }
+
+    /// 从 `offset` 开始读取最多 `size` 字节，到文件末尾时返回的数据较短
+    fn read_at(reader: &mut EncryptedFileReader<File>, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut data = vec![0u8; size];
+        let len = crate::crypto::format::read_chunk(reader, &mut data)?;
+        data.truncate(len);
+        Ok(data)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::crypto::{CryptoEngine, EncryptOptions};
+    use crate::models::Settings;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_mount_lists_and_reads_decrypted_files() {
+        let dir = TestDir::new("mount");
+        let encrypted = dir.join("encrypted");
+        let mountpoint = dir.join("mnt");
+        fs::create_dir_all(&encrypted).unwrap();
+        fs::create_dir_all(&mountpoint).unwrap();
+        let contents = b"mounted and decrypted on the fly\n".repeat(50_000);
+        fs::write(dir.join("notes.txt"), &contents).unwrap();
+        let options = EncryptOptions { password: "pw".to_string(), ..EncryptOptions::default() };
+        CryptoEngine::encrypt_path(&dir.join("notes.txt"), &encrypted.join("notes.txt.enc"), &options).unwrap();
+
+        let settings = Settings { password: "pw".to_string(), ..Settings::default() };
+        let mount = match EncryptedMount::mount(EncryptedFolderView::open(&encrypted, &settings), &mountpoint) {
+            Ok(mount) => mount,
+            // 没有FUSE或没有挂载权限的环境（例如容器）中跳过
+            Err(e) => {
+                eprintln!("skipping: cannot mount FUSE here: {}", e);
+                return;
+            }
+        };
+        let names: Vec<_> = fs::read_dir(&mountpoint).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["notes.txt"]);
+        assert_eq!(fs::read(mountpoint.join("notes.txt")).unwrap(), contents);
+        assert!(fs::write(mountpoint.join("notes.txt"), b"x").is_err());
+        assert!(fs::read(mountpoint.join("missing.txt")).is_err());
+
+        mount.unmount().unwrap();
+        assert_eq!(fs::read_dir(&mountpoint).unwrap().count(), 0);
+    }
+}
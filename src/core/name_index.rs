@@ -0,0 +1,115 @@
+use crate::crypto::armor;
+use crate::crypto::{CryptoError, CryptoResult};
+use crate::models::EncryptionAlgorithm;
+use super::app_data;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 文件名索引的文件名（位于加密输出目录中）
+pub const NAME_INDEX_FILE_NAME: &str = ".krypton-names.kidx";
+
+/// 加密文件名索引 - 记录随机文件名与原始文件名的对应关系
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilenameIndex {
+    /// 随机文件名（不含加密扩展名） -> 原始文件名
+    entries: BTreeMap<String, String>,
+}
+
+/// 恢复文件名操作的结果统计
+#[derive(Debug, Clone, Default)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub missing: usize,
+    pub conflicts: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl FilenameIndex {
+    /// 获取目录中的索引文件路径
+    pub fn index_path(directory: &Path) -> PathBuf {
+        directory.join(NAME_INDEX_FILE_NAME)
+    }
+
+    /// 读取目录中的索引，不存在时返回空索引
+    pub fn load(directory: &Path, password: &str) -> CryptoResult<Self> {
+        let path = Self::index_path(directory);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let armored = fs::read_to_string(&path)?;
+        let json = armor::decrypt_text(password, &armored)?;
+        serde_json::from_str(&json)
+            .map_err(|e| CryptoError::DecryptionError(format!("文件名索引损坏: {}", e)))
+    }
+
+    /// 加密并保存索引到目录
+    pub fn save(&self, directory: &Path, algorithm: &EncryptionAlgorithm, password: &str) -> CryptoResult<()> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| CryptoError::EncryptionError(format!("文件名索引序列化失败: {}", e)))?;
+        let armored = armor::encrypt_text(algorithm, password, &json)?;
+        app_data::write_atomic(&Self::index_path(directory), armored.as_bytes())?;
+        Ok(())
+    }
+
+    /// 记录一个随机文件名对应的原始文件名
+    pub fn insert(&mut self, random_name: impl Into<String>, original_name: impl Into<String>) {
+        self.entries.insert(random_name.into(), original_name.into());
+    }
+
+    /// 查询随机文件名对应的原始文件名
+    pub fn original_name(&self, random_name: &str) -> Option<&str> {
+        self.entries.get(random_name).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 将新的对应关系合并到目录中已有的索引并保存
+    pub fn merge_into_directory(
+        directory: &Path,
+        algorithm: &EncryptionAlgorithm,
+        password: &str,
+        new_entries: impl IntoIterator<Item = (String, String)>,
+    ) -> CryptoResult<()> {
+        let mut index = Self::load(directory, password)?;
+        for (random_name, original_name) in new_entries {
+            index.insert(random_name, original_name);
+        }
+        index.save(directory, algorithm, password)
+    }
+
+    /// 根据索引把目录中已解密的随机文件名恢复为原始文件名
+    pub fn restore_names(directory: &Path, password: &str) -> CryptoResult<RestoreSummary> {
+        let index = Self::load(directory, password)?;
+        let mut summary = RestoreSummary::default();
+
+        for (random_name, original_name) in &index.entries {
+            let decrypted_path = directory.join(random_name);
+            let target_path = directory.join(original_name);
+
+            if !decrypted_path.is_file() {
+                summary.missing += 1;
+                continue;
+            }
+            if target_path.exists() {
+                summary.conflicts.push(original_name.clone());
+                continue;
+            }
+
+            match fs::rename(&decrypted_path, &target_path) {
+                Ok(()) => summary.restored += 1,
+                Err(e) => summary.errors.push(format!("{}: {}", original_name, e)),
+            }
+        }
+
+        Ok(summary)
+    }
+}
@@ -0,0 +1,245 @@
+//! 网络共享的识别和访问检查
+//!
+//! 识别UNC路径（`\\server\share`）和挂载的网络文件系统（NFS、SMB/CIFS、SSHFS等），
+//! 在较长的超时时间内于后台线程中检查能否访问，并把错误区分为共享离线、需要登录或无响应。
+//! Windows上可以用 `net use` 以指定的用户名和密码连接共享
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// 检查网络共享时的超时时间（服务器休眠或VPN重连时可能需要较长时间）
+pub const NETWORK_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// 当前平台能否在程序中用用户名和密码连接共享
+pub const CAN_CONNECT: bool = cfg!(windows);
+
+/// 视为网络共享的文件系统类型
+#[cfg(target_os = "linux")]
+const NETWORK_FILESYSTEMS: [&str; 12] = [
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "9p", "afs", "ceph", "glusterfs", "davfs", "fuse.sshfs", "fuse.rclone",
+];
+
+/// 表示服务器不可达的Windows错误码：ERROR_BAD_NETPATH、ERROR_NETNAME_DELETED、ERROR_BAD_NET_NAME、
+/// ERROR_SEM_TIMEOUT、ERROR_NO_NETWORK
+#[cfg(windows)]
+const OFFLINE_ERRORS: [i32; 5] = [53, 64, 67, 121, 1222];
+/// 表示需要登录的Windows错误码：ERROR_INVALID_PASSWORD、ERROR_SESSION_CREDENTIAL_CONFLICT、
+/// ERROR_LOGON_FAILURE、ERROR_NOT_AUTHENTICATED
+#[cfg(windows)]
+const LOGON_ERRORS: [i32; 4] = [86, 1219, 1326, 1244];
+
+/// 目录所在的网络共享
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkShare {
+    /// 共享在本机上的根目录（UNC路径的 `\\server\share` 部分或挂载点）
+    pub root: PathBuf,
+    /// 共享的远程名称，例如 `\\server\share` 或 `server:/export`
+    pub source: String,
+}
+
+/// 无法打开目录的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirectoryError {
+    NotFound,
+    NotADirectory,
+    /// 网络共享的服务器离线或网络断开
+    ShareOffline(String),
+    /// 访问网络共享需要登录
+    CredentialsRequired(String),
+    /// 网络共享在超时时间内没有响应
+    TimedOut(String),
+    Unreadable(String),
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirectoryError::NotFound => write!(f, "The folder does not exist"),
+            DirectoryError::NotADirectory => write!(f, "The path is not a folder"),
+            DirectoryError::ShareOffline(share) => write!(f, "Network share '{}' is offline or unreachable", share),
+            DirectoryError::CredentialsRequired(share) => write!(f, "Network share '{}' needs a user name and password", share),
+            DirectoryError::TimedOut(share) => {
+                write!(f, "Network share '{}' did not respond within {} seconds", share, NETWORK_TIMEOUT.as_secs())
+            }
+            DirectoryError::Unreadable(error) => write!(f, "Cannot read the folder: {}", error),
+        }
+    }
+}
+
+/// 返回路径所在的网络共享，本地路径返回None
+pub fn network_share(path: &Path) -> Option<NetworkShare> {
+    unc_share(path).or_else(|| mounted_share(path))
+}
+
+/// 解析UNC路径 `\\server\share\...`（也接受正斜杠），不包括 `\\?\` 等设备路径
+fn unc_share(path: &Path) -> Option<NetworkShare> {
+    let text = path.to_str()?;
+    let rest = text.strip_prefix(r"\\").or_else(|| text.strip_prefix("//"))?;
+    let mut parts = rest.split(['\\', '/']).filter(|part| !part.is_empty());
+    let server = parts.next().filter(|server| *server != "?" && *server != ".")?;
+    let share = parts.next()?;
+    let source = format!(r"\\{}\{}", server, share);
+    Some(NetworkShare { root: PathBuf::from(&source), source })
+}
+
+/// 在 /proc/mounts 中查找包含该路径的网络文件系统挂载点
+#[cfg(target_os = "linux")]
+fn mounted_share(path: &Path) -> Option<NetworkShare> {
+    let path = std::path::absolute(path).ok()?;
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (source, mount_point, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            Some((unescape_mount_field(source), PathBuf::from(unescape_mount_field(mount_point)), fs_type))
+        })
+        .filter(|(_, mount_point, _)| path.starts_with(mount_point))
+        // 嵌套挂载时取最深的挂载点
+        .max_by_key(|(_, mount_point, _)| mount_point.components().count())
+        .filter(|(_, _, fs_type)| NETWORK_FILESYSTEMS.contains(fs_type))
+        .map(|(source, root, _)| NetworkShare { root, source })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mounted_share(_path: &Path) -> Option<NetworkShare> {
+    None
+}
+
+/// /proc/mounts 中的空格等字符写作八进制转义（例如 `\040`）
+#[cfg(target_os = "linux")]
+fn unescape_mount_field(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match escaped {
+            Some(byte) => {
+                output.push(byte);
+                i += 4;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// 检查能否列出目录内容。网络共享在后台线程中检查并最多等待 `NETWORK_TIMEOUT`，
+/// 无响应的检查线程留在后台自行结束
+pub fn check_directory(path: &Path) -> Result<(), DirectoryError> {
+    let Some(share) = network_share(path) else {
+        return probe(path, None);
+    };
+    let (sender, receiver) = mpsc::channel();
+    let target = path.to_path_buf();
+    let probed_share = share.clone();
+    std::thread::spawn(move || {
+        let _ = sender.send(probe(&target, Some(&probed_share)));
+    });
+    receiver.recv_timeout(NETWORK_TIMEOUT)
+        .unwrap_or(Err(DirectoryError::TimedOut(share.source)))
+}
+
+fn probe(path: &Path, share: Option<&NetworkShare>) -> Result<(), DirectoryError> {
+    let metadata = fs::metadata(path).map_err(|e| classify(&e, share))?;
+    if !metadata.is_dir() {
+        return Err(DirectoryError::NotADirectory);
+    }
+    fs::read_dir(path).map(|_| ()).map_err(|e| classify(&e, share))
+}
+
+/// 按错误类型和所在的共享区分错误原因
+fn classify(error: &io::Error, share: Option<&NetworkShare>) -> DirectoryError {
+    let Some(share) = share else {
+        return match error.kind() {
+            io::ErrorKind::NotFound => DirectoryError::NotFound,
+            _ => DirectoryError::Unreadable(error.to_string()),
+        };
+    };
+    let source = share.source.clone();
+    if is_offline_error(error) {
+        return DirectoryError::ShareOffline(source);
+    }
+    if is_logon_error(error) {
+        return DirectoryError::CredentialsRequired(source);
+    }
+    match error.kind() {
+        // 共享本身还能访问时才是目录不存在
+        io::ErrorKind::NotFound if fs::metadata(&share.root).is_ok() => DirectoryError::NotFound,
+        io::ErrorKind::NotFound => DirectoryError::ShareOffline(source),
+        io::ErrorKind::TimedOut => DirectoryError::TimedOut(source),
+        _ => DirectoryError::Unreadable(error.to_string()),
+    }
+}
+
+fn is_offline_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    if error.raw_os_error().is_some_and(|code| OFFLINE_ERRORS.contains(&code)) {
+        return true;
+    }
+    matches!(
+        error.kind(),
+        io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::NetworkDown
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+fn is_logon_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    if error.raw_os_error().is_some_and(|code| LOGON_ERRORS.contains(&code)) {
+        return true;
+    }
+    error.kind() == io::ErrorKind::PermissionDenied
+}
+
+/// 以指定的用户名和密码连接共享（不保存为持久连接）
+#[cfg(windows)]
+pub fn connect_share(share: &str, user: &str, password: &str) -> Result<(), String> {
+    let output = std::process::Command::new("net")
+        .args(["use", share, password])
+        .arg(format!("/user:{}", user))
+        .arg("/persistent:no")
+        .output()
+        .map_err(|e| format!("Failed to run net use: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&output.stderr);
+    Err(format!("Cannot connect to '{}': {}", share, message.trim()))
+}
+
+#[cfg(not(windows))]
+pub fn connect_share(share: &str, _user: &str, _password: &str) -> Result<(), String> {
+    Err(format!("Mount '{}' with its credentials first", share))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unc_share_and_local_errors() {
+        let share = unc_share(Path::new(r"\\nas\backup\2024\photos")).unwrap();
+        assert_eq!(share.source, r"\\nas\backup");
+        assert_eq!(unc_share(Path::new("//nas/backup")).unwrap().source, r"\\nas\backup");
+        assert_eq!(unc_share(Path::new(r"\\nas")), None);
+        assert_eq!(unc_share(Path::new(r"\\?\C:\data")), None);
+        assert_eq!(unc_share(Path::new("/home/user")), None);
+
+        let missing = std::env::temp_dir().join(format!("krypton_missing_{}", std::process::id()));
+        assert_eq!(check_directory(&missing), Err(DirectoryError::NotFound));
+        assert_eq!(check_directory(&std::env::temp_dir()), Ok(()));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::crypto::armor;
+use crate::crypto::{CryptoError, CryptoResult};
+use crate::models::EncryptionAlgorithm;
+use super::app_data;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 笔记存储文件名
+const NOTES_FILE_NAME: &str = "notes.kdb";
+
+/// 加密笔记
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    pub title: String,
+    pub body: String,
+    /// 最后修改时间（Unix秒）
+    pub updated_at: u64,
+}
+
+impl Note {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            body: String::new(),
+            updated_at: unix_now(),
+        }
+    }
+
+    /// 更新修改时间
+    pub fn touch(&mut self) {
+        self.updated_at = unix_now();
+    }
+}
+
+/// 加密笔记存储 - 整个笔记列表以主密码加密后保存为单个封装文本文件
+pub struct NoteStore {
+    path: PathBuf,
+    algorithm: EncryptionAlgorithm,
+}
+
+impl NoteStore {
+    /// 使用指定路径和算法创建笔记存储
+    pub fn new(path: PathBuf, algorithm: EncryptionAlgorithm) -> Self {
+        Self { path, algorithm }
+    }
+
+    /// 使用应用数据目录中的默认位置
+    pub fn open_default(algorithm: EncryptionAlgorithm) -> CryptoResult<Self> {
+        let path = app_data::app_data_file(NOTES_FILE_NAME)?;
+        Ok(Self::new(path, algorithm))
+    }
+
+    /// 存储文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 存储文件是否已存在
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// 使用主密码解锁并读取所有笔记，存储不存在时返回空列表
+    pub fn load(&self, password: &str) -> CryptoResult<Vec<Note>> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+        if !self.exists() {
+            return Ok(Vec::new());
+        }
+
+        let armored = fs::read_to_string(&self.path)?;
+        let json = armor::decrypt_text(password, &armored)
+            .map_err(|_| CryptoError::InvalidPassword)?;
+        serde_json::from_str(&json)
+            .map_err(|e| CryptoError::DecryptionError(format!("笔记数据损坏: {}", e)))
+    }
+
+    /// 使用主密码加密并保存所有笔记
+    pub fn save(&self, password: &str, notes: &[Note]) -> CryptoResult<()> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+
+        let json = serde_json::to_string(notes)
+            .map_err(|e| CryptoError::EncryptionError(format!("笔记序列化失败: {}", e)))?;
+        let armored = armor::encrypt_text(&self.algorithm, password, &json)?;
+        app_data::write_atomic(&self.path, armored.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,258 @@
+use super::direct_io::DirectWriter;
+use super::mapped_io::MappedWriter;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// 加密/解密的输出文件
+///
+/// 先写入同目录下的临时文件（见 [`OutputFile::partial_path`]），创建时按预计大小预分配磁盘空间，
+/// [`OutputFile::finish`] 截断到实际长度后重命名为目标路径，已存在的目标文件在此之前保持不变；
+/// 未调用 `finish` 就被丢弃时（写入失败、解密失败等）自动删除不完整的临时文件
+pub struct OutputFile {
+    path: PathBuf,
+    partial: PathBuf,
+    writer: Option<OutputWriter>,
+}
+
+enum OutputWriter {
+    Buffered(BufWriter<File>),
+    /// 绕过页缓存写入
+    Direct(DirectWriter),
+    /// 写入内存映射
+    Mapped(MappedWriter),
+}
+
+impl OutputWriter {
+    fn as_write(&mut self) -> &mut dyn Write {
+        match self {
+            OutputWriter::Buffered(writer) => writer,
+            OutputWriter::Direct(writer) => writer,
+            OutputWriter::Mapped(writer) => writer,
+        }
+    }
+}
+
+impl OutputFile {
+    /// 写入 `path` 的输出先使用的临时文件
+    pub fn partial_path(path: &Path) -> PathBuf {
+        let mut temp_name = path.file_name()
+            .map(|name| name.to_os_string())
+            .unwrap_or_default();
+        temp_name.push(".tmp");
+        path.with_file_name(temp_name)
+    }
+
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            partial: Self::partial_path(path),
+            writer: None,
+        }
+    }
+
+    /// 创建输出文件并预分配 `expected_len` 字节
+    pub fn create(path: &Path, expected_len: u64) -> io::Result<Self> {
+        let mut output = Self::new(path);
+        let file = File::create(&output.partial)?;
+
+        // 预分配失败（例如空间不足）时由Drop删除空文件
+        preallocate(&file, expected_len)?;
+        output.writer = Some(OutputWriter::Buffered(BufWriter::new(file)));
+        Ok(output)
+    }
+
+    /// 创建绕过操作系统页缓存写入的输出文件
+    pub fn create_direct(path: &Path, expected_len: u64) -> io::Result<Self> {
+        let mut output = Self::new(path);
+        let writer = DirectWriter::create(&output.partial)?;
+
+        preallocate(writer.file(), expected_len)?;
+        output.writer = Some(OutputWriter::Direct(writer));
+        Ok(output)
+    }
+
+    /// 创建按预计大小映射到内存写入的输出文件
+    pub fn create_mapped(path: &Path, expected_len: u64) -> io::Result<Self> {
+        let mut output = Self::new(path);
+        let mut writer = MappedWriter::create(&output.partial)?;
+
+        writer.reserve(expected_len)?;
+        output.writer = Some(OutputWriter::Mapped(writer));
+        Ok(output)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 刷新缓冲区，把文件截断到实际写入的长度后重命名为目标路径（替换已存在的文件）
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        match self.writer.take().expect("output file already finished") {
+            OutputWriter::Buffered(writer) => {
+                let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+                let written = file.stream_position()?;
+                file.set_len(written)?;
+            }
+            OutputWriter::Direct(writer) => {
+                writer.finish()?;
+            }
+            OutputWriter::Mapped(writer) => {
+                writer.finish()?;
+            }
+        }
+        fs::rename(&self.partial, &self.path)?;
+        self.partial = PathBuf::new();
+        Ok(self.path.clone())
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.as_mut().expect("output file already finished").as_write().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().expect("output file already finished").as_write().flush()
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if self.partial.as_os_str().is_empty() {
+            return;
+        }
+        // 先关闭文件再删除（Windows无法删除打开的文件）
+        drop(self.writer.take());
+        let _ = fs::remove_file(&self.partial);
+    }
+}
+
+/// 判断错误是否由磁盘空间不足引起
+pub fn is_disk_full(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::StorageFull || error.kind() == io::ErrorKind::QuotaExceeded
+}
+
+/// 在 `path` 已存在（或正在写入）时返回同目录下带编号的空闲路径，编号加在最后一个扩展名之前，例如 "report (1).pdf"
+pub fn unique_path(path: &Path) -> PathBuf {
+    let taken = |path: &Path| path.exists() || OutputFile::partial_path(path).exists();
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|number| path.with_file_name(format!("{} ({}){}", stem, number, extension)))
+        .find(|candidate| !taken(candidate))
+        .expect("unbounded range")
+}
+
+/// 为文件预留磁盘空间
+#[cfg(target_os = "linux")]
+pub(super) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if len == 0 {
+        return Ok(());
+    }
+    let len = libc::off_t::try_from(len).unwrap_or(libc::off_t::MAX);
+    match unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, len) } {
+        0 => Ok(()),
+        // 文件系统不支持预分配时直接写入
+        libc::EOPNOTSUPP | libc::EINVAL => Ok(()),
+        code => Err(io::Error::from_raw_os_error(code)),
+    }
+}
+
+/// 为文件预留磁盘空间（Windows上设置文件长度会实际分配空间）
+#[cfg(not(target_os = "linux"))]
+pub(super) fn preallocate(file: &File, len: u64) -> io::Result<()> {
+    file.set_len(len)
+}
+
+/// 路径本身或最近的已存在的上级目录（输出目录可能还没有创建），空路径按当前目录处理
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+    path.ancestors().find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+}
+
+/// `path` 所在文件系统上当前用户可用的空间，无法查询时返回None
+#[cfg(target_os = "linux")]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(existing_ancestor(path)?.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// `path` 所在文件系统上当前用户可用的空间，无法查询时返回None
+#[cfg(not(target_os = "linux"))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// `path` 所在设备的编号，用于把同一文件系统上的输出合计在一起；无法获取时返回None
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(existing_ancestor(path)?).ok().map(|metadata| metadata.dev())
+}
+
+/// `path` 所在设备的编号，用于把同一文件系统上的输出合计在一起；无法获取时返回None
+#[cfg(not(unix))]
+pub fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_output_truncated_or_removed() {
+        let dir = TestDir::new("output");
+
+        // 完成时截断到实际长度
+        let path = dir.join("done.bin");
+        let mut output = OutputFile::create(&path, 4096).unwrap();
+        output.write_all(b"hello").unwrap();
+        output.finish().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"hello");
+
+        // 未完成时删除部分输出
+        let partial = dir.join("partial.bin");
+        let mut output = OutputFile::create(&partial, 4096).unwrap();
+        output.write_all(b"half").unwrap();
+        drop(output);
+        assert!(!partial.exists());
+        assert!(!OutputFile::partial_path(&partial).exists());
+    }
+
+    #[test]
+    fn test_existing_target_replaced_only_on_finish() {
+        let dir = TestDir::new("output_replace");
+        let path = dir.join("existing.bin");
+        fs::write(&path, b"original").unwrap();
+
+        // 写入失败时已存在的目标文件保持不变
+        for create in [OutputFile::create, OutputFile::create_mapped] {
+            let mut output = create(&path, 4096).unwrap();
+            output.write_all(b"replacement").unwrap();
+            assert_eq!(fs::read(&path).unwrap(), b"original");
+            drop(output);
+            assert_eq!(fs::read(&path).unwrap(), b"original");
+        }
+
+        let mut output = OutputFile::create(&path, 4096).unwrap();
+        output.write_all(b"replacement").unwrap();
+        assert_eq!(output.finish().unwrap(), path);
+        assert_eq!(fs::read(&path).unwrap(), b"replacement");
+        assert!(!OutputFile::partial_path(&path).exists());
+    }
+}
@@ -0,0 +1,287 @@
+//! 开始批处理前的输出预览
+//!
+//! 列出每个选中文件的输出路径和目标中是否已有同名文件，以及按冲突处理方式会新建、覆盖、跳过还是另存。
+//! [`dry_run`] 在此基础上估计输出大小并与各文件系统的可用空间比较，不写出任何文件
+
+use super::archiver;
+use super::container::CONTAINER_EXTENSION;
+use super::output::{available_space, device_id};
+use crate::crypto::CryptoEngine;
+use crate::models::{ConflictPolicy, FileItem, OperationMode, Settings};
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 开始后对一个文件的输出会做什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OutputAction {
+    New,
+    Overwrite,
+    Skip,
+    KeepBoth,
+}
+
+impl OutputAction {
+    /// 差异列表中的前缀符号
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            OutputAction::New => "+",
+            OutputAction::Overwrite => "!",
+            OutputAction::Skip => "-",
+            OutputAction::KeepBoth => "~",
+        }
+    }
+}
+
+impl fmt::Display for OutputAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputAction::New => write!(f, "New"),
+            OutputAction::Overwrite => write!(f, "Would overwrite"),
+            OutputAction::Skip => write!(f, "Would skip"),
+            OutputAction::KeepBoth => write!(f, "Keep both"),
+        }
+    }
+}
+
+/// 一个选中文件的计划输出
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlannedOutput {
+    /// 文件在传入的文件列表中的位置
+    pub index: usize,
+    pub name: String,
+    /// 输出路径，加密文件名时为None
+    pub output: Option<PathBuf>,
+    /// 输出路径上已有文件
+    pub exists: bool,
+}
+
+impl PlannedOutput {
+    /// 按生效的冲突处理方式得到的结果
+    pub fn action(&self, policy: ConflictPolicy) -> OutputAction {
+        match (self.exists, policy) {
+            (false, _) => OutputAction::New,
+            (true, ConflictPolicy::Overwrite) => OutputAction::Overwrite,
+            // 运行中无法询问，按跳过处理
+            (true, ConflictPolicy::Skip | ConflictPolicy::Ask) => OutputAction::Skip,
+            (true, ConflictPolicy::KeepBoth) => OutputAction::KeepBoth,
+        }
+    }
+}
+
+/// 列出 `files` 中选中文件的计划输出
+pub fn plan_outputs(settings: &Settings, files: &[FileItem]) -> Vec<PlannedOutput> {
+    files.iter()
+        .enumerate()
+        .filter(|(_, file)| file.selected)
+        .map(|(index, file)| {
+            let output = CryptoEngine::planned_output_path(settings, file);
+            PlannedOutput {
+                index,
+                name: file.name.clone(),
+                exists: output.as_ref().is_some_and(|path| path.exists()),
+                output,
+            }
+        })
+        .collect()
+}
+
+/// 试运行中一个选中文件的计划
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunEntry {
+    pub planned: PlannedOutput,
+    /// 按文件生效的冲突处理方式得到的结果
+    pub action: OutputAction,
+    /// 输入文件的大小
+    pub input_size: u64,
+    /// 输出大小的估计，跳过的文件为0
+    pub estimated_size: u64,
+}
+
+/// 一个文件系统上的输出需要的空间
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpaceRequirement {
+    /// 写入该文件系统的第一个输出目录
+    pub directory: PathBuf,
+    pub required: u64,
+    /// 可用空间，无法查询时为None
+    pub available: Option<u64>,
+}
+
+impl SpaceRequirement {
+    /// 可用空间足够（无法查询时视为足够）
+    pub fn is_sufficient(&self) -> bool {
+        self.available.is_none_or(|available| available >= self.required)
+    }
+}
+
+/// 试运行的结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunReport {
+    pub operation_mode: OperationMode,
+    pub entries: Vec<DryRunEntry>,
+    /// 归档和容器模式下整批写入的文件，已存在且不能覆盖时为开始后会报告的错误
+    pub bundle: Option<Result<PathBuf, String>>,
+    pub space: Vec<SpaceRequirement>,
+}
+
+impl DryRunReport {
+    /// 输出路径上已有文件的数量
+    pub fn collisions(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.planned.exists).count()
+    }
+
+    /// 所有输出大小的估计之和
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.estimated_size).sum()
+    }
+
+    /// 每个文件系统上的可用空间都足够
+    pub fn has_enough_space(&self) -> bool {
+        self.space.iter().all(SpaceRequirement::is_sufficient)
+    }
+}
+
+/// 试运行：按当前设置列出 `files` 中选中文件的输出路径、冲突、输出大小的估计和需要的磁盘空间，
+/// 不创建输出目录，也不读取或写出文件内容（解密时只读取文件头）
+///
+/// 增量加密中未修改的文件和解密时恢复的原始文件名要到开始后才能确定，这里按照会处理、按加密文件名输出估计
+pub fn dry_run(settings: &Settings, files: &[FileItem]) -> DryRunReport {
+    let entries: Vec<DryRunEntry> = plan_outputs(settings, files).into_iter()
+        .map(|planned| {
+            let file = &files[planned.index];
+            let action = planned.action(CryptoEngine::conflict_policy(settings, file));
+            DryRunEntry {
+                action,
+                input_size: file.size_on_disk(),
+                estimated_size: match action {
+                    OutputAction::Skip => 0,
+                    _ => CryptoEngine::estimated_output_size(settings, file),
+                },
+                planned,
+            }
+        })
+        .collect();
+
+    let selected: Vec<FileItem> = files.iter().filter(|file| file.selected).cloned().collect();
+    let bundle = match settings.operation_mode {
+        OperationMode::Archive if !selected.is_empty() => Some(archiver::archive_path(settings, &selected, archiver::ARCHIVE_EXTENSION)),
+        OperationMode::Container if !selected.is_empty() => Some(archiver::archive_path(settings, &selected, CONTAINER_EXTENSION)),
+        _ => None,
+    };
+
+    // 按输出所在的文件系统合计，无法识别文件系统时按目录合计
+    let mut space: Vec<(Option<u64>, SpaceRequirement)> = Vec::new();
+    for entry in &entries {
+        let directory = match &bundle {
+            Some(Ok(path)) => path.parent().map(Path::to_path_buf),
+            Some(Err(_)) => None,
+            None => CryptoEngine::planned_output_directory(settings, &files[entry.planned.index]),
+        };
+        let Some(directory) = directory.filter(|_| entry.estimated_size > 0) else {
+            continue;
+        };
+        let device = device_id(&directory);
+        let existing = space.iter_mut().find(|(id, requirement)| match device {
+            Some(_) => *id == device,
+            None => requirement.directory == directory,
+        });
+        match existing {
+            Some((_, requirement)) => requirement.required += entry.estimated_size,
+            None => space.push((device, SpaceRequirement {
+                available: available_space(&directory),
+                directory,
+                required: entry.estimated_size,
+            })),
+        }
+    }
+
+    DryRunReport {
+        operation_mode: settings.operation_mode.clone(),
+        entries,
+        bundle,
+        space: space.into_iter().map(|(_, requirement)| requirement).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OperationMode;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_plan_marks_existing_outputs() {
+        let dir = TestDir::new("preflight");
+        fs::write(dir.join("a.txt.enc"), b"old").unwrap();
+        let mut files = vec![
+            FileItem::new(dir.join("a.txt"), "a.txt".to_string()),
+            FileItem::new(dir.join("b.txt"), "b.txt".to_string()),
+            FileItem::new(dir.join("c.txt"), "c.txt".to_string()),
+        ];
+        files[0].selected = true;
+        files[1].selected = true;
+
+        let settings = Settings { operation_mode: OperationMode::Encrypt, encrypt_filename: false, ..Settings::default() };
+        let plan = plan_outputs(&settings, &files);
+        assert_eq!(plan.len(), 2);
+        assert_eq!(plan[0].output, Some(dir.join("a.txt.enc")));
+        assert_eq!(plan[0].action(ConflictPolicy::Skip), OutputAction::Skip);
+        assert_eq!(plan[0].action(ConflictPolicy::Ask), OutputAction::Skip);
+        assert_eq!(plan[1].action(ConflictPolicy::Overwrite), OutputAction::New);
+
+        // 随机文件名不会冲突
+        let settings = Settings { encrypt_filename: true, ..settings };
+        assert!(plan_outputs(&settings, &files).iter().all(|planned| planned.output.is_none() && !planned.exists));
+    }
+
+    #[test]
+    fn test_dry_run_estimates_without_writing() {
+        use crate::crypto::EncryptOptions;
+        use crate::models::{KdfAlgorithm, KdfParams};
+
+        let dir = TestDir::new("dry_run");
+        fs::write(dir.join("a.txt"), vec![b'a'; 200_000]).unwrap();
+        fs::write(dir.join("b.txt"), b"small").unwrap();
+        let files: Vec<FileItem> = ["a.txt", "b.txt"].iter()
+            .map(|name| FileItem { selected: true, ..FileItem::new(dir.join(name), name.to_string()) })
+            .collect();
+
+        let output_directory = dir.join("out").join("nested");
+        let settings = Settings {
+            operation_mode: OperationMode::Encrypt,
+            encrypt_filename: false,
+            output_directory: Some(output_directory.clone()),
+            ..Settings::default()
+        };
+        let report = dry_run(&settings, &files);
+        assert!(!dir.join("out").exists());
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(report.collisions(), 0);
+        assert_eq!(report.space.len(), 1);
+        assert_eq!(report.space[0].required, report.total_size());
+        assert!(report.entries.iter().all(|entry| entry.estimated_size > entry.input_size));
+
+        // 估计与实际加密的大小只差文件头中的几个字段
+        let options = EncryptOptions {
+            password: "pw".to_string(),
+            kdf: KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) },
+            ..EncryptOptions::default()
+        };
+        let encrypted = dir.join("a.txt.enc");
+        CryptoEngine::encrypt_path(&files[0].path, &encrypted, &options).unwrap();
+        let actual = fs::metadata(&encrypted).unwrap().len();
+        assert!(report.entries[0].estimated_size.abs_diff(actual) < 256);
+
+        // 解密按文件头中记录的明文长度估计，已有的输出按冲突处理方式计算
+        let decrypt = Settings { operation_mode: OperationMode::Decrypt, output_directory: None, conflict_policy: ConflictPolicy::Skip, ..settings };
+        let encrypted_file = FileItem { selected: true, ..FileItem::new(encrypted, "a.txt.enc".to_string()) };
+        let report = dry_run(&decrypt, std::slice::from_ref(&encrypted_file));
+        assert_eq!(report.collisions(), 1);
+        assert_eq!(report.entries[0].action, OutputAction::Skip);
+        assert_eq!(report.total_size(), 0);
+        let report = dry_run(&Settings { conflict_policy: ConflictPolicy::Overwrite, ..decrypt }, &[encrypted_file]);
+        assert_eq!(report.entries[0].estimated_size, 200_000);
+    }
+}
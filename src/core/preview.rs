@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// 文本和十六进制预览最多读取的字节数
+pub const PREVIEW_BYTES: usize = 64 * 1024;
+/// 十六进制预览最多显示的字节数
+const HEX_PREVIEW_BYTES: usize = 4 * 1024;
+/// 超过此大小的图片不解码预览
+const MAX_IMAGE_BYTES: u64 = 32 * 1024 * 1024;
+/// 图片预览的最大边长（像素）
+const IMAGE_PREVIEW_SIZE: u32 = 512;
+
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "bmp"];
+
+/// 预览内容
+#[derive(Debug, Clone)]
+pub enum PreviewContent {
+    /// 文本文件，附带检测到的编码名称
+    Text { encoding: String, text: String },
+    /// 二进制文件的十六进制转储
+    Hex(String),
+    /// 缩小后的RGBA图片
+    Image { width: u32, height: u32, rgba: Vec<u8> },
+}
+
+/// 源文件的预览
+#[derive(Debug, Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub size: u64,
+    /// 只预览了文件的开头部分
+    pub truncated: bool,
+    pub content: PreviewContent,
+}
+
+impl FilePreview {
+    /// 读取文件开头并生成预览（图片按扩展名识别，解码失败时退回十六进制）
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let size = std::fs::metadata(path)?.len();
+        Self::from_reader(path, size, File::open(path)?)
+    }
+
+    /// 从任意读取器生成预览，`path` 只用于显示文件名和识别图片扩展名
+    pub fn from_reader(path: &Path, size: u64, mut reader: impl Read) -> io::Result<Self> {
+        let mut data = Vec::with_capacity(PREVIEW_BYTES.min(size as usize));
+        if is_image(path) && size <= MAX_IMAGE_BYTES {
+            reader.read_to_end(&mut data)?;
+            if let Ok(image) = image::load_from_memory(&data) {
+                let image = image.thumbnail(IMAGE_PREVIEW_SIZE, IMAGE_PREVIEW_SIZE).to_rgba8();
+                return Ok(Self {
+                    path: path.to_path_buf(),
+                    size,
+                    truncated: false,
+                    content: PreviewContent::Image {
+                        width: image.width(),
+                        height: image.height(),
+                        rgba: image.into_raw(),
+                    },
+                });
+            }
+            data.truncate(PREVIEW_BYTES);
+        } else {
+            reader.take(PREVIEW_BYTES as u64).read_to_end(&mut data)?;
+        }
+
+        let truncated = (data.len() as u64) < size;
+        let content = match decode_text(&data, !truncated) {
+            Some((encoding, text)) => PreviewContent::Text { encoding, text },
+            None => PreviewContent::Hex(hex_dump(&data[..data.len().min(HEX_PREVIEW_BYTES)])),
+        };
+        let truncated = match &content {
+            PreviewContent::Hex(_) => size > HEX_PREVIEW_BYTES as u64,
+            _ => truncated,
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            size,
+            truncated,
+            content,
+        })
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// 检测编码并解码文本，数据看起来是二进制时返回None
+fn decode_text(data: &[u8], is_complete: bool) -> Option<(String, String)> {
+    // 文本文件中几乎不会出现NUL字节（UTF-16除外，带BOM时由下面处理）
+    let has_bom = data.starts_with(&[0xFF, 0xFE]) || data.starts_with(&[0xFE, 0xFF]);
+    if !has_bom && data.iter().take(8192).any(|&byte| byte == 0) {
+        return None;
+    }
+
+    let encoding = match encoding_rs::Encoding::for_bom(data) {
+        Some((encoding, _)) => encoding,
+        None => {
+            let mut detector = chardetng::EncodingDetector::new();
+            detector.feed(data, is_complete);
+            detector.guess(None, true)
+        }
+    };
+    let (text, used, had_errors) = encoding.decode(data);
+    // 截断处可能切断多字节字符，只有大量替换字符时才视为二进制
+    if had_errors && text.matches('\u{FFFD}').count() > 8 {
+        return None;
+    }
+    Some((used.name().to_string(), text.into_owned()))
+}
+
+/// 生成十六进制转储（偏移、16个字节、可打印字符）
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::with_capacity(data.len() * 4 + data.len() / 16 * 12);
+    for (line, chunk) in data.chunks(16).enumerate() {
+        dump.push_str(&format!("{:08x}  ", line * 16));
+        for column in 0..16 {
+            match chunk.get(column) {
+                Some(byte) => dump.push_str(&format!("{:02x} ", byte)),
+                None => dump.push_str("   "),
+            }
+            if column == 7 {
+                dump.push(' ');
+            }
+        }
+        dump.push(' ');
+        dump.extend(chunk.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }));
+        dump.push('\n');
+    }
+    dump
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_and_binary_detection() {
+        let (encoding, text) = decode_text("héllo wörld".as_bytes(), true).unwrap();
+        assert_eq!(encoding, "UTF-8");
+        assert_eq!(text, "héllo wörld");
+
+        assert!(decode_text(&[0x00, 0x01, 0x02, 0xFF], true).is_none());
+        assert_eq!(
+            hex_dump(b"KRYP\x00\x01"),
+            "00000000  4b 52 59 50 00 01                                 KRYP..\n"
+        );
+    }
+}
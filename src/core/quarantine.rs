@@ -0,0 +1,169 @@
+//! 删除源文件前的隔离区
+//!
+//! 设置了删除源文件时，源文件先移动到所在目录下的隐藏文件夹 `.krypton-quarantine/<批次>/`，
+//! 批处理成功后才真正删除（或覆写后删除）；批处理失败、取消或用户撤销时移回原处。
+//! 移动发生在同一目录下，不会跨文件系统复制数据
+
+use super::shred::shred_file;
+use rand::RngCore;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 隔离文件夹的名称（位于源文件所在目录中）
+pub const QUARANTINE_DIR_NAME: &str = ".krypton-quarantine";
+
+/// 一个批处理的隔离区，克隆的实例共享同一份记录
+#[derive(Debug, Clone)]
+pub struct Quarantine {
+    batch_id: String,
+    /// 清除时先覆写的遍数，为None时直接删除
+    shred_passes: Option<u32>,
+    /// （原路径，隔离后的路径）
+    entries: Arc<Mutex<Vec<(PathBuf, PathBuf)>>>,
+    /// 存放隔离文件的批次文件夹（包括接管的中断批次的文件夹）
+    directories: Arc<Mutex<BTreeSet<PathBuf>>>,
+}
+
+impl Quarantine {
+    pub fn new(shred_passes: Option<u32>) -> Self {
+        let mut suffix = [0u8; 4];
+        rand::thread_rng().fill_bytes(&mut suffix);
+        Self {
+            batch_id: format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), hex::encode(suffix)),
+            shred_passes,
+            entries: Arc::new(Mutex::new(Vec::new())),
+            directories: Arc::new(Mutex::new(BTreeSet::new())),
+        }
+    }
+
+    /// 把源文件移入隔离区
+    pub fn move_in(&self, path: &Path) -> io::Result<()> {
+        let name = path.file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        let parent = path.parent().unwrap_or(Path::new(""));
+        let directory = parent.join(QUARANTINE_DIR_NAME).join(&self.batch_id);
+        fs::create_dir_all(&directory)?;
+        self.directories.lock().unwrap().insert(directory.clone());
+        let target = directory.join(name);
+        fs::rename(path, &target)?;
+        self.entries.lock().unwrap().push((path.to_path_buf(), target));
+        Ok(())
+    }
+
+    /// 隔离的文件（原路径，隔离后的路径）
+    pub fn entries(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// 接管中断的批处理隔离的文件，与本批次的文件一起清除或恢复
+    pub fn adopt(&self, entries: Vec<(PathBuf, PathBuf)>) {
+        let mut directories = self.directories.lock().unwrap();
+        for (_, quarantined) in &entries {
+            directories.extend(quarantined.parent().map(Path::to_path_buf));
+        }
+        self.entries.lock().unwrap().extend(entries.into_iter().filter(|(_, quarantined)| quarantined.exists()));
+    }
+
+    /// 隔离的文件数
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 把所有隔离的文件移回原处，返回恢复的文件数。
+    /// 原处已有同名文件时不覆盖，该文件留在隔离区并报告错误
+    pub fn restore(&self) -> io::Result<usize> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut restored = 0;
+        let mut first_error = None;
+        entries.retain(|(original, quarantined)| {
+            let result = if original.exists() {
+                Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' already exists", original.display())))
+            } else {
+                fs::rename(quarantined, original)
+            };
+            match result {
+                Ok(()) => {
+                    restored += 1;
+                    false
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    true
+                }
+            }
+        });
+        drop(entries);
+        self.cleanup_directories();
+        first_error.map_or(Ok(restored), Err)
+    }
+
+    /// 删除（或覆写后删除）所有隔离的文件，返回删除的文件数
+    pub fn purge(&self) -> io::Result<usize> {
+        let mut entries = self.entries.lock().unwrap();
+        let mut purged = 0;
+        let mut first_error = None;
+        entries.retain(|(_, quarantined)| {
+            let result = match self.shred_passes {
+                Some(passes) => shred_file(quarantined, passes),
+                None => fs::remove_file(quarantined),
+            };
+            match result {
+                Ok(()) => {
+                    purged += 1;
+                    false
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    true
+                }
+            }
+        });
+        drop(entries);
+        self.cleanup_directories();
+        first_error.map_or(Ok(purged), Err)
+    }
+
+    /// 删除已经清空的隔离文件夹
+    fn cleanup_directories(&self) {
+        for directory in self.directories.lock().unwrap().iter() {
+            // 只删除空文件夹，仍有文件时忽略错误
+            let _ = fs::remove_dir(directory);
+            if let Some(root) = directory.parent() {
+                let _ = fs::remove_dir(root);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_restore_and_purge() {
+        let dir = TestDir::new("quarantine");
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, b"a").unwrap();
+        fs::write(&b, b"b").unwrap();
+
+        let quarantine = Quarantine::new(None);
+        quarantine.move_in(&a).unwrap();
+        assert!(!a.exists());
+        assert_eq!(quarantine.restore().unwrap(), 1);
+        assert_eq!(fs::read(&a).unwrap(), b"a");
+
+        quarantine.move_in(&a).unwrap();
+        quarantine.move_in(&b).unwrap();
+        assert_eq!(quarantine.purge().unwrap(), 2);
+        assert!(!a.exists() && !b.exists());
+        assert!(!dir.join(QUARANTINE_DIR_NAME).exists());
+    }
+}
@@ -0,0 +1,237 @@
+//! 纠删码恢复数据
+//!
+//! 加密时可以为每个输出文件写出 `<文件名>.rec`（类似PAR2）：文件按固定大小分块，每最多 `STRIPE_BLOCKS`
+//! 个连续数据块为一组，用Reed-Solomon编码生成一定比例的校验块，并记录每块的BLAKE3哈希。
+//! 解密前如果输入文件旁有恢复文件，按哈希找出损坏或缺失（文件被截断）的数据块，在原文件中就地修复
+//!
+//! 恢复文件格式：`KRYPREC` + 版本(1) | 块大小(u32) | 数据长度(u64) | 百分比(u8)，
+//! 之后每组依次为数据块哈希、校验块哈希和校验块
+
+use crate::models::RecoveryLevel;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 恢复文件的扩展名
+pub const RECOVERY_EXTENSION: &str = "rec";
+const RECOVERY_MAGIC: [u8; 8] = *b"KRYPREC\x01";
+const HEADER_LEN: usize = RECOVERY_MAGIC.len() + 4 + 8 + 1;
+const HASH_LEN: usize = 32;
+/// 最小块大小（一个扇区簇）
+const MIN_BLOCK_SIZE: usize = 4096;
+/// 最大块大小，限制每组需要的内存
+const MAX_BLOCK_SIZE: usize = 64 * 1024;
+/// 小文件也至少分成这么多块，使校验块能修复局部损坏
+const TARGET_BLOCKS: u64 = 4096;
+/// 每组的最大数据块数（数据块与校验块合计不能超过GF(2^8)的256个）
+const STRIPE_BLOCKS: usize = 200;
+
+/// 恢复文件的路径：在文件名后追加 `.rec`
+pub fn recovery_path(path: &Path) -> Option<PathBuf> {
+    let mut name = path.file_name()?.to_os_string();
+    name.push(".");
+    name.push(RECOVERY_EXTENSION);
+    Some(path.with_file_name(name))
+}
+
+/// 文件旁已有的恢复文件
+pub fn existing_recovery(path: &Path) -> Option<PathBuf> {
+    recovery_path(path).filter(|recovery| recovery.is_file())
+}
+
+/// 分块和分组方式，由数据长度、块大小和百分比决定
+struct Layout {
+    block_size: usize,
+    data_len: u64,
+    percent: u8,
+}
+
+impl Layout {
+    fn new(data_len: u64, percent: u8) -> Self {
+        let block_size = (data_len.div_ceil(TARGET_BLOCKS) as usize)
+            .next_multiple_of(MIN_BLOCK_SIZE)
+            .clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
+        Self { block_size, data_len, percent }
+    }
+
+    /// 每组的（第一个数据块的编号，数据块数）
+    fn stripes(&self) -> impl Iterator<Item = (u64, usize)> {
+        let blocks = self.data_len.div_ceil(self.block_size as u64);
+        (0..blocks).step_by(STRIPE_BLOCKS)
+            .map(move |first| (first, (blocks - first).min(STRIPE_BLOCKS as u64) as usize))
+    }
+
+    /// 一组数据块对应的校验块数
+    fn parity_count(&self, data_blocks: usize) -> usize {
+        (data_blocks * self.percent as usize).div_ceil(100).max(1)
+    }
+
+    /// 数据块在文件中的位置和实际长度（最后一块可能不满）
+    fn block_range(&self, block: u64) -> (u64, usize) {
+        let offset = block * self.block_size as u64;
+        (offset, (self.data_len - offset).min(self.block_size as u64) as usize)
+    }
+
+    fn write_header(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&RECOVERY_MAGIC)?;
+        writer.write_all(&(self.block_size as u32).to_le_bytes())?;
+        writer.write_all(&self.data_len.to_le_bytes())?;
+        writer.write_all(&[self.percent])
+    }
+
+    fn read_header(reader: &mut impl Read) -> Option<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header).ok()?;
+        if header[..RECOVERY_MAGIC.len()] != RECOVERY_MAGIC {
+            return None;
+        }
+        let block_size = u32::from_le_bytes(header[8..12].try_into().ok()?) as usize;
+        let data_len = u64::from_le_bytes(header[12..20].try_into().ok()?);
+        let percent = header[20];
+        let valid = (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size) && (1..=100).contains(&percent);
+        valid.then_some(Self { block_size, data_len, percent })
+    }
+}
+
+/// 为文件写出恢复文件，返回恢复文件的路径（未启用时返回None）
+pub fn write_recovery(path: &Path, level: RecoveryLevel) -> io::Result<Option<PathBuf>> {
+    let (Some(recovery), percent @ 1..) = (recovery_path(path), level.percent()) else {
+        return Ok(None);
+    };
+    let mut input = BufReader::new(File::open(path)?);
+    let layout = Layout::new(input.get_ref().metadata()?.len(), percent);
+    let mut output = BufWriter::new(File::create(&recovery)?);
+    layout.write_header(&mut output)?;
+
+    for (first, data_blocks) in layout.stripes() {
+        let mut data = Vec::with_capacity(data_blocks);
+        for block in first..first + data_blocks as u64 {
+            let mut buffer = vec![0u8; layout.block_size];
+            input.read_exact(&mut buffer[..layout.block_range(block).1])?;
+            data.push(buffer);
+        }
+        let mut parity = vec![vec![0u8; layout.block_size]; layout.parity_count(data_blocks)];
+        ReedSolomon::new(data_blocks, parity.len())
+            .and_then(|codec| codec.encode_sep(&data, &mut parity))
+            .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+        for block in data.iter().chain(&parity) {
+            output.write_all(blake3::hash(block).as_bytes())?;
+        }
+        for block in &parity {
+            output.write_all(block)?;
+        }
+    }
+    output.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(Some(recovery))
+}
+
+/// 用文件旁的恢复文件就地修复损坏或缺失的数据块，返回修复的块数（没有恢复文件时返回0）
+pub fn repair(path: &Path) -> Result<usize, String> {
+    let Some(recovery) = existing_recovery(path) else {
+        return Ok(0);
+    };
+    let damaged = || format!("Recovery file '{}' is damaged", recovery.display());
+    let mut reader = BufReader::new(File::open(&recovery)
+        .map_err(|e| format!("Cannot read recovery file '{}': {}", recovery.display(), e))?);
+    let layout = Layout::read_header(&mut reader).ok_or_else(damaged)?;
+    let mut file = OpenOptions::new().read(true).write(true).open(path)
+        .map_err(|e| format!("Cannot open '{}' for repair: {}", path.display(), e))?;
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut repaired = 0;
+    for (first, data_blocks) in layout.stripes() {
+        let parity_blocks = layout.parity_count(data_blocks);
+        let mut hashes = vec![[0u8; HASH_LEN]; data_blocks + parity_blocks];
+        for hash in hashes.iter_mut() {
+            reader.read_exact(hash).map_err(|_| damaged())?;
+        }
+
+        // 哈希不符的块视为丢失，由Reed-Solomon按位置恢复
+        let mut shards = Vec::with_capacity(data_blocks + parity_blocks);
+        for block in first..first + data_blocks as u64 {
+            let (offset, len) = layout.block_range(block);
+            let mut buffer = vec![0u8; layout.block_size];
+            read_block(&mut file, offset, &mut buffer[..len], file_len).map_err(|e| e.to_string())?;
+            shards.push(Some(buffer));
+        }
+        for _ in 0..parity_blocks {
+            let mut buffer = vec![0u8; layout.block_size];
+            reader.read_exact(&mut buffer).map_err(|_| damaged())?;
+            shards.push(Some(buffer));
+        }
+        for (shard, hash) in shards.iter_mut().zip(&hashes) {
+            if shard.as_ref().is_some_and(|block| blake3::hash(block).as_bytes() != hash) {
+                *shard = None;
+            }
+        }
+        let missing: Vec<usize> = (0..data_blocks).filter(|&i| shards[i].is_none()).collect();
+        if missing.is_empty() {
+            continue;
+        }
+
+        let lost = shards.iter().filter(|shard| shard.is_none()).count();
+        ReedSolomon::new(data_blocks, parity_blocks)
+            .and_then(|codec| codec.reconstruct_data(&mut shards))
+            .map_err(|_| format!(
+                "'{}' is too damaged to repair: {} blocks near offset {} are lost, the recovery data covers {}",
+                path.display(), lost, first * layout.block_size as u64, parity_blocks
+            ))?;
+        for index in missing {
+            let (offset, len) = layout.block_range(first + index as u64);
+            let block = shards[index].as_ref().expect("reconstructed block");
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.write_all(&block[..len]))
+                .map_err(|e| format!("Cannot write the repaired data to '{}': {}", path.display(), e))?;
+            repaired += 1;
+        }
+    }
+    // 去掉文件末尾多出的数据
+    if file_len > layout.data_len {
+        file.set_len(layout.data_len).map_err(|e| e.to_string())?;
+    }
+    file.sync_all().map_err(|e| e.to_string())?;
+    Ok(repaired)
+}
+
+/// 读取一个数据块，超出文件末尾的部分保持为0（截断的文件按损坏处理）
+fn read_block(file: &mut File, offset: u64, buffer: &mut [u8], file_len: u64) -> io::Result<()> {
+    let available = file_len.saturating_sub(offset).min(buffer.len() as u64) as usize;
+    if available == 0 {
+        return Ok(());
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer[..available])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_repair_damaged_and_truncated_file() {
+        let dir = TestDir::new("recovery");
+        let path = dir.join("data.enc");
+        let original: Vec<u8> = (0..300_000u32).map(|i| (i * 7 + i / 251) as u8).collect();
+        fs::write(&path, &original).unwrap();
+
+        assert_eq!(write_recovery(&path, RecoveryLevel::None).unwrap(), None);
+        let recovery = write_recovery(&path, RecoveryLevel::Percent10).unwrap().unwrap();
+        assert_eq!(recovery, dir.join("data.enc.rec"));
+        assert_eq!(repair(&path), Ok(0));
+
+        // 覆写一段数据并截掉末尾
+        let mut damaged = original.clone();
+        damaged[10_000..14_000].fill(0xff);
+        damaged.truncate(original.len() - 1000);
+        fs::write(&path, &damaged).unwrap();
+        assert_eq!(repair(&path), Ok(4));
+        assert_eq!(fs::read(&path).unwrap(), original);
+
+        // 损坏超过恢复数据的能力时报告错误
+        fs::write(&path, &original[..4096]).unwrap();
+        assert!(repair(&path).is_err());
+    }
+}
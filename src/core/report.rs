@@ -0,0 +1,163 @@
+//! 批处理结束后导出的JSON操作报告
+//!
+//! 报告记录每个文件的结果、大小、耗时和输出文件的SHA-256，以及本次设置的指纹，
+//! 用于审计留档和自动化流程核对。报告不包含密码或密钥服务的凭据
+
+use crate::models::{EncryptionAlgorithm, FileItem, OperationMode, OperationStatus, Settings};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 单个文件的处理结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum FileOutcome {
+    Completed,
+    Skipped,
+    Failed { error: String },
+    /// 操作被取消或提前停止，文件没有处理
+    NotProcessed,
+}
+
+/// 报告中的一个文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileReport {
+    pub name: String,
+    pub source: PathBuf,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+    pub source_size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_size: Option<u64>,
+    /// 输出文件的SHA-256
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sha256: Option<String>,
+    /// 处理耗时（秒）
+    pub duration_secs: f64,
+}
+
+impl FileReport {
+    /// 处理结果为 `outcome` 的文件，成功时读取输出文件的大小并计算哈希
+    pub fn new(file: &FileItem, outcome: Result<&Path, &str>, duration: Duration) -> Self {
+        let mut report = Self::not_processed(file);
+        report.duration_secs = duration.as_secs_f64();
+        match outcome {
+            Ok(output) => {
+                report.outcome = FileOutcome::Completed;
+                report.output_size = fs::metadata(output).map(|metadata| metadata.len()).ok();
+                report.output_sha256 = hash_file(output).ok();
+                report.output = Some(output.to_path_buf());
+            }
+            Err(error) => report.outcome = FileOutcome::Failed { error: error.to_string() },
+        }
+        report
+    }
+
+    pub fn skipped(file: &FileItem) -> Self {
+        Self { outcome: FileOutcome::Skipped, ..Self::not_processed(file) }
+    }
+
+    pub fn not_processed(file: &FileItem) -> Self {
+        Self {
+            name: file.name.clone(),
+            source: file.path.clone(),
+            outcome: FileOutcome::NotProcessed,
+            source_size: file.size_on_disk(),
+            output: None,
+            output_size: None,
+            output_sha256: None,
+            duration_secs: 0.0,
+        }
+    }
+}
+
+/// 一次批处理的报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationReport {
+    /// 开始时间（RFC 3339）
+    pub started: String,
+    pub duration_secs: f64,
+    pub mode: OperationMode,
+    pub algorithm: EncryptionAlgorithm,
+    /// 设置的SHA-256指纹，设置相同的批处理指纹相同
+    pub settings_fingerprint: String,
+    pub status: OperationStatus,
+    pub files: Vec<FileReport>,
+}
+
+impl OperationReport {
+    /// 写出报告：设置中指定了路径时写到该路径，否则在输出目录（或第一个源文件所在目录）中按开始时间命名
+    pub fn write(&self, settings: &Settings) -> io::Result<PathBuf> {
+        let path = if settings.report_path.trim().is_empty() {
+            let directory = settings.output_directory.clone()
+                .or_else(|| self.files.first().and_then(|file| file.source.parent()).map(Path::to_path_buf))
+                .unwrap_or_default();
+            let started = chrono::DateTime::parse_from_rfc3339(&self.started)
+                .map(|time| time.format("%Y%m%d-%H%M%S").to_string())
+                .unwrap_or_default();
+            directory.join(format!("krypton-report-{}.json", started))
+        } else {
+            PathBuf::from(settings.report_path.trim())
+        };
+
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&path, json)?;
+        Ok(path)
+    }
+}
+
+/// 设置的指纹：序列化后（不含密码和凭据）的SHA-256
+pub fn settings_fingerprint(settings: &Settings) -> String {
+    let json = serde_json::to_vec(settings).unwrap_or_default();
+    hex::encode(Sha256::digest(json))
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_report_round_trips_through_json() {
+        let dir = TestDir::new("report");
+        let output = dir.join("a.enc");
+        fs::write(&output, b"abc").unwrap();
+        let file = FileItem::new(dir.join("a.txt"), "a.txt".to_string());
+
+        let settings = Settings { output_directory: Some(dir.to_path_buf()), ..Settings::default() };
+        let report = OperationReport {
+            started: chrono::Local::now().to_rfc3339(),
+            duration_secs: 1.5,
+            mode: settings.operation_mode.clone(),
+            algorithm: settings.encryption_algorithm.clone(),
+            settings_fingerprint: settings_fingerprint(&settings),
+            status: OperationStatus::Completed,
+            files: vec![
+                FileReport::new(&file, Ok(&output), Duration::from_millis(20)),
+                FileReport::new(&file, Err("disk full"), Duration::ZERO),
+                FileReport::skipped(&file),
+            ],
+        };
+        let path = report.write(&settings).unwrap();
+        assert_eq!(path.parent(), Some(&*dir));
+
+        let read: OperationReport = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(read.files[0].output_size, Some(3));
+        assert_eq!(read.files[0].output_sha256.as_deref(), Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+        assert_eq!(read.files[1].outcome, FileOutcome::Failed { error: "disk full".to_string() });
+        assert_eq!(read.files[2].outcome, FileOutcome::Skipped);
+        assert_eq!(read.settings_fingerprint, settings_fingerprint(&settings));
+    }
+}
@@ -0,0 +1,120 @@
+//! 并行递归扫描目录树
+//!
+//! 每个子目录作为一个rayon任务读取，发现的文件立即交给回调，调用方可以边扫描边处理，
+//! 不必等整棵树扫描完成。不跟随符号链接；回调的顺序不确定，需要稳定顺序时由调用方排序
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+/// 扫描到的普通文件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedFile {
+    pub path: PathBuf,
+    /// 相对于扫描根目录、以/分隔的路径
+    pub relative: String,
+}
+
+/// 并行扫描 `root` 下的所有普通文件，每发现一个文件调用一次 `on_file`（可能在多个线程中调用）
+///
+/// 读取任何目录出错时停止扫描并返回第一个错误；`cancel` 被置位时尽快停止并返回Ok
+pub fn scan_tree(root: &Path, cancel: &AtomicBool, on_file: &(dyn Fn(ScannedFile) + Sync)) -> io::Result<()> {
+    let walker = Walker {
+        root,
+        cancel,
+        on_file,
+        error: Mutex::new(None),
+    };
+    rayon::scope(|scope| walker.scan_directory(scope, root.to_path_buf()));
+    match walker.error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// 在后台并行扫描，把结果逐个交给当前线程的 `on_file`，适用于不能跨线程调用的回调
+pub fn scan_tree_into(root: &Path, cancel: &AtomicBool, mut on_file: impl FnMut(ScannedFile)) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        let scanner = scope.spawn(move || scan_tree(root, cancel, &|file| {
+            let _ = sender.send(file);
+        }));
+        for file in receiver {
+            on_file(file);
+        }
+        scanner.join().unwrap()
+    })
+}
+
+struct Walker<'a> {
+    root: &'a Path,
+    cancel: &'a AtomicBool,
+    on_file: &'a (dyn Fn(ScannedFile) + Sync),
+    /// 第一个错误，出现后其余任务不再读取新目录
+    error: Mutex<Option<io::Error>>,
+}
+
+impl<'a> Walker<'a> {
+    fn scan_directory<'s>(&'s self, scope: &rayon::Scope<'s>, directory: PathBuf) {
+        if self.stopped() {
+            return;
+        }
+        if let Err(e) = self.read_directory(scope, &directory) {
+            self.error.lock().unwrap()
+                .get_or_insert_with(|| io::Error::new(e.kind(), format!("{}: {}", directory.display(), e)));
+        }
+    }
+
+    fn read_directory<'s>(&'s self, scope: &rayon::Scope<'s>, directory: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                scope.spawn(move |scope| self.scan_directory(scope, path));
+            } else if file_type.is_file() {
+                (self.on_file)(ScannedFile { relative: relative_path(self.root, &path), path });
+            }
+        }
+        Ok(())
+    }
+
+    fn stopped(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed) || self.error.lock().unwrap().is_some()
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_scan_finds_nested_files() {
+        let root = TestDir::new("scan");
+        for directory in ["a/b/c", "d", "e"] {
+            fs::create_dir_all(root.join(directory)).unwrap();
+        }
+        for file in ["top.txt", "a/one.txt", "a/b/two.txt", "a/b/c/three.txt", "d/four.txt"] {
+            fs::write(root.join(file), file).unwrap();
+        }
+
+        let mut files = Vec::new();
+        scan_tree_into(&root, &AtomicBool::new(false), |file| files.push(file.relative)).unwrap();
+        files.sort();
+        assert_eq!(files, ["a/b/c/three.txt", "a/b/two.txt", "a/one.txt", "d/four.txt", "top.txt"]);
+
+        assert!(scan_tree_into(&root.join("missing"), &AtomicBool::new(false), |_| {}).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use super::app_data;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+
+/// 计划任务列表的存储文件名
+const SCHEDULE_FILE_NAME: &str = "schedule.json";
+
+/// 任务的重复周期
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Recurrence {
+    /// 每隔固定分钟数运行
+    Interval { minutes: u32 },
+    /// 每天在指定时间运行
+    Daily { hour: u32, minute: u32 },
+    /// 每周在指定星期（0 = 周一）和时间运行
+    Weekly { weekday: u32, hour: u32, minute: u32 },
+}
+
+impl Default for Recurrence {
+    fn default() -> Self {
+        Recurrence::Daily { hour: 2, minute: 0 }
+    }
+}
+
+impl Recurrence {
+    /// 计算严格晚于 `after` 的下一次运行时间
+    pub fn next_run_after(&self, after: DateTime<Local>) -> DateTime<Local> {
+        match *self {
+            Recurrence::Interval { minutes } => after + Duration::minutes(minutes.max(1) as i64),
+            Recurrence::Daily { hour, minute } => {
+                let mut candidate = at_time(after, hour, minute);
+                while candidate <= after {
+                    candidate = at_time(candidate + Duration::days(1), hour, minute);
+                }
+                candidate
+            }
+            Recurrence::Weekly { weekday, hour, minute } => {
+                let current = after.weekday().num_days_from_monday();
+                let days_ahead = (weekday.min(6) + 7 - current) % 7;
+                let mut candidate = at_time(after + Duration::days(days_ahead as i64), hour, minute);
+                while candidate <= after {
+                    candidate = at_time(candidate + Duration::days(7), hour, minute);
+                }
+                candidate
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+        match self {
+            Recurrence::Interval { minutes } => write!(f, "Every {} min", minutes),
+            Recurrence::Daily { hour, minute } => write!(f, "Daily at {:02}:{:02}", hour, minute),
+            Recurrence::Weekly { weekday, hour, minute } => write!(
+                f,
+                "Weekly on {} at {:02}:{:02}",
+                WEEKDAYS[(*weekday).min(6) as usize],
+                hour,
+                minute
+            ),
+        }
+    }
+}
+
+/// 把日期时间的时分设置为指定值（遇到夏令时跳变时取最早的有效时间）
+fn at_time(date: DateTime<Local>, hour: u32, minute: u32) -> DateTime<Local> {
+    let time = NaiveTime::from_hms_opt(hour.min(23), minute.min(59), 0).unwrap_or_default();
+    let naive = date.date_naive().and_time(time);
+    Local.from_local_datetime(&naive)
+        .earliest()
+        .unwrap_or_else(|| date + Duration::hours(1))
+}
+
+/// 一个计划运行的任务
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    /// 任务定义文件（.kjob）
+    pub job_path: PathBuf,
+    pub recurrence: Recurrence,
+    pub enabled: bool,
+    /// 加入计划的时间（Unix秒），用于计算首次运行时间
+    pub created_at: i64,
+    /// 上次运行的时间（Unix秒）
+    pub last_run: Option<i64>,
+}
+
+impl ScheduledJob {
+    pub fn new(job_path: PathBuf, recurrence: Recurrence) -> Self {
+        Self {
+            job_path,
+            recurrence,
+            enabled: true,
+            created_at: Local::now().timestamp(),
+            last_run: None,
+        }
+    }
+
+    /// 下一次计划运行的时间
+    pub fn next_run(&self) -> DateTime<Local> {
+        let base = self.last_run.unwrap_or(self.created_at);
+        let base = Local.timestamp_opt(base, 0).single().unwrap_or_else(Local::now);
+        self.recurrence.next_run_after(base)
+    }
+
+    /// 是否已到运行时间
+    pub fn is_due(&self, now: DateTime<Local>) -> bool {
+        self.enabled && self.next_run() <= now
+    }
+
+    /// 显示用的任务名称
+    pub fn display_name(&self) -> String {
+        self.job_path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.job_path.display().to_string())
+    }
+}
+
+/// 计划任务列表，保存在应用数据目录中
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    pub jobs: Vec<ScheduledJob>,
+}
+
+impl Schedule {
+    /// 加载计划任务列表，不存在时返回空列表
+    pub fn load() -> io::Result<Self> {
+        app_data::load_json(&app_data::app_data_file(SCHEDULE_FILE_NAME)?)
+    }
+
+    /// 保存计划任务列表
+    pub fn save(&self) -> io::Result<()> {
+        app_data::save_config(&app_data::app_data_file(SCHEDULE_FILE_NAME)?, self)
+    }
+
+    /// 第一个已到运行时间的任务
+    pub fn next_due(&self, now: DateTime<Local>) -> Option<usize> {
+        self.jobs.iter().position(|job| job.is_due(now))
+    }
+
+    pub fn has_enabled_jobs(&self) -> bool {
+        self.jobs.iter().any(|job| job.enabled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).earliest().unwrap()
+    }
+
+    #[test]
+    fn test_next_run_after() {
+        // 2025-01-06 是周一
+        let now = local(2025, 1, 6, 10, 30);
+
+        let interval = Recurrence::Interval { minutes: 15 };
+        assert_eq!(interval.next_run_after(now), local(2025, 1, 6, 10, 45));
+
+        let daily = Recurrence::Daily { hour: 2, minute: 0 };
+        assert_eq!(daily.next_run_after(now), local(2025, 1, 7, 2, 0));
+        let later_today = Recurrence::Daily { hour: 18, minute: 5 };
+        assert_eq!(later_today.next_run_after(now), local(2025, 1, 6, 18, 5));
+
+        let weekly = Recurrence::Weekly { weekday: 2, hour: 9, minute: 0 };
+        assert_eq!(weekly.next_run_after(now), local(2025, 1, 8, 9, 0));
+        let same_day_passed = Recurrence::Weekly { weekday: 0, hour: 9, minute: 0 };
+        assert_eq!(same_day_passed.next_run_after(now), local(2025, 1, 13, 9, 0));
+    }
+}
@@ -0,0 +1,136 @@
+//! 删除源文件前的安全覆写
+//!
+//! 覆写只能尽力而为：SSD的磨损均衡和写时复制文件系统（btrfs、ZFS、APFS等）会把新数据写到别处，
+//! 原来的数据块可能仍然残留，快照和备份中的副本也不受影响。[`limitations`] 检测已知会使覆写无效的情况
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// 覆写时每次写入的块大小
+const SHRED_BLOCK_SIZE: usize = 64 * 1024;
+/// 覆写遍数的上限
+pub const MAX_SHRED_PASSES: u32 = 7;
+
+/// 会使覆写无效的存储特性
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShredLimitation {
+    /// 写时复制文件系统，覆写的数据写到新的位置
+    CopyOnWrite,
+    /// 固态硬盘，磨损均衡会把覆写的数据写到新的闪存块
+    SolidState,
+}
+
+impl fmt::Display for ShredLimitation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShredLimitation::CopyOnWrite => write!(f, "copy-on-write filesystem"),
+            ShredLimitation::SolidState => write!(f, "solid-state drive"),
+        }
+    }
+}
+
+/// 用随机数据覆写文件内容 `passes` 遍，改为随机文件名后删除
+///
+/// 只能尽力而为：在写时复制文件系统或SSD上，原始数据块可能仍然残留
+pub fn shred_file(path: &Path, passes: u32) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut block = vec![0u8; SHRED_BLOCK_SIZE];
+    for _ in 0..passes.clamp(1, MAX_SHRED_PASSES) {
+        file.seek(SeekFrom::Start(0))?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(SHRED_BLOCK_SIZE as u64) as usize;
+            OsRng.fill_bytes(&mut block[..n]);
+            file.write_all(&block[..n])?;
+            remaining -= n as u64;
+        }
+        // 每一遍都写到磁盘，否则后一遍只会覆盖页缓存
+        file.sync_all()?;
+    }
+    file.set_len(0)?;
+    file.sync_all()?;
+    drop(file);
+
+    // 目录项中不留下原文件名，改名失败时按原名删除
+    let mut random_name = [0u8; 8];
+    OsRng.fill_bytes(&mut random_name);
+    let renamed = path.with_file_name(hex::encode(random_name));
+    match fs::rename(path, &renamed) {
+        Ok(()) => fs::remove_file(renamed),
+        Err(_) => fs::remove_file(path),
+    }
+}
+
+/// `path` 所在的存储上已知会使覆写无效的特性，无法检测时返回空列表
+#[cfg(target_os = "linux")]
+pub fn limitations(path: &Path) -> Vec<ShredLimitation> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut found = Vec::new();
+    if is_copy_on_write(path) {
+        found.push(ShredLimitation::CopyOnWrite);
+    }
+    if fs::metadata(path).is_ok_and(|metadata| is_solid_state(metadata.dev())) {
+        found.push(ShredLimitation::SolidState);
+    }
+    found
+}
+
+/// `path` 所在的存储上已知会使覆写无效的特性，无法检测时返回空列表
+#[cfg(not(target_os = "linux"))]
+pub fn limitations(_path: &Path) -> Vec<ShredLimitation> {
+    Vec::new()
+}
+
+/// 按文件系统类型判断是否为写时复制文件系统
+#[cfg(target_os = "linux")]
+fn is_copy_on_write(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const BTRFS_SUPER_MAGIC: u32 = 0x9123_683E;
+    const ZFS_SUPER_MAGIC: u32 = 0x2FC1_2FC1;
+    const BCACHEFS_SUPER_MAGIC: u32 = 0xCA45_1A4E;
+
+    let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    [BTRFS_SUPER_MAGIC, ZFS_SUPER_MAGIC, BCACHEFS_SUPER_MAGIC].contains(&(stat.f_type as u32))
+}
+
+/// 按 `/sys/dev/block` 中设备（分区时为其所在的磁盘）的rotational标志判断是否为固态硬盘
+#[cfg(target_os = "linux")]
+fn is_solid_state(device: u64) -> bool {
+    let device = format!("/sys/dev/block/{}:{}", libc::major(device), libc::minor(device));
+    [format!("{}/queue/rotational", device), format!("{}/../queue/rotational", device)].iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .is_some_and(|rotational| rotational.trim() == "0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_shred_removes_file() {
+        let dir = TestDir::new("shred");
+        let path = dir.join("secret.txt");
+        fs::write(&path, vec![b'x'; SHRED_BLOCK_SIZE * 2 + 5]).unwrap();
+        shred_file(&path, 3).unwrap();
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(shred_file(&path, 1).is_err());
+        // 检测失败时不报错，只是返回空列表
+        let _ = limitations(&dir);
+    }
+}
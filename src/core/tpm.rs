@@ -0,0 +1,187 @@
+//! 用TPM密封的本机密钥存储
+//!
+//! 容器密码被密封到本机TPM中（通过 tpm2-tools），密封后的数据只能在同一台设备上解封，
+//! 复制到其他机器无法使用。未绑定PCR策略，本机上能访问TPM的任何程序都可以解封，
+//! 因此这是一种便利模式，不能替代完整的密码
+
+use super::app_data;
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// 密封密钥存储文件名
+const SEALED_KEYS_FILE_NAME: &str = "tpm_sealed_keys.json";
+/// TPM设备（优先使用资源管理器）
+const TPM_DEVICES: [&str; 2] = ["/dev/tpmrm0", "/dev/tpm0"];
+/// 需要的 tpm2-tools 命令
+const REQUIRED_TOOLS: [&str; 4] = ["tpm2_createprimary", "tpm2_create", "tpm2_load", "tpm2_unseal"];
+
+/// 密封在TPM中的一个秘密（只在创建它的TPM上有效）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedSecret {
+    /// 密封对象的公共部分（base64）
+    public: String,
+    /// 由TPM加密的私有部分（base64）
+    private: String,
+}
+
+/// 按容器路径保存的密封密码
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SealedKeyStore {
+    #[serde(skip)]
+    path: PathBuf,
+    entries: BTreeMap<String, SealedSecret>,
+}
+
+impl SealedKeyStore {
+    /// 从应用数据目录加载
+    pub fn load_default() -> io::Result<Self> {
+        let path = app_data::app_data_file(SEALED_KEYS_FILE_NAME)?;
+        let mut store: Self = app_data::load_json(&path)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        app_data::save_config(&self.path, self)
+    }
+
+    /// 是否保存了该文件的密封密码
+    pub fn contains(&self, target: &Path) -> bool {
+        self.entries.contains_key(&store_key(target))
+    }
+
+    /// 把密码密封到TPM并保存
+    pub fn seal(&mut self, target: &Path, password: &str) -> io::Result<()> {
+        let sealed = seal_secret(password.as_bytes())?;
+        self.entries.insert(store_key(target), sealed);
+        self.save()
+    }
+
+    /// 用TPM解封该文件的密码，没有保存时返回None
+    pub fn unseal(&self, target: &Path) -> io::Result<Option<String>> {
+        let Some(sealed) = self.entries.get(&store_key(target)) else {
+            return Ok(None);
+        };
+        let secret = unseal_secret(sealed)?;
+        String::from_utf8(secret)
+            .map(Some)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Unsealed data is not a password"))
+    }
+
+    /// 删除该文件的密封密码
+    pub fn forget(&mut self, target: &Path) -> io::Result<()> {
+        if self.entries.remove(&store_key(target)).is_some() {
+            self.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// 本机是否有可用的TPM和 tpm2-tools
+pub fn tpm_available() -> bool {
+    cfg!(target_os = "linux")
+        && TPM_DEVICES.iter().any(|device| Path::new(device).exists())
+        && REQUIRED_TOOLS.iter().all(|tool| find_in_path(tool))
+}
+
+fn find_in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// 存储的键（尽量使用规范化的绝对路径）
+fn store_key(path: &Path) -> String {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().to_string()
+}
+
+/// 在所有者主密钥下创建密封对象
+fn seal_secret(secret: &[u8]) -> io::Result<SealedSecret> {
+    let work = WorkDir::new()?;
+    create_primary(&work)?;
+    run_tool(
+        "tpm2_create",
+        &["-C", &work.arg("primary.ctx"), "-i", "-", "-u", &work.arg("seal.pub"), "-r", &work.arg("seal.priv")],
+        Some(secret),
+    )?;
+    Ok(SealedSecret {
+        public: BASE64.encode(fs::read(work.path("seal.pub"))?),
+        private: BASE64.encode(fs::read(work.path("seal.priv"))?),
+    })
+}
+
+/// 重新生成主密钥、加载密封对象并解封
+fn unseal_secret(sealed: &SealedSecret) -> io::Result<Vec<u8>> {
+    let decode = |data: &str| BASE64.decode(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    let work = WorkDir::new()?;
+    fs::write(work.path("seal.pub"), decode(&sealed.public)?)?;
+    fs::write(work.path("seal.priv"), decode(&sealed.private)?)?;
+
+    create_primary(&work)?;
+    run_tool(
+        "tpm2_load",
+        &["-C", &work.arg("primary.ctx"), "-u", &work.arg("seal.pub"), "-r", &work.arg("seal.priv"), "-c", &work.arg("seal.ctx")],
+        None,
+    )?;
+    run_tool("tpm2_unseal", &["-c", &work.arg("seal.ctx")], None)
+}
+
+/// 主密钥由TPM的种子确定性地派生，每次用相同模板都会得到同一个密钥
+fn create_primary(work: &WorkDir) -> io::Result<()> {
+    run_tool("tpm2_createprimary", &["-C", "o", "-g", "sha256", "-G", "ecc", "-c", &work.arg("primary.ctx")], None)?;
+    Ok(())
+}
+
+/// 运行 tpm2-tools 命令，返回标准输出
+fn run_tool(program: &str, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+    if let (Some(data), Some(mut input)) = (stdin, child.stdin.take()) {
+        input.write_all(data)?;
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// 存放TPM上下文文件的临时目录，离开作用域时删除
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new() -> io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("krypton-tpm-{}-{}", std::process::id(), rand::random::<u64>()));
+        fs::create_dir(&dir)?;
+        Ok(Self(dir))
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    fn arg(&self, name: &str) -> String {
+        self.path(name).to_string_lossy().to_string()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
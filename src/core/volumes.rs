@@ -0,0 +1,157 @@
+use crate::models::FileItem;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// 分卷编号的最小位数（例如 `.001`）
+const MIN_VOLUME_DIGITS: usize = 3;
+
+/// 解析分卷文件名，返回（逻辑文件名，分卷编号）
+///
+/// 例如 `report.pdf.enc.002` 返回 `("report.pdf.enc", 2)`
+pub fn parse_volume_name(file_name: &str) -> Option<(&str, u32)> {
+    let (base, number) = file_name.rsplit_once('.')?;
+    if base.is_empty() || number.len() < MIN_VOLUME_DIGITS || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok().map(|number| (base, number))
+}
+
+/// 生成第N个分卷的文件名
+pub fn volume_name(base_name: &str, number: u32) -> String {
+    format!("{}.{:0width$}", base_name, number, width = MIN_VOLUME_DIGITS)
+}
+
+/// 检查分卷集合是否完整（编号必须从1开始连续，且每个分卷都存在）
+pub fn validate_volume_set(base_name: &str, volumes: &[PathBuf]) -> Result<(), String> {
+    for (index, path) in volumes.iter().enumerate() {
+        let expected = index as u32 + 1;
+        let number = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_volume_name)
+            .map(|(_, number)| number);
+
+        if number != Some(expected) {
+            return Err(format!(
+                "Volume set '{}' is incomplete: volume '{}' is missing",
+                base_name,
+                volume_name(base_name, expected)
+            ));
+        }
+        if !path.is_file() {
+            return Err(format!(
+                "Volume set '{}' is incomplete: volume '{}' is missing",
+                base_name,
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 打开文件项的输入流（多分卷文件会先检查完整性，再按顺序拼接各分卷）
+pub fn open_file_item(file: &FileItem) -> Result<Box<dyn Read + Send>, String> {
+    if file.is_multi_volume() {
+        validate_volume_set(&file.name, &file.volumes)?;
+        Ok(Box::new(MultiVolumeReader::new(file.volumes.clone())))
+    } else {
+        let input_file = File::open(&file.path)
+            .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?;
+        Ok(Box::new(BufReader::new(input_file)))
+    }
+}
+
+/// 多分卷读取器 - 按顺序把各分卷拼接成一个连续的数据流
+pub struct MultiVolumeReader {
+    volumes: Vec<PathBuf>,
+    next_volume: usize,
+    current: Option<BufReader<File>>,
+}
+
+impl MultiVolumeReader {
+    pub fn new(volumes: Vec<PathBuf>) -> Self {
+        Self {
+            volumes,
+            next_volume: 0,
+            current: None,
+        }
+    }
+
+    /// 最后一个已打开的分卷
+    pub fn last_opened(&self) -> Option<&Path> {
+        self.next_volume.checked_sub(1)
+            .and_then(|index| self.volumes.get(index))
+            .map(PathBuf::as_path)
+    }
+
+    fn open_next(&mut self) -> io::Result<bool> {
+        let Some(path) = self.volumes.get(self.next_volume) else {
+            return Ok(false);
+        };
+
+        let file = File::open(path).map_err(|e| {
+            io::Error::new(e.kind(), format!("Volume '{}' is missing or unreadable: {}", path.display(), e))
+        })?;
+        self.current = Some(BufReader::new(file));
+        self.next_volume += 1;
+        Ok(true)
+    }
+}
+
+impl Read for MultiVolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if let Some(reader) = self.current.as_mut() {
+                let bytes_read = reader.read(buf)?;
+                if bytes_read > 0 {
+                    return Ok(bytes_read);
+                }
+                self.current = None;
+            }
+
+            if !self.open_next()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_parse_volume_name() {
+        assert_eq!(parse_volume_name("a.txt.enc.001"), Some(("a.txt.enc", 1)));
+        assert_eq!(parse_volume_name("a.txt.enc.0012"), Some(("a.txt.enc", 12)));
+        assert_eq!(parse_volume_name("a.txt.enc.01"), None);
+        assert_eq!(parse_volume_name("a.txt.enc"), None);
+        assert_eq!(parse_volume_name(".001"), None);
+    }
+
+    #[test]
+    fn test_reader_joins_volumes() {
+        let dir = TestDir::new("volumes");
+        let parts: Vec<PathBuf> = (1..=3)
+            .map(|number| {
+                let path = dir.join(volume_name("data.enc", number));
+                std::fs::write(&path, format!("part{};", number)).unwrap();
+                path
+            })
+            .collect();
+
+        validate_volume_set("data.enc", &parts).unwrap();
+        let mut joined = String::new();
+        MultiVolumeReader::new(parts.clone()).read_to_string(&mut joined).unwrap();
+        assert_eq!(joined, "part1;part2;part3;");
+
+        let gap = vec![parts[0].clone(), parts[2].clone()];
+        let error = validate_volume_set("data.enc", &gap).unwrap_err();
+        assert!(error.contains("data.enc.002"));
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 文件的大小和修改时间，用于判断文件是否仍在写入
+type FileStamp = (u64, Option<SystemTime>);
+
+/// 轮询式的文件夹监视器
+///
+/// 每次轮询列出目录中的文件，新文件在连续两次轮询中大小和修改时间都不变时才视为写入完成，
+/// 避免处理仍在复制中的文件。启动时已存在的文件不会被处理
+#[derive(Debug)]
+pub struct FolderWatcher {
+    directory: PathBuf,
+    /// 已处理（或启动时已存在）的文件
+    known: HashSet<PathBuf>,
+    /// 等待写入完成的新文件及上次看到的大小和修改时间
+    pending: HashMap<PathBuf, FileStamp>,
+}
+
+impl FolderWatcher {
+    /// 开始监视目录，记录当前已有的文件
+    pub fn new(directory: &Path) -> io::Result<Self> {
+        let known = list_files(directory)?.into_iter().map(|(path, _)| path).collect();
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            known,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// 轮询一次目录，返回已写入完成、可以处理的新文件
+    pub fn poll(&mut self) -> io::Result<Vec<PathBuf>> {
+        let files = list_files(&self.directory)?;
+        let present: HashSet<&PathBuf> = files.iter().map(|(path, _)| path).collect();
+        // 被删除或移走的文件重新出现时再次处理
+        self.known.retain(|path| present.contains(path));
+        self.pending.retain(|path, _| present.contains(path));
+
+        let mut ready = Vec::new();
+        for (path, stamp) in files {
+            if self.known.contains(&path) {
+                continue;
+            }
+            match self.pending.get(&path) {
+                Some(previous) if *previous == stamp => {
+                    self.pending.remove(&path);
+                    self.known.insert(path.clone());
+                    ready.push(path);
+                }
+                _ => {
+                    self.pending.insert(path, stamp);
+                }
+            }
+        }
+        ready.sort();
+        Ok(ready)
+    }
+}
+
+/// 列出目录中的普通文件（跳过隐藏文件和子目录）
+fn list_files(directory: &Path) -> io::Result<Vec<(PathBuf, FileStamp)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_file() {
+            files.push((entry.path(), (metadata.len(), metadata.modified().ok())));
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_new_files_reported_once_stable() {
+        let dir = TestDir::new("watch");
+        fs::write(dir.join("existing.txt"), b"old").unwrap();
+
+        let mut watcher = FolderWatcher::new(&dir).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // 新文件第一次出现时等待，大小不变后才报告
+        fs::write(dir.join("new.txt"), b"new").unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        assert_eq!(watcher.poll().unwrap(), vec![dir.join("new.txt")]);
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+}
@@ -1,19 +1,34 @@
-use super::traits::{CryptoProvider, CryptoResult, CryptoError, KeyDerivation, Argon2KeyDerivation};
+use super::format;
+use super::stream;
+use super::traits::{CryptoProvider, ChunkDecryptor, ChunkEncryptor, CryptoResult, CryptoError, KeyDerivation, BatchKey, FileKeyDerivation, StreamControl};
+use crate::models::KdfParams;
 use std::io::{Read, Write};
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
-use aes_gcm::aead::{Aead, OsRng};
-use rand::RngCore;
+use aes_gcm::aead::AeadInPlace;
+
 
 /// AES-256-GCM加密提供者
 #[derive(Debug)]
 pub struct AesCryptoProvider {
-    key_derivation: Argon2KeyDerivation,
+    key_derivation: FileKeyDerivation,
 }
 
 impl AesCryptoProvider {
     pub fn new() -> Self {
+        Self::with_kdf(KdfParams::default())
+    }
+
+    /// 使用指定的密钥派生参数（Argon2、scrypt或PBKDF2）派生密钥
+    pub fn with_kdf(params: KdfParams) -> Self {
+        Self {
+            key_derivation: FileKeyDerivation::password(params),
+        }
+    }
+
+    /// 从批处理主密钥派生每个文件的密钥
+    pub fn with_batch_key(key: BatchKey) -> Self {
         Self {
-            key_derivation: Argon2KeyDerivation,
+            key_derivation: FileKeyDerivation::Batch(key),
         }
     }
 }
@@ -23,118 +38,77 @@ impl CryptoProvider for AesCryptoProvider {
         "AES-256-GCM"
     }
     
-    fn encrypt_stream<R: Read, W: Write>(
+    fn encrypt_stream(
         &self,
         password: &str,
-        reader: &mut R,
-        writer: &mut W,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
     ) -> CryptoResult<()> {
-        if password.is_empty() {
-            return Err(CryptoError::InvalidPassword);
-        }
-        
-        // 生成盐值
-        let salt = self.key_derivation.generate_salt();
-        
-        // 派生密钥
-        let key = self.key_derivation.derive_key(password, &salt)?;
-        
-        // 创建AES-GCM实例
-        let cipher = Aes256Gcm::new_from_slice(&key)
-            .map_err(|e| CryptoError::EncryptionError(format!("AES密钥创建失败: {}", e)))?;
-        
-        // 写入盐值到文件头
+        // 生成盐值并派生密钥，盐值写在数据块之前
+        let (salt, cipher) = self.chunk_encryptor(password)?;
         writer.write_all(&salt)?;
-        
-        // 分块加密
-        let mut buffer = vec![0u8; self.chunk_size()];
-        let mut chunk_index = 0u64;
-        
-        loop {
-            let bytes_read = reader.read(&mut buffer)?;
-            
-            if bytes_read == 0 {
-                break; // 文件读取完毕
-            }
-            
-            // 生成随机nonce
-            let mut nonce_bytes = [0u8; 12];
-            OsRng.fill_bytes(&mut nonce_bytes);
-            let nonce = Nonce::from_slice(&nonce_bytes);
-            
-            // 加密数据块
-            let chunk = &buffer[0..bytes_read];
-            let ciphertext = cipher.encrypt(nonce, chunk)
-                .map_err(|e| CryptoError::EncryptionError(format!("加密失败 (块 {}): {}", chunk_index, e)))?;
-            
-            // 写入nonce和加密数据
-            writer.write_all(&nonce_bytes)?;
-            writer.write_all(&(ciphertext.len() as u32).to_le_bytes())?;
-            writer.write_all(&ciphertext)?;
-            
-            chunk_index += 1;
-        }
-        
-        Ok(())
+        stream::encrypt_chunks(cipher.as_ref(), self.chunk_size(), self.nonce_len(), reader, writer, control)
     }
     
-    fn decrypt_stream<R: Read, W: Write>(
+    fn decrypt_stream(
         &self,
         password: &str,
-        reader: &mut R,
-        writer: &mut W,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
     ) -> CryptoResult<()> {
+        let mut salt = vec![0u8; format::SALT_LEN];
+        reader.read_exact(&mut salt)?;
+        let cipher = self.chunk_decryptor(password, &salt)?;
+        stream::decrypt_chunks(cipher.as_ref(), self.chunk_size(), self.nonce_len(), reader, writer, control)
+    }
+
+    fn chunk_decryptor(&self, password: &str, salt: &[u8]) -> CryptoResult<Box<dyn ChunkDecryptor>> {
         if password.is_empty() {
             return Err(CryptoError::InvalidPassword);
         }
-        
-        // 读取盐值
-        let mut salt = vec![0u8; 32];
-        reader.read_exact(&mut salt)?;
-        
-        // 派生密钥
-        let key = self.key_derivation.derive_key(password, &salt)?;
-        
-        // 创建AES-GCM实例
+
+        let key = self.key_derivation.derive_key(password, salt)?;
         let cipher = Aes256Gcm::new_from_slice(&key)
             .map_err(|e| CryptoError::DecryptionError(format!("AES密钥创建失败: {}", e)))?;
-        
-        // 分块解密
-        let mut chunk_index = 0u64;
-        
-        loop {
-            // 读取nonce
-            let mut nonce_bytes = [0u8; 12];
-            match reader.read_exact(&mut nonce_bytes) {
-                Ok(_) => {},
-                Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(CryptoError::IoError(e)),
-            }
-            let nonce = Nonce::from_slice(&nonce_bytes);
-            
-            // 读取数据长度
-            let mut length_bytes = [0u8; 4];
-            reader.read_exact(&mut length_bytes)?;
-            let data_length = u32::from_le_bytes(length_bytes) as usize;
-            
-            // 读取加密数据
-            let mut ciphertext = vec![0u8; data_length];
-            reader.read_exact(&mut ciphertext)?;
-            
-            // 解密数据块
-            let plaintext = cipher.decrypt(nonce, ciphertext.as_slice())
-                .map_err(|e| CryptoError::DecryptionError(format!("解密失败 (块 {}): {}", chunk_index, e)))?;
-            
-            // 写入解密数据
-            writer.write_all(&plaintext)?;
-            
-            chunk_index += 1;
+        Ok(Box::new(cipher))
+    }
+
+    fn chunk_encryptor(&self, password: &str) -> CryptoResult<(Vec<u8>, Box<dyn ChunkEncryptor>)> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
         }
-        
-        Ok(())
+
+        let salt = self.key_derivation.generate_salt();
+        let key = self.key_derivation.derive_key(password, &salt)?;
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| CryptoError::EncryptionError(format!("AES密钥创建失败: {}", e)))?;
+        Ok((salt, Box::new(cipher)))
     }
 }
 
+impl ChunkEncryptor for Aes256Gcm {
+    fn encrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()> {
+        if nonce.len() != 12 {
+            return Err(CryptoError::InvalidFormat);
+        }
+        self.encrypt_in_place(Nonce::from_slice(nonce), associated_data, buffer)
+            .map_err(|e| CryptoError::EncryptionError(format!("加密失败: {}", e)))
+    }
+}
+
+impl ChunkDecryptor for Aes256Gcm {
+    fn decrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()> {
+        if nonce.len() != 12 {
+            return Err(CryptoError::InvalidFormat);
+        }
+        self.decrypt_in_place(Nonce::from_slice(nonce), associated_data, buffer)
+            .map_err(|e| CryptoError::DecryptionError(format!("解密失败: {}", e)))
+    }
+}
+
+
 impl Default for AesCryptoProvider {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,344 @@
+//! age v1 格式（与 age / rage 命令行工具互通）
+//!
+//! 只支持口令（scrypt接收者）：加密写出的文件可以用 `age -d` 输入口令解密，`age -p` 加密的文件也能在这里解密。
+//! 文本文件头中的scrypt节包装16字节的文件密钥，文件头以HMAC-SHA256认证；之后是16字节随机nonce和
+//! STREAM分块密文（64 KiB明文块，nonce为11字节大端计数器 + 最后一块标志）。
+//! age文件没有Krypton文件头，引擎按开头的版本行识别
+
+use super::format;
+use super::traits::{CryptoError, CryptoProvider, CryptoResult, StreamControl};
+use crate::models::{KdfAlgorithm, KdfParams};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD_NO_PAD as BASE64;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io::{BufRead, BufReader, Read, Write};
+
+/// 文件开头的版本行
+pub const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+const SCRYPT_LABEL: &[u8] = b"age-encryption.org/v1/scrypt";
+const FILE_KEY_LEN: usize = 16;
+const SCRYPT_SALT_LEN: usize = 16;
+/// 加密时默认的scrypt工作因子（与age的默认值相同）
+pub const SCRYPT_LOG_N: u8 = 18;
+/// 加密时的最小工作因子
+const MIN_SCRYPT_LOG_N: u8 = 10;
+/// 解密时接受的最大工作因子，避免恶意文件耗尽内存
+const MAX_SCRYPT_LOG_N: u8 = 22;
+/// 每块明文的长度
+const CHUNK_SIZE: usize = 64 * 1024;
+const PAYLOAD_NONCE_LEN: usize = 16;
+/// 文件头的最大长度
+const MAX_HEADER_LEN: usize = 64 * 1024;
+/// 节内容按64列换行，短于64列的行是最后一行
+const BODY_COLUMNS: usize = 64;
+/// 加密写出的文件头长度：版本行、scrypt节（盐值、两位数的工作因子和包装后的文件密钥）以及MAC行
+const HEADER_LEN: u64 = 150;
+
+/// 数据是否以age的版本行开头
+pub fn is_age(prefix: &[u8]) -> bool {
+    prefix.starts_with(AGE_MAGIC)
+}
+
+/// 明文加密为age文件后的大小
+pub fn encrypted_size(plaintext_len: u64) -> u64 {
+    let chunks = plaintext_len.div_ceil(CHUNK_SIZE as u64).max(1);
+    HEADER_LEN + PAYLOAD_NONCE_LEN as u64 + plaintext_len + chunks * format::TAG_LEN as u64
+}
+
+/// age文件中明文长度的上限
+pub fn max_plaintext_size(file_len: u64) -> u64 {
+    file_len.saturating_sub((PAYLOAD_NONCE_LEN + format::TAG_LEN) as u64)
+}
+
+/// age格式的加密提供者
+#[derive(Debug, Clone, Copy)]
+pub struct AgeCryptoProvider {
+    /// 加密时的scrypt工作因子（10到22）
+    pub log_n: u8,
+}
+
+impl Default for AgeCryptoProvider {
+    fn default() -> Self {
+        Self { log_n: SCRYPT_LOG_N }
+    }
+}
+
+impl AgeCryptoProvider {
+    /// 密钥派生算法选择scrypt时使用其工作因子（限制在age接受的范围内），否则使用默认值
+    pub fn with_kdf(kdf: KdfParams) -> Self {
+        match kdf.algorithm {
+            KdfAlgorithm::Scrypt => Self { log_n: (kdf.memory_kib.trailing_zeros() as u8).clamp(MIN_SCRYPT_LOG_N, MAX_SCRYPT_LOG_N) },
+            _ => Self::default(),
+        }
+    }
+}
+
+impl CryptoProvider for AgeCryptoProvider {
+    fn algorithm_name(&self) -> &'static str {
+        "age"
+    }
+
+    fn chunk_size(&self) -> usize {
+        CHUNK_SIZE
+    }
+
+    fn encrypt_stream(&self, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, control: &StreamControl) -> CryptoResult<()> {
+        let file_key: [u8; FILE_KEY_LEN] = rand::random();
+        writer.write_all(encode_header(password, &file_key, self.log_n)?.as_bytes())?;
+        let nonce: [u8; PAYLOAD_NONCE_LEN] = rand::random();
+        writer.write_all(&nonce)?;
+        let cipher = payload_cipher(&file_key, &nonce);
+
+        // 读到下一块为空才知道当前块是最后一块（明文长度正好是块大小的整数倍时最后一块是满的）
+        let mut current = vec![0u8; CHUNK_SIZE];
+        let mut next = vec![0u8; CHUNK_SIZE];
+        let mut len = format::read_chunk(reader, &mut current)?;
+        let mut processed = 0;
+        for counter in 0u64.. {
+            control.check()?;
+            let next_len = if len == CHUNK_SIZE { format::read_chunk(reader, &mut next)? } else { 0 };
+            let last = next_len == 0;
+            let sealed = cipher.encrypt(&stream_nonce(counter, last), &current[..len])
+                .map_err(|_| CryptoError::EncryptionError("数据块加密失败".to_string()))?;
+            writer.write_all(&sealed)?;
+            processed += len as u64;
+            control.report(processed);
+            if last {
+                break;
+            }
+            std::mem::swap(&mut current, &mut next);
+            len = next_len;
+        }
+        Ok(())
+    }
+
+    fn decrypt_stream(&self, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, control: &StreamControl) -> CryptoResult<()> {
+        let mut reader = BufReader::new(reader);
+        let file_key = read_header(&mut reader, password)?;
+        let truncated = || CryptoError::DecryptionError("age文件被截断".to_string());
+        let mut nonce = [0u8; PAYLOAD_NONCE_LEN];
+        reader.read_exact(&mut nonce).map_err(|_| truncated())?;
+        let cipher = payload_cipher(&file_key, &nonce);
+
+        let mut current = vec![0u8; CHUNK_SIZE + format::TAG_LEN];
+        let mut next = vec![0u8; CHUNK_SIZE + format::TAG_LEN];
+        let mut len = format::read_chunk(&mut reader, &mut current)?;
+        let mut processed = 0;
+        for counter in 0u64.. {
+            control.check()?;
+            let next_len = if len == current.len() { format::read_chunk(&mut reader, &mut next)? } else { 0 };
+            let last = next_len == 0;
+            if len < format::TAG_LEN {
+                return Err(truncated());
+            }
+            let plaintext = cipher.decrypt(&stream_nonce(counter, last), &current[..len])
+                .map_err(|_| CryptoError::DecryptionError("数据块认证失败，文件已损坏或被截断".to_string()))?;
+            // 只有空文件的唯一一块可以为空
+            if last && plaintext.is_empty() && counter > 0 {
+                return Err(CryptoError::DecryptionError("最后一个数据块为空".to_string()));
+            }
+            writer.write_all(&plaintext)?;
+            processed += len as u64;
+            control.report(processed);
+            if last {
+                break;
+            }
+            std::mem::swap(&mut current, &mut next);
+            len = next_len;
+        }
+        Ok(())
+    }
+
+    /// `data` 为age文件的开头，只需包含完整的文件头：解开文件密钥并核对文件头的MAC
+    fn verify_password(&self, password: &str, data: &[u8]) -> CryptoResult<bool> {
+        match read_header(&mut &data[..], password) {
+            Ok(_) => Ok(true),
+            Err(CryptoError::InvalidPassword) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// 生成包含scrypt节和MAC的文件头
+fn encode_header(password: &str, file_key: &[u8; FILE_KEY_LEN], log_n: u8) -> CryptoResult<String> {
+    let salt: [u8; SCRYPT_SALT_LEN] = rand::random();
+    let wrap_key = scrypt_key(password, &salt, log_n)?;
+    let body = ChaCha20Poly1305::new(Key::from_slice(&wrap_key))
+        .encrypt(Nonce::from_slice(&[0u8; 12]), file_key.as_slice())
+        .map_err(|_| CryptoError::EncryptionError("文件密钥包装失败".to_string()))?;
+    // 32字节的节内容编码后不足64列，只占一行
+    let mut header = format!(
+        "{}\n-> scrypt {} {}\n{}\n---",
+        String::from_utf8_lossy(AGE_MAGIC), BASE64.encode(salt), log_n, BASE64.encode(body)
+    );
+    let mac = header_mac(file_key).chain_update(header.as_bytes()).finalize().into_bytes();
+    header.push_str(&format!(" {}\n", BASE64.encode(mac)));
+    Ok(header)
+}
+
+/// 读取文件头，用口令解开scrypt节中的文件密钥并核对MAC
+fn read_header(reader: &mut dyn BufRead, password: &str) -> CryptoResult<[u8; FILE_KEY_LEN]> {
+    let mut header = Vec::new();
+    if read_line(reader, &mut header)?.as_bytes() != AGE_MAGIC {
+        return Err(CryptoError::InvalidFormat);
+    }
+
+    let mut stanzas = Vec::new();
+    let mac = loop {
+        let start = header.len();
+        let line = read_line(reader, &mut header)?;
+        if let Some(mac) = line.strip_prefix("--- ") {
+            // MAC覆盖到 "---" 为止
+            header.truncate(start + 3);
+            break decode(mac)?;
+        }
+        let args: Vec<String> = line.strip_prefix("-> ")
+            .ok_or_else(|| invalid_header("无法识别的行"))?
+            .split(' ')
+            .map(str::to_string)
+            .collect();
+        let mut body = Vec::new();
+        loop {
+            let line = read_line(reader, &mut header)?;
+            body.extend_from_slice(&decode(&line)?);
+            if line.len() < BODY_COLUMNS {
+                break;
+            }
+        }
+        stanzas.push((args, body));
+    };
+
+    // scrypt节必须是唯一的节
+    let (args, body) = match stanzas.as_slice() {
+        [(args, body)] if args.first().is_some_and(|kind| kind == "scrypt") => (args, body),
+        stanzas if stanzas.iter().any(|(args, _)| args.first().is_some_and(|kind| kind == "scrypt")) => {
+            return Err(invalid_header("口令节必须是唯一的接收者"));
+        }
+        _ => return Err(CryptoError::DecryptionError("该age文件不是用口令加密的（只支持口令）".to_string())),
+    };
+    let [_, salt, log_n] = args.as_slice() else {
+        return Err(invalid_header("口令节的参数数量错误"));
+    };
+    let salt: [u8; SCRYPT_SALT_LEN] = decode(salt)?.try_into()
+        .map_err(|_| invalid_header("口令节的盐值长度错误"))?;
+    let log_n: u8 = log_n.parse().ok()
+        .filter(|n| *n > 0 && !log_n.starts_with('0'))
+        .ok_or_else(|| invalid_header("口令节的工作因子无效"))?;
+    if log_n > MAX_SCRYPT_LOG_N {
+        return Err(CryptoError::DecryptionError(format!("scrypt工作因子 {} 过大（最大 {}）", log_n, MAX_SCRYPT_LOG_N)));
+    }
+    if body.len() != FILE_KEY_LEN + format::TAG_LEN {
+        return Err(invalid_header("包装后的文件密钥长度错误"));
+    }
+
+    let wrap_key = scrypt_key(password, &salt, log_n)?;
+    let file_key: [u8; FILE_KEY_LEN] = ChaCha20Poly1305::new(Key::from_slice(&wrap_key))
+        .decrypt(Nonce::from_slice(&[0u8; 12]), body.as_slice())
+        .map_err(|_| CryptoError::InvalidPassword)?
+        .try_into()
+        .map_err(|_| CryptoError::InvalidPassword)?;
+    header_mac(&file_key).chain_update(&header).verify_slice(&mac)
+        .map_err(|_| CryptoError::DecryptionError("age文件头认证失败".to_string()))?;
+    Ok(file_key)
+}
+
+/// 读取一行（不含换行符），原始字节追加到 `header`
+fn read_line(reader: &mut dyn BufRead, header: &mut Vec<u8>) -> CryptoResult<String> {
+    let start = header.len();
+    let limit = (MAX_HEADER_LEN + 1).saturating_sub(start) as u64;
+    Read::take(&mut *reader, limit).read_until(b'\n', header)?;
+    if header.len() > MAX_HEADER_LEN {
+        return Err(invalid_header("文件头过长"));
+    }
+    if header.last() != Some(&b'\n') {
+        return Err(CryptoError::DecryptionError("age文件头被截断".to_string()));
+    }
+    String::from_utf8(header[start..header.len() - 1].to_vec())
+        .map_err(|_| invalid_header("文件头不是文本"))
+}
+
+fn decode(text: &str) -> CryptoResult<Vec<u8>> {
+    BASE64.decode(text).map_err(|_| invalid_header("base64编码无效"))
+}
+
+fn invalid_header(detail: &str) -> CryptoError {
+    CryptoError::DecryptionError(format!("age文件头无效: {}", detail))
+}
+
+fn scrypt_key(password: &str, salt: &[u8; SCRYPT_SALT_LEN], log_n: u8) -> CryptoResult<[u8; 32]> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| CryptoError::KeyDerivationError(format!("密钥派生参数无效: {}", e)))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(password.as_bytes(), &[SCRYPT_LABEL, salt].concat(), &params, &mut key)
+        .map_err(|e| CryptoError::KeyDerivationError(format!("密钥派生失败: {}", e)))?;
+    Ok(key)
+}
+
+fn hkdf_key(file_key: &[u8; FILE_KEY_LEN], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), file_key)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+fn header_mac(file_key: &[u8; FILE_KEY_LEN]) -> Hmac<Sha256> {
+    <Hmac<Sha256> as Mac>::new_from_slice(&hkdf_key(file_key, &[], b"header")).expect("HMAC accepts keys of any length")
+}
+
+fn payload_cipher(file_key: &[u8; FILE_KEY_LEN], nonce: &[u8; PAYLOAD_NONCE_LEN]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(Key::from_slice(&hkdf_key(file_key, nonce, b"payload")))
+}
+
+/// STREAM nonce：11字节大端计数器 + 最后一块标志
+fn stream_nonce(counter: u64, last: bool) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    *Nonce::from_slice(&nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_age_round_trip() {
+        // 降低工作因子，测试不必每次分配256 MiB
+        let provider = AgeCryptoProvider::with_kdf(KdfParams { memory_kib: 1024, ..KdfParams::for_algorithm(KdfAlgorithm::Scrypt) });
+        assert_eq!(provider.log_n, 10);
+        assert_eq!(AgeCryptoProvider::with_kdf(KdfParams::default()).log_n, SCRYPT_LOG_N);
+        for len in [0, 100, CHUNK_SIZE, 2 * CHUNK_SIZE + 1] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut encrypted = Vec::new();
+            provider.encrypt_stream("pw", &mut &plaintext[..], &mut encrypted, &StreamControl::default()).unwrap();
+            assert_eq!(encrypted.len() as u64, encrypted_size(len as u64));
+            assert!(max_plaintext_size(encrypted.len() as u64) >= len as u64);
+            assert!(is_age(&encrypted));
+            assert!(provider.verify_password("pw", &encrypted).unwrap());
+            assert!(!provider.verify_password("wrong", &encrypted).unwrap());
+
+            let mut decrypted = Vec::new();
+            provider.decrypt_stream("pw", &mut &encrypted[..], &mut decrypted, &StreamControl::default()).unwrap();
+            assert_eq!(decrypted, plaintext);
+            assert!(matches!(
+                provider.decrypt_stream("wrong", &mut &encrypted[..], &mut Vec::new(), &StreamControl::default()),
+                Err(CryptoError::InvalidPassword)
+            ));
+        }
+
+        // 整块被截掉或文件头被改动都会被发现
+        let plaintext = vec![7u8; 2 * CHUNK_SIZE + 1];
+        let mut encrypted = Vec::new();
+        provider.encrypt_stream("pw", &mut &plaintext[..], &mut encrypted, &StreamControl::default()).unwrap();
+        let truncated = &encrypted[..encrypted.len() - (1 + format::TAG_LEN)];
+        assert!(provider.decrypt_stream("pw", &mut &truncated[..], &mut Vec::new(), &StreamControl::default()).is_err());
+        let mut tampered = encrypted.clone();
+        tampered[AGE_MAGIC.len() + 12] ^= 1;
+        assert!(provider.decrypt_stream("pw", &mut &tampered[..], &mut Vec::new(), &StreamControl::default()).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use super::traits::{CryptoResult, CryptoError};
+use super::{encrypt_stream, decrypt_stream};
+use crate::models::EncryptionAlgorithm;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+
+/// 文本封装的起始标记
+pub const ARMOR_BEGIN: &str = "-----BEGIN KRYPTON MESSAGE-----";
+/// 文本封装的结束标记
+pub const ARMOR_END: &str = "-----END KRYPTON MESSAGE-----";
+
+/// 分段封装中每一段的起始标记
+pub const PART_BEGIN: &str = "-----BEGIN KRYPTON MESSAGE PART-----";
+/// 分段封装中每一段的结束标记
+pub const PART_END: &str = "-----END KRYPTON MESSAGE PART-----";
+
+/// 每行Base64字符数
+const LINE_WIDTH: usize = 64;
+/// 每一段中标记和头部字段占用的最大字节数
+const PART_OVERHEAD: usize = 160;
+/// 分段的最大数量
+pub const MAX_PARTS: usize = 999;
+
+/// 将密文封装为可粘贴的文本
+pub fn armor(algorithm: &EncryptionAlgorithm, data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut armored = String::with_capacity(encoded.len() + encoded.len() / LINE_WIDTH + 128);
+
+    armored.push_str(ARMOR_BEGIN);
+    armored.push('\n');
+    armored.push_str(&format!("Algorithm: {}\n\n", algorithm));
+
+    for line in encoded.as_bytes().chunks(LINE_WIDTH) {
+        // Base64输出只包含ASCII字符
+        armored.push_str(std::str::from_utf8(line).unwrap_or_default());
+        armored.push('\n');
+    }
+
+    armored.push_str(ARMOR_END);
+    armored.push('\n');
+    armored
+}
+
+/// 将密文封装为多段文本，每段（包括标记和头部）不超过 `max_part_len` 字节，适合粘贴到邮件或聊天中
+///
+/// 每段带有相同的消息ID和 "序号/总数"，导入时可以按任意顺序粘贴
+pub fn armor_parts(algorithm: &EncryptionAlgorithm, data: &[u8], max_part_len: usize) -> CryptoResult<Vec<String>> {
+    let encoded = STANDARD.encode(data);
+    let lines_per_part = max_part_len.saturating_sub(PART_OVERHEAD) / (LINE_WIDTH + 1);
+    if lines_per_part == 0 {
+        return Err(CryptoError::EncryptionError(format!("分段大小至少为 {} 字节", PART_OVERHEAD + LINE_WIDTH + 1)));
+    }
+    let pieces: Vec<&[u8]> = if encoded.is_empty() {
+        vec![&[]]
+    } else {
+        encoded.as_bytes().chunks(lines_per_part * LINE_WIDTH).collect()
+    };
+    if pieces.len() > MAX_PARTS {
+        return Err(CryptoError::EncryptionError(format!("需要 {} 段，超过上限 {}，请增大分段大小", pieces.len(), MAX_PARTS)));
+    }
+
+    let message_id = hex::encode(rand::random::<[u8; 4]>());
+    let total = pieces.len();
+    Ok(pieces.into_iter().enumerate().map(|(index, piece)| {
+        let mut part = String::with_capacity(piece.len() + piece.len() / LINE_WIDTH + PART_OVERHEAD);
+        part.push_str(PART_BEGIN);
+        part.push('\n');
+        part.push_str(&format!("Algorithm: {}\nMessage: {}\nPart: {}/{}\n\n", algorithm, message_id, index + 1, total));
+        for line in piece.chunks(LINE_WIDTH) {
+            part.push_str(std::str::from_utf8(line).unwrap_or_default());
+            part.push('\n');
+        }
+        part.push_str(PART_END);
+        part.push('\n');
+        part
+    }).collect())
+}
+
+/// 重新拼合分段封装的文本，返回算法和原始密文
+///
+/// 段可以按任意顺序出现，重复的段会被忽略，段之间可以有其他文本
+pub fn dearmor_parts(text: &str) -> CryptoResult<(EncryptionAlgorithm, Vec<u8>)> {
+    let mut lines = text.lines().map(str::trim);
+    let mut algorithm = None;
+    let mut message_id = None;
+    let mut total = None;
+    let mut pieces: BTreeMap<usize, String> = BTreeMap::new();
+
+    while lines.by_ref().any(|line| line == PART_BEGIN) {
+        let mut header = HashMap::new();
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                header.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let mut body = String::new();
+        let mut terminated = false;
+        for line in lines.by_ref() {
+            if line == PART_END {
+                terminated = true;
+                break;
+            }
+            body.push_str(line);
+        }
+        let (Some(part_algorithm), Some(id), Some(position), true) =
+            (header.get("algorithm"), header.get("message"), header.get("part"), terminated)
+        else {
+            return Err(CryptoError::InvalidFormat);
+        };
+        let (index, count) = position.split_once('/')
+            .and_then(|(index, count)| Some((index.trim().parse::<usize>().ok()?, count.trim().parse::<usize>().ok()?)))
+            .filter(|(index, count)| (1..=*count).contains(index) && *count <= MAX_PARTS)
+            .ok_or(CryptoError::InvalidFormat)?;
+
+        if message_id.get_or_insert_with(|| id.clone()) != id {
+            return Err(CryptoError::DecryptionError("粘贴的分段来自不同的消息".to_string()));
+        }
+        if *total.get_or_insert(count) != count {
+            return Err(CryptoError::InvalidFormat);
+        }
+        algorithm = part_algorithm.parse::<EncryptionAlgorithm>().ok();
+        pieces.entry(index).or_insert(body);
+    }
+
+    let (Some(algorithm), Some(total)) = (algorithm, total) else {
+        return Err(CryptoError::InvalidFormat);
+    };
+    let missing: Vec<String> = (1..=total)
+        .filter(|index| !pieces.contains_key(index))
+        .map(|index| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(CryptoError::DecryptionError(format!("缺少第 {} 段（共 {} 段）", missing.join(", "), total)));
+    }
+
+    let body: String = pieces.into_values().collect();
+    let data = STANDARD.decode(body.as_bytes())
+        .map_err(|_| CryptoError::InvalidFormat)?;
+    Ok((algorithm, data))
+}
+
+/// 文本是否包含分段封装
+pub fn is_multipart(text: &str) -> bool {
+    text.lines().any(|line| line.trim() == PART_BEGIN)
+}
+
+/// 解析封装文本，返回算法和原始密文
+pub fn dearmor(text: &str) -> CryptoResult<(EncryptionAlgorithm, Vec<u8>)> {
+    let mut lines = text.lines().map(str::trim);
+
+    // 跳过起始标记之前的内容（例如邮件正文）
+    if !lines.by_ref().any(|line| line == ARMOR_BEGIN) {
+        return Err(CryptoError::InvalidFormat);
+    }
+
+    // 读取头部字段，直到空行
+    let mut algorithm = None;
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("Algorithm") {
+                algorithm = value.trim().parse::<EncryptionAlgorithm>().ok();
+            }
+        }
+    }
+    let algorithm = algorithm.ok_or(CryptoError::InvalidFormat)?;
+
+    // 读取Base64正文，直到结束标记
+    let mut body = String::new();
+    let mut terminated = false;
+    for line in lines {
+        if line == ARMOR_END {
+            terminated = true;
+            break;
+        }
+        body.push_str(line);
+    }
+    if !terminated {
+        return Err(CryptoError::InvalidFormat);
+    }
+
+    let data = STANDARD.decode(body.as_bytes())
+        .map_err(|_| CryptoError::InvalidFormat)?;
+    Ok((algorithm, data))
+}
+
+/// 加密文本并输出封装格式
+pub fn encrypt_text(algorithm: &EncryptionAlgorithm, password: &str, text: &str) -> CryptoResult<String> {
+    let mut input = Cursor::new(text.as_bytes());
+    let mut encrypted = Vec::new();
+    encrypt_stream(algorithm, password, &mut input, &mut encrypted)?;
+    Ok(armor(algorithm, &encrypted))
+}
+
+/// 加密文本并输出分段封装
+pub fn encrypt_text_parts(algorithm: &EncryptionAlgorithm, password: &str, text: &str, max_part_len: usize) -> CryptoResult<Vec<String>> {
+    let mut input = Cursor::new(text.as_bytes());
+    let mut encrypted = Vec::new();
+    encrypt_stream(algorithm, password, &mut input, &mut encrypted)?;
+    armor_parts(algorithm, &encrypted, max_part_len)
+}
+
+/// 解密封装文本（单段或分段），算法从封装头部读取
+pub fn decrypt_text(password: &str, armored: &str) -> CryptoResult<String> {
+    let (algorithm, encrypted) = if is_multipart(armored) { dearmor_parts(armored)? } else { dearmor(armored)? };
+    let mut input = Cursor::new(encrypted);
+    let mut decrypted = Vec::new();
+    decrypt_stream(&algorithm, password, &mut input, &mut decrypted)?;
+    String::from_utf8(decrypted)
+        .map_err(|_| CryptoError::DecryptionError("解密结果不是有效的UTF-8文本".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_roundtrip() {
+        let armored = encrypt_text(&EncryptionAlgorithm::ChaCha20, "secret", "hello 世界").unwrap();
+        assert!(armored.starts_with(ARMOR_BEGIN));
+        assert_eq!(decrypt_text("secret", &armored).unwrap(), "hello 世界");
+    }
+
+    #[test]
+    fn test_dearmor_ignores_surrounding_text() {
+        let armored = armor(&EncryptionAlgorithm::AES256, b"payload");
+        let pasted = format!("Hi,\n\nhere it is:\n{}\nBye", armored);
+        let (algorithm, data) = dearmor(&pasted).unwrap();
+        assert_eq!(algorithm, EncryptionAlgorithm::AES256);
+        assert_eq!(data, b"payload");
+    }
+
+    #[test]
+    fn test_parts_reassemble_in_any_order() {
+        let text = "lorem ipsum ".repeat(200);
+        let parts = encrypt_text_parts(&EncryptionAlgorithm::AES256, "secret", &text, 1024).unwrap();
+        assert!(parts.len() > 2);
+        assert!(parts.iter().all(|part| part.len() <= 1024));
+
+        let pasted = parts.iter().rev().cloned().collect::<Vec<_>>().join("\n-- forwarded --\n");
+        assert_eq!(decrypt_text("secret", &pasted).unwrap(), text);
+
+        let incomplete = parts[1..].join("\n");
+        assert!(matches!(dearmor_parts(&incomplete), Err(CryptoError::DecryptionError(_))));
+    }
+
+    #[test]
+    fn test_dearmor_rejects_truncated_message() {
+        let armored = armor(&EncryptionAlgorithm::AES256, b"payload");
+        let truncated = armored.replace(ARMOR_END, "");
+        assert!(matches!(dearmor(&truncated), Err(CryptoError::InvalidFormat)));
+    }
+}
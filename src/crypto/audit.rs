@@ -0,0 +1,300 @@
+use super::traits::{CryptoResult, CryptoError, StreamControl};
+use super::{create_crypto_provider, create_decryption_provider};
+use super::format::{self, LENGTH_LEN, SALT_LEN, TAG_LEN};
+use super::cascade;
+use super::compression::DecompressWriter;
+use super::header::FileHeader;
+use super::stream::{ChunkNonces, PlaintextDigest};
+use crate::core::volumes::open_file_item;
+use crate::models::{EncryptionAlgorithm, FileItem};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// 单个文件的审计结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AuditStatus {
+    Passed,
+    Failed(String),
+}
+
+/// 单个文件的审计报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditReport {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+    pub chunks: u64,
+    /// 是否使用密码校验了认证标签
+    pub authenticated: bool,
+    pub status: AuditStatus,
+}
+
+impl AuditReport {
+    pub fn passed(&self) -> bool {
+        self.status == AuditStatus::Passed
+    }
+}
+
+/// 审计单个加密文件：检查文件头和分块结构，提供密码时还会校验每个块的认证标签
+pub fn audit_file(file: &FileItem, algorithm: &EncryptionAlgorithm, password: Option<&str>) -> AuditReport {
+    let mut report = AuditReport {
+        path: file.path.clone(),
+        name: file.name.clone(),
+        size: file.size_on_disk(),
+        chunks: 0,
+        authenticated: false,
+        status: AuditStatus::Passed,
+    };
+
+    // 第一步：检查文件头和分块结构
+    let structure = open_file_item(file)
+        .map_err(CryptoError::DecryptionError)
+        .and_then(|mut input| {
+            let (header, mut reader) = FileHeader::read_from(&mut input)?;
+            let provider = create_crypto_provider(disk_algorithm(algorithm, header.as_ref()));
+            check_framing(&mut reader, provider.chunk_size(), provider.nonce_len())
+        });
+    match structure {
+        Ok(chunks) => report.chunks = chunks,
+        Err(e) => {
+            report.status = AuditStatus::Failed(e.to_string());
+            return report;
+        }
+    }
+
+    // 第二步：使用密码校验认证标签（不写出明文）
+    if let Some(password) = password.filter(|p| !p.is_empty()) {
+        let result = open_file_item(file)
+            .map_err(CryptoError::DecryptionError)
+            .and_then(|mut input| {
+                let (header, mut reader) = FileHeader::read_from(&mut input)?;
+                // 摘要块绑定文件头时一并核对，压缩的文件要解压后才能核对最终明文
+                let binding = header.as_ref().and_then(FileHeader::digest_binding);
+                let control = StreamControl::default().with_binding(binding.clone());
+                let compression = header.as_ref().and_then(|header| header.compression);
+                let mut decompress = compression
+                    .map(|compression| DecompressWriter::new(PlaintextDigest::new(io::sink(), binding.as_ref()), compression.algorithm));
+                let mut sink = io::sink();
+                let writer: &mut (dyn Write + Send) = match decompress.as_mut() {
+                    Some(decompress) => decompress,
+                    None => &mut sink,
+                };
+                // 级联加密的文件要解开两层才能校验全部认证标签
+                if let Some(cascade) = header.as_ref().and_then(|header| header.cascade.as_ref()) {
+                    let inner = create_decryption_provider(&cascade.inner, header.as_ref(), password)?;
+                    let outer = create_decryption_provider(&cascade.outer, header.as_ref(), password)?;
+                    cascade::decrypt_stream(inner.as_ref(), outer.as_ref(), password, &mut reader, writer, None, &control)?;
+                } else {
+                    let algorithm = header.as_ref().map_or(algorithm, |header| header.data_algorithm(algorithm));
+                    create_decryption_provider(algorithm, header.as_ref(), password)?
+                        .decrypt_stream(password, &mut reader, writer, &control)?;
+                }
+                if let (Some(decompress), Some(compression)) = (decompress, compression) {
+                    if decompress.finish()? != compression.plaintext_len {
+                        return Err(CryptoError::DecryptionError("解压后的长度与文件头不符".to_string()));
+                    }
+                }
+                binding.map_or(Ok(()), |binding| binding.verify())
+            });
+        match result {
+            Ok(()) => report.authenticated = true,
+            Err(e) => report.status = AuditStatus::Failed(e.to_string()),
+        }
+    }
+
+    report
+}
+
+/// 快速完整性检查的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntegrityBadge {
+    /// 文件头和分块结构完整，可以尝试解密
+    Valid,
+    /// 不是可识别的Krypton文件（或无法读取）
+    UnknownFormat,
+    /// 文件比记录的长度短，或最后一块不完整
+    Truncated,
+}
+
+impl IntegrityBadge {
+    pub fn label(&self) -> &'static str {
+        match self {
+            IntegrityBadge::Valid => "Valid",
+            IntegrityBadge::UnknownFormat => "Unknown format",
+            IntegrityBadge::Truncated => "Truncated",
+        }
+    }
+}
+
+/// 快速检查加密文件的格式，不需要密码：只读取文件头和各块的长度字段，跳过密文本身
+///
+/// 文件头记录了明文总长度时直接比较文件大小；旧格式逐块定位。多分卷文件只检查第一个分卷的文件头和总大小
+pub fn quick_check(file: &FileItem, algorithm: &EncryptionAlgorithm) -> IntegrityBadge {
+    let Ok(mut input) = File::open(&file.path) else {
+        return IntegrityBadge::UnknownFormat;
+    };
+    let Ok(header) = FileHeader::read_from(&mut input).map(|(header, _)| header) else {
+        return IntegrityBadge::UnknownFormat;
+    };
+    let provider = create_crypto_provider(disk_algorithm(algorithm, header.as_ref()));
+    let (chunk_size, nonce_len) = (provider.chunk_size(), provider.nonce_len());
+    let header_len = header.as_ref().map_or(0, FileHeader::encoded_len);
+    let size = file.size_on_disk();
+
+    let totals = header.as_ref()
+        .and_then(|header| header.totals)
+        .filter(|totals| totals.is_consistent(chunk_size));
+    if let Some(totals) = totals {
+        // 新格式在最后一块之后还有摘要块
+        let digest_len = if first_chunk_nonces(&mut input, header_len, nonce_len).is_sequenced() {
+            format::digest_chunk_len(nonce_len) as u64
+        } else {
+            0
+        };
+        return match size.cmp(&(header_len + totals.encrypted_len(nonce_len) + digest_len)) {
+            Ordering::Less => IntegrityBadge::Truncated,
+            Ordering::Equal => IntegrityBadge::Valid,
+            Ordering::Greater => IntegrityBadge::UnknownFormat,
+        };
+    }
+    if size < header_len + SALT_LEN as u64 {
+        return if header.is_some() { IntegrityBadge::Truncated } else { IntegrityBadge::UnknownFormat };
+    }
+    if file.is_multi_volume() {
+        return IntegrityBadge::Valid;
+    }
+    walk_chunk_lengths(&mut input, header_len + SALT_LEN as u64, size, chunk_size, nonce_len)
+        .unwrap_or(IntegrityBadge::UnknownFormat)
+}
+
+/// 磁盘上数据块所用的算法，旧文件使用 `algorithm`
+fn disk_algorithm<'a>(algorithm: &'a EncryptionAlgorithm, header: Option<&'a FileHeader>) -> &'a EncryptionAlgorithm {
+    header.map_or(algorithm, |header| header.disk_algorithm(algorithm))
+}
+
+/// 按第一块的nonce判断数据块的nonce方式，读不到第一块时按旧格式处理
+fn first_chunk_nonces(input: &mut File, header_len: u64, nonce_len: usize) -> ChunkNonces {
+    let mut nonce = vec![0u8; nonce_len];
+    match input.seek(SeekFrom::Start(header_len + SALT_LEN as u64)).and_then(|_| input.read_exact(&mut nonce)) {
+        Ok(()) => ChunkNonces::detect(&nonce),
+        Err(_) => ChunkNonces::Random,
+    }
+}
+
+/// 从 `start` 开始按长度字段逐块定位到文件末尾
+fn walk_chunk_lengths(input: &mut File, start: u64, size: u64, chunk_size: usize, nonce_len: usize) -> io::Result<IntegrityBadge> {
+    let max_chunk_len = format::max_chunk_ciphertext(chunk_size) as u64;
+    let mut position = start;
+    while position < size {
+        if size - position < (nonce_len + LENGTH_LEN) as u64 {
+            return Ok(IntegrityBadge::Truncated);
+        }
+        input.seek(SeekFrom::Start(position + nonce_len as u64))?;
+        let mut length_bytes = [0u8; LENGTH_LEN];
+        input.read_exact(&mut length_bytes)?;
+        let length = u32::from_le_bytes(length_bytes) as u64;
+        if length < TAG_LEN as u64 || length > max_chunk_len {
+            return Ok(IntegrityBadge::UnknownFormat);
+        }
+        position += (nonce_len + LENGTH_LEN) as u64 + length;
+    }
+    Ok(if position == size { IntegrityBadge::Valid } else { IntegrityBadge::Truncated })
+}
+
+/// 遍历分块结构并返回块数量，不需要密码
+fn check_framing<R: Read>(reader: &mut R, chunk_size: usize, nonce_len: usize) -> CryptoResult<u64> {
+    let mut salt = [0u8; SALT_LEN];
+    reader.read_exact(&mut salt)
+        .map_err(|_| CryptoError::DecryptionError("文件头不完整".to_string()))?;
+
+    let max_chunk_len = (chunk_size + TAG_LEN) as u64;
+    let mut chunks = 0u64;
+
+    loop {
+        let mut nonce = vec![0u8; nonce_len];
+        match read_full(reader, &mut nonce)? {
+            0 => break,
+            n if n == nonce_len => {}
+            _ => return Err(CryptoError::DecryptionError(format!("块 {} 的nonce被截断", chunks))),
+        }
+
+        let mut length_bytes = [0u8; 4];
+        if read_full(reader, &mut length_bytes)? != length_bytes.len() {
+            return Err(CryptoError::DecryptionError(format!("块 {} 的长度字段被截断", chunks)));
+        }
+        let length = u32::from_le_bytes(length_bytes) as u64;
+        if length < TAG_LEN as u64 || length > max_chunk_len {
+            return Err(CryptoError::DecryptionError(format!("块 {} 的长度无效: {}", chunks, length)));
+        }
+
+        let copied = io::copy(&mut reader.take(length), &mut io::sink())?;
+        if copied != length {
+            return Err(CryptoError::DecryptionError(format!("块 {} 的数据被截断", chunks)));
+        }
+
+        chunks += 1;
+    }
+
+    Ok(chunks)
+}
+
+/// 尽量读满缓冲区，返回实际读取的字节数（到达文件末尾时可能小于缓冲区长度）
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::chacha20::ChaCha20CryptoProvider;
+    use crate::crypto::header::StreamTotals;
+    use crate::crypto::CryptoProvider;
+    use crate::models::KdfParams;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_quick_check_badges() {
+        let dir = TestDir::new("quick_check");
+        let provider = ChaCha20CryptoProvider::with_kdf(KdfParams { memory_kib: 8 * 1024, iterations: 1, parallelism: 1, ..KdfParams::default() });
+        let plaintext = vec![7u8; provider.chunk_size() + 100];
+        let algorithm = EncryptionAlgorithm::ChaCha20;
+        let check = |name: &str, data: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, data).unwrap();
+            quick_check(&FileItem::new(path, name.to_string()), &algorithm)
+        };
+
+        let mut legacy = Vec::new();
+        provider.encrypt_stream("pw", &mut plaintext.as_slice(), &mut legacy, &StreamControl::default()).unwrap();
+        assert_eq!(check("legacy.enc", &legacy), IntegrityBadge::Valid);
+        assert_eq!(check("legacy_cut.enc", &legacy[..legacy.len() - 5]), IntegrityBadge::Truncated);
+        assert_eq!(check("random.enc", &[0xAB; 100]), IntegrityBadge::UnknownFormat);
+
+        let mut current = Vec::new();
+        let header = FileHeader {
+            totals: Some(StreamTotals::for_plaintext(plaintext.len() as u64, provider.chunk_size())),
+            ..FileHeader::default()
+        };
+        header.write_to(&mut current).unwrap();
+        current.extend_from_slice(&legacy);
+        assert_eq!(check("current.enc", &current), IntegrityBadge::Valid);
+        // 在块边界处截断：每块单独看都完整，只能通过文件头中的总长度发现
+        let digest_chunk = format::digest_chunk_len(format::NONCE_LEN);
+        let boundary = current.len() - digest_chunk - (100 + format::CHUNK_OVERHEAD);
+        assert_eq!(check("current_cut.enc", &current[..boundary]), IntegrityBadge::Truncated);
+        assert_eq!(check("current_no_digest.enc", &current[..current.len() - digest_chunk]), IntegrityBadge::Truncated);
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+/// 单个任务除分块缓冲区外的额外开销估算（读写缓冲区等）
+const TASK_OVERHEAD: usize = 64 * 1024;
+
+/// 全局缓冲区预算 - 限制所有工作线程同时占用的缓冲区内存总量
+///
+/// 工作线程在处理文件前申请租约，预算不足时阻塞等待，租约释放后唤醒等待者
+#[derive(Debug)]
+pub struct BufferBudget {
+    capacity: usize,
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+impl BufferBudget {
+    /// 创建指定容量（字节）的预算
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            capacity: capacity.max(1),
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        })
+    }
+
+    /// 估算处理一个文件所需的缓冲区大小（明文块、密文块及读写缓冲区）
+    pub fn task_estimate(chunk_size: usize) -> usize {
+        chunk_size * 2 + TASK_OVERHEAD
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 当前已被租用的字节数
+    pub fn in_use(&self) -> usize {
+        *self.used.lock().unwrap()
+    }
+
+    /// 申请租约，预算不足时阻塞直到其他租约释放
+    ///
+    /// 超过总容量的申请会被截断为总容量，保证单个大任务不会永久等待
+    pub fn acquire(self: &Arc<Self>, bytes: usize) -> BufferLease {
+        let bytes = bytes.min(self.capacity);
+        let mut used = self.used.lock().unwrap();
+        while *used + bytes > self.capacity {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+
+        BufferLease {
+            budget: self.clone(),
+            bytes,
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        *used -= bytes;
+        self.released.notify_all();
+    }
+}
+
+/// 缓冲区租约，离开作用域时自动归还预算
+#[derive(Debug)]
+pub struct BufferLease {
+    budget: Arc<BufferBudget>,
+    bytes: usize,
+}
+
+impl BufferLease {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for BufferLease {
+    fn drop(&mut self) {
+        self.budget.release(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_acquire_waits_for_release() {
+        let budget = BufferBudget::new(100);
+        let first = budget.acquire(80);
+        assert_eq!(budget.in_use(), 80);
+
+        let (tx, rx) = mpsc::channel();
+        let waiter_budget = budget.clone();
+        let waiter = thread::spawn(move || {
+            let lease = waiter_budget.acquire(50);
+            tx.send(lease.bytes()).unwrap();
+        });
+
+        // 预算不足，第二个租约必须等待
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(first);
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), 50);
+        waiter.join().unwrap();
+        assert_eq!(budget.in_use(), 0);
+
+        // 超过总容量的申请被截断
+        assert_eq!(budget.acquire(500).bytes(), 100);
+    }
+}
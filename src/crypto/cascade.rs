@@ -0,0 +1,158 @@
+//! 级联加密：先用一种算法加密，再用另一种算法加密第一层的完整密文
+//!
+//! 内层输出完整的加密流（盐值 + 数据块），外层把它当作明文再加密一次。两层各自生成随机盐值，
+//! 独立派生密钥，其中一种算法被攻破时数据仍受另一层保护。文件头记录两层的算法，
+//! 文件头中的总长度和块数描述外层（即磁盘上的数据）。两层在两个线程中通过内存管道流式处理
+
+use super::format::{self, Counted};
+use super::header::{algorithm_code, algorithm_from_code};
+use super::parallel;
+use super::traits::{CryptoError, CryptoProvider, CryptoResult, StreamControl};
+use crate::models::EncryptionAlgorithm;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// 级联加密的两层算法
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cascade {
+    /// 先加密明文的算法
+    pub inner: EncryptionAlgorithm,
+    /// 再加密内层密文的算法
+    pub outer: EncryptionAlgorithm,
+}
+
+impl Cascade {
+    /// 以 `inner` 为内层、另一种内置算法为外层，插件算法不支持级联
+    pub fn for_algorithm(inner: &EncryptionAlgorithm) -> Option<Self> {
+        let outer = match inner {
+            EncryptionAlgorithm::AES256 => EncryptionAlgorithm::ChaCha20,
+            EncryptionAlgorithm::ChaCha20 | EncryptionAlgorithm::XChaCha20 => EncryptionAlgorithm::AES256,
+            EncryptionAlgorithm::Age | EncryptionAlgorithm::OpenSsl | EncryptionAlgorithm::Plugin(_) => return None,
+        };
+        Some(Self { inner: inner.clone(), outer })
+    }
+
+    /// 写入文件头的算法代码（内层，外层）
+    pub(crate) fn codes(&self) -> [u8; 2] {
+        [algorithm_code(&self.inner).unwrap_or(0), algorithm_code(&self.outer).unwrap_or(0)]
+    }
+
+    pub(crate) fn from_codes(codes: [u8; 2]) -> Option<Self> {
+        let inner = algorithm_from_code(codes[0])?;
+        let outer = algorithm_from_code(codes[1])?;
+        (inner != outer).then_some(Self { inner, outer })
+    }
+}
+
+impl fmt::Display for Cascade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} + {}", self.inner, self.outer)
+    }
+}
+
+/// 级联加密数据流：当前线程运行内层并读取 `reader`，外层在另一个线程中写出到 `writer`。
+/// 只有内层报告进度，摘要块也只在内层绑定文件头
+pub fn encrypt_stream(
+    inner: &dyn CryptoProvider,
+    outer: &dyn CryptoProvider,
+    password: &str,
+    reader: &mut dyn Read,
+    writer: &mut (dyn Write + Send),
+    batch_size: Option<usize>,
+    control: &StreamControl,
+) -> CryptoResult<()> {
+    let (pipe_writer, pipe_reader) = format::pipe();
+    std::thread::scope(|scope| {
+        let outer_layer = scope.spawn(move || {
+            let mut pipe_reader = pipe_reader;
+            encrypt_layer(outer, password, &mut pipe_reader, writer, batch_size, &control.without_progress().without_binding())
+        });
+        let mut pipe_writer = pipe_writer;
+        let inner_result = encrypt_layer(inner, password, reader, &mut pipe_writer, batch_size, control);
+        // 关闭管道，外层读到结尾
+        drop(pipe_writer);
+        layer_result(inner_result, outer_layer.join().expect("cascade layer panicked"))
+    })
+}
+
+/// 级联解密数据流：当前线程运行外层并读取 `reader`，内层在另一个线程中写出到 `writer`。
+/// 返回外层解出的字节数（即内层密文的长度），用于与文件头中的总长度比较。只有外层报告进度，
+/// 摘要块只在内层核对文件头
+pub fn decrypt_stream(
+    inner: &dyn CryptoProvider,
+    outer: &dyn CryptoProvider,
+    password: &str,
+    reader: &mut dyn Read,
+    writer: &mut (dyn Write + Send),
+    batch_size: Option<usize>,
+    control: &StreamControl,
+) -> CryptoResult<u64> {
+    let (pipe_writer, pipe_reader) = format::pipe();
+    std::thread::scope(|scope| {
+        let inner_layer = scope.spawn(move || {
+            let mut pipe_reader = pipe_reader;
+            decrypt_layer(inner, password, &mut pipe_reader, writer, batch_size, &control.without_progress())
+        });
+        let mut outer_output = Counted::new(pipe_writer);
+        let outer_result = decrypt_layer(outer, password, reader, &mut outer_output, batch_size, &control.without_binding());
+        let count = outer_output.count();
+        drop(outer_output);
+        layer_result(outer_result, inner_layer.join().expect("cascade layer panicked")).map(|_| count)
+    })
+}
+
+fn encrypt_layer(provider: &dyn CryptoProvider, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, batch_size: Option<usize>, control: &StreamControl) -> CryptoResult<()> {
+    match batch_size {
+        Some(batch_size) => parallel::encrypt_stream(provider, password, reader, writer, batch_size, control),
+        None => provider.encrypt_stream(password, reader, writer, control),
+    }
+}
+
+fn decrypt_layer(provider: &dyn CryptoProvider, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, batch_size: Option<usize>, control: &StreamControl) -> CryptoResult<()> {
+    match batch_size {
+        Some(batch_size) => parallel::decrypt_stream(provider, password, reader, writer, batch_size, control),
+        None => provider.decrypt_stream(password, reader, writer, control),
+    }
+}
+
+/// 一层失败时，写入管道的一层只会看到管道中断，此时报告读取端真正的错误
+fn layer_result(upstream: CryptoResult<()>, downstream: CryptoResult<()>) -> CryptoResult<()> {
+    match (upstream, downstream) {
+        (Err(CryptoError::IoError(e)), Err(error)) if e.kind() == io::ErrorKind::BrokenPipe => Err(error),
+        (Err(error), _) => Err(error),
+        (Ok(()), downstream) => downstream,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::create_crypto_provider;
+
+    #[test]
+    fn test_cascade_round_trip() {
+        let cascade = Cascade::for_algorithm(&EncryptionAlgorithm::AES256).unwrap();
+        assert_eq!(cascade.outer, EncryptionAlgorithm::ChaCha20);
+        assert_eq!(Cascade::from_codes(cascade.codes()), Some(cascade.clone()));
+        assert_eq!(Cascade::from_codes([1, 1]), None);
+
+        let inner = create_crypto_provider(&cascade.inner);
+        let outer = create_crypto_provider(&cascade.outer);
+        let plaintext = b"two layers of protection".repeat(1000);
+        let mut encrypted = Vec::new();
+        encrypt_stream(inner.as_ref(), outer.as_ref(), "pw", &mut &plaintext[..], &mut encrypted, None, &StreamControl::default()).unwrap();
+        let inner_len = format::encrypted_size(plaintext.len() as u64, inner.chunk_size(), inner.nonce_len());
+        assert_eq!(encrypted.len() as u64, format::encrypted_size(inner_len, outer.chunk_size(), outer.nonce_len()));
+
+        let mut decrypted = Vec::new();
+        let outer_len = decrypt_stream(inner.as_ref(), outer.as_ref(), "pw", &mut &encrypted[..], &mut decrypted, Some(4), &StreamControl::default()).unwrap();
+        assert_eq!(outer_len, inner_len);
+        assert_eq!(decrypted, plaintext);
+
+        // 只用外层算法解密得到的是内层密文，不是明文
+        let mut outer_only = Vec::new();
+        outer.decrypt_stream("pw", &mut &encrypted[..], &mut outer_only, &StreamControl::default()).unwrap();
+        assert_ne!(outer_only, plaintext);
+        assert!(decrypt_stream(inner.as_ref(), outer.as_ref(), "wrong", &mut &encrypted[..], &mut Vec::new(), None, &StreamControl::default()).is_err());
+    }
+}
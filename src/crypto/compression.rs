@@ -0,0 +1,204 @@
+//! 加密前的明文压缩
+//!
+//! 明文按 [`BLOCK_SIZE`] 分块压缩，每块写为：长度(u32 LE，最高位表示按原样存储) + 数据。
+//! 压缩后不比原数据小的块按原样存储，已经压缩过的数据最多只增加每块4字节。
+//! 压缩后的数据再交给加密提供者，文件头中记录压缩算法和压缩前的长度。
+//! 注意压缩后的密文长度会反映明文的可压缩程度
+
+use super::format;
+use crate::models::CompressionAlgorithm;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// 每块压缩前的最大长度
+pub const BLOCK_SIZE: usize = 1024 * 1024;
+/// 块长度字段的字节数
+const LENGTH_LEN: usize = 4;
+/// 长度字段的最高位：该块按原样存储
+const STORED_FLAG: u32 = 0x8000_0000;
+/// zstd的压缩级别
+const ZSTD_LEVEL: i32 = 3;
+/// 已经压缩过、再压缩没有效果的文件扩展名
+const COMPRESSED_EXTENSIONS: [&str; 24] = [
+    "zip", "rar", "7z", "gz", "tgz", "bz2", "xz", "zst", "lz4", "jpg", "jpeg", "png", "gif", "webp",
+    "heic", "mp3", "mp4", "m4a", "mkv", "mov", "avi", "ogg", "docx", "xlsx",
+];
+
+/// 文件是否值得压缩：已经压缩过的文件类型不再压缩
+pub fn should_compress(path: &Path) -> bool {
+    path.extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .is_none_or(|extension| !COMPRESSED_EXTENSIONS.contains(&extension.as_str()))
+}
+
+/// 压缩后长度的上限（全部按原样存储时），用于预分配输出
+pub fn max_compressed_size(plaintext_len: u64) -> u64 {
+    plaintext_len + format::chunk_count(plaintext_len, BLOCK_SIZE) * LENGTH_LEN as u64
+}
+
+fn compress_block(algorithm: CompressionAlgorithm, block: &[u8]) -> io::Result<Vec<u8>> {
+    match algorithm {
+        CompressionAlgorithm::Zstd => zstd::bulk::compress(block, ZSTD_LEVEL),
+        CompressionAlgorithm::Lz4 => Ok(lz4_flex::block::compress_prepend_size(block)),
+        CompressionAlgorithm::None => Ok(block.to_vec()),
+    }
+}
+
+fn decompress_block(algorithm: CompressionAlgorithm, data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("the compressed data is corrupt: {}", e));
+    let block = match algorithm {
+        CompressionAlgorithm::Zstd => zstd::bulk::decompress(data, BLOCK_SIZE).map_err(|e| invalid(e.to_string()))?,
+        CompressionAlgorithm::Lz4 => lz4_flex::block::decompress_size_prepended(data).map_err(|e| invalid(e.to_string()))?,
+        CompressionAlgorithm::None => data.to_vec(),
+    };
+    if block.len() > BLOCK_SIZE {
+        return Err(invalid(format!("block of {} bytes exceeds the limit", block.len())));
+    }
+    Ok(block)
+}
+
+/// 边读取边压缩的读取器，读出的是分块压缩后的数据
+pub struct CompressReader<R> {
+    inner: R,
+    algorithm: CompressionAlgorithm,
+    /// 已压缩、尚未读出的数据
+    pending: Vec<u8>,
+    position: usize,
+    input: Vec<u8>,
+}
+
+impl<R: Read> CompressReader<R> {
+    pub fn new(inner: R, algorithm: CompressionAlgorithm) -> Self {
+        Self { inner, algorithm, pending: Vec::new(), position: 0, input: vec![0u8; BLOCK_SIZE] }
+    }
+
+    /// 读取并压缩下一块，输入结束时返回false
+    fn fill(&mut self) -> io::Result<bool> {
+        let len = format::read_chunk(&mut self.inner, &mut self.input)?;
+        if len == 0 {
+            return Ok(false);
+        }
+        let block = &self.input[..len];
+        let compressed = compress_block(self.algorithm, block)?;
+        let (data, length) = match compressed.len() < len {
+            true => (compressed.as_slice(), compressed.len() as u32),
+            false => (block, len as u32 | STORED_FLAG),
+        };
+        self.pending.clear();
+        self.pending.extend_from_slice(&length.to_le_bytes());
+        self.pending.extend_from_slice(data);
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for CompressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.pending.len() && !self.fill()? {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.pending.len() - self.position);
+        buf[..len].copy_from_slice(&self.pending[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// 边写入边解压的写入器，写入的是分块压缩后的数据
+pub struct DecompressWriter<W> {
+    inner: W,
+    algorithm: CompressionAlgorithm,
+    /// 尚未凑成完整一块的数据
+    pending: Vec<u8>,
+    written: u64,
+}
+
+impl<W: Write> DecompressWriter<W> {
+    pub fn new(inner: W, algorithm: CompressionAlgorithm) -> Self {
+        Self { inner, algorithm, pending: Vec::new(), written: 0 }
+    }
+
+    /// 结束写入，返回解压后的总长度；最后一块不完整时返回错误
+    pub fn finish(self) -> io::Result<u64> {
+        if !self.pending.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the compressed data is truncated"));
+        }
+        Ok(self.written)
+    }
+
+    /// 解压并写出缓冲区中所有完整的块
+    fn flush_blocks(&mut self) -> io::Result<()> {
+        let mut start = 0;
+        while let Some(length) = self.pending.get(start..start + LENGTH_LEN) {
+            let length = u32::from_le_bytes(length.try_into().expect("4 bytes"));
+            let data_len = (length & !STORED_FLAG) as usize;
+            if data_len > BLOCK_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid compressed block length: {}", data_len)));
+            }
+            let Some(data) = self.pending.get(start + LENGTH_LEN..start + LENGTH_LEN + data_len) else {
+                break;
+            };
+            if length & STORED_FLAG != 0 {
+                self.inner.write_all(data)?;
+                self.written += data.len() as u64;
+            } else {
+                let block = decompress_block(self.algorithm, data)?;
+                self.inner.write_all(&block)?;
+                self.written += block.len() as u64;
+            }
+            start += LENGTH_LEN + data_len;
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for DecompressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.flush_blocks()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_round_trip() {
+        let text = b"timestamp,level,message\n2024-01-01,INFO,started\n".repeat(60_000);
+        let random: Vec<u8> = (0..BLOCK_SIZE + 10).map(|_| rand::random()).collect();
+        for algorithm in [CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4] {
+            for data in [&text[..], &random[..], b""] {
+                let mut compressed = Vec::new();
+                CompressReader::new(data, algorithm).read_to_end(&mut compressed).unwrap();
+                assert!(compressed.len() as u64 <= max_compressed_size(data.len() as u64));
+
+                // 按不同的长度分段写入
+                let mut decompressed = Vec::new();
+                let mut writer = DecompressWriter::new(&mut decompressed, algorithm);
+                for piece in compressed.chunks(7919) {
+                    writer.write_all(piece).unwrap();
+                }
+                assert_eq!(writer.finish().unwrap(), data.len() as u64);
+                assert_eq!(decompressed, data);
+
+                if !compressed.is_empty() {
+                    let mut writer = DecompressWriter::new(io::sink(), algorithm);
+                    writer.write_all(&compressed[..compressed.len() - 1]).unwrap();
+                    assert!(writer.finish().is_err());
+                }
+            }
+            let mut compressed = Vec::new();
+            CompressReader::new(&text[..], algorithm).read_to_end(&mut compressed).unwrap();
+            assert!(compressed.len() < text.len() / 10);
+        }
+        assert!(should_compress(Path::new("server.log")));
+        assert!(!should_compress(Path::new("photo.JPG")));
+    }
+}
@@ -1,35 +1,181 @@
-use crate::models::{FileItem, Settings, OperationMode, OperationHandle, OperationStatus, ProgressInfo, ProgressCallback};
-use crate::progress::{ProgressManager, ProgressTracker};
-use super::traits::{CryptoProvider, CryptoResult, CryptoError};
-use super::create_crypto_provider;
+use crate::models::{FileItem, KdfParams, Settings, ConflictPolicy, CompressionAlgorithm, OperationMode, EncryptionAlgorithm, BatchKeyMode, ErrorPolicy, HardwareToken, KeySource, OperationEvent, OperationHandle, OperationStatus, ProgressInfo, ProgressCallback, SkipRequests};
+use crate::progress::{ProgressFormatter, ProgressManager, ProgressTracker};
+use super::traits::{BatchKey, CryptoProvider, CryptoResult, CryptoError, StreamControl};
+use super::{create_crypto_provider, create_crypto_provider_with_batch_key, create_crypto_provider_with_kdf, create_decryption_provider};
+use super::audit::{audit_file, AuditReport};
+use super::budget::BufferBudget;
+use super::cascade::{self, Cascade};
+use super::compression::{self, CompressReader, DecompressWriter};
+use super::format;
+use super::format::{Counted, Digested, ReadAhead};
+use super::header::{algorithm_code, Compression, FileHeader, StreamTotals, MAX_ORIGINAL_NAME_LEN};
+use super::keyfile;
+use super::kms;
+use super::parallel;
+use super::stream::PlaintextDigest;
+use super::token;
+use super::hooks::FileHooks;
+use super::interop;
+use crate::core::{archiver, container};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::{self, BufReader, Read, Write};
+use crate::core::checksum;
+use crate::core::direct_io::DirectReader;
+use crate::core::mapped_io::{MappedReader, MIN_MAPPED_SIZE};
+use crate::core::incremental::IncrementalState;
+use crate::core::journal::Journal;
+use crate::core::name_index::FilenameIndex;
+use crate::core::output::{is_disk_full, unique_path, OutputFile};
+use crate::core::quarantine::Quarantine;
+use crate::core::recovery;
+use crate::core::report::{settings_fingerprint, FileReport, OperationReport};
+use crate::core::shred::shred_file;
+use crate::core::volumes::open_file_item;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::fs;
-use std::sync::{Arc, atomic::AtomicBool, Mutex, mpsc};
+use std::sync::{Arc, atomic::AtomicBool, Mutex, OnceLock, mpsc};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use rand::RngCore;
 use aes_gcm::aead::OsRng;
 use threadpool::ThreadPool;
 
+/// 线程池中单个文件任务的结果
+enum TaskOutcome {
+    Completed(PathBuf),
+    Skipped,
+    Cancelled,
+    Failed(String),
+}
+
+/// 设置了超时时间时检查任务是否超时的间隔
+const TIMEOUT_POLL: Duration = Duration::from_millis(500);
+
+/// 已提交到线程池、尚未回报结果的文件任务
+struct InFlightTask {
+    /// 工作线程开始处理的时间（排队等待线程和缓冲区预算的时间不计入超时）
+    started: Arc<OnceLock<Instant>>,
+    /// 超时后置位：任务的结果已记为失败，稍后结束时丢弃结果
+    abandoned: Arc<AtomicBool>,
+}
+
+/// 单个文件加密/解密的选项（见 [`CryptoEngine::encrypt_path`] 和 [`CryptoEngine::decrypt_path`]）
+#[derive(Debug, Clone)]
+pub struct EncryptOptions {
+    pub password: String,
+    pub algorithm: EncryptionAlgorithm,
+    /// 与密码一起参与密钥派生的密钥文件
+    pub keyfile: Option<PathBuf>,
+    /// 写入文件头的明文密码提示，只在加密时使用
+    pub password_hint: String,
+    /// Argon2参数，只在加密时使用（解密时从文件头读取）
+    pub kdf: KdfParams,
+    /// 加密前压缩明文，只在加密时使用（解密时按文件头解压）
+    pub compression: CompressionAlgorithm,
+    /// 用rayon并行处理同一文件的数据块
+    pub parallel_chunks: bool,
+    /// 绕过操作系统页缓存读写文件
+    pub direct_io: bool,
+    /// 加密时在后台线程中预读下一块
+    pub read_ahead: bool,
+    /// 大文件用内存映射读写
+    pub mapped_io: bool,
+    /// 并行处理数据块时的缓冲区内存上限（MB）
+    pub memory_budget_mb: u32,
+}
+
+impl Default for EncryptOptions {
+    fn default() -> Self {
+        let settings = Settings::default();
+        Self {
+            password: String::new(),
+            algorithm: settings.encryption_algorithm,
+            keyfile: None,
+            password_hint: String::new(),
+            kdf: settings.kdf,
+            compression: settings.compression,
+            parallel_chunks: settings.parallel_chunks,
+            direct_io: settings.direct_io,
+            read_ahead: settings.read_ahead,
+            mapped_io: settings.mapped_io,
+            memory_budget_mb: settings.memory_budget_mb,
+        }
+    }
+}
+
+impl EncryptOptions {
+    fn to_settings(&self, operation_mode: OperationMode) -> Settings {
+        Settings {
+            operation_mode,
+            encryption_algorithm: self.algorithm.clone(),
+            password: self.password.clone(),
+            keyfile: self.keyfile.clone(),
+            password_hint: self.password_hint.clone(),
+            kdf: self.kdf,
+            compression: self.compression,
+            parallel_chunks: self.parallel_chunks,
+            direct_io: self.direct_io,
+            read_ahead: self.read_ahead,
+            mapped_io: self.mapped_io,
+            memory_budget_mb: self.memory_budget_mb,
+            ..Settings::default()
+        }
+    }
+}
+
+/// 所有引擎共享的全局线程池，排队的批处理、监视目录和审计都提交到这里
+static SHARED_POOL: OnceLock<Mutex<ThreadPool>> = OnceLock::new();
+
 /// 重构后的加密引擎，使用策略模式和线程池
+///
+/// 克隆的引擎共享同一个线程池
+#[derive(Clone)]
 pub struct CryptoEngine {
-    thread_pool: Arc<ThreadPool>,
+    thread_pool: ThreadPool,
+    hooks: FileHooks,
 }
 
 impl CryptoEngine {
-    /// 创建新的加密引擎实例
+    /// 创建使用独立线程池的加密引擎实例
     pub fn new(max_threads: usize) -> Self {
-        let thread_pool = ThreadPool::new(max_threads);
         Self {
-            thread_pool: Arc::new(thread_pool),
+            thread_pool: ThreadPool::new(max_threads.max(1)),
+            hooks: FileHooks::default(),
         }
     }
 
-    /// 从设置创建加密引擎实例
+    /// 从设置创建使用全局共享线程池的加密引擎实例，池大小调整为设置中的线程数
     pub fn from_settings(settings: &Settings) -> Self {
-        Self::new(settings.max_threads as usize)
+        Self {
+            thread_pool: Self::shared_pool(settings.max_threads as usize),
+            hooks: FileHooks::default(),
+        }
+    }
+
+    /// 设置每个文件处理前后调用的钩子
+    pub fn with_hooks(mut self, hooks: FileHooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// 调整全局线程池的大小，已创建的引擎立即生效；
+    /// 缩小时正在执行的任务不受影响，多余的线程在完成当前任务后退出
+    pub fn resize_shared_pool(max_threads: usize) {
+        Self::shared_pool(max_threads);
+    }
+
+    /// 获取全局线程池的句柄，必要时先调整大小
+    fn shared_pool(max_threads: usize) -> ThreadPool {
+        let max_threads = max_threads.max(1);
+        let mut pool = SHARED_POOL.get_or_init(|| Mutex::new(ThreadPool::new(max_threads)))
+            .lock()
+            .unwrap();
+        if pool.max_count() != max_threads {
+            pool.set_num_threads(max_threads);
+        }
+        pool.clone()
     }
 
     /// 开始异步加密/解密操作
@@ -39,10 +185,8 @@ impl CryptoEngine {
         files: Vec<FileItem>,
         progress_callback: Option<ProgressCallback>,
     ) -> Result<OperationHandle, String> {
-        // 验证密码不为空
-        if settings.password.is_empty() {
-            return Err("Password cannot be empty".to_string());
-        }
+        // 校验密钥来源、扩展名、线程数和密钥派生参数
+        settings.validate().map_err(|e| e.to_string())?;
 
         // 筛选已选中的文件
         let selected_files: Vec<FileItem> = files.into_iter()
@@ -55,50 +199,63 @@ impl CryptoEngine {
 
         // 创建控制标志
         let should_stop = Arc::new(AtomicBool::new(false));
-        let should_skip = Arc::new(AtomicBool::new(false));
+        let should_skip = SkipRequests::default();
         let status = Arc::new(Mutex::new(OperationStatus::Running));
-        let progress = Arc::new(Mutex::new(ProgressInfo {
-            current_file: String::new(),
-            current_file_index: 0,
-            total_files: selected_files.len(),
-            current_file_progress: 0.0,
-            overall_progress: 0.0,
-            current_file_size: 0,
-            processed_bytes: 0,
-            total_bytes: 0,
-            speed_mbps: 0.0,
-            elapsed_time: 0.0,
-            estimated_remaining: 0.0,
-        }));
-
-        // 创建进度消息通道
-        let (progress_sender, progress_receiver) = mpsc::channel::<ProgressInfo>();
-
-        // 创建进度跟踪器
-        let progress_tracker = ProgressManager::create_tracker(
-            &selected_files,
-            progress_sender,
+        let progress = Arc::new(Mutex::new(ProgressInfo::new(selected_files.len(), 0, settings.operation_mode.clone())));
+        let results = Arc::new(Mutex::new(Vec::new()));
+
+        // 创建操作事件通道（进度、文件状态和操作结束都通过它发送给UI）
+        let (event_sender, event_receiver) = mpsc::channel::<OperationEvent>();
+
+        // 创建进度跟踪器，总大小由工作线程统计后再报告
+        let progress_tracker = ProgressTracker::new(
+            selected_files.len(),
+            0,
+            settings.operation_mode.clone(),
+            event_sender.clone(),
             progress_callback,
             Some(progress.clone()),
-        );
+        ).with_results(results.clone());
 
         // 克隆用于线程的引用
         let should_stop_clone = should_stop.clone();
         let should_skip_clone = should_skip.clone();
         let status_clone = status.clone();
-        let thread_pool_clone = self.thread_pool.clone();
+        let engine = self.clone();
 
         // 启动工作线程
         let thread_handle = thread::spawn(move || {
-            Self::process_files_async_with_pool(
-                &settings,
-                &selected_files,
-                should_stop_clone,
-                should_skip_clone,
-                status_clone,
-                progress_tracker,
-                thread_pool_clone,
-            )
+            // 解密时同一批文件的主密钥只派生一次，批处理结束时清除
+            let _batch_key_scope = BatchKey::cache_scope();
+            let prepared = Self::prepare_files(&settings, selected_files, &progress_tracker)
+                .and_then(|files| Self::check_password(&settings, files.first()).map(|_| files))
+                .and_then(|files| Ok((Self::with_quarantine(Self::with_batch_key(&settings)?), files)));
+            let result = match prepared {
+                Ok((settings, files)) if settings.operation_mode.packs_files() => Self::archive_files_async(
+                    &settings,
+                    &files,
+                    &should_stop_clone,
+                    &status_clone,
+                    &progress_tracker,
+                ),
+                Ok((settings, files)) => engine.process_files_async_with_pool(
+                    &Self::with_journal(settings, &files),
+                    &files,
+                    should_stop_clone,
+                    should_skip_clone,
+                    status_clone.clone(),
+                    progress_tracker,
+                ),
+                Err(e) => {
+                    *status_clone.lock().unwrap() = OperationStatus::Failed(e.clone());
+                    Err(e)
+                }
+            };
+
+            // 最后发送操作结束事件
+            let final_status = status_clone.lock().unwrap().clone();
+            let _ = event_sender.send(OperationEvent::Finished(final_status));
+            result
         });
 
         Ok(OperationHandle {
@@ -107,20 +264,65 @@ impl CryptoEngine {
             should_skip,
             status,
             progress,
-            progress_receiver: Some(progress_receiver),
+            results,
+            event_receiver: Some(event_receiver),
         })
     }
 
+    /// 启用批处理密钥时，在批处理开始时为整批文件派生一次主密钥
+    fn with_batch_key(settings: &Settings) -> Result<Settings, String> {
+        let mut settings = settings.clone();
+        // 使用密钥管理服务或硬件令牌时每个文件的密码不同，仍按文件派生
+        if settings.operation_mode != OperationMode::Encrypt
+            || settings.key_source != KeySource::Passphrase
+            || settings.hardware_token != HardwareToken::None
+        {
+            return Ok(settings);
+        }
+        // 密钥文件的混合结果对每个文件都相同，主密钥从混合后的密码派生
+        let password = || keyfile::bind_password(settings.keyfile.as_deref(), &settings.password).map(|(password, _)| password);
+        let key = match settings.batch_key {
+            BatchKeyMode::PerFile => return Ok(settings),
+            BatchKeyMode::Derived => BatchKey::generate(&password()?, settings.kdf),
+            BatchKeyMode::Session => BatchKey::generate_session(&password()?, settings.kdf),
+        };
+        settings.batch_master_key = Some(key.map_err(|e| format!("Failed to derive the batch key: {}", e))?);
+        Ok(settings)
+    }
+
+    /// 在工作线程中准备待处理的文件：增量筛选、按调度策略排序并报告总大小
+    ///
+    /// 这些步骤都要逐个读取文件元数据，网络共享上的大量文件会很慢，不能放在调用线程（UI线程）中
+    fn prepare_files(settings: &Settings, files: Vec<FileItem>, progress_tracker: &ProgressTracker) -> Result<Vec<FileItem>, String> {
+        // 增量模式下只处理新增或已修改的文件
+        let mut files = match Self::load_incremental_state(settings)? {
+            Some(state) => {
+                let changed: Vec<FileItem> = files.into_iter()
+                    .filter(|file| state.needs_processing(file))
+                    .collect();
+                if changed.is_empty() {
+                    return Err("All selected files are up to date".to_string());
+                }
+                changed
+            }
+            None => files,
+        };
+
+        // 按设置的调度策略排序
+        settings.processing_order.sort(&mut files);
+
+        progress_tracker.set_totals(files.len(), ProgressManager::calculate_total_size(&files));
+        Ok(files)
+    }
+
     /// 同步版本的开始加密/解密操作（实例方法）
     pub fn start_operation(
         &self,
         settings: &Settings,
         files: &[FileItem],
     ) -> Result<(), String> {
-        // 验证密码不为空
-        if settings.password.is_empty() {
-            return Err("Password cannot be empty".to_string());
-        }
+        // 校验密钥来源、扩展名、线程数和密钥派生参数
+        settings.validate().map_err(|e| e.to_string())?;
 
         // 筛选已选中的文件
         let selected_files: Vec<&FileItem> = files.iter()
@@ -131,27 +333,158 @@ impl CryptoEngine {
             return Err("No files selected".to_string());
         }
 
-        // 根据是否启用多线程决定处理方式
-        if settings.max_threads > 1 {
+        // 增量模式下只处理新增或已修改的文件
+        let mut selected_files = match Self::load_incremental_state(settings)? {
+            Some(state) => {
+                let changed: Vec<&FileItem> = selected_files.into_iter()
+                    .filter(|file| state.needs_processing(file))
+                    .collect();
+                if changed.is_empty() {
+                    return Err("All selected files are up to date".to_string());
+                }
+                changed
+            }
+            None => selected_files,
+        };
+
+        // 输出已存在且设置为跳过的文件不处理
+        selected_files.retain(|file| !Self::skips_existing_output(settings, file));
+
+        // 按设置的调度策略排序
+        settings.processing_order.sort(&mut selected_files);
+        Self::check_password(settings, selected_files.first().copied())?;
+        let settings = &Self::with_quarantine(Self::with_batch_key(settings)?);
+
+        // 根据是否启用多线程决定处理方式，归档和容器模式把所有文件写入同一个输出
+        let result = if settings.operation_mode.packs_files() {
+            let (event_sender, _) = mpsc::channel();
+            let files: Vec<FileItem> = selected_files.iter().map(|file| (*file).clone()).collect();
+            let total_size = ProgressManager::calculate_total_size(&files);
+            let progress_tracker = ProgressTracker::new(files.len(), total_size, settings.operation_mode.clone(), event_sender, None, None);
+            Self::archive_files(settings, &files, &AtomicBool::new(false), &progress_tracker).map(|_| ())
+        } else if settings.max_threads > 1 {
             self.process_files_with_pool(settings, &selected_files)
         } else {
-            Self::process_files_sequential(settings, &selected_files)
+            self.process_files_sequential(settings, &selected_files)
+        };
+        let settled = Self::settle_quarantine(settings, result.is_ok());
+        result.and(settled.map(|_| ()))
+    }
+
+    /// 把所有文件写入一个加密的7z归档或Krypton容器，成功后按设置删除源文件，返回归档路径
+    fn archive_files(settings: &Settings, files: &[FileItem], should_stop: &AtomicBool, progress_tracker: &ProgressTracker) -> Result<PathBuf, String> {
+        let output = match settings.operation_mode {
+            OperationMode::Container => {
+                let output = archiver::archive_path(settings, files, container::CONTAINER_EXTENSION)?;
+                container::create_container(settings, files, &output, should_stop, progress_tracker)?;
+                output
+            }
+            _ => {
+                let output = archiver::archive_path(settings, files, archiver::ARCHIVE_EXTENSION)?;
+                archiver::create_archive(files, &output, &settings.password, should_stop, progress_tracker)?;
+                output
+            }
+        };
+        if settings.delete_source {
+            files.iter().try_for_each(|file| Self::remove_source(settings, &file.path))?;
+        }
+        Ok(output)
+    }
+
+    /// 在工作线程中创建归档并设置最终状态，源文件的隔离区与普通批处理一样在结束时处理
+    fn archive_files_async(
+        settings: &Settings,
+        files: &[FileItem],
+        should_stop: &AtomicBool,
+        status: &Mutex<OperationStatus>,
+        progress_tracker: &ProgressTracker,
+    ) -> Result<(), String> {
+        let result = Self::archive_files(settings, files, should_stop, progress_tracker);
+        let mut final_status = match &result {
+            Ok(_) => OperationStatus::Completed,
+            Err(_) if should_stop.load(std::sync::atomic::Ordering::Relaxed) => OperationStatus::Cancelled,
+            Err(e) => OperationStatus::Failed(e.clone()),
+        };
+        match Self::settle_quarantine(settings, final_status == OperationStatus::Completed) {
+            Ok(Some(quarantine)) => progress_tracker.send_event(OperationEvent::SourcesQuarantined(quarantine)),
+            Ok(None) => {}
+            Err(e) => {
+                if final_status == OperationStatus::Completed {
+                    final_status = OperationStatus::Failed(e);
+                }
+            }
+        }
+        *status.lock().unwrap() = final_status.clone();
+        match final_status {
+            OperationStatus::Failed(e) => Err(e),
+            OperationStatus::Cancelled => Err("Operation cancelled".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// 设置了删除源文件时为本次批处理创建隔离区，源文件先移入隔离区，批处理结束后再决定删除还是恢复
+    fn with_quarantine(mut settings: Settings) -> Settings {
+        if settings.delete_source {
+            settings.quarantine = Some(Quarantine::new(settings.shred_source.then_some(settings.shred_passes)));
+        }
+        settings
+    }
+
+    /// 设置了可恢复的加密或解密批处理写出日志；
+    /// 继续中断的批处理时沿用传入的日志，并接管中断前隔离的源文件
+    fn with_journal(mut settings: Settings, files: &[FileItem]) -> Settings {
+        if !settings.resumable || !matches!(settings.operation_mode, OperationMode::Encrypt | OperationMode::Decrypt) {
+            return settings;
+        }
+        match &settings.journal {
+            Some(journal) => {
+                if let Some(quarantine) = &settings.quarantine {
+                    quarantine.adopt(journal.record().quarantined);
+                }
+            }
+            // 日志写不出来时照常处理，只是中断后不能继续
+            None => settings.journal = Journal::begin(&settings, files).ok(),
+        }
+        settings
+    }
+
+    /// 批处理结束时处理隔离的源文件：失败或取消时全部恢复；成功时删除，
+    /// 设置了 `undo_delete` 时保留并返回隔离区，由调用方决定
+    fn settle_quarantine(settings: &Settings, succeeded: bool) -> Result<Option<Quarantine>, String> {
+        let Some(quarantine) = settings.quarantine.as_ref().filter(|quarantine| !quarantine.is_empty()) else {
+            return Ok(None);
+        };
+        if !succeeded {
+            quarantine.restore()
+                .map_err(|e| format!("Failed to restore source files from quarantine: {}", e))?;
+            return Ok(None);
+        }
+        if settings.undo_delete {
+            return Ok(Some(quarantine.clone()));
         }
+        quarantine.purge()
+            .map_err(|e| format!("Failed to delete source files: {}", e))?;
+        Ok(None)
     }
     
     /// 顺序处理文件
-    fn process_files_sequential(settings: &Settings, files: &[&FileItem]) -> Result<(), String> {
+    fn process_files_sequential(&self, settings: &Settings, files: &[&FileItem]) -> Result<(), String> {
+        let mut completed = Vec::new();
+        let mut first_error = None;
         for file in files {
-            match settings.operation_mode {
-                OperationMode::Encrypt => {
-                    Self::encrypt_file(settings, file)?;
-                }
-                OperationMode::Decrypt => {
-                    Self::decrypt_file(settings, file)?;
+            match self.hooks.run(file, || Self::process_file(settings, file)) {
+                Ok(output_path) => completed.push((output_path, (*file).clone())),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                    if settings.error_policy == ErrorPolicy::StopOnError {
+                        break;
+                    }
                 }
             }
         }
-        Ok(())
+
+        Self::record_completed(settings, &completed)?;
+        first_error.map_or(Ok(()), Err)
     }
 
 
@@ -160,208 +493,1056 @@ impl CryptoEngine {
         use std::sync::mpsc;
 
         let (tx, rx) = mpsc::channel();
+        let (budget, task_bytes) = Self::buffer_budget(settings);
 
         // 为每个文件提交任务到线程池
         for file in files {
             let tx = tx.clone();
             let settings = settings.clone();
             let file = (*file).clone();
+            let budget = budget.clone();
+            let hooks = self.hooks.clone();
 
             self.thread_pool.execute(move || {
-                let result = match settings.operation_mode {
-                    OperationMode::Encrypt => {
-                        Self::encrypt_file(&settings, &file)
-                    }
-                    OperationMode::Decrypt => {
-                        Self::decrypt_file(&settings, &file)
-                    }
-                };
+                // 等待缓冲区预算，任务结束时自动归还
+                let _lease = budget.acquire(task_bytes);
+                let result = hooks.run(&file, || Self::process_file(&settings, &file))
+                    .map(|output_path| (output_path, file));
                 tx.send(result).unwrap();
             });
         }
 
         // 等待所有任务完成并收集结果
         drop(tx); // 关闭发送端
+        let mut first_error = None;
+        let mut completed = Vec::new();
         for _ in 0..files.len() {
             match rx.recv() {
-                Ok(result) => result?,
-                Err(_) => return Err("Failed to receive result from thread pool".to_string()),
+                Ok(Ok(done)) => completed.push(done),
+                Ok(Err(e)) => {
+                    first_error.get_or_insert(e);
+                }
+                Err(_) => {
+                    first_error.get_or_insert_with(|| "Failed to receive result from thread pool".to_string());
+                    break;
+                }
             }
         }
 
-        Ok(())
+        Self::record_completed(settings, &completed)?;
+        first_error.map_or(Ok(()), Err)
     }
 
     /// 异步处理文件（带进度回调和取消支持，使用线程池）
     fn process_files_async_with_pool(
+        &self,
         settings: &Settings,
         files: &[FileItem],
         should_stop: Arc<AtomicBool>,
-        should_skip: Arc<AtomicBool>,
+        should_skip: SkipRequests,
         status: Arc<Mutex<OperationStatus>>,
         progress_tracker: ProgressTracker,
-        thread_pool: Arc<ThreadPool>,
     ) -> Result<(), String> {
         use std::sync::mpsc;
 
+        // 停止时正在处理的文件在当前块之后中断，不必等整个文件处理完
+        let settings = &Settings { stream_control: StreamControl::new(should_stop.clone()), ..settings.clone() };
         let (tx, rx) = mpsc::channel();
-        let mut pending_tasks = 0;
+        let (budget, task_bytes) = Self::buffer_budget(settings);
+        let started = chrono::Local::now();
+        let started_at = Instant::now();
 
-        for (index, file) in files.iter().enumerate() {
-            // 检查是否应该停止
-            if should_stop.load(std::sync::atomic::Ordering::Relaxed) {
-                *status.lock().unwrap() = OperationStatus::Cancelled;
-                return Err("Operation cancelled".to_string());
-            }
+        // 每个批处理在线程池队列中最多保留与线程数相同的任务，完成一个再提交下一个。
+        // 多个批处理同时运行时各自的任务在队列中交替排列，后开始的批处理不必等前一个的文件全部排完
+        let window = self.thread_pool.max_count().max(1);
+        let mut next_index = 0;
+        let mut in_flight = 0;
+        let mut first_error = None;
+        let mut failed = 0;
+        let mut timed_out = 0;
+        let mut tasks: HashMap<usize, InFlightTask> = HashMap::new();
+        let mut completed = Vec::new();
+        let mut report_entries: Vec<Option<FileReport>> = vec![None; files.len()];
+        loop {
+            // 检查是否应该停止（已提交的任务仍需等待，以便记录其输出）
+            while in_flight < window && next_index < files.len() && !should_stop.load(std::sync::atomic::Ordering::Relaxed) {
+                let index = next_index;
+                let file = files[index].clone();
+                next_index += 1;
 
-            // 获取当前文件大小
-            let current_file_size = fs::metadata(&file.path)
-                .map(|m| m.len())
-                .unwrap_or(0);
+                // 开始处理文件
+                progress_tracker.start_file(index, &file);
+                // 继续中断的批处理时，已完成的文件不再处理
+                if let Some(output) = settings.journal.as_ref().and_then(|journal| journal.completed_output(&file.path)) {
+                    match output {
+                        Some(output) => {
+                            progress_tracker.send_event(OperationEvent::FileCompleted { index, name: file.name.clone(), output: output.clone() });
+                            completed.push((output.clone(), file.clone()));
+                            progress_tracker.complete_file(file.size_on_disk(), Some(output));
+                        }
+                        None => {
+                            progress_tracker.send_event(OperationEvent::FileSkipped { index, name: file.name.clone() });
+                            progress_tracker.complete_file(file.size_on_disk(), None);
+                        }
+                    }
+                    continue;
+                }
+                if let Some(journal) = &settings.journal {
+                    journal.file_started(&file.path);
+                }
+                in_flight += 1;
 
-            // 开始处理文件
-            progress_tracker.start_file(index, file.name.clone(), current_file_size);
+                // 提交任务到线程池；处理中的文件也响应跳过，并报告文件内的进度（可恢复的批处理同时记入日志）
+                let tx = tx.clone();
+                let tracker = progress_tracker.clone();
+                let journal = settings.journal.clone();
+                let source = file.path.clone();
+                let report_progress = move |processed| {
+                    tracker.update_file_bytes(index, processed);
+                    if let Some(journal) = &journal {
+                        journal.file_progress(&source, processed);
+                    }
+                };
+                let stream_control = settings.stream_control.clone()
+                    .with_skip(should_skip.flag(&file.path))
+                    .with_progress(Arc::new(report_progress));
+                let settings = Settings { stream_control, ..settings.clone() };
+                let should_stop_clone = should_stop.clone();
+                let budget = budget.clone();
+                let hooks = self.hooks.clone();
+                let task = InFlightTask {
+                    started: Arc::new(OnceLock::new()),
+                    abandoned: Arc::new(AtomicBool::new(false)),
+                };
+                let (started, abandoned) = (task.started.clone(), task.abandoned.clone());
+                tasks.insert(index, task);
 
-            // 提交任务到线程池
-            let tx = tx.clone();
-            let settings = settings.clone();
-            let file = file.clone();
-            let should_stop_clone = should_stop.clone();
-            let should_skip_clone = should_skip.clone();
-
-            thread_pool.execute(move || {
-                // 在任务执行前再次检查是否应该停止
-                if should_stop_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                    tx.send((index, Err("Operation cancelled".to_string()))).unwrap();
-                    return;
-                }
+                self.thread_pool.execute(move || {
+                    let task = || {
+                        // 等待缓冲区预算，任务结束时自动归还
+                        let _lease = budget.acquire(task_bytes);
+                        let _ = started.set(Instant::now());
+
+                        // 在任务执行前再次检查是否应该停止
+                        if should_stop_clone.load(std::sync::atomic::Ordering::Relaxed) {
+                            return (TaskOutcome::Cancelled, None);
+                        }
 
-                // 检查是否跳过当前文件
-                if should_skip_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                    should_skip_clone.store(false, std::sync::atomic::Ordering::Relaxed);
-                    tx.send((index, Ok(()))).unwrap();
-                    return;
+                        // 检查是否跳过当前文件（用户跳过，或输出已存在且设置为跳过）
+                        if settings.stream_control.skipped() || Self::skips_existing_output(&settings, &file) {
+                            return (TaskOutcome::Skipped, settings.write_report.then(|| FileReport::skipped(&file)));
+                        }
+
+                        // 处理单个文件；启用报告时在工作线程中计算输出文件的哈希
+                        let file_started = Instant::now();
+                        let result = hooks.run(&file, || Self::process_file(&settings, &file));
+                        // 超时后才结束的任务已记为失败，删除迟到的输出（校验模式的“输出”就是输入文件本身）
+                        if abandoned.load(std::sync::atomic::Ordering::Relaxed) {
+                            if let (Ok(output), false) = (&result, settings.operation_mode == OperationMode::Verify) {
+                                let _ = fs::remove_file(output);
+                            }
+                            return (TaskOutcome::Cancelled, None);
+                        }
+                        // 停止或跳过时中途中断的文件不算失败，不完整的输出已被删除
+                        if result.is_err() && should_stop_clone.load(std::sync::atomic::Ordering::Relaxed) {
+                            return (TaskOutcome::Cancelled, None);
+                        }
+                        if result.is_err() && settings.stream_control.skipped() {
+                            return (TaskOutcome::Skipped, settings.write_report.then(|| FileReport::skipped(&file)));
+                        }
+                        let entry = settings.write_report.then(|| {
+                            FileReport::new(&file, result.as_deref().map_err(String::as_str), file_started.elapsed())
+                        });
+                        let outcome = match result {
+                            Ok(output_path) => TaskOutcome::Completed(output_path),
+                            Err(e) => TaskOutcome::Failed(e),
+                        };
+                        (outcome, entry)
+                    };
+                    // 主循环持有发送端，任务崩溃时也必须回报结果，否则会一直等待
+                    let (outcome, entry) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(task))
+                        .unwrap_or_else(|_| (TaskOutcome::Failed(format!("Worker thread panicked while processing {}", file.name)), None));
+                    let _ = tx.send((index, outcome, entry));
+                });
+            }
+
+            // 等待已提交的任务完成
+            if in_flight == 0 {
+                break;
+            }
+            let received = match rx.recv_timeout(TIMEOUT_POLL) {
+                Ok(message) => Some(message),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    first_error.get_or_insert_with(|| "Failed to receive result from thread pool".to_string());
+                    break;
                 }
+            };
 
-                // 处理单个文件
-                let result = match settings.operation_mode {
-                    OperationMode::Encrypt => {
-                        Self::encrypt_file(&settings, &file)
-                    }
-                    OperationMode::Decrypt => {
-                        Self::decrypt_file(&settings, &file)
-                    }
+            // 超时的任务记为失败，不再等待它（卡住的线程留在后台自行结束）；整个批处理超时时取消所有未完成的文件
+            let batch_expired = settings.batch_timeout_mins > 0
+                && started_at.elapsed() > Duration::from_secs(settings.batch_timeout_mins as u64 * 60);
+            if batch_expired {
+                should_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                first_error.get_or_insert_with(|| format!("The batch did not finish within {} minutes", settings.batch_timeout_mins));
+            }
+            for (index, error) in Self::expired_tasks(settings, files, &tasks, batch_expired) {
+                let Some(task) = tasks.remove(&index) else {
+                    continue;
                 };
+                task.abandoned.store(true, std::sync::atomic::Ordering::Relaxed);
+                in_flight -= 1;
+                timed_out += 1;
+                let file = &files[index];
+                report_entries[index] = settings.write_report.then(|| {
+                    let elapsed = task.started.get().map_or(Duration::ZERO, Instant::elapsed);
+                    FileReport::new(file, Err(&error), elapsed)
+                });
+                if let Some(journal) = &settings.journal {
+                    journal.file_failed(&file.path);
+                }
+                progress_tracker.send_event(OperationEvent::FileFailed { index, name: file.name.clone(), error });
+                progress_tracker.fail_file();
+            }
 
-                tx.send((index, result)).unwrap();
-            });
+            // 已超时放弃的任务迟到的结果直接丢弃
+            let Some((index, outcome, entry)) = received else {
+                continue;
+            };
+            if tasks.remove(&index).is_none() {
+                continue;
+            }
+            in_flight -= 1;
+            let Some(file) = files.get(index) else {
+                continue;
+            };
+            report_entries[index] = entry;
+            if let Some(journal) = &settings.journal {
+                let quarantined = settings.quarantine.as_ref().map(Quarantine::entries).unwrap_or_default();
+                match &outcome {
+                    TaskOutcome::Completed(output_path) => journal.file_completed(&file.path, Some(output_path), quarantined),
+                    TaskOutcome::Skipped => journal.file_completed(&file.path, None, quarantined),
+                    TaskOutcome::Failed(_) => journal.file_failed(&file.path),
+                    TaskOutcome::Cancelled => {}
+                }
+            }
 
-            pending_tasks += 1;
+            match outcome {
+                TaskOutcome::Completed(output_path) => {
+                    progress_tracker.send_event(OperationEvent::FileCompleted {
+                        index,
+                        name: file.name.clone(),
+                        output: output_path.clone(),
+                    });
+                    completed.push((output_path.clone(), file.clone()));
+                    progress_tracker.complete_file(file.size_on_disk(), Some(output_path));
+                }
+                TaskOutcome::Skipped => {
+                    progress_tracker.send_event(OperationEvent::FileSkipped {
+                        index,
+                        name: file.name.clone(),
+                    });
+                    progress_tracker.complete_file(file.size_on_disk(), None);
+                }
+                TaskOutcome::Cancelled => {}
+                TaskOutcome::Failed(e) => {
+                    progress_tracker.send_event(OperationEvent::FileFailed {
+                        index,
+                        name: file.name.clone(),
+                        error: e.clone(),
+                    });
+                    progress_tracker.fail_file();
+                    failed += 1;
+                    // 校验或设置为跳过失败的文件时继续处理其余文件，结束后汇总；否则记录第一个错误，并让排队中的任务尽快退出
+                    if settings.operation_mode != OperationMode::Verify && settings.error_policy == ErrorPolicy::StopOnError {
+                        first_error.get_or_insert(e);
+                        should_stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        if failed > 0 && settings.operation_mode == OperationMode::Verify {
+            first_error.get_or_insert_with(|| format!("{} of {} files failed verification", failed, files.len()));
+        }
+        if timed_out > 0 {
+            first_error.get_or_insert_with(|| format!("{} files did not finish within {} seconds", timed_out, settings.file_timeout_secs));
         }
 
-        // 等待所有任务完成
-        drop(tx); // 关闭发送端
-        for _ in 0..pending_tasks {
-            match rx.recv() {
-                Ok((index, result)) => {
-                    // 检查是否应该停止
-                    if should_stop.load(std::sync::atomic::Ordering::Relaxed) {
-                        *status.lock().unwrap() = OperationStatus::Cancelled;
-                        return Err("Operation cancelled".to_string());
-                    }
+        // 无论成功与否，都要记录已完成文件的随机文件名和增量状态，避免原始文件名丢失
+        if let Err(e) = Self::record_completed(settings, &completed) {
+            first_error.get_or_insert(e);
+        }
 
-                    // 处理结果
-                    if let Err(e) = result {
-                        *status.lock().unwrap() = OperationStatus::Failed(e.clone());
-                        return Err(e);
-                    }
+        let mut final_status = match first_error {
+            Some(e) => OperationStatus::Failed(e),
+            None if should_stop.load(std::sync::atomic::Ordering::Relaxed) => OperationStatus::Cancelled,
+            None => OperationStatus::Completed,
+        };
 
-                    // 完成文件处理
-                    if let Some(file) = files.get(index) {
-                        let file_size = fs::metadata(&file.path)
-                            .map(|m| m.len())
-                            .unwrap_or(0);
-                        progress_tracker.complete_file(file_size);
-                    }
+        // 隔离的源文件：成功时删除（或交给调用方），否则恢复
+        match Self::settle_quarantine(settings, final_status == OperationStatus::Completed) {
+            Ok(Some(quarantine)) => progress_tracker.send_event(OperationEvent::SourcesQuarantined(quarantine)),
+            Ok(None) => {}
+            Err(e) => {
+                if final_status == OperationStatus::Completed {
+                    final_status = OperationStatus::Failed(e);
                 }
-                Err(_) => {
-                    *status.lock().unwrap() = OperationStatus::Failed("Failed to receive result from thread pool".to_string());
-                    return Err("Failed to receive result from thread pool".to_string());
+            }
+        }
+        // 跳过失败的文件继续处理时，其余文件照常完成（失败文件的源文件不会被隔离），整个批处理仍报告失败
+        if failed > 0 && final_status == OperationStatus::Completed {
+            final_status = OperationStatus::Failed(format!("{} of {} files failed", failed, files.len()));
+        }
+
+        // 失败或取消的批处理同样写出报告；报告写入失败时操作视为失败
+        if settings.write_report {
+            let report = OperationReport {
+                started: started.to_rfc3339(),
+                duration_secs: started_at.elapsed().as_secs_f64(),
+                mode: settings.operation_mode.clone(),
+                algorithm: settings.encryption_algorithm.clone(),
+                settings_fingerprint: settings_fingerprint(settings),
+                status: final_status.clone(),
+                files: files.iter().zip(report_entries)
+                    .map(|(file, entry)| entry.unwrap_or_else(|| FileReport::not_processed(file)))
+                    .collect(),
+            };
+            match report.write(settings) {
+                Ok(path) => progress_tracker.send_event(OperationEvent::ReportWritten(path)),
+                Err(e) => {
+                    if final_status == OperationStatus::Completed {
+                        final_status = OperationStatus::Failed(format!("Failed to write operation report: {}", e));
+                    }
                 }
             }
         }
 
-        // 操作完成
-        *status.lock().unwrap() = OperationStatus::Completed;
-        Ok(())
+        // 批处理已经结束（包括失败和取消），不再需要继续
+        if let Some(journal) = &settings.journal {
+            let _ = journal.finish();
+        }
+
+        *status.lock().unwrap() = final_status.clone();
+        match final_status {
+            OperationStatus::Failed(e) => Err(e),
+            OperationStatus::Cancelled => Err("Operation cancelled".to_string()),
+            _ => Ok(()),
+        }
     }
 
 
     
-    /// 加密单个文件
-    fn encrypt_file(settings: &Settings, file: &FileItem) -> Result<(), String> {
-        let input_path = &file.path;
-        let output_path = Self::generate_output_path(settings, file, true)?;
-        
-        // 打开输入文件
-        let input_file = File::open(input_path)
-            .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?;
-        let mut reader = BufReader::new(input_file);
+    /// 超时的任务及其错误信息：整个批处理超时时为所有未完成的任务，否则为处理时间超过单个文件超时时间的任务
+    fn expired_tasks(settings: &Settings, files: &[FileItem], tasks: &HashMap<usize, InFlightTask>, batch_expired: bool) -> Vec<(usize, String)> {
+        if batch_expired {
+            return tasks.keys()
+                .map(|&index| (index, format!(
+                    "'{}' was cancelled because the batch did not finish within {} minutes",
+                    files[index].name, settings.batch_timeout_mins
+                )))
+                .collect();
+        }
+        if settings.file_timeout_secs == 0 {
+            return Vec::new();
+        }
+        let limit = Duration::from_secs(settings.file_timeout_secs as u64);
+        tasks.iter()
+            .filter(|(_, task)| task.started.get().is_some_and(|started| started.elapsed() > limit))
+            .map(|(&index, _)| (index, format!(
+                "'{}' did not finish within {} seconds and was cancelled",
+                files[index].name, settings.file_timeout_secs
+            )))
+            .collect()
+    }
 
-        // 创建输出文件
-        let output_file = File::create(&output_path)
-            .map_err(|e| format!("Failed to create output file: {}", e))?;
-        let mut writer = BufWriter::new(output_file);
+    /// 根据设置创建本次操作的缓冲区预算，并返回每个任务需要租用的字节数
+    fn buffer_budget(settings: &Settings) -> (Arc<BufferBudget>, usize) {
+        let chunk_size = create_crypto_provider(&settings.encryption_algorithm).chunk_size();
+        let budget = BufferBudget::new(settings.memory_budget_mb as usize * 1024 * 1024);
+        (budget, BufferBudget::task_estimate(chunk_size))
+    }
 
-        // 使用策略模式进行加密
-        let crypto_provider = create_crypto_provider(&settings.encryption_algorithm);
-        crypto_provider.encrypt_stream(&settings.password, &mut reader, &mut writer)
-            .map_err(|e| format!("Failed to encrypt file '{}': {}", file.name, e))?;
+    /// 启用分块并行时每批处理的数据块数
+    fn chunk_batch_size(settings: &Settings) -> Option<usize> {
+        settings.parallel_chunks.then(|| {
+            let chunk_size = create_crypto_provider(&settings.encryption_algorithm).chunk_size();
+            parallel::batch_size(chunk_size, settings.memory_budget_mb as usize * 1024 * 1024)
+        })
+    }
+
+    /// 处理单个文件，返回输出文件路径
+    fn process_file(settings: &Settings, file: &FileItem) -> Result<PathBuf, String> {
+        // 文件单独指定了算法时，使用该算法创建提供者
+        let overridden;
+        let settings = match &file.algorithm {
+            Some(algorithm) if *algorithm != settings.encryption_algorithm => {
+                overridden = Settings {
+                    encryption_algorithm: algorithm.clone(),
+                    ..settings.clone()
+                };
+                &overridden
+            }
+            _ => settings,
+        };
+
+        match settings.operation_mode {
+            OperationMode::Encrypt => Self::encrypt_file(settings, file),
+            OperationMode::Decrypt => Self::decrypt_file(settings, file),
+            OperationMode::Verify => Self::verify_item(settings, file),
+            OperationMode::Archive => Err("Archive mode packs all files into one archive".to_string()),
+            OperationMode::Container => Err("Container mode packs all files into one container".to_string()),
+        }
+    }
+
+    /// 记录已完成文件的输出信息（文件名索引和增量状态）
+    fn record_completed(settings: &Settings, completed: &[(PathBuf, FileItem)]) -> Result<(), String> {
+        Self::record_name_index(settings, completed)?;
+        Self::record_incremental_state(settings, completed)
+    }
+
+    /// 增量模式下加载状态数据库，未启用时返回None
+    fn load_incremental_state(settings: &Settings) -> Result<Option<IncrementalState>, String> {
+        if settings.operation_mode != OperationMode::Encrypt || !settings.incremental {
+            return Ok(None);
+        }
+
+        IncrementalState::load_default()
+            .map(Some)
+            .map_err(|e| format!("Failed to load incremental state: {}", e))
+    }
+
+    /// 增量模式下记录成功加密的源文件指纹
+    fn record_incremental_state(settings: &Settings, completed: &[(PathBuf, FileItem)]) -> Result<(), String> {
+        // 删除源文件时无需追踪，下次不会再出现同一源文件
+        if settings.delete_source || completed.is_empty() {
+            return Ok(());
+        }
+        let Some(mut state) = Self::load_incremental_state(settings)? else {
+            return Ok(());
+        };
+
+        for (output_path, file) in completed {
+            state.update(&file.path, output_path)
+                .map_err(|e| format!("Failed to update incremental state for '{}': {}", file.name, e))?;
+        }
+        state.save()
+            .map_err(|e| format!("Failed to save incremental state: {}", e))
+    }
+
+    /// 将本次生成的随机文件名写入各输出目录的加密文件名索引
+    fn record_name_index(settings: &Settings, completed: &[(PathBuf, FileItem)]) -> Result<(), String> {
+        if settings.operation_mode != OperationMode::Encrypt || !settings.encrypt_filename {
+            return Ok(());
+        }
+        // 索引本身用密码加密，使用外部密钥管理服务且没有填写密码时无法保存
+        if settings.password.is_empty() {
+            return Err("File names were randomized but no password is set to protect the filename index, so original names were not recorded".to_string());
+        }
+
+        let extension_with_dot = format!(".{}", settings.file_extension);
+        let mut by_directory: HashMap<PathBuf, Vec<(String, String)>> = HashMap::new();
+        for (output_path, file) in completed {
+            let Some(output_name) = output_path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let random_name = output_name.trim_end_matches(&extension_with_dot).to_string();
+            let directory = output_path.parent().map(PathBuf::from).unwrap_or_default();
+            by_directory.entry(directory).or_default().push((random_name, file.name.clone()));
+        }
+
+        for (directory, entries) in by_directory {
+            FilenameIndex::merge_into_directory(&directory, &settings.encryption_algorithm, &settings.password, entries)
+                .map_err(|e| format!("Failed to update filename index in '{}': {}", directory.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    /// 加密单个文件
+    fn encrypt_file(settings: &Settings, file: &FileItem) -> Result<PathBuf, String> {
+        let output_path = Self::generate_output_path(settings, file, true)?;
+        let plaintext_digest = Self::encrypt_to(settings, file, &output_path)?;
+        if let Some(digest) = plaintext_digest {
+            Self::verify_output(settings, file, &output_path, digest)?;
+        }
+        checksum::write_sidecar(&output_path, settings.checksum_sidecar)
+            .map_err(|e| format!("Failed to write the checksum file for '{}': {}", file.name, e))?;
+        recovery::write_recovery(&output_path, settings.recovery_level)
+            .map_err(|e| format!("Failed to write the recovery data for '{}': {}", file.name, e))?;
 
         // 如果设置删除源文件
         if settings.delete_source {
-            fs::remove_file(input_path)
-                .map_err(|e| format!("Failed to delete source file: {}", e))?;
+            Self::remove_source(settings, &file.path)?;
         }
         
-        Ok(())
+        Ok(output_path)
+    }
+
+    /// 重新打开刚写出的加密文件，完整解密并与加密时读取的明文摘要比较。
+    /// 不一致或无法解密时删除该输出并返回错误，源文件保持不动
+    fn verify_output(settings: &Settings, file: &FileItem, output_path: &Path, plaintext_digest: blake3::Hash) -> Result<(), String> {
+        // 文件内的进度已在加密时走完，校验时不再重新报告
+        let settings = &Settings { stream_control: settings.stream_control.without_progress(), ..settings.clone() };
+        let result = Self::decrypt_with(settings, &Self::path_item(output_path), |_, _| Ok(Digested::new(io::sink(), true)))
+            .and_then(|sink| match sink.digest() == Some(plaintext_digest) {
+                true => Ok(()),
+                false => Err("the decrypted data does not match the source".to_string()),
+            });
+        result.map_err(|e| {
+            let _ = fs::remove_file(output_path);
+            format!("Verification of the encrypted output for '{}' failed, the output was removed and the source kept: {}", file.name, e)
+        })
+    }
+
+    /// 把文件加密到指定的输出路径；设置了加密后校验时返回读取的明文的摘要
+    fn encrypt_to(settings: &Settings, file: &FileItem, output_path: &Path) -> Result<Option<blake3::Hash>, String> {
+        let input_path = &file.path;
+        
+        // 打开输入文件，设置了预读时由后台线程读取下一块
+        let reader: Box<dyn Read + Send> = if settings.direct_io {
+            Box::new(DirectReader::open(input_path)
+                .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?)
+        } else if Self::use_mapped_io(settings, file.size_on_disk()) {
+            Box::new(MappedReader::open(input_path)
+                .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?)
+        } else {
+            let input_file = File::open(input_path)
+                .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?;
+            Box::new(BufReader::new(input_file))
+        };
+        let reader: Box<dyn Read> = match settings.read_ahead {
+            true => Box::new(ReadAhead::new(reader, format::DEFAULT_CHUNK_SIZE)),
+            false => reader,
+        };
+        let mut reader = Digested::new(reader, settings.verify_after_encrypt);
+        if settings.encryption_algorithm.is_interop() {
+            Self::encrypt_interop(settings, file, &mut reader, output_path)?;
+            return Ok(reader.digest());
+        }
+
+        // 创建输出文件并预分配空间（失败时自动删除不完整的输出）
+        // 使用外部密钥管理服务时为每个文件生成新的数据密钥
+        let (password, wrapped_key) = kms::encryption_key(settings.key_source, &settings.kms, &settings.password)
+            .map_err(|e| format!("Failed to get a key for '{}': {}", file.name, e))?;
+        // 使用密钥文件时先混入密钥文件，再混入硬件令牌对随机挑战的响应
+        let (password, keyfile_check) = keyfile::bind_password(settings.keyfile.as_deref(), &password)
+            .map_err(|e| format!("Failed to get a key for '{}': {}", file.name, e))?;
+        let (password, token_challenge) = token::bind_password(settings.hardware_token.slot(), &password)
+            .map_err(|e| format!("Failed to get a key for '{}': {}", file.name, e))?;
+
+        // 有批处理主密钥时从主密钥派生文件密钥（插件算法仍自行派生）
+        let batch = settings.batch_master_key.as_ref().and_then(|key| {
+            create_crypto_provider_with_batch_key(&settings.encryption_algorithm, key).map(|provider| (key, provider))
+        });
+        let (batch_salt, session_key, crypto_provider) = match batch {
+            Some((key, provider)) => (Some(key.salt), key.wrapped_session_key, provider),
+            None => (None, None, create_crypto_provider_with_kdf(&settings.encryption_algorithm, &settings.kdf)),
+        };
+        // 级联加密时外层用另一种内置算法，以同样的方式独立派生密钥
+        let cascade = match settings.cascade {
+            true => Some(Cascade::for_algorithm(&settings.encryption_algorithm)
+                .ok_or_else(|| "Cascade encryption needs a built-in algorithm".to_string())?),
+            false => None,
+        };
+        let outer_provider = cascade.as_ref().map(|cascade| {
+            settings.batch_master_key.as_ref()
+                .and_then(|key| create_crypto_provider_with_batch_key(&cascade.outer, key))
+                .unwrap_or_else(|| create_crypto_provider_with_kdf(&cascade.outer, &settings.kdf))
+        });
+        // 加密文件名时原始文件名用同一提供者单独加密后写入文件头（批处理密钥下只多一次HKDF）
+        let encrypted_name = settings.encrypt_filename
+            .then(|| Self::encrypt_name(crypto_provider.as_ref(), &password, &file.name))
+            .transpose()
+            .map_err(|e| format!("Failed to encrypt the name of '{}': {}", file.name, e))?;
+        let plaintext_len = file.size_on_disk();
+        // 已经压缩过的文件类型不再压缩，文件头中只为压缩的文件记录压缩算法
+        let compression = (settings.compression != CompressionAlgorithm::None && compression::should_compress(&file.path))
+            .then_some(Compression { algorithm: settings.compression, plaintext_len });
+        // 压缩信息由内置算法的摘要块认证
+        let bound_digest = algorithm_code(&settings.encryption_algorithm).is_some();
+        if compression.is_some() && !bound_digest {
+            return Err("Compression needs a built-in algorithm".to_string());
+        }
+        // 压缩后的长度事先未知，按全部按原样存储的上限预分配
+        let data_len = match compression {
+            Some(_) => compression::max_compressed_size(plaintext_len),
+            None => plaintext_len,
+        };
+        // 文件头中的总长度描述写入磁盘的一层：级联加密时是外层加密的内层密文
+        let (stream_len, chunk_size, nonce_len) = match &outer_provider {
+            Some(outer) => (
+                format::encrypted_size(data_len, crypto_provider.chunk_size(), crypto_provider.nonce_len()),
+                outer.chunk_size(),
+                outer.nonce_len(),
+            ),
+            None => (data_len, crypto_provider.chunk_size(), crypto_provider.nonce_len()),
+        };
+        let header = FileHeader {
+            wrapped_key,
+            token_challenge,
+            keyfile_check,
+            kdf_params: (!settings.kdf.is_default()).then_some(settings.kdf),
+            batch_salt,
+            session_key,
+            cascade,
+            algorithm: Some(settings.encryption_algorithm.clone()),
+            totals: compression.is_none().then(|| StreamTotals::for_plaintext(stream_len, chunk_size)),
+            compression,
+            encrypted_name,
+            // 内置算法的摘要块绑定文件头，插件算法自行处理数据流
+            bound_digest,
+            ..FileHeader::with_hint(&settings.password_hint)
+        };
+        let binding = header.digest_binding();
+        let control = settings.stream_control.clone().with_binding(binding.clone());
+        let expected_len = header.encoded_len() + format::encrypted_size(stream_len, chunk_size, nonce_len);
+        let mut writer = Self::create_output(settings, file, output_path, expected_len)
+            .map_err(|e| Self::create_output_error(file, expected_len, e))?;
+
+        // 在盐值前写入文件头：明文总长度和块数，以及可选的密码提示、包装密钥、硬件令牌挑战和Argon2参数
+        header.write_to(&mut writer)
+            .map_err(|e| Self::write_output_error(file, e))?;
+        let mut reader = Counted::new(reader);
+        // 压缩的文件由摘要绑定累计压缩前的明文
+        let mut source: Box<dyn Read + '_> = match compression {
+            Some(compression) => Box::new(CompressReader::new(PlaintextDigest::new(&mut reader, binding.as_ref()), compression.algorithm)),
+            None => Box::new(&mut reader),
+        };
+
+        // 使用策略模式进行加密
+        let result = match (&outer_provider, Self::chunk_batch_size(settings)) {
+            (Some(outer), batch_size) => cascade::encrypt_stream(
+                crypto_provider.as_ref(), outer.as_ref(), &password, &mut source, &mut writer, batch_size, &control,
+            ),
+            (None, Some(batch_size)) => parallel::encrypt_stream(
+                crypto_provider.as_ref(), &password, &mut source, &mut writer, batch_size, &control,
+            ),
+            (None, None) => crypto_provider.encrypt_stream(&password, &mut source, &mut writer, &control),
+        };
+        drop(source);
+        result
+            .map_err(|e| match e {
+                CryptoError::IoError(e) => Self::write_output_error(file, e),
+                e => format!("Failed to encrypt file '{}': {}", file.name, e),
+            })?;
+        // 文件在加密过程中被修改时，文件头中的总长度与实际内容不符
+        if reader.count() != plaintext_len {
+            return Err(format!(
+                "File '{}' changed while it was being encrypted ({} bytes expected, {} read)",
+                file.name, plaintext_len, reader.count()
+            ));
+        }
+        writer.finish()
+            .map_err(|e| Self::write_output_error(file, e))?;
+        Ok(reader.into_inner().digest())
     }
     
+    /// 加密为互通格式（age、OpenSSL）：没有Krypton文件头，整个文件由对应的提供者写出
+    fn encrypt_interop(settings: &Settings, file: &FileItem, reader: &mut dyn Read, output_path: &Path) -> Result<(), String> {
+        let plaintext_len = file.size_on_disk();
+        let expected_len = interop::encrypted_size(&settings.encryption_algorithm, plaintext_len);
+        let mut writer = Self::create_output(settings, file, output_path, expected_len)
+            .map_err(|e| Self::create_output_error(file, expected_len, e))?;
+        let mut reader = Counted::new(reader);
+        create_crypto_provider_with_kdf(&settings.encryption_algorithm, &settings.kdf)
+            .encrypt_stream(&settings.password, &mut reader, &mut writer, &settings.stream_control)
+            .map_err(|e| match e {
+                CryptoError::IoError(e) => Self::write_output_error(file, e),
+                e => format!("Failed to encrypt file '{}': {}", file.name, e),
+            })?;
+        if reader.count() != plaintext_len {
+            return Err(format!(
+                "File '{}' changed while it was being encrypted ({} bytes expected, {} read)",
+                file.name, plaintext_len, reader.count()
+            ));
+        }
+        writer.finish()
+            .map_err(|e| Self::write_output_error(file, e))?;
+        Ok(())
+    }
+
     /// 解密单个文件
-    fn decrypt_file(settings: &Settings, file: &FileItem) -> Result<(), String> {
-        let input_path = &file.path;
+    fn decrypt_file(settings: &Settings, file: &FileItem) -> Result<PathBuf, String> {
+        // 输入文件旁有恢复文件时先修复损坏的数据块，再核对校验和附属文件，传输中损坏的文件不必尝试解密
+        for path in file.input_paths() {
+            recovery::repair(&path)
+                .map_err(|e| format!("Failed to repair '{}': {}", file.name, e))?;
+            checksum::verify_sidecars(&path)
+                .map_err(|e| format!("Failed to verify '{}': {}", file.name, e))?;
+        }
         let output_path = Self::generate_output_path(settings, file, false)?;
+        let output_path = Self::decrypt_to(settings, file, &output_path, true)?;
+
+        // 如果设置删除源文件（多分卷时删除所有分卷），附属文件一并删除
+        if settings.delete_source {
+            for path in file.input_paths() {
+                Self::remove_source(settings, &path)?;
+                for (_, sidecar) in checksum::existing_sidecars(&path) {
+                    Self::remove_source(settings, &sidecar)?;
+                }
+                if let Some(recovery) = recovery::existing_recovery(&path) {
+                    Self::remove_source(settings, &recovery)?;
+                }
+            }
+        }
 
-        // 打开输入文件
-        let input_file = File::open(input_path)
-            .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?;
-        let mut reader = BufReader::new(input_file);
+        Ok(output_path)
+    }
 
-        // 创建输出文件
-        let output_file = File::create(&output_path)
-            .map_err(|e| format!("Failed to create output file: {}", e))?;
-        let mut writer = BufWriter::new(output_file);
+    /// 校验单个文件：核对校验和附属文件，再完整解密一遍并丢弃明文（包括核对整个文件的明文摘要），
+    /// 不修复也不写出任何文件。返回校验的文件路径
+    fn verify_item(settings: &Settings, file: &FileItem) -> Result<PathBuf, String> {
+        for path in file.input_paths() {
+            checksum::verify_sidecars(&path)
+                .map_err(|e| format!("Failed to verify '{}': {}", file.name, e))?;
+        }
+        Self::decrypt_with(settings, file, |_, _| Ok(io::sink()))?;
+        Ok(file.path.clone())
+    }
+
+    /// 把文件解密到指定的输出路径，返回实际写出的路径。
+    /// `restore_name` 为true且文件头中记录了原始文件名时，改为输出到同一目录下的原始文件名
+    fn decrypt_to(settings: &Settings, file: &FileItem, output_path: &Path, restore_name: bool) -> Result<PathBuf, String> {
+        let mut path = output_path.to_path_buf();
+        let writer = Self::decrypt_with(settings, file, |expected_len, original_name| {
+            if let Some(name) = original_name.filter(|_| restore_name) {
+                path = Self::restored_output_path(settings, file, output_path, name)?;
+            }
+            Self::create_output(settings, file, &path, expected_len)
+                .map_err(|e| Self::create_output_error(file, expected_len, e))
+        })?;
+        writer.finish()
+            .map_err(|e| Self::write_output_error(file, e))?;
+        Ok(path)
+    }
+
+    /// 恢复原始文件名后的输出路径：与按加密文件名生成的路径在同一目录，已存在时按冲突策略处理
+    fn restored_output_path(settings: &Settings, file: &FileItem, output_path: &Path, original_name: &str) -> Result<PathBuf, String> {
+        let path = output_path.with_file_name(original_name);
+        match Self::conflict_policy(settings, file) {
+            ConflictPolicy::KeepBoth => Ok(unique_path(&path)),
+            ConflictPolicy::Skip if path.exists() => Err(format!("Output '{}' for '{}' already exists", path.display(), file.name)),
+            _ => Ok(path),
+        }
+    }
+
+    /// 用文件的提供者单独加密原始文件名，写入文件头
+    fn encrypt_name(provider: &dyn CryptoProvider, password: &str, name: &str) -> CryptoResult<Vec<u8>> {
+        if name.len() > MAX_ORIGINAL_NAME_LEN {
+            return Err(CryptoError::EncryptionError(format!("文件名过长: {} 字节", name.len())));
+        }
+        let mut encrypted = Vec::new();
+        provider.encrypt_stream(password, &mut name.as_bytes(), &mut encrypted, &StreamControl::default())?;
+        Ok(encrypted)
+    }
 
-        // 使用策略模式进行解密
-        let crypto_provider = create_crypto_provider(&settings.encryption_algorithm);
-        crypto_provider.decrypt_stream(&settings.password, &mut reader, &mut writer)
+    /// 解密文件头中的原始文件名，只接受单个普通文件名（不含路径分隔符和 `..`），否则返回None
+    fn decrypt_name(provider: &dyn CryptoProvider, password: &str, encrypted: &[u8]) -> CryptoResult<Option<String>> {
+        let mut name = Vec::new();
+        provider.decrypt_stream(password, &mut &encrypted[..], &mut name, &StreamControl::default())?;
+        Ok(String::from_utf8(name).ok().filter(|name| {
+            let mut components = Path::new(name).components();
+            matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        }))
+    }
+
+    /// 解密文件并写入 `create_output` 按预计的明文长度（和文件头中的原始文件名）创建的输出，返回该输出
+    fn decrypt_with<W: Write + Send>(
+        settings: &Settings,
+        file: &FileItem,
+        create_output: impl FnOnce(u64, Option<&str>) -> Result<W, String>,
+    ) -> Result<W, String> {
+        // 打开输入文件（多分卷文件按顺序拼接各分卷）
+        let input: Box<dyn Read> = if settings.direct_io && !file.is_multi_volume() {
+            Box::new(DirectReader::open(&file.path)
+                .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?)
+        } else if Self::use_mapped_io(settings, file.size_on_disk()) && !file.is_multi_volume() {
+            Box::new(MappedReader::open(&file.path)
+                .map_err(|e| format!("Failed to open file '{}': {}", file.name, e))?)
+        } else {
+            open_file_item(file)?
+        };
+        // 互通格式按文件开头识别，与设置中的算法无关（OpenSSL文件的迭代次数取自设置中的PBKDF2参数）
+        let (interop_algorithm, mut input) = interop::detect(input)
+            .map_err(|e| format!("Failed to read file '{}': {}", file.name, e))?;
+        if let Some(algorithm) = interop_algorithm {
+            let mut writer = create_output(interop::max_plaintext_size(&algorithm, file.size_on_disk()), None)?;
+            create_crypto_provider_with_kdf(&algorithm, &settings.kdf).decrypt_stream(&settings.password, &mut input, &mut writer, &settings.stream_control)
+                .map_err(|e| match e {
+                    CryptoError::IoError(e) if is_disk_full(&e) => Self::write_output_error(file, e),
+                    e => format!("Failed to decrypt file '{}': {}", file.name, e),
+                })?;
+            return Ok(writer);
+        }
+
+        // 跳过可选的文件头（带密码提示的文件），其中有包装密钥时交给密钥管理服务解包
+        let (header, mut reader) = FileHeader::read_from(&mut input)
             .map_err(|e| format!("Failed to decrypt file '{}': {}", file.name, e))?;
+        let header = header.unwrap_or_default();
+        let binding = header.digest_binding();
+        let control = settings.stream_control.clone().with_binding(binding.clone());
+        let password = kms::decryption_password(settings.key_source, &settings.kms, &settings.password, header.wrapped_key.as_ref())
+            .and_then(|password| keyfile::unlock_password(settings.keyfile.as_deref(), &password, header.keyfile_check.as_ref()))
+            .and_then(|password| token::unlock_password(&password, header.token_challenge.as_ref()))
+            .map_err(|e| format!("Failed to get the key for '{}': {}", file.name, e))?;
 
-        // 如果设置删除源文件
-        if settings.delete_source {
-            fs::remove_file(input_path)
-                .map_err(|e| format!("Failed to delete source file: {}", e))?;
+        // 创建输出文件并预分配空间（失败时自动删除不完整的输出）
+        // 使用文件头中记录的算法（级联加密时为两层算法），旧文件使用设置中的算法
+        let algorithm = header.data_algorithm(&settings.encryption_algorithm);
+        let crypto_provider = create_decryption_provider(algorithm, Some(&header), &password)
+            .map_err(|e| format!("Failed to get the key for '{}': {}", file.name, e))?;
+        let outer_provider = header.cascade.as_ref()
+            .map(|cascade| create_decryption_provider(&cascade.outer, Some(&header), &password))
+            .transpose()
+            .map_err(|e| format!("Failed to get the key for '{}': {}", file.name, e))?;
+        let chunk_size = outer_provider.as_ref().unwrap_or(&crypto_provider).chunk_size();
+        if let Some(totals) = header.totals.filter(|totals| !totals.is_consistent(chunk_size)) {
+            return Err(format!(
+                "Failed to decrypt file '{}': the header records {} chunks for {} bytes, check the algorithm",
+                file.name, totals.chunks, totals.plaintext_len
+            ));
         }
+        let expected_len = Self::expected_plaintext_len(&header, crypto_provider.as_ref(), outer_provider.as_deref(), file.size_on_disk());
+        let original_name = header.encrypted_name.as_deref()
+            .map(|encrypted| Self::decrypt_name(crypto_provider.as_ref(), &password, encrypted))
+            .transpose()
+            .map_err(|e| format!("Failed to decrypt file '{}': {}", file.name, e))?
+            .flatten();
+        let mut writer = create_output(expected_len, original_name.as_deref())?;
 
-        Ok(())
+        // 使用策略模式进行解密，压缩的文件边解密边解压，由摘要绑定累计解压后的明文
+        let mut decompress = None;
+        let sink: &mut (dyn Write + Send) = match header.compression {
+            Some(compression) => decompress.insert(DecompressWriter::new(PlaintextDigest::new(&mut writer, binding.as_ref()), compression.algorithm)),
+            None => &mut writer,
+        };
+        let mut counted = Counted::new(sink);
+        let result = match (&outer_provider, Self::chunk_batch_size(settings)) {
+            // 级联加密时与文件头比较的是外层解出的内层密文长度
+            (Some(outer), batch_size) => cascade::decrypt_stream(
+                crypto_provider.as_ref(), outer.as_ref(), &password, &mut reader, &mut counted, batch_size, &control,
+            ),
+            (None, Some(batch_size)) => parallel::decrypt_stream(
+                crypto_provider.as_ref(), &password, &mut reader, &mut counted, batch_size, &control,
+            ).map(|_| counted.count()),
+            (None, None) => crypto_provider.decrypt_stream(&password, &mut reader, &mut counted, &control)
+                .map(|_| counted.count()),
+        };
+        let written = result
+            .map_err(|e| match e {
+                CryptoError::IoError(e) if is_disk_full(&e) => Self::write_output_error(file, e),
+                e if file.is_multi_volume() => format!(
+                    "Failed to decrypt volume set '{}' ({} volumes, the set may be missing trailing volumes): {}",
+                    file.name, file.volumes.len(), e
+                ),
+                e => format!("Failed to decrypt file '{}': {}", file.name, e),
+            })?;
+        // 数据流本身以摘要块结束，旧格式的文件只能通过文件头中的总长度发现整块缺失
+        if let Some(totals) = header.totals.filter(|totals| totals.plaintext_len != written) {
+            return Err(format!(
+                "Failed to decrypt file '{}': expected {} bytes but the encrypted data holds {}, the file is truncated",
+                file.name, totals.plaintext_len, written
+            ));
+        }
+        if let (Some(decompress), Some(compression)) = (decompress, header.compression) {
+            let decompressed = decompress.finish()
+                .map_err(|e| format!("Failed to decompress file '{}': {}", file.name, e))?;
+            if decompressed != compression.plaintext_len {
+                return Err(format!(
+                    "Failed to decompress file '{}': expected {} bytes but got {}",
+                    file.name, compression.plaintext_len, decompressed
+                ));
+            }
+        }
+        // 压缩的文件解压完成后才能核对最终明文和文件头
+        if let Some(binding) = &binding {
+            binding.verify()
+                .map_err(|e| format!("Failed to decrypt file '{}': {}", file.name, e))?;
+        }
+        Ok(writer)
     }
 
 
     
-    /// 生成输出文件路径
+    /// 按文件头得到的明文长度：优先使用记录的压缩前长度和总长度，没有记录时按文件大小估计上限
+    fn expected_plaintext_len(header: &FileHeader, inner: &dyn CryptoProvider, outer: Option<&dyn CryptoProvider>, file_len: u64) -> u64 {
+        if let Some(compression) = header.compression {
+            return compression.plaintext_len;
+        }
+        let disk_layer = outer.unwrap_or(inner);
+        let expected_len = header.totals.map_or_else(
+            || format::max_plaintext_size(file_len, disk_layer.chunk_size(), disk_layer.nonce_len()),
+            |totals| totals.plaintext_len,
+        );
+        match outer {
+            Some(_) => format::max_plaintext_size(expected_len, inner.chunk_size(), inner.nonce_len()),
+            None => expected_len,
+        }
+    }
+
+    /// 创建输出文件并按预计大小预分配空间，启用直接I/O时绕过页缓存写入。
+    /// 可恢复的批处理在日志中记录输出的临时文件，中断后继续时删除不完整的输出
+    fn create_output(settings: &Settings, file: &FileItem, path: &Path, expected_len: u64) -> std::io::Result<OutputFile> {
+        if let Some(journal) = &settings.journal {
+            journal.output_created(&file.path, &OutputFile::partial_path(path));
+        }
+        if settings.direct_io {
+            OutputFile::create_direct(path, expected_len)
+        } else if Self::use_mapped_io(settings, expected_len) {
+            OutputFile::create_mapped(path, expected_len)
+        } else {
+            OutputFile::create(path, expected_len)
+        }
+    }
+
+    /// 是否用内存映射读写这么大的文件（直接I/O优先）
+    fn use_mapped_io(settings: &Settings, len: u64) -> bool {
+        settings.mapped_io && !settings.direct_io && len >= MIN_MAPPED_SIZE
+    }
+
+    /// 创建输出文件失败时的错误信息
+    fn create_output_error(file: &FileItem, expected_len: u64, error: std::io::Error) -> String {
+        if is_disk_full(&error) {
+            format!(
+                "Not enough disk space to write output for '{}' (needs {})",
+                file.name,
+                ProgressFormatter::format_bytes(expected_len)
+            )
+        } else {
+            format!("Failed to create output file for '{}': {}", file.name, error)
+        }
+    }
+
+    /// 写入输出文件失败时的错误信息（不完整的输出已被删除）
+    fn write_output_error(file: &FileItem, error: std::io::Error) -> String {
+        if is_disk_full(&error) {
+            format!("Disk full while writing output for '{}'; the partial output was removed", file.name)
+        } else {
+            format!("Failed to write output for '{}': {}", file.name, error)
+        }
+    }
+
+    /// 删除源文件（设置了覆写时先覆写再删除）；批处理有隔离区时先移入隔离区
+    fn remove_source(settings: &Settings, path: &Path) -> Result<(), String> {
+        if let Some(quarantine) = &settings.quarantine {
+            return quarantine.move_in(path)
+                .map_err(|e| format!("Failed to move source file to quarantine: {}", e));
+        }
+        let result = if settings.shred_source {
+            shred_file(path, settings.shred_passes)
+        } else {
+            fs::remove_file(path)
+        };
+        result.map_err(|e| format!("Failed to delete source file: {}", e))
+    }
+
+    /// 文件的输出路径，加密文件名时为None（随机文件名每次都不同，不会与已有文件冲突）
+    pub fn planned_output_path(settings: &Settings, file: &FileItem) -> Option<PathBuf> {
+        match settings.operation_mode {
+            OperationMode::Encrypt => (!settings.encrypt_filename).then(|| Self::output_path(settings, file, true)),
+            OperationMode::Decrypt => Some(Self::output_path(settings, file, false)),
+            // 校验不写出文件，归档的所有文件写入同一个归档
+            OperationMode::Verify | OperationMode::Archive | OperationMode::Container => None,
+        }
+    }
+
+    /// 文件输出所在的目录（加密文件名时文件名未定，但目录已经确定），校验、归档和容器为None
+    pub fn planned_output_directory(settings: &Settings, file: &FileItem) -> Option<PathBuf> {
+        let is_encrypt = match settings.operation_mode {
+            OperationMode::Encrypt => true,
+            OperationMode::Decrypt => false,
+            OperationMode::Verify | OperationMode::Archive | OperationMode::Container => return None,
+        };
+        Self::output_path(settings, file, is_encrypt).parent().map(Path::to_path_buf)
+    }
+
+    /// 文件输出大小的估计，不读取也不写出文件内容
+    ///
+    /// 加密时按各层加密和文件头的开销计算（压缩时按不压缩的上限），解密时按文件头中记录的明文长度，
+    /// 归档和容器按源文件大小，校验不写出文件
+    pub fn estimated_output_size(settings: &Settings, file: &FileItem) -> u64 {
+        let size = file.size_on_disk();
+        match settings.operation_mode {
+            OperationMode::Encrypt => Self::estimated_encrypted_size(settings, file),
+            OperationMode::Decrypt => Self::estimated_plaintext_size(settings, file).unwrap_or(size),
+            OperationMode::Archive | OperationMode::Container => size,
+            OperationMode::Verify => 0,
+        }
+    }
+
+    fn estimated_encrypted_size(settings: &Settings, file: &FileItem) -> u64 {
+        let algorithm = &settings.encryption_algorithm;
+        let plaintext_len = file.size_on_disk();
+        if algorithm.is_interop() {
+            return interop::encrypted_size(algorithm, plaintext_len);
+        }
+        let data_len = match settings.compression != CompressionAlgorithm::None && compression::should_compress(&file.path) {
+            true => compression::max_compressed_size(plaintext_len),
+            false => plaintext_len,
+        };
+        let provider = create_crypto_provider(algorithm);
+        let mut len = format::encrypted_size(data_len, provider.chunk_size(), provider.nonce_len());
+        if let Some(cascade) = Cascade::for_algorithm(algorithm).filter(|_| settings.cascade) {
+            let outer = create_crypto_provider(&cascade.outer);
+            len = format::encrypted_size(len, outer.chunk_size(), outer.nonce_len());
+        }
+        // 文件头按密码提示和加密后的原始文件名估计，包装密钥等其余字段只有几十字节
+        let header = FileHeader {
+            encrypted_name: settings.encrypt_filename
+                .then(|| vec![0; format::encrypted_size(file.name.len() as u64, provider.chunk_size(), provider.nonce_len()) as usize]),
+            ..FileHeader::with_hint(&settings.password_hint)
+        };
+        header.encoded_len() + len
+    }
+
+    /// 读取文件头得到的明文长度，文件无法读取或不是加密文件时返回None
+    fn estimated_plaintext_size(settings: &Settings, file: &FileItem) -> Option<u64> {
+        let (interop_algorithm, mut input) = interop::detect(open_file_item(file).ok()?).ok()?;
+        if let Some(algorithm) = interop_algorithm {
+            return Some(interop::max_plaintext_size(&algorithm, file.size_on_disk()));
+        }
+        let (header, _) = FileHeader::read_from(&mut input).ok()?;
+        let header = header.unwrap_or_default();
+        let inner = create_crypto_provider(header.data_algorithm(&settings.encryption_algorithm));
+        let outer = header.cascade.as_ref().map(|cascade| create_crypto_provider(&cascade.outer));
+        Some(Self::expected_plaintext_len(&header, inner.as_ref(), outer.as_deref(), file.size_on_disk()))
+    }
+
+    /// 文件生效的输出冲突处理方式
+    pub fn conflict_policy(settings: &Settings, file: &FileItem) -> ConflictPolicy {
+        match file.conflict.unwrap_or(settings.conflict_policy) {
+            // 运行中无法再询问，没有在开始前决定的文件不覆盖
+            ConflictPolicy::Ask => ConflictPolicy::Skip,
+            policy => policy,
+        }
+    }
+
+    /// 输出文件已存在且设置为跳过
+    fn skips_existing_output(settings: &Settings, file: &FileItem) -> bool {
+        Self::conflict_policy(settings, file) == ConflictPolicy::Skip
+            && Self::planned_output_path(settings, file).is_some_and(|path| path.exists())
+    }
+
+    /// 创建输出目录（包括文件所在的子目录）并生成输出文件路径，输出已存在且设置为保留两者时换用带编号的文件名
     fn generate_output_path(settings: &Settings, file: &FileItem, is_encrypt: bool) -> Result<PathBuf, String> {
+        let output_path = Self::output_path(settings, file, is_encrypt);
+        if let Some(directory) = settings.output_directory.as_ref().and(output_path.parent()) {
+            fs::create_dir_all(directory)
+                .map_err(|e| format!("Failed to create output directory '{}': {}", directory.display(), e))?;
+        }
+        if Self::conflict_policy(settings, file) == ConflictPolicy::KeepBoth {
+            return Ok(unique_path(&output_path));
+        }
+        Ok(output_path)
+    }
+
+    /// 输出文件路径，设置了输出目录时在其中保留文件所在的子目录
+    fn output_path(settings: &Settings, file: &FileItem, is_encrypt: bool) -> PathBuf {
         let input_path = &file.path;
-        let mut output_path = input_path.clone();
+        let mut output_path = match &settings.output_directory {
+            Some(directory) => directory.join(file.subdirectory.as_deref().unwrap_or(Path::new(""))).join(&file.name),
+            None => input_path.clone(),
+        };
         
         if is_encrypt {
             // 加密：生成输出文件名
@@ -387,9 +1568,37 @@ impl CryptoEngine {
             }
         }
         
-        Ok(output_path)
+        output_path
     }
     
+    /// 并行审计一组加密文件，按输入顺序返回每个文件的报告
+    pub fn audit_files(
+        &self,
+        files: &[FileItem],
+        algorithm: &EncryptionAlgorithm,
+        password: Option<&str>,
+    ) -> Vec<AuditReport> {
+        let _batch_key_scope = BatchKey::cache_scope();
+        let (tx, rx) = mpsc::channel();
+
+        for (index, file) in files.iter().enumerate() {
+            let tx = tx.clone();
+            let file = file.clone();
+            let algorithm = algorithm.clone();
+            let password = password.map(str::to_string);
+
+            self.thread_pool.execute(move || {
+                let report = audit_file(&file, &algorithm, password.as_deref());
+                let _ = tx.send((index, report));
+            });
+        }
+
+        drop(tx);
+        let mut reports: Vec<(usize, AuditReport)> = rx.iter().collect();
+        reports.sort_by_key(|(index, _)| *index);
+        reports.into_iter().map(|(_, report)| report).collect()
+    }
+
     /// 获取加密算法信息
     pub fn get_algorithm_info(settings: &Settings) -> String {
         let provider = create_crypto_provider(&settings.encryption_algorithm);
@@ -398,17 +1607,50 @@ impl CryptoEngine {
                 provider.chunk_size() / (1024 * 1024))
     }
     
-    /// 验证密码是否正确（通过尝试读取加密文件头）
+    /// 验证密码是否正确：派生密钥后只解密第一个数据块，不读取文件的其余部分
     pub fn verify_password(settings: &Settings, file_path: &std::path::Path) -> CryptoResult<bool> {
-        let provider = create_crypto_provider(&settings.encryption_algorithm);
-        let file_data = fs::read(file_path)
-            .map_err(CryptoError::IoError)?;
-        
-        if file_data.len() < 32 {
-            return Ok(false); // File too small to be a valid encrypted file
+        Self::password_matches(settings, &Self::path_item(file_path))
+    }
+
+    fn password_matches(settings: &Settings, file: &FileItem) -> CryptoResult<bool> {
+        let input = open_file_item(file).map_err(CryptoError::DecryptionError)?;
+        let (interop_algorithm, mut input) = interop::detect(input)?;
+        if let Some(algorithm) = interop_algorithm {
+            // age的口令只用于解开文件头中的文件密钥，读取文件头即可验证；OpenSSL文件无法预先验证
+            let mut prefix = Vec::new();
+            input.take(64 * 1024).read_to_end(&mut prefix)?;
+            return create_crypto_provider_with_kdf(&algorithm, &settings.kdf).verify_password(&settings.password, &prefix);
+        }
+        let (header, reader) = FileHeader::read_from(&mut input)?;
+        let header = header.unwrap_or_default();
+        let password = kms::decryption_password(settings.key_source, &settings.kms, &settings.password, header.wrapped_key.as_ref())
+            .and_then(|password| keyfile::unlock_password(settings.keyfile.as_deref(), &password, header.keyfile_check.as_ref()))
+            .and_then(|password| token::unlock_password(&password, header.token_challenge.as_ref()))
+            .map_err(CryptoError::KeyDerivationError)?;
+
+        // 级联加密时验证磁盘上的外层
+        let algorithm = header.disk_algorithm(&settings.encryption_algorithm);
+        let provider = match create_decryption_provider(algorithm, Some(&header), &password) {
+            Ok(provider) => provider,
+            Err(CryptoError::InvalidPassword) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let prefix_len = format::SALT_LEN + provider.nonce_len() + format::LENGTH_LEN + format::max_chunk_ciphertext(provider.chunk_size());
+        let mut prefix = Vec::new();
+        reader.take(prefix_len as u64).read_to_end(&mut prefix)?;
+        provider.verify_password(&password, &prefix)
+    }
+
+    /// 解密或校验前先用第一个文件验证密码，密码错误时整批文件都不处理。
+    /// 第一个文件无法验证（例如已损坏）时照常处理，由各文件自己报告错误
+    fn check_password(settings: &Settings, first: Option<&FileItem>) -> Result<(), String> {
+        let Some(first) = first.filter(|_| settings.operation_mode.reads_encrypted()) else {
+            return Ok(());
+        };
+        match Self::password_matches(settings, first) {
+            Ok(false) => Err(format!("Incorrect password for '{}', no files were processed", first.name)),
+            _ => Ok(()),
         }
-        
-        provider.verify_password(&settings.password, &file_data[0..64])
     }
     
     /// 静态方法：同步版本的开始加密/解密操作（保持向后兼容）
@@ -420,6 +1662,37 @@ impl CryptoEngine {
         engine.start_operation(settings, files)
     }
 
+    /// 把 `input` 加密到 `output`（已存在时覆盖），不需要构造文件列表和完整的设置
+    ///
+    /// 输出格式与批处理相同；不加密文件名、不删除源文件，也不记录增量状态。
+    /// 失败时不会留下不完整的输出文件
+    pub fn encrypt_path(input: &Path, output: &Path, options: &EncryptOptions) -> Result<(), String> {
+        let settings = options.to_settings(OperationMode::Encrypt);
+        settings.validate().map_err(|e| e.to_string())?;
+        Self::encrypt_to(&settings, &Self::path_item(input), output).map(|_| ())
+    }
+
+    /// 把加密文件 `input` 解密到 `output`（已存在时覆盖），密码提示和Argon2参数以文件头为准
+    pub fn decrypt_path(input: &Path, output: &Path, options: &EncryptOptions) -> Result<(), String> {
+        let settings = options.to_settings(OperationMode::Decrypt);
+        settings.validate().map_err(|e| e.to_string())?;
+        Self::decrypt_to(&settings, &Self::path_item(input), output, false).map(|_| ())
+    }
+
+    /// 校验加密文件 `input` 的完整性：解密全部数据块并核对整个文件的明文摘要，不写出明文。
+    /// 文件被截断、缺少末尾的数据块或密码错误时返回错误
+    pub fn verify_file(input: &Path, options: &EncryptOptions) -> Result<(), String> {
+        let settings = options.to_settings(OperationMode::Verify);
+        settings.validate().map_err(|e| e.to_string())?;
+        Self::verify_item(&settings, &Self::path_item(input)).map(|_| ())
+    }
+
+    /// 单个路径对应的文件项，名称用于错误信息
+    fn path_item(path: &Path) -> FileItem {
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+        FileItem::new(path.to_path_buf(), name)
+    }
+
     /// 静态方法：开始异步加密/解密操作（保持向后兼容）
     pub fn start_operation_async_static(
         settings: Settings,
@@ -431,4 +1704,513 @@ impl CryptoEngine {
     }
 
 
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use crate::core::FileManager;
+    use crate::models::{FileOutcome, KdfAlgorithm};
+
+    #[test]
+    fn test_path_api_round_trip() {
+        let dir = TestDir::new("path_api");
+        let (input, encrypted, decrypted) = (dir.join("plain.txt"), dir.join("plain.enc"), dir.join("plain.out"));
+        fs::write(&input, b"library users need no file list").unwrap();
+
+        let options = EncryptOptions { password: "pw".to_string(), ..Default::default() };
+        CryptoEngine::encrypt_path(&input, &encrypted, &options).unwrap();
+        CryptoEngine::decrypt_path(&encrypted, &decrypted, &options).unwrap();
+        assert_eq!(fs::read(&decrypted).unwrap(), fs::read(&input).unwrap());
+
+        let wrong = EncryptOptions { password: "other".to_string(), ..options.clone() };
+        assert!(CryptoEngine::decrypt_path(&encrypted, &dir.join("wrong.out"), &wrong).is_err());
+        assert!(!dir.join("wrong.out").exists());
+
+        // scrypt和PBKDF2的选择记录在文件头中，解密时不需要再指定
+        for kdf in [
+            KdfParams { memory_kib: 1024, ..KdfParams::for_algorithm(KdfAlgorithm::Scrypt) },
+            KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) },
+        ] {
+            let kdf_options = EncryptOptions { kdf, ..options.clone() };
+            CryptoEngine::encrypt_path(&input, &encrypted, &kdf_options).unwrap();
+            assert_eq!(FileHeader::read_from(&mut File::open(&encrypted).unwrap()).unwrap().0.unwrap().kdf_params, Some(kdf));
+            CryptoEngine::decrypt_path(&encrypted, &decrypted, &options).unwrap();
+            assert_eq!(fs::read(&decrypted).unwrap(), fs::read(&input).unwrap());
+        }
+
+        // 去掉末尾的摘要块后每个数据块仍然完整，但校验必须失败
+        CryptoEngine::verify_file(&encrypted, &options).unwrap();
+        assert!(CryptoEngine::verify_file(&encrypted, &wrong).is_err());
+        let data = fs::read(&encrypted).unwrap();
+        let digest_chunk = format::digest_chunk_len(format::NONCE_LEN);
+        fs::write(&encrypted, &data[..data.len() - digest_chunk]).unwrap();
+        assert!(CryptoEngine::verify_file(&encrypted, &options).is_err());
+    }
+
+    #[test]
+    fn test_verify_mode_writes_nothing() {
+        let dir = TestDir::new("verify");
+        let (input, encrypted) = (dir.join("plain.txt"), dir.join("plain.txt.enc"));
+        fs::write(&input, b"checked without writing").unwrap();
+        let options = EncryptOptions { password: "pw".to_string(), ..Default::default() };
+        CryptoEngine::encrypt_path(&input, &encrypted, &options).unwrap();
+        fs::remove_file(&input).unwrap();
+
+        let file = FileItem::new(encrypted.clone(), "plain.txt.enc".to_string());
+        let settings = Settings::builder().operation_mode(OperationMode::Verify).password("pw").build().unwrap();
+        assert_eq!(CryptoEngine::planned_output_path(&settings, &file), None);
+        assert_eq!(CryptoEngine::process_file(&settings, &file), Ok(encrypted.clone()));
+        let wrong = Settings { password: "other".to_string(), ..settings.clone() };
+        assert!(CryptoEngine::process_file(&wrong, &file).is_err());
+        assert!(CryptoEngine::verify_password(&settings, &encrypted).unwrap());
+        assert!(!CryptoEngine::verify_password(&wrong, &encrypted).unwrap());
+        let selected = FileItem { selected: true, ..file.clone() };
+        let error = CryptoEngine::new(1).start_operation(&wrong, &[selected]).unwrap_err();
+        assert!(error.contains("Incorrect password"), "{}", error);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_expired_tasks() {
+        let files = vec![
+            FileItem::new(PathBuf::from("slow.bin"), "slow.bin".to_string()),
+            FileItem::new(PathBuf::from("fast.bin"), "fast.bin".to_string()),
+            FileItem::new(PathBuf::from("queued.bin"), "queued.bin".to_string()),
+        ];
+        let task = |started: Option<Instant>| {
+            let task = InFlightTask { started: Arc::new(OnceLock::new()), abandoned: Arc::new(AtomicBool::new(false)) };
+            if let Some(started) = started {
+                task.started.set(started).unwrap();
+            }
+            task
+        };
+        let tasks = HashMap::from([
+            (0, task(Instant::now().checked_sub(Duration::from_secs(30)))),
+            (1, task(Some(Instant::now()))),
+            (2, task(None)),
+        ]);
+
+        let settings = Settings { file_timeout_secs: 10, batch_timeout_mins: 5, ..Settings::default() };
+        let expired = CryptoEngine::expired_tasks(&settings, &files, &tasks, false);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].0, 0);
+        assert!(expired[0].1.contains("10 seconds"));
+        assert_eq!(CryptoEngine::expired_tasks(&settings, &files, &tasks, true).len(), 3);
+        let unlimited = Settings { file_timeout_secs: 0, ..settings };
+        assert!(CryptoEngine::expired_tasks(&unlimited, &files, &tasks, false).is_empty());
+    }
+
+    #[test]
+    fn test_batch_key_files_decrypt_individually() {
+        let dir = TestDir::new("batch_key");
+        let mut files = Vec::new();
+        for name in ["a.txt", "b.txt"] {
+            fs::write(dir.join(name), name.repeat(1000)).unwrap();
+            let mut file = FileItem::new(dir.join(name), name.to_string());
+            file.selected = true;
+            files.push(file);
+        }
+
+        for mode in [BatchKeyMode::Derived, BatchKeyMode::Session] {
+            let settings = Settings::builder()
+                .password("pw")
+                .encrypt_filename(false)
+                .delete_source(false, false)
+                .batch_key(mode)
+                .build()
+                .unwrap();
+            CryptoEngine::new(1).start_operation(&settings, &files).unwrap();
+
+            let headers: Vec<_> = ["a.txt.enc", "b.txt.enc"].iter()
+                .map(|name| {
+                    let mut input = File::open(dir.join(name)).unwrap();
+                    let (header, _) = FileHeader::read_from(&mut input).unwrap();
+                    let header = header.unwrap();
+                    (header.batch_salt, header.session_key)
+                })
+                .collect();
+            assert!(headers[0].0.is_some());
+            assert_eq!(headers[0].1.is_some(), mode == BatchKeyMode::Session);
+            assert_eq!(headers[0], headers[1]);
+
+            let options = EncryptOptions { password: "pw".to_string(), ..Default::default() };
+            CryptoEngine::decrypt_path(&dir.join("b.txt.enc"), &dir.join("b.out"), &options).unwrap();
+            assert_eq!(fs::read(dir.join("b.out")).unwrap(), fs::read(dir.join("b.txt")).unwrap());
+            let wrong = EncryptOptions { password: "other".to_string(), ..options };
+            assert!(CryptoEngine::decrypt_path(&dir.join("a.txt.enc"), &dir.join("a.out"), &wrong).is_err());
+        }
+    }
+
+    #[test]
+    fn test_interop_files_are_detected_without_a_header() {
+        let dir = TestDir::new("engine_interop");
+        let input = dir.join("plain.txt");
+        fs::write(&input, b"readable by age -d and openssl enc -d".repeat(5000)).unwrap();
+
+        // 使用scrypt参数中的工作因子、PBKDF2参数中的迭代次数
+        for (algorithm, kdf) in [
+            (EncryptionAlgorithm::Age, KdfParams { memory_kib: 1024, ..KdfParams::for_algorithm(KdfAlgorithm::Scrypt) }),
+            (EncryptionAlgorithm::OpenSsl, KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) }),
+        ] {
+            let options = EncryptOptions { password: "pw".to_string(), algorithm: algorithm.clone(), kdf, ..Default::default() };
+            let encrypted_path = dir.join(format!("plain.{}", algorithm.id()));
+            CryptoEngine::encrypt_path(&input, &encrypted_path, &options).unwrap();
+            let encrypted = fs::read(&encrypted_path).unwrap();
+            assert_eq!(interop::detect(Box::new(&encrypted[..])).unwrap().0, Some(algorithm.clone()));
+            assert_eq!(encrypted.len() as u64, interop::encrypted_size(&algorithm, fs::metadata(&input).unwrap().len()));
+
+            // 解密时按文件内容识别，与设置中的算法无关
+            let decrypt = EncryptOptions { algorithm: EncryptionAlgorithm::AES256, ..options };
+            CryptoEngine::decrypt_path(&encrypted_path, &dir.join("out.txt"), &decrypt).unwrap();
+            assert_eq!(fs::read(dir.join("out.txt")).unwrap(), fs::read(&input).unwrap());
+            // OpenSSL文件没有认证，无法可靠地发现错误的口令
+            if algorithm == EncryptionAlgorithm::Age {
+                let wrong = EncryptOptions { password: "other".to_string(), ..decrypt };
+                assert!(!CryptoEngine::verify_password(&wrong.to_settings(OperationMode::Decrypt), &encrypted_path).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_keyfile_required_for_decryption() {
+        let dir = TestDir::new("engine_keyfile");
+        let input = dir.join("plain.txt");
+        fs::write(&input, b"needs the keyfile".repeat(100)).unwrap();
+        fs::write(dir.join("key.bin"), b"keyfile").unwrap();
+        fs::write(dir.join("other.bin"), b"another keyfile").unwrap();
+
+        // 整批派生一次主密钥时同样混入密钥文件
+        let settings = Settings::builder()
+            .password("pw")
+            .encrypt_filename(false)
+            .delete_source(false, false)
+            .keyfile(dir.join("key.bin"))
+            .build()
+            .unwrap();
+        let file = FileItem { selected: true, ..FileItem::new(input.clone(), "plain.txt".to_string()) };
+        CryptoEngine::new(1).start_operation(&settings, &[file]).unwrap();
+        let encrypted = dir.join("plain.txt.enc");
+
+        let options = EncryptOptions { password: "pw".to_string(), keyfile: Some(dir.join("key.bin")), ..Default::default() };
+        CryptoEngine::decrypt_path(&encrypted, &dir.join("out.txt"), &options).unwrap();
+        assert_eq!(fs::read(dir.join("out.txt")).unwrap(), fs::read(&input).unwrap());
+
+        for keyfile in [None, Some(dir.join("other.bin")), Some(dir.join("missing.bin"))] {
+            let options = EncryptOptions { keyfile, ..options.clone() };
+            assert!(CryptoEngine::decrypt_path(&encrypted, &dir.join("bad.txt"), &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compressed_files_round_trip() {
+        let dir = TestDir::new("compressed");
+        let (input, encrypted, decrypted) = (dir.join("server.log"), dir.join("server.enc"), dir.join("server.out"));
+        fs::write(&input, b"2024-01-01 12:00:00 INFO request served in 3 ms\n".repeat(100_000)).unwrap();
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+
+        for compression in [CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4] {
+            for parallel_chunks in [false, true] {
+                let options = EncryptOptions { password: "pw".to_string(), kdf, compression, parallel_chunks, ..Default::default() };
+                CryptoEngine::encrypt_path(&input, &encrypted, &options).unwrap();
+                let header = FileHeader::read_from(&mut File::open(&encrypted).unwrap()).unwrap().0.unwrap();
+                assert_eq!(header.compression, Some(Compression { algorithm: compression, plaintext_len: fs::metadata(&input).unwrap().len() }));
+                assert!(fs::metadata(&encrypted).unwrap().len() < fs::metadata(&input).unwrap().len() / 10);
+
+                // 解密时按文件头解压，不需要指定压缩算法
+                let plain = EncryptOptions { compression: CompressionAlgorithm::None, ..options.clone() };
+                CryptoEngine::decrypt_path(&encrypted, &decrypted, &plain).unwrap();
+                assert_eq!(fs::read(&decrypted).unwrap(), fs::read(&input).unwrap());
+                CryptoEngine::verify_file(&encrypted, &plain).unwrap();
+            }
+        }
+
+        // 文件头中的压缩信息受摘要块认证：去掉压缩信息或改动压缩前的长度都无法解密，也不会输出压缩后的数据
+        let options = EncryptOptions { password: "pw".to_string(), kdf, compression: CompressionAlgorithm::Zstd, ..Default::default() };
+        let changes: [fn(&mut FileHeader); 3] = [
+            |header| header.compression = None,
+            |header| header.compression.as_mut().unwrap().plaintext_len -= 1,
+            |header| header.compression.as_mut().unwrap().algorithm = CompressionAlgorithm::Lz4,
+        ];
+        fs::remove_file(&decrypted).unwrap();
+        for change in changes {
+            CryptoEngine::encrypt_path(&input, &encrypted, &options).unwrap();
+            rewrite_header(&encrypted, change);
+            assert!(CryptoEngine::decrypt_path(&encrypted, &decrypted, &options).is_err());
+            assert!(!decrypted.exists());
+            assert!(CryptoEngine::verify_file(&encrypted, &options).is_err());
+        }
+
+        // 已经压缩过的文件类型不再压缩
+        let photo = dir.join("photo.jpg");
+        fs::write(&photo, b"not really a jpeg").unwrap();
+        let options = EncryptOptions { password: "pw".to_string(), kdf, compression: CompressionAlgorithm::Zstd, ..Default::default() };
+        CryptoEngine::encrypt_path(&photo, &encrypted, &options).unwrap();
+        assert_eq!(FileHeader::read_from(&mut File::open(&encrypted).unwrap()).unwrap().0.unwrap().compression, None);
+    }
+
+    /// 改写 `path` 的文件头，数据流保持不变
+    fn rewrite_header(path: &Path, change: impl FnOnce(&mut FileHeader)) {
+        let data = fs::read(path).unwrap();
+        let mut input = data.as_slice();
+        let (header, mut rest) = FileHeader::read_from(&mut input).unwrap();
+        let mut header = header.unwrap();
+        change(&mut header);
+        let mut rewritten = header.encode();
+        rest.read_to_end(&mut rewritten).unwrap();
+        drop(rest);
+        fs::write(path, rewritten).unwrap();
+    }
+
+    #[test]
+    fn test_header_is_authenticated() {
+        let dir = TestDir::new("header_binding");
+        let (input, encrypted, decrypted) = (dir.join("notes.bin"), dir.join("notes.enc"), dir.join("notes.out"));
+        fs::write(&input, vec![7u8; 3 * 1024 * 1024 + 5]).unwrap();
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+
+        for parallel_chunks in [false, true] {
+            let options = EncryptOptions {
+                password: "pw".to_string(), password_hint: "first pet".to_string(), kdf, parallel_chunks, ..Default::default()
+            };
+            CryptoEngine::encrypt_path(&input, &encrypted, &options).unwrap();
+            assert!(FileHeader::read_from(&mut File::open(&encrypted).unwrap()).unwrap().0.unwrap().bound_digest);
+            CryptoEngine::verify_file(&encrypted, &options).unwrap();
+
+            // 改动文件头中的任何字段（这里是明文密码提示）都使摘要不符
+            rewrite_header(&encrypted, |header| header.hint = "second pet".to_string());
+            assert!(CryptoEngine::decrypt_path(&encrypted, &decrypted, &options).is_err());
+            assert!(!decrypted.exists());
+            assert!(CryptoEngine::verify_file(&encrypted, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn test_encrypted_filename_is_restored() {
+        let dir = TestDir::new("restore_name");
+        let (encrypted_dir, decrypted_dir) = (dir.join("encrypted"), dir.join("decrypted"));
+        fs::write(dir.join("报告 2024.txt"), b"quarterly numbers".repeat(100)).unwrap();
+        let mut file = FileItem::new(dir.join("报告 2024.txt"), "报告 2024.txt".to_string());
+        file.selected = true;
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+
+        let settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(true)
+            .delete_source(false, false)
+            .output_directory(&encrypted_dir)
+            .build()
+            .unwrap();
+        CryptoEngine::new(1).start_operation(&settings, &[file]).unwrap();
+        let encrypted = fs::read_dir(&encrypted_dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|extension| extension == "enc"))
+            .unwrap();
+        assert!(!encrypted.to_string_lossy().contains("报告"));
+        let name = encrypted.file_name().unwrap().to_string_lossy().to_string();
+        let mut encrypted_file = FileItem::new(encrypted.clone(), name);
+        encrypted_file.selected = true;
+
+        // 解密时从文件头恢复原始文件名，而不是输出 `随机名.decrypted`
+        let settings = Settings::builder()
+            .operation_mode(OperationMode::Decrypt)
+            .password("pw")
+            .delete_source(false, false)
+            .output_directory(&decrypted_dir)
+            .build()
+            .unwrap();
+        CryptoEngine::new(1).start_operation(&settings, std::slice::from_ref(&encrypted_file)).unwrap();
+        assert_eq!(fs::read(decrypted_dir.join("报告 2024.txt")).unwrap(), fs::read(dir.join("报告 2024.txt")).unwrap());
+
+        let keep_both = Settings { conflict_policy: ConflictPolicy::KeepBoth, ..settings };
+        CryptoEngine::new(1).start_operation(&keep_both, &[encrypted_file]).unwrap();
+        assert!(decrypted_dir.join("报告 2024 (1).txt").exists());
+    }
+
+    #[test]
+    fn test_recursive_directories_keep_their_structure() {
+        let dir = TestDir::new("recursive");
+        let source = dir.join("source");
+        for (relative, contents) in [("top.txt", "top"), ("a/one.txt", "one"), ("a/b/two.txt", "two"), ("a/b/c/three.txt", "three")] {
+            let path = source.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+        let source_name = source.to_string_lossy().to_string();
+
+        let flat = FileManager::load_files_from_directory(&source_name, &Settings::default());
+        assert_eq!(flat.iter().map(FileItem::relative_name).collect::<Vec<_>>(), ["top.txt"]);
+        let limited = Settings { recursive: true, max_depth: 2, ..Settings::default() };
+        let files = FileManager::load_files_from_directory(&source_name, &limited);
+        assert_eq!(files.iter().map(FileItem::relative_name).collect::<Vec<_>>(), ["a/b/two.txt", "a/one.txt", "top.txt"]);
+
+        let settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(false)
+            .delete_source(false, false)
+            .recursive(true, 0)
+            .output_directory(dir.join("encrypted"))
+            .build()
+            .unwrap();
+        let mut files = FileManager::load_files_from_directory(&source_name, &settings);
+        assert_eq!(files.len(), 4);
+        files.iter_mut().for_each(|file| file.selected = true);
+        CryptoEngine::new(2).start_operation(&settings, &files).unwrap();
+        assert!(dir.join("encrypted/a/b/c/three.txt.enc").exists());
+
+        // 解密时同样在输出目录下重建目录结构
+        let settings = Settings {
+            operation_mode: OperationMode::Decrypt,
+            output_directory: Some(dir.join("decrypted")),
+            ..settings
+        };
+        let mut encrypted = FileManager::load_encrypted_files_from_directory(&dir.join("encrypted").to_string_lossy(), &settings);
+        assert_eq!(encrypted.iter().map(FileItem::relative_name).collect::<Vec<_>>(),
+            ["a/b/c/three.txt.enc", "a/b/two.txt.enc", "a/one.txt.enc", "top.txt.enc"]);
+        encrypted.iter_mut().for_each(|file| file.selected = true);
+        CryptoEngine::new(2).start_operation(&settings, &encrypted).unwrap();
+        for relative in ["top.txt", "a/one.txt", "a/b/two.txt", "a/b/c/three.txt"] {
+            assert_eq!(fs::read(dir.join("decrypted").join(relative)).unwrap(), fs::read(source.join(relative)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_skip_and_continue_records_every_file() {
+        let dir = TestDir::new("error_policy");
+        let files: Vec<FileItem> = ["a.txt", "missing.txt", "c.txt"].iter()
+            .map(|name| {
+                if *name != "missing.txt" {
+                    fs::write(dir.join(name), name.repeat(100)).unwrap();
+                }
+                FileItem { selected: true, ..FileItem::new(dir.join(name), name.to_string()) }
+            })
+            .collect();
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+        let settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(false)
+            .delete_source(false, false)
+            .error_policy(ErrorPolicy::SkipAndContinue)
+            .build()
+            .unwrap();
+
+        let handle = CryptoEngine::new(1).start_operation_async(settings.clone(), files.clone(), None).unwrap();
+        let results = handle.results.clone();
+        let status = handle.status.clone();
+        assert!(handle.wait().is_err());
+        assert_eq!(*status.lock().unwrap(), OperationStatus::Failed("1 of 3 files failed".to_string()));
+        let results = results.lock().unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|result| result.name == "missing.txt" && matches!(result.outcome, FileOutcome::Failed { .. })));
+        assert!(dir.join("a.txt.enc").exists() && dir.join("c.txt.enc").exists());
+
+        // 默认在第一个失败的文件处停止
+        fs::remove_file(dir.join("c.txt.enc")).unwrap();
+        let stop = Settings { error_policy: ErrorPolicy::StopOnError, ..settings };
+        let ordered = vec![files[1].clone(), files[2].clone()];
+        let handle = CryptoEngine::new(1).start_operation_async(stop, ordered, None).unwrap();
+        assert!(handle.wait().is_err());
+        assert!(!dir.join("c.txt.enc").exists());
+    }
+
+    #[test]
+    fn test_stop_interrupts_file_and_removes_partial_output() {
+        let dir = TestDir::new("stop");
+        let source = dir.join("large.bin");
+        fs::write(&source, vec![3u8; format::DEFAULT_CHUNK_SIZE * 3]).unwrap();
+        let file = CryptoEngine::path_item(&source);
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(false)
+            .build()
+            .unwrap();
+        settings.stream_control = StreamControl::new(stop.clone());
+
+        let error = CryptoEngine::encrypt_file(&settings, &file).unwrap_err();
+        assert!(error.contains("E1007"), "{}", error);
+        assert!(!dir.join("large.bin.enc").exists());
+        assert!(source.exists());
+
+        // 跳过只中断请求跳过的文件，其他文件照常处理
+        stop.store(false, std::sync::atomic::Ordering::Relaxed);
+        let skips = SkipRequests::default();
+        skips.request(&source);
+        let other = dir.join("other.bin");
+        let with_skip = |path: &Path| Settings { stream_control: settings.stream_control.clone().with_skip(skips.flag(path)), ..settings.clone() };
+        let skipping = with_skip(&source);
+        assert!(CryptoEngine::encrypt_file(&skipping, &file).is_err());
+        assert!(skipping.stream_control.skipped());
+        assert!(!dir.join("large.bin.enc").exists());
+        assert!(!with_skip(&other).stream_control.skipped());
+        assert!(CryptoEngine::encrypt_file(&settings, &file).is_ok());
+    }
+
+    #[test]
+    fn test_resumed_batch_skips_completed_files() {
+        let dir = TestDir::new("resume");
+        let files: Vec<FileItem> = ["a.txt", "b.txt"].iter()
+            .map(|name| {
+                fs::write(dir.join(name), name.repeat(100)).unwrap();
+                FileItem { selected: true, ..FileItem::new(dir.join(name), name.to_string()) }
+            })
+            .collect();
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+        let mut settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(false)
+            .delete_source(false, false)
+            .resumable(true)
+            .build()
+            .unwrap();
+
+        // 中断前 a.txt 已经完成
+        let journal = Journal::begin_in(&dir, &settings, &files).unwrap();
+        journal.file_completed(&files[0].path, Some(&dir.join("a.txt.enc")), Vec::new());
+        settings.journal = Some(journal.clone());
+
+        let handle = CryptoEngine::new(1).start_operation_async(settings, files, None).unwrap();
+        let results = handle.results.clone();
+        handle.wait().unwrap();
+        assert_eq!(results.lock().unwrap().len(), 2);
+        assert!(!dir.join("a.txt.enc").exists());
+        assert!(dir.join("b.txt.enc").exists());
+        assert!(!journal.path().exists());
+    }
+
+    #[test]
+    fn test_verify_after_encrypt_keeps_source_on_mismatch() {
+        let dir = TestDir::new("verify_after");
+        let source = dir.join("notes.txt");
+        fs::write(&source, b"keep me safe".repeat(5000)).unwrap();
+        let file = CryptoEngine::path_item(&source);
+        let kdf = KdfParams { iterations: 1000, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) };
+        let settings = Settings::builder()
+            .password("pw")
+            .kdf(kdf)
+            .encrypt_filename(false)
+            .delete_source(true, false)
+            .verify_after_encrypt(true)
+            .build()
+            .unwrap();
+
+        let output = CryptoEngine::encrypt_file(&settings, &file).unwrap();
+        assert!(output.exists());
+        assert!(!source.exists());
+
+        // 摘要不一致时删除输出
+        fs::write(&source, b"keep me safe").unwrap();
+        let digest = CryptoEngine::encrypt_to(&settings, &file, &output).unwrap().unwrap();
+        assert_eq!(digest, blake3::hash(b"keep me safe"));
+        assert!(CryptoEngine::verify_output(&settings, &file, &output, blake3::hash(b"other")).is_err());
+        assert!(!output.exists());
+        assert!(source.exists());
+    }
+}
@@ -0,0 +1,382 @@
+//! 分块加密文件格式的公共常量和尺寸计算
+//!
+//! 文件结构：盐值(32) + 若干数据块，每块为 nonce + 密文长度(u32 LE) + 密文（含16字节认证标签）。
+//! nonce长度由算法决定（AES-256-GCM和ChaCha20-Poly1305为12字节，XChaCha20-Poly1305为24字节）
+//! 盐值之前可能有可选的文件头（见 [`super::header`]），这里的尺寸计算不包含文件头
+//!
+//! 单块的长度字段是u32，块大小不能超过 [`MAX_CHUNK_SIZE`]；文件总长度、块数和块序号一律使用u64，
+//! 因此文件大小不受4 GB限制。加密时除最后一块外每块都是完整的块，块数只由明文长度决定，
+//! 空明文也有一个空的最后一块（旧版本可能写出不满的块或不写数据块，解密时仍然接受）。
+//! 最后一块之后是固定长度的摘要块（旧版本没有）。各块的nonce和关联数据见 [`super::stream`]
+
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+/// 管道中最多缓冲的写入次数
+const PIPE_DEPTH: usize = 4;
+/// 预读时轮流使用的缓冲区个数：一个正在从磁盘读取，一个正在加密，其余排队等待
+const READ_AHEAD_BUFFERS: usize = 3;
+
+/// 文件头中盐值的长度
+pub const SALT_LEN: usize = 32;
+/// 每个数据块的默认nonce长度
+pub const NONCE_LEN: usize = 12;
+/// 每个数据块的长度字段
+pub const LENGTH_LEN: usize = 4;
+/// AEAD认证标签长度
+pub const TAG_LEN: usize = 16;
+/// 摘要块中明文摘要（BLAKE3）的长度
+pub const DIGEST_LEN: usize = 32;
+/// 使用默认nonce长度时每个数据块除明文外的额外开销
+pub const CHUNK_OVERHEAD: usize = chunk_overhead(NONCE_LEN);
+/// 内置算法的块大小
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+/// 块大小上限：密文（明文加认证标签）必须能放进u32长度字段
+pub const MAX_CHUNK_SIZE: usize = u32::MAX as usize - TAG_LEN;
+
+/// 每个数据块除明文外的额外开销
+pub const fn chunk_overhead(nonce_len: usize) -> usize {
+    nonce_len + LENGTH_LEN + TAG_LEN
+}
+
+/// 最后一块之后的摘要块在磁盘上的长度
+pub const fn digest_chunk_len(nonce_len: usize) -> usize {
+    chunk_overhead(nonce_len) + DIGEST_LEN
+}
+
+/// 明文分成的块数（不含摘要块）
+pub fn chunk_count(plaintext_len: u64, chunk_size: usize) -> u64 {
+    plaintext_len.div_ceil(chunk_size as u64).max(1)
+}
+
+/// 单块密文的最大长度，解密时长度字段超过它说明数据已损坏，避免按损坏的长度分配超大缓冲区
+pub fn max_chunk_ciphertext(chunk_size: usize) -> usize {
+    chunk_size.min(MAX_CHUNK_SIZE) + TAG_LEN
+}
+
+/// 读满一个数据块，只有读到文件末尾时返回的长度才会小于缓冲区
+pub fn read_chunk(reader: &mut dyn Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// 计算明文加密后的文件大小
+pub fn encrypted_size(plaintext_len: u64, chunk_size: usize, nonce_len: usize) -> u64 {
+    let chunks = chunk_count(plaintext_len, chunk_size);
+    SALT_LEN as u64 + chunks * chunk_overhead(nonce_len) as u64 + plaintext_len + digest_chunk_len(nonce_len) as u64
+}
+
+/// 估算密文解密后的最大明文大小（按没有摘要块的旧格式估算，结果不会小于实际大小）
+pub fn max_plaintext_size(ciphertext_len: u64, chunk_size: usize, nonce_len: usize) -> u64 {
+    let overhead = chunk_overhead(nonce_len) as u64;
+    let body = ciphertext_len.saturating_sub(SALT_LEN as u64);
+    let chunks = body.div_ceil(chunk_size as u64 + overhead);
+    body.saturating_sub(chunks * overhead)
+}
+
+/// 统计经过的字节数的读写包装
+pub struct Counted<T> {
+    inner: T,
+    count: u64,
+}
+
+impl<T> Counted<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// 已读取或写入的字节数
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 用BLAKE3计算经过的数据摘要的读写包装，未启用时直接转发
+pub struct Digested<T> {
+    inner: T,
+    hasher: Option<blake3::Hasher>,
+}
+
+impl<T> Digested<T> {
+    pub fn new(inner: T, enabled: bool) -> Self {
+        Self { inner, hasher: enabled.then(blake3::Hasher::new) }
+    }
+
+    /// 已读取或写入的数据的摘要，未启用时为None
+    pub fn digest(&self) -> Option<blake3::Hash> {
+        self.hasher.as_ref().map(blake3::Hasher::finalize)
+    }
+}
+
+impl<R: Read> Read for Digested<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Digested<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 在两个线程之间传递数据的内存管道，写入端关闭后读取端读到结尾
+pub fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = mpsc::sync_channel(PIPE_DEPTH);
+    (PipeWriter { sender }, PipeReader { receiver, buffer: Vec::new(), position: 0 })
+}
+
+pub struct PipeWriter {
+    sender: mpsc::SyncSender<Vec<u8>>,
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sender.send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the reading side of the pipe stopped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct PipeReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buffer.len() {
+            match self.receiver.recv() {
+                Ok(data) => {
+                    self.buffer = data;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.buffer.len() - self.position);
+        buf[..len].copy_from_slice(&self.buffer[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// 预读读取器：后台线程从 `inner` 按块读取下一块，同时调用方处理当前块
+///
+/// 读满的缓冲区经通道交给调用方，用完后送回读取线程重复使用，整个文件最多分配 [`READ_AHEAD_BUFFERS`] 个缓冲区。
+/// 读取错误在此前读满的块之后返回；丢弃时关闭通道并等待读取线程结束
+pub struct ReadAhead {
+    /// 读满的缓冲区和送回空缓冲区的通道，读到结尾或出错后为None
+    channels: Option<ReadAheadChannels>,
+    current: Vec<u8>,
+    position: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+type ReadAheadChannels = (mpsc::Receiver<io::Result<Vec<u8>>>, mpsc::Sender<Vec<u8>>);
+
+impl ReadAhead {
+    /// 在后台线程中按 `chunk_size` 字节一块预读 `inner`
+    pub fn new(inner: impl Read + Send + 'static, chunk_size: usize) -> Self {
+        let (filled_sender, filled) = mpsc::sync_channel(READ_AHEAD_BUFFERS);
+        let (recycle, recycled) = mpsc::channel::<Vec<u8>>();
+        let thread = thread::spawn(move || {
+            let mut inner = inner;
+            let mut allocated = 0;
+            loop {
+                // 先用送回的缓冲区，不够时才分配新的，用完全部缓冲区后等待调用方送回
+                let mut buffer = match recycled.try_recv() {
+                    Ok(buffer) => buffer,
+                    Err(_) if allocated < READ_AHEAD_BUFFERS => {
+                        allocated += 1;
+                        Vec::with_capacity(chunk_size)
+                    }
+                    Err(_) => match recycled.recv() {
+                        Ok(buffer) => buffer,
+                        Err(_) => return,
+                    },
+                };
+                buffer.resize(chunk_size, 0);
+                let result = read_chunk(&mut inner, &mut buffer).map(|len| {
+                    buffer.truncate(len);
+                    buffer
+                });
+                match result {
+                    Ok(buffer) if buffer.is_empty() => return,
+                    result => {
+                        let failed = result.is_err();
+                        if filled_sender.send(result).is_err() || failed {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        Self { channels: Some((filled, recycle)), current: Vec::new(), position: 0, thread: Some(thread) }
+    }
+}
+
+impl Read for ReadAhead {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.current.len() {
+            let Some((filled, recycle)) = &self.channels else {
+                return Ok(0);
+            };
+            match filled.recv() {
+                Ok(Ok(chunk)) => {
+                    let used = std::mem::replace(&mut self.current, chunk);
+                    if used.capacity() > 0 {
+                        let _ = recycle.send(used);
+                    }
+                    self.position = 0;
+                }
+                Ok(Err(e)) => {
+                    self.channels = None;
+                    return Err(e);
+                }
+                Err(_) => self.channels = None,
+            }
+        }
+        let len = buf.len().min(self.current.len() - self.position);
+        buf[..len].copy_from_slice(&self.current[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        // 关闭通道后读取线程最多再读完正在读的一块
+        self.channels = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_estimates_round_trip() {
+        let chunk_size = 1024 * 1024;
+        for plaintext_len in [0u64, 1, 1024 * 1024, 1024 * 1024 + 1, 5 * 1024 * 1024 + 17] {
+            for nonce_len in [NONCE_LEN, 24] {
+                // 估算按没有摘要块的旧格式进行，去掉摘要块后应得到准确的明文大小
+                let encrypted = encrypted_size(plaintext_len, chunk_size, nonce_len);
+                assert!(max_plaintext_size(encrypted, chunk_size, nonce_len) >= plaintext_len);
+                let without_digest = encrypted - digest_chunk_len(nonce_len) as u64;
+                assert_eq!(max_plaintext_size(without_digest, chunk_size, nonce_len), plaintext_len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sizes_beyond_four_gigabytes() {
+        const GIB: u64 = 1024 * 1024 * 1024;
+        let chunk_size = 1024 * 1024;
+        let chunks_per_4g = 4 * GIB / chunk_size as u64;
+
+        // 4 GB前后的块边界
+        for (plaintext_len, chunks) in [
+            (4 * GIB - 1, chunks_per_4g),
+            (4 * GIB, chunks_per_4g),
+            (4 * GIB + 1, chunks_per_4g + 1),
+            (u32::MAX as u64, chunks_per_4g),
+            (5 * GIB + 17, 5 * chunks_per_4g / 4 + 1),
+            (3 * 1024 * GIB, 3 * 1024 * chunks_per_4g / 4),
+        ] {
+            assert_eq!(chunk_count(plaintext_len, chunk_size), chunks);
+            let encrypted = encrypted_size(plaintext_len, chunk_size, NONCE_LEN);
+            let digest_chunk = digest_chunk_len(NONCE_LEN) as u64;
+            assert_eq!(encrypted, SALT_LEN as u64 + chunks * CHUNK_OVERHEAD as u64 + plaintext_len + digest_chunk);
+            assert!(encrypted > u32::MAX as u64);
+            assert!(max_plaintext_size(encrypted, chunk_size, NONCE_LEN) >= plaintext_len);
+            assert_eq!(max_plaintext_size(encrypted - digest_chunk, chunk_size, NONCE_LEN), plaintext_len);
+        }
+
+        assert_eq!(chunk_count(0, chunk_size), 1);
+
+        // 块大小不能让密文超出u32长度字段
+        assert_eq!(max_chunk_ciphertext(usize::MAX), u32::MAX as usize);
+        assert_eq!(max_chunk_ciphertext(chunk_size), chunk_size + TAG_LEN);
+    }
+
+    #[test]
+    fn test_read_ahead_matches_source() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for chunk_size in [1, 1000, 4096, 20_000] {
+            let mut read = Vec::new();
+            ReadAhead::new(io::Cursor::new(data.clone()), chunk_size).read_to_end(&mut read).unwrap();
+            assert_eq!(read, data);
+        }
+
+        // 读取错误在已读满的块之后返回；提前丢弃时不等待读完
+        let failing = io::Cursor::new(data.clone()).chain(FailingReader);
+        let mut reader = ReadAhead::new(failing, 1000);
+        let mut read = vec![0u8; data.len()];
+        reader.read_exact(&mut read).unwrap();
+        assert_eq!(read, data);
+        assert!(reader.read(&mut [0u8; 16]).is_err());
+        drop(ReadAhead::new(io::repeat(7), 1024));
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk error"))
+        }
+    }
+}
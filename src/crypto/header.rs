@@ -0,0 +1,713 @@
+//! 可选的文件头，位于盐值之前
+//!
+//! 结构：魔数 "KRYP"(4) + 版本(1) + 标志(1) + 提示长度(u16 LE) + 密码提示(UTF-8)，
+//! 标志包含 [`FLAG_WRAPPED_KEY`] 时随后是密钥来源(1) + 包装密钥长度(u16 LE) + 包装后的数据密钥，
+//! 包含 [`FLAG_TOKEN_CHALLENGE`] 时再随后是YubiKey槽位(1) + 挑战(32)，
+//! 包含 [`FLAG_KDF_PARAMS`] 时再随后是密钥派生的内存(u32 LE, KiB) + 迭代次数(u32 LE) + 并行度(u32 LE)，
+//! 包含 [`FLAG_BATCH_KEY`] 时再随后是批处理主密钥的盐值(32)，
+//! 同时包含 [`FLAG_SESSION_KEY`] 时再随后是用密码派生的密钥包装的会话密钥(nonce 12 + 密文32 + 标签16)，
+//! 包含 [`FLAG_CASCADE`] 时再随后是级联加密的内层和外层算法代码(各1字节)，
+//! 包含 [`FLAG_ALGORITHM`] 时再随后是加密数据的算法代码(1)，
+//! 包含 [`FLAG_EXTENSIONS`] 时再随后是扩展数量(u8)和各扩展：类型(1) + 数据长度(u16 LE) + 数据，
+//! 标志位用完后新的字段都写为扩展（密钥派生算法、密钥文件、压缩、加密的原始文件名、摘要绑定），不认识的扩展类型视为不支持的文件头。
+//! 版本2的文件头最后是明文总长度(u64 LE) + 数据块数(u64 LE)，用于发现在块边界处被截断的文件；
+//! 不记录总长度的文件头仍写为版本1，旧版本程序可以读取。
+//! 密码提示以明文保存，任何人都能读取；文件头本身不加密，带 [`EXT_BOUND_DIGEST`] 的文件由数据流的摘要块认证
+//! 整个文件头（见 [`FileHeader::digest_binding`]），旧文件的文件头不受认证保护。没有文件头的文件是最早的旧格式，
+//! 开头既不是魔数也不像旧格式的数据块时视为不是Krypton文件
+
+use super::cascade::Cascade;
+use super::format::{self, LENGTH_LEN, NONCE_LEN, SALT_LEN, TAG_LEN};
+use super::stream::DigestBinding;
+use super::traits::{CryptoError, CryptoResult};
+use crate::models::{CompressionAlgorithm, EncryptionAlgorithm, KdfAlgorithm, KdfParams, KeySource};
+use std::fs::File;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+
+/// 文件头魔数
+pub const HEADER_MAGIC: [u8; 4] = *b"KRYP";
+/// 当前文件头版本（记录总长度和块数）
+pub const HEADER_VERSION: u8 = 2;
+/// 不含总长度的文件头版本
+const HEADER_VERSION_V1: u8 = 1;
+/// 密码提示的最大字节数
+pub const MAX_HINT_LEN: usize = 256;
+/// 标志位：文件头中包含由外部密钥管理服务包装的数据密钥
+pub const FLAG_WRAPPED_KEY: u8 = 0x01;
+/// 标志位：文件头中包含硬件令牌的挑战，解密时需要同一个令牌
+pub const FLAG_TOKEN_CHALLENGE: u8 = 0x02;
+/// 标志位：文件头中包含非默认的密钥派生参数
+pub const FLAG_KDF_PARAMS: u8 = 0x04;
+/// 标志位：文件密钥由批处理主密钥经HKDF派生，文件头中包含主密钥的盐值
+pub const FLAG_BATCH_KEY: u8 = 0x08;
+/// 标志位：批处理主密钥是随机会话密钥，文件头中包含包装后的会话密钥（须与 [`FLAG_BATCH_KEY`] 同时使用）
+pub const FLAG_SESSION_KEY: u8 = 0x10;
+/// 标志位：数据经过两种算法的级联加密，文件头中包含两层的算法
+pub const FLAG_CASCADE: u8 = 0x20;
+/// 标志位：文件头中包含加密数据的算法
+pub const FLAG_ALGORITHM: u8 = 0x40;
+/// 标志位：文件头中包含扩展列表
+pub const FLAG_EXTENSIONS: u8 = 0x80;
+/// 扩展：密钥派生算法代码(1)，只在使用Argon2id以外的算法时写入（须与 [`FLAG_KDF_PARAMS`] 同时使用）
+const EXT_KDF_ALGORITHM: u8 = 0x01;
+/// 扩展：密钥文件的盐值和校验值
+const EXT_KEYFILE: u8 = 0x02;
+/// 扩展：压缩算法代码(1) + 压缩前的明文长度(u64 LE)
+const EXT_COMPRESSION: u8 = 0x03;
+/// 扩展：用文件的密钥单独加密的原始文件名（完整的加密数据流：盐值、数据块和摘要块）
+const EXT_ORIGINAL_NAME: u8 = 0x04;
+/// 扩展（无数据）：数据流的摘要块同时认证文件头和最终明文
+const EXT_BOUND_DIGEST: u8 = 0x05;
+/// 原始文件名的最大字节数
+pub const MAX_ORIGINAL_NAME_LEN: usize = 4096;
+/// 批处理主密钥盐值的字节数
+pub const BATCH_SALT_LEN: usize = 32;
+/// 包装后的会话密钥的字节数
+pub const WRAPPED_SESSION_KEY_LEN: usize = 12 + 32 + 16;
+/// 硬件令牌挑战的字节数
+pub const TOKEN_CHALLENGE_LEN: usize = 32;
+/// 密钥文件盐值（以及校验值）的字节数
+pub const KEYFILE_SALT_LEN: usize = 16;
+/// Argon2参数的字节数
+const KDF_PARAMS_LEN: usize = 12;
+/// 级联算法代码的字节数
+const CASCADE_LEN: usize = 2;
+/// 版本2中总长度和块数的字节数
+const TOTALS_LEN: usize = 16;
+/// 包装密钥的最大字节数（多个SSH接收者时包含每个接收者的包装密钥）
+const MAX_WRAPPED_KEY_LEN: usize = 32 * 1024;
+
+/// 魔数、版本、标志和提示长度字段的总长度
+const FIXED_LEN: usize = HEADER_MAGIC.len() + 1 + 1 + 2;
+/// 判断旧格式时读取的长度：盐值和第一个数据块的nonce、长度字段
+const LEGACY_PREFIX_LEN: usize = SALT_LEN + NONCE_LEN + LENGTH_LEN;
+
+/// 写入文件头的密钥派生算法代码，Argon2id是默认算法，不写入
+fn kdf_algorithm_code(algorithm: KdfAlgorithm) -> Option<u8> {
+    match algorithm {
+        KdfAlgorithm::Argon2id => None,
+        KdfAlgorithm::Scrypt => Some(1),
+        KdfAlgorithm::Pbkdf2Sha256 => Some(2),
+    }
+}
+
+fn kdf_algorithm_from_code(code: u8) -> Option<KdfAlgorithm> {
+    KdfAlgorithm::ALL.into_iter().find(|algorithm| kdf_algorithm_code(*algorithm) == Some(code))
+}
+
+/// 写入文件头的压缩算法代码，不压缩时不写入扩展
+fn compression_code(algorithm: CompressionAlgorithm) -> Option<u8> {
+    match algorithm {
+        CompressionAlgorithm::None => None,
+        CompressionAlgorithm::Zstd => Some(1),
+        CompressionAlgorithm::Lz4 => Some(2),
+    }
+}
+
+fn compression_from_code(code: u8) -> Option<CompressionAlgorithm> {
+    CompressionAlgorithm::ALL.into_iter().find(|algorithm| compression_code(*algorithm) == Some(code))
+}
+
+/// 写入文件头的算法代码，插件算法没有代码
+pub(crate) fn algorithm_code(algorithm: &EncryptionAlgorithm) -> Option<u8> {
+    match algorithm {
+        EncryptionAlgorithm::AES256 => Some(1),
+        EncryptionAlgorithm::ChaCha20 => Some(2),
+        EncryptionAlgorithm::XChaCha20 => Some(3),
+        EncryptionAlgorithm::Age | EncryptionAlgorithm::OpenSsl | EncryptionAlgorithm::Plugin(_) => None,
+    }
+}
+
+pub(crate) fn algorithm_from_code(code: u8) -> Option<EncryptionAlgorithm> {
+    match code {
+        1 => Some(EncryptionAlgorithm::AES256),
+        2 => Some(EncryptionAlgorithm::ChaCha20),
+        3 => Some(EncryptionAlgorithm::XChaCha20),
+        _ => None,
+    }
+}
+
+/// 由外部密钥管理服务包装的数据密钥
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrappedKey {
+    /// 包装该密钥的服务
+    pub source: KeySource,
+    /// 服务返回的密文
+    pub blob: Vec<u8>,
+}
+
+impl WrappedKey {
+    fn source_code(&self) -> u8 {
+        match self.source {
+            KeySource::Passphrase => 0,
+            KeySource::AwsKms => 1,
+            KeySource::HashiCorpVault => 2,
+            KeySource::SshKeys => 3,
+            KeySource::Pkcs11 => 4,
+            KeySource::X25519 => 5,
+        }
+    }
+
+    fn source_from_code(code: u8) -> Option<KeySource> {
+        match code {
+            1 => Some(KeySource::AwsKms),
+            2 => Some(KeySource::HashiCorpVault),
+            3 => Some(KeySource::SshKeys),
+            4 => Some(KeySource::Pkcs11),
+            5 => Some(KeySource::X25519),
+            _ => None,
+        }
+    }
+}
+
+/// 发给硬件令牌的挑战，令牌的响应参与密钥派生
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenChallenge {
+    /// YubiKey槽位
+    pub slot: u8,
+    pub challenge: [u8; TOKEN_CHALLENGE_LEN],
+}
+
+/// 密钥文件的校验信息，用于在解密前发现缺少或用错的密钥文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyfileCheck {
+    pub salt: [u8; KEYFILE_SALT_LEN],
+    /// 以盐值为上下文的密钥文件哈希校验值
+    pub check: [u8; KEYFILE_SALT_LEN],
+}
+
+/// 加密时记录的明文总长度和数据块数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamTotals {
+    pub plaintext_len: u64,
+    pub chunks: u64,
+}
+
+impl StreamTotals {
+    /// 按块大小计算明文的总长度和块数
+    pub fn for_plaintext(plaintext_len: u64, chunk_size: usize) -> Self {
+        Self { plaintext_len, chunks: format::chunk_count(plaintext_len, chunk_size) }
+    }
+
+    /// 块数与明文长度是否一致（旧版本加密的空文件没有数据块）
+    pub fn is_consistent(&self, chunk_size: usize) -> bool {
+        *self == Self::for_plaintext(self.plaintext_len, chunk_size) || (self.plaintext_len == 0 && self.chunks == 0)
+    }
+
+    /// 盐值和全部数据块的长度
+    pub fn encrypted_len(&self, nonce_len: usize) -> u64 {
+        SALT_LEN as u64 + self.chunks * format::chunk_overhead(nonce_len) as u64 + self.plaintext_len
+    }
+}
+
+/// 加密前对明文的压缩
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub algorithm: CompressionAlgorithm,
+    /// 压缩前的明文长度
+    pub plaintext_len: u64,
+}
+
+/// 加密文件头
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileHeader {
+    /// 明文密码提示
+    pub hint: String,
+    /// 包装后的数据密钥，密钥来源为密码时为None
+    pub wrapped_key: Option<WrappedKey>,
+    /// 硬件令牌挑战，不使用令牌时为None
+    pub token_challenge: Option<TokenChallenge>,
+    /// 密钥文件的校验信息，不使用密钥文件时为None
+    pub keyfile_check: Option<KeyfileCheck>,
+    /// 加密时使用的密钥派生参数（含算法），使用默认参数时为None
+    pub kdf_params: Option<KdfParams>,
+    /// 批处理主密钥的盐值，每个文件单独派生密钥时为None
+    pub batch_salt: Option<[u8; BATCH_SALT_LEN]>,
+    /// 包装后的会话密钥，批处理主密钥直接由密码派生时为None
+    pub session_key: Option<[u8; WRAPPED_SESSION_KEY_LEN]>,
+    /// 级联加密的两层算法，只用一种算法时为None
+    pub cascade: Option<Cascade>,
+    /// 加密数据的算法（级联加密时为内层），插件算法不写入文件头，旧文件为None
+    pub algorithm: Option<EncryptionAlgorithm>,
+    /// 明文总长度和块数，有值时写为版本2的文件头；级联加密时描述外层
+    pub totals: Option<StreamTotals>,
+    /// 加密前的压缩，不压缩时为None。压缩后的长度事先未知，压缩的文件不记录总长度
+    pub compression: Option<Compression>,
+    /// 加密的原始文件名，加密文件名（随机输出文件名）时写入，解密时用它恢复文件名
+    pub encrypted_name: Option<Vec<u8>>,
+    /// 数据流的摘要块是否绑定文件头和最终明文，内置算法加密的新文件为true，压缩的文件必须为true
+    pub bound_digest: bool,
+}
+
+impl FileHeader {
+    /// 创建带密码提示的文件头，超长的提示按字符边界截断
+    pub fn with_hint(hint: &str) -> Self {
+        let mut end = hint.len().min(MAX_HINT_LEN);
+        while !hint.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            hint: hint[..end].to_string(),
+            wrapped_key: None,
+            token_challenge: None,
+            keyfile_check: None,
+            kdf_params: None,
+            batch_salt: None,
+            session_key: None,
+            cascade: None,
+            algorithm: None,
+            totals: None,
+            compression: None,
+            encrypted_name: None,
+            bound_digest: false,
+        }
+    }
+
+    /// 摘要块绑定的文件头和最终明文，旧文件返回None。绑定的是按字段重新编码的文件头，
+    /// 解析出的字段与加密时相同的文件头编码也相同，改动任何字段都会使摘要不符
+    pub fn digest_binding(&self) -> Option<DigestBinding> {
+        self.bound_digest.then(|| DigestBinding::new(&self.encode(), self.compression.is_some()))
+    }
+
+    /// 编码后的文件头
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(self.encoded_len() as usize);
+        self.write_to(&mut encoded).expect("writing to a Vec cannot fail");
+        encoded
+    }
+
+    /// 加密明文的算法：级联加密时是内层算法，未记录算法的文件使用 `default`
+    pub fn data_algorithm<'a>(&'a self, default: &'a EncryptionAlgorithm) -> &'a EncryptionAlgorithm {
+        match &self.cascade {
+            Some(cascade) => &cascade.inner,
+            None => self.algorithm.as_ref().unwrap_or(default),
+        }
+    }
+
+    /// 磁盘上数据块所用的算法：级联加密时是外层算法
+    pub fn disk_algorithm<'a>(&'a self, default: &'a EncryptionAlgorithm) -> &'a EncryptionAlgorithm {
+        match &self.cascade {
+            Some(cascade) => &cascade.outer,
+            None => self.data_algorithm(default),
+        }
+    }
+
+    fn algorithm_code(&self) -> Option<u8> {
+        self.algorithm.as_ref().and_then(algorithm_code)
+    }
+
+    /// 需要写入的扩展（类型, 数据）
+    fn extensions(&self) -> Vec<(u8, Vec<u8>)> {
+        let mut extensions = Vec::new();
+        if let Some(code) = self.kdf_params.and_then(|params| kdf_algorithm_code(params.algorithm)) {
+            extensions.push((EXT_KDF_ALGORITHM, vec![code]));
+        }
+        if let Some(keyfile) = &self.keyfile_check {
+            extensions.push((EXT_KEYFILE, [keyfile.salt, keyfile.check].concat()));
+        }
+        if let Some(compression) = &self.compression {
+            if let Some(code) = compression_code(compression.algorithm) {
+                extensions.push((EXT_COMPRESSION, [&[code][..], &compression.plaintext_len.to_le_bytes()].concat()));
+            }
+        }
+        if let Some(name) = &self.encrypted_name {
+            extensions.push((EXT_ORIGINAL_NAME, name.clone()));
+        }
+        if self.bound_digest {
+            extensions.push((EXT_BOUND_DIGEST, Vec::new()));
+        }
+        extensions
+    }
+
+    /// 编码后的长度
+    pub fn encoded_len(&self) -> u64 {
+        let wrapped_len = self.wrapped_key.as_ref().map_or(0, |key| 1 + 2 + key.blob.len());
+        let token_len = self.token_challenge.as_ref().map_or(0, |_| 1 + TOKEN_CHALLENGE_LEN);
+        let kdf_len = self.kdf_params.map_or(0, |_| KDF_PARAMS_LEN);
+        let batch_len = self.batch_salt.map_or(0, |_| BATCH_SALT_LEN) + self.session_key.map_or(0, |_| WRAPPED_SESSION_KEY_LEN);
+        let cascade_len = self.cascade.as_ref().map_or(0, |_| CASCADE_LEN);
+        let algorithm_len = self.algorithm_code().map_or(0, |_| 1);
+        let extensions = self.extensions();
+        let extensions_len = match extensions.len() {
+            0 => 0,
+            _ => 1 + extensions.iter().map(|(_, data)| 1 + 2 + data.len()).sum::<usize>(),
+        };
+        let totals_len = self.totals.map_or(0, |_| TOTALS_LEN);
+        (FIXED_LEN + self.hint.len() + wrapped_len + token_len + kdf_len + batch_len + cascade_len + algorithm_len + extensions_len + totals_len) as u64
+    }
+
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut flags = 0;
+        if self.wrapped_key.is_some() {
+            flags |= FLAG_WRAPPED_KEY;
+        }
+        if self.token_challenge.is_some() {
+            flags |= FLAG_TOKEN_CHALLENGE;
+        }
+        if self.kdf_params.is_some() {
+            flags |= FLAG_KDF_PARAMS;
+        }
+        if self.batch_salt.is_some() {
+            flags |= FLAG_BATCH_KEY;
+        }
+        if self.session_key.is_some() {
+            flags |= FLAG_SESSION_KEY;
+        }
+        if self.cascade.is_some() {
+            flags |= FLAG_CASCADE;
+        }
+        if self.algorithm_code().is_some() {
+            flags |= FLAG_ALGORITHM;
+        }
+        let extensions = self.extensions();
+        if !extensions.is_empty() {
+            flags |= FLAG_EXTENSIONS;
+        }
+        writer.write_all(&HEADER_MAGIC)?;
+        let version = if self.totals.is_some() { HEADER_VERSION } else { HEADER_VERSION_V1 };
+        writer.write_all(&[version, flags])?;
+        writer.write_all(&(self.hint.len() as u16).to_le_bytes())?;
+        writer.write_all(self.hint.as_bytes())?;
+        if let Some(key) = &self.wrapped_key {
+            writer.write_all(&[key.source_code()])?;
+            writer.write_all(&(key.blob.len() as u16).to_le_bytes())?;
+            writer.write_all(&key.blob)?;
+        }
+        if let Some(token) = &self.token_challenge {
+            writer.write_all(&[token.slot])?;
+            writer.write_all(&token.challenge)?;
+        }
+        if let Some(params) = &self.kdf_params {
+            for value in [params.memory_kib, params.iterations, params.parallelism] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        if let Some(salt) = &self.batch_salt {
+            writer.write_all(salt)?;
+        }
+        if let Some(session_key) = &self.session_key {
+            writer.write_all(session_key)?;
+        }
+        if let Some(cascade) = &self.cascade {
+            writer.write_all(&cascade.codes())?;
+        }
+        if let Some(code) = self.algorithm_code() {
+            writer.write_all(&[code])?;
+        }
+        if !extensions.is_empty() {
+            writer.write_all(&[extensions.len() as u8])?;
+            for (kind, data) in &extensions {
+                writer.write_all(&[*kind])?;
+                writer.write_all(&(data.len() as u16).to_le_bytes())?;
+                writer.write_all(data)?;
+            }
+        }
+        if let Some(totals) = &self.totals {
+            writer.write_all(&totals.plaintext_len.to_le_bytes())?;
+            writer.write_all(&totals.chunks.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// 读取文件头，返回文件头和定位到盐值处的读取器
+    ///
+    /// 没有文件头（旧格式）时返回None，已读取的字节会被放回返回的读取器中；
+    /// 既没有文件头也不像旧格式的数据返回 [`CryptoError::InvalidFormat`]
+    pub fn read_from<'a>(reader: &'a mut dyn Read) -> CryptoResult<(Option<Self>, Box<dyn Read + 'a>)> {
+        let mut prefix = [0u8; LEGACY_PREFIX_LEN];
+        let mut filled = read_full(reader, &mut prefix[..HEADER_MAGIC.len()])?;
+        if filled < HEADER_MAGIC.len() || prefix[..HEADER_MAGIC.len()] != HEADER_MAGIC {
+            filled += read_full(reader, &mut prefix[filled..])?;
+            if !is_legacy_prefix(&prefix[..filled]) {
+                return Err(CryptoError::InvalidFormat);
+            }
+            let prefix = Cursor::new(prefix[..filled].to_vec());
+            return Ok((None, Box::new(prefix.chain(reader))));
+        }
+
+        let mut fixed = [0u8; FIXED_LEN - HEADER_MAGIC.len()];
+        reader.read_exact(&mut fixed)
+            .map_err(|_| CryptoError::DecryptionError("文件头不完整".to_string()))?;
+        let version = fixed[0];
+        if !(HEADER_VERSION_V1..=HEADER_VERSION).contains(&version) {
+            return Err(CryptoError::DecryptionError(format!("不支持的文件头版本: {}", fixed[0])));
+        }
+        // 所有标志位都已使用，新字段通过扩展识别
+        if fixed[1] & FLAG_SESSION_KEY != 0 && fixed[1] & FLAG_BATCH_KEY == 0 {
+            return Err(CryptoError::DecryptionError(format!("不支持的文件头标志: {:#04x}", fixed[1])));
+        }
+        let hint_len = u16::from_le_bytes([fixed[2], fixed[3]]) as usize;
+        if hint_len > MAX_HINT_LEN {
+            return Err(CryptoError::DecryptionError(format!("密码提示长度无效: {}", hint_len)));
+        }
+
+        let mut hint = vec![0u8; hint_len];
+        reader.read_exact(&mut hint)
+            .map_err(|_| CryptoError::DecryptionError("密码提示被截断".to_string()))?;
+        let hint = String::from_utf8_lossy(&hint).into_owned();
+
+        let wrapped_key = if fixed[1] & FLAG_WRAPPED_KEY != 0 {
+            let mut prefix = [0u8; 3];
+            reader.read_exact(&mut prefix)
+                .map_err(|_| CryptoError::DecryptionError("包装密钥被截断".to_string()))?;
+            let source = WrappedKey::source_from_code(prefix[0])
+                .ok_or_else(|| CryptoError::DecryptionError(format!("未知的密钥来源: {}", prefix[0])))?;
+            let len = u16::from_le_bytes([prefix[1], prefix[2]]) as usize;
+            if len == 0 || len > MAX_WRAPPED_KEY_LEN {
+                return Err(CryptoError::DecryptionError(format!("包装密钥长度无效: {}", len)));
+            }
+            let mut blob = vec![0u8; len];
+            reader.read_exact(&mut blob)
+                .map_err(|_| CryptoError::DecryptionError("包装密钥被截断".to_string()))?;
+            Some(WrappedKey { source, blob })
+        } else {
+            None
+        };
+
+        let token_challenge = if fixed[1] & FLAG_TOKEN_CHALLENGE != 0 {
+            let mut data = [0u8; 1 + TOKEN_CHALLENGE_LEN];
+            reader.read_exact(&mut data)
+                .map_err(|_| CryptoError::DecryptionError("硬件令牌挑战被截断".to_string()))?;
+            let mut challenge = [0u8; TOKEN_CHALLENGE_LEN];
+            challenge.copy_from_slice(&data[1..]);
+            Some(TokenChallenge { slot: data[0], challenge })
+        } else {
+            None
+        };
+
+        let mut kdf_params = if fixed[1] & FLAG_KDF_PARAMS != 0 {
+            let mut data = [0u8; KDF_PARAMS_LEN];
+            reader.read_exact(&mut data)
+                .map_err(|_| CryptoError::DecryptionError("密钥派生参数被截断".to_string()))?;
+            let value = |index: usize| u32::from_le_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+            Some(KdfParams { memory_kib: value(0), iterations: value(1), parallelism: value(2), ..KdfParams::default() })
+        } else {
+            None
+        };
+
+        let batch_salt = if fixed[1] & FLAG_BATCH_KEY != 0 {
+            let mut salt = [0u8; BATCH_SALT_LEN];
+            reader.read_exact(&mut salt)
+                .map_err(|_| CryptoError::DecryptionError("批处理密钥盐值被截断".to_string()))?;
+            Some(salt)
+        } else {
+            None
+        };
+
+        let session_key = if fixed[1] & FLAG_SESSION_KEY != 0 {
+            let mut session_key = [0u8; WRAPPED_SESSION_KEY_LEN];
+            reader.read_exact(&mut session_key)
+                .map_err(|_| CryptoError::DecryptionError("会话密钥被截断".to_string()))?;
+            Some(session_key)
+        } else {
+            None
+        };
+
+        let cascade = if fixed[1] & FLAG_CASCADE != 0 {
+            let mut codes = [0u8; CASCADE_LEN];
+            reader.read_exact(&mut codes)
+                .map_err(|_| CryptoError::DecryptionError("级联算法被截断".to_string()))?;
+            Some(Cascade::from_codes(codes)
+                .ok_or_else(|| CryptoError::DecryptionError(format!("未知的级联算法: {:?}", codes)))?)
+        } else {
+            None
+        };
+
+        let algorithm = if fixed[1] & FLAG_ALGORITHM != 0 {
+            let mut code = [0u8; 1];
+            reader.read_exact(&mut code)
+                .map_err(|_| CryptoError::DecryptionError("算法代码被截断".to_string()))?;
+            Some(algorithm_from_code(code[0])
+                .ok_or_else(|| CryptoError::DecryptionError(format!("未知的加密算法: {}", code[0])))?)
+        } else {
+            None
+        };
+
+        let extensions = if fixed[1] & FLAG_EXTENSIONS != 0 {
+            read_extensions(reader)?
+        } else {
+            Vec::new()
+        };
+        let mut keyfile_check = None;
+        let mut compression = None;
+        let mut encrypted_name = None;
+        let mut bound_digest = false;
+        for (kind, data) in extensions {
+            match (kind, data.as_slice(), kdf_params.as_mut()) {
+                (EXT_KEYFILE, data, _) if data.len() == 2 * KEYFILE_SALT_LEN => {
+                    let (salt, check) = data.split_at(KEYFILE_SALT_LEN);
+                    keyfile_check = Some(KeyfileCheck { salt: salt.try_into().unwrap(), check: check.try_into().unwrap() });
+                }
+                (EXT_COMPRESSION, &[code, ref len @ ..], _) if len.len() == 8 => {
+                    let algorithm = compression_from_code(code)
+                        .ok_or_else(|| CryptoError::DecryptionError(format!("未知的压缩算法: {}", code)))?;
+                    compression = Some(Compression { algorithm, plaintext_len: u64::from_le_bytes(len.try_into().unwrap()) });
+                }
+                (EXT_ORIGINAL_NAME, data, _) if !data.is_empty() => encrypted_name = Some(data.to_vec()),
+                (EXT_BOUND_DIGEST, &[], _) => bound_digest = true,
+                (EXT_KDF_ALGORITHM, &[code], Some(params)) => {
+                    params.algorithm = kdf_algorithm_from_code(code)
+                        .ok_or_else(|| CryptoError::DecryptionError(format!("未知的密钥派生算法: {}", code)))?;
+                }
+                _ => return Err(CryptoError::DecryptionError(format!("不支持的文件头扩展: {:#04x}", kind))),
+            }
+        }
+
+        // 压缩信息决定如何还原明文，只接受由摘要块认证的压缩信息
+        if compression.is_some() && !bound_digest {
+            return Err(CryptoError::DecryptionError("压缩的文件缺少文件头认证".to_string()));
+        }
+
+        let totals = if version >= HEADER_VERSION {
+            let mut data = [0u8; TOTALS_LEN];
+            reader.read_exact(&mut data)
+                .map_err(|_| CryptoError::DecryptionError("文件长度信息被截断".to_string()))?;
+            let plaintext_len = u64::from_le_bytes(data[..8].try_into().unwrap());
+            let chunks = u64::from_le_bytes(data[8..].try_into().unwrap());
+            Some(StreamTotals { plaintext_len, chunks })
+        } else {
+            None
+        };
+
+        let header = Self {
+            hint, wrapped_key, token_challenge, keyfile_check, kdf_params, batch_salt, session_key, cascade, algorithm, totals, compression,
+            encrypted_name, bound_digest,
+        };
+        Ok((Some(header), Box::new(reader)))
+    }
+}
+
+/// 没有文件头的数据是否像旧格式：只有盐值（空文件），或盐值后是第一个数据块完整的nonce和长度字段。
+/// 旧格式的文件都由内置算法以默认块大小写出，长度字段超出范围的数据不是Krypton文件
+fn is_legacy_prefix(prefix: &[u8]) -> bool {
+    if prefix.len() == SALT_LEN {
+        return true;
+    }
+    let Some(length) = prefix.get(SALT_LEN + NONCE_LEN..LEGACY_PREFIX_LEN) else {
+        return false;
+    };
+    let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+    (TAG_LEN..=format::max_chunk_ciphertext(format::DEFAULT_CHUNK_SIZE)).contains(&length)
+}
+
+/// 读取扩展列表（类型, 数据）
+fn read_extensions(reader: &mut dyn Read) -> CryptoResult<Vec<(u8, Vec<u8>)>> {
+    let truncated = || CryptoError::DecryptionError("文件头扩展被截断".to_string());
+    let mut count = [0u8; 1];
+    reader.read_exact(&mut count).map_err(|_| truncated())?;
+    let mut extensions = Vec::with_capacity(count[0] as usize);
+    for _ in 0..count[0] {
+        let mut prefix = [0u8; 3];
+        reader.read_exact(&mut prefix).map_err(|_| truncated())?;
+        let mut data = vec![0u8; u16::from_le_bytes([prefix[1], prefix[2]]) as usize];
+        reader.read_exact(&mut data).map_err(|_| truncated())?;
+        extensions.push((prefix[0], data));
+    }
+    Ok(extensions)
+}
+
+/// 读取加密文件中的密码提示，没有提示时返回None
+pub fn read_hint(path: &Path) -> CryptoResult<Option<String>> {
+    let mut file = File::open(path)?;
+    let (header, _) = FileHeader::read_from(&mut file)?;
+    Ok(header.map(|header| header.hint).filter(|hint| !hint.is_empty()))
+}
+
+/// 尽量读满缓冲区，返回实际读取的字节数
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip_and_legacy() {
+        let mut data = Vec::new();
+        let header = FileHeader::with_hint("first pet");
+        header.write_to(&mut data).unwrap();
+        data.extend_from_slice(b"salt...");
+        assert_eq!(data.len() as u64, header.encoded_len() + 7);
+        assert_eq!(data[HEADER_MAGIC.len()], HEADER_VERSION_V1);
+
+        let mut reader = data.as_slice();
+        let (parsed, mut rest) = FileHeader::read_from(&mut reader).unwrap();
+        assert_eq!(parsed, Some(header));
+        let mut remaining = Vec::new();
+        rest.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, b"salt...");
+
+        // 带包装密钥、硬件令牌挑战、密钥文件校验值、Argon2参数、批处理盐值、会话密钥、级联算法和总长度（版本2）的文件头
+        let header = FileHeader {
+            wrapped_key: Some(WrappedKey { source: KeySource::HashiCorpVault, blob: b"vault:v1:abc".to_vec() }),
+            token_challenge: Some(TokenChallenge { slot: 2, challenge: [7; TOKEN_CHALLENGE_LEN] }),
+            keyfile_check: Some(KeyfileCheck { salt: [3; KEYFILE_SALT_LEN], check: [4; KEYFILE_SALT_LEN] }),
+            kdf_params: Some(KdfParams { memory_kib: 262_144, iterations: 3, parallelism: 1, ..KdfParams::default() }),
+            batch_salt: Some([9; BATCH_SALT_LEN]),
+            session_key: Some([5; WRAPPED_SESSION_KEY_LEN]),
+            cascade: Cascade::for_algorithm(&EncryptionAlgorithm::ChaCha20),
+            algorithm: Some(EncryptionAlgorithm::XChaCha20),
+            totals: Some(StreamTotals::for_plaintext(5 * 1024 * 1024 * 1024 + 1, 1024 * 1024)),
+            ..FileHeader::with_hint("")
+        };
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        assert_eq!(data.len() as u64, header.encoded_len());
+        assert_eq!(data[HEADER_MAGIC.len()], HEADER_VERSION);
+        let (parsed, _) = FileHeader::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(parsed, Some(header));
+
+        // 压缩信息、加密的原始文件名和摘要绑定写为扩展，压缩的文件必须绑定摘要
+        let header = FileHeader {
+            compression: Some(Compression { algorithm: CompressionAlgorithm::Lz4, plaintext_len: 1 << 40 }),
+            encrypted_name: Some(vec![6; 200]),
+            bound_digest: true,
+            ..FileHeader::with_hint("")
+        };
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        assert_eq!(data.len() as u64, header.encoded_len());
+        let (parsed, _) = FileHeader::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(parsed, Some(header.clone()));
+        let unbound = FileHeader { bound_digest: false, ..header };
+        assert!(FileHeader::read_from(&mut unbound.encode().as_slice()).is_err());
+
+        // 非默认的密钥派生算法写为扩展
+        let header = FileHeader { kdf_params: Some(KdfParams::for_algorithm(KdfAlgorithm::Scrypt)), ..FileHeader::with_hint("") };
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        assert_eq!(data.len() as u64, header.encoded_len());
+        assert_ne!(data[HEADER_MAGIC.len() + 1] & FLAG_EXTENSIONS, 0);
+        let (parsed, _) = FileHeader::read_from(&mut data.as_slice()).unwrap();
+        assert_eq!(parsed, Some(header));
+        *data.last_mut().unwrap() = 9;
+        assert!(FileHeader::read_from(&mut data.as_slice()).is_err());
+
+        // 旧格式：已读取的字节必须原样放回
+        let mut legacy = vec![3u8; SALT_LEN + NONCE_LEN];
+        legacy.extend_from_slice(&100u32.to_le_bytes());
+        legacy.extend_from_slice(&[4u8; 100]);
+        let mut reader = legacy.as_slice();
+        let (parsed, mut rest) = FileHeader::read_from(&mut reader).unwrap();
+        assert!(parsed.is_none());
+        let mut remaining = Vec::new();
+        rest.read_to_end(&mut remaining).unwrap();
+        assert_eq!(remaining, legacy);
+
+        // 既没有魔数也不像旧格式的数据不是Krypton文件
+        for data in [&b"random salt bytes"[..], &[0xff; 64], b""] {
+            assert!(matches!(FileHeader::read_from(&mut &data[..]), Err(CryptoError::InvalidFormat)));
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! 文件处理钩子
+//!
+//! 调用方可以在每个文件处理前、成功后和失败后执行回调，例如输出一完成就上传，
+//! 或者在外部数据库中标记文件。钩子在处理该文件的工作线程中同步调用，耗时的操作会拖慢整个批处理；
+//! 钩子不能中止处理，也不影响文件的结果
+
+use crate::models::FileItem;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+type BeforeFileHook = Arc<dyn Fn(&FileItem) + Send + Sync>;
+type SuccessHook = Arc<dyn Fn(&FileItem, &Path) + Send + Sync>;
+type FailureHook = Arc<dyn Fn(&FileItem, &str) + Send + Sync>;
+
+/// 一组文件处理钩子，同一类钩子按注册顺序调用
+#[derive(Clone, Default)]
+pub struct FileHooks {
+    before_file: Vec<BeforeFileHook>,
+    after_success: Vec<SuccessHook>,
+    after_failure: Vec<FailureHook>,
+}
+
+impl FileHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始处理文件之前调用
+    pub fn before_file(mut self, hook: impl Fn(&FileItem) + Send + Sync + 'static) -> Self {
+        self.before_file.push(Arc::new(hook));
+        self
+    }
+
+    /// 文件处理成功后调用，参数为输出文件路径
+    pub fn after_success(mut self, hook: impl Fn(&FileItem, &Path) + Send + Sync + 'static) -> Self {
+        self.after_success.push(Arc::new(hook));
+        self
+    }
+
+    /// 文件处理失败后调用，参数为错误信息
+    pub fn after_failure(mut self, hook: impl Fn(&FileItem, &str) + Send + Sync + 'static) -> Self {
+        self.after_failure.push(Arc::new(hook));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before_file.is_empty() && self.after_success.is_empty() && self.after_failure.is_empty()
+    }
+
+    /// 在钩子之间执行文件处理，返回处理结果
+    pub(crate) fn run<T: AsRef<Path>>(&self, file: &FileItem, process: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+        for hook in &self.before_file {
+            hook(file);
+        }
+        let result = process();
+        match &result {
+            Ok(output) => self.after_success.iter().for_each(|hook| hook(file, output.as_ref())),
+            Err(error) => self.after_failure.iter().for_each(|hook| hook(file, error)),
+        }
+        result
+    }
+}
+
+impl fmt::Debug for FileHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileHooks")
+            .field("before_file", &self.before_file.len())
+            .field("after_success", &self.after_success.len())
+            .field("after_failure", &self.after_failure.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_hooks_run_around_processing() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (before, success, failure) = (calls.clone(), calls.clone(), calls.clone());
+        let hooks = FileHooks::new()
+            .before_file(move |file| before.lock().unwrap().push(format!("before {}", file.name)))
+            .after_success(move |_, output| success.lock().unwrap().push(format!("done {}", output.display())))
+            .after_failure(move |_, error| failure.lock().unwrap().push(format!("failed {}", error)));
+        let file = FileItem::new(PathBuf::from("a.txt"), "a.txt".to_string());
+
+        assert!(hooks.run(&file, || Ok(PathBuf::from("a.enc"))).is_ok());
+        assert!(hooks.run(&file, || Err::<PathBuf, _>("disk full".to_string())).is_err());
+        assert_eq!(*calls.lock().unwrap(), ["before a.txt", "done a.enc", "before a.txt", "failed disk full"]);
+    }
+}
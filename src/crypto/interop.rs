@@ -0,0 +1,59 @@
+//! 与其他工具互通的格式（age、OpenSSL `enc`）
+//!
+//! 这些文件没有Krypton文件头，整个文件由对应的提供者读写。解密时引擎按文件开头识别格式，
+//! 与设置中选择的算法无关
+
+use super::{age, format, openssl};
+use crate::models::EncryptionAlgorithm;
+use std::io::{self, Read};
+
+/// 读取开头判断是否为互通格式，返回识别出的算法和放回已读字节的reader
+pub fn detect<'a>(mut reader: Box<dyn Read + 'a>) -> io::Result<(Option<EncryptionAlgorithm>, Box<dyn Read + 'a>)> {
+    let mut prefix = vec![0u8; age::AGE_MAGIC.len().max(openssl::OPENSSL_MAGIC.len())];
+    let len = format::read_chunk(&mut reader, &mut prefix)?;
+    prefix.truncate(len);
+    let algorithm = if age::is_age(&prefix) {
+        Some(EncryptionAlgorithm::Age)
+    } else if openssl::is_openssl(&prefix) {
+        Some(EncryptionAlgorithm::OpenSsl)
+    } else {
+        None
+    };
+    Ok((algorithm, Box::new(io::Cursor::new(prefix).chain(reader))))
+}
+
+/// 明文加密为互通格式后的大小
+pub fn encrypted_size(algorithm: &EncryptionAlgorithm, plaintext_len: u64) -> u64 {
+    match algorithm {
+        EncryptionAlgorithm::OpenSsl => openssl::encrypted_size(plaintext_len),
+        _ => age::encrypted_size(plaintext_len),
+    }
+}
+
+/// 互通格式文件中明文长度的上限
+pub fn max_plaintext_size(algorithm: &EncryptionAlgorithm, file_len: u64) -> u64 {
+    match algorithm {
+        EncryptionAlgorithm::OpenSsl => openssl::max_plaintext_size(file_len),
+        _ => age::max_plaintext_size(file_len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_restores_prefix() {
+        for (data, expected) in [
+            (&b"age-encryption.org/v1\n-> scrypt"[..], Some(EncryptionAlgorithm::Age)),
+            (&b"Salted__12345678"[..], Some(EncryptionAlgorithm::OpenSsl)),
+            (&b"Salt"[..], None),
+        ] {
+            let (algorithm, mut reader) = detect(Box::new(data)).unwrap();
+            assert_eq!(algorithm, expected);
+            let mut restored = Vec::new();
+            reader.read_to_end(&mut restored).unwrap();
+            assert_eq!(restored, data);
+        }
+    }
+}
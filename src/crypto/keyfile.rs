@@ -0,0 +1,100 @@
+//! 密钥文件参与的密钥派生
+//!
+//! 密钥文件的SHA-256与密码一起经HKDF-SHA256得到新的密码，再交给加密提供者派生密钥。
+//! 文件头中记录随机盐值和密钥文件哈希的校验值，解密时据此区分缺少密钥文件、密钥文件不匹配和密码错误
+
+use super::header::{KeyfileCheck, KEYFILE_SALT_LEN};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const MIX_INFO: &[u8] = b"krypton keyfile v1";
+const CHECK_CONTEXT: &str = "krypton keyfile check v1";
+
+/// 为新文件混合密钥文件，返回混合后的密码和需要写入文件头的校验值
+pub fn bind_password(keyfile: Option<&Path>, password: &str) -> Result<(String, Option<KeyfileCheck>), String> {
+    let Some(keyfile) = keyfile else {
+        return Ok((password.to_string(), None));
+    };
+    let hash = hash_keyfile(keyfile)?;
+    let salt = rand::random::<[u8; KEYFILE_SALT_LEN]>();
+    Ok((mix_keyfile(password, &hash), Some(KeyfileCheck { salt, check: check_value(&salt, &hash) })))
+}
+
+/// 解密时使用的密码：文件头中有校验值时读取密钥文件、核对后混合
+pub fn unlock_password(keyfile: Option<&Path>, password: &str, check: Option<&KeyfileCheck>) -> Result<String, String> {
+    let Some(check) = check else {
+        return Ok(password.to_string());
+    };
+    let keyfile = keyfile.ok_or_else(|| "This file was encrypted with a keyfile, select it to decrypt".to_string())?;
+    let hash = hash_keyfile(keyfile)?;
+    if check_value(&check.salt, &hash) != check.check {
+        return Err(format!("Keyfile '{}' does not match the one used for encryption", keyfile.display()));
+    }
+    Ok(mix_keyfile(password, &hash))
+}
+
+/// 密钥文件内容的SHA-256
+fn hash_keyfile(path: &Path) -> Result<[u8; 32], String> {
+    let read = || -> io::Result<[u8; 32]> {
+        let mut file = File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            match file.read(&mut buffer)? {
+                0 => return Ok(hasher.finalize().into()),
+                n => hasher.update(&buffer[..n]),
+            }
+        }
+    };
+    read().map_err(|e| format!("Cannot read keyfile '{}': {}", path.display(), e))
+}
+
+/// HKDF-SHA256(密码 || 密钥文件哈希) 的十六进制形式代替密码
+fn mix_keyfile(password: &str, hash: &[u8; 32]) -> String {
+    let mut ikm = password.as_bytes().to_vec();
+    ikm.extend_from_slice(hash);
+    let mut output = [0u8; 32];
+    Hkdf::<Sha256>::new(None, &ikm)
+        .expand(MIX_INFO, &mut output)
+        .expect("32 bytes is a valid HKDF output length");
+    hex::encode(output)
+}
+
+/// 以盐值为上下文的密钥文件哈希校验值，不泄露哈希本身
+fn check_value(salt: &[u8; KEYFILE_SALT_LEN], hash: &[u8; 32]) -> [u8; KEYFILE_SALT_LEN] {
+    let mut hasher = blake3::Hasher::new_derive_key(CHECK_CONTEXT);
+    hasher.update(salt);
+    hasher.update(hash);
+    let mut check = [0u8; KEYFILE_SALT_LEN];
+    check.copy_from_slice(&hasher.finalize().as_bytes()[..KEYFILE_SALT_LEN]);
+    check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use std::fs;
+
+    #[test]
+    fn test_keyfile_binding() {
+        let dir = TestDir::new("keyfile");
+        let keyfile = dir.join("key.bin");
+        let other = dir.join("other.bin");
+        fs::write(&keyfile, b"keyfile contents").unwrap();
+        fs::write(&other, b"something else").unwrap();
+
+        assert_eq!(bind_password(None, "pw").unwrap(), ("pw".to_string(), None));
+        let (mixed, check) = bind_password(Some(&keyfile), "pw").unwrap();
+        let check = check.unwrap();
+        assert_ne!(mixed, "pw");
+        assert_eq!(unlock_password(Some(&keyfile), "pw", Some(&check)).unwrap(), mixed);
+        assert_ne!(unlock_password(Some(&keyfile), "other", Some(&check)).unwrap(), mixed);
+        assert!(unlock_password(None, "pw", Some(&check)).is_err());
+        assert!(unlock_password(Some(&other), "pw", Some(&check)).is_err());
+        assert!(unlock_password(Some(&dir.join("missing")), "pw", Some(&check)).is_err());
+    }
+}
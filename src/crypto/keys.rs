@@ -0,0 +1,324 @@
+//! X25519公钥接收者
+//!
+//! 每个文件的随机数据密钥分别为每个接收者包装：与临时X25519密钥做ECDH，经HKDF派生包装密钥，
+//! 用ChaCha20-Poly1305加密数据密钥。持有任一接收者私钥的人都能解密，不需要共享密码。
+//! 与age/LUKS的密钥槽类似，还可以再添加一个密码槽位：数据密钥另用Argon2id从密码派生的密钥包装一份。
+//! 公钥和私钥以带前缀的base64文本导入导出，私钥文件只包含一行私钥（可带#注释）
+
+use super::header::WrappedKey;
+use super::kms::{DataKey, KeyManagementService};
+use super::traits::{Argon2KeyDerivation, KeyDerivation};
+use crate::models::{KeySource, KmsSettings};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// 公钥的文本前缀
+pub const PUBLIC_KEY_PREFIX: &str = "krypton-x25519:";
+/// 私钥的文本前缀
+pub const SECRET_KEY_PREFIX: &str = "KRYPTON-X25519-SECRET:";
+/// 数据密钥长度
+const FILE_KEY_LEN: usize = 32;
+/// 接收者标识的长度（公钥SHA-256的前几个字节）
+const TAG_LEN: usize = 4;
+/// 每个接收者：标识 + 临时公钥 + 包装后的数据密钥
+const STANZA_LEN: usize = TAG_LEN + 32 + FILE_KEY_LEN + 16;
+const WRAP_INFO: &[u8] = b"krypton-x25519";
+/// 密码槽位的盐值长度
+const PASSWORD_SALT_LEN: usize = 16;
+/// 密码槽位：盐值 + 包装后的数据密钥
+const PASSWORD_SLOT_LEN: usize = PASSWORD_SALT_LEN + FILE_KEY_LEN + 16;
+
+/// 接收者的公钥
+#[derive(Clone, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Recipient {
+    /// 解析 `krypton-x25519:<base64>` 形式的公钥
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bytes = decode_key(text.trim(), PUBLIC_KEY_PREFIX)
+            .ok_or_else(|| format!("Invalid X25519 public key '{}'", shorten(text.trim())))?;
+        Ok(Self(PublicKey::from(bytes)))
+    }
+
+    fn tag(&self) -> [u8; TAG_LEN] {
+        let hash = Sha256::digest(self.0.as_bytes());
+        let mut tag = [0u8; TAG_LEN];
+        tag.copy_from_slice(&hash[..TAG_LEN]);
+        tag
+    }
+
+    /// 为该接收者包装数据密钥
+    fn wrap(&self, file_key: &[u8; FILE_KEY_LEN]) -> Result<Vec<u8>, String> {
+        let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&self.0);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&wrap_key(shared.as_bytes(), &ephemeral_public, &self.0)));
+        let sealed = cipher.encrypt(Nonce::from_slice(&[0u8; 12]), file_key.as_slice())
+            .map_err(|_| "Failed to wrap the file key".to_string())?;
+
+        let mut stanza = self.tag().to_vec();
+        stanza.extend_from_slice(ephemeral_public.as_bytes());
+        stanza.extend_from_slice(&sealed);
+        Ok(stanza)
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", PUBLIC_KEY_PREFIX, BASE64.encode(self.0.as_bytes()))
+    }
+}
+
+impl fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recipient({})", self)
+    }
+}
+
+/// 自己的私钥
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    /// 生成新的密钥对
+    pub fn generate() -> Self {
+        Self(StaticSecret::from(rand::random::<[u8; 32]>()))
+    }
+
+    /// 解析 `KRYPTON-X25519-SECRET:<base64>` 形式的私钥
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bytes = decode_key(text.trim(), SECRET_KEY_PREFIX).ok_or("Invalid X25519 secret key")?;
+        Ok(Self(StaticSecret::from(bytes)))
+    }
+
+    /// 读取私钥文件，忽略空行和#注释
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read X25519 secret key '{}': {}", path.display(), e))?;
+        let line = text.lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .ok_or_else(|| format!("'{}' does not contain an X25519 secret key", path.display()))?;
+        Self::parse(line).map_err(|e| format!("{} in '{}'", e, path.display()))
+    }
+
+    /// 写出私钥文件（Unix上只有所有者可读），注释中附带对应的公钥
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = format!("# public key: {}\n{}\n", self.recipient(), self.export_secret());
+        write_private(path, content.as_bytes())
+            .map_err(|e| format!("Failed to write X25519 secret key '{}': {}", path.display(), e))
+    }
+
+    /// 导出私钥文本
+    pub fn export_secret(&self) -> String {
+        format!("{}{}", SECRET_KEY_PREFIX, BASE64.encode(self.0.to_bytes()))
+    }
+
+    /// 对应的公钥
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// 解开发给自己的数据密钥，不是发给自己时返回None
+    fn unwrap(&self, stanza: &[u8]) -> Option<Vec<u8>> {
+        let recipient = self.recipient();
+        if stanza[..TAG_LEN] != recipient.tag() {
+            return None;
+        }
+        let mut ephemeral = [0u8; 32];
+        ephemeral.copy_from_slice(&stanza[TAG_LEN..TAG_LEN + 32]);
+        let ephemeral = PublicKey::from(ephemeral);
+        let shared = self.0.diffie_hellman(&ephemeral);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&wrap_key(shared.as_bytes(), &ephemeral, &recipient.0)));
+        cipher.decrypt(Nonce::from_slice(&[0u8; 12]), &stanza[TAG_LEN + 32..]).ok()
+    }
+}
+
+/// 解析接收者列表（每行一个公钥，忽略空行和#注释）
+pub fn parse_recipients(text: &str) -> Result<Vec<Recipient>, String> {
+    let recipients = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Recipient::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    match recipients.len() {
+        0 => Err("No X25519 recipients".to_string()),
+        n if n > u8::MAX as usize => Err(format!("Too many X25519 recipients ({}, at most {})", n, u8::MAX)),
+        _ => Ok(recipients),
+    }
+}
+
+/// X25519接收者作为密钥来源
+pub struct X25519Recipients {
+    recipients: String,
+    identity: String,
+    /// 加密时是否添加密码槽位
+    password_slot: bool,
+    password: String,
+}
+
+impl X25519Recipients {
+    pub fn new(settings: &KmsSettings, password: &str) -> Self {
+        Self {
+            recipients: settings.x25519_recipients.clone(),
+            identity: settings.x25519_identity.trim().to_string(),
+            password_slot: settings.x25519_password_slot,
+            password: password.to_string(),
+        }
+    }
+
+    /// 用密码包装数据密钥：盐值(16) + 包装后的数据密钥(48)
+    fn wrap_with_password(&self, file_key: &[u8; FILE_KEY_LEN]) -> Result<Vec<u8>, String> {
+        let salt: [u8; PASSWORD_SALT_LEN] = rand::random();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&password_key(&self.password, &salt)?));
+        let sealed = cipher.encrypt(Nonce::from_slice(&[0u8; 12]), file_key.as_slice())
+            .map_err(|_| "Failed to wrap the file key".to_string())?;
+        Ok([salt.as_slice(), &sealed].concat())
+    }
+
+    fn unwrap_with_password(&self, slot: &[u8]) -> Result<Vec<u8>, String> {
+        let (salt, sealed) = slot.split_at(PASSWORD_SALT_LEN);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&password_key(&self.password, salt)?));
+        cipher.decrypt(Nonce::from_slice(&[0u8; 12]), sealed)
+            .map_err(|_| "Wrong password for the password slot".to_string())
+    }
+}
+
+impl KeyManagementService for X25519Recipients {
+    fn generate_data_key(&self) -> Result<DataKey, String> {
+        let recipients = parse_recipients(&self.recipients)?;
+        let file_key: [u8; FILE_KEY_LEN] = rand::random();
+        // 结构：接收者数(1)，之后是每个接收者的标识(4) + 临时公钥(32) + 包装后的数据密钥(48)，最后是可选的密码槽位
+        let mut blob = vec![recipients.len() as u8];
+        for recipient in &recipients {
+            blob.extend_from_slice(&recipient.wrap(&file_key)?);
+        }
+        if self.password_slot {
+            if self.password.is_empty() {
+                return Err("The password slot needs a password".to_string());
+            }
+            blob.extend_from_slice(&self.wrap_with_password(&file_key)?);
+        }
+        Ok(DataKey {
+            password: hex::encode(file_key),
+            wrapped: WrappedKey { source: KeySource::X25519, blob },
+        })
+    }
+
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String> {
+        let invalid = || "The wrapped key is not a list of X25519 recipients".to_string();
+        let (&count, rest) = blob.split_first().ok_or_else(invalid)?;
+        let (stanzas, password_slot) = rest.split_at_checked(count as usize * STANZA_LEN).ok_or_else(invalid)?;
+        let password_slot = match password_slot.len() {
+            0 => None,
+            PASSWORD_SLOT_LEN => Some(password_slot),
+            _ => return Err(invalid()),
+        };
+
+        // 优先使用私钥，没有私钥或私钥不匹配时再尝试密码槽位
+        let key = if self.identity.is_empty() {
+            None
+        } else {
+            let identity = Identity::load(Path::new(&self.identity))?;
+            stanzas.chunks_exact(STANZA_LEN).find_map(|stanza| identity.unwrap(stanza))
+        };
+        let key = match (key, password_slot) {
+            (Some(key), _) => key,
+            (None, Some(slot)) if !self.password.is_empty() => self.unwrap_with_password(slot)?,
+            (None, _) if self.identity.is_empty() => {
+                return Err(match password_slot {
+                    Some(_) => "Select your X25519 secret key file or enter the password".to_string(),
+                    None => "Select your X25519 secret key file".to_string(),
+                });
+            }
+            (None, _) => return Err("The file was not encrypted to this X25519 key".to_string()),
+        };
+        Some(key)
+            .filter(|key| key.len() == FILE_KEY_LEN)
+            .map(hex::encode)
+            .ok_or_else(invalid)
+    }
+}
+
+fn wrap_key(shared: &[u8], ephemeral: &PublicKey, recipient: &PublicKey) -> [u8; 32] {
+    let salt = [ephemeral.as_bytes().as_slice(), recipient.as_bytes()].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// 密码槽位的包装密钥：使用默认参数的Argon2id
+fn password_key(password: &str, salt: &[u8]) -> Result<Vec<u8>, String> {
+    Argon2KeyDerivation::default().derive_key(password, salt).map_err(|e| e.to_string())
+}
+
+fn decode_key(text: &str, prefix: &str) -> Option<[u8; 32]> {
+    let encoded = text.strip_prefix(prefix)?;
+    BASE64.decode(encoded).ok()?.try_into().ok()
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?.write_all(content)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    fs::write(path, content)
+}
+
+/// 错误信息中只显示公钥的开头
+fn shorten(line: &str) -> String {
+    line.chars().take(40).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_x25519_recipient_round_trip() {
+        let dir = TestDir::new("x25519");
+        let (alice, bob, mallory) = (Identity::generate(), Identity::generate(), Identity::generate());
+        let recipients = format!("{}\n# colleague\n{}\n", alice.recipient(), bob.recipient());
+        assert_eq!(Recipient::parse(&alice.recipient().to_string()).unwrap(), alice.recipient());
+        assert_eq!(Identity::parse(&alice.export_secret()).unwrap().recipient(), alice.recipient());
+        assert!(parse_recipients("krypton-x25519:AAAA").is_err());
+
+        let settings_for = |identity: &Identity, name: &str| {
+            let path = dir.join(name);
+            identity.save(&path).unwrap();
+            KmsSettings {
+                x25519_recipients: recipients.clone(),
+                x25519_identity: path.to_string_lossy().to_string(),
+                x25519_password_slot: true,
+                ..KmsSettings::default()
+            }
+        };
+
+        // 两个公钥槽位加一个密码槽位，任何一个都能解开数据密钥
+        let data_key = X25519Recipients::new(&settings_for(&alice, "alice"), "pw").generate_data_key().unwrap();
+        for (identity, name) in [(&alice, "alice"), (&bob, "bob")] {
+            let password = X25519Recipients::new(&settings_for(identity, name), "").unwrap_key(&data_key.wrapped.blob).unwrap();
+            assert_eq!(password, data_key.password);
+        }
+        assert!(X25519Recipients::new(&settings_for(&mallory, "mallory"), "").unwrap_key(&data_key.wrapped.blob).is_err());
+        let password_only = KmsSettings::default();
+        assert_eq!(X25519Recipients::new(&password_only, "pw").unwrap_key(&data_key.wrapped.blob).unwrap(), data_key.password);
+        assert!(X25519Recipients::new(&password_only, "wrong").unwrap_key(&data_key.wrapped.blob).is_err());
+        assert!(X25519Recipients::new(&password_only, "").unwrap_key(&data_key.wrapped.blob).is_err());
+        assert!(X25519Recipients::new(&settings_for(&alice, "alice"), "").generate_data_key().is_err());
+    }
+}
@@ -0,0 +1,365 @@
+//! 外部密钥管理服务（信封加密）
+//!
+//! 每个文件使用由KMS生成的随机数据密钥加密，包装后的数据密钥保存在文件头中，解密时再交给KMS解包。
+//! 数据密钥以十六进制字符串代替密码传给加密提供者
+
+use super::header::WrappedKey;
+use super::keys::X25519Recipients;
+use super::pkcs11::Pkcs11Token;
+use super::ssh::SshRecipients;
+use crate::models::{KeySource, KmsSettings};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// 未填写私有访问密钥时读取的环境变量
+pub const AWS_SECRET_ENV: &str = "AWS_SECRET_ACCESS_KEY";
+/// 使用临时凭证时的会话令牌环境变量
+pub const AWS_SESSION_TOKEN_ENV: &str = "AWS_SESSION_TOKEN";
+/// 未填写令牌时读取的环境变量
+pub const VAULT_TOKEN_ENV: &str = "VAULT_TOKEN";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 新生成的数据密钥
+pub struct DataKey {
+    /// 传给加密提供者的密码（数据密钥的十六进制形式）
+    pub password: String,
+    pub wrapped: WrappedKey,
+}
+
+/// 外部密钥管理服务
+pub trait KeyManagementService: Send + Sync {
+    /// 生成新的数据密钥，返回明文和包装后的密钥
+    fn generate_data_key(&self) -> Result<DataKey, String>;
+
+    /// 解包数据密钥，返回传给加密提供者的密码
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String>;
+}
+
+/// 按设置创建密钥管理服务，`password` 用于X25519接收者的密码槽位
+pub fn create_kms(source: KeySource, settings: &KmsSettings, password: &str) -> Result<Box<dyn KeyManagementService>, String> {
+    match source {
+        KeySource::Passphrase => Err("The key source is a passphrase, not a key management service".to_string()),
+        KeySource::AwsKms => Ok(Box::new(AwsKms::new(settings)?)),
+        KeySource::HashiCorpVault => Ok(Box::new(VaultTransit::new(settings)?)),
+        KeySource::SshKeys => Ok(Box::new(SshRecipients::new(settings))),
+        KeySource::Pkcs11 => Ok(Box::new(Pkcs11Token::new(settings))),
+        KeySource::X25519 => Ok(Box::new(X25519Recipients::new(settings, password))),
+    }
+}
+
+/// 加密单个文件时使用的密码，以及需要写入文件头的包装密钥
+pub fn encryption_key(source: KeySource, settings: &KmsSettings, password: &str) -> Result<(String, Option<WrappedKey>), String> {
+    if source == KeySource::Passphrase {
+        return Ok((password.to_string(), None));
+    }
+    let key = create_kms(source, settings, password)?.generate_data_key()?;
+    Ok((key.password, Some(key.wrapped)))
+}
+
+/// 解密单个文件时使用的密码：文件头中有包装密钥时交给对应的服务解包
+pub fn decryption_password(
+    source: KeySource,
+    settings: &KmsSettings,
+    password: &str,
+    wrapped: Option<&WrappedKey>,
+) -> Result<String, String> {
+    let Some(wrapped) = wrapped else {
+        return Ok(password.to_string());
+    };
+    if wrapped.source != source {
+        return Err(format!("The file key is protected by {}; select it as the key source", wrapped.source));
+    }
+    create_kms(source, settings, password)?.unwrap_key(&wrapped.blob)
+}
+
+fn http_agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build()
+}
+
+/// 把HTTP错误转换为带服务返回内容的错误信息
+fn request_error(service: &str, error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("{} request failed ({}): {}", service, code, body.trim())
+        }
+        ureq::Error::Transport(e) => format!("{} request failed: {}", service, e),
+    }
+}
+
+fn secret_or_env(secret: &str, env: &str) -> Option<String> {
+    Some(secret.to_string())
+        .filter(|secret| !secret.is_empty())
+        .or_else(|| std::env::var(env).ok().filter(|value| !value.is_empty()))
+}
+
+/// AWS KMS（通过JSON API，使用Signature Version 4签名）
+pub struct AwsKms {
+    endpoint: String,
+    host: String,
+    region: String,
+    key_id: String,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsKeyResponse {
+    plaintext: String,
+    #[serde(default)]
+    ciphertext_blob: String,
+}
+
+impl AwsKms {
+    pub fn new(settings: &KmsSettings) -> Result<Self, String> {
+        let region = settings.region.trim().to_string();
+        let endpoint = match settings.endpoint.trim() {
+            "" => format!("https://kms.{}.amazonaws.com", region),
+            endpoint => endpoint.trim_end_matches('/').to_string(),
+        };
+        let host = endpoint.split("://").nth(1).unwrap_or(&endpoint)
+            .split('/').next().unwrap_or_default()
+            .to_string();
+        let access_key_id = Some(settings.access_key_id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok())
+            .ok_or("AWS access key ID is empty")?;
+        let secret_access_key = secret_or_env(&settings.secret, AWS_SECRET_ENV)
+            .ok_or_else(|| format!("AWS secret access key is empty (set it or {})", AWS_SECRET_ENV))?;
+
+        Ok(Self {
+            endpoint,
+            host,
+            region,
+            key_id: settings.key_id.trim().to_string(),
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var(AWS_SESSION_TOKEN_ENV).ok().filter(|token| !token.is_empty()),
+        })
+    }
+
+    /// 调用KMS的一个操作（例如 GenerateDataKey）
+    fn call(&self, action: &str, body: serde_json::Value) -> Result<AwsKeyResponse, String> {
+        let body = body.to_string();
+        let target = format!("TrentService.{}", action);
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("content-type".to_string(), "application/x-amz-json-1.1".to_string()),
+            ("host".to_string(), self.host.clone()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+            ("x-amz-target".to_string(), target),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        headers.sort();
+        let authorization = sigv4_authorization(&SigningRequest {
+            access_key_id: &self.access_key_id,
+            secret_access_key: &self.secret_access_key,
+            region: &self.region,
+            service: "kms",
+            amz_date: &amz_date,
+            method: "POST",
+            canonical_uri: "/",
+            canonical_query: "",
+            headers: &headers,
+            payload: body.as_bytes(),
+        });
+
+        let mut request = http_agent().post(&format!("{}/", self.endpoint));
+        for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+            request = request.set(name, value);
+        }
+        request.set("authorization", &authorization)
+            .send_string(&body)
+            .map_err(|e| request_error("AWS KMS", e))?
+            .into_json()
+            .map_err(|e| format!("AWS KMS returned an invalid response: {}", e))
+    }
+}
+
+impl KeyManagementService for AwsKms {
+    fn generate_data_key(&self) -> Result<DataKey, String> {
+        let response = self.call("GenerateDataKey", serde_json::json!({ "KeyId": self.key_id, "KeySpec": "AES_256" }))?;
+        let plaintext = BASE64.decode(&response.plaintext).map_err(|e| format!("Invalid data key from AWS KMS: {}", e))?;
+        let blob = BASE64.decode(&response.ciphertext_blob).map_err(|e| format!("Invalid data key from AWS KMS: {}", e))?;
+        Ok(DataKey {
+            password: hex::encode(plaintext),
+            wrapped: WrappedKey { source: KeySource::AwsKms, blob },
+        })
+    }
+
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String> {
+        let response = self.call("Decrypt", serde_json::json!({ "KeyId": self.key_id, "CiphertextBlob": BASE64.encode(blob) }))?;
+        let plaintext = BASE64.decode(&response.plaintext).map_err(|e| format!("Invalid data key from AWS KMS: {}", e))?;
+        Ok(hex::encode(plaintext))
+    }
+}
+
+/// Signature Version 4 签名所需的请求信息，`headers` 须为小写名称并已排序
+struct SigningRequest<'a> {
+    access_key_id: &'a str,
+    secret_access_key: &'a str,
+    region: &'a str,
+    service: &'a str,
+    /// 格式为 YYYYMMDD'T'HHMMSS'Z'
+    amz_date: &'a str,
+    method: &'a str,
+    canonical_uri: &'a str,
+    canonical_query: &'a str,
+    headers: &'a [(String, String)],
+    payload: &'a [u8],
+}
+
+/// 生成AWS Signature Version 4 的Authorization头
+fn sigv4_authorization(request: &SigningRequest) -> String {
+    let date = &request.amz_date[..8];
+    let canonical_headers: String = request.headers.iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = request.headers.iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        request.canonical_uri,
+        request.canonical_query,
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(request.payload))
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, request.region, request.service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        request.amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac_sha256(format!("AWS4{}", request.secret_access_key).as_bytes(), date.as_bytes());
+    for part in [request.region, request.service, "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        request.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// HashiCorp Vault 的 transit 密钥引擎
+pub struct VaultTransit {
+    address: String,
+    mount: String,
+    key_name: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct VaultResponse {
+    data: VaultKeyData,
+}
+
+#[derive(Deserialize)]
+struct VaultKeyData {
+    plaintext: String,
+    #[serde(default)]
+    ciphertext: String,
+}
+
+impl VaultTransit {
+    pub fn new(settings: &KmsSettings) -> Result<Self, String> {
+        let token = secret_or_env(&settings.secret, VAULT_TOKEN_ENV)
+            .ok_or_else(|| format!("Vault token is empty (set it or {})", VAULT_TOKEN_ENV))?;
+        let mount = match settings.vault_mount.trim().trim_matches('/') {
+            "" => "transit".to_string(),
+            mount => mount.to_string(),
+        };
+        Ok(Self {
+            address: settings.endpoint.trim().trim_end_matches('/').to_string(),
+            mount,
+            key_name: settings.key_id.trim().to_string(),
+            token,
+        })
+    }
+
+    fn call(&self, path: &str, body: serde_json::Value) -> Result<VaultKeyData, String> {
+        let url = format!("{}/v1/{}/{}/{}", self.address, self.mount, path, self.key_name);
+        let response: VaultResponse = http_agent().post(&url)
+            .set("X-Vault-Token", &self.token)
+            .send_json(body)
+            .map_err(|e| request_error("Vault", e))?
+            .into_json()
+            .map_err(|e| format!("Vault returned an invalid response: {}", e))?;
+        Ok(response.data)
+    }
+}
+
+impl KeyManagementService for VaultTransit {
+    fn generate_data_key(&self) -> Result<DataKey, String> {
+        let data = self.call("datakey/plaintext", serde_json::json!({ "bits": 256 }))?;
+        let plaintext = BASE64.decode(&data.plaintext).map_err(|e| format!("Invalid data key from Vault: {}", e))?;
+        Ok(DataKey {
+            password: hex::encode(plaintext),
+            wrapped: WrappedKey { source: KeySource::HashiCorpVault, blob: data.ciphertext.into_bytes() },
+        })
+    }
+
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String> {
+        let ciphertext = std::str::from_utf8(blob).map_err(|_| "The wrapped key is not a Vault ciphertext".to_string())?;
+        let data = self.call("decrypt", serde_json::json!({ "ciphertext": ciphertext }))?;
+        let plaintext = BASE64.decode(&data.plaintext).map_err(|e| format!("Invalid data key from Vault: {}", e))?;
+        Ok(hex::encode(plaintext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigv4_matches_aws_example() {
+        // AWS文档中的签名示例（IAM ListUsers）
+        let headers = vec![
+            ("content-type".to_string(), "application/x-www-form-urlencoded; charset=utf-8".to_string()),
+            ("host".to_string(), "iam.amazonaws.com".to_string()),
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+        ];
+        let authorization = sigv4_authorization(&SigningRequest {
+            access_key_id: "AKIDEXAMPLE",
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            region: "us-east-1",
+            service: "iam",
+            amz_date: "20150830T123600Z",
+            method: "GET",
+            canonical_uri: "/",
+            canonical_query: "Action=ListUsers&Version=2010-05-08",
+            headers: &headers,
+            payload: b"",
+        });
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+}
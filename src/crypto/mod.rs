@@ -1,74 +1,85 @@
 pub mod traits;
 pub mod aes;
+pub mod age;
 pub mod chacha20;
+pub mod compression;
+pub mod xchacha20;
 pub mod engine;
+pub mod hooks;
+pub mod interop;
+pub mod armor;
+pub mod audit;
+pub mod budget;
+pub mod cascade;
+pub mod format;
+pub mod vault;
+pub mod header;
+pub mod keyfile;
+pub mod keys;
+pub mod kms;
+pub mod openssl;
+pub mod parallel;
+pub mod pkcs11;
+pub mod random_access;
+pub mod stream;
+pub mod token;
+pub mod registry;
+pub mod ssh;
 
-pub use traits::{CryptoProvider, CryptoResult};
-pub use engine::CryptoEngine;
+pub use traits::{CryptoProvider, CryptoResult, CryptoError, StreamControl};
+pub use engine::{CryptoEngine, EncryptOptions};
+pub use hooks::FileHooks;
 
-use crate::models::EncryptionAlgorithm;
+use crate::models::{EncryptionAlgorithm, KdfParams};
+use header::FileHeader;
 use std::io::{Read, Write};
+use traits::BatchKey;
 
-/// 加密提供者枚举，解决trait对象安全问题
-#[derive(Debug)]
-pub enum CryptoProviderEnum {
-    Aes(aes::AesCryptoProvider),
-    ChaCha20(chacha20::ChaCha20CryptoProvider),
+/// 创建对应的加密提供者
+pub fn create_crypto_provider(algorithm: &EncryptionAlgorithm) -> Box<dyn CryptoProvider> {
+    create_crypto_provider_with_kdf(algorithm, &KdfParams::default())
 }
 
-impl CryptoProvider for CryptoProviderEnum {
-    fn algorithm_name(&self) -> &'static str {
-        match self {
-            CryptoProviderEnum::Aes(provider) => provider.algorithm_name(),
-            CryptoProviderEnum::ChaCha20(provider) => provider.algorithm_name(),
-        }
-    }
-    
-    fn chunk_size(&self) -> usize {
-        match self {
-            CryptoProviderEnum::Aes(provider) => provider.chunk_size(),
-            CryptoProviderEnum::ChaCha20(provider) => provider.chunk_size(),
-        }
-    }
-    
-    fn encrypt_stream<R: Read, W: Write>(
-        &self,
-        password: &str,
-        reader: &mut R,
-        writer: &mut W,
-    ) -> CryptoResult<()> {
-        match self {
-            CryptoProviderEnum::Aes(provider) => provider.encrypt_stream(password, reader, writer),
-            CryptoProviderEnum::ChaCha20(provider) => provider.encrypt_stream(password, reader, writer),
-        }
-    }
-    
-    fn decrypt_stream<R: Read, W: Write>(
-        &self,
-        password: &str,
-        reader: &mut R,
-        writer: &mut W,
-    ) -> CryptoResult<()> {
-        match self {
-            CryptoProviderEnum::Aes(provider) => provider.decrypt_stream(password, reader, writer),
-            CryptoProviderEnum::ChaCha20(provider) => provider.decrypt_stream(password, reader, writer),
-        }
-    }
-    
-    fn verify_password(&self, password: &str, data: &[u8]) -> CryptoResult<bool> {
-        match self {
-            CryptoProviderEnum::Aes(provider) => provider.verify_password(password, data),
-            CryptoProviderEnum::ChaCha20(provider) => provider.verify_password(password, data),
-        }
+/// 创建使用指定Argon2参数的加密提供者（插件算法自行派生密钥，忽略该参数）
+pub fn create_crypto_provider_with_kdf(algorithm: &EncryptionAlgorithm, kdf: &KdfParams) -> Box<dyn CryptoProvider> {
+    match algorithm {
+        EncryptionAlgorithm::AES256 => Box::new(aes::AesCryptoProvider::with_kdf(*kdf)),
+        EncryptionAlgorithm::ChaCha20 => Box::new(chacha20::ChaCha20CryptoProvider::with_kdf(*kdf)),
+        EncryptionAlgorithm::XChaCha20 => Box::new(xchacha20::XChaCha20CryptoProvider::with_kdf(*kdf)),
+        EncryptionAlgorithm::Age => Box::new(age::AgeCryptoProvider::with_kdf(*kdf)),
+        EncryptionAlgorithm::OpenSsl => Box::new(openssl::OpenSslCryptoProvider::with_kdf(*kdf)),
+        EncryptionAlgorithm::Plugin(id) => registry::create_registered(*id)
+            .unwrap_or_else(|| Box::new(registry::UnregisteredProvider { id: *id })),
     }
 }
 
-/// 创建对应的加密提供者
-pub fn create_crypto_provider(algorithm: &EncryptionAlgorithm) -> CryptoProviderEnum {
+/// 创建从批处理主密钥派生文件密钥的提供者，插件算法不支持时返回None
+pub fn create_crypto_provider_with_batch_key(algorithm: &EncryptionAlgorithm, key: &BatchKey) -> Option<Box<dyn CryptoProvider>> {
     match algorithm {
-        EncryptionAlgorithm::AES256 => CryptoProviderEnum::Aes(aes::AesCryptoProvider::new()),
-        EncryptionAlgorithm::ChaCha20 => CryptoProviderEnum::ChaCha20(chacha20::ChaCha20CryptoProvider::new()),
+        EncryptionAlgorithm::AES256 => Some(Box::new(aes::AesCryptoProvider::with_batch_key(key.clone()))),
+        EncryptionAlgorithm::ChaCha20 => Some(Box::new(chacha20::ChaCha20CryptoProvider::with_batch_key(key.clone()))),
+        EncryptionAlgorithm::XChaCha20 => Some(Box::new(xchacha20::XChaCha20CryptoProvider::with_batch_key(key.clone()))),
+        EncryptionAlgorithm::Age | EncryptionAlgorithm::OpenSsl | EncryptionAlgorithm::Plugin(_) => None,
+    }
+}
+
+/// 按文件头创建解密用的提供者：使用文件头中的Argon2参数，有批处理盐值时先派生（或解包）批处理主密钥
+pub fn create_decryption_provider(
+    algorithm: &EncryptionAlgorithm,
+    header: Option<&FileHeader>,
+    password: &str,
+) -> CryptoResult<Box<dyn CryptoProvider>> {
+    let kdf = header.and_then(|header| header.kdf_params).unwrap_or_default();
+    if let Some(salt) = header.and_then(|header| header.batch_salt) {
+        let key = match header.and_then(|header| header.session_key.as_ref()) {
+            Some(wrapped) => BatchKey::open_session(password, salt, wrapped, kdf)?,
+            None => BatchKey::derive(password, salt, kdf)?,
+        };
+        if let Some(provider) = create_crypto_provider_with_batch_key(algorithm, &key) {
+            return Ok(provider);
+        }
     }
+    Ok(create_crypto_provider_with_kdf(algorithm, &kdf))
 }
 
 /// 加密工具函数
@@ -79,7 +90,7 @@ pub fn encrypt_stream<R: Read, W: Write>(
     writer: &mut W,
 ) -> CryptoResult<()> {
     let provider = create_crypto_provider(algorithm);
-    provider.encrypt_stream(password, reader, writer)
+    provider.encrypt_stream(password, reader, writer, &StreamControl::default())
 }
 
 /// 解密工具函数
@@ -90,5 +101,5 @@ pub fn decrypt_stream<R: Read, W: Write>(
     writer: &mut W,
 ) -> CryptoResult<()> {
     let provider = create_crypto_provider(algorithm);
-    provider.decrypt_stream(password, reader, writer)
+    provider.decrypt_stream(password, reader, writer, &StreamControl::default())
 } 
\ No newline at end of file
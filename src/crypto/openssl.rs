@@ -0,0 +1,215 @@
+//! OpenSSL `enc` 格式（与 `openssl enc -aes-256-cbc -pbkdf2` 互通）
+//!
+//! 文件以 `Salted__` 和8字节盐值开头，之后是PKCS#7填充的AES-256-CBC密文；
+//! 密钥和IV由PBKDF2-HMAC-SHA256(口令, 盐值)派生的48字节拆分得到。加密的文件可以用
+//! `openssl enc -d -aes-256-cbc -pbkdf2 -in FILE` 解密，反之亦然。`openssl enc` 不支持GCM等AEAD模式，
+//! 所以这里只有CBC：密文没有认证，错误的口令只能在最后一块的填充检查时发现（约1/256的概率漏检）。
+//! 文件中不记录迭代次数，双方必须一致（`openssl` 的 `-iter`，默认10000次）。
+//! 文件没有Krypton文件头，引擎按开头的 `Salted__` 识别
+
+use super::format;
+use super::traits::{CryptoError, CryptoProvider, CryptoResult, StreamControl};
+use crate::models::{KdfAlgorithm, KdfParams};
+use ::aes::Aes256;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use cbc::cipher::generic_array::GenericArray;
+use sha2::Sha256;
+use std::io::{Read, Write};
+
+/// 文件开头的标记
+pub const OPENSSL_MAGIC: &[u8] = b"Salted__";
+const SALT_LEN: usize = 8;
+const HEADER_LEN: usize = OPENSSL_MAGIC.len() + SALT_LEN;
+const BLOCK_LEN: usize = 16;
+/// `openssl enc -pbkdf2` 的默认迭代次数
+pub const DEFAULT_ITERATIONS: u32 = 10_000;
+/// 每次读写的长度（块长度的整数倍）
+const BUFFER_SIZE: usize = 64 * 1024;
+
+type Encryptor = cbc::Encryptor<Aes256>;
+type Decryptor = cbc::Decryptor<Aes256>;
+
+/// 数据是否以 `Salted__` 开头
+pub fn is_openssl(prefix: &[u8]) -> bool {
+    prefix.starts_with(OPENSSL_MAGIC)
+}
+
+/// 明文加密后的大小：文件头加上填充到下一个整块的密文（整块时多一个填充块）
+pub fn encrypted_size(plaintext_len: u64) -> u64 {
+    HEADER_LEN as u64 + (plaintext_len / BLOCK_LEN as u64 + 1) * BLOCK_LEN as u64
+}
+
+/// 文件中明文长度的上限
+pub fn max_plaintext_size(file_len: u64) -> u64 {
+    file_len.saturating_sub((HEADER_LEN + 1) as u64)
+}
+
+/// OpenSSL `enc` 格式的加密提供者
+#[derive(Debug, Clone, Copy)]
+pub struct OpenSslCryptoProvider {
+    /// PBKDF2迭代次数
+    pub iterations: u32,
+}
+
+impl Default for OpenSslCryptoProvider {
+    fn default() -> Self {
+        Self { iterations: DEFAULT_ITERATIONS }
+    }
+}
+
+impl OpenSslCryptoProvider {
+    /// 密钥派生算法选择PBKDF2时使用其迭代次数，否则使用 `openssl` 的默认值
+    pub fn with_kdf(kdf: KdfParams) -> Self {
+        match kdf.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => Self { iterations: kdf.iterations.max(1) },
+            _ => Self::default(),
+        }
+    }
+
+    /// PBKDF2派生的前32字节为密钥，后16字节为IV
+    fn key_iv(&self, password: &str, salt: &[u8]) -> ([u8; 32], [u8; BLOCK_LEN]) {
+        let mut output = [0u8; 32 + BLOCK_LEN];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, self.iterations, &mut output);
+        let mut key = [0u8; 32];
+        let mut iv = [0u8; BLOCK_LEN];
+        key.copy_from_slice(&output[..32]);
+        iv.copy_from_slice(&output[32..]);
+        (key, iv)
+    }
+}
+
+impl CryptoProvider for OpenSslCryptoProvider {
+    fn algorithm_name(&self) -> &'static str {
+        "OpenSSL AES-256-CBC"
+    }
+
+    fn chunk_size(&self) -> usize {
+        BUFFER_SIZE
+    }
+
+    fn encrypt_stream(&self, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, control: &StreamControl) -> CryptoResult<()> {
+        let salt: [u8; SALT_LEN] = rand::random();
+        writer.write_all(OPENSSL_MAGIC)?;
+        writer.write_all(&salt)?;
+        let (key, iv) = self.key_iv(password, &salt);
+        let mut cipher = Encryptor::new(&key.into(), &iv.into());
+
+        // 多留一块的空间给最后的填充
+        let mut buffer = vec![0u8; BUFFER_SIZE + BLOCK_LEN];
+        let mut processed = 0;
+        loop {
+            control.check()?;
+            let len = format::read_chunk(reader, &mut buffer[..BUFFER_SIZE])?;
+            if len < BUFFER_SIZE {
+                let padding = BLOCK_LEN - len % BLOCK_LEN;
+                buffer[len..len + padding].fill(padding as u8);
+                encrypt_blocks(&mut cipher, &mut buffer[..len + padding]);
+                writer.write_all(&buffer[..len + padding])?;
+                return Ok(());
+            }
+            encrypt_blocks(&mut cipher, &mut buffer[..BUFFER_SIZE]);
+            writer.write_all(&buffer[..BUFFER_SIZE])?;
+            processed += BUFFER_SIZE as u64;
+            control.report(processed);
+        }
+    }
+
+    fn decrypt_stream(&self, password: &str, reader: &mut dyn Read, writer: &mut dyn Write, control: &StreamControl) -> CryptoResult<()> {
+        let mut header = [0u8; HEADER_LEN];
+        if format::read_chunk(reader, &mut header)? < HEADER_LEN || !is_openssl(&header) {
+            return Err(CryptoError::InvalidFormat);
+        }
+        let (key, iv) = self.key_iv(password, &header[OPENSSL_MAGIC.len()..]);
+        let mut cipher = Decryptor::new(&key.into(), &iv.into());
+
+        // 最后一块要去掉填充，读满缓冲区时留下最后一块（仍是密文）到下一轮
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        let mut held = 0;
+        let mut processed = HEADER_LEN as u64;
+        loop {
+            control.check()?;
+            let read = format::read_chunk(reader, &mut buffer[held..])?;
+            let len = held + read;
+            processed += read as u64;
+            if len < BUFFER_SIZE {
+                if len == 0 || len % BLOCK_LEN != 0 {
+                    return Err(CryptoError::DecryptionError("密文长度不是块长度的整数倍，文件已损坏或被截断".to_string()));
+                }
+                decrypt_blocks(&mut cipher, &mut buffer[..len]);
+                // 填充无效时最可能是口令错误（CBC没有认证，无法区分数据损坏）
+                let padding = buffer[len - 1] as usize;
+                if !(1..=BLOCK_LEN).contains(&padding) || buffer[len - padding..len].iter().any(|&b| b as usize != padding) {
+                    return Err(CryptoError::InvalidPassword);
+                }
+                writer.write_all(&buffer[..len - padding])?;
+                return Ok(());
+            }
+            decrypt_blocks(&mut cipher, &mut buffer[..len - BLOCK_LEN]);
+            writer.write_all(&buffer[..len - BLOCK_LEN])?;
+            control.report(processed);
+            buffer.copy_within(len - BLOCK_LEN..len, 0);
+            held = BLOCK_LEN;
+        }
+    }
+
+    /// CBC密文没有认证，只有解密到最后一块才能检查填充，无法只凭文件开头验证口令，总是返回true
+    fn verify_password(&self, _password: &str, data: &[u8]) -> CryptoResult<bool> {
+        if !is_openssl(data) {
+            return Err(CryptoError::InvalidFormat);
+        }
+        Ok(true)
+    }
+}
+
+fn encrypt_blocks(cipher: &mut Encryptor, data: &mut [u8]) {
+    for block in data.chunks_exact_mut(BLOCK_LEN) {
+        cipher.encrypt_block_mut(GenericArray::from_mut_slice(block));
+    }
+}
+
+fn decrypt_blocks(cipher: &mut Decryptor, data: &mut [u8]) {
+    for block in data.chunks_exact_mut(BLOCK_LEN) {
+        cipher.decrypt_block_mut(GenericArray::from_mut_slice(block));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openssl_round_trip() {
+        let provider = OpenSslCryptoProvider::with_kdf(KdfParams { iterations: 10, ..KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256) });
+        assert_eq!(provider.iterations, 10);
+        assert_eq!(OpenSslCryptoProvider::with_kdf(KdfParams::default()).iterations, DEFAULT_ITERATIONS);
+        for len in [0, 15, 16, 100, BUFFER_SIZE, 2 * BUFFER_SIZE + 1] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut encrypted = Vec::new();
+            provider.encrypt_stream("pw", &mut &plaintext[..], &mut encrypted, &StreamControl::default()).unwrap();
+            assert_eq!(encrypted.len() as u64, encrypted_size(len as u64));
+            assert!(max_plaintext_size(encrypted.len() as u64) >= len as u64);
+            assert!(is_openssl(&encrypted));
+
+            let mut decrypted = Vec::new();
+            provider.decrypt_stream("pw", &mut &encrypted[..], &mut decrypted, &StreamControl::default()).unwrap();
+            assert_eq!(decrypted, plaintext);
+            let truncated = &encrypted[..encrypted.len() - 1];
+            assert!(provider.decrypt_stream("pw", &mut &truncated[..], &mut Vec::new(), &StreamControl::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_openssl_known_answer() {
+        // printf 'hello openssl' | openssl enc -aes-256-cbc -pbkdf2 -iter 1000 -pass pass:pw -S 0102030405060708
+        // （指定盐值时OpenSSL 3不写出文件头，这里补上）
+        let encrypted = hex::decode("53616c7465645f5f010203040506070828e616b1aec0d3422e58ac21b7bbf9e3").unwrap();
+        let provider = OpenSslCryptoProvider { iterations: 1000 };
+        let mut decrypted = Vec::new();
+        provider.decrypt_stream("pw", &mut &encrypted[..], &mut decrypted, &StreamControl::default()).unwrap();
+        assert_eq!(decrypted, b"hello openssl");
+        assert!(matches!(
+            provider.decrypt_stream("wrong", &mut &encrypted[..], &mut Vec::new(), &StreamControl::default()),
+            Err(CryptoError::InvalidPassword)
+        ));
+    }
+}
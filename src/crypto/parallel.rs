@@ -0,0 +1,162 @@
+//! 用rayon并行处理单个文件的数据块
+//!
+//! 每次读入一批数据块，在rayon线程池中并行加密或解密，再按原顺序写出。块序号和nonce在读入时按顺序确定，
+//! 输出格式与顺序处理完全相同，两种方式加密的文件可以互相解密。
+//! 提供者不支持分块加解密时退回顺序处理
+
+use super::format;
+use super::stream::{self, ChunkDigest, ChunkKind, ChunkNonces, ChunkSequence};
+use super::traits::{CryptoError, CryptoProvider, CryptoResult, StreamControl};
+use rayon::prelude::*;
+use std::io::{self, Read, Write};
+
+/// 一批数据块的数量：每个rayon线程两块，且不超过内存预算（每块需要明文和密文两份缓冲区）
+pub fn batch_size(chunk_size: usize, memory_budget: usize) -> usize {
+    let by_threads = rayon::current_num_threads() * 2;
+    let by_memory = memory_budget / (chunk_size * 2).max(1);
+    by_threads.min(by_memory).max(1)
+}
+
+/// 并行加密数据流，每读入一块之前检查是否被取消，每批写出后报告已加密的明文长度
+pub fn encrypt_stream(
+    provider: &dyn CryptoProvider,
+    password: &str,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    batch_size: usize,
+    control: &StreamControl,
+) -> CryptoResult<()> {
+    let (salt, encryptor) = match provider.chunk_encryptor(password) {
+        Ok(encryptor) => encryptor,
+        Err(CryptoError::EncryptionError(_)) => return provider.encrypt_stream(password, reader, writer, control),
+        Err(e) => return Err(e),
+    };
+    writer.write_all(&salt)?;
+
+    let chunk_size = provider.chunk_size();
+    let nonces = ChunkNonces::generate(provider.nonce_len());
+    let mut digest = ChunkDigest::new(control);
+    let mut next = Vec::new();
+    stream::read_plaintext_chunk(reader, &mut next, chunk_size)?;
+    let mut chunk_index = 0u64;
+    let mut processed = 0;
+    let mut batch = Vec::with_capacity(batch_size);
+    loop {
+        control.check()?;
+        // 与顺序加密一致：除最后一块外每块都读满，读满的块要读出下一块才知道是否为最后一块
+        let mut chunk = std::mem::take(&mut next);
+        if chunk.len() == chunk_size {
+            stream::read_plaintext_chunk(reader, &mut next, chunk_size)?;
+        }
+        let last = next.is_empty();
+        let (nonce, associated_data) = nonces.seal(chunk_index, ChunkKind::data(last))?;
+        // 摘要按明文顺序累计，在并行加密之前计算
+        digest.update(&chunk);
+        processed += chunk.len() as u64;
+        chunk.reserve_exact(format::TAG_LEN);
+        batch.push((chunk_index, nonce, associated_data, chunk));
+        chunk_index += 1;
+        if batch.len() < batch_size && !last {
+            continue;
+        }
+
+        batch.par_iter_mut()
+            .map(|(index, nonce, associated_data, chunk)| {
+                encryptor.encrypt_chunk(nonce, associated_data, chunk).map_err(|e| stream::chunk_error(e, *index))
+            })
+            .collect::<CryptoResult<()>>()?;
+        for (_, nonce, _, ciphertext) in batch.drain(..) {
+            stream::write_chunk(writer, &nonce, &ciphertext)?;
+        }
+        control.report(processed);
+        if last {
+            return digest.write_chunk(encryptor.as_ref(), &nonces, chunk_index, writer);
+        }
+    }
+}
+
+/// 并行解密数据流，每读入一块之前检查是否被取消，每批写出后报告已读取的密文长度
+pub fn decrypt_stream(
+    provider: &dyn CryptoProvider,
+    password: &str,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    batch_size: usize,
+    control: &StreamControl,
+) -> CryptoResult<()> {
+    let mut salt = vec![0u8; format::SALT_LEN];
+    reader.read_exact(&mut salt)?;
+    let decryptor = match provider.chunk_decryptor(password, &salt) {
+        Ok(decryptor) => decryptor,
+        // 盐值已被读取，放回后交给顺序解密
+        Err(CryptoError::DecryptionError(_)) => {
+            let mut reader = io::Cursor::new(salt).chain(reader);
+            return provider.decrypt_stream(password, &mut reader, writer, control);
+        }
+        Err(e) => return Err(e),
+    };
+
+    let max_ciphertext = format::max_chunk_ciphertext(provider.chunk_size());
+    let nonce_len = provider.nonce_len();
+    let mut sequence = ChunkSequence::new(control);
+    let mut chunk_index = 0u64;
+    let mut processed = format::SALT_LEN as u64;
+    loop {
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut finished = false;
+        while batch.len() < batch_size {
+            control.check()?;
+            let Some((nonce, ciphertext)) = stream::read_chunk(reader, nonce_len, max_ciphertext, chunk_index)? else {
+                finished = true;
+                break;
+            };
+            let (associated_data, kind) = sequence.next(&nonce)?;
+            processed += (nonce.len() + format::LENGTH_LEN + ciphertext.len()) as u64;
+            batch.push((chunk_index, kind, nonce, associated_data, ciphertext));
+            chunk_index += 1;
+        }
+
+        batch.par_iter_mut()
+            .map(|(index, _, nonce, associated_data, buffer)| {
+                decryptor.decrypt_chunk(nonce, associated_data, buffer).map_err(|e| stream::chunk_error(e, *index))
+            })
+            .collect::<CryptoResult<()>>()?;
+        for (_, kind, _, _, plaintext) in &batch {
+            writer.write_all(sequence.accept(*kind, plaintext)?)?;
+        }
+        control.report(processed);
+
+        if finished {
+            return sequence.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::create_crypto_provider;
+    use crate::models::EncryptionAlgorithm;
+
+    #[test]
+    fn test_parallel_output_interoperates_with_sequential() {
+        for algorithm in [EncryptionAlgorithm::ChaCha20, EncryptionAlgorithm::XChaCha20] {
+            let provider = create_crypto_provider(&algorithm);
+            let data: Vec<u8> = (0..provider.chunk_size() * 5 + 777).map(|i| (i % 253) as u8).collect();
+
+            let mut parallel = Vec::new();
+            let control = StreamControl::default();
+            encrypt_stream(provider.as_ref(), "pw", &mut data.as_slice(), &mut parallel, 2, &control).unwrap();
+            let mut decrypted = Vec::new();
+            provider.decrypt_stream("pw", &mut parallel.as_slice(), &mut decrypted, &control).unwrap();
+            assert_eq!(decrypted, data);
+
+            let mut sequential = Vec::new();
+            provider.encrypt_stream("pw", &mut data.as_slice(), &mut sequential, &control).unwrap();
+            assert_eq!(sequential.len() as u64, format::encrypted_size(data.len() as u64, provider.chunk_size(), provider.nonce_len()));
+            let mut decrypted = Vec::new();
+            decrypt_stream(provider.as_ref(), "pw", &mut sequential.as_slice(), &mut decrypted, 3, &control).unwrap();
+            assert_eq!(decrypted, data);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! PKCS#11令牌（智能卡、HSM）上的RSA密钥包装数据密钥
+//!
+//! 加密时从令牌读取公钥，在本地用RSA-OAEP(SHA-256)包装每个文件的随机数据密钥，不需要PIN；
+//! 解密时由令牌用私钥解包，私钥始终不离开硬件。
+//! 通过 pkcs11-tool（OpenSC）访问令牌，PIN经环境变量传给子进程，不出现在命令行中
+
+use super::header::WrappedKey;
+use super::kms::{DataKey, KeyManagementService};
+use crate::models::{KeySource, KmsSettings};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Oaep, RsaPublicKey};
+use sha2::Sha256;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// 未填写PIN时读取的环境变量
+pub const PKCS11_PIN_ENV: &str = "PKCS11_PIN";
+/// 传给 pkcs11-tool 的PIN所在的环境变量
+const CHILD_PIN_ENV: &str = "KRYPTON_PKCS11_PIN";
+/// 数据密钥长度
+const FILE_KEY_LEN: usize = 32;
+
+/// PKCS#11令牌上的包装密钥
+pub struct Pkcs11Token {
+    module: String,
+    slot: String,
+    label: String,
+    pin: Option<String>,
+}
+
+impl Pkcs11Token {
+    pub fn new(settings: &KmsSettings) -> Self {
+        Self {
+            module: settings.pkcs11_module.trim().to_string(),
+            slot: settings.pkcs11_slot.trim().to_string(),
+            label: settings.key_id.trim().to_string(),
+            pin: Some(settings.secret.clone())
+                .filter(|pin| !pin.is_empty())
+                .or_else(|| std::env::var(PKCS11_PIN_ENV).ok().filter(|pin| !pin.is_empty())),
+        }
+    }
+
+    /// 选择模块、槽位和密钥的公共参数
+    fn command(&self) -> Command {
+        let mut command = Command::new("pkcs11-tool");
+        if !self.module.is_empty() {
+            command.arg("--module").arg(&self.module);
+        }
+        if !self.slot.is_empty() {
+            command.arg("--slot").arg(&self.slot);
+        }
+        command.arg("--label").arg(&self.label);
+        command
+    }
+
+    /// 读取令牌上的公钥
+    fn public_key(&self) -> Result<RsaPublicKey, String> {
+        let mut command = self.command();
+        command.args(["--read-object", "--type", "pubkey"]);
+        let der = run(command, &[])?;
+        parse_public_key(&der)
+            .ok_or_else(|| format!("PKCS#11 key '{}' is not an RSA key", self.label))
+    }
+}
+
+impl KeyManagementService for Pkcs11Token {
+    fn generate_data_key(&self) -> Result<DataKey, String> {
+        let public_key = self.public_key()?;
+        let file_key: [u8; FILE_KEY_LEN] = rand::random();
+        let blob = public_key.encrypt(&mut rand::thread_rng(), Oaep::new::<Sha256>(), &file_key)
+            .map_err(|e| format!("Failed to wrap the data key: {}", e))?;
+        Ok(DataKey {
+            password: hex::encode(file_key),
+            wrapped: WrappedKey { source: KeySource::Pkcs11, blob },
+        })
+    }
+
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String> {
+        let pin = self.pin.as_ref()
+            .ok_or_else(|| format!("Token PIN is empty (set it or {})", PKCS11_PIN_ENV))?;
+        let mut command = self.command();
+        command.args(["--login", "--pin", &format!("env:{}", CHILD_PIN_ENV)])
+            .args(["--decrypt", "--mechanism", "RSA-PKCS-OAEP", "--hash-algorithm", "SHA256", "--mgf", "MGF1-SHA256"])
+            .env(CHILD_PIN_ENV, pin);
+        let file_key = run(command, blob)?;
+        if file_key.len() != FILE_KEY_LEN {
+            return Err("The token returned a data key of the wrong length".to_string());
+        }
+        Ok(hex::encode(file_key))
+    }
+}
+
+/// 运行 pkcs11-tool，把 `input` 写入标准输入并返回标准输出
+fn run(mut command: Command, input: &[u8]) -> Result<Vec<u8>, String> {
+    let mut child = command.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "pkcs11-tool (OpenSC) is not installed".to_string(),
+            _ => format!("Failed to run pkcs11-tool: {}", e),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input).map_err(|e| format!("Failed to talk to pkcs11-tool: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run pkcs11-tool: {}", e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("PKCS#11 token error: {}", message.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// 解析DER格式的RSA公钥（SubjectPublicKeyInfo，旧版 pkcs11-tool 输出PKCS#1）
+fn parse_public_key(der: &[u8]) -> Option<RsaPublicKey> {
+    RsaPublicKey::from_public_key_der(der).ok()
+        .or_else(|| RsaPublicKey::from_pkcs1_der(der).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsa::pkcs1::EncodeRsaPublicKey;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::RsaPrivateKey;
+
+    #[test]
+    fn test_parse_public_key_formats() {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let public_key = RsaPublicKey::from(&private_key);
+        let spki = public_key.to_public_key_der().unwrap();
+        let pkcs1 = public_key.to_pkcs1_der().unwrap();
+        assert_eq!(parse_public_key(spki.as_bytes()), Some(public_key.clone()));
+        assert_eq!(parse_public_key(pkcs1.as_bytes()), Some(public_key));
+        assert_eq!(parse_public_key(b"not a key"), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! 加密文件的随机访问读取
+//!
+//! 每个数据块使用独立的随机nonce，可以单独解密，因此只需定位到目标块即可读取任意位置，
+//! 解密结果只保存在内存中，不会写出明文文件
+
+use super::format::{chunk_count, chunk_overhead, digest_chunk_len, max_plaintext_size, LENGTH_LEN, SALT_LEN, TAG_LEN};
+use super::header::FileHeader;
+use super::stream::{ChunkKind, ChunkNonces};
+use super::traits::{ChunkDecryptor, CryptoError, CryptoProvider, CryptoResult};
+use crate::models::EncryptionAlgorithm;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// 可随机定位的解密读取器
+pub struct EncryptedFileReader<R> {
+    inner: R,
+    decryptor: Box<dyn ChunkDecryptor>,
+    /// 第一个数据块在文件中的偏移（文件头和盐值之后）
+    data_offset: u64,
+    chunk_size: usize,
+    nonce_len: usize,
+    /// 按第一块的nonce判断的nonce方式
+    nonces: ChunkNonces,
+    len: u64,
+    position: u64,
+    /// 最近解密的数据块（块序号, 明文）
+    cached: Option<(u64, Vec<u8>)>,
+}
+
+impl EncryptedFileReader<File> {
+    /// 打开磁盘上的加密文件
+    pub fn open(path: &Path, algorithm: &EncryptionAlgorithm, password: &str) -> CryptoResult<Self> {
+        // 文件头中可能记录了非默认的Argon2参数或批处理盐值，需要先读出再创建提供者
+        let mut file = File::open(path)?;
+        let (header, _) = FileHeader::read_from(&mut file)?;
+        // 级联加密的内层密文在外层明文中的位置与块边界不对齐，无法按块定位
+        if header.as_ref().is_some_and(|header| header.cascade.is_some()) {
+            return Err(CryptoError::DecryptionError("级联加密的文件不支持随机访问".to_string()));
+        }
+        let algorithm = header.as_ref().map_or(algorithm, |header| header.data_algorithm(algorithm));
+        let provider = super::create_decryption_provider(algorithm, header.as_ref(), password)?;
+        Self::new(file, provider.as_ref(), password)
+    }
+}
+
+impl<R: Read + Seek> EncryptedFileReader<R> {
+    /// 读取文件头和盐值并派生密钥，会立即解密第一个数据块以校验密码
+    pub fn new(mut inner: R, provider: &dyn CryptoProvider, password: &str) -> CryptoResult<Self> {
+        inner.seek(SeekFrom::Start(0))?;
+        let (header_len, totals) = {
+            let (header, _) = FileHeader::read_from(&mut inner)?;
+            // 压缩后的数据块与明文位置不对应，无法按块定位
+            if header.as_ref().is_some_and(|header| header.compression.is_some()) {
+                return Err(CryptoError::DecryptionError("压缩的文件不支持随机访问".to_string()));
+            }
+            header.map_or((0, None), |header| (header.encoded_len(), header.totals))
+        };
+
+        let total = inner.seek(SeekFrom::End(0))?;
+        if total < header_len + SALT_LEN as u64 {
+            return Err(CryptoError::InvalidFormat);
+        }
+        inner.seek(SeekFrom::Start(header_len))?;
+        let mut salt = [0u8; SALT_LEN];
+        inner.read_exact(&mut salt)?;
+
+        let chunk_size = provider.chunk_size();
+        let nonce_len = provider.nonce_len();
+        let mut first_nonce = vec![0u8; nonce_len];
+        let nonces = if total - header_len > SALT_LEN as u64 {
+            inner.read_exact(&mut first_nonce)?;
+            ChunkNonces::detect(&first_nonce)
+        } else {
+            ChunkNonces::Random
+        };
+        // 文件头记录了总长度时以它为准，旧格式按密文大小估算（新格式不计末尾的摘要块）
+        let len = totals.map_or_else(
+            || {
+                let digest_len = if nonces.is_sequenced() { digest_chunk_len(nonce_len) as u64 } else { 0 };
+                max_plaintext_size((total - header_len).saturating_sub(digest_len), chunk_size, nonce_len)
+            },
+            |totals| totals.plaintext_len,
+        );
+        let mut reader = Self {
+            inner,
+            decryptor: provider.chunk_decryptor(password, &salt)?,
+            data_offset: header_len + SALT_LEN as u64,
+            chunk_size,
+            nonce_len,
+            nonces,
+            len,
+            position: 0,
+            cached: None,
+        };
+        if reader.len > 0 {
+            reader.load_chunk(0)?;
+        }
+        Ok(reader)
+    }
+
+    /// 明文总长度
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 解密指定的数据块并放入缓存
+    fn load_chunk(&mut self, index: u64) -> CryptoResult<&[u8]> {
+        if self.cached.as_ref().is_none_or(|(cached, _)| *cached != index) {
+            let chunk_size = self.chunk_size as u64;
+            let expected = (self.len - index * chunk_size).min(chunk_size) as usize + TAG_LEN;
+            let offset = self.data_offset + index * (chunk_size + chunk_overhead(self.nonce_len) as u64);
+            self.inner.seek(SeekFrom::Start(offset))?;
+
+            let mut nonce = vec![0u8; self.nonce_len];
+            self.inner.read_exact(&mut nonce)?;
+            let mut length = [0u8; LENGTH_LEN];
+            self.inner.read_exact(&mut length)?;
+            if u32::from_le_bytes(length) as usize != expected {
+                return Err(CryptoError::DecryptionError(format!("数据块长度不符 (块 {})", index)));
+            }
+
+            // 最后一块的标志必须与按总长度算出的位置一致，否则文件在块边界处被截断或被追加了数据块。
+            // 随机访问只读取需要的块，不核对末尾的摘要块
+            let (associated_data, kind) = self.nonces.open(index, &nonce)?;
+            let expected_kind = ChunkKind::data(index + 1 == chunk_count(self.len, self.chunk_size));
+            if self.nonces.is_sequenced() && kind != expected_kind {
+                return Err(CryptoError::DecryptionError(format!("块 {} 的位置与文件长度不符，文件可能被截断", index)));
+            }
+
+            let mut buffer = vec![0u8; expected];
+            self.inner.read_exact(&mut buffer)?;
+            self.decryptor.decrypt_chunk(&nonce, &associated_data, &mut buffer)
+                .map_err(|e| CryptoError::DecryptionError(format!("{} (块 {})", e, index)))?;
+            self.cached = Some((index, buffer));
+        }
+        Ok(self.cached.as_ref().map(|(_, plaintext)| plaintext.as_slice()).unwrap_or_default())
+    }
+}
+
+impl<R: Read + Seek> Read for EncryptedFileReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.position >= self.len {
+            return Ok(0);
+        }
+
+        let index = self.position / self.chunk_size as u64;
+        let start = (self.position % self.chunk_size as u64) as usize;
+        let chunk = self.load_chunk(index).map_err(|e| match e {
+            CryptoError::IoError(e) => e,
+            other => io::Error::new(io::ErrorKind::InvalidData, other.to_string()),
+        })?;
+        let count = buf.len().min(chunk.len() - start);
+        buf[..count].copy_from_slice(&chunk[start..start + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for EncryptedFileReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match target {
+            Some(target) => {
+                self.position = target;
+                Ok(target)
+            }
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::aes::AesCryptoProvider;
+    use crate::crypto::traits::StreamControl;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_random_access_across_chunks() {
+        let provider = AesCryptoProvider::new();
+        let plaintext: Vec<u8> = (0..provider.chunk_size() * 2 + 1000).map(|i| (i % 251) as u8).collect();
+
+        let mut encrypted = Vec::new();
+        FileHeader::with_hint("hint").write_to(&mut encrypted).unwrap();
+        provider.encrypt_stream("secret", &mut plaintext.as_slice(), &mut encrypted, &StreamControl::default()).unwrap();
+
+        let mut reader = EncryptedFileReader::new(Cursor::new(encrypted.clone()), &provider, "secret").unwrap();
+        assert_eq!(reader.len(), plaintext.len() as u64);
+
+        // 跨越块边界读取
+        let offset = provider.chunk_size() - 10;
+        reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+        let mut buf = [0u8; 20];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &plaintext[offset..offset + 20]);
+
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut all = Vec::new();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, plaintext);
+
+        assert!(EncryptedFileReader::new(Cursor::new(encrypted), &provider, "wrong").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+use super::traits::{CryptoProvider, CryptoResult, CryptoError, StreamControl};
+use crate::models::EncryptionAlgorithm;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// 内置算法占用的ID范围上限，外部提供者的ID必须大于该值
+pub const RESERVED_ALGORITHM_IDS: u16 = 0xFF;
+
+type ProviderFactory = Arc<dyn Fn() -> Box<dyn CryptoProvider> + Send + Sync>;
+
+/// 注册表中的一个外部提供者
+struct Registration {
+    display_name: &'static str,
+    factory: ProviderFactory,
+}
+
+fn registry() -> &'static RwLock<BTreeMap<u16, Registration>> {
+    static REGISTRY: OnceLock<RwLock<BTreeMap<u16, Registration>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(BTreeMap::new()))
+}
+
+/// 注册外部加密提供者
+///
+/// `id` 会写入加密文件和封装文本中用于识别算法，必须大于 [`RESERVED_ALGORITHM_IDS`] 且未被占用；
+/// `display_name` 显示在界面的算法选择框中，不能与已有算法重名。
+pub fn register_provider<P, F>(id: u16, display_name: &'static str, factory: F) -> CryptoResult<EncryptionAlgorithm>
+where
+    P: CryptoProvider + 'static,
+    F: Fn() -> P + Send + Sync + 'static,
+{
+    if id <= RESERVED_ALGORITHM_IDS {
+        return Err(CryptoError::EncryptionError(format!("算法ID {} 为内置算法保留", id)));
+    }
+    if display_name.parse::<EncryptionAlgorithm>().is_ok() {
+        return Err(CryptoError::EncryptionError(format!("算法名称 {} 已被使用", display_name)));
+    }
+
+    let mut registry = registry().write().unwrap();
+    if registry.contains_key(&id) {
+        return Err(CryptoError::EncryptionError(format!("算法ID {} 已被注册", id)));
+    }
+
+    registry.insert(id, Registration {
+        display_name,
+        factory: Arc::new(move || Box::new(factory())),
+    });
+    Ok(EncryptionAlgorithm::Plugin(id))
+}
+
+/// 创建已注册的外部提供者实例
+pub fn create_registered(id: u16) -> Option<Box<dyn CryptoProvider>> {
+    let factory = registry().read().unwrap().get(&id)?.factory.clone();
+    Some(factory())
+}
+
+/// 外部提供者的显示名称
+pub fn display_name(id: u16) -> Option<&'static str> {
+    registry().read().unwrap().get(&id).map(|registration| registration.display_name)
+}
+
+/// 根据显示名称查找外部提供者的ID
+pub fn find_by_name(name: &str) -> Option<u16> {
+    registry().read().unwrap().iter()
+        .find(|(_, registration)| registration.display_name == name)
+        .map(|(id, _)| *id)
+}
+
+/// 所有可用的算法（内置算法在前，外部提供者按ID排序）
+pub fn available_algorithms() -> Vec<EncryptionAlgorithm> {
+    let mut algorithms = vec![EncryptionAlgorithm::AES256, EncryptionAlgorithm::ChaCha20, EncryptionAlgorithm::XChaCha20, EncryptionAlgorithm::Age, EncryptionAlgorithm::OpenSsl];
+    algorithms.extend(registry().read().unwrap().keys().map(|id| EncryptionAlgorithm::Plugin(*id)));
+    algorithms
+}
+
+/// 占位提供者 - 算法ID未注册时（例如缺少对应插件）返回明确的错误
+#[derive(Debug)]
+pub struct UnregisteredProvider {
+    pub id: u16,
+}
+
+impl CryptoProvider for UnregisteredProvider {
+    fn algorithm_name(&self) -> &'static str {
+        "Unregistered"
+    }
+
+    fn encrypt_stream(&self, _password: &str, _reader: &mut dyn Read, _writer: &mut dyn Write, _control: &StreamControl) -> CryptoResult<()> {
+        Err(CryptoError::EncryptionError(format!("算法ID {} 未注册", self.id)))
+    }
+
+    fn decrypt_stream(&self, _password: &str, _reader: &mut dyn Read, _writer: &mut dyn Write, _control: &StreamControl) -> CryptoResult<()> {
+        Err(CryptoError::DecryptionError(format!("算法ID {} 未注册", self.id)))
+    }
+
+    fn verify_password(&self, _password: &str, _data: &[u8]) -> CryptoResult<bool> {
+        Err(CryptoError::DecryptionError(format!("算法ID {} 未注册", self.id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::aes::AesCryptoProvider;
+    use crate::crypto::create_crypto_provider;
+
+    #[test]
+    fn test_registered_provider_is_available() {
+        let algorithm = register_provider(0x8001, "Test-AES", AesCryptoProvider::new).unwrap();
+        assert_eq!(algorithm, EncryptionAlgorithm::Plugin(0x8001));
+        assert!(available_algorithms().contains(&algorithm));
+        assert_eq!(algorithm.to_string(), "Test-AES");
+        assert_eq!("Test-AES".parse::<EncryptionAlgorithm>().unwrap(), algorithm);
+
+        assert!(register_provider(0x8001, "Test-AES-2", AesCryptoProvider::new).is_err());
+        assert!(register_provider(1, "Test-AES-3", AesCryptoProvider::new).is_err());
+        assert!(register_provider(0x8002, "AES-256", AesCryptoProvider::new).is_err());
+
+        let provider = create_crypto_provider(&algorithm);
+        let mut encrypted = Vec::new();
+        provider.encrypt_stream("pw", &mut &b"plugin data"[..], &mut encrypted, &StreamControl::default()).unwrap();
+        let mut decrypted = Vec::new();
+        provider.decrypt_stream("pw", &mut encrypted.as_slice(), &mut decrypted, &StreamControl::default()).unwrap();
+        assert_eq!(decrypted, b"plugin data");
+
+        let missing = create_crypto_provider(&EncryptionAlgorithm::Plugin(0x8FFF));
+        assert!(missing.encrypt_stream("pw", &mut &b"x"[..], &mut Vec::new(), &StreamControl::default()).is_err());
+    }
+}
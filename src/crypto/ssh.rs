@@ -0,0 +1,293 @@
+//! 以SSH公钥作为接收者（类似age的ssh-ed25519/ssh-rsa接收者）
+//!
+//! 每个文件的随机数据密钥分别为每个接收者包装，全部写入文件头；持有任一对应私钥的人都能解密。
+//! ssh-ed25519：公钥转换为X25519公钥，与临时密钥做ECDH后经HKDF派生包装密钥，用ChaCha20-Poly1305加密数据密钥。
+//! ssh-rsa：用RSA-OAEP(SHA-256)直接加密数据密钥
+
+use super::header::WrappedKey;
+use super::kms::{DataKey, KeyManagementService};
+use crate::models::{KeySource, KmsSettings};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use hkdf::Hkdf;
+use rsa::{BigUint, Oaep};
+use sha2::{Digest, Sha256, Sha512};
+use ssh_key::private::{KeypairData, RsaKeypair};
+use ssh_key::public::KeyData;
+use ssh_key::{HashAlg, Mpint, PrivateKey, PublicKey};
+use std::path::PathBuf;
+use x25519_dalek::StaticSecret;
+
+/// 数据密钥长度
+const FILE_KEY_LEN: usize = 32;
+/// 接收者标识的长度（公钥SHA-256指纹的前几个字节）
+const TAG_LEN: usize = 4;
+const KIND_ED25519: u8 = 1;
+const KIND_RSA: u8 = 2;
+const ED25519_INFO: &[u8] = b"krypton-ssh-ed25519";
+const RSA_LABEL: &str = "krypton-ssh-rsa";
+/// 未指定私钥时依次尝试的默认文件
+const DEFAULT_IDENTITIES: [&str; 2] = ["id_ed25519", "id_rsa"];
+
+/// 为一个接收者包装的数据密钥
+struct Stanza {
+    kind: u8,
+    tag: [u8; TAG_LEN],
+    body: Vec<u8>,
+}
+
+/// SSH公钥接收者
+pub struct SshRecipients {
+    recipients: String,
+    identity: String,
+    passphrase: String,
+}
+
+impl SshRecipients {
+    pub fn new(settings: &KmsSettings) -> Self {
+        Self {
+            recipients: settings.ssh_recipients.clone(),
+            identity: settings.ssh_identity.trim().to_string(),
+            passphrase: settings.secret.clone(),
+        }
+    }
+
+    /// 读取解密用的私钥，未指定时使用 ~/.ssh 中的默认私钥
+    fn load_identity(&self) -> Result<PrivateKey, String> {
+        let path = if self.identity.is_empty() {
+            let ssh_dir = dirs::home_dir().ok_or("Cannot find the home directory")?.join(".ssh");
+            DEFAULT_IDENTITIES.iter()
+                .map(|name| ssh_dir.join(name))
+                .find(|path| path.is_file())
+                .ok_or("No SSH private key found in ~/.ssh; set the identity file")?
+        } else {
+            expand_home(&self.identity)
+        };
+
+        let key = PrivateKey::read_openssh_file(&path)
+            .map_err(|e| format!("Failed to read SSH private key '{}': {}", path.display(), e))?;
+        if !key.is_encrypted() {
+            return Ok(key);
+        }
+        if self.passphrase.is_empty() {
+            return Err(format!("SSH private key '{}' is protected; enter its passphrase", path.display()));
+        }
+        key.decrypt(&self.passphrase)
+            .map_err(|_| format!("Wrong passphrase for SSH private key '{}'", path.display()))
+    }
+}
+
+impl KeyManagementService for SshRecipients {
+    fn generate_data_key(&self) -> Result<DataKey, String> {
+        let recipients = parse_recipients(&self.recipients)?;
+        let file_key: [u8; FILE_KEY_LEN] = rand::random();
+        let stanzas = recipients.iter()
+            .map(|recipient| wrap_for(recipient, &file_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DataKey {
+            password: hex::encode(file_key),
+            wrapped: WrappedKey { source: KeySource::SshKeys, blob: encode_stanzas(&stanzas) },
+        })
+    }
+
+    fn unwrap_key(&self, blob: &[u8]) -> Result<String, String> {
+        let identity = self.load_identity()?;
+        let tag = recipient_tag(identity.public_key());
+        let stanzas = decode_stanzas(blob)?;
+        stanzas.iter()
+            .filter(|stanza| stanza.tag == tag)
+            .find_map(|stanza| unwrap_with(&identity, stanza))
+            .map(hex::encode)
+            .ok_or_else(|| "The file was not encrypted to this SSH key".to_string())
+    }
+}
+
+/// 解析接收者列表（authorized_keys格式，每行一个公钥，忽略空行和#注释）
+pub fn parse_recipients(text: &str) -> Result<Vec<PublicKey>, String> {
+    let recipients = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let key = PublicKey::from_openssh(line)
+                .map_err(|e| format!("Invalid SSH public key '{}': {}", shorten(line), e))?;
+            match key.key_data() {
+                KeyData::Ed25519(_) | KeyData::Rsa(_) => Ok(key),
+                _ => Err(format!("Unsupported SSH key type {} (use ssh-ed25519 or ssh-rsa)", key.algorithm())),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match recipients.len() {
+        0 => Err("No SSH recipients".to_string()),
+        n if n > u8::MAX as usize => Err(format!("Too many SSH recipients ({}, at most {})", n, u8::MAX)),
+        _ => Ok(recipients),
+    }
+}
+
+fn wrap_for(recipient: &PublicKey, file_key: &[u8; FILE_KEY_LEN]) -> Result<Stanza, String> {
+    let tag = recipient_tag(recipient);
+    match recipient.key_data() {
+        KeyData::Ed25519(key) => {
+            let recipient_point = edwards_to_x25519(&key.0)
+                .ok_or("Invalid ssh-ed25519 public key")?;
+            let ephemeral = StaticSecret::from(rand::random::<[u8; 32]>());
+            let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral);
+            let shared = ephemeral.diffie_hellman(&recipient_point);
+            let wrap_key = ed25519_wrap_key(shared.as_bytes(), ephemeral_public.as_bytes(), recipient_point.as_bytes());
+
+            let cipher = ChaCha20Poly1305::new(Key::from_slice(&wrap_key));
+            let sealed = cipher.encrypt(Nonce::from_slice(&[0u8; 12]), file_key.as_slice())
+                .map_err(|_| "Failed to wrap the file key".to_string())?;
+            let mut body = ephemeral_public.as_bytes().to_vec();
+            body.extend_from_slice(&sealed);
+            Ok(Stanza { kind: KIND_ED25519, tag, body })
+        }
+        KeyData::Rsa(key) => {
+            let key = rsa::RsaPublicKey::try_from(key).map_err(|e| format!("Invalid ssh-rsa public key: {}", e))?;
+            let body = key.encrypt(&mut rand::thread_rng(), Oaep::new_with_label::<Sha256, _>(RSA_LABEL), file_key)
+                .map_err(|e| format!("Failed to wrap the file key: {}", e))?;
+            Ok(Stanza { kind: KIND_RSA, tag, body })
+        }
+        _ => Err(format!("Unsupported SSH key type {}", recipient.algorithm())),
+    }
+}
+
+/// 用私钥解开一个接收者的数据密钥，私钥不匹配时返回None
+fn unwrap_with(identity: &PrivateKey, stanza: &Stanza) -> Option<Vec<u8>> {
+    match (identity.key_data(), stanza.kind) {
+        (KeypairData::Ed25519(keypair), KIND_ED25519) if stanza.body.len() > 32 => {
+            // 与Ed25519私钥对应的X25519私钥是种子哈希的前32字节（StaticSecret会自动钳位）
+            let hash = Sha512::digest(keypair.private.to_bytes());
+            let mut scalar = [0u8; 32];
+            scalar.copy_from_slice(&hash[..32]);
+            let secret = StaticSecret::from(scalar);
+
+            let mut ephemeral = [0u8; 32];
+            ephemeral.copy_from_slice(&stanza.body[..32]);
+            let ephemeral = x25519_dalek::PublicKey::from(ephemeral);
+            let recipient_point = x25519_dalek::PublicKey::from(&secret);
+            let shared = secret.diffie_hellman(&ephemeral);
+            let wrap_key = ed25519_wrap_key(shared.as_bytes(), ephemeral.as_bytes(), recipient_point.as_bytes());
+
+            let cipher = ChaCha20Poly1305::new(Key::from_slice(&wrap_key));
+            cipher.decrypt(Nonce::from_slice(&[0u8; 12]), &stanza.body[32..]).ok()
+        }
+        (KeypairData::Rsa(keypair), KIND_RSA) => {
+            let key = rsa_private_key(keypair)?;
+            key.decrypt(Oaep::new_with_label::<Sha256, _>(RSA_LABEL), &stanza.body).ok()
+        }
+        _ => None,
+    }
+    .filter(|key| key.len() == FILE_KEY_LEN)
+}
+
+/// 由SSH私钥的各分量构造RSA私钥（ssh-key自带的转换对部分OpenSSH私钥会失败）
+fn rsa_private_key(keypair: &RsaKeypair) -> Option<rsa::RsaPrivateKey> {
+    let int = |value: &Mpint| value.as_positive_bytes().map(BigUint::from_bytes_be);
+    let primes = vec![int(&keypair.private.p)?, int(&keypair.private.q)?];
+    rsa::RsaPrivateKey::from_components(int(&keypair.public.n)?, int(&keypair.public.e)?, int(&keypair.private.d)?, primes).ok()
+}
+
+fn ed25519_wrap_key(shared: &[u8], ephemeral: &[u8], recipient: &[u8]) -> [u8; 32] {
+    let salt = [ephemeral, recipient].concat();
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(ED25519_INFO, &mut key)
+        .expect("32 bytes is a valid HKDF output length");
+    key
+}
+
+/// Ed25519公钥（Edwards曲线点）转换为对应的X25519公钥
+fn edwards_to_x25519(public: &[u8; 32]) -> Option<x25519_dalek::PublicKey> {
+    let point = CompressedEdwardsY(*public).decompress()?;
+    Some(x25519_dalek::PublicKey::from(point.to_montgomery().to_bytes()))
+}
+
+fn recipient_tag(key: &PublicKey) -> [u8; TAG_LEN] {
+    let fingerprint = key.fingerprint(HashAlg::Sha256);
+    let mut tag = [0u8; TAG_LEN];
+    tag.copy_from_slice(&fingerprint.as_bytes()[..TAG_LEN]);
+    tag
+}
+
+/// 结构：接收者数(1)，每个接收者为 类型(1) + 标识(4) + 长度(u16 LE) + 包装后的数据密钥
+fn encode_stanzas(stanzas: &[Stanza]) -> Vec<u8> {
+    let mut blob = vec![stanzas.len() as u8];
+    for stanza in stanzas {
+        blob.push(stanza.kind);
+        blob.extend_from_slice(&stanza.tag);
+        blob.extend_from_slice(&(stanza.body.len() as u16).to_le_bytes());
+        blob.extend_from_slice(&stanza.body);
+    }
+    blob
+}
+
+fn decode_stanzas(blob: &[u8]) -> Result<Vec<Stanza>, String> {
+    let invalid = || "The wrapped key is not a list of SSH recipients".to_string();
+    let (&count, mut rest) = blob.split_first().ok_or_else(invalid)?;
+    let mut stanzas = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        if rest.len() < 1 + TAG_LEN + 2 {
+            return Err(invalid());
+        }
+        let kind = rest[0];
+        let mut tag = [0u8; TAG_LEN];
+        tag.copy_from_slice(&rest[1..1 + TAG_LEN]);
+        let len = u16::from_le_bytes([rest[1 + TAG_LEN], rest[2 + TAG_LEN]]) as usize;
+        rest = &rest[3 + TAG_LEN..];
+        if rest.len() < len {
+            return Err(invalid());
+        }
+        stanzas.push(Stanza { kind, tag, body: rest[..len].to_vec() });
+        rest = &rest[len..];
+    }
+    Ok(stanzas)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// 错误信息中只显示公钥的开头
+fn shorten(line: &str) -> String {
+    line.chars().take(40).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+    use ssh_key::{Algorithm, LineEnding};
+
+    #[test]
+    fn test_ed25519_recipient_round_trip() {
+        let mut rng = rand::thread_rng();
+        let alice = PrivateKey::random(&mut rng, Algorithm::Ed25519).unwrap();
+        let bob = PrivateKey::random(&mut rng, Algorithm::Ed25519).unwrap();
+        let mallory = PrivateKey::random(&mut rng, Algorithm::Ed25519).unwrap();
+        let recipients = [&alice, &bob]
+            .map(|key| key.public_key().to_openssh().unwrap())
+            .join("\n# teammates\n");
+
+        let dir = TestDir::new("ssh");
+        let settings_for = |key: &PrivateKey, name: &str| {
+            let path = dir.join(name);
+            key.write_openssh_file(&path, LineEnding::LF).unwrap();
+            KmsSettings {
+                ssh_recipients: recipients.clone(),
+                ssh_identity: path.to_string_lossy().to_string(),
+                ..KmsSettings::default()
+            }
+        };
+
+        let data_key = SshRecipients::new(&settings_for(&alice, "alice")).generate_data_key().unwrap();
+        for (key, name) in [(&alice, "alice"), (&bob, "bob")] {
+            let password = SshRecipients::new(&settings_for(key, name)).unwrap_key(&data_key.wrapped.blob).unwrap();
+            assert_eq!(password, data_key.password);
+        }
+        assert!(SshRecipients::new(&settings_for(&mallory, "mallory")).unwrap_key(&data_key.wrapped.blob).is_err());
+    }
+}
@@ -0,0 +1,556 @@
+//! STREAM式的分块认证加密
+//!
+//! 每个文件生成随机的nonce前缀，第i块的nonce为 前缀 + i(u32 BE) + 最后一块标志(1)，
+//! 关联数据为 块序号(u64 LE) + 最后一块标志。数据块被重排、复制、删除或在块边界处截断时解密失败。
+//! 空明文也写出一个空的最后一块，否则无法与被截断的数据区分
+//!
+//! 最后一块之后是摘要块：nonce标志为2，明文为全部明文的BLAKE3哈希。摘要块与数据块使用同一密钥认证，
+//! 解密时必须读到摘要块且与解出的明文一致，缺少末尾数据块或摘要块的文件会报错。
+//! 文件头不在数据流中、本身没有认证，新文件的摘要块改为同时覆盖编码后的文件头和最终明文（见 [`DigestBinding`]）
+//!
+//! nonce仍写在每块之前，分块结构与旧格式相同。旧格式每块使用随机nonce、没有关联数据，
+//! 按第一块的nonce区分两种格式；新格式的块只能带关联数据解密，无法被当作旧格式的块接受
+
+use super::format;
+use super::traits::{ChunkDecryptor, ChunkEncryptor, CryptoError, CryptoResult, StreamControl};
+use aes_gcm::aead::OsRng;
+use rand::RngCore;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// nonce中块计数器的字节数
+const COUNTER_LEN: usize = 4;
+/// nonce末尾最后一块标志的取值
+const LAST_CHUNK: u8 = 1;
+/// nonce末尾摘要块标志的取值
+const DIGEST_CHUNK: u8 = 2;
+/// 关联数据的长度：块序号和最后一块标志
+const ASSOCIATED_DATA_LEN: usize = 8 + 1;
+/// 绑定文件头的摘要的BLAKE3密钥派生上下文
+const BINDING_CONTEXT: &str = "krypton 2024 file header and plaintext digest";
+
+/// 数据块在数据流中的作用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    Data,
+    /// 最后一个数据块
+    Last,
+    /// 最后一块之后的整个文件的明文摘要
+    Digest,
+}
+
+impl ChunkKind {
+    /// 数据块的作用：是否为最后一块
+    pub fn data(last: bool) -> Self {
+        if last { ChunkKind::Last } else { ChunkKind::Data }
+    }
+
+    fn flag(self) -> u8 {
+        match self {
+            ChunkKind::Data => 0,
+            ChunkKind::Last => LAST_CHUNK,
+            ChunkKind::Digest => DIGEST_CHUNK,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        match flag {
+            0 => Some(ChunkKind::Data),
+            LAST_CHUNK => Some(ChunkKind::Last),
+            DIGEST_CHUNK => Some(ChunkKind::Digest),
+            _ => None,
+        }
+    }
+}
+
+/// 数据块的nonce方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkNonces {
+    /// 随机前缀加块计数器，带块序号和最后一块标志的关联数据
+    Sequenced { prefix: Vec<u8> },
+    /// 旧格式：每块随机nonce，没有关联数据
+    Random,
+}
+
+impl ChunkNonces {
+    /// 为新文件生成随机的nonce前缀
+    pub fn generate(nonce_len: usize) -> Self {
+        let mut prefix = vec![0u8; nonce_len - COUNTER_LEN - 1];
+        OsRng.fill_bytes(&mut prefix);
+        ChunkNonces::Sequenced { prefix }
+    }
+
+    /// 按第一块的nonce判断格式：计数器为0且标志有效时为新格式
+    pub fn detect(first_nonce: &[u8]) -> Self {
+        let Some(prefix_len) = first_nonce.len().checked_sub(COUNTER_LEN + 1) else {
+            return ChunkNonces::Random;
+        };
+        let (prefix, suffix) = first_nonce.split_at(prefix_len);
+        match suffix {
+            [0, 0, 0, 0, 0 | LAST_CHUNK] => ChunkNonces::Sequenced { prefix: prefix.to_vec() },
+            _ => ChunkNonces::Random,
+        }
+    }
+
+    pub fn is_sequenced(&self) -> bool {
+        matches!(self, ChunkNonces::Sequenced { .. })
+    }
+
+    /// 加密第 `index` 块时使用的nonce和关联数据
+    pub fn seal(&self, index: u64, kind: ChunkKind) -> CryptoResult<(Vec<u8>, Vec<u8>)> {
+        let ChunkNonces::Sequenced { prefix } = self else {
+            return Err(CryptoError::EncryptionError("新文件必须使用计数器nonce".to_string()));
+        };
+        let counter = u32::try_from(index)
+            .map_err(|_| CryptoError::EncryptionError(format!("数据块过多: {}", index)))?;
+        let mut nonce = prefix.clone();
+        nonce.extend_from_slice(&counter.to_be_bytes());
+        nonce.push(kind.flag());
+        Ok((nonce, associated_data(index, kind)))
+    }
+
+    /// 核对第 `index` 块保存的nonce，返回解密用的关联数据和该块的作用
+    pub fn open(&self, index: u64, nonce: &[u8]) -> CryptoResult<(Vec<u8>, ChunkKind)> {
+        let ChunkNonces::Sequenced { prefix } = self else {
+            return Ok((Vec::new(), ChunkKind::Data));
+        };
+        let Some(kind) = nonce.get(prefix.len() + COUNTER_LEN).and_then(|&flag| ChunkKind::from_flag(flag)) else {
+            return Err(CryptoError::DecryptionError(format!("块 {} 的nonce无效", index)));
+        };
+        let counter_matches = u32::try_from(index)
+            .is_ok_and(|counter| nonce[prefix.len()..prefix.len() + COUNTER_LEN] == counter.to_be_bytes());
+        if nonce.len() != prefix.len() + COUNTER_LEN + 1 || !nonce.starts_with(prefix) || !counter_matches {
+            return Err(CryptoError::DecryptionError(format!("块 {} 的顺序不正确，数据块可能被重排、复制或删除", index)));
+        }
+        Ok((associated_data(index, kind), kind))
+    }
+}
+
+fn associated_data(index: u64, kind: ChunkKind) -> Vec<u8> {
+    let mut data = Vec::with_capacity(ASSOCIATED_DATA_LEN);
+    data.extend_from_slice(&index.to_le_bytes());
+    data.push(kind.flag());
+    data
+}
+
+/// 摘要块绑定的文件头和最终明文
+///
+/// 绑定时摘要块的内容为 BLAKE3(文件头长度(u64 LE) + 编码后的文件头 + 最终明文的BLAKE3哈希)，
+/// 改动文件头的任何字节（压缩信息、总长度、密钥派生参数、算法、原始文件名等）都会使摘要不符。
+/// 压缩的文件的最终明文是压缩前的数据，数据流只看到压缩后的数据：加密时由调用方把压缩前的明文交给
+/// [`PlaintextDigest`] 包装的读取器；解密时摘要块只记下期望值，解压完成后由调用方调用 [`Self::verify`] 核对
+#[derive(Debug, Clone)]
+pub struct DigestBinding {
+    header: Arc<[u8]>,
+    /// 由调用方累计的最终明文摘要，最终明文就是数据流的明文时为None
+    plaintext: Option<Arc<Mutex<blake3::Hasher>>>,
+    /// 解密时摘要块中的值，等待调用方核对
+    expected: Arc<Mutex<Option<[u8; format::DIGEST_LEN]>>>,
+}
+
+impl DigestBinding {
+    /// 绑定编码后的文件头 `header`；`separate_plaintext` 为true时最终明文由调用方累计（压缩的文件）
+    pub fn new(header: &[u8], separate_plaintext: bool) -> Self {
+        Self {
+            header: header.into(),
+            plaintext: separate_plaintext.then(Default::default),
+            expected: Default::default(),
+        }
+    }
+
+    /// 摘要块的内容，`stream_plaintext` 为数据流明文的摘要
+    fn digest(&self, stream_plaintext: &blake3::Hasher) -> blake3::Hash {
+        let plaintext = match &self.plaintext {
+            Some(hasher) => hasher.lock().unwrap().finalize(),
+            None => stream_plaintext.finalize(),
+        };
+        let mut hasher = blake3::Hasher::new_derive_key(BINDING_CONTEXT);
+        hasher.update(&(self.header.len() as u64).to_le_bytes());
+        hasher.update(&self.header);
+        hasher.update(plaintext.as_bytes());
+        hasher.finalize()
+    }
+
+    /// 核对由调用方累计的最终明文：解密完成（压缩的文件解压完成）后调用。最终明文就是数据流的明文时
+    /// 摘要块已在数据流中核对过，直接返回
+    pub fn verify(&self) -> CryptoResult<()> {
+        if self.plaintext.is_none() {
+            return Ok(());
+        }
+        let Some(expected) = *self.expected.lock().unwrap() else {
+            return Err(CryptoError::DecryptionError("数据流缺少完整性摘要".to_string()));
+        };
+        if self.digest(&blake3::Hasher::new()) != blake3::Hash::from_bytes(expected) {
+            return Err(CryptoError::DecryptionError("解密后的数据或文件头与文件记录的摘要不符".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 把经过的最终明文计入 [`DigestBinding`] 的读写器包装
+pub struct PlaintextDigest<T> {
+    inner: T,
+    hasher: Option<Arc<Mutex<blake3::Hasher>>>,
+}
+
+impl<T> PlaintextDigest<T> {
+    /// 把经过的数据计入 `binding` 的最终明文摘要；没有绑定或最终明文就是数据流的明文时原样传递
+    pub fn new(inner: T, binding: Option<&DigestBinding>) -> Self {
+        Self { inner, hasher: binding.and_then(|binding| binding.plaintext.clone()) }
+    }
+
+    fn update(&self, data: &[u8]) {
+        if let Some(hasher) = &self.hasher {
+            hasher.lock().unwrap().update(data);
+        }
+    }
+}
+
+impl<R: Read> Read for PlaintextDigest<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for PlaintextDigest<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 加密时按顺序累计明文摘要，最后一块之后写出摘要块
+#[derive(Debug, Default)]
+pub struct ChunkDigest {
+    hasher: blake3::Hasher,
+    binding: Option<DigestBinding>,
+}
+
+impl ChunkDigest {
+    /// 摘要块按 `control` 中的 [`DigestBinding`] 绑定文件头
+    pub fn new(control: &StreamControl) -> Self {
+        Self { hasher: blake3::Hasher::new(), binding: control.binding().cloned() }
+    }
+
+    /// 按顺序加入一块明文
+    pub fn update(&mut self, plaintext: &[u8]) {
+        self.hasher.update(plaintext);
+    }
+
+    fn value(&self) -> blake3::Hash {
+        match &self.binding {
+            Some(binding) => binding.digest(&self.hasher),
+            None => self.hasher.finalize(),
+        }
+    }
+
+    /// 核对摘要块中的值；最终明文由调用方累计时只记下，由 [`DigestBinding::verify`] 核对
+    fn check(&self, expected: [u8; format::DIGEST_LEN]) -> CryptoResult<()> {
+        if let Some(binding) = self.binding.as_ref().filter(|binding| binding.plaintext.is_some()) {
+            *binding.expected.lock().unwrap() = Some(expected);
+            return Ok(());
+        }
+        if self.value() != blake3::Hash::from_bytes(expected) {
+            return Err(CryptoError::DecryptionError("解密后的数据与文件记录的摘要不符".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 加密并写出第 `index` 块（最后一块之后）的摘要块
+    pub fn write_chunk(&self, encryptor: &dyn ChunkEncryptor, nonces: &ChunkNonces, index: u64, writer: &mut dyn Write) -> CryptoResult<()> {
+        let (nonce, associated_data) = nonces.seal(index, ChunkKind::Digest)?;
+        let mut buffer = Vec::with_capacity(format::DIGEST_LEN + format::TAG_LEN);
+        buffer.extend_from_slice(self.value().as_bytes());
+        encryptor.encrypt_chunk(&nonce, &associated_data, &mut buffer)
+            .map_err(|e| chunk_error(e, index))?;
+        write_chunk(writer, &nonce, &buffer)?;
+        Ok(())
+    }
+}
+
+/// 解密时依次核对各块的序号和明文摘要，并检查数据流是否在摘要块处结束
+#[derive(Debug, Default)]
+pub struct ChunkSequence {
+    nonces: Option<ChunkNonces>,
+    index: u64,
+    /// 已读到最后一个数据块
+    finished: bool,
+    /// 已核对摘要块
+    verified: bool,
+    digest: ChunkDigest,
+}
+
+impl ChunkSequence {
+    /// 摘要块按 `control` 中的 [`DigestBinding`] 核对文件头
+    pub fn new(control: &StreamControl) -> Self {
+        Self { digest: ChunkDigest::new(control), ..Self::default() }
+    }
+
+    /// 读到下一块的nonce，返回该块的关联数据和作用
+    pub fn next(&mut self, nonce: &[u8]) -> CryptoResult<(Vec<u8>, ChunkKind)> {
+        if self.verified {
+            return Err(CryptoError::DecryptionError(format!("摘要块之后还有数据 (块 {})", self.index)));
+        }
+        let nonces = self.nonces.get_or_insert_with(|| ChunkNonces::detect(nonce));
+        let (associated_data, kind) = nonces.open(self.index, nonce)?;
+        match (self.finished, kind) {
+            (false, ChunkKind::Digest) => {
+                return Err(CryptoError::DecryptionError(format!("摘要块出现在最后一块之前 (块 {})", self.index)));
+            }
+            (true, ChunkKind::Data | ChunkKind::Last) => {
+                return Err(CryptoError::DecryptionError(format!("最后一块之后还有数据 (块 {})", self.index)));
+            }
+            _ => {}
+        }
+        self.finished |= kind == ChunkKind::Last;
+        self.verified = kind == ChunkKind::Digest;
+        self.index += 1;
+        Ok((associated_data, kind))
+    }
+
+    /// 按顺序交给解密后的一块明文，返回需要写出的部分：数据块原样返回，摘要块与已解出的明文核对后返回空
+    pub fn accept<'a>(&mut self, kind: ChunkKind, plaintext: &'a [u8]) -> CryptoResult<&'a [u8]> {
+        if kind != ChunkKind::Digest {
+            self.digest.update(plaintext);
+            return Ok(plaintext);
+        }
+        let expected = <[u8; format::DIGEST_LEN]>::try_from(plaintext)
+            .map_err(|_| CryptoError::DecryptionError("摘要块的长度无效".to_string()))?;
+        self.digest.check(expected)?;
+        Ok(&[])
+    }
+
+    /// 读到数据流末尾。新格式必须以摘要块结束；没有数据块的是旧格式的空文件
+    pub fn finish(&self) -> CryptoResult<()> {
+        match &self.nonces {
+            Some(ChunkNonces::Sequenced { .. }) if !self.finished => {
+                Err(CryptoError::DecryptionError(format!("数据在块 {} 之后被截断", self.index)))
+            }
+            Some(ChunkNonces::Sequenced { .. }) if !self.verified => {
+                Err(CryptoError::DecryptionError("数据在最后一块之后被截断，缺少完整性摘要".to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 读满一块明文到 `buffer`，只有最后一块会不满
+pub(crate) fn read_plaintext_chunk(reader: &mut dyn Read, buffer: &mut Vec<u8>, chunk_size: usize) -> io::Result<()> {
+    buffer.resize(chunk_size, 0);
+    let filled = format::read_chunk(reader, buffer)?;
+    buffer.truncate(filled);
+    Ok(())
+}
+
+/// 写出一个数据块：nonce、密文长度和密文
+pub(crate) fn write_chunk(writer: &mut dyn Write, nonce: &[u8], ciphertext: &[u8]) -> io::Result<()> {
+    writer.write_all(nonce)?;
+    writer.write_all(&(ciphertext.len() as u32).to_le_bytes())?;
+    writer.write_all(ciphertext)
+}
+
+/// 读取一个数据块的nonce、长度和密文，数据流在块边界处结束时返回None
+pub(crate) fn read_chunk(reader: &mut dyn Read, nonce_len: usize, max_ciphertext: usize, index: u64) -> CryptoResult<Option<(Vec<u8>, Vec<u8>)>> {
+    let mut nonce = vec![0u8; nonce_len];
+    match reader.read_exact(&mut nonce) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(CryptoError::IoError(e)),
+    }
+    let mut length = [0u8; format::LENGTH_LEN];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length) as usize;
+    // 单块密文不能超过明文块大小加认证标签，防止损坏的长度字段导致超大分配
+    if length < format::TAG_LEN || length > max_ciphertext {
+        return Err(CryptoError::DecryptionError(format!("数据块长度无效 (块 {}): {}", index, length)));
+    }
+    let mut ciphertext = vec![0u8; length];
+    reader.read_exact(&mut ciphertext)?;
+    Ok(Some((nonce, ciphertext)))
+}
+
+/// 顺序加密盐值之后的全部数据块，每块之前检查是否被取消，之后报告已加密的明文长度
+pub fn encrypt_chunks(
+    encryptor: &dyn ChunkEncryptor,
+    chunk_size: usize,
+    nonce_len: usize,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    control: &StreamControl,
+) -> CryptoResult<()> {
+    let nonces = ChunkNonces::generate(nonce_len);
+    let mut digest = ChunkDigest::new(control);
+    let mut current = Vec::with_capacity(chunk_size + format::TAG_LEN);
+    let mut next = Vec::with_capacity(chunk_size + format::TAG_LEN);
+    read_plaintext_chunk(reader, &mut current, chunk_size)?;
+
+    let mut index = 0;
+    let mut processed = 0;
+    loop {
+        control.check()?;
+        // 读满的块之后可能还有数据，先读出下一块才能确定当前块是否为最后一块
+        next.clear();
+        if current.len() == chunk_size {
+            read_plaintext_chunk(reader, &mut next, chunk_size)?;
+        }
+        let last = next.is_empty();
+        let (nonce, associated_data) = nonces.seal(index, ChunkKind::data(last))?;
+        digest.update(&current);
+        processed += current.len() as u64;
+        encryptor.encrypt_chunk(&nonce, &associated_data, &mut current)
+            .map_err(|e| chunk_error(e, index))?;
+        write_chunk(writer, &nonce, &current)?;
+        control.report(processed);
+        index += 1;
+        if last {
+            return digest.write_chunk(encryptor, &nonces, index, writer);
+        }
+        std::mem::swap(&mut current, &mut next);
+    }
+}
+
+/// 顺序解密盐值之后的全部数据块，每块原地解密，每块之前检查是否被取消，之后报告已读取的密文长度
+pub fn decrypt_chunks(
+    decryptor: &dyn ChunkDecryptor,
+    chunk_size: usize,
+    nonce_len: usize,
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    control: &StreamControl,
+) -> CryptoResult<()> {
+    let max_ciphertext = format::max_chunk_ciphertext(chunk_size);
+    let mut sequence = ChunkSequence::new(control);
+    let mut processed = format::SALT_LEN as u64;
+    for index in 0.. {
+        control.check()?;
+        let Some((nonce, mut buffer)) = read_chunk(reader, nonce_len, max_ciphertext, index)? else {
+            break;
+        };
+        processed += (nonce.len() + format::LENGTH_LEN + buffer.len()) as u64;
+        let (associated_data, kind) = sequence.next(&nonce)?;
+        decryptor.decrypt_chunk(&nonce, &associated_data, &mut buffer)
+            .map_err(|e| chunk_error(e, index))?;
+        writer.write_all(sequence.accept(kind, &buffer)?)?;
+        control.report(processed);
+    }
+    sequence.finish()
+}
+
+/// 解密 `data` 开头的第一个数据块，只用于判断密钥是否正确，没有数据块时返回true
+pub(crate) fn verify_first_chunk(decryptor: &dyn ChunkDecryptor, nonce_len: usize, max_ciphertext: usize, data: &[u8]) -> CryptoResult<bool> {
+    let Some((nonce, mut buffer)) = read_chunk(&mut &data[..], nonce_len, max_ciphertext, 0)? else {
+        return Ok(true);
+    };
+    let (associated_data, _) = ChunkNonces::detect(&nonce).open(0, &nonce)?;
+    Ok(decryptor.decrypt_chunk(&nonce, &associated_data, &mut buffer).is_ok())
+}
+
+/// 在加解密错误的说明后附上块序号
+pub(crate) fn chunk_error(error: CryptoError, index: u64) -> CryptoError {
+    match error {
+        CryptoError::EncryptionError(message) => CryptoError::EncryptionError(format!("{} (块 {})", message, index)),
+        CryptoError::DecryptionError(message) => CryptoError::DecryptionError(format!("{} (块 {})", message, index)),
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::create_crypto_provider;
+    use crate::models::EncryptionAlgorithm;
+
+    /// 按块拆开盐值之后的数据
+    fn split_chunks(data: &[u8], nonce_len: usize) -> Vec<Vec<u8>> {
+        let mut chunks = Vec::new();
+        let mut rest = &data[format::SALT_LEN..];
+        while !rest.is_empty() {
+            let length = u32::from_le_bytes(rest[nonce_len..nonce_len + 4].try_into().unwrap()) as usize;
+            let (chunk, tail) = rest.split_at(nonce_len + 4 + length);
+            chunks.push(chunk.to_vec());
+            rest = tail;
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_reordered_and_truncated_chunks_are_rejected() {
+        let provider = create_crypto_provider(&EncryptionAlgorithm::AES256);
+        let nonce_len = provider.nonce_len();
+        let plaintext: Vec<u8> = (0..provider.chunk_size() * 3).map(|i| (i % 249) as u8).collect();
+        let mut encrypted = Vec::new();
+        provider.encrypt_stream("pw", &mut plaintext.as_slice(), &mut encrypted, &StreamControl::default()).unwrap();
+        assert_eq!(encrypted.len() as u64, format::encrypted_size(plaintext.len() as u64, provider.chunk_size(), nonce_len));
+
+        let salt = &encrypted[..format::SALT_LEN];
+        let chunks = split_chunks(&encrypted, nonce_len);
+        assert_eq!(chunks.len(), 4);
+        let decrypt = |order: &[usize]| {
+            let mut data = salt.to_vec();
+            order.iter().for_each(|&i| data.extend_from_slice(&chunks[i]));
+            provider.decrypt_stream("pw", &mut data.as_slice(), &mut Vec::new(), &StreamControl::default())
+        };
+        assert!(decrypt(&[0, 1, 2, 3]).is_ok());
+        assert!(decrypt(&[0, 2, 1, 3]).is_err());
+        assert!(decrypt(&[0, 1, 1, 2, 3]).is_err());
+        assert!(decrypt(&[0, 1, 3]).is_err());
+        assert!(decrypt(&[0, 1, 2, 3, 3]).is_err());
+        // 缺少摘要块时每个数据块都能解密，但数据流不完整
+        assert!(decrypt(&[0, 1, 2]).is_err());
+
+        // 空明文也有一个最后一块和摘要块，只剩盐值时按旧格式的空文件处理
+        let mut empty = Vec::new();
+        provider.encrypt_stream("pw", &mut &b""[..], &mut empty, &StreamControl::default()).unwrap();
+        assert_eq!(split_chunks(&empty, nonce_len).len(), 2);
+        assert!(provider.decrypt_stream("pw", &mut empty.as_slice(), &mut Vec::new(), &StreamControl::default()).is_ok());
+    }
+
+    #[test]
+    fn test_progress_reports_input_bytes() {
+        use std::sync::{Arc, Mutex};
+
+        let provider = create_crypto_provider(&EncryptionAlgorithm::ChaCha20);
+        let plaintext = vec![5u8; provider.chunk_size() * 2 + 100];
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let recorded = reports.clone();
+        let control = StreamControl::default().with_progress(Arc::new(move |processed| recorded.lock().unwrap().push(processed)));
+
+        let mut encrypted = Vec::new();
+        provider.encrypt_stream("pw", &mut plaintext.as_slice(), &mut encrypted, &control).unwrap();
+        let chunk_size = provider.chunk_size() as u64;
+        assert_eq!(*reports.lock().unwrap(), vec![chunk_size, chunk_size * 2, plaintext.len() as u64]);
+
+        reports.lock().unwrap().clear();
+        provider.decrypt_stream("pw", &mut encrypted.as_slice(), &mut Vec::new(), &control).unwrap();
+        let reports = reports.lock().unwrap();
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(reports.last(), Some(&(encrypted.len() as u64)));
+    }
+
+    #[test]
+    fn test_legacy_random_nonces_still_decrypt() {
+        use aes_gcm::aead::{Aead, KeyInit};
+        use aes_gcm::{Aes256Gcm, Nonce};
+        use crate::crypto::traits::{Argon2KeyDerivation, KeyDerivation};
+
+        // 旧格式：随机nonce、没有关联数据，最后一块之前允许不满的块
+        let kdf = Argon2KeyDerivation::default();
+        let salt = kdf.generate_salt();
+        let cipher = Aes256Gcm::new_from_slice(&kdf.derive_key("pw", &salt).unwrap()).unwrap();
+        let mut legacy = salt.clone();
+        for (nonce, part) in [([9u8; 12], &b"old "[..]), ([7u8; 12], &b"format"[..])] {
+            let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), part).unwrap();
+            write_chunk(&mut legacy, &nonce, &ciphertext).unwrap();
+        }
+        let mut decrypted = Vec::new();
+        create_crypto_provider(&EncryptionAlgorithm::AES256).decrypt_stream("pw", &mut legacy.as_slice(), &mut decrypted, &StreamControl::default()).unwrap();
+        assert_eq!(decrypted, b"old format");
+    }
+}
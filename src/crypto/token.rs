@@ -0,0 +1,74 @@
+//! 硬件令牌参与的密钥派生
+//!
+//! 加密时为每个文件生成随机挑战并写入文件头，YubiKey用槽位中的HMAC-SHA1密钥计算响应，
+//! 响应与密码混合后再交给加密提供者派生密钥。解密时需要同一个YubiKey和密码。
+//! 通过 ykchalresp（yubikey-personalization）或 ykman 访问令牌
+
+use super::header::{TokenChallenge, TOKEN_CHALLENGE_LEN};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::io;
+use std::process::Command;
+
+/// 为新文件生成挑战并混合令牌响应，返回混合后的密码和需要写入文件头的挑战
+pub fn bind_password(slot: Option<u8>, password: &str) -> Result<(String, Option<TokenChallenge>), String> {
+    let Some(slot) = slot else {
+        return Ok((password.to_string(), None));
+    };
+    let token = TokenChallenge { slot, challenge: rand::random::<[u8; TOKEN_CHALLENGE_LEN]>() };
+    let response = challenge_response(&token)?;
+    Ok((mix_response(password, &response), Some(token)))
+}
+
+/// 解密时使用的密码：文件头中有挑战时向令牌请求响应并混合
+pub fn unlock_password(password: &str, token: Option<&TokenChallenge>) -> Result<String, String> {
+    match token {
+        Some(token) => Ok(mix_response(password, &challenge_response(token)?)),
+        None => Ok(password.to_string()),
+    }
+}
+
+/// 以令牌响应为密钥对密码做HMAC-SHA256，结果的十六进制形式代替密码
+fn mix_response(password: &str, response: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(response).expect("HMAC accepts keys of any length");
+    mac.update(password.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// 向YubiKey发送挑战，返回HMAC-SHA1响应
+fn challenge_response(token: &TokenChallenge) -> Result<Vec<u8>, String> {
+    let challenge = hex::encode(token.challenge);
+    let slot = token.slot.to_string();
+    let output = match Command::new("ykchalresp").arg(format!("-{}", slot)).arg("-x").arg(&challenge).output() {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Command::new("ykman")
+            .args(["otp", "calculate", &slot, &challenge])
+            .output()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => "Neither ykchalresp nor ykman is installed".to_string(),
+                _ => format!("Failed to run ykman: {}", e),
+            })?,
+        result => result.map_err(|e| format!("Failed to run ykchalresp: {}", e))?,
+    };
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("YubiKey slot {} did not answer the challenge: {}", slot, message.trim()));
+    }
+    let response = String::from_utf8_lossy(&output.stdout);
+    hex::decode(response.trim()).map_err(|_| format!("Unexpected YubiKey response: {}", response.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_password_depends_on_response() {
+        let password = "correct horse";
+        let mixed = mix_response(password, &[1; 20]);
+        assert_eq!(mixed, mix_response(password, &[1; 20]));
+        assert_ne!(mixed, mix_response(password, &[2; 20]));
+        assert_ne!(mixed, mix_response("other", &[1; 20]));
+        assert_eq!(unlock_password(password, None).unwrap(), password);
+    }
+}
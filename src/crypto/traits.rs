@@ -1,5 +1,17 @@
+use crate::i18n;
+use super::header::WRAPPED_SESSION_KEY_LEN;
+use crate::models::{KdfAlgorithm, KdfParams};
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 use std::io::{Read, Write};
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
 
 /// 加密操作结果类型
 pub type CryptoResult<T> = Result<T, CryptoError>;
@@ -13,17 +25,48 @@ pub enum CryptoError {
     KeyDerivationError(String),
     InvalidPassword,
     InvalidFormat,
+    /// 处理中途被取消，不完整的输出由调用方删除
+    Cancelled,
 }
 
+impl CryptoError {
+    /// 稳定的错误代码，不随语言变化，供脚本和日志检索使用
+    pub fn code(&self) -> &'static str {
+        match self {
+            CryptoError::IoError(_) => "E1001",
+            CryptoError::EncryptionError(_) => "E1002",
+            CryptoError::DecryptionError(_) => "E1003",
+            CryptoError::KeyDerivationError(_) => "E1004",
+            CryptoError::InvalidPassword => "E1005",
+            CryptoError::InvalidFormat => "E1006",
+            CryptoError::Cancelled => "E1007",
+        }
+    }
+
+    /// 本地化消息的键（见 [`crate::i18n`]）
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            CryptoError::IoError(_) => "error.io",
+            CryptoError::EncryptionError(_) => "error.encryption",
+            CryptoError::DecryptionError(_) => "error.decryption",
+            CryptoError::KeyDerivationError(_) => "error.key_derivation",
+            CryptoError::InvalidPassword => "error.invalid_password",
+            CryptoError::InvalidFormat => "error.invalid_format",
+            CryptoError::Cancelled => "error.cancelled",
+        }
+    }
+}
+
+/// 以当前语言显示，格式为 `[代码] 消息: 详细信息`
 impl fmt::Display for CryptoError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code(), i18n::tr(self.message_key()))?;
         match self {
-            CryptoError::IoError(e) => write!(f, "IO错误: {}", e),
-            CryptoError::EncryptionError(msg) => write!(f, "加密错误: {}", msg),
-            CryptoError::DecryptionError(msg) => write!(f, "解密错误: {}", msg),
-            CryptoError::KeyDerivationError(msg) => write!(f, "密钥派生错误: {}", msg),
-            CryptoError::InvalidPassword => write!(f, "密码无效"),
-            CryptoError::InvalidFormat => write!(f, "文件格式无效"),
+            CryptoError::IoError(e) => write!(f, ": {}", e),
+            CryptoError::EncryptionError(msg)
+            | CryptoError::DecryptionError(msg)
+            | CryptoError::KeyDerivationError(msg) => write!(f, ": {}", msg),
+            CryptoError::InvalidPassword | CryptoError::InvalidFormat | CryptoError::Cancelled => Ok(()),
         }
     }
 }
@@ -36,6 +79,88 @@ impl From<std::io::Error> for CryptoError {
     }
 }
 
+/// 数据流已读取的输入字节数的回调
+pub type StreamProgress = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// 加解密数据流过程中的控制，分块处理的循环每块检查一次是否取消并报告进度。默认值永远不会取消
+#[derive(Clone, Default)]
+pub struct StreamControl {
+    stop: Option<Arc<AtomicBool>>,
+    skip: Option<Arc<AtomicBool>>,
+    progress: Option<StreamProgress>,
+    binding: Option<super::stream::DigestBinding>,
+}
+
+impl fmt::Debug for StreamControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamControl")
+            .field("stop", &self.stop)
+            .field("skip", &self.skip)
+            .field("progress", &self.progress.is_some())
+            .field("binding", &self.binding)
+            .finish()
+    }
+}
+
+impl StreamControl {
+    /// `stop` 被设置后，正在处理的数据流在当前块结束后中断
+    pub fn new(stop: Arc<AtomicBool>) -> Self {
+        Self { stop: Some(stop), ..Self::default() }
+    }
+
+    /// 同时响应跳过：`skip` 是这一个文件的跳过标志，被设置后数据流在当前块结束后中断
+    pub fn with_skip(self, skip: Arc<AtomicBool>) -> Self {
+        Self { skip: Some(skip), ..self }
+    }
+
+    /// 已被取消或跳过时返回 [`CryptoError::Cancelled`]
+    pub fn check(&self) -> CryptoResult<()> {
+        if self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return Err(CryptoError::Cancelled);
+        }
+        if self.skipped() {
+            return Err(CryptoError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// 是否已请求跳过这个文件
+    pub fn skipped(&self) -> bool {
+        self.skip.as_ref().is_some_and(|skip| skip.load(Ordering::Relaxed))
+    }
+
+    /// 每处理完一块后用已读取的输入字节数（加密时为明文，解密时为密文）调用 `progress`
+    pub fn with_progress(self, progress: StreamProgress) -> Self {
+        Self { progress: Some(progress), ..self }
+    }
+
+    /// 不报告进度的副本，用于级联加密中不直接读取输入的一层
+    pub fn without_progress(&self) -> Self {
+        Self { progress: None, ..self.clone() }
+    }
+
+    /// 数据流的摘要块绑定文件头和最终明文（见 [`super::stream::DigestBinding`]）
+    pub fn with_binding(self, binding: Option<super::stream::DigestBinding>) -> Self {
+        Self { binding, ..self }
+    }
+
+    /// 不绑定文件头的副本，用于级联加密的外层：摘要块只在直接处理明文的内层绑定
+    pub fn without_binding(&self) -> Self {
+        Self { binding: None, ..self.clone() }
+    }
+
+    pub fn binding(&self) -> Option<&super::stream::DigestBinding> {
+        self.binding.as_ref()
+    }
+
+    /// 报告已读取的输入字节数
+    pub fn report(&self, processed: u64) {
+        if let Some(progress) = &self.progress {
+            progress(processed);
+        }
+    }
+}
+
 /// 通用加密提供者接口
 pub trait CryptoProvider: Send + Sync {
     /// 获取算法名称
@@ -43,29 +168,62 @@ pub trait CryptoProvider: Send + Sync {
     
     /// 获取推荐的分块大小
     fn chunk_size(&self) -> usize {
-        1024 * 1024 // 默认1MB
+        super::format::DEFAULT_CHUNK_SIZE
+    }
+
+    /// 每个数据块的nonce长度
+    fn nonce_len(&self) -> usize {
+        super::format::NONCE_LEN
     }
     
-    /// 加密数据流
-    fn encrypt_stream<R: Read, W: Write>(
+    /// 加密数据流，每块之后通过 `control` 报告进度，`control` 被取消时在当前块之后返回 [`CryptoError::Cancelled`]
+    fn encrypt_stream(
         &self,
         password: &str,
-        reader: &mut R,
-        writer: &mut W,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
     ) -> CryptoResult<()>;
     
-    /// 解密数据流
-    fn decrypt_stream<R: Read, W: Write>(
+    /// 解密数据流，每块之后通过 `control` 报告进度，`control` 被取消时在当前块之后返回 [`CryptoError::Cancelled`]
+    fn decrypt_stream(
         &self,
         password: &str,
-        reader: &mut R,
-        writer: &mut W,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
     ) -> CryptoResult<()>;
     
-    /// 验证密码（可选实现）
-    fn verify_password(&self, _password: &str, _data: &[u8]) -> CryptoResult<bool> {
-        Ok(true) // 默认实现总是返回true
+    /// 验证密码：`data` 为盐值和完整的第一个数据块（之后可以有更多数据），第一块能通过认证即密码正确。
+    /// 默认实现使用 [`Self::chunk_decryptor`]，只有盐值的旧格式空文件无法验证，返回true
+    fn verify_password(&self, password: &str, data: &[u8]) -> CryptoResult<bool> {
+        let salt = data.get(..super::format::SALT_LEN).ok_or(CryptoError::InvalidFormat)?;
+        let decryptor = self.chunk_decryptor(password, salt)?;
+        let max_ciphertext = super::format::max_chunk_ciphertext(self.chunk_size());
+        super::stream::verify_first_chunk(decryptor.as_ref(), self.nonce_len(), max_ciphertext, &data[salt.len()..])
     }
+
+    /// 根据盐值创建单块解密器，用于随机访问和并行解密（可选实现）
+    fn chunk_decryptor(&self, _password: &str, _salt: &[u8]) -> CryptoResult<Box<dyn ChunkDecryptor>> {
+        Err(CryptoError::DecryptionError(format!("{} 不支持随机访问解密", self.algorithm_name())))
+    }
+
+    /// 生成盐值并创建单块加密器，用于并行加密（可选实现），返回盐值和加密器
+    fn chunk_encryptor(&self, _password: &str) -> CryptoResult<(Vec<u8>, Box<dyn ChunkEncryptor>)> {
+        Err(CryptoError::EncryptionError(format!("{} 不支持分块并行加密", self.algorithm_name())))
+    }
+}
+
+/// 单个数据块的解密器，密钥只派生一次，可按任意顺序解密数据块
+pub trait ChunkDecryptor: Send + Sync {
+    /// 原地解密：`buffer` 中的密文（末尾为认证标签）替换为明文
+    fn decrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()>;
+}
+
+/// 单个数据块的加密器，密钥只派生一次，nonce和关联数据由调用方按块序号生成（见 [`super::stream`]）
+pub trait ChunkEncryptor: Send + Sync {
+    /// 原地加密：`buffer` 中的明文替换为密文，并在末尾追加认证标签
+    fn encrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()>;
 }
 
 /// 密钥派生工具trait
@@ -77,15 +235,45 @@ pub trait KeyDerivation {
     fn generate_salt(&self) -> Vec<u8>;
 }
 
-/// 默认的Argon2密钥派生实现
-#[derive(Debug)]
-pub struct Argon2KeyDerivation;
+/// Argon2id密钥派生实现
+#[derive(Debug, Clone, Default)]
+pub struct Argon2KeyDerivation {
+    pub params: KdfParams,
+}
+
+impl Argon2KeyDerivation {
+    pub fn with_params(params: KdfParams) -> Self {
+        Self { params }
+    }
+
+    /// 在本机测量Argon2的速度，选择解锁时间接近 `target` 的参数
+    ///
+    /// 先用64 MiB、1次迭代测出每KiB·迭代的耗时，再优先增加内存（上限1 GiB），内存到上限后增加迭代次数
+    pub fn calibrate(target: Duration) -> CryptoResult<KdfParams> {
+        const PROBE_MEMORY_KIB: u32 = 64 * 1024;
+        const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+        let probe = Self::with_params(KdfParams { memory_kib: PROBE_MEMORY_KIB, iterations: 1, parallelism: 1, ..KdfParams::default() });
+        let salt = probe.generate_salt();
+        let started = Instant::now();
+        probe.derive_key("calibration", &salt)?;
+        let per_unit = started.elapsed().as_secs_f64() / PROBE_MEMORY_KIB as f64;
+
+        let budget = (target.as_secs_f64() / per_unit.max(f64::MIN_POSITIVE)).min(u32::MAX as f64) as u64;
+        let minimum = KdfParams::default();
+        let memory_kib = (budget.min(MAX_MEMORY_KIB as u64) as u32 / 1024 * 1024).max(minimum.memory_kib);
+        let iterations = (budget / memory_kib as u64).clamp(1, 64) as u32;
+        Ok(KdfParams { memory_kib, iterations, parallelism: 1, ..KdfParams::default() })
+    }
+}
 
 impl KeyDerivation for Argon2KeyDerivation {
     fn derive_key(&self, password: &str, salt: &[u8]) -> CryptoResult<Vec<u8>> {
-        use argon2::{Argon2, PasswordHasher, password_hash::SaltString};
+        use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version, password_hash::SaltString};
         
-        let argon2 = Argon2::default();
+        let params = Params::new(self.params.memory_kib, self.params.iterations, self.params.parallelism, None)
+            .map_err(|e| CryptoError::KeyDerivationError(format!("密钥派生参数无效: {}", e)))?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
         let password_bytes = password.as_bytes();
         let salt_string = SaltString::encode_b64(salt)
             .map_err(|e| CryptoError::KeyDerivationError(format!("盐值编码失败: {}", e)))?;
@@ -106,4 +294,235 @@ impl KeyDerivation for Argon2KeyDerivation {
         OsRng.fill_bytes(&mut salt);
         salt
     }
-} 
\ No newline at end of file
+}
+
+/// scrypt密钥派生实现：N为参数中的内存（KiB），r = 8，p为并行度
+#[derive(Debug, Clone)]
+pub struct ScryptKeyDerivation {
+    pub params: KdfParams,
+}
+
+impl KeyDerivation for ScryptKeyDerivation {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> CryptoResult<Vec<u8>> {
+        let params = scrypt::Params::new(self.params.memory_kib.trailing_zeros() as u8, 8, self.params.parallelism, 32)
+            .map_err(|e| CryptoError::KeyDerivationError(format!("密钥派生参数无效: {}", e)))?;
+        let mut key = vec![0u8; 32];
+        scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+            .map_err(|e| CryptoError::KeyDerivationError(format!("密钥派生失败: {}", e)))?;
+        Ok(key)
+    }
+
+    fn generate_salt(&self) -> Vec<u8> {
+        Argon2KeyDerivation::default().generate_salt()
+    }
+}
+
+/// PBKDF2-HMAC-SHA256密钥派生实现，只使用参数中的迭代次数
+#[derive(Debug, Clone)]
+pub struct Pbkdf2KeyDerivation {
+    pub params: KdfParams,
+}
+
+impl KeyDerivation for Pbkdf2KeyDerivation {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> CryptoResult<Vec<u8>> {
+        let mut key = vec![0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, self.params.iterations, &mut key);
+        Ok(key)
+    }
+
+    fn generate_salt(&self) -> Vec<u8> {
+        Argon2KeyDerivation::default().generate_salt()
+    }
+}
+
+/// 批处理主密钥的HKDF上下文
+const BATCH_KEY_INFO: &[u8] = b"krypton batch file key v1";
+
+/// 最近派生的批处理主密钥，只在有操作持有 [`BatchKeyScope`] 时缓存
+static RECENT_BATCH_KEY: Mutex<BatchKeyCache> = Mutex::new(BatchKeyCache { operations: 0, recent: None });
+
+struct BatchKeyCache {
+    /// 持有 [`BatchKeyScope`] 的操作数
+    operations: usize,
+    /// （密码摘要, 主密钥），解密同一批文件时不必重复运行Argon2
+    recent: Option<([u8; 32], BatchKey)>,
+}
+
+/// 一次操作（批处理、审计、迁移）期间缓存派生的批处理主密钥；
+/// 最后一个操作结束、丢弃它时清除并清零缓存的主密钥和密码摘要
+pub struct BatchKeyScope(());
+
+impl Drop for BatchKeyScope {
+    fn drop(&mut self) {
+        let mut cache = RECENT_BATCH_KEY.lock().unwrap();
+        cache.operations -= 1;
+        if cache.operations == 0 {
+            if let Some((mut digest, _)) = cache.recent.take() {
+                digest.zeroize();
+            }
+        }
+    }
+}
+
+/// 批处理主密钥：整批文件只运行一次Argon2，每个文件再用HKDF从主密钥和文件自己的盐值派生密钥
+///
+/// 主密钥可以直接由密码派生，也可以是随机的会话密钥（用密码派生的密钥包装）。
+/// 盐值和包装后的会话密钥记录在每个文件头中，每个文件仍可单独解密
+#[derive(Clone)]
+pub struct BatchKey {
+    /// 派生主密钥（或包装会话密钥的密钥）时使用的盐值
+    pub salt: [u8; 32],
+    /// 包装后的会话密钥，主密钥直接由密码派生时为None
+    pub wrapped_session_key: Option<[u8; WRAPPED_SESSION_KEY_LEN]>,
+    master: [u8; 32],
+}
+
+impl BatchKey {
+    /// 为新的批处理生成盐值并派生主密钥
+    pub fn generate(password: &str, params: KdfParams) -> CryptoResult<Self> {
+        let mut salt = [0u8; 32];
+        salt.copy_from_slice(&Argon2KeyDerivation::default().generate_salt());
+        Self::derive(password, salt, params)
+    }
+
+    /// 开始一次缓存主密钥的操作，见 [`BatchKeyScope`]
+    pub fn cache_scope() -> BatchKeyScope {
+        RECENT_BATCH_KEY.lock().unwrap().operations += 1;
+        BatchKeyScope(())
+    }
+
+    /// 从密码和文件头中的盐值派生主密钥；操作持有 [`BatchKeyScope`] 且与上次派生的参数相同时直接返回上次的结果
+    pub fn derive(password: &str, salt: [u8; 32], params: KdfParams) -> CryptoResult<Self> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+        let mut digest: [u8; 32] = Sha256::new()
+            .chain_update(password.as_bytes())
+            .chain_update(salt)
+            .chain_update(params.to_string().as_bytes())
+            .finalize()
+            .into();
+
+        // 持有锁派生，同时解密同一批文件的其他线程等待后直接使用结果
+        let mut cache = RECENT_BATCH_KEY.lock().unwrap();
+        if let Some((_, key)) = cache.recent.as_ref().filter(|(cached, _)| *cached == digest) {
+            digest.zeroize();
+            return Ok(key.clone());
+        }
+        let mut derived = FileKeyDerivation::password(params).derive_key(password, &salt)?;
+        let mut master = [0u8; 32];
+        master.copy_from_slice(&derived);
+        derived.zeroize();
+        let key = Self { salt, wrapped_session_key: None, master };
+        if cache.operations > 0 {
+            if let Some((mut replaced, _)) = cache.recent.replace((digest, key.clone())) {
+                replaced.zeroize();
+            }
+        } else {
+            digest.zeroize();
+        }
+        Ok(key)
+    }
+
+    /// 为新的批处理生成随机会话密钥，并用密码派生的密钥包装
+    pub fn generate_session(password: &str, params: KdfParams) -> CryptoResult<Self> {
+        let wrapping = Self::generate(password, params)?;
+        let mut session = [0u8; 32];
+        OsRng.fill_bytes(&mut session);
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = ChaCha20Poly1305::new((&wrapping.master).into())
+            .encrypt(Nonce::from_slice(&nonce), session.as_slice())
+            .map_err(|e| CryptoError::EncryptionError(format!("会话密钥包装失败: {}", e)))?;
+
+        let mut wrapped = [0u8; WRAPPED_SESSION_KEY_LEN];
+        wrapped[..12].copy_from_slice(&nonce);
+        wrapped[12..].copy_from_slice(&ciphertext);
+        let key = Self { salt: wrapping.salt, wrapped_session_key: Some(wrapped), master: session };
+        session.zeroize();
+        Ok(key)
+    }
+
+    /// 用密码和文件头中的盐值解包会话密钥，密码错误时返回 [`CryptoError::InvalidPassword`]
+    pub fn open_session(password: &str, salt: [u8; 32], wrapped: &[u8; WRAPPED_SESSION_KEY_LEN], params: KdfParams) -> CryptoResult<Self> {
+        let wrapping = Self::derive(password, salt, params)?;
+        let mut session = ChaCha20Poly1305::new((&wrapping.master).into())
+            .decrypt(Nonce::from_slice(&wrapped[..12]), &wrapped[12..])
+            .map_err(|_| CryptoError::InvalidPassword)?;
+        let mut master = [0u8; 32];
+        master.copy_from_slice(&session);
+        session.zeroize();
+        Ok(Self { salt, wrapped_session_key: Some(*wrapped), master })
+    }
+}
+
+impl Drop for BatchKey {
+    fn drop(&mut self) {
+        self.master.zeroize();
+    }
+}
+
+impl fmt::Debug for BatchKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchKey").field("salt", &hex::encode(self.salt)).finish_non_exhaustive()
+    }
+}
+
+impl KeyDerivation for BatchKey {
+    /// 文件密钥只由主密钥和文件的盐值决定，密码已在派生主密钥时使用
+    fn derive_key(&self, _password: &str, salt: &[u8]) -> CryptoResult<Vec<u8>> {
+        let mut key = vec![0u8; 32];
+        Hkdf::<Sha256>::new(Some(salt), &self.master)
+            .expand(BATCH_KEY_INFO, &mut key)
+            .map_err(|e| CryptoError::KeyDerivationError(format!("HKDF派生失败: {}", e)))?;
+        Ok(key)
+    }
+
+    fn generate_salt(&self) -> Vec<u8> {
+        Argon2KeyDerivation::default().generate_salt()
+    }
+}
+
+/// 提供者派生每个文件密钥的方式
+#[derive(Debug, Clone)]
+pub enum FileKeyDerivation {
+    /// 每个文件用Argon2从密码派生
+    Argon2(Argon2KeyDerivation),
+    /// 每个文件用scrypt从密码派生
+    Scrypt(ScryptKeyDerivation),
+    /// 每个文件用PBKDF2从密码派生
+    Pbkdf2(Pbkdf2KeyDerivation),
+    /// 从批处理主密钥派生
+    Batch(BatchKey),
+}
+
+impl FileKeyDerivation {
+    /// 按参数中的算法从密码派生
+    pub fn password(params: KdfParams) -> Self {
+        match params.algorithm {
+            KdfAlgorithm::Argon2id => FileKeyDerivation::Argon2(Argon2KeyDerivation::with_params(params)),
+            KdfAlgorithm::Scrypt => FileKeyDerivation::Scrypt(ScryptKeyDerivation { params }),
+            KdfAlgorithm::Pbkdf2Sha256 => FileKeyDerivation::Pbkdf2(Pbkdf2KeyDerivation { params }),
+        }
+    }
+}
+
+impl KeyDerivation for FileKeyDerivation {
+    fn derive_key(&self, password: &str, salt: &[u8]) -> CryptoResult<Vec<u8>> {
+        match self {
+            FileKeyDerivation::Argon2(kdf) => kdf.derive_key(password, salt),
+            FileKeyDerivation::Scrypt(kdf) => kdf.derive_key(password, salt),
+            FileKeyDerivation::Pbkdf2(kdf) => kdf.derive_key(password, salt),
+            FileKeyDerivation::Batch(key) => key.derive_key(password, salt),
+        }
+    }
+
+    fn generate_salt(&self) -> Vec<u8> {
+        match self {
+            FileKeyDerivation::Argon2(kdf) => kdf.generate_salt(),
+            FileKeyDerivation::Scrypt(kdf) => kdf.generate_salt(),
+            FileKeyDerivation::Pbkdf2(kdf) => kdf.generate_salt(),
+            FileKeyDerivation::Batch(key) => key.generate_salt(),
+        }
+    }
+}
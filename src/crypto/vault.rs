@@ -0,0 +1,491 @@
+//! 单文件加密容器（迷你保险库）
+//!
+//! 文件结构：
+//! 头部 "KVLT"(4) + 版本(1) + 盐值(32)，之后是各条目的密文块，
+//! 然后是加密的条目表，最后16字节为尾部：条目表偏移(u64 LE) + 条目表长度(u32 LE) + "KVIX"(4)。
+//!
+//! 每个密文块独立使用AES-256-GCM加密，块的位置和nonce记录在（同样加密认证的）条目表中，
+//! 因此添加、删除条目时只需追加新数据和新的条目表，无需重新加密整个容器。
+//! 旧的条目表和已删除条目的数据会留在文件中，直到执行压缩。
+
+use super::traits::{Argon2KeyDerivation, CryptoError, CryptoResult, KeyDerivation};
+use aes_gcm::aead::{Aead, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 容器文件的扩展名
+pub const VAULT_FILE_EXTENSION: &str = "kvault";
+
+const VAULT_MAGIC: [u8; 4] = *b"KVLT";
+const VAULT_VERSION: u8 = 1;
+const FOOTER_MAGIC: [u8; 4] = *b"KVIX";
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: u64 = (VAULT_MAGIC.len() + 1 + SALT_LEN) as u64;
+const FOOTER_LEN: u64 = 8 + 4 + FOOTER_MAGIC.len() as u64;
+/// 条目数据的分块大小
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// 条目表的最大长度，防止损坏的尾部导致分配过多内存
+const MAX_INDEX_LEN: u32 = 64 * 1024 * 1024;
+
+/// 区分数据块和条目表的附加认证数据
+const CHUNK_AAD: &[u8] = b"krypton-vault-chunk";
+const INDEX_AAD: &[u8] = b"krypton-vault-index";
+
+/// 条目数据块在容器中的位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkRef {
+    pub offset: u64,
+    /// 密文长度（含认证标签）
+    pub len: u32,
+    /// nonce（十六进制）
+    pub nonce: String,
+}
+
+/// 容器中的一个条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub name: String,
+    /// 明文大小
+    pub size: u64,
+    /// 原文件的修改时间（Unix秒）
+    pub modified: i64,
+    pub chunks: Vec<ChunkRef>,
+}
+
+impl VaultEntry {
+    /// 条目在容器中占用的字节数
+    pub fn stored_size(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.len as u64).sum()
+    }
+}
+
+/// 打开的加密容器
+pub struct Vault {
+    path: PathBuf,
+    file: File,
+    salt: Vec<u8>,
+    cipher: Aes256Gcm,
+    entries: Vec<VaultEntry>,
+}
+
+impl Vault {
+    /// 创建新的空容器（文件已存在时失败）
+    pub fn create(path: &Path, password: &str) -> CryptoResult<Self> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+        let key_derivation = Argon2KeyDerivation::default();
+        let salt = key_derivation.generate_salt();
+        let cipher = Self::cipher(password, &salt)?;
+
+        let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        file.write_all(&VAULT_MAGIC)?;
+        file.write_all(&[VAULT_VERSION])?;
+        file.write_all(&salt)?;
+
+        let mut vault = Self {
+            path: path.to_path_buf(),
+            file,
+            salt,
+            cipher,
+            entries: Vec::new(),
+        };
+        vault.write_index()?;
+        Ok(vault)
+    }
+
+    /// 打开已有容器，密码错误时返回 [`CryptoError::InvalidPassword`]
+    pub fn open(path: &Path, password: &str) -> CryptoResult<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| CryptoError::InvalidFormat)?;
+        if header[..4] != VAULT_MAGIC {
+            return Err(CryptoError::InvalidFormat);
+        }
+        if header[4] != VAULT_VERSION {
+            return Err(CryptoError::DecryptionError(format!("不支持的容器版本: {}", header[4])));
+        }
+        let salt = header[5..].to_vec();
+        let cipher = Self::cipher(password, &salt)?;
+
+        let mut vault = Self {
+            path: path.to_path_buf(),
+            file,
+            salt,
+            cipher,
+            entries: Vec::new(),
+        };
+        vault.entries = vault.read_index()?;
+        Ok(vault)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> &[VaultEntry] {
+        &self.entries
+    }
+
+    /// 文件中不再被引用的字节数（已删除的条目和旧的条目表），可通过压缩回收
+    pub fn wasted_bytes(&self) -> io::Result<u64> {
+        let live: u64 = self.entries.iter().map(VaultEntry::stored_size).sum();
+        let (_, index_len) = self.current_footer()?;
+        let used = HEADER_LEN + live + index_len as u64 + FOOTER_LEN;
+        Ok(self.file.metadata()?.len().saturating_sub(used))
+    }
+
+    /// 添加文件，已有同名条目时替换
+    pub fn add_file(&mut self, source: &Path) -> CryptoResult<()> {
+        let name = source.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| CryptoError::EncryptionError(format!("无效的文件名: {}", source.display())))?;
+        let modified = fs::metadata(source)?.modified().ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let mut reader = io::BufReader::new(File::open(source)?);
+        self.add(&name, &mut reader, modified)
+    }
+
+    /// 从数据流添加条目，已有同名条目时替换
+    ///
+    /// 新数据追加到文件末尾，最后写入新的条目表；中途失败时旧的条目表仍然有效
+    pub fn add(&mut self, name: &str, reader: &mut dyn Read, modified: i64) -> CryptoResult<()> {
+        let mut offset = self.file.seek(SeekFrom::End(0))?;
+        let mut entry = VaultEntry {
+            name: name.to_string(),
+            size: 0,
+            modified,
+            chunks: Vec::new(),
+        };
+
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            let bytes_read = read_full(reader, &mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            let (nonce, ciphertext) = self.seal(&buffer[..bytes_read], CHUNK_AAD)?;
+            self.file.write_all(&ciphertext)?;
+            entry.chunks.push(ChunkRef {
+                offset,
+                len: ciphertext.len() as u32,
+                nonce: hex::encode(nonce),
+            });
+            entry.size += bytes_read as u64;
+            offset += ciphertext.len() as u64;
+        }
+
+        let mut entries = self.entries.clone();
+        entries.retain(|existing| existing.name != entry.name);
+        entries.push(entry);
+        entries.sort_by_key(|entry| entry.name.to_lowercase());
+        self.commit(entries)
+    }
+
+    /// 把条目解密写出到数据流
+    pub fn extract(&mut self, name: &str, writer: &mut dyn Write) -> CryptoResult<()> {
+        let entry = self.entry(name)?.clone();
+        for (index, chunk) in entry.chunks.iter().enumerate() {
+            self.file.seek(SeekFrom::Start(chunk.offset))?;
+            let mut ciphertext = vec![0u8; chunk.len as usize];
+            self.file.read_exact(&mut ciphertext)?;
+            let nonce = hex::decode(&chunk.nonce)
+                .map_err(|_| CryptoError::InvalidFormat)?;
+            let plaintext = self.open_sealed(&nonce, &ciphertext, CHUNK_AAD)
+                .map_err(|_| CryptoError::DecryptionError(format!("条目 '{}' 的块 {} 认证失败", name, index)))?;
+            writer.write_all(&plaintext)?;
+        }
+        Ok(())
+    }
+
+    /// 把条目解密到目录中，返回输出文件路径（同名文件已存在时失败）
+    pub fn extract_to(&mut self, name: &str, directory: &Path) -> CryptoResult<PathBuf> {
+        // 条目名称只能是单个文件名，防止写到目标目录之外
+        let file_name = Path::new(name).file_name();
+        if file_name.is_none() || file_name != Some(std::ffi::OsStr::new(name)) {
+            return Err(CryptoError::DecryptionError(format!("无效的条目名称: {}", name)));
+        }
+        let output_path = directory.join(name);
+        let mut writer = io::BufWriter::new(
+            OpenOptions::new().write(true).create_new(true).open(&output_path)?
+        );
+        let result = self.extract(name, &mut writer).and_then(|()| Ok(writer.flush()?));
+        if let Err(e) = result {
+            drop(writer);
+            let _ = fs::remove_file(&output_path);
+            return Err(e);
+        }
+        Ok(output_path)
+    }
+
+    /// 删除条目（只写入新的条目表，数据在压缩时回收）
+    pub fn remove(&mut self, name: &str) -> CryptoResult<()> {
+        self.entry(name)?;
+        let mut entries = self.entries.clone();
+        entries.retain(|entry| entry.name != name);
+        self.commit(entries)
+    }
+
+    /// 压缩容器：把仍被引用的密文块复制到新文件并替换原文件，不需要重新加密
+    pub fn compact(&mut self) -> CryptoResult<()> {
+        let mut temp_name = self.path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+        temp_name.push(".tmp");
+        let temp_path = self.path.with_file_name(temp_name);
+
+        let result = self.write_compacted(&temp_path);
+        let (file, entries) = match result {
+            Ok(compacted) => compacted,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path);
+                return Err(e);
+            }
+        };
+        fs::rename(&temp_path, &self.path)?;
+        self.file = file;
+        self.entries = entries;
+        Ok(())
+    }
+
+    fn write_compacted(&mut self, temp_path: &Path) -> CryptoResult<(File, Vec<VaultEntry>)> {
+        let mut output = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(temp_path)?;
+        output.write_all(&VAULT_MAGIC)?;
+        output.write_all(&[VAULT_VERSION])?;
+        output.write_all(&self.salt)?;
+
+        let mut offset = HEADER_LEN;
+        let mut entries = self.entries.clone();
+        for entry in &mut entries {
+            for chunk in &mut entry.chunks {
+                self.file.seek(SeekFrom::Start(chunk.offset))?;
+                let copied = io::copy(&mut (&mut self.file).take(chunk.len as u64), &mut output)?;
+                if copied != chunk.len as u64 {
+                    return Err(CryptoError::DecryptionError(format!("条目 '{}' 的数据被截断", entry.name)));
+                }
+                chunk.offset = offset;
+                offset += chunk.len as u64;
+            }
+        }
+
+        Self::write_index_to(&self.cipher, &mut output, &entries)?;
+        output.sync_all()?;
+        Ok((output, entries))
+    }
+
+    /// 写入新的条目表，成功后才更新内存中的条目
+    fn commit(&mut self, entries: Vec<VaultEntry>) -> CryptoResult<()> {
+        self.file.seek(SeekFrom::End(0))?;
+        Self::write_index_to(&self.cipher, &mut self.file, &entries)?;
+        self.file.sync_all()?;
+        self.entries = entries;
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> CryptoResult<()> {
+        self.commit(self.entries.clone())
+    }
+
+    /// 在写入位置追加加密的条目表和尾部
+    fn write_index_to(cipher: &Aes256Gcm, file: &mut File, entries: &[VaultEntry]) -> CryptoResult<()> {
+        let json = serde_json::to_vec(entries)
+            .map_err(|e| CryptoError::EncryptionError(format!("条目表序列化失败: {}", e)))?;
+        let (nonce, ciphertext) = seal_with(cipher, &json, INDEX_AAD)?;
+
+        let index_offset = file.stream_position()?;
+        let index_len = (NONCE_LEN + ciphertext.len()) as u32;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
+        file.write_all(&index_offset.to_le_bytes())?;
+        file.write_all(&index_len.to_le_bytes())?;
+        file.write_all(&FOOTER_MAGIC)?;
+        Ok(())
+    }
+
+    /// 读取当前的条目表
+    ///
+    /// 文件末尾的尾部损坏时（例如添加条目时中断），向前查找最近一个有效的条目表
+    fn read_index(&mut self) -> CryptoResult<Vec<VaultEntry>> {
+        let file_len = self.file.metadata()?.len();
+        let mut footer_end = file_len;
+        loop {
+            match self.read_index_at(footer_end) {
+                Ok(entries) => {
+                    if footer_end != file_len {
+                        // 去掉中断时写入的不完整数据
+                        self.file.set_len(footer_end)?;
+                    }
+                    return Ok(entries);
+                }
+                // 最后一个尾部有效但无法解密：密码错误
+                Err(CryptoError::InvalidPassword) => return Err(CryptoError::InvalidPassword),
+                Err(_) => {}
+            }
+            match self.previous_footer_end(footer_end)? {
+                Some(end) => footer_end = end,
+                None => return Err(CryptoError::DecryptionError("找不到有效的条目表".to_string())),
+            }
+        }
+    }
+
+    /// 读取以 `footer_end` 结尾的尾部所指向的条目表
+    fn read_index_at(&mut self, footer_end: u64) -> CryptoResult<Vec<VaultEntry>> {
+        if footer_end < HEADER_LEN + FOOTER_LEN {
+            return Err(CryptoError::InvalidFormat);
+        }
+        let (index_offset, index_len) = self.footer_at(footer_end)?;
+        if index_len > MAX_INDEX_LEN
+            || (index_len as usize) < NONCE_LEN
+            || index_offset < HEADER_LEN
+            || index_offset + index_len as u64 + FOOTER_LEN != footer_end
+        {
+            return Err(CryptoError::InvalidFormat);
+        }
+
+        self.file.seek(SeekFrom::Start(index_offset))?;
+        let mut data = vec![0u8; index_len as usize];
+        self.file.read_exact(&mut data)?;
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let json = self.open_sealed(nonce, ciphertext, INDEX_AAD)
+            .map_err(|_| CryptoError::InvalidPassword)?;
+        serde_json::from_slice(&json)
+            .map_err(|e| CryptoError::DecryptionError(format!("条目表无效: {}", e)))
+    }
+
+    fn footer_at(&mut self, footer_end: u64) -> CryptoResult<(u64, u32)> {
+        self.file.seek(SeekFrom::Start(footer_end - FOOTER_LEN))?;
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        self.file.read_exact(&mut footer)?;
+        if footer[12..] != FOOTER_MAGIC {
+            return Err(CryptoError::InvalidFormat);
+        }
+        let index_offset = u64::from_le_bytes(footer[..8].try_into().unwrap());
+        let index_len = u32::from_le_bytes(footer[8..12].try_into().unwrap());
+        Ok((index_offset, index_len))
+    }
+
+    fn current_footer(&self) -> io::Result<(u64, u32)> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let mut footer = [0u8; FOOTER_LEN as usize];
+        file.read_exact(&mut footer)?;
+        Ok((
+            u64::from_le_bytes(footer[..8].try_into().unwrap()),
+            u32::from_le_bytes(footer[8..12].try_into().unwrap()),
+        ))
+    }
+
+    /// 在 `before` 之前向后查找尾部魔数，返回该尾部的结束位置
+    fn previous_footer_end(&mut self, before: u64) -> io::Result<Option<u64>> {
+        const WINDOW: u64 = 64 * 1024;
+        let magic_len = FOOTER_MAGIC.len() as u64;
+        // 只查找结束位置严格小于 before 的尾部
+        let mut upper = before.saturating_sub(1);
+        while upper >= HEADER_LEN + magic_len {
+            let start = upper.saturating_sub(WINDOW).max(HEADER_LEN);
+            self.file.seek(SeekFrom::Start(start))?;
+            let mut window = vec![0u8; (upper - start) as usize];
+            self.file.read_exact(&mut window)?;
+            if let Some(position) = window.windows(FOOTER_MAGIC.len()).rposition(|w| w == FOOTER_MAGIC) {
+                return Ok(Some(start + position as u64 + magic_len));
+            }
+            if start == HEADER_LEN {
+                break;
+            }
+            // 窗口之间保留重叠，避免漏掉跨越边界的魔数
+            upper = start + magic_len - 1;
+        }
+        Ok(None)
+    }
+
+    fn entry(&self, name: &str) -> CryptoResult<&VaultEntry> {
+        self.entries.iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| CryptoError::DecryptionError(format!("条目不存在: {}", name)))
+    }
+
+    fn cipher(password: &str, salt: &[u8]) -> CryptoResult<Aes256Gcm> {
+        let key = Argon2KeyDerivation::default().derive_key(password, salt)?;
+        Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| CryptoError::EncryptionError(format!("AES密钥创建失败: {}", e)))
+    }
+
+    fn seal(&self, plaintext: &[u8], aad: &[u8]) -> CryptoResult<([u8; NONCE_LEN], Vec<u8>)> {
+        seal_with(&self.cipher, plaintext, aad)
+    }
+
+    fn open_sealed(&self, nonce: &[u8], ciphertext: &[u8], aad: &[u8]) -> CryptoResult<Vec<u8>> {
+        if nonce.len() != NONCE_LEN {
+            return Err(CryptoError::InvalidFormat);
+        }
+        self.cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|e| CryptoError::DecryptionError(format!("认证失败: {}", e)))
+    }
+}
+
+fn seal_with(cipher: &Aes256Gcm, plaintext: &[u8], aad: &[u8]) -> CryptoResult<([u8; NONCE_LEN], Vec<u8>)> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .map_err(|e| CryptoError::EncryptionError(format!("加密失败: {}", e)))?;
+    Ok((nonce, ciphertext))
+}
+
+/// 尽量读满缓冲区，返回实际读取的字节数
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestDir;
+
+    #[test]
+    fn test_vault_add_remove_compact() {
+        let dir = TestDir::new("vault");
+        let path = dir.join("test.kvault");
+        let _ = fs::remove_file(&path);
+
+        let mut vault = Vault::create(&path, "pw").unwrap();
+        vault.add("a.txt", &mut &b"alpha"[..], 0).unwrap();
+        vault.add("b.txt", &mut &b"bravo"[..], 0).unwrap();
+        vault.remove("a.txt").unwrap();
+        assert!(vault.wasted_bytes().unwrap() > 0);
+        drop(vault);
+
+        assert!(matches!(Vault::open(&path, "wrong"), Err(CryptoError::InvalidPassword)));
+
+        let mut vault = Vault::open(&path, "pw").unwrap();
+        assert_eq!(vault.entries().len(), 1);
+        vault.compact().unwrap();
+        assert_eq!(vault.wasted_bytes().unwrap(), 0);
+
+        // 模拟添加条目时中断：末尾的不完整数据被忽略
+        drop(vault);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"partial chunk data").unwrap();
+        drop(file);
+
+        let mut vault = Vault::open(&path, "pw").unwrap();
+        let mut output = Vec::new();
+        vault.extract("b.txt", &mut output).unwrap();
+        assert_eq!(output, b"bravo");
+    }
+}
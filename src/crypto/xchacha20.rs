@@ -0,0 +1,125 @@
+use super::format;
+use super::stream;
+use super::traits::{CryptoProvider, ChunkDecryptor, ChunkEncryptor, CryptoResult, CryptoError, KeyDerivation, BatchKey, FileKeyDerivation, StreamControl};
+use crate::models::KdfParams;
+use std::io::{Read, Write};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce, KeyInit};
+use chacha20poly1305::aead::AeadInPlace;
+
+/// 每个数据块的nonce长度
+pub const XNONCE_LEN: usize = 24;
+
+/// XChaCha20-Poly1305加密提供者
+///
+/// 与ChaCha20-Poly1305相同，但每块使用24字节的随机nonce。同一密钥下加密的块数极多时（超大文件），
+/// 随机nonce重复的概率仍可以忽略不计
+#[derive(Debug)]
+pub struct XChaCha20CryptoProvider {
+    key_derivation: FileKeyDerivation,
+}
+
+impl XChaCha20CryptoProvider {
+    pub fn new() -> Self {
+        Self::with_kdf(KdfParams::default())
+    }
+
+    /// 使用指定的密钥派生参数（Argon2、scrypt或PBKDF2）派生密钥
+    pub fn with_kdf(params: KdfParams) -> Self {
+        Self {
+            key_derivation: FileKeyDerivation::password(params),
+        }
+    }
+
+    /// 从批处理主密钥派生每个文件的密钥
+    pub fn with_batch_key(key: BatchKey) -> Self {
+        Self {
+            key_derivation: FileKeyDerivation::Batch(key),
+        }
+    }
+}
+
+impl CryptoProvider for XChaCha20CryptoProvider {
+    fn algorithm_name(&self) -> &'static str {
+        "XChaCha20-Poly1305"
+    }
+
+    fn nonce_len(&self) -> usize {
+        XNONCE_LEN
+    }
+    
+    fn encrypt_stream(
+        &self,
+        password: &str,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
+    ) -> CryptoResult<()> {
+        // 生成盐值并派生密钥，盐值写在数据块之前
+        let (salt, cipher) = self.chunk_encryptor(password)?;
+        writer.write_all(&salt)?;
+        stream::encrypt_chunks(cipher.as_ref(), self.chunk_size(), self.nonce_len(), reader, writer, control)
+    }
+    
+    fn decrypt_stream(
+        &self,
+        password: &str,
+        reader: &mut dyn Read,
+        writer: &mut dyn Write,
+        control: &StreamControl,
+    ) -> CryptoResult<()> {
+        let mut salt = vec![0u8; format::SALT_LEN];
+        reader.read_exact(&mut salt)?;
+        let cipher = self.chunk_decryptor(password, &salt)?;
+        stream::decrypt_chunks(cipher.as_ref(), self.chunk_size(), self.nonce_len(), reader, writer, control)
+    }
+
+    fn chunk_decryptor(&self, password: &str, salt: &[u8]) -> CryptoResult<Box<dyn ChunkDecryptor>> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+
+        let key = self.key_derivation.derive_key(password, salt)?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|e| CryptoError::DecryptionError(format!("XChaCha20密钥创建失败: {}", e)))?;
+        Ok(Box::new(cipher))
+    }
+
+    fn chunk_encryptor(&self, password: &str) -> CryptoResult<(Vec<u8>, Box<dyn ChunkEncryptor>)> {
+        if password.is_empty() {
+            return Err(CryptoError::InvalidPassword);
+        }
+
+        let salt = self.key_derivation.generate_salt();
+        let key = self.key_derivation.derive_key(password, &salt)?;
+        let cipher = XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|e| CryptoError::EncryptionError(format!("XChaCha20密钥创建失败: {}", e)))?;
+        Ok((salt, Box::new(cipher)))
+    }
+}
+
+impl ChunkEncryptor for XChaCha20Poly1305 {
+    fn encrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()> {
+        if nonce.len() != XNONCE_LEN {
+            return Err(CryptoError::InvalidFormat);
+        }
+        self.encrypt_in_place(XNonce::from_slice(nonce), associated_data, buffer)
+            .map_err(|e| CryptoError::EncryptionError(format!("加密失败: {}", e)))
+    }
+}
+
+impl ChunkDecryptor for XChaCha20Poly1305 {
+    fn decrypt_chunk(&self, nonce: &[u8], associated_data: &[u8], buffer: &mut Vec<u8>) -> CryptoResult<()> {
+        if nonce.len() != XNONCE_LEN {
+            return Err(CryptoError::InvalidFormat);
+        }
+        self.decrypt_in_place(XNonce::from_slice(nonce), associated_data, buffer)
+            .map_err(|e| CryptoError::DecryptionError(format!("解密失败: {}", e)))
+    }
+}
+
+
+impl Default for XChaCha20CryptoProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
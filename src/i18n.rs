@@ -0,0 +1,109 @@
+//! 图形界面和命令行共用的本地化消息
+//!
+//! 消息通过稳定的键查找。语言在首次使用时从环境变量确定：`KRYPTON_LANG` 优先，
+//! 其次是 `LC_ALL`、`LC_MESSAGES` 和 `LANG`，以zh开头时使用中文，否则使用英文
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 指定界面语言的环境变量（例如 `en` 或 `zh_CN`）
+pub const LANGUAGE_ENV: &str = "KRYPTON_LANG";
+
+/// 尚未确定语言
+const UNSET: u8 = u8::MAX;
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(UNSET);
+
+/// 消息使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Chinese,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Chinese];
+
+    /// 按区域设置名称（如 `zh_CN.UTF-8`）选择语言
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.to_ascii_lowercase().starts_with("zh") {
+            Language::Chinese
+        } else {
+            Language::English
+        }
+    }
+
+    fn from_env() -> Self {
+        [LANGUAGE_ENV, "LC_ALL", "LC_MESSAGES", "LANG"].iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map_or_else(Language::default, |locale| Language::from_locale(&locale))
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::English => write!(f, "English"),
+            Language::Chinese => write!(f, "中文"),
+        }
+    }
+}
+
+/// 当前语言，首次调用时从环境变量确定
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        UNSET => {
+            let language = Language::from_env();
+            set_language(language);
+            language
+        }
+        value => Language::ALL.get(value as usize).copied().unwrap_or_default(),
+    }
+}
+
+/// 切换之后生成的消息使用的语言
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// 键、英文、中文
+const MESSAGES: &[(&str, &str, &str)] = &[
+    ("error.io", "I/O error", "IO错误"),
+    ("error.encryption", "Encryption error", "加密错误"),
+    ("error.decryption", "Decryption error", "解密错误"),
+    ("error.key_derivation", "Key derivation error", "密钥派生错误"),
+    ("error.invalid_password", "Invalid password", "密码无效"),
+    ("error.invalid_format", "Invalid file format", "文件格式无效"),
+    ("error.cancelled", "Operation cancelled", "操作已取消"),
+];
+
+/// 用当前语言查找消息，未知的键原样返回
+pub fn tr(key: &'static str) -> &'static str {
+    tr_in(language(), key)
+}
+
+/// 用指定语言查找消息，未知的键原样返回
+pub fn tr_in(language: Language, key: &'static str) -> &'static str {
+    MESSAGES.iter()
+        .find(|(message_key, _, _)| *message_key == key)
+        .map_or(key, |(_, english, chinese)| match language {
+            Language::English => english,
+            Language::Chinese => chinese,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_by_language() {
+        assert_eq!(Language::from_locale("zh_CN.UTF-8"), Language::Chinese);
+        assert_eq!(Language::from_locale("en_US.UTF-8"), Language::English);
+        assert_eq!(tr_in(Language::English, "error.invalid_password"), "Invalid password");
+        assert_eq!(tr_in(Language::Chinese, "error.invalid_password"), "密码无效");
+        assert_eq!(tr_in(Language::Chinese, "error.unknown"), "error.unknown");
+    }
+}
@@ -1,4 +1,8 @@
 pub mod core;
 pub mod models;
 pub mod crypto;
-pub mod progress;
\ No newline at end of file
+pub mod progress;
+pub mod i18n;
+
+#[cfg(test)]
+mod test_support;
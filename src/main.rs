@@ -1,9 +1,8 @@
-mod models;
-mod core;
-mod crypto;
 mod ui;
 mod app;
-mod progress;
+mod cli;
+
+use krypton::{models, core, crypto, progress};
 
 use app::KryptonApp;
 use eframe::egui;
@@ -87,6 +86,12 @@ fn setup_builtin_chinese_support(fonts: &mut egui::FontDefinitions) {
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
+
+    // 命令行模式：krypton run-job <file.kjob>
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
     
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -100,6 +105,7 @@ fn main() -> Result<(), eframe::Error> {
         options,
         Box::new(|cc| {
             load_chinese_fonts(&cc.egui_ctx);
+            ui::a11y::apply_focus_style(&cc.egui_ctx);
 
             Ok(Box::new(KryptonApp::new()))
         }),
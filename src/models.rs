@@ -1,35 +1,631 @@
-use std::path::PathBuf;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, atomic::AtomicBool, mpsc};
 use std::thread::JoinHandle;
+use std::time::Instant;
+use crate::core::api::{ApiConfig, ApiServer, JobReport};
+use crate::core::backup::BackupManifest;
+use crate::core::file_details::FileDetails;
+use crate::core::migrate::{MigratedFile, MigrationReport};
+use crate::core::network::DirectoryError;
+use crate::core::notes::Note;
+use crate::core::journal::Journal;
+use crate::core::preflight::{DryRunReport, PlannedOutput};
+use crate::core::preview::FilePreview;
+use crate::core::quarantine::Quarantine;
+use crate::core::shred::{ShredLimitation, MAX_SHRED_PASSES};
+use crate::core::watch::FolderWatcher;
+use crate::core::scheduler::{Recurrence, Schedule};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::crypto::audit::{AuditReport, IntegrityBadge};
+use crate::crypto::traits::{BatchKey, StreamControl};
+use crate::crypto::vault::Vault;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OperationMode {
     Encrypt,
     Decrypt,
+    /// 只校验加密文件的密码和认证标签，不写出任何文件
+    Verify,
+    /// 把选中的文件打包为一个AES-256加密的7z归档，可以用7-Zip等标准归档工具打开
+    Archive,
+    /// 把选中的文件连同相对路径和修改时间打包为一个加密的 .krypton 容器，可以列出和单独提取条目
+    Container,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl OperationMode {
+    /// 处理的是右侧的加密文件（解密和校验）
+    pub fn reads_encrypted(&self) -> bool {
+        matches!(self, OperationMode::Decrypt | OperationMode::Verify)
+    }
+
+    /// 所有选中的文件写入同一个输出文件（7z归档或Krypton容器）
+    pub fn packs_files(&self) -> bool {
+        matches!(self, OperationMode::Archive | OperationMode::Container)
+    }
+}
+
+/// 文件处理顺序
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ProcessingOrder {
+    /// 按列表中的顺序
+    #[default]
+    AsListed,
+    /// 小文件优先，尽快完成更多文件
+    SmallestFirst,
+    /// 大文件优先，多线程时负载更均衡
+    LargestFirst,
+    /// 按文件名排序
+    Alphabetical,
+}
+
+impl ProcessingOrder {
+    pub const ALL: [ProcessingOrder; 4] = [
+        ProcessingOrder::AsListed,
+        ProcessingOrder::SmallestFirst,
+        ProcessingOrder::LargestFirst,
+        ProcessingOrder::Alphabetical,
+    ];
+
+    /// 按处理顺序排序文件
+    pub fn sort<T: std::borrow::Borrow<FileItem>>(&self, files: &mut [T]) {
+        match self {
+            ProcessingOrder::AsListed => {}
+            ProcessingOrder::SmallestFirst => files.sort_by_cached_key(|file| file.borrow().size_on_disk()),
+            ProcessingOrder::LargestFirst => files.sort_by_cached_key(|file| std::cmp::Reverse(file.borrow().size_on_disk())),
+            ProcessingOrder::Alphabetical => files.sort_by_cached_key(|file| file.borrow().name.to_lowercase()),
+        }
+    }
+}
+
+impl std::fmt::Display for ProcessingOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessingOrder::AsListed => write!(f, "As Listed"),
+            ProcessingOrder::SmallestFirst => write!(f, "Smallest First"),
+            ProcessingOrder::LargestFirst => write!(f, "Largest First"),
+            ProcessingOrder::Alphabetical => write!(f, "Alphabetical"),
+        }
+    }
+}
+
+/// 某个文件处理失败时批处理如何继续
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// 第一个文件失败时停止整个批处理
+    #[default]
+    StopOnError,
+    /// 跳过失败的文件继续处理其余文件，结束后批处理仍报告失败
+    SkipAndContinue,
+}
+
+impl ErrorPolicy {
+    pub const ALL: [ErrorPolicy; 2] = [ErrorPolicy::StopOnError, ErrorPolicy::SkipAndContinue];
+}
+
+impl std::fmt::Display for ErrorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorPolicy::StopOnError => write!(f, "Stop on Error"),
+            ErrorPolicy::SkipAndContinue => write!(f, "Skip and Continue"),
+        }
+    }
+}
+
+/// 批处理中每个文件密钥的来源
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum BatchKeyMode {
+    /// 每个文件单独用Argon2从密码派生
+    #[default]
+    PerFile,
+    /// 整批只运行一次Argon2，每个文件的密钥用HKDF从派生结果派生
+    Derived,
+    /// 每批生成随机会话密钥，用密码派生的密钥包装后写入文件头，每个文件的密钥用HKDF从会话密钥派生
+    Session,
+}
+
+impl BatchKeyMode {
+    pub const ALL: [BatchKeyMode; 3] = [BatchKeyMode::PerFile, BatchKeyMode::Derived, BatchKeyMode::Session];
+}
+
+impl std::fmt::Display for BatchKeyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchKeyMode::PerFile => write!(f, "Per File"),
+            BatchKeyMode::Derived => write!(f, "Derived Once"),
+            BatchKeyMode::Session => write!(f, "Session Key"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EncryptionAlgorithm {
     AES256,
     ChaCha20,
+    /// 24字节nonce的ChaCha20-Poly1305，适合块数极多的超大文件
+    XChaCha20,
+    /// age v1格式（口令加密），可以用age/rage命令行工具解密
+    Age,
+    /// OpenSSL `enc` 格式（PBKDF2 + AES-256-CBC），可以用 `openssl enc -d -aes-256-cbc -pbkdf2` 解密
+    OpenSsl,
+    /// 通过 `crypto::registry` 注册的外部算法（值为算法ID）
+    Plugin(u16),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileItem {
     pub path: PathBuf,
     pub selected: bool,
     pub name: String,
+    /// 包含子目录时文件相对于所选目录的子目录，位于所选目录中时为None
+    #[serde(default)]
+    pub subdirectory: Option<PathBuf>,
+    /// 多分卷文件的各分卷路径（按编号排序），单文件时为空
+    #[serde(default)]
+    pub volumes: Vec<PathBuf>,
+    /// 单独为该文件指定的算法，为None时使用全局设置
+    #[serde(default)]
+    pub algorithm: Option<EncryptionAlgorithm>,
+    /// 加密文件头中的密码提示
+    #[serde(default)]
+    pub hint: Option<String>,
+    /// 单独为该文件指定的输出冲突处理方式，为None时使用全局设置
+    #[serde(default)]
+    pub conflict: Option<ConflictPolicy>,
+}
+
+/// 状态颜色的调色板
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorPalette {
+    #[default]
+    Standard,
+    /// 色盲友好的调色板（不依赖红绿区分）
+    ColorBlind,
+}
+
+impl ColorPalette {
+    pub const ALL: [ColorPalette; 2] = [ColorPalette::Standard, ColorPalette::ColorBlind];
+}
+
+impl std::fmt::Display for ColorPalette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorPalette::Standard => write!(f, "Standard"),
+            ColorPalette::ColorBlind => write!(f, "Color-blind"),
+        }
+    }
+}
+
+/// 界面控件的尺寸
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LayoutMode {
+    #[default]
+    Standard,
+    /// 触摸屏：更大的点击区域、复选框和滚动条
+    Touch,
+}
+
+impl LayoutMode {
+    pub const ALL: [LayoutMode; 2] = [LayoutMode::Standard, LayoutMode::Touch];
+}
+
+impl std::fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutMode::Standard => write!(f, "Standard"),
+            LayoutMode::Touch => write!(f, "Touch"),
+        }
+    }
+}
+
+/// 输出文件已经存在时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    #[default]
+    Overwrite,
+    /// 不处理该文件
+    Skip,
+    /// 输出到带编号的新文件名，例如 "report (1).pdf"
+    KeepBoth,
+    /// 开始前在界面中逐个询问；无法询问时（命令行、计划任务、监视文件夹）按跳过处理
+    Ask,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 4] = [ConflictPolicy::Overwrite, ConflictPolicy::Skip, ConflictPolicy::KeepBoth, ConflictPolicy::Ask];
+    /// 可以为单个文件选择的处理方式
+    pub const CHOICES: [ConflictPolicy; 3] = [ConflictPolicy::Overwrite, ConflictPolicy::Skip, ConflictPolicy::KeepBoth];
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictPolicy::Overwrite => write!(f, "Overwrite"),
+            ConflictPolicy::Skip => write!(f, "Skip"),
+            ConflictPolicy::KeepBoth => write!(f, "Keep Both"),
+            ConflictPolicy::Ask => write!(f, "Ask"),
+        }
+    }
+}
+
+/// 加密前压缩明文的算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompressionAlgorithm {
+    #[default]
+    None,
+    /// zstd，压缩率高，适合日志、CSV等文本数据
+    Zstd,
+    /// LZ4，压缩率较低但速度很快，适合高速磁盘
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    pub const ALL: [CompressionAlgorithm; 3] = [CompressionAlgorithm::None, CompressionAlgorithm::Zstd, CompressionAlgorithm::Lz4];
+}
+
+impl std::fmt::Display for CompressionAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionAlgorithm::None => write!(f, "None"),
+            CompressionAlgorithm::Zstd => write!(f, "zstd"),
+            CompressionAlgorithm::Lz4 => write!(f, "LZ4"),
+        }
+    }
+}
+
+/// 加密时为每个输出文件写出的校验和附属文件
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChecksumSidecar {
+    #[default]
+    None,
+    /// `.sha256`，可用 sha256sum -c 检查
+    Sha256,
+    /// `.b3`，可用 b3sum -c 检查
+    Blake3,
+}
+
+impl ChecksumSidecar {
+    pub const ALL: [ChecksumSidecar; 3] = [ChecksumSidecar::None, ChecksumSidecar::Sha256, ChecksumSidecar::Blake3];
+
+    /// 附属文件的扩展名，不写出时为None
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            ChecksumSidecar::None => None,
+            ChecksumSidecar::Sha256 => Some("sha256"),
+            ChecksumSidecar::Blake3 => Some("b3"),
+        }
+    }
+}
+
+impl std::fmt::Display for ChecksumSidecar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChecksumSidecar::None => write!(f, "None"),
+            ChecksumSidecar::Sha256 => write!(f, "SHA-256"),
+            ChecksumSidecar::Blake3 => write!(f, "BLAKE3"),
+        }
+    }
+}
+
+/// 加密时为每个输出文件写出的纠删码恢复数据（按加密文件大小的百分比）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecoveryLevel {
+    #[default]
+    None,
+    Percent5,
+    Percent10,
+    Percent20,
+}
+
+impl RecoveryLevel {
+    pub const ALL: [RecoveryLevel; 4] = [RecoveryLevel::None, RecoveryLevel::Percent5, RecoveryLevel::Percent10, RecoveryLevel::Percent20];
+
+    /// 恢复数据占加密文件大小的百分比，不写出时为0
+    pub fn percent(self) -> u8 {
+        match self {
+            RecoveryLevel::None => 0,
+            RecoveryLevel::Percent5 => 5,
+            RecoveryLevel::Percent10 => 10,
+            RecoveryLevel::Percent20 => 20,
+        }
+    }
+}
+
+impl std::fmt::Display for RecoveryLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecoveryLevel::None => write!(f, "None"),
+            level => write!(f, "{}%", level.percent()),
+        }
+    }
+}
+
+/// 数据加密密钥的来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeySource {
+    /// 由密码派生密钥
+    #[default]
+    Passphrase,
+    /// 每个文件的数据密钥由AWS KMS生成并包装
+    AwsKms,
+    /// 每个文件的数据密钥由HashiCorp Vault的transit引擎生成并包装
+    HashiCorpVault,
+    /// 每个文件的数据密钥为每个SSH公钥接收者分别包装
+    SshKeys,
+    /// 每个文件的数据密钥由PKCS#11令牌（智能卡、HSM）上的RSA密钥包装
+    Pkcs11,
+    /// 每个文件的数据密钥为每个X25519公钥接收者分别包装
+    X25519,
+}
+
+impl KeySource {
+    pub const ALL: [KeySource; 6] = [KeySource::Passphrase, KeySource::AwsKms, KeySource::HashiCorpVault, KeySource::SshKeys, KeySource::Pkcs11, KeySource::X25519];
+}
+
+impl std::fmt::Display for KeySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeySource::Passphrase => write!(f, "Passphrase"),
+            KeySource::AwsKms => write!(f, "AWS KMS"),
+            KeySource::HashiCorpVault => write!(f, "HashiCorp Vault"),
+            KeySource::SshKeys => write!(f, "SSH Keys"),
+            KeySource::Pkcs11 => write!(f, "PKCS#11 Token"),
+            KeySource::X25519 => write!(f, "X25519 Recipients"),
+        }
+    }
+}
+
+/// 从密码派生密钥的算法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KdfAlgorithm {
+    #[default]
+    Argon2id,
+    /// scrypt（r = 8），用于没有Argon2的环境
+    Scrypt,
+    /// PBKDF2-HMAC-SHA256，只有迭代次数一个参数，用于需要FIPS算法的环境
+    Pbkdf2Sha256,
+}
+
+impl KdfAlgorithm {
+    pub const ALL: [KdfAlgorithm; 3] = [KdfAlgorithm::Argon2id, KdfAlgorithm::Scrypt, KdfAlgorithm::Pbkdf2Sha256];
+}
+
+impl std::fmt::Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KdfAlgorithm::Argon2id => write!(f, "Argon2id"),
+            KdfAlgorithm::Scrypt => write!(f, "scrypt"),
+            KdfAlgorithm::Pbkdf2Sha256 => write!(f, "PBKDF2-SHA256"),
+        }
+    }
+}
+
+/// 密钥派生参数，默认为Argon2id，参数与argon2的默认参数相同
+///
+/// 各算法使用的字段：Argon2id使用全部三项；scrypt的N等于内存（KiB，必须是2的幂，r = 8时每个N占1 KiB），
+/// p等于并行度；PBKDF2只使用迭代次数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    /// 内存开销（KiB）
+    pub memory_kib: u32,
+    /// 迭代次数
+    pub iterations: u32,
+    /// 并行度
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::for_algorithm(KdfAlgorithm::Argon2id)
+    }
+}
+
+impl KdfParams {
+    /// 算法的默认参数（scrypt为N = 2^15，PBKDF2为OWASP建议的60万次迭代）
+    pub fn for_algorithm(algorithm: KdfAlgorithm) -> Self {
+        let (memory_kib, iterations, parallelism) = match algorithm {
+            KdfAlgorithm::Argon2id => (19 * 1024, 2, 1),
+            KdfAlgorithm::Scrypt => (32 * 1024, 1, 1),
+            KdfAlgorithm::Pbkdf2Sha256 => (0, 600_000, 1),
+        };
+        Self { algorithm, memory_kib, iterations, parallelism }
+    }
+
+    /// 是否满足算法的要求。Argon2：至少1次迭代、1条并行通道，每条通道至少8 KiB内存；
+    /// scrypt：N是大于1的2的幂，p至少为1；PBKDF2：至少1次迭代
+    pub fn is_valid(&self) -> bool {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => {
+                self.iterations >= 1
+                    && (1..=0x00FF_FFFF).contains(&self.parallelism)
+                    && self.memory_kib / 8 >= self.parallelism
+            }
+            KdfAlgorithm::Scrypt => {
+                self.memory_kib.is_power_of_two() && self.memory_kib > 1 && (1..=0xFFFF).contains(&self.parallelism)
+            }
+            KdfAlgorithm::Pbkdf2Sha256 => self.iterations >= 1,
+        }
+    }
+
+    /// 是否为默认参数（默认参数不需要写入文件头）
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => write!(f, "{} MiB × {} passes", self.memory_kib / 1024, self.iterations),
+            KdfAlgorithm::Scrypt => write!(f, "scrypt {} MiB × {} lanes", self.memory_kib / 1024, self.parallelism),
+            KdfAlgorithm::Pbkdf2Sha256 => write!(f, "PBKDF2-SHA256 × {} iterations", self.iterations),
+        }
+    }
+}
+
+/// 加密时参与密钥派生的硬件令牌
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HardwareToken {
+    /// 只使用密码
+    #[default]
+    None,
+    /// YubiKey第1个槽位的HMAC-SHA1挑战-响应
+    YubiKeySlot1,
+    /// YubiKey第2个槽位的HMAC-SHA1挑战-响应
+    YubiKeySlot2,
+}
+
+impl HardwareToken {
+    pub const ALL: [HardwareToken; 3] = [HardwareToken::None, HardwareToken::YubiKeySlot1, HardwareToken::YubiKeySlot2];
+
+    /// YubiKey槽位号，不使用令牌时为None
+    pub fn slot(self) -> Option<u8> {
+        match self {
+            HardwareToken::None => None,
+            HardwareToken::YubiKeySlot1 => Some(1),
+            HardwareToken::YubiKeySlot2 => Some(2),
+        }
+    }
+}
+
+impl std::fmt::Display for HardwareToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HardwareToken::None => write!(f, "None"),
+            HardwareToken::YubiKeySlot1 => write!(f, "YubiKey Slot 1"),
+            HardwareToken::YubiKeySlot2 => write!(f, "YubiKey Slot 2"),
+        }
+    }
+}
+
+/// 外部密钥管理服务的连接设置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KmsSettings {
+    /// 服务地址；AWS KMS留空时按区域使用默认地址
+    pub endpoint: String,
+    /// AWS KMS的密钥ID、ARN或别名，Vault transit引擎中的密钥名称，或PKCS#11令牌上的密钥标签
+    pub key_id: String,
+    /// AWS区域
+    pub region: String,
+    /// AWS访问密钥ID
+    pub access_key_id: String,
+    /// AWS私有访问密钥、Vault令牌、SSH私钥的口令或令牌PIN（不保存），为空时读取环境变量
+    #[serde(skip)]
+    pub secret: String,
+    /// Vault transit引擎的挂载路径，为空时使用 "transit"
+    pub vault_mount: String,
+    /// 加密时的SSH公钥接收者（authorized_keys格式，每行一个）
+    pub ssh_recipients: String,
+    /// 解密时使用的SSH私钥文件，为空时使用 ~/.ssh 中的默认私钥
+    pub ssh_identity: String,
+    /// PKCS#11模块（.so/.dll）路径，为空时使用 pkcs11-tool 的默认模块
+    pub pkcs11_module: String,
+    /// PKCS#11槽位ID，为空时使用第一个插有令牌的槽位
+    pub pkcs11_slot: String,
+    /// 加密时的X25519公钥接收者（每行一个）
+    pub x25519_recipients: String,
+    /// 解密时使用的X25519私钥文件
+    pub x25519_identity: String,
+    /// 加密时再添加一个密码槽位，不持有私钥时用密码也能解密
+    pub x25519_password_slot: bool,
+}
+
+/// 按扩展名划分的文件类型，用于在文件列表中显示图标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Document,
+    Image,
+    Archive,
+    Encrypted,
+    Other,
+}
+
+impl FileKind {
+    const DOCUMENT_EXTENSIONS: [&'static str; 14] = [
+        "txt", "md", "pdf", "doc", "docx", "odt", "rtf", "xls", "xlsx", "ods", "ppt", "pptx", "csv", "json",
+    ];
+    const IMAGE_EXTENSIONS: [&'static str; 10] = [
+        "png", "jpg", "jpeg", "gif", "bmp", "webp", "tif", "tiff", "svg", "heic",
+    ];
+    const ARCHIVE_EXTENSIONS: [&'static str; 9] = [
+        "zip", "rar", "7z", "tar", "gz", "bz2", "xz", "zst", "iso",
+    ];
+
+    /// 根据文件名判断类型，`encrypted_extension` 为当前设置中的加密文件扩展名
+    pub fn from_name(name: &str, encrypted_extension: &str) -> Self {
+        let Some((_, extension)) = name.rsplit_once('.') else {
+            return FileKind::Other;
+        };
+        let extension = extension.to_lowercase();
+        // 多分卷文件名以 .001 等编号结尾，去掉编号后再判断
+        let extension = if extension.chars().all(|c| c.is_ascii_digit()) {
+            name[..name.len() - extension.len() - 1].rsplit_once('.')
+                .map(|(_, ext)| ext.to_lowercase())
+                .unwrap_or(extension)
+        } else {
+            extension
+        };
+
+        if extension.eq_ignore_ascii_case(encrypted_extension) {
+            FileKind::Encrypted
+        } else if Self::DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+            FileKind::Document
+        } else if Self::IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            FileKind::Image
+        } else if Self::ARCHIVE_EXTENSIONS.contains(&extension.as_str()) {
+            FileKind::Archive
+        } else {
+            FileKind::Other
+        }
+    }
+
+    /// 内置字体中的图标字符
+    pub fn icon(&self) -> &'static str {
+        match self {
+            FileKind::Document => "📄",
+            FileKind::Image => "🖼",
+            FileKind::Archive => "📦",
+            FileKind::Encrypted => "🔒",
+            FileKind::Other => "🗋",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileKind::Document => "Document",
+            FileKind::Image => "Image",
+            FileKind::Archive => "Archive",
+            FileKind::Encrypted => "Encrypted",
+            FileKind::Other => "File",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+pub enum ActiveTab {
+    Files,
+    Tools,
+    Notes,
+    Schedule,
+    Watch,
+    Backup,
+    Vault,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum AppState {
+    #[default]
     Idle,
     Running,
     Paused,
 }
 
 /// 异步操作状态
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OperationStatus {
     Running,
     Completed,
@@ -38,7 +634,7 @@ pub enum OperationStatus {
 }
 
 /// 进度信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgressInfo {
     pub current_file: String,
     pub current_file_index: usize,
@@ -47,23 +643,129 @@ pub struct ProgressInfo {
     pub overall_progress: f32,       // 0.0 - 1.0
     pub current_file_size: u64,      // 当前文件大小（字节）
     pub processed_bytes: u64,        // 已处理字节数
+    /// 当前文件已处理的字节数（尚未计入 `processed_bytes`），用于计算速度和剩余时间
+    #[serde(default)]
+    pub current_file_processed: u64,
     pub total_bytes: u64,            // 总字节数
     pub speed_mbps: f64,             // 处理速度（MB/s）
     pub elapsed_time: f64,           // 已用时间（秒）
     pub estimated_remaining: f64,    // 预计剩余时间（秒）
+    /// 当前文件的完整源路径
+    #[serde(default)]
+    pub current_source: PathBuf,
+    /// 最近完成的文件的输出路径
+    #[serde(default)]
+    pub last_output: Option<PathBuf>,
+    pub operation_mode: OperationMode,
+    /// 到目前为止处理失败的文件数
+    #[serde(default)]
+    pub failed_files: usize,
+}
+
+impl ProgressInfo {
+    /// 尚未开始处理任何文件的进度
+    pub fn new(total_files: usize, total_bytes: u64, operation_mode: OperationMode) -> Self {
+        Self {
+            current_file: String::new(),
+            current_file_index: 0,
+            total_files,
+            current_file_progress: 0.0,
+            overall_progress: 0.0,
+            current_file_size: 0,
+            processed_bytes: 0,
+            current_file_processed: 0,
+            total_bytes,
+            speed_mbps: 0.0,
+            elapsed_time: 0.0,
+            estimated_remaining: 0.0,
+            current_source: PathBuf::new(),
+            last_output: None,
+            operation_mode,
+            failed_files: 0,
+        }
+    }
+}
+
+/// 异步操作事件 - 工作线程通过单一通道发送给UI线程
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OperationEvent {
+    /// 整体进度更新
+    Progress(ProgressInfo),
+    /// 单个文件处理完成
+    FileCompleted { index: usize, name: String, output: PathBuf },
+    /// 单个文件被跳过
+    FileSkipped { index: usize, name: String },
+    /// 单个文件处理失败
+    FileFailed { index: usize, name: String, error: String },
+    /// 操作报告已写出（在 `Finished` 之前发送）
+    ReportWritten(PathBuf),
+    /// 批处理成功，删除的源文件暂存在隔离区中等待调用方清除或撤销（只在设置了 `undo_delete` 时发送）
+    #[serde(skip)]
+    SourcesQuarantined(Quarantine),
+    /// 操作结束（总是最后一个事件）
+    Finished(OperationStatus),
+}
+
+/// 批处理中一个文件的处理结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileResult {
+    /// 文件在批处理中的序号
+    pub index: usize,
+    pub name: String,
+    pub outcome: FileOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileOutcome {
+    Completed { output: PathBuf },
+    Skipped,
+    Failed { error: String },
+}
+
+impl FileResult {
+    /// 文件结束的事件对应的结果，其他事件返回None
+    pub fn from_event(event: &OperationEvent) -> Option<Self> {
+        let (index, name, outcome) = match event {
+            OperationEvent::FileCompleted { index, name, output } => (index, name, FileOutcome::Completed { output: output.clone() }),
+            OperationEvent::FileSkipped { index, name } => (index, name, FileOutcome::Skipped),
+            OperationEvent::FileFailed { index, name, error } => (index, name, FileOutcome::Failed { error: error.clone() }),
+            _ => return None,
+        };
+        Some(Self { index: *index, name: name.clone(), outcome })
+    }
 }
 
 /// 进度回调函数类型
 pub type ProgressCallback = Arc<dyn Fn(ProgressInfo) + Send + Sync>;
 
+/// 按源文件路径记录的跳过请求，克隆的实例共享同一份记录
+///
+/// 每个文件有各自的标志：正在处理的文件在当前块之后中断，尚未开始的文件开始时直接跳过，
+/// 同时处理的其他文件不受影响
+#[derive(Debug, Clone, Default)]
+pub struct SkipRequests(Arc<std::sync::Mutex<HashMap<PathBuf, Arc<AtomicBool>>>>);
+
+impl SkipRequests {
+    /// 请求跳过 `source`
+    pub fn request(&self, source: &Path) {
+        self.flag(source).store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// `source` 的跳过标志，处理该文件的数据流检查它
+    pub fn flag(&self, source: &Path) -> Arc<AtomicBool> {
+        self.0.lock().unwrap().entry(source.to_path_buf()).or_default().clone()
+    }
+}
+
 /// 异步操作句柄
 pub struct OperationHandle {
     pub(crate) thread_handle: Option<JoinHandle<Result<(), String>>>,
     pub(crate) should_stop: Arc<AtomicBool>,
-    pub(crate) should_skip: Arc<AtomicBool>,
+    pub(crate) should_skip: SkipRequests,
     pub(crate) status: Arc<std::sync::Mutex<OperationStatus>>,
     pub(crate) progress: Arc<std::sync::Mutex<ProgressInfo>>,
-    pub(crate) progress_receiver: Option<mpsc::Receiver<ProgressInfo>>,
+    pub(crate) results: Arc<std::sync::Mutex<Vec<FileResult>>>,
+    pub(crate) event_receiver: Option<mpsc::Receiver<OperationEvent>>,
 }
 
 impl OperationHandle {
@@ -77,14 +779,27 @@ impl OperationHandle {
         self.progress.lock().unwrap().clone()
     }
 
+    /// 到目前为止已结束的文件的结果（按结束的顺序）
+    pub fn results(&self) -> Vec<FileResult> {
+        self.results.lock().unwrap().clone()
+    }
+
     /// 请求停止操作
     pub fn stop(&self) {
         self.should_stop.store(true, std::sync::atomic::Ordering::Relaxed);
     }
 
-    /// 请求跳过当前文件
+    /// 请求跳过进度中显示的当前文件，还没有开始处理任何文件时不做任何事
     pub fn skip_current(&self) {
-        self.should_skip.store(true, std::sync::atomic::Ordering::Relaxed);
+        let source = self.progress().current_source;
+        if !source.as_os_str().is_empty() {
+            self.skip_file(&source);
+        }
+    }
+
+    /// 请求跳过指定的源文件：正在处理时在当前块之后中断并删除不完整的输出，尚未开始时不再处理
+    pub fn skip_file(&self, source: &Path) {
+        self.should_skip.request(source);
     }
 
     /// 等待操作完成
@@ -101,36 +816,170 @@ impl OperationHandle {
         matches!(self.status(), OperationStatus::Completed | OperationStatus::Failed(_) | OperationStatus::Cancelled)
     }
 
-    /// 尝试接收进度更新（非阻塞）
+    /// 尝试接收下一个操作事件（非阻塞）
+    ///
+    /// 如果工作线程意外退出而未发送结束事件，会补发一个 `Finished` 事件
+    pub fn try_recv_event(&mut self) -> Option<OperationEvent> {
+        let receiver = self.event_receiver.as_ref()?;
+        match receiver.try_recv() {
+            Ok(event) => Some(event),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.event_receiver = None;
+                let status = match self.status() {
+                    OperationStatus::Running => OperationStatus::Failed("Operation thread terminated unexpectedly".to_string()),
+                    status => status,
+                };
+                Some(OperationEvent::Finished(status))
+            }
+        }
+    }
+
+    /// 尝试接收进度更新（非阻塞，忽略其他类型的事件）
     pub fn try_recv_progress(&mut self) -> Option<ProgressInfo> {
-        if let Some(ref receiver) = self.progress_receiver {
-            receiver.try_recv().ok()
-        } else {
-            None
+        while let Some(event) = self.try_recv_event() {
+            if let OperationEvent::Progress(progress) = event {
+                return Some(progress);
+            }
         }
+        None
     }
 }
 
-/// 应用设置结构体
-#[derive(Debug, Clone)]
+/// 应用设置结构体（序列化时不包含密码）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub operation_mode: OperationMode,
     pub encryption_algorithm: EncryptionAlgorithm,
+    /// 级联加密：用另一种内置算法以独立派生的密钥再加密一层
+    pub cascade: bool,
+    /// 加密前压缩明文，已经压缩过的文件类型（图片、视频、压缩包等）不再压缩
+    pub compression: CompressionAlgorithm,
+    #[serde(skip)]
     pub password: String,
     pub max_threads: u32,
     pub encrypt_filename: bool,
     pub delete_source: bool,
+    /// 删除源文件前先用随机数据覆写
+    pub shred_source: bool,
+    /// 加密后重新打开输出文件完整解密一遍，与加密时读取的明文摘要比较，不一致时删除输出、保留源文件
+    pub verify_after_encrypt: bool,
+    /// 覆写源文件的遍数
+    pub shred_passes: u32,
+    /// 加密时写入文件头的明文密码提示（任何人可读，不要填写密码本身）
+    pub password_hint: String,
+    /// 输出目录，为空时输出到源文件所在目录
+    pub output_directory: Option<PathBuf>,
     pub file_extension: String,
+    /// 输出文件已经存在时的处理方式
+    pub conflict_policy: ConflictPolicy,
+    /// 加密时为每个输出文件写出的校验和附属文件
+    pub checksum_sidecar: ChecksumSidecar,
+    /// 加密时为每个输出文件写出的恢复数据，解密前用它修复损坏的数据块
+    pub recovery_level: RecoveryLevel,
+    /// 增量加密：只处理自上次加密后新增或修改的文件
+    pub incremental: bool,
+    /// 列出文件时包含子目录，输出时在输出目录下重建同样的目录结构
+    pub recursive: bool,
+    /// 包含子目录时进入的最大层数，0表示不限制
+    pub max_depth: u32,
+    /// 文件提交到线程池的顺序
+    pub processing_order: ProcessingOrder,
+    /// 某个文件失败时停止还是跳过它继续处理（归档和容器总是在第一个错误时停止）
+    pub error_policy: ErrorPolicy,
+    /// 加密和解密批处理写出日志，崩溃或重启后可以从中断处继续
+    pub resumable: bool,
+    /// 所有工作线程共享的缓冲区内存上限（MB）
+    pub memory_budget_mb: u32,
+    /// 绕过操作系统页缓存读写文件（O_DIRECT / FILE_FLAG_NO_BUFFERING），避免大批量处理挤掉页缓存
+    pub direct_io: bool,
+    /// 加密时在后台线程中预读下一块，读取和加密同时进行（适合机械硬盘和网络共享）
+    pub read_ahead: bool,
+    /// 大文件（见 [`crate::core::mapped_io::MIN_MAPPED_SIZE`]）用内存映射读写，减少系统调用和复制；与直接I/O同时设置时直接I/O优先
+    pub mapped_io: bool,
+    /// 用rayon并行加密/解密同一文件的数据块（按顺序写出）
+    pub parallel_chunks: bool,
+    /// 单个文件的超时时间（秒），0表示不限制。超时的文件记为失败，其余文件继续处理
+    pub file_timeout_secs: u32,
+    /// 整个批处理的超时时间（分钟），0表示不限制。超时后取消所有未完成的文件
+    pub batch_timeout_mins: u32,
+    /// 状态颜色使用的调色板
+    pub color_palette: ColorPalette,
+    /// 控件尺寸（标准或触摸屏）
+    pub layout_mode: LayoutMode,
+    /// 数据加密密钥的来源
+    pub key_source: KeySource,
+    /// 外部密钥管理服务的设置（密钥来源不是密码时使用）
+    pub kms: KmsSettings,
+    /// 加密时与密码一起参与密钥派生的硬件令牌
+    pub hardware_token: HardwareToken,
+    /// 与密码一起参与密钥派生的密钥文件，解密时需要同一个文件
+    pub keyfile: Option<PathBuf>,
+    /// 加密时使用的Argon2参数，非默认值会写入文件头
+    pub kdf: KdfParams,
+    /// 校准密钥派生参数时的目标解锁时间（毫秒）
+    pub kdf_target_ms: u32,
+    /// 批处理结束后写出JSON操作报告
+    pub write_report: bool,
+    /// 报告文件路径，为空时在输出目录中按时间命名
+    pub report_path: String,
+    /// 加密时每个文件密钥的来源，批处理模式下整批只运行一次Argon2
+    pub batch_key: BatchKeyMode,
+    /// 本次批处理的主密钥，由引擎在批处理开始时派生
+    #[serde(skip)]
+    pub batch_master_key: Option<BatchKey>,
+    /// 本次批处理的源文件隔离区，由引擎在设置了删除源文件时创建
+    #[serde(skip)]
+    pub quarantine: Option<Quarantine>,
+    /// 批处理成功后保留隔离的源文件，由调用方在 `SourcesQuarantined` 事件后清除或撤销
+    #[serde(skip)]
+    pub undo_delete: bool,
+    /// 本次批处理的日志，由引擎在设置了可恢复时创建；继续中断的批处理时由调用方传入
+    #[serde(skip)]
+    pub journal: Option<Journal>,
+    /// 本次批处理的取消控制，由引擎在批处理开始时设置，停止时正在处理的文件在当前块之后中断
+    #[serde(skip)]
+    pub stream_control: StreamControl,
 }
 
 /// 文件管理结构体
-#[derive(Debug, Clone)]
-#[derive(Default)]
+#[derive(Debug, Default)]
 pub struct FileManagerState {
     pub left_directory: String,
     pub right_directory: String,
     pub left_files: Vec<FileItem>,
     pub right_files: Vec<FileItem>,
+    /// 上次加载文件列表时的目录，用于判断是否打开了新目录
+    pub loaded_left_directory: String,
+    pub loaded_right_directory: String,
+    /// 正在预览的左侧文件
+    pub preview_index: Option<usize>,
+    /// 正在解密预览的右侧文件
+    pub encrypted_preview_index: Option<usize>,
+    /// 右侧文件的快速完整性检查结果
+    pub integrity: HashMap<PathBuf, IntegrityBadge>,
+    /// 后台完整性检查的结果，重新加载文件列表时替换（旧的检查随之停止）
+    pub integrity_receiver: Option<mpsc::Receiver<(PathBuf, IntegrityBadge)>>,
+    /// 无法打开目录的原因，显示在对应的文件列表中
+    pub left_error: Option<DirectoryError>,
+    pub right_error: Option<DirectoryError>,
+    /// 目录所在存储上会使覆写无效的特性，设置了覆写源文件时在对应的文件列表中提示
+    pub left_shred_limitations: Vec<ShredLimitation>,
+    pub right_shred_limitations: Vec<ShredLimitation>,
+    /// 悬停时显示的文件详情，第一次悬停时读取，重新加载文件列表时清除
+    pub details: HashMap<PathBuf, FileDetails>,
+}
+
+/// 文件预览窗格状态
+#[derive(Default)]
+pub struct PreviewState {
+    pub preview: Option<FilePreview>,
+    pub error: Option<String>,
+    /// 内容是在内存中解密的加密文件
+    pub decrypted: bool,
+    /// 图片预览的纹理，首次显示时创建
+    pub texture: Option<egui::TextureHandle>,
 }
 
 /// 进度状态结构体
@@ -147,6 +996,48 @@ pub struct ProgressState {
     pub speed_mbps: f64,
     pub elapsed_time: f64,
     pub estimated_remaining: f64,
+    pub completed_files: usize,
+    pub skipped_files: usize,
+    /// 本次操作中失败的文件及错误信息
+    pub failed_files: Vec<(String, String)>,
+    /// 本次操作中已结束的每个文件的结果
+    pub results: Vec<FileResult>,
+    /// 本次操作写出的报告文件
+    pub report_path: Option<PathBuf>,
+    /// 成功的批处理中等待清除或撤销的源文件
+    pub quarantine: Option<Quarantine>,
+}
+
+/// 一个批处理的进度视图，多个批处理可以同时运行，每个显示在自己的标签页中
+#[derive(Default)]
+pub struct OperationView {
+    /// 标签页标题，例如 "Encrypt · 12 files"
+    pub title: String,
+    pub handle: Option<OperationHandle>,
+    pub progress: ProgressState,
+    pub state: AppState,
+    /// 由计划任务启动时为该任务的索引
+    pub scheduled_job: Option<usize>,
+    /// 通过控制接口提交时为任务编号
+    pub api_job: Option<u64>,
+    /// 只校验不写出的批处理，结束时显示校验摘要
+    pub verify: bool,
+}
+
+impl OperationView {
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// 标签页上显示的文字，运行中附带总进度
+    pub fn tab_label(&self) -> String {
+        let title = if self.title.is_empty() { "New Batch" } else { self.title.as_str() };
+        if self.is_running() {
+            format!("{} ({}%)", title, (self.progress.total_progress * 100.0) as u32)
+        } else {
+            title.to_string()
+        }
+    }
 }
 
 /// 对话框状态结构体
@@ -156,6 +1047,542 @@ pub struct DialogState {
     pub show_error_dialog: bool,
     pub show_complete_dialog: bool,
     pub error_message: String,
+    pub show_info_dialog: bool,
+    pub info_title: String,
+    pub info_message: String,
+    /// 完成对话框中显示的报告文件
+    pub complete_report_path: Option<PathBuf>,
+    /// 完成对话框可以撤销删除的源文件，关闭对话框时清除
+    pub complete_quarantine: Option<Quarantine>,
+    /// 开始前的输出预览
+    pub show_preflight_dialog: bool,
+    pub preflight: Vec<PlannedOutput>,
+    /// 输出预览中只列出已有同名文件的输出
+    pub preflight_conflicts_only: bool,
+    /// 试运行的结果，关闭对话框时清除
+    pub dry_run: Option<DryRunReport>,
+    /// 冲突处理方式为询问时，还没有决定如何处理的已有输出，逐个询问
+    pub conflict_prompts: Vec<PlannedOutput>,
+    /// 把询问的结果用于剩下的所有文件
+    pub conflict_apply_all: bool,
+    /// 正在输入登录信息的网络共享
+    pub share_login: Option<ShareLogin>,
+    /// 中断的批处理，有时显示继续对话框
+    pub show_resume_dialog: bool,
+    pub interrupted: Vec<Journal>,
+    /// 继续中断的批处理时输入的密码（日志中不保存密码）
+    pub resume_password: String,
+    pub resume_error: Option<String>,
+}
+
+/// 连接网络共享时输入的登录信息
+#[derive(Debug, Clone, Default)]
+pub struct ShareLogin {
+    /// 共享的UNC路径
+    pub share: String,
+    pub user: String,
+    pub password: String,
+    /// 上次连接失败的原因
+    pub error: Option<String>,
+}
+
+impl DialogState {
+    /// 显示信息对话框
+    pub fn show_info(&mut self, title: impl Into<String>, message: impl Into<String>) {
+        self.info_title = title.into();
+        self.info_message = message.into();
+        self.show_info_dialog = true;
+    }
+
+    /// 显示错误对话框
+    pub fn show_error(&mut self, message: impl Into<String>) {
+        self.error_message = message.into();
+        self.show_error_dialog = true;
+    }
+}
+
+/// 目录审计状态结构体
+#[derive(Debug, Default)]
+pub struct AuditState {
+    pub running: bool,
+    pub show_dialog: bool,
+    pub reports: Vec<AuditReport>,
+    pub receiver: Option<mpsc::Receiver<Vec<AuditReport>>>,
+}
+
+/// 工具页状态结构体
+#[derive(Debug, Clone)]
+pub struct ToolsState {
+    pub input_text: String,
+    pub output_text: String,
+    /// 分段封装时每段的最大大小（KB）
+    pub part_size_kb: u32,
+    /// 最近一次分段封装的各段，用于分别保存
+    pub output_parts: Vec<String>,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl Default for ToolsState {
+    fn default() -> Self {
+        Self {
+            input_text: String::new(),
+            output_text: String::new(),
+            part_size_kb: 100,
+            output_parts: Vec::new(),
+            status_message: String::new(),
+            status_is_error: false,
+        }
+    }
+}
+
+/// 计划任务页状态结构体
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerState {
+    pub schedule: Schedule,
+    /// 本次会话中记住的任务密码（只保存在内存中，不写入磁盘）
+    pub session_passwords: HashMap<PathBuf, String>,
+    /// 新增任务的任务文件路径
+    pub new_job_path: String,
+    /// 新增任务的重复周期
+    pub new_recurrence: Recurrence,
+    /// 等待输入密码的计划任务
+    pub prompt_job: Option<usize>,
+    /// 待解密文件头中的密码提示
+    pub prompt_hint: Option<String>,
+    pub prompt_password: String,
+    pub prompt_remember: bool,
+    /// 正在运行的计划任务
+    pub running_job: Option<usize>,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl SchedulerState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 监视文件夹页的日志条目
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: String,
+    pub message: String,
+    pub is_error: bool,
+}
+
+/// 监视文件夹页状态结构体
+#[derive(Default)]
+pub struct WatchState {
+    /// 被监视的投放目录
+    pub source_directory: String,
+    /// 加密文件的输出目录
+    pub destination_directory: String,
+    pub delete_originals: bool,
+    pub shred_originals: bool,
+    /// 正在监视时为Some
+    pub watcher: Option<FolderWatcher>,
+    /// 等待加密的新文件
+    pub queue: Vec<PathBuf>,
+    /// 正在进行的加密批次
+    pub handle: Option<OperationHandle>,
+    pub last_poll: Option<Instant>,
+    pub log: Vec<LogEntry>,
+}
+
+impl WatchState {
+    /// 日志最多保留的条目数
+    const MAX_LOG_ENTRIES: usize = 500;
+
+    pub fn is_active(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// 添加一条日志
+    pub fn log(&mut self, message: impl Into<String>, is_error: bool) {
+        if self.log.len() >= Self::MAX_LOG_ENTRIES {
+            self.log.remove(0);
+        }
+        self.log.push(LogEntry {
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+            message: message.into(),
+            is_error,
+        });
+    }
+}
+
+/// 备份线程发回的消息
+#[derive(Debug, Clone)]
+pub enum BackupMessage {
+    Progress { done: usize, total: usize, current: String },
+    Finished { message: String, problems: Vec<String>, is_error: bool },
+}
+
+/// 备份页状态结构体
+#[derive(Default)]
+pub struct BackupState {
+    pub source_directory: String,
+    /// 备份目标目录
+    pub destination_directory: String,
+    /// 恢复到的目录
+    pub restore_directory: String,
+    /// 已加载的备份清单，用于选择要恢复的文件
+    pub manifest: Option<BackupManifest>,
+    /// 选中要恢复的文件（清单中的相对路径）
+    pub selected: BTreeSet<String>,
+    /// 清单列表的路径筛选
+    pub filter: String,
+    /// 正在运行的备份、校验或恢复
+    pub receiver: Option<mpsc::Receiver<BackupMessage>>,
+    pub cancel: Arc<AtomicBool>,
+    pub done: usize,
+    pub total: usize,
+    pub current_file: String,
+    /// 上一次校验或恢复发现的问题
+    pub problems: Vec<String>,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl BackupState {
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 格式迁移线程发回的消息
+#[derive(Debug, Clone)]
+pub enum MigrationMessage {
+    Progress { done: usize, total: usize, current: String },
+    Finished(MigrationReport),
+}
+
+/// 工具页中格式迁移的状态
+#[derive(Default)]
+pub struct MigrationState {
+    pub directory: String,
+    /// 扫描到的旧格式文件
+    pub files: Vec<PathBuf>,
+    /// 上一次迁移的文件，可以回滚或删除备份
+    pub migrated: Vec<MigratedFile>,
+    pub receiver: Option<mpsc::Receiver<MigrationMessage>>,
+    pub cancel: Arc<AtomicBool>,
+    pub done: usize,
+    pub total: usize,
+    pub current_file: String,
+    /// 上一次迁移、回滚或删除备份时出错的文件
+    pub problems: Vec<String>,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl MigrationState {
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 应用配置文件的主密码加密状态
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLockState {
+    /// 配置文件是否已用主密码加密
+    pub encrypted: bool,
+    /// 已加密但本次会话尚未解锁（此时不加载也不保存配置）
+    pub locked: bool,
+    /// 是否显示解锁对话框
+    pub show_unlock: bool,
+    pub unlock_password: String,
+    /// 上次解锁失败的原因
+    pub unlock_error: Option<String>,
+    /// 设置或更换主密码时输入的新密码
+    pub new_password: String,
+    pub confirm_password: String,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl ConfigLockState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 本机控制接口的状态
+#[derive(Default)]
+pub struct ApiState {
+    pub config: ApiConfig,
+    pub server: Option<ApiServer>,
+    /// 下一帧按设置启动服务（启动时需要界面上下文来唤醒界面线程）
+    pub pending_start: bool,
+    /// 本次会话中通过接口提交的任务
+    pub jobs: BTreeMap<u64, JobReport>,
+    pub next_job_id: u64,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl ApiState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 加密容器页状态结构体
+#[derive(Default)]
+pub struct VaultState {
+    pub password: String,
+    /// 已打开的容器
+    pub vault: Option<Vault>,
+    /// 选中的条目名称
+    pub selected: BTreeSet<String>,
+    /// 可通过压缩回收的字节数
+    pub wasted_bytes: u64,
+    /// 本机是否可以用TPM保存容器密码
+    pub tpm_available: bool,
+    /// 已打开容器的密码是否已密封到本机TPM
+    pub remembered: bool,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl VaultState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 加密笔记页状态结构体
+#[derive(Debug, Clone)]
+#[derive(Default)]
+pub struct NotesState {
+    pub master_password: String,
+    pub unlocked: bool,
+    pub notes: Vec<Note>,
+    pub selected: Option<usize>,
+    pub dirty: bool,
+    pub status_message: String,
+    pub status_is_error: bool,
+}
+
+impl NotesState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+impl ToolsState {
+    /// 设置状态提示
+    pub fn set_status(&mut self, message: impl Into<String>, is_error: bool) {
+        self.status_message = message.into();
+        self.status_is_error = is_error;
+    }
+}
+
+/// 线程数上限
+pub const MAX_THREADS: u32 = 256;
+
+/// 设置校验错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsError {
+    /// 密钥来源的设置不完整，无法获取密钥
+    Key(String),
+    /// 扩展名不能用作文件名后缀
+    Extension(String),
+    /// 线程数不在 1..=[`MAX_THREADS`] 范围内
+    ThreadCount(u32),
+    /// 缓冲区内存上限为0
+    MemoryBudget,
+    /// Argon2参数无效
+    Kdf(KdfParams),
+    /// 覆写遍数不在 1..=[`MAX_SHRED_PASSES`] 范围内
+    ShredPasses(u32),
+    /// 输出目录是一个已存在的文件
+    OutputDirectory(PathBuf),
+    /// 互相冲突的选项
+    Conflict(&'static str),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Key(message) | SettingsError::Extension(message) => write!(f, "{}", message),
+            SettingsError::ThreadCount(count) => write!(f, "Thread count must be between 1 and {}, got {}", MAX_THREADS, count),
+            SettingsError::MemoryBudget => write!(f, "Memory budget must be at least 1 MB"),
+            SettingsError::Kdf(params) => write!(
+                f,
+                "Invalid {} parameters ({} KiB, {} passes, {} lanes)",
+                params.algorithm, params.memory_kib, params.iterations, params.parallelism
+            ),
+            SettingsError::ShredPasses(passes) => write!(f, "Shred passes must be between 1 and {}, got {}", MAX_SHRED_PASSES, passes),
+            SettingsError::OutputDirectory(path) => write!(f, "Output directory '{}' is a file", path.display()),
+            SettingsError::Conflict(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl Settings {
+    /// 检查加密文件扩展名，不能用作文件名后缀时返回错误信息
+    pub fn extension_error(&self) -> Option<String> {
+        const INVALID_CHARS: [char; 11] = ['.', '/', '\\', ':', '*', '?', '"', '<', '>', '|', ' '];
+
+        if self.file_extension.is_empty() {
+            return Some("Extension is empty".to_string());
+        }
+        if let Some(c) = self.file_extension.chars().find(|c| INVALID_CHARS.contains(c) || c.is_control()) {
+            return Some(format!("Extension invalid: '{}' is not allowed", c.escape_default()));
+        }
+        None
+    }
+
+    /// 校验开始操作所需的设置（包括互相冲突的选项），返回第一个错误
+    ///
+    /// 界面中被禁用的选项会保留旧值（例如未勾选删除源文件时的覆写），界面开始操作前先用
+    /// [`Self::without_inactive_options`] 清除它们
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if let Some(error) = self.key_error() {
+            return Err(SettingsError::Key(error));
+        }
+        if let Some(error) = self.extension_error() {
+            return Err(SettingsError::Extension(error));
+        }
+        if !(1..=MAX_THREADS).contains(&self.max_threads) {
+            return Err(SettingsError::ThreadCount(self.max_threads));
+        }
+        if self.memory_budget_mb == 0 {
+            return Err(SettingsError::MemoryBudget);
+        }
+        if !self.kdf.is_valid() {
+            return Err(SettingsError::Kdf(self.kdf));
+        }
+        if !(1..=MAX_SHRED_PASSES).contains(&self.shred_passes) {
+            return Err(SettingsError::ShredPasses(self.shred_passes));
+        }
+        if let Some(directory) = self.output_directory.as_ref().filter(|directory| directory.is_file()) {
+            return Err(SettingsError::OutputDirectory(directory.clone()));
+        }
+        if let Some(error) = self.conflict_error() {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// 清除界面中因其他选项而被禁用、不起作用的选项（覆写、增量、级联、压缩和批处理密钥）
+    pub fn without_inactive_options(&self) -> Settings {
+        let encrypt = self.operation_mode == OperationMode::Encrypt;
+        let builtin = !self.encryption_algorithm.is_interop() && !matches!(self.encryption_algorithm, EncryptionAlgorithm::Plugin(_));
+        let mut settings = self.clone();
+        settings.shred_source &= settings.delete_source;
+        settings.incremental &= encrypt;
+        settings.cascade &= encrypt && builtin;
+        if !encrypt || settings.encryption_algorithm.is_interop() {
+            settings.compression = CompressionAlgorithm::None;
+        }
+        if !encrypt || settings.key_source != KeySource::Passphrase || settings.hardware_token != HardwareToken::None {
+            settings.batch_key = BatchKeyMode::PerFile;
+        }
+        settings
+    }
+
+    /// 检查只在特定选项下有意义的选项是否被单独设置
+    pub fn conflict_error(&self) -> Option<SettingsError> {
+        if self.shred_source && !self.delete_source {
+            return Some(SettingsError::Conflict("Shredding requires deleting the source files"));
+        }
+        if self.incremental && self.operation_mode != OperationMode::Encrypt {
+            return Some(SettingsError::Conflict("Incremental mode only applies to encryption"));
+        }
+        if self.hardware_token != HardwareToken::None && self.key_source != KeySource::Passphrase {
+            return Some(SettingsError::Conflict("A hardware token can only be combined with a password"));
+        }
+        if self.batch_key != BatchKeyMode::PerFile && (self.key_source != KeySource::Passphrase || self.hardware_token != HardwareToken::None) {
+            return Some(SettingsError::Conflict("A batch key can only be derived from a password"));
+        }
+        if self.keyfile.is_some() && self.key_source != KeySource::Passphrase {
+            return Some(SettingsError::Conflict("A keyfile can only be combined with a password"));
+        }
+        if self.cascade && (self.encryption_algorithm.is_interop() || matches!(self.encryption_algorithm, EncryptionAlgorithm::Plugin(_))) {
+            return Some(SettingsError::Conflict("Cascade encryption needs a built-in algorithm"));
+        }
+        if self.compression != CompressionAlgorithm::None && self.encryption_algorithm.is_interop() && self.operation_mode == OperationMode::Encrypt {
+            return Some(SettingsError::Conflict("age and OpenSSL files cannot be compressed"));
+        }
+        if self.encryption_algorithm.is_interop() && self.operation_mode == OperationMode::Encrypt
+            && (self.key_source != KeySource::Passphrase || self.hardware_token != HardwareToken::None || self.keyfile.is_some())
+        {
+            return Some(SettingsError::Conflict("age and OpenSSL files can only be protected by a password"));
+        }
+        if self.operation_mode == OperationMode::Archive
+            && (self.key_source != KeySource::Passphrase || self.hardware_token != HardwareToken::None || self.keyfile.is_some())
+        {
+            return Some(SettingsError::Conflict("7z archives can only be protected by a password"));
+        }
+        if self.operation_mode == OperationMode::Container
+            && (self.key_source != KeySource::Passphrase || self.hardware_token != HardwareToken::None)
+        {
+            return Some(SettingsError::Conflict("Containers can only be protected by a password and an optional keyfile"));
+        }
+        if self.operation_mode == OperationMode::Container
+            && (self.encryption_algorithm.is_interop() || matches!(self.encryption_algorithm, EncryptionAlgorithm::Plugin(_)))
+        {
+            return Some(SettingsError::Conflict("Containers need a built-in algorithm"));
+        }
+        None
+    }
+
+    /// 检查密钥来源的设置，无法获取密钥时返回错误信息
+    pub fn key_error(&self) -> Option<String> {
+        match self.key_source {
+            KeySource::Passphrase if self.password.is_empty() => Some("Password cannot be empty".to_string()),
+            KeySource::Passphrase => None,
+            KeySource::AwsKms | KeySource::HashiCorpVault if self.kms.key_id.trim().is_empty() => {
+                Some(format!("{} key ID is empty", self.key_source))
+            }
+            KeySource::AwsKms if self.kms.region.trim().is_empty() && self.kms.endpoint.trim().is_empty() => {
+                Some("AWS KMS region is empty".to_string())
+            }
+            KeySource::HashiCorpVault if self.kms.endpoint.trim().is_empty() => Some("Vault address is empty".to_string()),
+            KeySource::AwsKms | KeySource::HashiCorpVault => None,
+            KeySource::SshKeys if self.operation_mode == OperationMode::Encrypt => {
+                crate::crypto::ssh::parse_recipients(&self.kms.ssh_recipients).err()
+            }
+            KeySource::SshKeys => None,
+            KeySource::Pkcs11 if self.kms.key_id.trim().is_empty() => Some("PKCS#11 key label is empty".to_string()),
+            KeySource::Pkcs11 => None,
+            KeySource::X25519 if self.operation_mode == OperationMode::Encrypt => {
+                crate::crypto::keys::parse_recipients(&self.kms.x25519_recipients).err()
+                    .or_else(|| (self.kms.x25519_password_slot && self.password.is_empty()).then(|| "The password slot needs a password".to_string()))
+            }
+            KeySource::X25519 if self.kms.x25519_identity.trim().is_empty() && self.password.is_empty() => {
+                Some("Select an X25519 secret key file or enter the password of the password slot".to_string())
+            }
+            KeySource::X25519 => None,
+        }
+    }
 }
 
 impl Default for Settings {
@@ -163,15 +1590,263 @@ impl Default for Settings {
         Self {
             operation_mode: OperationMode::Encrypt,
             encryption_algorithm: EncryptionAlgorithm::AES256,
+            cascade: false,
+            compression: CompressionAlgorithm::None,
             password: String::new(),
             max_threads: 1,
             encrypt_filename: true,
             delete_source: true,
+            shred_source: false,
+            verify_after_encrypt: false,
+            shred_passes: 1,
+            password_hint: String::new(),
+            output_directory: None,
             file_extension: "enc".to_string(),
+            conflict_policy: ConflictPolicy::Overwrite,
+            checksum_sidecar: ChecksumSidecar::None,
+            recovery_level: RecoveryLevel::None,
+            incremental: false,
+            recursive: false,
+            max_depth: 0,
+            processing_order: ProcessingOrder::AsListed,
+            error_policy: ErrorPolicy::StopOnError,
+            resumable: false,
+            memory_budget_mb: 256,
+            direct_io: false,
+            read_ahead: false,
+            mapped_io: false,
+            parallel_chunks: false,
+            file_timeout_secs: 0,
+            batch_timeout_mins: 0,
+            color_palette: ColorPalette::Standard,
+            layout_mode: LayoutMode::Standard,
+            key_source: KeySource::Passphrase,
+            kms: KmsSettings::default(),
+            hardware_token: HardwareToken::None,
+            keyfile: None,
+            kdf: KdfParams::default(),
+            kdf_target_ms: 1000,
+            write_report: false,
+            report_path: String::new(),
+            batch_key: BatchKeyMode::PerFile,
+            batch_master_key: None,
+            quarantine: None,
+            undo_delete: false,
+            journal: None,
+            stream_control: StreamControl::default(),
         }
     }
 }
 
+/// 以代码构造 [`Settings`] 的构建器，[`SettingsBuilder::build`] 时校验全部设置
+#[derive(Debug, Clone, Default)]
+pub struct SettingsBuilder {
+    settings: Settings,
+}
+
+impl Settings {
+    pub fn builder() -> SettingsBuilder {
+        SettingsBuilder::default()
+    }
+}
+
+impl From<Settings> for SettingsBuilder {
+    fn from(settings: Settings) -> Self {
+        Self { settings }
+    }
+}
+
+impl SettingsBuilder {
+    pub fn operation_mode(mut self, operation_mode: OperationMode) -> Self {
+        self.settings.operation_mode = operation_mode;
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: EncryptionAlgorithm) -> Self {
+        self.settings.encryption_algorithm = algorithm;
+        self
+    }
+
+    /// 用另一种内置算法再加密一层
+    pub fn cascade(mut self, cascade: bool) -> Self {
+        self.settings.cascade = cascade;
+        self
+    }
+
+    /// 加密前压缩明文
+    pub fn compression(mut self, compression: CompressionAlgorithm) -> Self {
+        self.settings.compression = compression;
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.settings.password = password.into();
+        self
+    }
+
+    pub fn max_threads(mut self, max_threads: u32) -> Self {
+        self.settings.max_threads = max_threads;
+        self
+    }
+
+    pub fn encrypt_filename(mut self, encrypt_filename: bool) -> Self {
+        self.settings.encrypt_filename = encrypt_filename;
+        self
+    }
+
+    /// 处理完成后删除源文件，`shred` 为true时先覆写
+    pub fn delete_source(mut self, delete_source: bool, shred: bool) -> Self {
+        self.settings.delete_source = delete_source;
+        self.settings.shred_source = shred;
+        self
+    }
+
+    /// 加密后完整解密输出文件并与明文比较
+    pub fn verify_after_encrypt(mut self, verify: bool) -> Self {
+        self.settings.verify_after_encrypt = verify;
+        self
+    }
+
+    /// 覆写源文件的遍数
+    pub fn shred_passes(mut self, passes: u32) -> Self {
+        self.settings.shred_passes = passes;
+        self
+    }
+
+    pub fn password_hint(mut self, hint: impl Into<String>) -> Self {
+        self.settings.password_hint = hint.into();
+        self
+    }
+
+    pub fn output_directory(mut self, directory: impl Into<PathBuf>) -> Self {
+        self.settings.output_directory = Some(directory.into());
+        self
+    }
+
+    pub fn file_extension(mut self, extension: impl Into<String>) -> Self {
+        self.settings.file_extension = extension.into();
+        self
+    }
+
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.settings.conflict_policy = policy;
+        self
+    }
+
+    /// 加密时为每个输出文件写出校验和附属文件
+    pub fn checksum_sidecar(mut self, sidecar: ChecksumSidecar) -> Self {
+        self.settings.checksum_sidecar = sidecar;
+        self
+    }
+
+    /// 加密时为每个输出文件写出恢复数据
+    pub fn recovery_level(mut self, level: RecoveryLevel) -> Self {
+        self.settings.recovery_level = level;
+        self
+    }
+
+    pub fn incremental(mut self, incremental: bool) -> Self {
+        self.settings.incremental = incremental;
+        self
+    }
+
+    /// 列出文件时包含子目录，`max_depth` 为0时不限制层数
+    pub fn recursive(mut self, recursive: bool, max_depth: u32) -> Self {
+        self.settings.recursive = recursive;
+        self.settings.max_depth = max_depth;
+        self
+    }
+
+    pub fn processing_order(mut self, order: ProcessingOrder) -> Self {
+        self.settings.processing_order = order;
+        self
+    }
+
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.settings.error_policy = policy;
+        self
+    }
+
+    /// 写出批处理日志，中断后可以继续
+    pub fn resumable(mut self, resumable: bool) -> Self {
+        self.settings.resumable = resumable;
+        self
+    }
+
+    pub fn memory_budget_mb(mut self, memory_budget_mb: u32) -> Self {
+        self.settings.memory_budget_mb = memory_budget_mb;
+        self
+    }
+
+    pub fn direct_io(mut self, direct_io: bool) -> Self {
+        self.settings.direct_io = direct_io;
+        self
+    }
+
+    pub fn read_ahead(mut self, read_ahead: bool) -> Self {
+        self.settings.read_ahead = read_ahead;
+        self
+    }
+
+    pub fn mapped_io(mut self, mapped_io: bool) -> Self {
+        self.settings.mapped_io = mapped_io;
+        self
+    }
+
+    pub fn parallel_chunks(mut self, parallel_chunks: bool) -> Self {
+        self.settings.parallel_chunks = parallel_chunks;
+        self
+    }
+
+    /// 单个文件和整个批处理的超时时间，0表示不限制
+    pub fn timeouts(mut self, file_timeout_secs: u32, batch_timeout_mins: u32) -> Self {
+        self.settings.file_timeout_secs = file_timeout_secs;
+        self.settings.batch_timeout_mins = batch_timeout_mins;
+        self
+    }
+
+    /// 使用外部密钥管理服务或SSH密钥提供数据密钥
+    pub fn key_source(mut self, key_source: KeySource, kms: KmsSettings) -> Self {
+        self.settings.key_source = key_source;
+        self.settings.kms = kms;
+        self
+    }
+
+    pub fn hardware_token(mut self, token: HardwareToken) -> Self {
+        self.settings.hardware_token = token;
+        self
+    }
+
+    pub fn keyfile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.keyfile = Some(path.into());
+        self
+    }
+
+    pub fn kdf(mut self, kdf: KdfParams) -> Self {
+        self.settings.kdf = kdf;
+        self
+    }
+
+    /// 加密时每个文件密钥的来源
+    pub fn batch_key(mut self, batch_key: BatchKeyMode) -> Self {
+        self.settings.batch_key = batch_key;
+        self
+    }
+
+    /// 批处理结束后写出JSON操作报告，`path` 为None时写到输出目录
+    pub fn report(mut self, path: Option<PathBuf>) -> Self {
+        self.settings.write_report = true;
+        self.settings.report_path = path.map(|path| path.display().to_string()).unwrap_or_default();
+        self
+    }
+
+    /// 校验设置（包括互相冲突的选项）并返回结果
+    pub fn build(self) -> Result<Settings, SettingsError> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+}
+
 
 impl Default for ProgressState {
     fn default() -> Self {
@@ -187,19 +1862,133 @@ impl Default for ProgressState {
             speed_mbps: 0.0,
             elapsed_time: 0.0,
             estimated_remaining: 0.0,
+            completed_files: 0,
+            skipped_files: 0,
+            failed_files: Vec::new(),
+            results: Vec::new(),
+            report_path: None,
+            quarantine: None,
         }
     }
 }
 
 
+impl ProgressState {
+    /// 将工作线程的进度信息同步到UI进度状态
+    pub fn apply(&mut self, progress_info: ProgressInfo) {
+        self.current_file_name = progress_info.current_file;
+        self.current_progress = progress_info.current_file_progress;
+        self.total_progress = progress_info.overall_progress;
+        self.current_file_index = progress_info.current_file_index;
+        self.total_files = progress_info.total_files;
+        self.current_file_size = progress_info.current_file_size;
+        self.processed_bytes = progress_info.processed_bytes;
+        self.total_bytes = progress_info.total_bytes;
+        self.speed_mbps = progress_info.speed_mbps;
+        self.elapsed_time = progress_info.elapsed_time;
+        self.estimated_remaining = progress_info.estimated_remaining;
+    }
+}
+
 impl FileItem {
     pub fn new(path: PathBuf, name: String) -> Self {
         Self {
             path,
             selected: false,
             name,
+            subdirectory: None,
+            volumes: Vec::new(),
+            algorithm: None,
+            hint: None,
+            conflict: None,
+        }
+    }
+
+    /// 创建由多个分卷组成的逻辑文件项
+    pub fn from_volumes(name: String, volumes: Vec<PathBuf>) -> Self {
+        Self {
+            path: volumes.first().cloned().unwrap_or_default(),
+            selected: false,
+            name,
+            subdirectory: None,
+            volumes,
+            algorithm: None,
+            hint: None,
+            conflict: None,
+        }
+    }
+
+    /// 是否为多分卷文件
+    pub fn is_multi_volume(&self) -> bool {
+        !self.volumes.is_empty()
+    }
+
+    /// 文件在磁盘上的所有组成部分
+    pub fn input_paths(&self) -> Vec<PathBuf> {
+        if self.volumes.is_empty() {
+            vec![self.path.clone()]
+        } else {
+            self.volumes.clone()
+        }
+    }
+
+    /// 相对于所选目录、以/分隔的路径，位于所选目录中时就是文件名
+    pub fn relative_name(&self) -> String {
+        match &self.subdirectory {
+            Some(subdirectory) => subdirectory.components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .chain([self.name.clone()])
+                .collect::<Vec<_>>()
+                .join("/"),
+            None => self.name.clone(),
+        }
+    }
+
+    /// 列出文件时所选的目录（位于子目录中时去掉子目录部分）
+    pub fn listed_directory(&self) -> Option<&Path> {
+        let depth = self.subdirectory.as_ref().map_or(0, |subdirectory| subdirectory.components().count());
+        self.path.parent().and_then(|parent| parent.ancestors().nth(depth))
+    }
+
+    /// 文件在磁盘上的总大小（多分卷时为各分卷之和）
+    pub fn size_on_disk(&self) -> u64 {
+        self.input_paths()
+            .iter()
+            .map(|path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0))
+            .sum()
+    }
+}
+
+impl EncryptionAlgorithm {
+    /// 算法ID（写入加密数据中用于识别算法）
+    pub fn id(&self) -> u16 {
+        match self {
+            EncryptionAlgorithm::AES256 => 1,
+            EncryptionAlgorithm::ChaCha20 => 2,
+            EncryptionAlgorithm::XChaCha20 => 3,
+            EncryptionAlgorithm::Age => 4,
+            EncryptionAlgorithm::OpenSsl => 5,
+            EncryptionAlgorithm::Plugin(id) => *id,
+        }
+    }
+
+    /// 根据算法ID获取算法，未知的ID返回None
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            1 => Some(EncryptionAlgorithm::AES256),
+            2 => Some(EncryptionAlgorithm::ChaCha20),
+            3 => Some(EncryptionAlgorithm::XChaCha20),
+            4 => Some(EncryptionAlgorithm::Age),
+            5 => Some(EncryptionAlgorithm::OpenSsl),
+            id if crate::crypto::registry::display_name(id).is_some() => Some(EncryptionAlgorithm::Plugin(id)),
+            _ => None,
         }
     }
+
+    /// 没有Krypton文件头、与其他工具互通的格式（age、OpenSSL），不支持密钥管理服务、级联和批处理密钥
+    pub fn is_interop(&self) -> bool {
+        matches!(self, EncryptionAlgorithm::Age | EncryptionAlgorithm::OpenSsl)
+    }
 }
 
 impl std::fmt::Display for EncryptionAlgorithm {
@@ -207,6 +1996,89 @@ impl std::fmt::Display for EncryptionAlgorithm {
         match self {
             EncryptionAlgorithm::AES256 => write!(f, "AES-256"),
             EncryptionAlgorithm::ChaCha20 => write!(f, "ChaCha20"),
+            EncryptionAlgorithm::XChaCha20 => write!(f, "XChaCha20"),
+            EncryptionAlgorithm::Age => write!(f, "age"),
+            EncryptionAlgorithm::OpenSsl => write!(f, "OpenSSL"),
+            EncryptionAlgorithm::Plugin(id) => match crate::crypto::registry::display_name(*id) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "Plugin #{}", id),
+            },
         }
     }
-} 
\ No newline at end of file
+}
+
+impl std::str::FromStr for EncryptionAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "AES-256" => Ok(EncryptionAlgorithm::AES256),
+            "ChaCha20" => Ok(EncryptionAlgorithm::ChaCha20),
+            "XChaCha20" => Ok(EncryptionAlgorithm::XChaCha20),
+            "age" => Ok(EncryptionAlgorithm::Age),
+            "OpenSSL" => Ok(EncryptionAlgorithm::OpenSsl),
+            _ => crate::crypto::registry::find_by_name(s)
+                .map(EncryptionAlgorithm::Plugin)
+                .ok_or_else(|| format!("Unknown algorithm: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_validates_settings() {
+        let settings = Settings::builder().password("pw").max_threads(4).build().unwrap();
+        assert_eq!(settings.max_threads, 4);
+
+        assert!(matches!(Settings::builder().build(), Err(SettingsError::Key(_))));
+        assert_eq!(Settings::builder().password("pw").max_threads(0).build().unwrap_err(), SettingsError::ThreadCount(0));
+        assert!(matches!(Settings::builder().password("pw").file_extension("a.b").build(), Err(SettingsError::Extension(_))));
+        let kdf = KdfParams { memory_kib: 8, iterations: 1, parallelism: 2, ..KdfParams::default() };
+        assert_eq!(Settings::builder().password("pw").kdf(kdf).build().unwrap_err(), SettingsError::Kdf(kdf));
+        let scrypt = KdfParams { memory_kib: 3000, ..KdfParams::for_algorithm(KdfAlgorithm::Scrypt) };
+        assert_eq!(Settings::builder().password("pw").kdf(scrypt).build().unwrap_err(), SettingsError::Kdf(scrypt));
+        assert!(Settings::builder().password("pw").kdf(KdfParams::for_algorithm(KdfAlgorithm::Pbkdf2Sha256)).build().is_ok());
+        assert!(matches!(
+            Settings::builder().password("pw").delete_source(false, true).build(),
+            Err(SettingsError::Conflict(_))
+        ));
+        assert_eq!(Settings::builder().password("pw").shred_passes(0).build().unwrap_err(), SettingsError::ShredPasses(0));
+        let file = std::env::current_exe().unwrap();
+        assert_eq!(Settings::builder().password("pw").output_directory(&file).build().unwrap_err(), SettingsError::OutputDirectory(file));
+    }
+
+    #[test]
+    fn test_validate_rejects_conflicts() {
+        let settings = Settings { password: "pw".to_string(), ..Settings::default() };
+        assert!(settings.validate().is_ok());
+
+        // 引擎、命令行和API直接校验的设置也检查冲突，不再静默忽略不起作用的选项
+        let kms = KmsSettings { key_id: "alias/files".to_string(), region: "eu-west-1".to_string(), ..KmsSettings::default() };
+        let conflicts = [
+            Settings { operation_mode: OperationMode::Archive, key_source: KeySource::AwsKms, kms: kms.clone(), ..settings.clone() },
+            Settings { encryption_algorithm: EncryptionAlgorithm::Age, cascade: true, ..settings.clone() },
+            Settings { encryption_algorithm: EncryptionAlgorithm::OpenSsl, compression: CompressionAlgorithm::Zstd, ..settings.clone() },
+            Settings { key_source: KeySource::AwsKms, kms, keyfile: Some(PathBuf::from("key.bin")), ..settings.clone() },
+            Settings { delete_source: false, shred_source: true, ..settings.clone() },
+        ];
+        for conflict in conflicts {
+            assert!(matches!(conflict.validate(), Err(SettingsError::Conflict(_))), "{:?}", conflict.validate());
+        }
+
+        // 界面中被禁用的选项保留的旧值在开始前清除
+        let stale = Settings {
+            operation_mode: OperationMode::Decrypt,
+            delete_source: false,
+            shred_source: true,
+            incremental: true,
+            cascade: true,
+            compression: CompressionAlgorithm::Zstd,
+            ..settings.clone()
+        };
+        assert!(stale.validate().is_err());
+        assert!(stale.without_inactive_options().validate().is_ok());
+    }
+}
@@ -1,19 +1,28 @@
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, mpsc};
-use std::time::Instant;
-use crate::models::{ProgressInfo, ProgressCallback};
+use std::time::{Duration, Instant};
+use crate::models::{FileItem, FileResult, OperationEvent, OperationMode, ProgressInfo, ProgressCallback};
 
-/// 进度跟踪器 - 负责管理和计算进度信息
+/// 文件内进度两次发送之间的最短间隔
+const FILE_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 进度跟踪器 - 负责管理和计算进度信息，克隆的实例共享同一份进度（工作线程用来报告文件内的进度）
+#[derive(Clone)]
 pub struct ProgressTracker {
     /// 进度信息的共享状态
     progress_state: Arc<Mutex<ProgressInfo>>,
-    /// 进度消息发送器
-    progress_sender: mpsc::Sender<ProgressInfo>,
+    /// 操作事件发送器
+    event_sender: mpsc::Sender<OperationEvent>,
     /// 操作开始时间
     start_time: Instant,
     /// 进度回调函数
     callback: Option<ProgressCallback>,
     /// 外部共享的进度状态（用于与UI同步）
     external_progress: Option<Arc<Mutex<ProgressInfo>>>,
+    /// 外部共享的每个文件的结果
+    results: Option<Arc<Mutex<Vec<FileResult>>>>,
+    /// 上次发送文件内进度的时间
+    last_file_update: Arc<Mutex<Option<Instant>>>,
 }
 
 impl ProgressTracker {
@@ -21,40 +30,49 @@ impl ProgressTracker {
     pub fn new(
         total_files: usize,
         total_bytes: u64,
-        progress_sender: mpsc::Sender<ProgressInfo>,
+        operation_mode: OperationMode,
+        event_sender: mpsc::Sender<OperationEvent>,
         callback: Option<ProgressCallback>,
         external_progress: Option<Arc<Mutex<ProgressInfo>>>,
     ) -> Self {
-        let progress_state = Arc::new(Mutex::new(ProgressInfo {
-            current_file: String::new(),
-            current_file_index: 0,
-            total_files,
-            current_file_progress: 0.0,
-            overall_progress: 0.0,
-            current_file_size: 0,
-            processed_bytes: 0,
-            total_bytes,
-            speed_mbps: 0.0,
-            elapsed_time: 0.0,
-            estimated_remaining: 0.0,
-        }));
+        let progress_state = Arc::new(Mutex::new(ProgressInfo::new(total_files, total_bytes, operation_mode)));
 
         Self {
             progress_state,
-            progress_sender,
+            event_sender,
             start_time: Instant::now(),
             callback,
             external_progress,
+            results: None,
+            last_file_update: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// 把文件结束的事件同时记录到 `results` 中
+    pub fn with_results(mut self, results: Arc<Mutex<Vec<FileResult>>>) -> Self {
+        self.results = Some(results);
+        self
+    }
+
+    /// 设置文件总数和总字节数（在工作线程中统计完成后调用）
+    pub fn set_totals(&self, total_files: usize, total_bytes: u64) {
+        let mut progress = self.progress_state.lock().unwrap();
+        progress.total_files = total_files;
+        progress.total_bytes = total_bytes;
+        drop(progress);
+
+        self.send_update();
+    }
+
     /// 开始处理新文件
-    pub fn start_file(&self, file_index: usize, file_name: String, file_size: u64) {
+    pub fn start_file(&self, file_index: usize, file: &FileItem) {
         let mut progress = self.progress_state.lock().unwrap();
-        progress.current_file = file_name;
+        progress.current_file = file.name.clone();
+        progress.current_source = file.path.clone();
         progress.current_file_index = file_index;
-        progress.current_file_size = file_size;
+        progress.current_file_size = file.size_on_disk();
         progress.current_file_progress = 0.0;
+        progress.current_file_processed = 0;
         progress.overall_progress = file_index as f32 / progress.total_files as f32;
         
         self.update_timing(&mut progress);
@@ -63,10 +81,14 @@ impl ProgressTracker {
         self.send_update();
     }
 
-    /// 完成当前文件处理
-    pub fn complete_file(&self, file_size: u64) {
+    /// 完成当前文件处理，跳过的文件没有输出路径
+    pub fn complete_file(&self, file_size: u64, output: Option<PathBuf>) {
         let mut progress = self.progress_state.lock().unwrap();
         progress.current_file_progress = 1.0;
+        progress.current_file_processed = 0;
+        if output.is_some() {
+            progress.last_output = output;
+        }
         progress.processed_bytes += file_size;
         progress.overall_progress = (progress.current_file_index + 1) as f32 / progress.total_files as f32;
         
@@ -76,6 +98,16 @@ impl ProgressTracker {
         self.send_update();
     }
 
+    /// 记录一个处理失败的文件
+    pub fn fail_file(&self) {
+        let mut progress = self.progress_state.lock().unwrap();
+        progress.failed_files += 1;
+        progress.current_file_processed = 0;
+        drop(progress);
+
+        self.send_update();
+    }
+
     /// 更新文件内部进度（0.0 - 1.0）
     pub fn update_file_progress(&self, progress_ratio: f32) {
         let mut progress = self.progress_state.lock().unwrap();
@@ -87,6 +119,38 @@ impl ProgressTracker {
         self.send_update();
     }
 
+    /// 报告第 `file_index` 个文件已处理的字节数。多个文件同时处理时只有当前显示的文件计入；
+    /// 最多每 [`FILE_PROGRESS_INTERVAL`] 发送一次更新
+    pub fn update_file_bytes(&self, file_index: usize, processed: u64) {
+        let mut progress = self.progress_state.lock().unwrap();
+        if progress.current_file_index != file_index {
+            return;
+        }
+        progress.current_file_processed = processed.min(progress.current_file_size);
+        if progress.current_file_size > 0 {
+            progress.current_file_progress = progress.current_file_processed as f32 / progress.current_file_size as f32;
+        }
+
+        let mut last_update = self.last_file_update.lock().unwrap();
+        if last_update.is_some_and(|sent| sent.elapsed() < FILE_PROGRESS_INTERVAL) {
+            return;
+        }
+        *last_update = Some(Instant::now());
+        drop(last_update);
+        self.update_timing(&mut progress);
+        drop(progress);
+
+        self.send_update();
+    }
+
+    /// 发送非进度类事件（文件状态、操作结束等）
+    pub fn send_event(&self, event: OperationEvent) {
+        if let Some((results, result)) = self.results.as_ref().zip(FileResult::from_event(&event)) {
+            results.lock().unwrap().push(result);
+        }
+        let _ = self.event_sender.send(event);
+    }
+
     /// 获取当前进度信息的副本
     pub fn get_progress(&self) -> ProgressInfo {
         self.progress_state.lock().unwrap().clone()
@@ -97,12 +161,14 @@ impl ProgressTracker {
         let elapsed = self.start_time.elapsed().as_secs_f64();
         progress.elapsed_time = elapsed;
 
-        if elapsed > 0.0 && progress.processed_bytes > 0 {
+        // 正在处理的文件已处理的部分也计入，大文件处理过程中速度和剩余时间同样准确
+        let processed_bytes = progress.processed_bytes + progress.current_file_processed;
+        if elapsed > 0.0 && processed_bytes > 0 {
             // 计算处理速度（MB/s）
-            progress.speed_mbps = (progress.processed_bytes as f64) / (1024.0 * 1024.0) / elapsed;
+            progress.speed_mbps = (processed_bytes as f64) / (1024.0 * 1024.0) / elapsed;
 
             // 估算剩余时间
-            let remaining_bytes = progress.total_bytes - progress.processed_bytes;
+            let remaining_bytes = progress.total_bytes.saturating_sub(processed_bytes);
             if progress.speed_mbps > 0.0 {
                 progress.estimated_remaining = (remaining_bytes as f64) / (1024.0 * 1024.0) / progress.speed_mbps;
             } else {
@@ -128,7 +194,7 @@ impl ProgressTracker {
         }
 
         // 发送到UI线程
-        let _ = self.progress_sender.send(progress_info);
+        let _ = self.event_sender.send(OperationEvent::Progress(progress_info));
     }
 }
 
@@ -137,27 +203,24 @@ pub struct ProgressManager;
 
 impl ProgressManager {
     /// 计算文件列表的总大小
-    pub fn calculate_total_size(files: &[crate::models::FileItem]) -> u64 {
+    pub fn calculate_total_size(files: &[FileItem]) -> u64 {
         files.iter()
-            .map(|file| {
-                std::fs::metadata(&file.path)
-                    .map(|m| m.len())
-                    .unwrap_or(0)
-            })
+            .map(|file| file.size_on_disk())
             .sum()
     }
 
     /// 创建进度跟踪器
     pub fn create_tracker(
-        files: &[crate::models::FileItem],
-        progress_sender: mpsc::Sender<ProgressInfo>,
+        files: &[FileItem],
+        operation_mode: OperationMode,
+        event_sender: mpsc::Sender<OperationEvent>,
         callback: Option<ProgressCallback>,
         external_progress: Option<Arc<Mutex<ProgressInfo>>>,
     ) -> ProgressTracker {
         let total_files = files.len();
         let total_bytes = Self::calculate_total_size(files);
 
-        ProgressTracker::new(total_files, total_bytes, progress_sender, callback, external_progress)
+        ProgressTracker::new(total_files, total_bytes, operation_mode, event_sender, callback, external_progress)
     }
 }
 
@@ -181,6 +244,20 @@ impl ProgressFormatter {
         }
     }
 
+    /// 格式化预计完成的时刻（当天只显示时分，之后的日期附加星期或日期）
+    pub fn format_finish_time(now: chrono::DateTime<chrono::Local>, remaining_seconds: f64) -> String {
+        let remaining = chrono::Duration::seconds(remaining_seconds.max(0.0).round() as i64);
+        let finish = now + remaining;
+        let days = (finish.date_naive() - now.date_naive()).num_days();
+        if days == 0 {
+            format!("~{}", finish.format("%H:%M"))
+        } else if days < 7 {
+            format!("~{}", finish.format("%a %H:%M"))
+        } else {
+            format!("~{}", finish.format("%Y-%m-%d %H:%M"))
+        }
+    }
+
     /// 格式化字节大小显示
     pub fn format_bytes(bytes: u64) -> String {
         const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -229,6 +306,16 @@ mod tests {
         assert_eq!(ProgressFormatter::format_time(3661.0), "1h 1m 1s");
     }
 
+    #[test]
+    fn test_format_finish_time() {
+        use chrono::TimeZone;
+        // 2025-01-06 是周一
+        let now = chrono::Local.with_ymd_and_hms(2025, 1, 6, 14, 0, 0).earliest().unwrap();
+        assert_eq!(ProgressFormatter::format_finish_time(now, 32.0 * 60.0), "~14:32");
+        assert_eq!(ProgressFormatter::format_finish_time(now, 12.0 * 3600.0), "~Tue 02:00");
+        assert_eq!(ProgressFormatter::format_finish_time(now, 10.0 * 86400.0), "~2025-01-16 14:00");
+    }
+
     #[test]
     fn test_format_bytes() {
         assert_eq!(ProgressFormatter::format_bytes(512), "512 B");
@@ -0,0 +1,32 @@
+//! 测试共用的辅助工具
+
+use std::ops::Deref;
+use std::path::Path;
+
+/// 测试用的临时目录：名称唯一，并行运行的测试互不冲突；丢弃时连同内容一起删除，断言失败时也不会留下文件
+pub struct TestDir(tempfile::TempDir);
+
+impl TestDir {
+    /// 在系统临时目录中创建以 `krypton_<name>_` 开头的空目录
+    pub fn new(name: &str) -> Self {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("krypton_{}_", name))
+            .tempdir()
+            .expect("failed to create a temporary test directory");
+        Self(dir)
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        self.0.path()
+    }
+}
@@ -0,0 +1,32 @@
+//! 键盘和辅助技术支持
+//!
+//! egui通过AccessKit向屏幕阅读器报告控件，控件的名称来自它的文字或用 `labelled_by` 关联的标签。
+//! 这里放没有可见文字的控件和焦点样式的公共部分
+
+use eframe::egui;
+
+/// 键盘焦点边框的宽度
+const FOCUS_STROKE_WIDTH: f32 = 2.0;
+
+/// 加粗键盘焦点的边框，使Tab键切换到的控件在亮色和暗色主题下都清楚可见
+pub fn apply_focus_style(ctx: &egui::Context) {
+    ctx.all_styles_mut(|style| {
+        let focus_color = style.visuals.selection.bg_fill;
+        // 获得焦点的文本框使用选区描边，其他控件使用active状态的样式
+        style.visuals.selection.stroke.width = FOCUS_STROKE_WIDTH;
+        style.visuals.widgets.active.bg_stroke = egui::Stroke::new(FOCUS_STROKE_WIDTH, focus_color);
+    });
+}
+
+/// 列表行中没有可见文字的复选框，屏幕阅读器读出 `name`
+pub fn row_checkbox(ui: &mut egui::Ui, checked: &mut bool, name: impl Into<String>) -> egui::Response {
+    let response = ui.checkbox(checked, "");
+    let name = name.into();
+    response.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, ui.is_enabled(), *checked, &name));
+    response
+}
+
+/// 为没有可见标签的控件（例如只有占位提示的文本框）设置屏幕阅读器读出的名称
+pub fn set_name(response: &egui::Response, typ: egui::WidgetType, name: &str) {
+    response.widget_info(|| egui::WidgetInfo::labeled(typ, response.enabled(), name));
+}
@@ -0,0 +1,225 @@
+use eframe::egui;
+use super::theme::{status_label, StatusKind};
+use crate::models::BackupState;
+use crate::progress::ProgressFormatter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackupEvent {
+    BrowseSource,
+    BrowseDestination,
+    BrowseRestoreTarget,
+    Backup,
+    Verify,
+    LoadManifest,
+    SelectMatching,
+    ClearSelection,
+    RestoreAll,
+    RestoreSelected,
+    Cancel,
+}
+
+pub struct BackupPanel;
+
+impl BackupPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        backup: &mut BackupState,
+    ) -> Option<BackupEvent> {
+        let mut event = None;
+        let running = backup.is_running();
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label("Backup");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label("Uses the password and algorithm from the settings above");
+                });
+            });
+            ui.separator();
+
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    let source_label = ui.label("Source:      ");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut backup.source_directory)
+                            .hint_text("Folder to back up")
+                            .frame(true)
+                    ).labelled_by(source_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(BackupEvent::BrowseSource);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let destination_label = ui.label("Destination: ");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut backup.destination_directory)
+                            .hint_text("Backup location (local folder or mounted share)")
+                            .frame(true)
+                    ).labelled_by(destination_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(BackupEvent::BrowseDestination);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Back Up").clicked() {
+                        event = Some(BackupEvent::Backup);
+                    }
+                    if ui.button("Verify").on_hover_text("Check the manifest signature and every encrypted copy").clicked() {
+                        event = Some(BackupEvent::Verify);
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let restore_to_label = ui.label("Restore To:  ");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut backup.restore_directory)
+                            .hint_text("Folder to restore the backup into")
+                            .frame(true)
+                    ).labelled_by(restore_to_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(BackupEvent::BrowseRestoreTarget);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Restore All").clicked() {
+                        event = Some(BackupEvent::RestoreAll);
+                    }
+                    if ui.add_enabled(!backup.selected.is_empty(), egui::Button::new(format!("Restore Selected ({})", backup.selected.len())))
+                        .clicked()
+                    {
+                        event = Some(BackupEvent::RestoreSelected);
+                    }
+                    if ui.button("Load Contents").on_hover_text("Read the manifest to choose which files to restore").clicked() {
+                        event = Some(BackupEvent::LoadManifest);
+                    }
+                });
+            });
+
+            if running {
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        event = Some(BackupEvent::Cancel);
+                    }
+                    ui.spinner();
+                    ui.label(format!("{} / {} {}", backup.done, backup.total, backup.current_file));
+                });
+                let fraction = if backup.total == 0 { 0.0 } else { backup.done as f32 / backup.total as f32 };
+                ui.add(egui::ProgressBar::new(fraction).show_percentage());
+            }
+
+            if !backup.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(backup.status_is_error), &backup.status_message);
+            }
+        });
+
+        if let Some(event_from_list) = Self::render_manifest(ui, backup, running) {
+            event = Some(event_from_list);
+        }
+
+        if !backup.problems.is_empty() {
+            ui.group(|ui| {
+                ui.set_width(ui.available_width());
+                ui.label(format!("Problems ({})", backup.problems.len()));
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("backup_problems_scroll")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for problem in &backup.problems {
+                            status_label(ui, StatusKind::Warning, problem);
+                        }
+                    });
+            });
+        }
+
+        event
+    }
+
+    /// 渲染已加载的清单，勾选要恢复的文件
+    fn render_manifest(ui: &mut egui::Ui, backup: &mut BackupState, running: bool) -> Option<BackupEvent> {
+        let mut event = None;
+        let Some(manifest) = &backup.manifest else {
+            return None;
+        };
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                let deleted = manifest.entries.iter().filter(|entry| entry.is_deleted()).count();
+                ui.label(format!(
+                    "Backup of '{}' · {} files · {} · {} deleted",
+                    manifest.source.display(),
+                    manifest.entries.len() - deleted,
+                    ProgressFormatter::format_bytes(manifest.total_size()),
+                    deleted
+                ));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(format_time(&manifest.created));
+                });
+            });
+            ui.separator();
+
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    let filter_label = ui.label("Filter:");
+                    ui.add_sized(
+                        [240.0, 20.0],
+                        egui::TextEdit::singleline(&mut backup.filter)
+                            .hint_text("Part of a path, e.g. photos/")
+                            .frame(true)
+                    ).labelled_by(filter_label.id);
+                    if ui.button("Select Matching").clicked() {
+                        event = Some(BackupEvent::SelectMatching);
+                    }
+                    if ui.button("Clear Selection").clicked() {
+                        event = Some(BackupEvent::ClearSelection);
+                    }
+                });
+
+                let filter = backup.filter.to_lowercase();
+                let entries: Vec<_> = manifest.entries.iter()
+                    .filter(|entry| entry.path.to_lowercase().contains(&filter))
+                    .collect();
+                let row_height = ui.spacing().interact_size.y;
+                egui::ScrollArea::vertical()
+                    .id_salt("backup_manifest_scroll")
+                    .max_height(260.0)
+                    .auto_shrink([false, true])
+                    .show_rows(ui, row_height, entries.len(), |ui, rows| {
+                        for entry in &entries[rows] {
+                            ui.horizontal(|ui| {
+                                let mut checked = backup.selected.contains(&entry.path);
+                                if ui.checkbox(&mut checked, &entry.path).changed() {
+                                    if checked {
+                                        backup.selected.insert(entry.path.clone());
+                                    } else {
+                                        backup.selected.remove(&entry.path);
+                                    }
+                                }
+                                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                    ui.weak(ProgressFormatter::format_bytes(entry.size));
+                                    if let Some(deleted) = &entry.deleted {
+                                        status_label(ui, StatusKind::Warning, format!("deleted {}", format_time(deleted)));
+                                    }
+                                });
+                            });
+                        }
+                    });
+            });
+        });
+
+        event
+    }
+}
+
+/// 以本地时间显示清单中的RFC 3339时间
+fn format_time(created: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(created)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| created.to_string())
+}
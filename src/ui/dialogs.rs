@@ -1,9 +1,21 @@
 use eframe::egui;
+use super::a11y;
+use super::theme::{status_chip, status_label, StatusKind};
+use crate::core::journal::Journal;
+use crate::core::preflight::{DryRunReport, OutputAction, PlannedOutput};
+use crate::crypto::audit::{AuditReport, AuditStatus};
+use crate::models::{ConfigLockState, ConflictPolicy, FileItem, ShareLogin};
+use crate::progress::ProgressFormatter;
+use std::path::Path;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum DialogEvent {
     SkipCurrentTask,
     StopAllOperations,
+    /// 把隔离的源文件移回原处
+    UndoDelete,
+    /// 关闭完成对话框，删除隔离的源文件
+    PurgeQuarantine,
 }
 
 pub struct ErrorDialog;
@@ -20,7 +32,7 @@ impl ErrorDialog {
                 .collapsible(false)
                 .resizable(false)
                 .show(ctx, |ui| {
-                    ui.label(error_message);
+                    status_label(ui, StatusKind::Error, format!("{} {}", StatusKind::Error.symbol(), error_message));
                     ui.separator();
                     ui.horizontal(|ui| {
                         if ui.button("Skip").clicked() {
@@ -42,21 +54,647 @@ impl ErrorDialog {
 pub struct CompleteDialog;
 
 impl CompleteDialog {
+    /// `quarantined` 为隔离区中等待删除的源文件数，有文件时显示撤销删除按钮
     pub fn render(
         ctx: &egui::Context,
         show: &mut bool,
-    ) {
+        report_path: Option<&Path>,
+        quarantined: usize,
+    ) -> Option<DialogEvent> {
+        let mut event = None;
         if *show {
             egui::Window::new("Complete")
                 .collapsible(false)
                 .resizable(false)
                 .show(ctx, |ui| {
-                    ui.label("Operation completed successfully!");
+                    status_label(ui, StatusKind::Success, format!("{} Operation completed successfully!", StatusKind::Success.symbol()));
+                    if let Some(path) = report_path {
+                        ui.label(format!("Report: {}", path.display()));
+                    }
+                    if quarantined > 0 {
+                        ui.label(format!("{} source files will be deleted when this dialog is closed.", quarantined));
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("OK").clicked() || close_key_pressed(ui) {
+                            *show = false;
+                            if quarantined > 0 {
+                                event = Some(DialogEvent::PurgeQuarantine);
+                            }
+                        }
+                        if quarantined > 0 && ui.button(format!("Undo Delete ({} files)", quarantined)).clicked() {
+                            *show = false;
+                            event = Some(DialogEvent::UndoDelete);
+                        }
+                    });
+                });
+        }
+        event
+    }
+}
+
+pub struct InfoDialog;
+
+impl InfoDialog {
+    pub fn render(
+        ctx: &egui::Context,
+        show: &mut bool,
+        title: &str,
+        message: &str,
+    ) {
+        if *show {
+            egui::Window::new(title)
+                .id(egui::Id::new("info_dialog"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(message);
                     ui.separator();
-                    if ui.button("OK").clicked() {
+                    if ui.button("OK").clicked() || close_key_pressed(ui) {
                         *show = false;
                     }
                 });
         }
     }
-} 
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreflightEvent {
+    Start,
+    Cancel,
+}
+
+/// 开始前的输出预览：列出计划的输出与目标中已有的文件，可以逐个文件调整冲突处理方式
+pub struct PreflightDialog;
+
+impl PreflightDialog {
+    /// `files` 为计划中的序号所指的文件列表，调整的处理方式保存在文件项中
+    pub fn render(
+        ctx: &egui::Context,
+        show: &mut bool,
+        plan: &[PlannedOutput],
+        files: &mut [FileItem],
+        default_policy: ConflictPolicy,
+        conflicts_only: &mut bool,
+    ) -> Option<PreflightEvent> {
+        if !*show {
+            return None;
+        }
+
+        let mut event = None;
+        let policy_of = |files: &[FileItem], planned: &PlannedOutput| {
+            files.get(planned.index).and_then(|file| file.conflict).unwrap_or(default_policy)
+        };
+        egui::Window::new("Review Outputs")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                let count = |action: OutputAction| plan.iter().filter(|planned| planned.action(policy_of(files, planned)) == action).count();
+                ui.label(format!(
+                    "{} files: {} new, {} would overwrite, {} would skip, {} keep both",
+                    plan.len(),
+                    count(OutputAction::New),
+                    count(OutputAction::Overwrite),
+                    count(OutputAction::Skip),
+                    count(OutputAction::KeepBoth),
+                ));
+                ui.horizontal(|ui| {
+                    ui.label("Set all existing to:");
+                    for policy in ConflictPolicy::CHOICES {
+                        if ui.button(policy.to_string()).clicked() {
+                            for planned in plan.iter().filter(|planned| planned.exists) {
+                                if let Some(file) = files.get_mut(planned.index) {
+                                    file.conflict = Some(policy);
+                                }
+                            }
+                        }
+                    }
+                    ui.separator();
+                    ui.checkbox(conflicts_only, "Only show existing");
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .id_salt("preflight_scroll")
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("preflight_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                ui.strong("Result");
+                                ui.strong("File");
+                                ui.strong("Output");
+                                ui.strong("If Exists");
+                                ui.end_row();
+
+                                for planned in plan.iter().filter(|planned| planned.exists || !*conflicts_only) {
+                                    let action = planned.action(policy_of(files, planned));
+                                    let kind = match action {
+                                        OutputAction::New | OutputAction::KeepBoth => StatusKind::Success,
+                                        OutputAction::Skip => StatusKind::Warning,
+                                        OutputAction::Overwrite => StatusKind::Error,
+                                    };
+                                    status_label(ui, kind, format!("{} {}", action.symbol(), action));
+                                    ui.label(&planned.name);
+                                    match &planned.output {
+                                        Some(path) => ui.label(path.display().to_string()),
+                                        None => ui.weak("(random name)"),
+                                    };
+                                    match files.get_mut(planned.index) {
+                                        Some(file) if planned.exists => {
+                                            let default_label = format!("Default ({})", default_policy);
+                                            let response = egui::ComboBox::from_id_salt(("preflight_policy", planned.index))
+                                                .selected_text(file.conflict.map_or(default_label.clone(), |policy| policy.to_string()))
+                                                .show_ui(ui, |ui| {
+                                                    ui.selectable_value(&mut file.conflict, None, default_label);
+                                                    for policy in ConflictPolicy::CHOICES {
+                                                        ui.selectable_value(&mut file.conflict, Some(policy), policy.to_string());
+                                                    }
+                                                })
+                                                .response;
+                                            a11y::set_name(&response, egui::WidgetType::ComboBox, &format!("If {} exists", planned.name));
+                                        }
+                                        _ => {
+                                            ui.label("");
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Start").clicked() {
+                        event = Some(PreflightEvent::Start);
+                    }
+                    if ui.button("Cancel").clicked() || close_key_pressed(ui) {
+                        event = Some(PreflightEvent::Cancel);
+                    }
+                });
+            });
+
+        if event.is_some() {
+            *show = false;
+        }
+        event
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DryRunEvent {
+    Start,
+    Close,
+}
+
+/// 试运行的结果：每个文件的输出和估计大小，以及各文件系统需要和可用的空间
+pub struct DryRunDialog;
+
+impl DryRunDialog {
+    pub fn render(ctx: &egui::Context, report: &DryRunReport) -> Option<DryRunEvent> {
+        let mut event = None;
+        egui::Window::new("Dry Run")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(760.0)
+            .show(ctx, |ui| {
+                let count = |action: OutputAction| report.entries.iter().filter(|entry| entry.action == action).count();
+                ui.label(format!(
+                    "{} files: {} new, {} would overwrite, {} would skip, {} keep both · about {} to write",
+                    report.entries.len(),
+                    count(OutputAction::New),
+                    count(OutputAction::Overwrite),
+                    count(OutputAction::Skip),
+                    count(OutputAction::KeepBoth),
+                    ProgressFormatter::format_bytes(report.total_size()),
+                ));
+                match &report.bundle {
+                    Some(Ok(path)) => {
+                        ui.label(format!("All files go into '{}'", path.display()));
+                    }
+                    Some(Err(error)) => {
+                        status_label(ui, StatusKind::Error, format!("{} {}", StatusKind::Error.symbol(), error));
+                    }
+                    None => {}
+                }
+                ui.weak("Nothing has been written. Sizes are estimates; unchanged files of an incremental batch are counted as well.");
+                ui.separator();
+
+                egui::Grid::new("dry_run_space_grid")
+                    .striped(true)
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        ui.strong("Output Location");
+                        ui.strong("Needed");
+                        ui.strong("Available");
+                        ui.strong("");
+                        ui.end_row();
+                        for requirement in &report.space {
+                            ui.label(requirement.directory.display().to_string());
+                            ui.label(ProgressFormatter::format_bytes(requirement.required));
+                            ui.label(requirement.available.map_or("unknown".to_string(), ProgressFormatter::format_bytes));
+                            match requirement.is_sufficient() {
+                                true => status_label(ui, StatusKind::Success, format!("{} Enough space", StatusKind::Success.symbol())),
+                                false => status_label(ui, StatusKind::Error, format!("{} Not enough space", StatusKind::Error.symbol())),
+                            };
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .id_salt("dry_run_scroll")
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("dry_run_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                ui.strong("Result");
+                                ui.strong("File");
+                                ui.strong("Output");
+                                ui.strong("Size");
+                                ui.end_row();
+
+                                for entry in &report.entries {
+                                    let kind = match entry.action {
+                                        OutputAction::New | OutputAction::KeepBoth => StatusKind::Success,
+                                        OutputAction::Skip => StatusKind::Warning,
+                                        OutputAction::Overwrite => StatusKind::Error,
+                                    };
+                                    status_label(ui, kind, format!("{} {}", entry.action.symbol(), entry.action));
+                                    ui.label(&entry.planned.name);
+                                    match &entry.planned.output {
+                                        Some(path) => ui.label(path.display().to_string()),
+                                        None if report.bundle.is_some() => ui.weak("(into the archive)"),
+                                        None => ui.weak("(random name)"),
+                                    };
+                                    ui.label(format!(
+                                        "{} → {}",
+                                        ProgressFormatter::format_bytes(entry.input_size),
+                                        ProgressFormatter::format_bytes(entry.estimated_size),
+                                    ));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let start = ui.add_enabled(report.has_enough_space(), egui::Button::new("Start"));
+                    if start.on_disabled_hover_text("Free up space or choose another output directory first").clicked() {
+                        event = Some(DryRunEvent::Start);
+                    }
+                    if ui.button("Close").clicked() || close_key_pressed(ui) {
+                        event = Some(DryRunEvent::Close);
+                    }
+                });
+            });
+        event
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResumeEvent {
+    /// 继续第几个中断的批处理
+    Resume(usize),
+    /// 放弃第几个中断的批处理，删除其日志
+    Discard(usize),
+    Later,
+}
+
+/// 列出崩溃或重启前中断的批处理，输入密码后从中断处继续
+pub struct ResumeDialog;
+
+impl ResumeDialog {
+    pub fn render(ctx: &egui::Context, journals: &[Journal], password: &mut String, error: Option<&str>) -> Option<ResumeEvent> {
+        let mut event = None;
+        egui::Window::new("Resume Previous Operation")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(620.0)
+            .show(ctx, |ui| {
+                ui.label("These batches were interrupted before they finished. Completed files are not processed again; files that were in progress start over.");
+                ui.separator();
+                egui::Grid::new("resume_grid")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        ui.strong("Started");
+                        ui.strong("Mode");
+                        ui.strong("Files");
+                        ui.strong("Done");
+                        ui.strong("");
+                        ui.end_row();
+                        for (index, journal) in journals.iter().enumerate() {
+                            let record = journal.record();
+                            let started = chrono::DateTime::parse_from_rfc3339(&record.started)
+                                .map(|started| started.format("%Y-%m-%d %H:%M").to_string())
+                                .unwrap_or(record.started);
+                            ui.label(started);
+                            ui.label(format!("{:?}", record.settings.operation_mode));
+                            ui.label(record.files.len().to_string());
+                            ui.label(record.completed.len().to_string());
+                            ui.horizontal(|ui| {
+                                if ui.add_enabled(!password.is_empty(), egui::Button::new("Resume")).clicked() {
+                                    event = Some(ResumeEvent::Resume(index));
+                                }
+                                if ui.button("Discard")
+                                    .on_hover_text("Forget this batch; outputs already written are kept")
+                                    .clicked()
+                                {
+                                    event = Some(ResumeEvent::Discard(index));
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let password_label = ui.label("Password:");
+                    ui.add(egui::TextEdit::singleline(password).password(true))
+                        .labelled_by(password_label.id);
+                });
+                if let Some(error) = error {
+                    status_label(ui, StatusKind::Error, error);
+                }
+                ui.separator();
+                if ui.button("Later").clicked() || close_key_pressed(ui) {
+                    event = Some(ResumeEvent::Later);
+                }
+            });
+        event
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictPromptEvent {
+    /// 对当前文件（勾选了用于剩下的文件时对所有剩下的文件）使用该处理方式
+    Resolve(ConflictPolicy),
+    Cancel,
+}
+
+/// 冲突处理方式为询问时，开始前逐个询问已有的输出文件如何处理
+pub struct ConflictPromptDialog;
+
+impl ConflictPromptDialog {
+    /// `remaining` 为包括当前文件在内还需要询问的文件数
+    pub fn render(
+        ctx: &egui::Context,
+        planned: &PlannedOutput,
+        remaining: usize,
+        apply_all: &mut bool,
+    ) -> Option<ConflictPromptEvent> {
+        let mut event = None;
+        egui::Window::new("Output Exists")
+            .id(egui::Id::new("conflict_prompt_dialog"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("The output for '{}' already exists:", planned.name));
+                if let Some(path) = &planned.output {
+                    ui.monospace(path.display().to_string());
+                }
+                if remaining > 1 {
+                    ui.checkbox(apply_all, format!("Do this for the remaining {} files", remaining));
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    for policy in ConflictPolicy::CHOICES {
+                        if ui.button(policy.to_string()).clicked() {
+                            event = Some(ConflictPromptEvent::Resolve(policy));
+                        }
+                    }
+                    if ui.button("Cancel").clicked() || close_key_pressed(ui) {
+                        event = Some(ConflictPromptEvent::Cancel);
+                    }
+                });
+            });
+        event
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordPromptEvent {
+    Run,
+    SkipRun,
+}
+
+/// 计划任务运行前的密码输入对话框
+pub struct PasswordPromptDialog;
+
+impl PasswordPromptDialog {
+    pub fn render(
+        ctx: &egui::Context,
+        job_name: &str,
+        hint: Option<&str>,
+        password: &mut String,
+        remember: &mut bool,
+    ) -> Option<PasswordPromptEvent> {
+        let mut event = None;
+        egui::Window::new("Scheduled Job")
+            .id(egui::Id::new("password_prompt_dialog"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Job '{}' is due. Enter the password to run it.", job_name));
+                if let Some(hint) = hint {
+                    ui.label(format!("💡 Hint: {}", hint));
+                }
+                let response = ui.add(
+                    egui::TextEdit::singleline(password)
+                        .password(true)
+                        .frame(true)
+                );
+                a11y::set_name(&response, egui::WidgetType::TextEdit, "Password");
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                ui.checkbox(remember, "Remember for this session");
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if (ui.button("Run").clicked() || submitted) && !password.is_empty() {
+                        event = Some(PasswordPromptEvent::Run);
+                    }
+                    if ui.button("Skip This Run").clicked() {
+                        event = Some(PasswordPromptEvent::SkipRun);
+                    }
+                });
+            });
+        event
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareLoginEvent {
+    Connect,
+    Cancel,
+}
+
+/// 连接需要登录的网络共享
+pub struct ShareLoginDialog;
+
+impl ShareLoginDialog {
+    pub fn render(ctx: &egui::Context, login: &mut ShareLogin) -> Option<ShareLoginEvent> {
+        let mut event = None;
+        egui::Window::new("Connect to Network Share")
+            .id(egui::Id::new("share_login_dialog"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Enter the credentials for '{}'.", login.share));
+                egui::Grid::new("share_login_grid").num_columns(2).show(ui, |ui| {
+                    let user_label = ui.label("User:");
+                    ui.add(egui::TextEdit::singleline(&mut login.user).hint_text(r"DOMAIN\user")).labelled_by(user_label.id);
+                    ui.end_row();
+                    let password_label = ui.label("Password:");
+                    let response = ui.add(egui::TextEdit::singleline(&mut login.password).password(true)).labelled_by(password_label.id);
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) && !login.user.is_empty() {
+                        event = Some(ShareLoginEvent::Connect);
+                    }
+                    ui.end_row();
+                });
+                if let Some(error) = &login.error {
+                    status_label(ui, StatusKind::Error, error);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!login.user.is_empty(), egui::Button::new("Connect")).clicked() {
+                        event = Some(ShareLoginEvent::Connect);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        event = Some(ShareLoginEvent::Cancel);
+                    }
+                });
+            });
+        event
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigUnlockEvent {
+    Unlock,
+    /// 不解锁继续使用，本次会话不加载也不保存配置
+    Skip,
+}
+
+/// 启动时用主密码解锁加密的配置文件
+pub struct ConfigUnlockDialog;
+
+impl ConfigUnlockDialog {
+    pub fn render(ctx: &egui::Context, config_lock: &mut ConfigLockState) -> Option<ConfigUnlockEvent> {
+        let mut event = None;
+        egui::Window::new("Unlock Configuration")
+            .id(egui::Id::new("config_unlock_dialog"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Your settings, schedules and history are encrypted. Enter the master password to load them.");
+                ui.horizontal(|ui| {
+                    let password_label = ui.label("Master Password:");
+                    let response = ui.add(egui::TextEdit::singleline(&mut config_lock.unlock_password).password(true))
+                        .labelled_by(password_label.id);
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        event = Some(ConfigUnlockEvent::Unlock);
+                    }
+                });
+                if let Some(error) = &config_lock.unlock_error {
+                    status_label(ui, StatusKind::Error, error);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!config_lock.unlock_password.is_empty(), egui::Button::new("Unlock")).clicked() {
+                        event = Some(ConfigUnlockEvent::Unlock);
+                    }
+                    if ui.button("Continue Locked")
+                        .on_hover_text("Start without your saved configuration; changes are not saved")
+                        .clicked()
+                    {
+                        event = Some(ConfigUnlockEvent::Skip);
+                    }
+                });
+            });
+        event
+    }
+}
+
+pub struct AuditDialog;
+
+impl AuditDialog {
+    pub fn render(
+        ctx: &egui::Context,
+        show: &mut bool,
+        reports: &[AuditReport],
+    ) {
+        if !*show {
+            return;
+        }
+
+        let passed = reports.iter().filter(|report| report.passed()).count();
+        egui::Window::new("Audit Report")
+            .collapsible(false)
+            .resizable(true)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} files audited: {} passed, {} failed",
+                    reports.len(),
+                    passed,
+                    reports.len() - passed
+                ));
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .id_salt("audit_report_scroll")
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("audit_report_grid")
+                            .striped(true)
+                            .num_columns(4)
+                            .show(ui, |ui| {
+                                ui.strong("File");
+                                ui.strong("Result");
+                                ui.strong("Chunks");
+                                ui.strong("Details");
+                                ui.end_row();
+
+                                for report in reports {
+                                    ui.label(&report.name);
+                                    match &report.status {
+                                        AuditStatus::Passed => {
+                                            status_chip(ui, StatusKind::Success, "PASS");
+                                        }
+                                        AuditStatus::Failed(_) => {
+                                            status_chip(ui, StatusKind::Error, "FAIL");
+                                        }
+                                    }
+                                    ui.label(report.chunks.to_string());
+                                    match &report.status {
+                                        AuditStatus::Passed if report.authenticated => {
+                                            ui.label("Structure and authentication tags verified");
+                                        }
+                                        AuditStatus::Passed => {
+                                            ui.label("Structure verified (no password supplied)");
+                                        }
+                                        AuditStatus::Failed(reason) => {
+                                            ui.label(reason);
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                if ui.button("Close").clicked() || close_key_pressed(ui) {
+                    *show = false;
+                }
+            });
+    }
+}
+
+/// 按Esc关闭只需确认的对话框
+fn close_key_pressed(ui: &egui::Ui) -> bool {
+    ui.input(|input| input.key_pressed(egui::Key::Escape))
+}
@@ -1,5 +1,23 @@
+pub mod a11y;
 pub mod panels;
+pub mod backup;
 pub mod dialogs;
+pub mod tools;
+pub mod notes;
+pub mod palette;
+pub mod preview;
+pub mod schedule;
+pub mod theme;
+pub mod vault;
+pub mod watch;
 
 pub use panels::*;
-pub use dialogs::*; 
\ No newline at end of file
+pub use backup::*;
+pub use dialogs::*;
+pub use tools::*;
+pub use notes::*;
+pub use palette::*;
+pub use preview::*;
+pub use schedule::*;
+pub use vault::*;
+pub use watch::*; 
\ No newline at end of file
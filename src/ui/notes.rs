@@ -0,0 +1,128 @@
+use eframe::egui;
+use super::a11y;
+use super::theme::{status_label, StatusKind};
+use crate::models::NotesState;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotesEvent {
+    Unlock,
+    Lock,
+    NewNote,
+    DeleteNote(usize),
+    Save,
+}
+
+pub struct NotesPanel;
+
+impl NotesPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        notes: &mut NotesState,
+    ) -> Option<NotesEvent> {
+        let mut event = None;
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.label("Encrypted Notes");
+            ui.separator();
+
+            if !notes.unlocked {
+                // Unlock form
+                ui.horizontal(|ui| {
+                    let master_password_label = ui.label("Master Password: ");
+                    let response = ui.add_sized(
+                        [300.0, 20.0],
+                        egui::TextEdit::singleline(&mut notes.master_password)
+                            .password(true)
+                            .frame(true)
+                    ).labelled_by(master_password_label.id);
+                    let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    if ui.button("Unlock").clicked() || submitted {
+                        event = Some(NotesEvent::Unlock);
+                    }
+                });
+                ui.label("Notes are encrypted with the master password and stored in the application data directory.");
+            } else {
+                // Toolbar
+                ui.horizontal(|ui| {
+                    if ui.button("New Note").clicked() {
+                        event = Some(NotesEvent::NewNote);
+                    }
+                    if let Some(index) = notes.selected {
+                        if ui.button("Delete").clicked() {
+                            event = Some(NotesEvent::DeleteNote(index));
+                        }
+                    }
+                    let save_label = if notes.dirty { "Save *" } else { "Save" };
+                    if ui.button(save_label).clicked() {
+                        event = Some(NotesEvent::Save);
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Lock").clicked() {
+                            event = Some(NotesEvent::Lock);
+                        }
+                    });
+                });
+                ui.separator();
+
+                ui.horizontal_top(|ui| {
+                    // Note list
+                    ui.allocate_ui_with_layout(
+                        egui::Vec2::new(220.0, 420.0),
+                        egui::Layout::top_down(egui::Align::LEFT),
+                        |ui| {
+                            egui::ScrollArea::vertical()
+                                .id_salt("notes_list_scroll")
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    for (index, note) in notes.notes.iter().enumerate() {
+                                        let title = if note.title.is_empty() { "(untitled)" } else { note.title.as_str() };
+                                        if ui.selectable_label(notes.selected == Some(index), title).clicked() {
+                                            notes.selected = Some(index);
+                                        }
+                                    }
+                                    if notes.notes.is_empty() {
+                                        ui.label("No notes yet");
+                                    }
+                                });
+                        }
+                    );
+
+                    ui.separator();
+
+                    // Note editor
+                    ui.vertical(|ui| {
+                        let selected_note = notes.selected.and_then(|index| notes.notes.get_mut(index));
+                        if let Some(note) = selected_note {
+                            let title = ui.add(
+                                egui::TextEdit::singleline(&mut note.title)
+                                    .hint_text("Title")
+                                    .desired_width(f32::INFINITY)
+                            );
+                            a11y::set_name(&title, egui::WidgetType::TextEdit, "Note title");
+                            let body = ui.add_sized(
+                                [ui.available_width(), 380.0],
+                                egui::TextEdit::multiline(&mut note.body)
+                                    .hint_text("Secret content, e.g. license keys")
+                            );
+                            a11y::set_name(&body, egui::WidgetType::TextEdit, "Note content");
+                            if title.changed() || body.changed() {
+                                note.touch();
+                                notes.dirty = true;
+                            }
+                        } else {
+                            ui.label("Select a note to edit");
+                        }
+                    });
+                });
+            }
+
+            // Status line
+            if !notes.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(notes.status_is_error), &notes.status_message);
+            }
+        });
+
+        event
+    }
+}
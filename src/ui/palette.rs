@@ -0,0 +1,215 @@
+//! Ctrl+P 命令面板：按名称模糊搜索界面上的操作并用键盘执行
+
+use eframe::egui;
+use crate::crypto::registry;
+use crate::models::{ActiveTab, EncryptionAlgorithm, OperationMode};
+use super::panels::PanelEvent;
+use super::tools::ToolsEvent;
+
+/// 打开或关闭命令面板的快捷键
+const PALETTE_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::P);
+
+/// 最多列出的匹配命令数
+const MAX_RESULTS: usize = 12;
+
+/// 命令面板中选中命令后执行的操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteAction {
+    Panel(PanelEvent),
+    Tool(ToolsEvent),
+    ShowTab(ActiveTab),
+    SetMode(OperationMode),
+    SetAlgorithm(EncryptionAlgorithm),
+}
+
+/// 命令面板中的一条命令
+#[derive(Debug, Clone)]
+pub struct PaletteCommand {
+    pub label: String,
+    pub action: PaletteAction,
+}
+
+impl PaletteCommand {
+    fn new(label: impl Into<String>, action: PaletteAction) -> Self {
+        Self { label: label.into(), action }
+    }
+}
+
+/// 命令面板状态
+#[derive(Debug, Default)]
+pub struct CommandPaletteState {
+    pub open: bool,
+    query: String,
+    selected: usize,
+}
+
+pub struct CommandPalette;
+
+impl CommandPalette {
+    /// 所有可用的命令
+    pub fn commands() -> Vec<PaletteCommand> {
+        let mut commands = vec![
+            PaletteCommand::new("Open Directory to Encrypt...", PaletteAction::Panel(PanelEvent::SelectLeftDirectory)),
+            PaletteCommand::new("Open Directory to Decrypt...", PaletteAction::Panel(PanelEvent::SelectRightDirectory)),
+            PaletteCommand::new("Start", PaletteAction::Panel(PanelEvent::StartOperation)),
+            PaletteCommand::new("Dry Run", PaletteAction::Panel(PanelEvent::DryRun)),
+            PaletteCommand::new("Stop", PaletteAction::Panel(PanelEvent::StopOperation)),
+            PaletteCommand::new("Resume Previous Operation...", PaletteAction::Panel(PanelEvent::ResumePrevious)),
+            PaletteCommand::new("New Batch Tab", PaletteAction::Panel(PanelEvent::NewOperationTab)),
+            PaletteCommand::new("Audit Encrypted Files", PaletteAction::Panel(PanelEvent::AuditFiles)),
+            PaletteCommand::new("Restore Names", PaletteAction::Panel(PanelEvent::RestoreNames)),
+            PaletteCommand::new("Save Job...", PaletteAction::Panel(PanelEvent::SaveJob)),
+            PaletteCommand::new("Load Job...", PaletteAction::Panel(PanelEvent::LoadJob)),
+            PaletteCommand::new("Calibrate Unlock Time", PaletteAction::Panel(PanelEvent::CalibrateKdf)),
+            PaletteCommand::new("Mode: Encrypt", PaletteAction::SetMode(OperationMode::Encrypt)),
+            PaletteCommand::new("Mode: Decrypt", PaletteAction::SetMode(OperationMode::Decrypt)),
+            PaletteCommand::new("Mode: Verify", PaletteAction::SetMode(OperationMode::Verify)),
+            PaletteCommand::new("Mode: Archive (.7z)", PaletteAction::SetMode(OperationMode::Archive)),
+            PaletteCommand::new("Mode: Container (.krypton)", PaletteAction::SetMode(OperationMode::Container)),
+        ];
+        commands.extend(registry::available_algorithms().into_iter().map(|algorithm| {
+            PaletteCommand::new(format!("Algorithm: {}", algorithm), PaletteAction::SetAlgorithm(algorithm))
+        }));
+        commands.extend([
+            ("Show Files and Settings", ActiveTab::Files),
+            ("Show Tools", ActiveTab::Tools),
+            ("Show Notes", ActiveTab::Notes),
+            ("Show Schedule", ActiveTab::Schedule),
+            ("Show Watch", ActiveTab::Watch),
+            ("Show Backup", ActiveTab::Backup),
+            ("Show Vault", ActiveTab::Vault),
+        ].map(|(label, tab)| PaletteCommand::new(label, PaletteAction::ShowTab(tab))));
+        commands.extend([
+            ("Tool: Encrypt Text", ToolsEvent::EncryptText),
+            ("Tool: Encrypt Text to File...", ToolsEvent::EncryptTextToFile),
+            ("Tool: Encrypt Text to Parts", ToolsEvent::EncryptTextToParts),
+            ("Tool: Decrypt Text", ToolsEvent::DecryptText),
+            ("Tool: Paste from Clipboard", ToolsEvent::PasteFromClipboard),
+            ("Tool: Copy Output", ToolsEvent::CopyOutput),
+        ].map(|(label, event)| PaletteCommand::new(label, PaletteAction::Tool(event))));
+        commands
+    }
+
+    /// 处理Ctrl+P并在打开时渲染命令面板，返回选中的操作
+    pub fn render(ctx: &egui::Context, state: &mut CommandPaletteState) -> Option<PaletteAction> {
+        if ctx.input_mut(|input| input.consume_shortcut(&PALETTE_SHORTCUT)) {
+            state.open = !state.open;
+            state.query.clear();
+            state.selected = 0;
+        }
+        if !state.open {
+            return None;
+        }
+
+        let commands = Self::commands();
+        let mut matches: Vec<(i32, &PaletteCommand)> = commands.iter()
+            .filter_map(|command| fuzzy_score(&state.query, &command.label).map(|score| (score, command)))
+            .collect();
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        matches.truncate(MAX_RESULTS);
+
+        // 方向键在结果中移动，Enter执行，Esc关闭
+        let (up, down, enter, escape) = ctx.input(|input| (
+            input.key_pressed(egui::Key::ArrowUp),
+            input.key_pressed(egui::Key::ArrowDown),
+            input.key_pressed(egui::Key::Enter),
+            input.key_pressed(egui::Key::Escape),
+        ));
+        if down {
+            state.selected += 1;
+        }
+        if up {
+            state.selected = state.selected.saturating_sub(1);
+        }
+        state.selected = state.selected.min(matches.len().saturating_sub(1));
+
+        let mut chosen = None;
+        egui::Window::new("Command Palette")
+            .id(egui::Id::new("command_palette"))
+            .title_bar(false)
+            .collapsible(false)
+            .resizable(false)
+            .fixed_size([420.0, 0.0])
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                let search = ui.add(
+                    egui::TextEdit::singleline(&mut state.query)
+                        .hint_text("Type a command")
+                        .desired_width(f32::INFINITY)
+                );
+                super::a11y::set_name(&search, egui::WidgetType::TextEdit, "Command");
+                search.request_focus();
+                if search.changed() {
+                    state.selected = 0;
+                }
+                ui.separator();
+
+                if matches.is_empty() {
+                    ui.weak("No matching commands");
+                }
+                for (index, (_, command)) in matches.iter().enumerate() {
+                    let selected = index == state.selected;
+                    let response = ui.selectable_label(selected, &command.label);
+                    if selected {
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() || (selected && enter) {
+                        chosen = Some(command.action.clone());
+                    }
+                }
+            });
+
+        if chosen.is_some() || escape {
+            state.open = false;
+        }
+        chosen
+    }
+}
+
+/// 模糊匹配：`query` 的字符按顺序出现在 `text` 中（不区分大小写）时返回得分，越高越好。
+/// 连续匹配和单词开头的匹配得分更高，空查询匹配所有命令
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut previous_match: Option<usize> = None;
+    let mut text_chars = text.char_indices().peekable();
+    let mut previous_char = ' ';
+
+    for query_char in query.chars().filter(|c| !c.is_whitespace()) {
+        let query_char = query_char.to_ascii_lowercase();
+        loop {
+            let (position, text_char) = text_chars.next()?;
+            let word_start = !previous_char.is_alphanumeric();
+            previous_char = text_char;
+            if text_char.to_ascii_lowercase() != query_char {
+                continue;
+            }
+            score += 1;
+            if word_start {
+                score += 8;
+            }
+            match previous_match {
+                Some(previous) if text[previous..].chars().next().map(char::len_utf8) == Some(position - previous) => score += 5,
+                Some(previous) => score -= ((position - previous) as i32).min(5),
+                None => score -= (position as i32).min(5),
+            }
+            previous_match = Some(position);
+            break;
+        }
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score_prefers_word_starts() {
+        assert!(fuzzy_score("dec", "Mode: Decrypt").is_some());
+        assert!(fuzzy_score("xyz", "Mode: Decrypt").is_none());
+        assert!(fuzzy_score("", "Start").is_some());
+        // "sj" 匹配 Save Job 的两个单词开头，得分高于只在词中出现的匹配
+        assert!(fuzzy_score("sj", "Save Job...").unwrap() > fuzzy_score("sj", "Show Projects").unwrap());
+        assert!(fuzzy_score("start", "Start").unwrap() > fuzzy_score("start", "Show Settings and tart").unwrap());
+    }
+}
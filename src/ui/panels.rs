@@ -1,27 +1,80 @@
 use eframe::egui;
-use crate::models::{OperationMode, EncryptionAlgorithm, AppState, Settings, FileManagerState, ProgressState};
+use crate::core::file_details::{FileDetails, HeaderDetails};
+use crate::core::network::{self, DirectoryError};
+use crate::core::shred::{ShredLimitation, MAX_SHRED_PASSES};
+use crate::crypto::audit::IntegrityBadge;
+use crate::crypto::header::MAX_HINT_LEN;
+use crate::crypto::kms::{AWS_SECRET_ENV, VAULT_TOKEN_ENV};
+use crate::crypto::pkcs11::PKCS11_PIN_ENV;
+use crate::crypto::registry;
+use crate::models::{BatchKeyMode, ChecksumSidecar, CompressionAlgorithm, ColorPalette, ConflictPolicy, EncryptionAlgorithm, ErrorPolicy, FileOutcome, FileResult, HardwareToken, KeySource, LayoutMode, OperationMode, OperationView, ProcessingOrder, RecoveryLevel, AppState, FileItem, FileKind, KdfAlgorithm, KdfParams, Settings, FileManagerState, ProgressState};
 use crate::progress::ProgressFormatter;
+use super::a11y;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use super::theme::{status_chip, status_label, StatusKind};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PanelEvent {
     LoadLeftFiles,
     LoadRightFiles,
+    /// 包含子目录的设置改变，重新加载两侧的文件列表
+    ReloadFiles,
     StartOperation,
+    /// 试运行：预览输出、冲突和需要的磁盘空间，不写出任何文件
+    DryRun,
     StopOperation,
     ResumeOperation,
+    /// 继续上次崩溃或重启前中断的批处理
+    ResumePrevious,
     SelectLeftDirectory,
     SelectRightDirectory,
+    RestoreNames,
+    AuditFiles,
+    SaveJob,
+    LoadJob,
+    PreviewFile(usize),
+    PreviewEncryptedFile(usize),
+    ClosePreview,
+    CalibrateKdf,
+    SelectKeyfile,
+    SelectOutputDirectory,
+    /// 生成X25519密钥对并保存私钥
+    GenerateX25519Key,
+    /// 选择（导入）X25519私钥文件
+    SelectX25519Identity,
+    /// 把私钥对应的X25519公钥复制到剪贴板
+    CopyX25519PublicKey,
+    ResizeThreadPool,
+    NewOperationTab,
+    CloseOperationTab(usize),
+    /// 用用户名和密码连接网络共享
+    ConnectShare(String),
 }
 
 pub struct SettingsPanel;
 
 impl SettingsPanel {
+    /// 渲染设置面板，`locked` 为true时（操作进行中）所有设置只读
     pub fn render(
         ui: &mut egui::Ui,
         settings: &mut Settings,
-    ) {
+        locked: bool,
+    ) -> Option<PanelEvent> {
         ui.set_width(ui.available_width());
-        
+
+        if locked {
+            ui.horizontal(|ui| {
+                ui.label("🔒");
+                ui.weak("Settings are locked while this batch is running. Stop it, or open a new batch tab with + to start another one.");
+            });
+        }
+        ui.add_enabled_ui(!locked, |ui| Self::render_fields(ui, settings)).inner
+    }
+
+    fn render_fields(ui: &mut egui::Ui, settings: &mut Settings) -> Option<PanelEvent> {
+        let mut event = None;
+
         // First row: Operation mode, encryption algorithm, password input
         ui.horizontal(|ui| {
             ui.set_width(ui.available_width());
@@ -30,57 +83,536 @@ impl SettingsPanel {
             ui.label("Mode: ");
             ui.radio_value(&mut settings.operation_mode, OperationMode::Encrypt, "Encrypt");
             ui.radio_value(&mut settings.operation_mode, OperationMode::Decrypt, "Decrypt");
+            ui.radio_value(&mut settings.operation_mode, OperationMode::Verify, "Verify")
+                .on_hover_text("Check the password and authentication tags of the selected encrypted files without writing anything");
+            ui.radio_value(&mut settings.operation_mode, OperationMode::Archive, "Archive (.7z)")
+                .on_hover_text("Pack the selected files into one password-protected 7z archive (AES-256) that 7-Zip and other archive tools can open");
+            ui.radio_value(&mut settings.operation_mode, OperationMode::Container, "Container (.krypton)")
+                .on_hover_text("Pack the selected files with their relative paths and timestamps into one encrypted Krypton container whose entries can be listed and extracted individually");
 
             ui.separator();
 
             // Encryption algorithm selection
-            ui.label("Algorithm: ");
-            egui::ComboBox::from_label("")
+            let algorithm_label = ui.label("Algorithm: ");
+            egui::ComboBox::from_id_salt("algorithm")
                 .selected_text(settings.encryption_algorithm.to_string())
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut settings.encryption_algorithm, EncryptionAlgorithm::AES256, "AES-256");
-                    ui.selectable_value(&mut settings.encryption_algorithm, EncryptionAlgorithm::ChaCha20, "ChaCha20");
-                });
+                    for algorithm in registry::available_algorithms() {
+                        let label = algorithm.to_string();
+                        ui.selectable_value(&mut settings.encryption_algorithm, algorithm, label);
+                    }
+                })
+                .response
+                .labelled_by(algorithm_label.id);
+
+            // 级联加密只在加密时选择，解密时按文件头中记录的两层算法处理
+            let builtin = !settings.encryption_algorithm.is_interop() && !matches!(settings.encryption_algorithm, EncryptionAlgorithm::Plugin(_));
+            ui.add_enabled(settings.operation_mode == OperationMode::Encrypt && builtin, egui::Checkbox::new(&mut settings.cascade, "Cascade"))
+                .on_hover_text("Encrypt again with another built-in algorithm (AES-256-GCM paired with ChaCha20-Poly1305 or XChaCha20-Poly1305) using an independently derived key, so the data stays safe if one of them is broken. Slower and slightly larger.")
+                .on_disabled_hover_text("Cascade encryption is chosen when encrypting and needs a built-in algorithm; cascaded files are detected automatically");
 
             ui.separator();
 
+            // Key source selection
+            let key_label = ui.label("Key: ");
+            egui::ComboBox::from_id_salt("key_source")
+                .selected_text(settings.key_source.to_string())
+                .show_ui(ui, |ui| {
+                    for source in KeySource::ALL {
+                        ui.selectable_value(&mut settings.key_source, source, source.to_string());
+                    }
+                })
+                .response
+                .labelled_by(key_label.id);
+
             // Key input - fixed width
-            ui.label("Password: ");
-            ui.add_sized(
-                [400.0, 20.0],
+            let uses_kms = settings.key_source != KeySource::Passphrase;
+            let password_label = ui.label(if uses_kms { "Index Password: " } else { "Password: " });
+            let password_response = ui.add_sized(
+                [260.0, 20.0],
                 egui::TextEdit::singleline(&mut settings.password)
+                    .hint_text(if uses_kms { "optional" } else { "" })
                     .frame(true)
-            );
+            ).labelled_by(password_label.id);
+            if settings.key_source == KeySource::X25519 {
+                password_response.on_hover_text("With a password slot, this password can also decrypt the files. It protects the filename index too.");
+            } else if uses_kms {
+                password_response.on_hover_text("File keys come from the key management service. This password only protects the filename index.");
+            }
+
+            // 可选的密钥文件，与密码一起参与密钥派生，解密时需要同一个文件
+            if !uses_kms {
+                let keyfile_text = match &settings.keyfile {
+                    Some(path) => format!("🔑 {}", path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string())),
+                    None => "🔑 Keyfile…".to_string(),
+                };
+                let keyfile_response = ui.button(keyfile_text)
+                    .on_hover_text("Mix the contents of a file into the key. Decrypting then needs both the password and the same keyfile.");
+                if keyfile_response.clicked() {
+                    event = Some(PanelEvent::SelectKeyfile);
+                }
+                if settings.keyfile.is_some() && ui.small_button("✖").on_hover_text("Stop using the keyfile").clicked() {
+                    settings.keyfile = None;
+                }
+            }
+
+            // 加密时可选的硬件令牌，解密时从文件头得知需要的令牌
+            if settings.operation_mode == OperationMode::Encrypt {
+                let token_label = ui.label("Token: ");
+                egui::ComboBox::from_id_salt("hardware_token")
+                    .selected_text(settings.hardware_token.to_string())
+                    .show_ui(ui, |ui| {
+                        for token in HardwareToken::ALL {
+                            ui.selectable_value(&mut settings.hardware_token, token, token.to_string());
+                        }
+                    })
+                    .response
+                    .labelled_by(token_label.id)
+                    .on_hover_text("Mix a YubiKey HMAC-SHA1 challenge-response into the key. Decrypting then needs both the password and the same YubiKey.");
+            }
+
+            // 可选的密码提示，加密时以明文写入文件头
+            if settings.operation_mode == OperationMode::Encrypt {
+                let hint_label = ui.label("Hint: ");
+                ui.add_sized(
+                    [140.0, 20.0],
+                    egui::TextEdit::singleline(&mut settings.password_hint)
+                        .char_limit(MAX_HINT_LEN)
+                        .hint_text("optional")
+                        .frame(true)
+                ).labelled_by(hint_label.id).on_hover_text("Stored unencrypted in the file header. Anyone can read it, so never enter the password itself.");
+            }
         });
         
+        // 外部密钥管理服务的连接设置
+        if settings.key_source != KeySource::Passphrase {
+            if let Some(kms_event) = Self::render_kms_fields(ui, settings) {
+                event = Some(kms_event);
+            }
+        }
+
         // Second row: Max threads, file extension, checkboxes
         ui.horizontal(|ui| {
             ui.set_width(ui.available_width());
             
             // Max threads - fixed width
-            ui.label("Max Threads: ");
-            ui.add_sized(
+            let threads_label = ui.label("Max Threads: ");
+            // 所有批处理共享一个线程池，拖动滑块时立即调整池大小
+            if ui.add_sized(
                 [200.0, 20.0],
                 egui::Slider::new(&mut settings.max_threads, 1..=16)
-            );
+            ).labelled_by(threads_label.id).changed() {
+                event = Some(PanelEvent::ResizeThreadPool);
+            }
+
+            ui.separator();
+
+            // Memory budget shared by all worker threads
+            let memory_label = ui.label("Memory: ");
+            ui.add_sized(
+                [160.0, 20.0],
+                egui::Slider::new(&mut settings.memory_budget_mb, 16..=2048)
+                    .logarithmic(true)
+                    .suffix(" MB")
+            ).labelled_by(memory_label.id).on_hover_text("Upper bound for buffer memory used by all worker threads together");
+            ui.checkbox(&mut settings.parallel_chunks, "Parallel Chunks")
+                .on_hover_text("Encrypt or decrypt the chunks of each file in parallel batches on all CPU cores. Output is identical to normal processing.");
+            ui.checkbox(&mut settings.direct_io, "Direct I/O")
+                .on_hover_text("Read and write files without going through the OS page cache, so huge runs don't evict everything else from it");
+            ui.checkbox(&mut settings.read_ahead, "Read Ahead")
+                .on_hover_text("Read the next chunk from disk on a separate thread while the current one is encrypted. Helps on spinning disks and network shares.");
+            ui.add_enabled(!settings.direct_io, egui::Checkbox::new(&mut settings.mapped_io, "Memory-Mapped I/O"))
+                .on_hover_text("Map files of 64 MB and more into memory instead of reading and writing them through buffers. Another program truncating a file while it is mapped crashes the application.");
+
+            ui.separator();
+
+            // 超时时间，0表示不限制
+            let file_timeout_label = ui.label("File Timeout: ");
+            ui.add(egui::DragValue::new(&mut settings.file_timeout_secs).range(0..=86_400).suffix(" s"))
+                .labelled_by(file_timeout_label.id)
+                .on_hover_text("Cancel a file that takes longer than this (for example on a hung network mount) and continue with the rest. 0 means no limit.");
+            let batch_timeout_label = ui.label("Batch Timeout: ");
+            ui.add(egui::DragValue::new(&mut settings.batch_timeout_mins).range(0..=10_080).suffix(" min"))
+                .labelled_by(batch_timeout_label.id)
+                .on_hover_text("Cancel all unfinished files when the whole batch takes longer than this. 0 means no limit.");
+
+            ui.separator();
+
+            // Argon2密钥派生开销，按目标解锁时间在本机校准
+            let unlock_label = ui.label("Unlock Time: ");
+            ui.add(
+                egui::DragValue::new(&mut settings.kdf_target_ms)
+                    .range(100..=10_000)
+                    .speed(50)
+                    .suffix(" ms")
+            ).labelled_by(unlock_label.id);
+            if ui.button("Calibrate")
+                .on_hover_text("Benchmark Argon2 on this machine and pick the parameters that take about this long to unlock each file")
+                .clicked()
+            {
+                event = Some(PanelEvent::CalibrateKdf);
+            }
+            // 高级：选择密钥派生算法并直接设置参数，校准结果也在这里显示
+            ui.menu_button(format!("KDF: {}", settings.kdf), |ui| {
+                ui.horizontal(|ui| {
+                    for algorithm in KdfAlgorithm::ALL {
+                        if ui.selectable_label(settings.kdf.algorithm == algorithm, algorithm.to_string()).clicked()
+                            && settings.kdf.algorithm != algorithm
+                        {
+                            settings.kdf = KdfParams::for_algorithm(algorithm);
+                        }
+                    }
+                });
+                egui::Grid::new("kdf_params").num_columns(2).show(ui, |ui| {
+                    match settings.kdf.algorithm {
+                        KdfAlgorithm::Argon2id => {
+                            let mut memory_mib = settings.kdf.memory_kib / 1024;
+                            let memory_label = ui.label("Memory:");
+                            if ui.add(egui::DragValue::new(&mut memory_mib).range(1..=4096).suffix(" MiB"))
+                                .labelled_by(memory_label.id)
+                                .changed()
+                            {
+                                settings.kdf.memory_kib = memory_mib * 1024;
+                            }
+                            ui.end_row();
+                            let iterations_label = ui.label("Iterations:");
+                            ui.add(egui::DragValue::new(&mut settings.kdf.iterations).range(1..=64))
+                                .labelled_by(iterations_label.id);
+                            ui.end_row();
+                        }
+                        KdfAlgorithm::Scrypt => {
+                            // N必须是2的幂，按log2(N)调整
+                            let mut log_n = settings.kdf.memory_kib.max(2).ilog2();
+                            let cost_label = ui.label("Cost (log2 N):");
+                            if ui.add(egui::DragValue::new(&mut log_n).range(10..=22))
+                                .labelled_by(cost_label.id)
+                                .on_hover_text(format!("Uses {} MiB of memory", (1u32 << log_n) / 1024))
+                                .changed()
+                            {
+                                settings.kdf.memory_kib = 1 << log_n;
+                            }
+                            ui.end_row();
+                        }
+                        KdfAlgorithm::Pbkdf2Sha256 => {
+                            let iterations_label = ui.label("Iterations:");
+                            ui.add(egui::DragValue::new(&mut settings.kdf.iterations).range(1_000..=10_000_000).speed(1_000))
+                                .labelled_by(iterations_label.id);
+                            ui.end_row();
+                        }
+                    }
+                    if settings.kdf.algorithm != KdfAlgorithm::Pbkdf2Sha256 {
+                        let parallelism_label = ui.label("Parallelism:");
+                        ui.add(egui::DragValue::new(&mut settings.kdf.parallelism).range(1..=16))
+                            .labelled_by(parallelism_label.id);
+                        ui.end_row();
+                    }
+                });
+                if ui.button("Reset to Defaults").clicked() {
+                    settings.kdf = KdfParams::for_algorithm(settings.kdf.algorithm);
+                }
+            })
+            .response
+            .on_hover_text("Key derivation used for new files. Argon2id is recommended; scrypt and PBKDF2-SHA256 are for environments without Argon2. The choice is stored in each file header, so decrypting works on any machine and after the defaults change.");
+
+            ui.separator();
+
+            // Processing order selection
+            let order_label = ui.label("Order: ");
+            egui::ComboBox::from_id_salt("processing_order")
+                .selected_text(settings.processing_order.to_string())
+                .show_ui(ui, |ui| {
+                    for order in ProcessingOrder::ALL {
+                        ui.selectable_value(&mut settings.processing_order, order, order.to_string());
+                    }
+                })
+                .response
+                .labelled_by(order_label.id);
+
+            let error_label = ui.label("On Error: ");
+            egui::ComboBox::from_id_salt("error_policy")
+                .selected_text(settings.error_policy.to_string())
+                .show_ui(ui, |ui| {
+                    for policy in ErrorPolicy::ALL {
+                        ui.selectable_value(&mut settings.error_policy, policy, policy.to_string());
+                    }
+                })
+                .response
+                .on_hover_text("Stop the batch at the first failing file, or skip it and process the rest. Archives and containers always stop.")
+                .labelled_by(error_label.id);
+            ui.add_enabled(
+                matches!(settings.operation_mode, OperationMode::Encrypt | OperationMode::Decrypt),
+                egui::Checkbox::new(&mut settings.resumable, "Resumable"),
+            ).on_hover_text("Keep a journal of the batch so it can be resumed after a crash or reboot. Files that were being processed start over.");
 
             ui.separator();
 
             // File extension input - fixed width
-            ui.label("File Extension: ");
+            let extension_label = ui.label("File Extension: ");
             ui.add_sized(
                 [100.0, 20.0],
                 egui::TextEdit::singleline(&mut settings.file_extension)
                     .frame(true)
                     .hint_text("enc")
-            );
+            ).labelled_by(extension_label.id);
+
+            // 输出目录，未设置时输出到源文件所在目录
+            let output_text = match &settings.output_directory {
+                Some(path) => format!("📁 {}", path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string())),
+                None => "📁 Next to Source".to_string(),
+            };
+            let output_hover = match &settings.output_directory {
+                Some(path) => format!("Output goes to {}, keeping the subfolder structure", path.display()),
+                None => "Output goes next to each source file. Choose a folder to keep plaintext and ciphertext apart.".to_string(),
+            };
+            if ui.button(output_text).on_hover_text(output_hover).clicked() {
+                event = Some(PanelEvent::SelectOutputDirectory);
+            }
+            if settings.output_directory.is_some() && ui.small_button("✖").on_hover_text("Write output next to the source files").clicked() {
+                settings.output_directory = None;
+            }
 
             ui.separator();
 
             // Checkboxes - left aligned
             ui.checkbox(&mut settings.encrypt_filename, "Encrypt Filename");
             ui.checkbox(&mut settings.delete_source, "Delete Source");
+            ui.add_enabled(
+                settings.delete_source,
+                egui::Checkbox::new(&mut settings.shred_source, "Shred"),
+            ).on_hover_text("Overwrite source files with random data before deleting them. On SSDs, copy-on-write filesystems (btrfs, ZFS, APFS) and with snapshots or backups, old copies can survive.");
+            ui.add_enabled(
+                settings.delete_source && settings.shred_source,
+                egui::DragValue::new(&mut settings.shred_passes).range(1..=MAX_SHRED_PASSES).suffix(" passes"),
+            ).on_hover_text("How many times to overwrite each file; one pass is enough on modern hard drives");
+            ui.add_enabled(
+                settings.operation_mode == OperationMode::Encrypt,
+                egui::Checkbox::new(&mut settings.verify_after_encrypt, "Verify"),
+            ).on_hover_text("Decrypt each encrypted file again and compare it with the source before the source is deleted. Reads every file twice.");
+            ui.add_enabled(
+                settings.operation_mode == OperationMode::Encrypt,
+                egui::Checkbox::new(&mut settings.incremental, "Only New/Modified"),
+            ).on_hover_text("Skip files that have not changed since they were last encrypted");
+            let subfolders = ui.checkbox(&mut settings.recursive, "Subfolders")
+                .on_hover_text("Include files in subfolders and recreate the same folders under the output directory");
+            let depth = ui.add_enabled(
+                settings.recursive,
+                egui::DragValue::new(&mut settings.max_depth).range(0..=64).prefix("Depth: "),
+            ).on_hover_text("How many levels of subfolders to include; 0 means no limit");
+            if subfolders.changed() || depth.changed() {
+                event = Some(PanelEvent::ReloadFiles);
+            }
+
+            ui.separator();
+
+            let conflict_label = ui.label("If Exists: ");
+            egui::ComboBox::from_id_salt("conflict_policy")
+                .selected_text(settings.conflict_policy.to_string())
+                .show_ui(ui, |ui| {
+                    for policy in ConflictPolicy::ALL {
+                        ui.selectable_value(&mut settings.conflict_policy, policy, policy.to_string());
+                    }
+                })
+                .response
+                .on_hover_text("What to do when an output file already exists; you can change it per file before starting")
+                .labelled_by(conflict_label.id);
+
+            // 加密时写出校验和附属文件，解密时总是核对已有的附属文件
+            let checksum_label = ui.label("Checksum: ");
+            ui.add_enabled_ui(settings.operation_mode == OperationMode::Encrypt, |ui| {
+                egui::ComboBox::from_id_salt("checksum_sidecar")
+                    .selected_text(settings.checksum_sidecar.to_string())
+                    .show_ui(ui, |ui| {
+                        for sidecar in ChecksumSidecar::ALL {
+                            ui.selectable_value(&mut settings.checksum_sidecar, sidecar, sidecar.to_string());
+                        }
+                    })
+                    .response
+                    .labelled_by(checksum_label.id)
+            }).inner
+                .on_hover_text("Write a .sha256 or .b3 file next to each encrypted file, checkable with sha256sum -c or b3sum -c")
+                .on_disabled_hover_text("Checksum files next to encrypted files are always verified before decrypting");
+
+            // 加密时写出恢复数据，解密时总是先用已有的恢复文件修复
+            let recovery_label = ui.label("Recovery: ");
+            ui.add_enabled_ui(settings.operation_mode == OperationMode::Encrypt, |ui| {
+                egui::ComboBox::from_id_salt("recovery_level")
+                    .selected_text(settings.recovery_level.to_string())
+                    .show_ui(ui, |ui| {
+                        for level in RecoveryLevel::ALL {
+                            ui.selectable_value(&mut settings.recovery_level, level, level.to_string());
+                        }
+                    })
+                    .response
+                    .labelled_by(recovery_label.id)
+            }).inner
+                .on_hover_text("Write a .rec file with Reed-Solomon parity next to each encrypted file, so a few corrupted or missing blocks can be repaired")
+                .on_disabled_hover_text("Recovery files next to encrypted files are used to repair damaged blocks before decrypting");
+
+            // 加密前压缩，解密时按文件头自动解压
+            let compression_label = ui.label("Compress: ");
+            ui.add_enabled_ui(settings.operation_mode == OperationMode::Encrypt && !settings.encryption_algorithm.is_interop(), |ui| {
+                egui::ComboBox::from_id_salt("compression")
+                    .selected_text(settings.compression.to_string())
+                    .show_ui(ui, |ui| {
+                        for algorithm in CompressionAlgorithm::ALL {
+                            ui.selectable_value(&mut settings.compression, algorithm, algorithm.to_string());
+                        }
+                    })
+                    .response
+                    .labelled_by(compression_label.id)
+            }).inner
+                .on_hover_text("Compress each file before encrypting it. Logs, CSV and other text shrink a lot; images, videos and archives are stored as they are")
+                .on_disabled_hover_text("Compression is chosen when encrypting and is not available for age and OpenSSL files; compressed files are decompressed automatically");
+
+            ui.separator();
+
+            // 批处理密钥：整批只运行一次Argon2
+            let batch_key_label = ui.label("Batch Key: ");
+            ui.add_enabled_ui(
+                settings.operation_mode == OperationMode::Encrypt
+                    && settings.key_source == KeySource::Passphrase
+                    && settings.hardware_token == HardwareToken::None,
+                |ui| {
+                    egui::ComboBox::from_id_salt("batch_key")
+                        .selected_text(settings.batch_key.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in BatchKeyMode::ALL {
+                                ui.selectable_value(&mut settings.batch_key, mode, mode.to_string());
+                            }
+                        })
+                        .response
+                        .labelled_by(batch_key_label.id)
+                        .on_hover_text("Run Argon2 once for the whole batch instead of once per file. Much faster for many small files; every file can still be decrypted on its own. Session Key also stores a random batch key wrapped with your password in each header.");
+                },
+            );
+            let report_checkbox = ui.checkbox(&mut settings.write_report, "JSON Report")
+                .on_hover_text("After each batch, write a JSON report with every file's outcome, size, duration and output SHA-256");
+            if settings.write_report {
+                ui.add_sized(
+                    [160.0, 20.0],
+                    egui::TextEdit::singleline(&mut settings.report_path)
+                        .hint_text("next to outputs")
+                ).labelled_by(report_checkbox.id);
+            }
+
+            ui.separator();
+
+            // Status color palette
+            let colors_label = ui.label("Colors: ");
+            egui::ComboBox::from_id_salt("color_palette")
+                .selected_text(settings.color_palette.to_string())
+                .show_ui(ui, |ui| {
+                    for palette in ColorPalette::ALL {
+                        ui.selectable_value(&mut settings.color_palette, palette, palette.to_string());
+                    }
+                })
+                .response
+                .labelled_by(colors_label.id);
+
+            let layout_label = ui.label("Layout: ");
+            egui::ComboBox::from_id_salt("layout_mode")
+                .selected_text(settings.layout_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in LayoutMode::ALL {
+                        ui.selectable_value(&mut settings.layout_mode, mode, mode.to_string());
+                    }
+                })
+                .response
+                .labelled_by(layout_label.id)
+                .on_hover_text("Touch uses larger buttons, checkboxes and scroll bars for tablets and touchscreens");
         });
+
+        event
+    }
+
+    fn render_kms_fields(ui: &mut egui::Ui, settings: &mut Settings) -> Option<PanelEvent> {
+        let mut event = None;
+        let kms = &mut settings.kms;
+        ui.horizontal(|ui| {
+            ui.set_width(ui.available_width());
+            match settings.key_source {
+                KeySource::AwsKms => {
+                    let key_id_label = ui.label("Key ID: ");
+                    ui.add_sized([220.0, 20.0], egui::TextEdit::singleline(&mut kms.key_id).hint_text("key ID, ARN or alias/name")).labelled_by(key_id_label.id);
+                    let region_label = ui.label("Region: ");
+                    ui.add_sized([100.0, 20.0], egui::TextEdit::singleline(&mut kms.region).hint_text("us-east-1")).labelled_by(region_label.id);
+                    let access_key_label = ui.label("Access Key: ");
+                    ui.add_sized([160.0, 20.0], egui::TextEdit::singleline(&mut kms.access_key_id).hint_text("AWS_ACCESS_KEY_ID")).labelled_by(access_key_label.id);
+                    let secret_label = ui.label("Secret: ");
+                    ui.add_sized([160.0, 20.0], egui::TextEdit::singleline(&mut kms.secret).password(true).hint_text(AWS_SECRET_ENV)).labelled_by(secret_label.id);
+                    let endpoint_label = ui.label("Endpoint: ");
+                    ui.add_sized([200.0, 20.0], egui::TextEdit::singleline(&mut kms.endpoint).hint_text("default for the region")).labelled_by(endpoint_label.id);
+                }
+                KeySource::HashiCorpVault => {
+                    let address_label = ui.label("Address: ");
+                    ui.add_sized([220.0, 20.0], egui::TextEdit::singleline(&mut kms.endpoint).hint_text("https://vault.example.com:8200")).labelled_by(address_label.id);
+                    let mount_label = ui.label("Mount: ");
+                    ui.add_sized([100.0, 20.0], egui::TextEdit::singleline(&mut kms.vault_mount).hint_text("transit")).labelled_by(mount_label.id);
+                    let key_name_label = ui.label("Key Name: ");
+                    ui.add_sized([160.0, 20.0], egui::TextEdit::singleline(&mut kms.key_id)).labelled_by(key_name_label.id);
+                    let token_label = ui.label("Token: ");
+                    ui.add_sized([200.0, 20.0], egui::TextEdit::singleline(&mut kms.secret).password(true).hint_text(VAULT_TOKEN_ENV)).labelled_by(token_label.id);
+                }
+                // 加密时填写接收者的公钥，解密时填写自己的私钥
+                KeySource::SshKeys if settings.operation_mode == OperationMode::Encrypt => {
+                    let recipients_label = ui.label("Recipients: ");
+                    ui.add_sized(
+                        [520.0, 60.0],
+                        egui::TextEdit::multiline(&mut kms.ssh_recipients)
+                            .hint_text("ssh-ed25519 AAAA... alice@example.com\nssh-rsa AAAA... bob@example.com")
+                    ).labelled_by(recipients_label.id).on_hover_text("One ssh-ed25519 or ssh-rsa public key per line. Anyone holding a matching private key can decrypt.");
+                }
+                KeySource::SshKeys => {
+                    let private_key_label = ui.label("Private Key: ");
+                    ui.add_sized([260.0, 20.0], egui::TextEdit::singleline(&mut kms.ssh_identity).hint_text("~/.ssh/id_ed25519")).labelled_by(private_key_label.id);
+                    let passphrase_label = ui.label("Passphrase: ");
+                    ui.add_sized([160.0, 20.0], egui::TextEdit::singleline(&mut kms.secret).password(true).hint_text("if the key has one")).labelled_by(passphrase_label.id);
+                }
+                KeySource::Pkcs11 => {
+                    let module_label = ui.label("Module: ");
+                    ui.add_sized([240.0, 20.0], egui::TextEdit::singleline(&mut kms.pkcs11_module).hint_text("/usr/lib/opensc-pkcs11.so")).labelled_by(module_label.id)
+                        .on_hover_text("PKCS#11 library of the smart card or HSM. Leave empty to use the pkcs11-tool default.");
+                    let slot_label = ui.label("Slot: ");
+                    ui.add_sized([60.0, 20.0], egui::TextEdit::singleline(&mut kms.pkcs11_slot).hint_text("first")).labelled_by(slot_label.id);
+                    let key_label = ui.label("Key Label: ");
+                    ui.add_sized([160.0, 20.0], egui::TextEdit::singleline(&mut kms.key_id)).labelled_by(key_label.id)
+                        .on_hover_text("Label of an RSA key pair on the token. Encrypting only reads its public key; decrypting asks the token to unwrap each file key.");
+                    if settings.operation_mode.reads_encrypted() {
+                        let pin_label = ui.label("PIN: ");
+                        ui.add_sized([120.0, 20.0], egui::TextEdit::singleline(&mut kms.secret).password(true).hint_text(PKCS11_PIN_ENV)).labelled_by(pin_label.id);
+                    }
+                }
+                // 加密时填写接收者的公钥，解密时选择自己的私钥文件
+                KeySource::X25519 if settings.operation_mode == OperationMode::Encrypt => {
+                    let recipients_label = ui.label("Recipients: ");
+                    ui.add_sized(
+                        [520.0, 60.0],
+                        egui::TextEdit::multiline(&mut kms.x25519_recipients)
+                            .hint_text("krypton-x25519:... # alice\nkrypton-x25519:... # bob")
+                    ).labelled_by(recipients_label.id).on_hover_text("One X25519 public key per line. Anyone holding a matching secret key can decrypt, no password is shared.");
+                    ui.checkbox(&mut kms.x25519_password_slot, "Password Slot")
+                        .on_hover_text("Also wrap each file key with the password, so the files can be decrypted with the password alone");
+                    if ui.button("Generate Key Pair…").on_hover_text("Create a new secret key file and add its public key to the recipients").clicked() {
+                        event = Some(PanelEvent::GenerateX25519Key);
+                    }
+                }
+                KeySource::X25519 => {
+                    let secret_key_label = ui.label("Secret Key: ");
+                    ui.add_sized([260.0, 20.0], egui::TextEdit::singleline(&mut kms.x25519_identity).hint_text("path to the secret key file")).labelled_by(secret_key_label.id);
+                    if ui.button("Browse…").clicked() {
+                        event = Some(PanelEvent::SelectX25519Identity);
+                    }
+                    if ui.add_enabled(!kms.x25519_identity.trim().is_empty(), egui::Button::new("Copy Public Key"))
+                        .on_hover_text("Copy the public key of this secret key, to share it with people who encrypt files for you")
+                        .clicked()
+                    {
+                        event = Some(PanelEvent::CopyX25519PublicKey);
+                    }
+                }
+                KeySource::Passphrase => {}
+            }
+        });
+        event
     }
 }
 
@@ -103,12 +635,13 @@ impl FilePanel {
                     egui::Vec2::new(ui.available_width() / 2.0 - 1.0, ui.available_height()),
                     egui::Layout::top_down(egui::Align::LEFT),
                     |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Files to Encrypt");
+                        let heading = ui.horizontal(|ui| {
+                            let heading = ui.label("Files to Encrypt");
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 ui.label(format!("({} files)", file_manager.left_files.len()));
                             });
-                        });
+                            heading.id
+                        }).inner;
                         ui.separator();
                         
                         // Directory selection
@@ -116,7 +649,7 @@ impl FilePanel {
                             let text_edit_response = ui.add(
                                 egui::TextEdit::singleline(&mut file_manager.left_directory)
                                     .frame(true)
-                            );
+                            ).labelled_by(heading);
                             
                             // 当用户完成编辑（失去焦点且内容改变）时自动加载文件
                             if text_edit_response.lost_focus() && !file_manager.left_directory.is_empty() {
@@ -133,6 +666,8 @@ impl FilePanel {
                             }
                         });
                         
+                        Self::shred_warning(ui, settings, &file_manager.left_shred_limitations, !settings.operation_mode.reads_encrypted());
+
                         // File list - occupy remaining height
                         let remaining_height = (ui.available_height() - 10.0).max(400.0); // 确保最小高度
                         ui.group(|ui| {
@@ -145,12 +680,26 @@ impl FilePanel {
                                     ui.set_min_height(remaining_height);
                                     for (index, file) in file_manager.left_files.iter_mut().enumerate() {
                                         ui.horizontal(|ui| {
-                                            ui.checkbox(&mut file.selected, "");
-                                            ui.label(format!("{}. {}", index + 1, &file.name));
+                                            let relative_name = file.relative_name();
+                                            a11y::row_checkbox(ui, &mut file.selected, format!("Select {}", relative_name));
+                                            Self::file_icon(ui, &file.name, settings);
+                                            let previewing = file_manager.preview_index == Some(index);
+                                            let details = &mut file_manager.details;
+                                            if ui.selectable_label(previewing, format!("{}. {}", index + 1, relative_name))
+                                                .on_hover_ui(|ui| Self::file_details(ui, details, file, false, Some("Click to preview")))
+                                                .clicked()
+                                            {
+                                                event = Some(if previewing { PanelEvent::ClosePreview } else { PanelEvent::PreviewFile(index) });
+                                            }
+                                            Self::algorithm_override(ui, ("left_algorithm", index), file);
                                         });
                                     }
                                     // 如果没有文件，显示提示信息
-                                    if file_manager.left_files.is_empty() && !file_manager.left_directory.is_empty() {
+                                    if let Some(error) = &file_manager.left_error {
+                                        if let Some(connect) = Self::directory_error(ui, error) {
+                                            event = Some(connect);
+                                        }
+                                    } else if file_manager.left_files.is_empty() && !file_manager.left_directory.is_empty() {
                                         ui.centered_and_justified(|ui| {
                                             ui.label("No files found in this directory");
                                         });
@@ -172,12 +721,13 @@ impl FilePanel {
                     egui::Vec2::new(ui.available_width(), ui.available_height()),
                     egui::Layout::top_down(egui::Align::LEFT),
                     |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Files to Decrypt");
+                        let heading = ui.horizontal(|ui| {
+                            let heading = ui.label("Files to Decrypt");
                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                                 ui.label(format!("({} files)", file_manager.right_files.len()));
                             });
-                        });
+                            heading.id
+                        }).inner;
                         ui.separator();
                         
                         // Directory selection
@@ -185,7 +735,7 @@ impl FilePanel {
                             let text_edit_response = ui.add(
                                 egui::TextEdit::singleline(&mut file_manager.right_directory)
                                     .frame(true)
-                            );
+                            ).labelled_by(heading);
                             
                             // 当用户完成编辑（失去焦点且内容改变）时自动加载文件
                             if text_edit_response.lost_focus() && !file_manager.right_directory.is_empty() {
@@ -200,8 +750,31 @@ impl FilePanel {
                             if ui.button("Refresh").clicked() && !file_manager.right_directory.is_empty() {
                                 event = Some(PanelEvent::LoadRightFiles);
                             }
+
+                            // 审计目录中的加密文件
+                            if ui.button("Audit").clicked() && !file_manager.right_files.is_empty() {
+                                event = Some(PanelEvent::AuditFiles);
+                            }
+
+                            // 根据加密文件名索引恢复原始文件名
+                            if settings.operation_mode == OperationMode::Decrypt
+                                && ui.button("Restore Names").clicked()
+                                && !file_manager.right_directory.is_empty()
+                            {
+                                event = Some(PanelEvent::RestoreNames);
+                            }
                         });
                         
+                        // 解密和校验时显示所选文件的密码提示
+                        let hint = file_manager.right_files.iter()
+                            .filter(|file| file.selected)
+                            .find_map(|file| file.hint.as_deref());
+                        if let (true, Some(hint)) = (settings.operation_mode.reads_encrypted(), hint) {
+                            ui.label(format!("💡 Password hint: {}", hint));
+                        }
+
+                        Self::shred_warning(ui, settings, &file_manager.right_shred_limitations, settings.operation_mode.reads_encrypted());
+
                         // File list - occupy remaining height
                         let remaining_height = (ui.available_height() - 10.0).max(400.0); // 确保最小高度
                         ui.group(|ui| {
@@ -214,12 +787,36 @@ impl FilePanel {
                                     ui.set_min_height(remaining_height);
                                     for (index, file) in file_manager.right_files.iter_mut().enumerate() {
                                         ui.horizontal(|ui| {
-                                            ui.checkbox(&mut file.selected, "");
-                                            ui.label(format!("{}. {}", index + 1, &file.name));
+                                            let relative_name = file.relative_name();
+                                            a11y::row_checkbox(ui, &mut file.selected, format!("Select {}", relative_name));
+                                            Self::file_icon(ui, &file.name, settings);
+                                            let details = &mut file_manager.details;
+                                            if file.is_multi_volume() {
+                                                ui.label(format!("{}. {}", index + 1, relative_name))
+                                                    .on_hover_ui(|ui| Self::file_details(ui, details, file, true, None));
+                                                ui.weak(format!("[{} volumes]", file.volumes.len()));
+                                            } else {
+                                                let previewing = file_manager.encrypted_preview_index == Some(index);
+                                                if ui.selectable_label(previewing, format!("{}. {}", index + 1, relative_name))
+                                                    .on_hover_ui(|ui| Self::file_details(ui, details, file, true, Some("Click to decrypt a preview in memory")))
+                                                    .clicked()
+                                                {
+                                                    event = Some(if previewing { PanelEvent::ClosePreview } else { PanelEvent::PreviewEncryptedFile(index) });
+                                                }
+                                            }
+                                            if let Some(hint) = &file.hint {
+                                                ui.label("💡").on_hover_text(format!("Password hint: {}", hint));
+                                            }
+                                            Self::integrity_badge(ui, file_manager.integrity.get(&file.path));
+                                            Self::algorithm_override(ui, ("right_algorithm", index), file);
                                         });
                                     }
                                     // 如果没有文件，显示提示信息
-                                    if file_manager.right_files.is_empty() && !file_manager.right_directory.is_empty() {
+                                    if let Some(error) = &file_manager.right_error {
+                                        if let Some(connect) = Self::directory_error(ui, error) {
+                                            event = Some(connect);
+                                        }
+                                    } else if file_manager.right_files.is_empty() && !file_manager.right_directory.is_empty() {
                                         ui.centered_and_justified(|ui| {
                                             ui.label(format!("No .{} files found in this directory", settings.file_extension));
                                         });
@@ -237,17 +834,197 @@ impl FilePanel {
         
         event
     }
+
+    /// 无法打开目录时在文件列表中显示原因，需要登录的网络共享提供连接按钮
+    /// 设置了覆写源文件，而源文件所在的存储会使覆写无效时显示提示
+    fn shred_warning(ui: &mut egui::Ui, settings: &Settings, limitations: &[ShredLimitation], holds_sources: bool) {
+        if !holds_sources || !settings.delete_source || !settings.shred_source || limitations.is_empty() {
+            return;
+        }
+        let reasons = limitations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        status_label(ui, StatusKind::Warning, format!("⚠ Shredding may leave recoverable copies here ({})", reasons))
+            .on_hover_text("These drives write new data to new locations, so the original blocks can survive overwriting. Full-disk encryption protects them.");
+    }
+
+    fn directory_error(ui: &mut egui::Ui, error: &DirectoryError) -> Option<PanelEvent> {
+        let mut event = None;
+        ui.vertical_centered(|ui| {
+            status_label(ui, StatusKind::Error, format!("{} {}", StatusKind::Error.symbol(), error));
+            match error {
+                DirectoryError::CredentialsRequired(share) if network::CAN_CONNECT && ui.button("Connect...").clicked() => {
+                    event = Some(PanelEvent::ConnectShare(share.clone()));
+                }
+                DirectoryError::ShareOffline(_) | DirectoryError::TimedOut(_) => {
+                    ui.weak("Check the network connection or VPN, then press Refresh.");
+                }
+                _ => {}
+            }
+        });
+        event
+    }
+
+    /// 悬停在文件名上时显示的完整路径、大小、修改时间和文件头信息
+    fn file_details(
+        ui: &mut egui::Ui,
+        details: &mut HashMap<PathBuf, FileDetails>,
+        file: &FileItem,
+        encrypted: bool,
+        action: Option<&str>,
+    ) {
+        let details = details.entry(file.path.clone()).or_insert_with(|| FileDetails::load(file, encrypted));
+        ui.label(egui::RichText::new(details.path.display().to_string()).monospace());
+        egui::Grid::new("file_details_grid").num_columns(2).show(ui, |ui| {
+            let size = details.size.map_or_else(
+                || "Unavailable".to_string(),
+                |size| format!("{} ({} bytes)", ProgressFormatter::format_bytes(size), size),
+            );
+            Self::detail_row(ui, "Size:", size);
+            if details.volumes > 0 {
+                Self::detail_row(ui, "Volumes:", details.volumes.to_string());
+            }
+            if let Some(modified) = details.modified {
+                Self::detail_row(ui, "Modified:", chrono::DateTime::<chrono::Local>::from(modified).format("%Y-%m-%d %H:%M:%S").to_string());
+            }
+            match &details.header {
+                HeaderDetails::NotEncrypted => {}
+                HeaderDetails::Legacy => Self::detail_row(ui, "Header:", "None (older format)".to_string()),
+                HeaderDetails::Unreadable(error) => Self::detail_row(ui, "Header:", format!("Unreadable: {}", error)),
+                HeaderDetails::Header(header) => {
+                    let key = header.wrapped_key.as_ref().map_or(KeySource::Passphrase, |key| key.source);
+                    let mut key = key.to_string();
+                    if header.keyfile_check.is_some() {
+                        key.push_str(" + keyfile");
+                    }
+                    if let Some(token) = &header.token_challenge {
+                        key.push_str(&format!(" + YubiKey slot {}", token.slot));
+                    }
+                    Self::detail_row(ui, "Key:", key);
+                    match (&header.cascade, &header.algorithm) {
+                        (Some(cascade), _) => Self::detail_row(ui, "Cascade:", cascade.to_string()),
+                        (None, Some(algorithm)) => Self::detail_row(ui, "Algorithm:", algorithm.to_string()),
+                        (None, None) => {}
+                    }
+                    if let Some(params) = header.kdf_params {
+                        Self::detail_row(ui, "Argon2:", format!("{} MiB, {} passes, {} lanes", params.memory_kib / 1024, params.iterations, params.parallelism));
+                    }
+                    if header.batch_salt.is_some() {
+                        let mode = if header.session_key.is_some() { BatchKeyMode::Session } else { BatchKeyMode::Derived };
+                        Self::detail_row(ui, "Batch Key:", mode.to_string());
+                    }
+                    if let Some(totals) = header.totals {
+                        Self::detail_row(ui, "Original Size:", format!("{} in {} chunks", ProgressFormatter::format_bytes(totals.plaintext_len), totals.chunks));
+                    }
+                    if let Some(compression) = header.compression {
+                        Self::detail_row(ui, "Compression:", format!("{}, {} original", compression.algorithm, ProgressFormatter::format_bytes(compression.plaintext_len)));
+                    }
+                    if !header.hint.is_empty() {
+                        Self::detail_row(ui, "Hint:", header.hint.clone());
+                    }
+                }
+            }
+        });
+        if let Some(action) = action {
+            ui.weak(action);
+        }
+    }
+
+    fn detail_row(ui: &mut egui::Ui, label: &str, value: String) {
+        ui.strong(label);
+        ui.label(value);
+        ui.end_row();
+    }
+
+    /// 文件名前的类型图标
+    fn file_icon(ui: &mut egui::Ui, name: &str, settings: &Settings) {
+        let kind = FileKind::from_name(name, &settings.file_extension);
+        ui.label(kind.icon()).on_hover_text(kind.label());
+    }
+
+    /// 文件旁的快速完整性检查标记，检查尚未完成时不显示
+    fn integrity_badge(ui: &mut egui::Ui, badge: Option<&IntegrityBadge>) {
+        let Some(badge) = badge else {
+            return;
+        };
+        let (kind, hover) = match badge {
+            IntegrityBadge::Valid => (StatusKind::Success, "The header and chunk layout look intact"),
+            IntegrityBadge::UnknownFormat => (StatusKind::Warning, "This does not look like a Krypton file"),
+            IntegrityBadge::Truncated => (StatusKind::Error, "The file is shorter than its header records; the end is missing"),
+        };
+        status_label(ui, kind, format!("{} {}", kind.symbol(), badge.label())).on_hover_text(hover);
+    }
+
+    /// 文件行右侧的算法下拉框，用于为单个文件指定不同于全局设置的算法
+    fn algorithm_override(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, file: &mut FileItem) {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            let selected_text = match &file.algorithm {
+                Some(algorithm) => algorithm.to_string(),
+                None => "Default".to_string(),
+            };
+            egui::ComboBox::from_id_salt(id_salt)
+                .width(100.0)
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut file.algorithm, None, "Default");
+                    for algorithm in registry::available_algorithms() {
+                        let label = algorithm.to_string();
+                        ui.selectable_value(&mut file.algorithm, Some(algorithm), label);
+                    }
+                })
+                .response
+                .on_hover_text("Algorithm used for this file")
+                .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::ComboBox, true, format!("Algorithm for {}", file.name)));
+        });
+    }
+}
+
+/// 批处理标签页栏，每个标签页是一个可以独立运行的批处理
+pub struct OperationTabs;
+
+impl OperationTabs {
+    pub fn render(
+        ui: &mut egui::Ui,
+        operations: &[OperationView],
+        active: &mut usize,
+    ) -> Option<PanelEvent> {
+        let mut event = None;
+        ui.horizontal(|ui| {
+            for (index, operation) in operations.iter().enumerate() {
+                ui.selectable_value(active, index, operation.tab_label());
+                if operations.len() > 1 && !operation.is_running() {
+                    let close = ui.small_button("×").on_hover_text("Close this batch tab");
+                    a11y::set_name(&close, egui::WidgetType::Button, &format!("Close {}", operation.tab_label()));
+                    if close.clicked() {
+                        event = Some(PanelEvent::CloseOperationTab(index));
+                    }
+                }
+            }
+            let new_tab = ui.button("+").on_hover_text("Open another batch tab to run alongside the others");
+            a11y::set_name(&new_tab, egui::WidgetType::Button, "New batch tab");
+            if new_tab.clicked() {
+                event = Some(PanelEvent::NewOperationTab);
+            }
+        });
+        event
+    }
 }
 
 pub struct ProgressPanel;
 
 impl ProgressPanel {
+    /// `mini_window` 切换置顶的迷你进度窗口
     pub fn render(
         ui: &mut egui::Ui,
         progress: &ProgressState,
+        mini_window: &mut bool,
     ) {
         ui.group(|ui| {
-            ui.label("Progress");
+            ui.horizontal(|ui| {
+                ui.label("Progress");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.toggle_value(mini_window, "Mini Window")
+                        .on_hover_text("Show the overall progress in a small window that stays on top of other applications");
+                });
+            });
             ui.separator();
 
             // Current file information
@@ -260,6 +1037,17 @@ impl ProgressPanel {
             ui.horizontal(|ui| {
                 ui.label("Files: ");
                 ui.label(format!("{}/{}", progress.current_file_index + 1, progress.total_files));
+
+                if progress.completed_files + progress.skipped_files + progress.failed_files.len() > 0 {
+                    ui.separator();
+                    status_chip(ui, StatusKind::Success, format!("Done: {}", progress.completed_files));
+                    if progress.skipped_files > 0 {
+                        status_chip(ui, StatusKind::Warning, format!("Skipped: {}", progress.skipped_files));
+                    }
+                    if !progress.failed_files.is_empty() {
+                        status_chip(ui, StatusKind::Error, format!("Failed: {}", progress.failed_files.len()));
+                    }
+                }
             });
 
             // Overall progress with percentage
@@ -285,6 +1073,10 @@ impl ProgressPanel {
                     ui.separator();
                     ui.label("Remaining: ");
                     ui.label(ProgressFormatter::format_time(progress.estimated_remaining));
+                    ui.label(format!(
+                        "(finishes {})",
+                        ProgressFormatter::format_finish_time(chrono::Local::now(), progress.estimated_remaining)
+                    ));
                 }
                 
                 ui.separator();
@@ -296,18 +1088,115 @@ impl ProgressPanel {
                     ProgressFormatter::format_bytes(progress.total_bytes)
                 ));
             });
+
+            Self::results(ui, &progress.results);
         });
     }
 
+    /// 已结束的每个文件的结果，失败的文件显示错误
+    fn results(ui: &mut egui::Ui, results: &[FileResult]) {
+        if results.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(format!("Results ({} files)", results.len()))
+            .id_salt("progress_results")
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_salt("progress_results_scroll")
+                    .max_height(160.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("progress_results_grid").striped(true).num_columns(3).show(ui, |ui| {
+                            for result in results {
+                                match &result.outcome {
+                                    FileOutcome::Completed { output } => {
+                                        status_chip(ui, StatusKind::Success, "Done");
+                                        ui.label(&result.name);
+                                        ui.weak(output.display().to_string());
+                                    }
+                                    FileOutcome::Skipped => {
+                                        status_chip(ui, StatusKind::Warning, "Skipped");
+                                        ui.label(&result.name);
+                                        ui.label("");
+                                    }
+                                    FileOutcome::Failed { error } => {
+                                        status_chip(ui, StatusKind::Error, "Failed");
+                                        ui.label(&result.name);
+                                        status_label(ui, StatusKind::Error, error);
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+            });
+    }
+
 
 }
 
+/// 置顶的迷你进度窗口，只显示总进度、速度和取消按钮
+pub struct MiniProgressWindow;
+
+impl MiniProgressWindow {
+    /// 用户关闭窗口时 `show` 置为false，点击Cancel时返回 `PanelEvent::StopOperation`
+    pub fn render(
+        ctx: &egui::Context,
+        progress: &ProgressState,
+        app_state: &AppState,
+        show: &mut bool,
+    ) -> Option<PanelEvent> {
+        let mut event = None;
+        let viewport = egui::ViewportBuilder::default()
+            .with_title("Krypton Progress")
+            .with_inner_size([340.0, 80.0])
+            .with_resizable(false)
+            .with_always_on_top();
+
+        ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("mini_progress"), viewport, |ctx, class| {
+            let contents = |ui: &mut egui::Ui| {
+                ui.add(egui::ProgressBar::new(progress.total_progress).show_percentage());
+                ui.horizontal(|ui| {
+                    ui.label(ProgressFormatter::format_speed(progress.speed_mbps));
+                    if progress.estimated_remaining > 0.0 && progress.total_progress < 1.0 {
+                        ui.separator();
+                        ui.label(format!("{} left", ProgressFormatter::format_time(progress.estimated_remaining)));
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.add_enabled(*app_state == AppState::Running, egui::Button::new("Cancel")).clicked() {
+                            event = Some(PanelEvent::StopOperation);
+                        }
+                    });
+                });
+            };
+
+            if class == egui::ViewportClass::Embedded {
+                // 后端不支持多个原生窗口时，退化为主窗口内的浮动窗口
+                egui::Window::new("Progress")
+                    .id(egui::Id::new("mini_progress_window"))
+                    .open(show)
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(ctx, contents);
+            } else {
+                egui::CentralPanel::default().show(ctx, contents);
+                if ctx.input(|input| input.viewport().close_requested()) {
+                    *show = false;
+                }
+            }
+        });
+
+        event
+    }
+}
+
 pub struct ControlPanel;
 
 impl ControlPanel {
+    /// `start_blockers` 非空时禁用Start按钮并显示原因
     pub fn render(
         ui: &mut egui::Ui,
         app_state: &AppState,
+        start_blockers: &[String],
     ) -> Option<PanelEvent> {
         let mut event = None;
         ui.horizontal(|ui| {
@@ -315,12 +1204,29 @@ impl ControlPanel {
                 std::process::exit(0);
             }
 
+            ui.separator();
+
+            // 保存/加载批处理任务定义
+            let idle = *app_state == AppState::Idle;
+            if ui.add_enabled(idle, egui::Button::new("Save Job...")).clicked() {
+                event = Some(PanelEvent::SaveJob);
+            }
+            if ui.add_enabled(idle, egui::Button::new("Load Job...")).clicked() {
+                event = Some(PanelEvent::LoadJob);
+            }
+
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 match app_state {
                     AppState::Idle => {
-                        if ui.button("Start").clicked() {
+                        if ui.add_enabled(start_blockers.is_empty(), egui::Button::new("Start")).clicked() {
                             event = Some(PanelEvent::StartOperation);
                         }
+                        if ui.button("Dry Run").on_hover_text("Preview outputs, conflicts and disk space without writing anything").clicked() {
+                            event = Some(PanelEvent::DryRun);
+                        }
+                        if !start_blockers.is_empty() {
+                            status_label(ui, StatusKind::Warning, format!("{} {}", StatusKind::Warning.symbol(), start_blockers.join(" · ")));
+                        }
                     }
                     AppState::Running => {
                         if ui.button("Stop").clicked() {
@@ -0,0 +1,76 @@
+use eframe::egui;
+use crate::core::preview::PreviewContent;
+use crate::models::PreviewState;
+use crate::progress::ProgressFormatter;
+use super::PanelEvent;
+use super::theme::{status_label, StatusKind};
+
+pub struct PreviewPanel;
+
+impl PreviewPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        state: &mut PreviewState,
+    ) -> Option<PanelEvent> {
+        let mut event = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Preview");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Close").clicked() {
+                    event = Some(PanelEvent::ClosePreview);
+                }
+            });
+        });
+        ui.separator();
+
+        if let Some(error) = &state.error {
+            status_label(ui, StatusKind::Error, error);
+            return event;
+        }
+        let Some(preview) = &state.preview else {
+            return event;
+        };
+
+        let name = preview.path.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        ui.strong(name);
+        let kind = match &preview.content {
+            PreviewContent::Text { encoding, .. } => format!("Text ({})", encoding),
+            PreviewContent::Hex(_) => "Binary".to_string(),
+            PreviewContent::Image { width, height, .. } => format!("Image ({}x{} preview)", width, height),
+        };
+        ui.label(format!("{} · {}", kind, ProgressFormatter::format_bytes(preview.size)));
+        if state.decrypted {
+            ui.weak("🔓 Decrypted in memory, no plaintext copy is written to disk");
+        }
+        if preview.truncated {
+            ui.weak("Showing the beginning of the file only");
+        }
+        ui.separator();
+
+        egui::ScrollArea::both()
+            .id_salt("preview_scroll")
+            .auto_shrink([false, false])
+            .show(ui, |ui| match &preview.content {
+                PreviewContent::Text { text, .. } => {
+                    ui.add(egui::Label::new(egui::RichText::new(text).monospace()).wrap());
+                }
+                PreviewContent::Hex(dump) => {
+                    ui.add(egui::Label::new(egui::RichText::new(dump).monospace()).extend());
+                }
+                PreviewContent::Image { width, height, rgba } => {
+                    let texture = state.texture.get_or_insert_with(|| {
+                        let image = egui::ColorImage::from_rgba_unmultiplied([*width as usize, *height as usize], rgba);
+                        ui.ctx().load_texture("file_preview", image, egui::TextureOptions::LINEAR)
+                    });
+                    let size = texture.size_vec2();
+                    let scale = (ui.available_width() / size.x).min(1.0);
+                    ui.image((texture.id(), size * scale));
+                }
+            });
+
+        event
+    }
+}
@@ -0,0 +1,181 @@
+use eframe::egui;
+use super::a11y;
+use super::theme::{status_label, StatusKind};
+use crate::core::scheduler::Recurrence;
+use crate::models::SchedulerState;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleEvent {
+    BrowseJob,
+    AddJob,
+    RemoveJob(usize),
+    RunNow(usize),
+    /// 计划任务列表被修改（启用状态等），需要保存
+    Changed,
+}
+
+pub struct SchedulePanel;
+
+impl SchedulePanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        scheduler: &mut SchedulerState,
+    ) -> Option<ScheduleEvent> {
+        let mut event = None;
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.label("Scheduled Jobs");
+            ui.separator();
+
+            // Add job form
+            ui.horizontal(|ui| {
+                let job_file_label = ui.label("Job File: ");
+                ui.add_sized(
+                    [360.0, 20.0],
+                    egui::TextEdit::singleline(&mut scheduler.new_job_path)
+                        .hint_text("path/to/job.kjob")
+                        .frame(true)
+                ).labelled_by(job_file_label.id);
+                if ui.button("Browse").clicked() {
+                    event = Some(ScheduleEvent::BrowseJob);
+                }
+            });
+            ui.horizontal(|ui| {
+                let repeat_label = ui.label("Repeat: ");
+                Self::recurrence_editor(ui, &mut scheduler.new_recurrence, repeat_label.id);
+                if ui.button("Add").clicked() && !scheduler.new_job_path.is_empty() {
+                    event = Some(ScheduleEvent::AddJob);
+                }
+            });
+            ui.separator();
+
+            // Job list
+            if scheduler.schedule.jobs.is_empty() {
+                ui.label("No scheduled jobs. Save a job from the Files tab, then add it here.");
+            } else {
+                egui::Grid::new("schedule_grid")
+                    .striped(true)
+                    .num_columns(6)
+                    .show(ui, |ui| {
+                        ui.strong("Enabled");
+                        ui.strong("Job");
+                        ui.strong("Repeat");
+                        ui.strong("Last Run");
+                        ui.strong("Next Run");
+                        ui.strong("");
+                        ui.end_row();
+
+                        for (index, job) in scheduler.schedule.jobs.iter_mut().enumerate() {
+                            let name = format!("Enable {}", job.display_name());
+                            if a11y::row_checkbox(ui, &mut job.enabled, name).changed() {
+                                event = Some(ScheduleEvent::Changed);
+                            }
+                            ui.label(job.display_name()).on_hover_text(job.job_path.display().to_string());
+                            ui.label(job.recurrence.to_string());
+                            ui.label(match job.last_run {
+                                Some(timestamp) => format_timestamp(timestamp),
+                                None => "Never".to_string(),
+                            });
+                            ui.label(if job.enabled {
+                                job.next_run().format("%Y-%m-%d %H:%M").to_string()
+                            } else {
+                                "-".to_string()
+                            });
+                            ui.horizontal(|ui| {
+                                let running = scheduler.running_job == Some(index);
+                                if ui.add_enabled(!running, egui::Button::new("Run Now")).clicked() {
+                                    event = Some(ScheduleEvent::RunNow(index));
+                                }
+                                if ui.add_enabled(!running, egui::Button::new("Remove")).clicked() {
+                                    event = Some(ScheduleEvent::RemoveJob(index));
+                                }
+                                if scheduler.session_passwords.contains_key(&job.job_path) {
+                                    ui.weak("password remembered");
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            ui.separator();
+            ui.label("Jobs run automatically while Krypton is open. Passwords are only kept in memory for the current session.");
+
+            // Status line
+            if !scheduler.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(scheduler.status_is_error), &scheduler.status_message);
+            }
+        });
+
+        event
+    }
+
+    /// 重复周期编辑器，`label` 为周期类型下拉框的标签
+    fn recurrence_editor(ui: &mut egui::Ui, recurrence: &mut Recurrence, label: egui::Id) {
+        const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+        let kind = match recurrence {
+            Recurrence::Interval { .. } => "Interval",
+            Recurrence::Daily { .. } => "Daily",
+            Recurrence::Weekly { .. } => "Weekly",
+        };
+        egui::ComboBox::from_id_salt("recurrence_kind")
+            .selected_text(kind)
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(kind == "Interval", "Interval").clicked() {
+                    *recurrence = Recurrence::Interval { minutes: 60 };
+                }
+                if ui.selectable_label(kind == "Daily", "Daily").clicked() {
+                    *recurrence = Recurrence::Daily { hour: 2, minute: 0 };
+                }
+                if ui.selectable_label(kind == "Weekly", "Weekly").clicked() {
+                    *recurrence = Recurrence::Weekly { weekday: 0, hour: 2, minute: 0 };
+                }
+            })
+            .response
+            .labelled_by(label);
+
+        match recurrence {
+            Recurrence::Interval { minutes } => {
+                let every = ui.label("every");
+                ui.add(egui::DragValue::new(minutes).range(1..=10080).suffix(" min")).labelled_by(every.id);
+            }
+            Recurrence::Daily { hour, minute } => {
+                ui.label("at");
+                Self::time_editor(ui, hour, minute);
+            }
+            Recurrence::Weekly { weekday, hour, minute } => {
+                let weekday_response = egui::ComboBox::from_id_salt("recurrence_weekday")
+                    .selected_text(WEEKDAYS[(*weekday).min(6) as usize])
+                    .show_ui(ui, |ui| {
+                        for (index, name) in WEEKDAYS.iter().enumerate() {
+                            ui.selectable_value(weekday, index as u32, *name);
+                        }
+                    })
+                    .response;
+                a11y::set_name(&weekday_response, egui::WidgetType::ComboBox, "Weekday");
+                ui.label("at");
+                Self::time_editor(ui, hour, minute);
+            }
+        }
+    }
+
+    /// 小时和分钟输入框
+    fn time_editor(ui: &mut egui::Ui, hour: &mut u32, minute: &mut u32) {
+        let hour = ui.add(egui::DragValue::new(hour).range(0..=23));
+        a11y::set_name(&hour, egui::WidgetType::DragValue, "Hour");
+        ui.label(":");
+        let minute = ui.add(egui::DragValue::new(minute).range(0..=59));
+        a11y::set_name(&minute, egui::WidgetType::DragValue, "Minute");
+    }
+}
+
+/// 格式化Unix时间戳为本地时间
+fn format_timestamp(timestamp: i64) -> String {
+    use chrono::TimeZone;
+    chrono::Local.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
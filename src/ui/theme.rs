@@ -0,0 +1,104 @@
+use eframe::egui;
+use crate::models::{ColorPalette, LayoutMode};
+
+/// 状态的类别，决定显示颜色和符号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusKind {
+    Success,
+    Warning,
+    Error,
+}
+
+impl StatusKind {
+    /// 状态符号，使颜色不是区分状态的唯一依据
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            StatusKind::Success => "✔",
+            StatusKind::Warning => "⚠",
+            StatusKind::Error => "✖",
+        }
+    }
+
+    /// 根据是否出错选择状态类别（用于各页的状态提示行）
+    pub fn from_error(is_error: bool) -> Self {
+        if is_error { StatusKind::Error } else { StatusKind::Success }
+    }
+}
+
+/// 保存当前调色板的上下文数据ID
+fn palette_id() -> egui::Id {
+    egui::Id::new("krypton_color_palette")
+}
+
+/// 设置本帧使用的调色板（由应用在每帧开始时调用）
+pub fn set_palette(ctx: &egui::Context, palette: ColorPalette) {
+    ctx.data_mut(|data| data.insert_temp(palette_id(), palette));
+}
+
+/// 保存当前布局的上下文数据ID
+fn layout_id() -> egui::Id {
+    egui::Id::new("krypton_layout_mode")
+}
+
+/// 切换控件尺寸（由应用在每帧开始时调用，只在布局改变时修改样式）
+pub fn set_layout(ctx: &egui::Context, mode: LayoutMode) {
+    if ctx.data(|data| data.get_temp::<LayoutMode>(layout_id())) == Some(mode) {
+        return;
+    }
+    ctx.data_mut(|data| data.insert_temp(layout_id(), mode));
+    ctx.all_styles_mut(|style| {
+        style.spacing = egui::style::Spacing::default();
+        if mode == LayoutMode::Touch {
+            // 按钮和输入框至少约40像素高，便于手指点中
+            let spacing = &mut style.spacing;
+            spacing.interact_size = egui::vec2(56.0, 40.0);
+            spacing.button_padding = egui::vec2(12.0, 8.0);
+            spacing.item_spacing = egui::vec2(12.0, 10.0);
+            spacing.icon_width = 28.0;
+            spacing.icon_width_inner = 16.0;
+            spacing.icon_spacing = 8.0;
+            spacing.combo_height = 400.0;
+            spacing.slider_rail_height = 12.0;
+            // 滚动条始终显示且足够宽；列表也可以直接用手指拖动滚动
+            spacing.scroll = egui::style::ScrollStyle::solid();
+            spacing.scroll.bar_width = 20.0;
+        }
+    });
+}
+
+fn palette(ctx: &egui::Context) -> ColorPalette {
+    ctx.data(|data| data.get_temp(palette_id())).unwrap_or_default()
+}
+
+/// 状态对应的颜色
+pub fn status_color(ctx: &egui::Context, kind: StatusKind) -> egui::Color32 {
+    match (palette(ctx), kind) {
+        (ColorPalette::Standard, StatusKind::Success) => egui::Color32::from_rgb(0x3C, 0xB3, 0x4A),
+        (ColorPalette::Standard, StatusKind::Warning) => egui::Color32::from_rgb(0xE0, 0xA8, 0x00),
+        (ColorPalette::Standard, StatusKind::Error) => egui::Color32::from_rgb(0xE0, 0x3E, 0x3E),
+        // Okabe-Ito 调色板：蓝/橙/朱红在常见色觉缺陷下仍可区分
+        (ColorPalette::ColorBlind, StatusKind::Success) => egui::Color32::from_rgb(0x00, 0x72, 0xB2),
+        (ColorPalette::ColorBlind, StatusKind::Warning) => egui::Color32::from_rgb(0xE6, 0x9F, 0x00),
+        (ColorPalette::ColorBlind, StatusKind::Error) => egui::Color32::from_rgb(0xD5, 0x5E, 0x00),
+    }
+}
+
+/// 带状态颜色的文本
+pub fn status_label(ui: &mut egui::Ui, kind: StatusKind, text: impl Into<String>) -> egui::Response {
+    let color = status_color(ui.ctx(), kind);
+    ui.colored_label(color, text.into())
+}
+
+/// 带符号和背景色的状态标签，用于列表和计数
+pub fn status_chip(ui: &mut egui::Ui, kind: StatusKind, text: impl Into<String>) -> egui::Response {
+    let color = status_color(ui.ctx(), kind);
+    egui::Frame::none()
+        .fill(color.gamma_multiply(0.2))
+        .stroke(egui::Stroke::new(1.0, color))
+        .rounding(8.0)
+        .inner_margin(egui::Margin::symmetric(6.0, 1.0))
+        .show(ui, |ui| {
+            ui.colored_label(color, format!("{} {}", kind.symbol(), text.into()));
+        })
+        .response
+}
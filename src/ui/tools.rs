@@ -0,0 +1,343 @@
+use eframe::egui;
+use super::a11y;
+use super::theme::{status_label, StatusKind};
+use crate::core::api::API_PREFIX;
+use crate::models::{ApiState, ConfigLockState, MigrationState, Settings, ToolsState};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolsEvent {
+    EncryptText,
+    EncryptTextToFile,
+    EncryptTextToParts,
+    SaveParts,
+    LoadParts,
+    DecryptText,
+    PasteFromClipboard,
+    CopyOutput,
+    UseOutputAsInput,
+    Clear,
+    BrowseMigrationDirectory,
+    ScanForMigration,
+    StartMigration,
+    CancelMigration,
+    RollBackMigration,
+    DiscardMigrationBackups,
+    EncryptConfig,
+    DecryptConfig,
+    UnlockConfig,
+    ToggleApi,
+    CopyApiToken,
+    RegenerateApiToken,
+}
+
+pub struct ToolsPanel;
+
+impl ToolsPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        tools: &mut ToolsState,
+        migration: &mut MigrationState,
+        config_lock: &mut ConfigLockState,
+        api: &mut ApiState,
+        settings: &Settings,
+    ) -> Option<ToolsEvent> {
+        let mut event = None;
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label("Text Encryption");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(format!("Algorithm: {}", settings.encryption_algorithm));
+                });
+            });
+            ui.separator();
+
+            // Input area
+            let input_label = ui.horizontal(|ui| {
+                let input_label = ui.label("Input (plain text or armored message):");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Clear").clicked() {
+                        event = Some(ToolsEvent::Clear);
+                    }
+                    if ui.button("Paste").clicked() {
+                        event = Some(ToolsEvent::PasteFromClipboard);
+                    }
+                    if ui.button("Load Parts...").on_hover_text("Load saved message parts from text files").clicked() {
+                        event = Some(ToolsEvent::LoadParts);
+                    }
+                });
+                input_label.id
+            }).inner;
+            egui::ScrollArea::vertical()
+                .id_salt("tools_input_scroll")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    ui.add_sized(
+                        [ui.available_width(), 200.0],
+                        egui::TextEdit::multiline(&mut tools.input_text)
+                            .hint_text("Paste a snippet to encrypt, or an armored message to decrypt")
+                    ).labelled_by(input_label);
+                });
+
+            // Actions
+            ui.horizontal(|ui| {
+                if ui.button("Encrypt to Text").clicked() {
+                    event = Some(ToolsEvent::EncryptText);
+                }
+                if ui.button("Encrypt to File...").clicked() {
+                    event = Some(ToolsEvent::EncryptTextToFile);
+                }
+                if ui.button("Encrypt to Parts")
+                    .on_hover_text("Split the armored message into numbered blocks for pasting into email or chat")
+                    .clicked()
+                {
+                    event = Some(ToolsEvent::EncryptTextToParts);
+                }
+                let part_size = ui.add(
+                    egui::DragValue::new(&mut tools.part_size_kb)
+                        .range(1..=10_240)
+                        .suffix(" KB per part")
+                );
+                a11y::set_name(&part_size, egui::WidgetType::DragValue, "Part size in KB");
+
+                ui.separator();
+
+                if ui.button("Decrypt Text").clicked() {
+                    event = Some(ToolsEvent::DecryptText);
+                }
+            });
+
+            ui.separator();
+
+            // Output area
+            let output_label = ui.horizontal(|ui| {
+                let output_label = ui.label("Output:");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Use as Input").clicked() {
+                        event = Some(ToolsEvent::UseOutputAsInput);
+                    }
+                    if !tools.output_parts.is_empty() && ui.button(format!("Save {} Parts...", tools.output_parts.len())).clicked() {
+                        event = Some(ToolsEvent::SaveParts);
+                    }
+                    if ui.button("Copy").clicked() {
+                        event = Some(ToolsEvent::CopyOutput);
+                    }
+                });
+                output_label.id
+            }).inner;
+            egui::ScrollArea::vertical()
+                .id_salt("tools_output_scroll")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    ui.add_sized(
+                        [ui.available_width(), 200.0],
+                        egui::TextEdit::multiline(&mut tools.output_text)
+                            .interactive(true)
+                            .font(egui::TextStyle::Monospace)
+                    ).labelled_by(output_label);
+                });
+
+            // Status line
+            if !tools.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(tools.status_is_error), &tools.status_message);
+            }
+        });
+
+        if let Some(migration_event) = Self::render_migration(ui, migration, settings) {
+            event = Some(migration_event);
+        }
+        if let Some(config_event) = Self::render_config_lock(ui, config_lock) {
+            event = Some(config_event);
+        }
+        if let Some(api_event) = Self::render_api(ui, api) {
+            event = Some(api_event);
+        }
+
+        event
+    }
+
+    /// 旧格式文件迁移
+    fn render_migration(
+        ui: &mut egui::Ui,
+        migration: &mut MigrationState,
+        settings: &Settings,
+    ) -> Option<ToolsEvent> {
+        let mut event = None;
+        let running = migration.is_running();
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label("Format Migration");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(format!("Algorithm: {}", settings.encryption_algorithm));
+                });
+            });
+            ui.label(format!(
+                "Re-encrypt older .{} files into the current format with the same password. The originals are kept until you discard them.",
+                settings.file_extension
+            ));
+            ui.separator();
+
+            ui.add_enabled_ui(!running, |ui| {
+                ui.horizontal(|ui| {
+                    let folder_label = ui.label("Folder:");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut migration.directory)
+                            .hint_text("Folder to scan, including subfolders")
+                            .frame(true)
+                    ).labelled_by(folder_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(ToolsEvent::BrowseMigrationDirectory);
+                    }
+                    if ui.button("Scan").clicked() {
+                        event = Some(ToolsEvent::ScanForMigration);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!migration.files.is_empty(), egui::Button::new(format!("Migrate {} Files", migration.files.len())))
+                        .on_hover_text("Uses the password from the settings")
+                        .clicked()
+                    {
+                        event = Some(ToolsEvent::StartMigration);
+                    }
+                    if !migration.migrated.is_empty() {
+                        ui.separator();
+                        if ui.button(format!("Roll Back {} Files", migration.migrated.len()))
+                            .on_hover_text("Put the original files back")
+                            .clicked()
+                        {
+                            event = Some(ToolsEvent::RollBackMigration);
+                        }
+                        if ui.button("Discard Backups").on_hover_text("Delete the original files kept after migration").clicked() {
+                            event = Some(ToolsEvent::DiscardMigrationBackups);
+                        }
+                    }
+                });
+            });
+
+            if running {
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        event = Some(ToolsEvent::CancelMigration);
+                    }
+                    ui.spinner();
+                    ui.label(format!("{} / {} {}", migration.done, migration.total, migration.current_file));
+                });
+                let fraction = if migration.total == 0 { 0.0 } else { migration.done as f32 / migration.total as f32 };
+                ui.add(egui::ProgressBar::new(fraction).show_percentage());
+            }
+
+            if !migration.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(migration.status_is_error), &migration.status_message);
+            }
+            if !migration.problems.is_empty() {
+                egui::ScrollArea::vertical()
+                    .id_salt("migration_problems_scroll")
+                    .max_height(120.0)
+                    .show(ui, |ui| {
+                        for problem in &migration.problems {
+                            status_label(ui, StatusKind::Error, problem);
+                        }
+                    });
+            }
+        });
+
+        event
+    }
+
+    /// 用主密码加密应用配置文件（设置记录、计划任务、增量状态等）
+    fn render_config_lock(ui: &mut egui::Ui, config_lock: &mut ConfigLockState) -> Option<ToolsEvent> {
+        let mut event = None;
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.label("Configuration Encryption");
+            ui.label("Saved settings, schedules and history can contain folder names, keyfile paths and remote endpoints. Encrypt them with a master password that is asked for on startup.");
+            ui.separator();
+
+            if config_lock.locked {
+                ui.horizontal(|ui| {
+                    status_label(ui, StatusKind::Warning, "Locked: the saved configuration is not loaded and changes are not saved");
+                    if ui.button("Unlock...").clicked() {
+                        event = Some(ToolsEvent::UnlockConfig);
+                    }
+                });
+            } else {
+                ui.label(if config_lock.encrypted { "Status: encrypted" } else { "Status: not encrypted" });
+                egui::Grid::new("config_lock_grid").num_columns(2).show(ui, |ui| {
+                    let password_label = ui.label("New Master Password:");
+                    ui.add(egui::TextEdit::singleline(&mut config_lock.new_password).password(true)).labelled_by(password_label.id);
+                    ui.end_row();
+                    let confirm_label = ui.label("Confirm:");
+                    ui.add(egui::TextEdit::singleline(&mut config_lock.confirm_password).password(true)).labelled_by(confirm_label.id);
+                    ui.end_row();
+                });
+                ui.horizontal(|ui| {
+                    let label = if config_lock.encrypted { "Change Password" } else { "Encrypt Configuration" };
+                    if ui.add_enabled(!config_lock.new_password.is_empty(), egui::Button::new(label)).clicked() {
+                        event = Some(ToolsEvent::EncryptConfig);
+                    }
+                    if config_lock.encrypted && ui.button("Remove Encryption")
+                        .on_hover_text("Store the configuration files as plain text again")
+                        .clicked()
+                    {
+                        event = Some(ToolsEvent::DecryptConfig);
+                    }
+                });
+            }
+
+            if !config_lock.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(config_lock.status_is_error), &config_lock.status_message);
+            }
+        });
+
+        event
+    }
+
+    /// 本机REST控制接口
+    fn render_api(ui: &mut egui::Ui, api: &mut ApiState) -> Option<ToolsEvent> {
+        let mut event = None;
+        let running = api.server.is_some();
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.label("Control API");
+            ui.label("Let scripts and other applications on this computer submit jobs, follow their progress and cancel them over HTTP. Only local connections with the token are accepted.");
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut api.config.enabled, "Enable").changed() {
+                    event = Some(ToolsEvent::ToggleApi);
+                }
+                let port_label = ui.label("Port:");
+                ui.add_enabled(!running, egui::DragValue::new(&mut api.config.port).range(1024..=65535))
+                    .labelled_by(port_label.id)
+                    .on_disabled_hover_text("Disable the API to change the port");
+            });
+            if !api.config.token.is_empty() {
+                ui.horizontal(|ui| {
+                    let token_label = ui.label("Token:");
+                    ui.label(egui::RichText::new(&api.config.token).monospace()).labelled_by(token_label.id);
+                    if ui.button("Copy").clicked() {
+                        event = Some(ToolsEvent::CopyApiToken);
+                    }
+                    if ui.button("New Token").on_hover_text("Scripts using the old token stop working").clicked() {
+                        event = Some(ToolsEvent::RegenerateApiToken);
+                    }
+                });
+            }
+            if let Some(server) = &api.server {
+                ui.label(format!("Listening on http://127.0.0.1:{}{}/jobs", server.port(), API_PREFIX));
+            }
+
+            if !api.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(api.status_is_error), &api.status_message);
+            }
+        });
+
+        event
+    }
+}
@@ -0,0 +1,151 @@
+use eframe::egui;
+use super::a11y;
+use super::theme::{status_label, StatusKind};
+use crate::models::VaultState;
+use crate::progress::ProgressFormatter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VaultEvent {
+    New,
+    Open,
+    Close,
+    AddFiles,
+    ExtractSelected,
+    RemoveSelected,
+    Compact,
+    RememberOnDevice,
+    ForgetOnDevice,
+}
+
+pub struct VaultPanel;
+
+impl VaultPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        state: &mut VaultState,
+    ) -> Option<VaultEvent> {
+        let mut event = None;
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.label("Vault");
+            ui.separator();
+
+            match &state.vault {
+                None => {
+                    ui.horizontal(|ui| {
+                        let vault_password_label = ui.label("Vault Password: ");
+                        ui.add_sized(
+                            [300.0, 20.0],
+                            egui::TextEdit::singleline(&mut state.password)
+                                .password(true)
+                                .frame(true)
+                        ).labelled_by(vault_password_label.id);
+                        let has_password = !state.password.is_empty();
+                        if ui.add_enabled(has_password || state.tpm_available, egui::Button::new("Open..."))
+                            .on_hover_text("Leave the password empty to use a password remembered on this device")
+                            .clicked()
+                        {
+                            event = Some(VaultEvent::Open);
+                        }
+                        if ui.add_enabled(has_password, egui::Button::new("New...")).clicked() {
+                            event = Some(VaultEvent::New);
+                        }
+                    });
+                    ui.label("A vault is a single encrypted file holding many entries. Entries can be added, extracted and removed without re-encrypting the whole vault.");
+                }
+                Some(vault) => {
+                    // Toolbar
+                    ui.horizontal(|ui| {
+                        ui.label(vault.path().display().to_string());
+                        ui.separator();
+                        if ui.button("Add Files...").clicked() {
+                            event = Some(VaultEvent::AddFiles);
+                        }
+                        let has_selection = !state.selected.is_empty();
+                        if ui.add_enabled(has_selection, egui::Button::new("Extract...")).clicked() {
+                            event = Some(VaultEvent::ExtractSelected);
+                        }
+                        if ui.add_enabled(has_selection, egui::Button::new("Remove")).clicked() {
+                            event = Some(VaultEvent::RemoveSelected);
+                        }
+                        if ui.add_enabled(state.wasted_bytes > 0, egui::Button::new("Compact"))
+                            .on_hover_text(format!(
+                                "Reclaim {} left by removed or replaced entries",
+                                ProgressFormatter::format_bytes(state.wasted_bytes)
+                            ))
+                            .clicked()
+                        {
+                            event = Some(VaultEvent::Compact);
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("Close").clicked() {
+                                event = Some(VaultEvent::Close);
+                            }
+                            if state.remembered {
+                                if ui.button("Forget on This Device").clicked() {
+                                    event = Some(VaultEvent::ForgetOnDevice);
+                                }
+                            } else if state.tpm_available
+                                && ui.button("Remember on This Device")
+                                    .on_hover_text("Seal the vault password to this computer's TPM so the vault opens here without typing it")
+                                    .clicked()
+                            {
+                                event = Some(VaultEvent::RememberOnDevice);
+                            }
+                        });
+                    });
+                    ui.separator();
+
+                    // Entry list
+                    if vault.entries().is_empty() {
+                        ui.label("This vault is empty. Use Add Files to store files in it.");
+                    } else {
+                        egui::ScrollArea::vertical()
+                            .id_salt("vault_entries_scroll")
+                            .max_height(400.0)
+                            .show(ui, |ui| {
+                                egui::Grid::new("vault_grid")
+                                    .striped(true)
+                                    .num_columns(4)
+                                    .show(ui, |ui| {
+                                        ui.strong("");
+                                        ui.strong("Name");
+                                        ui.strong("Size");
+                                        ui.strong("Modified");
+                                        ui.end_row();
+
+                                        for entry in vault.entries() {
+                                            let mut selected = state.selected.contains(&entry.name);
+                                            if a11y::row_checkbox(ui, &mut selected, format!("Select {}", entry.name)).changed() {
+                                                if selected {
+                                                    state.selected.insert(entry.name.clone());
+                                                } else {
+                                                    state.selected.remove(&entry.name);
+                                                }
+                                            }
+                                            ui.label(&entry.name);
+                                            ui.label(ProgressFormatter::format_bytes(entry.size));
+                                            ui.label(
+                                                chrono::DateTime::from_timestamp(entry.modified, 0)
+                                                    .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                                                    .unwrap_or_default()
+                                            );
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                        ui.label(format!("{} entries", vault.entries().len()));
+                    }
+                }
+            }
+
+            // Status line
+            if !state.status_message.is_empty() {
+                status_label(ui, StatusKind::from_error(state.status_is_error), &state.status_message);
+            }
+        });
+
+        event
+    }
+}
@@ -0,0 +1,114 @@
+use eframe::egui;
+use super::theme::{status_label, StatusKind};
+use crate::models::WatchState;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    BrowseSource,
+    BrowseDestination,
+    Start,
+    Stop,
+    ClearLog,
+}
+
+pub struct WatchPanel;
+
+impl WatchPanel {
+    pub fn render(
+        ui: &mut egui::Ui,
+        watch: &mut WatchState,
+    ) -> Option<WatchEvent> {
+        let mut event = None;
+        let active = watch.is_active();
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label("Watch Folder");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label("Uses the password and algorithm from the settings above");
+                });
+            });
+            ui.separator();
+
+            ui.add_enabled_ui(!active, |ui| {
+                ui.horizontal(|ui| {
+                    let drop_folder_label = ui.label("Drop Folder:  ");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut watch.source_directory)
+                            .hint_text("Folder to watch for new files")
+                            .frame(true)
+                    ).labelled_by(drop_folder_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(WatchEvent::BrowseSource);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let destination_label = ui.label("Destination: ");
+                    ui.add_sized(
+                        [360.0, 20.0],
+                        egui::TextEdit::singleline(&mut watch.destination_directory)
+                            .hint_text("Folder for encrypted files")
+                            .frame(true)
+                    ).labelled_by(destination_label.id);
+                    if ui.button("Browse").clicked() {
+                        event = Some(WatchEvent::BrowseDestination);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut watch.delete_originals, "Delete Originals");
+                    ui.add_enabled(
+                        watch.delete_originals,
+                        egui::Checkbox::new(&mut watch.shred_originals, "Shred"),
+                    ).on_hover_text("Overwrite originals with random data before deleting them");
+                });
+            });
+
+            ui.horizontal(|ui| {
+                if active {
+                    if ui.button("Stop Watching").clicked() {
+                        event = Some(WatchEvent::Stop);
+                    }
+                    ui.spinner();
+                    ui.label(format!("Watching... {} file(s) queued", watch.queue.len()));
+                } else if ui.button("Start Watching").clicked() {
+                    event = Some(WatchEvent::Start);
+                }
+            });
+        });
+
+        ui.group(|ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.label("Activity");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("Clear").clicked() {
+                        event = Some(WatchEvent::ClearLog);
+                    }
+                });
+            });
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .id_salt("watch_log_scroll")
+                .stick_to_bottom(true)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    if watch.log.is_empty() {
+                        ui.label("No activity yet.");
+                    }
+                    for entry in &watch.log {
+                        let text = format!("[{}] {}", entry.time, entry.message);
+                        if entry.is_error {
+                            status_label(ui, StatusKind::Error, text);
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                });
+        });
+
+        event
+    }
+}